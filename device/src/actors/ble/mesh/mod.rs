@@ -2,11 +2,14 @@ pub mod bearer;
 
 use crate::drivers::ble::mesh::composition::ElementsHandler;
 use crate::drivers::ble::mesh::config::configuration_manager::ConfigurationManager;
+use crate::drivers::ble::mesh::driver::node::oob::{NoOob, OobHandler};
+use crate::drivers::ble::mesh::driver::node::sar::SarConfig;
 pub use crate::drivers::ble::mesh::driver::node::MeshNodeMessage;
 use crate::drivers::ble::mesh::driver::node::Node;
 use crate::drivers::ble::mesh::interface::NetworkInterfaces;
 use crate::drivers::ble::mesh::provisioning::Capabilities;
 use crate::drivers::ble::mesh::storage::Storage;
+use crate::drivers::ble::mesh::vault::{StorageVaultFactory, VaultFactory};
 use embassy::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy::channel::mpmc::{Channel, DynamicReceiver as ChannelReceiver};
 use heapless::Vec;
@@ -16,24 +19,29 @@ const PDU_SIZE: usize = 384;
 
 pub type NodeMutex = ThreadModeRawMutex;
 
-pub struct MeshNode<'a, E, N, S, R>
+pub struct MeshNode<'a, E, N, S, R, O = NoOob, V = StorageVaultFactory>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     S: Storage + 'a,
     R: RngCore + CryptoRng + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     channel: Channel<NodeMutex, Vec<u8, PDU_SIZE>, 6>,
     elements: Option<E>,
     force_reset: bool,
+    sar_config: SarConfig,
     capabilities: Option<Capabilities>,
     network: Option<N>,
     storage: Option<S>,
     rng: Option<R>,
-    node: Option<Node<'a, E, N, S, R>>,
+    oob: Option<O>,
+    vault: Option<V>,
+    node: Option<Node<'a, E, N, S, R, O, V>>,
 }
 
-impl<'a, E, N, S, R> MeshNode<'a, E, N, S, R>
+impl<'a, E, N, S, R> MeshNode<'a, E, N, S, R, NoOob, StorageVaultFactory>
 where
     E: ElementsHandler<'a>,
     N: NetworkInterfaces,
@@ -45,10 +53,60 @@ where
             channel: Channel::new(),
             elements: Some(elements),
             force_reset: false,
+            sar_config: Default::default(),
             capabilities: Some(capabilities),
             network: Some(network),
             storage: Some(storage),
             rng: Some(rng),
+            oob: Some(NoOob),
+            vault: Some(StorageVaultFactory),
+            node: None,
+        }
+    }
+}
+
+impl<'a, E, N, S, R, O, V> MeshNode<'a, E, N, S, R, O, V>
+where
+    E: ElementsHandler<'a>,
+    N: NetworkInterfaces,
+    S: Storage,
+    R: RngCore + CryptoRng,
+    O: OobHandler,
+    V: VaultFactory<S>,
+{
+    /// Use `oob` to authenticate the provisioner by the out-of-band actions
+    /// advertised in the capabilities.
+    pub fn with_oob_handler<P: OobHandler>(self, oob: P) -> MeshNode<'a, E, N, S, R, P, V> {
+        MeshNode {
+            channel: self.channel,
+            elements: self.elements,
+            force_reset: self.force_reset,
+            sar_config: self.sar_config,
+            capabilities: self.capabilities,
+            network: self.network,
+            storage: self.storage,
+            rng: self.rng,
+            oob: Some(oob),
+            vault: self.vault,
+            node: None,
+        }
+    }
+
+    /// Keep the keys of the node in the vaults provided by `vault` rather
+    /// than in the storage, such as a fixed key pair with a
+    /// [`FixedKeyVaultFactory`](crate::drivers::ble::mesh::vault::FixedKeyVaultFactory).
+    pub fn with_vault<W: VaultFactory<S>>(self, vault: W) -> MeshNode<'a, E, N, S, R, O, W> {
+        MeshNode {
+            channel: self.channel,
+            elements: self.elements,
+            force_reset: self.force_reset,
+            sar_config: self.sar_config,
+            capabilities: self.capabilities,
+            network: self.network,
+            storage: self.storage,
+            rng: self.rng,
+            oob: self.oob,
+            vault: Some(vault),
             node: None,
         }
     }
@@ -60,21 +118,35 @@ where
         }
     }
 
+    pub fn with_sar_config(self, sar_config: SarConfig) -> Self {
+        Self { sar_config, ..self }
+    }
+
     pub async fn run(&'a mut self, control: ChannelReceiver<'_, MeshNodeMessage>) {
-        let configuration_manager = ConfigurationManager::new(
+        let configuration_manager = match ConfigurationManager::new(
             self.storage.take().unwrap(),
             self.elements.as_ref().unwrap().composition().clone(),
             self.force_reset,
+        ) {
+            Ok(configuration_manager) => configuration_manager,
+            Err(e) => {
+                error!("unable to create configuration: {:?}", e);
+                return;
+            }
+        };
+        self.node.replace(
+            Node::new(
+                self.elements.take().unwrap(),
+                self.capabilities.take().unwrap(),
+                self.network.take().unwrap(),
+                configuration_manager,
+                self.rng.take().unwrap(),
+                self.oob.take().unwrap(),
+                self.vault.take().unwrap(),
+            )
+            .with_sar_config(self.sar_config),
         );
 
-        self.node.replace(Node::new(
-            self.elements.take().unwrap(),
-            self.capabilities.take().unwrap(),
-            self.network.take().unwrap(),
-            configuration_manager,
-            self.rng.take().unwrap(),
-        ));
-
         self.node.as_mut().unwrap().run(control).await.ok();
     }
 }
@@ -1,4 +1,10 @@
+use crate::drivers::ble::mesh::crypto::{aes_cmac, beacon_key, k3};
 use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use cmac::crypto_mac::InvalidKeyLength;
+use core::convert::TryInto;
+use heapless::Vec;
 
 pub enum Beacon {
     Unprovisioned {
@@ -7,12 +13,62 @@ pub enum Beacon {
         uri_hash: Option<[u8; 4]>,
     },
 
-    SecureNetwork {
-        flags: Flags,
-        network_id: [u8; 8],
-        iv_index: u32,
-        authentication_value: [u8; 8],
-    },
+    SecureNetwork(SecureNetworkBeacon),
+}
+
+impl Beacon {
+    const UNPROVISIONED_DEVICE: u8 = 0x00;
+    const SECURE_NETWORK: u8 = 0x01;
+
+    /// Parses the beacon payload following the `MESH_BEACON` AD type.
+    pub fn parse(data: &[u8]) -> Result<Self, ParseError> {
+        if data.is_empty() {
+            return Err(ParseError::InvalidLength);
+        }
+        match data[0] {
+            Self::UNPROVISIONED_DEVICE => {
+                if data.len() != 19 && data.len() != 23 {
+                    return Err(ParseError::InvalidLength);
+                }
+                let uuid = Uuid(
+                    data[1..17]
+                        .try_into()
+                        .map_err(|_| ParseError::InvalidLength)?,
+                );
+                let oob = OobInformation::parse(u16::from_be_bytes([data[17], data[18]]));
+                let uri_hash = if data.len() == 23 {
+                    Some(
+                        data[19..23]
+                            .try_into()
+                            .map_err(|_| ParseError::InvalidLength)?,
+                    )
+                } else {
+                    None
+                };
+                Ok(Beacon::Unprovisioned {
+                    uuid,
+                    oob,
+                    uri_hash,
+                })
+            }
+            Self::SECURE_NETWORK => {
+                if data.len() != 22 {
+                    return Err(ParseError::InvalidLength);
+                }
+                Ok(Beacon::SecureNetwork(SecureNetworkBeacon {
+                    flags: Flags::parse(data[1]),
+                    network_id: data[2..10]
+                        .try_into()
+                        .map_err(|_| ParseError::InvalidLength)?,
+                    iv_index: u32::from_be_bytes([data[10], data[11], data[12], data[13]]),
+                    authentication_value: data[14..22]
+                        .try_into()
+                        .map_err(|_| ParseError::InvalidLength)?,
+                }))
+            }
+            _ => Err(ParseError::InvalidPDUFormat),
+        }
+    }
 }
 
 pub struct OobInformation {
@@ -30,7 +86,167 @@ pub struct OobInformation {
     pub on_device: bool,
 }
 
+impl OobInformation {
+    pub fn parse(bits: u16) -> Self {
+        Self {
+            other: bits & 0b0000_0000_0000_0001 != 0,
+            electronic_url: bits & 0b0000_0000_0000_0010 != 0,
+            two_dimensional_machine_readable_code: bits & 0b0000_0000_0000_0100 != 0,
+            bar_code: bits & 0b0000_0000_0000_1000 != 0,
+            nfc: bits & 0b0000_0000_0001_0000 != 0,
+            number: bits & 0b0000_0000_0010_0000 != 0,
+            string: bits & 0b0000_0000_0100_0000 != 0,
+            on_box: bits & 0b0000_1000_0000_0000 != 0,
+            inside_box: bits & 0b0001_0000_0000_0000 != 0,
+            on_piece_of_paper: bits & 0b0010_0000_0000_0000 != 0,
+            inside_manual: bits & 0b0100_0000_0000_0000 != 0,
+            on_device: bits & 0b1000_0000_0000_0000 != 0,
+        }
+    }
+}
+
+/// A beacon advertising the IV index and key refresh state of a subnet,
+/// authenticated with the beacon key of the subnet's network key.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SecureNetworkBeacon {
+    pub flags: Flags,
+    pub network_id: [u8; 8],
+    pub iv_index: u32,
+    pub authentication_value: [u8; 8],
+}
+
+impl SecureNetworkBeacon {
+    pub fn new(
+        flags: Flags,
+        network_key: &[u8; 16],
+        iv_index: u32,
+    ) -> Result<Self, InvalidKeyLength> {
+        let network_id = k3(network_key)?;
+        let authentication_value =
+            Self::authentication_value(flags, &network_id, iv_index, network_key)?;
+        Ok(Self {
+            flags,
+            network_id,
+            iv_index,
+            authentication_value,
+        })
+    }
+
+    /// Whether the beacon was secured with `network_key`.
+    pub fn authenticate(&self, network_key: &[u8; 16]) -> bool {
+        match k3(network_key) {
+            Ok(network_id) if network_id == self.network_id => matches!(
+                Self::authentication_value(self.flags, &self.network_id, self.iv_index, network_key),
+                Ok(authentication_value) if authentication_value == self.authentication_value
+            ),
+            _ => false,
+        }
+    }
+
+    fn authentication_value(
+        flags: Flags,
+        network_id: &[u8; 8],
+        iv_index: u32,
+        network_key: &[u8; 16],
+    ) -> Result<[u8; 8], InvalidKeyLength> {
+        let beacon_key = beacon_key(network_key)?;
+        let mut input = [0; 13];
+        input[0] = flags.emit();
+        input[1..9].copy_from_slice(network_id);
+        input[9..13].copy_from_slice(&iv_index.to_be_bytes());
+        let result = aes_cmac(&beacon_key, &input)?.into_bytes();
+        result[0..8].try_into().map_err(|_| InvalidKeyLength)
+    }
+
+    /// Emits the beacon payload following the `MESH_BEACON` AD type.
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(Beacon::SECURE_NETWORK)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.flags.emit())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.network_id)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.iv_index.to_be_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.authentication_value)
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Flags {
-    key_refresh: bool,
-    iv_update: bool,
+    pub key_refresh: bool,
+    pub iv_update: bool,
+}
+
+impl Flags {
+    pub fn parse(data: u8) -> Self {
+        Self {
+            key_refresh: data & 0b01 != 0,
+            iv_update: data & 0b10 != 0,
+        }
+    }
+
+    pub fn emit(&self) -> u8 {
+        let mut data = 0;
+        if self.key_refresh {
+            data |= 0b01;
+        }
+        if self.iv_update {
+            data |= 0b10;
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sample data from the mesh profile specification.
+    const NETWORK_KEY: [u8; 16] = [
+        0x7d, 0xd7, 0x36, 0x4c, 0xd8, 0x42, 0xad, 0x18, 0xc1, 0x7c, 0x2b, 0x82, 0x0c, 0x84, 0xc3,
+        0xd6,
+    ];
+
+    #[test]
+    fn secure_network_beacon() {
+        assert_eq!(
+            beacon_key(&NETWORK_KEY).unwrap(),
+            [
+                0x54, 0x23, 0xd9, 0x67, 0xda, 0x63, 0x9a, 0x99, 0xcb, 0x02, 0x23, 0x1a, 0x83, 0xf7,
+                0xd2, 0x54
+            ]
+        );
+
+        let beacon = SecureNetworkBeacon::new(
+            Flags {
+                key_refresh: false,
+                iv_update: false,
+            },
+            &NETWORK_KEY,
+            0x12345678,
+        )
+        .unwrap();
+
+        let mut data: Vec<u8, 22> = Vec::new();
+        beacon.emit(&mut data).unwrap();
+        assert_eq!(
+            &data[..],
+            &[
+                0x01, 0x00, 0x3e, 0xca, 0xff, 0x67, 0x2f, 0x67, 0x33, 0x70, 0x12, 0x34, 0x56, 0x78,
+                0x8e, 0xa2, 0x61, 0x58, 0x2f, 0x36, 0x4f, 0x6f
+            ]
+        );
+
+        if let Ok(Beacon::SecureNetwork(parsed)) = Beacon::parse(&data) {
+            assert!(parsed.authenticate(&NETWORK_KEY));
+            assert!(!parsed.authenticate(&[0; 16]));
+        } else {
+            panic!("not a secure network beacon");
+        }
+    }
 }
@@ -1,6 +1,9 @@
 #[cfg(feature = "ble+nrf-softdevice-s140")]
 pub mod nrf52;
 
+#[cfg(feature = "std")]
+pub mod simulator;
+
 /*
 use core::future::Future;
 
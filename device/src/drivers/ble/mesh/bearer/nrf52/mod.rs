@@ -115,6 +115,7 @@ pub struct SoftdeviceGattBearer {
     connection_channel: RefCell<Option<ConnectionChannel>>,
     server: MeshGattServer,
     connected: AtomicBool,
+    connections: Cell<u32>,
     outbound: Channel<NodeMutex, Vec<u8, 66>, 5>,
     inbound: Channel<NodeMutex, Vec<u8, 66>, 5>,
     state: Cell<State>,
@@ -128,6 +129,7 @@ impl SoftdeviceGattBearer {
             server: gatt_server::register(sd).unwrap(),
             connection: Signal::new(),
             connected: AtomicBool::new(false),
+            connections: Cell::new(0),
             current_connection: RefCell::new(None),
             connection_channel: RefCell::new(None),
             outbound: Channel::new(),
@@ -141,6 +143,7 @@ impl SoftdeviceGattBearer {
         loop {
             let connection = self.connection.wait().await;
             self.current_connection.borrow_mut().replace(connection);
+            self.connections.set(self.connections.get().wrapping_add(1));
             gatt_server::run(
                 self.current_connection.borrow().as_ref().unwrap(),
                 &self.server,
@@ -282,6 +285,14 @@ impl GattBearer<66> for SoftdeviceGattBearer {
             Ok(())
         }
     }
+
+    #[cfg(feature = "ble-mesh-proxy")]
+    fn connection(&self) -> Option<u32> {
+        self.current_connection
+            .borrow()
+            .as_ref()
+            .map(|_| self.connections.get())
+    }
 }
 
 pub struct SoftdeviceAdvertisingBearer {
@@ -0,0 +1,1131 @@
+//! In-memory bearers for running several mesh nodes inside a single host process.
+//!
+//! A [`SimulatedMedium`] models the shared radio channel. Each node attached to the
+//! medium receives a [`SimulatedAdvertisingBearer`] and a [`SimulatedGattBearer`] which
+//! implement the regular bearer traits, so they can be plugged into
+//! `AdvertisingOnlyNetworkInterfaces` or `AdvertisingAndGattNetworkInterfaces` unchanged.
+//!
+//! Advertising packets are broadcast to every other node within range of the sender,
+//! subject to the configured loss ratio and latency. GATT connections are point-to-point
+//! and are opened from the test side through [`SimulatedMedium::connect`].
+
+use crate::drivers::ble::mesh::driver::node::{NetworkId, State};
+use crate::drivers::ble::mesh::interface::{
+    AdvertisingBearer, BearerError, GattBearer, PB_ADV_MTU,
+};
+use core::future::Future;
+use embassy::time::{Duration, Instant, Timer};
+use heapless::Vec;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Physical characteristics of the simulated radio channel.
+#[derive(Copy, Clone, Debug)]
+pub struct MediumConfig {
+    /// Ratio of packets dropped per receiver, between `0.0` and `1.0`.
+    pub loss: f32,
+    /// Delay between transmission and reception.
+    pub latency: Duration,
+    /// Maximum distance at which a receiver can hear a transmitter.
+    pub range: f32,
+    /// Seed for the pseudo-random loss generator, to keep runs reproducible.
+    pub seed: u64,
+}
+
+impl Default for MediumConfig {
+    fn default() -> Self {
+        Self {
+            loss: 0.0,
+            latency: Duration::from_millis(0),
+            range: f32::MAX,
+            seed: 0x5eed,
+        }
+    }
+}
+
+/// Location of a node on the simulated plane.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Position {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    fn distance(&self, other: &Position) -> f32 {
+        let dx = self.x - other.x;
+        let dy = self.y - other.y;
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+/// Handle identifying a node attached to a medium.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NodeId(usize);
+
+struct Packet<const N: usize> {
+    deliver_at: Instant,
+    data: Vec<u8, N>,
+}
+
+struct Queue<const N: usize> {
+    packets: VecDeque<Packet<N>>,
+}
+
+impl<const N: usize> Queue<N> {
+    fn new() -> Self {
+        Self {
+            packets: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, deliver_at: Instant, data: Vec<u8, N>) {
+        // keep ordered by delivery time, latency may have changed between sends.
+        let index = self
+            .packets
+            .iter()
+            .position(|p| p.deliver_at > deliver_at)
+            .unwrap_or(self.packets.len());
+        self.packets.insert(index, Packet { deliver_at, data });
+    }
+
+    fn pop_due(&mut self, now: Instant) -> Option<Vec<u8, N>> {
+        if let Some(packet) = self.packets.front() {
+            if packet.deliver_at <= now {
+                return self.packets.pop_front().map(|p| p.data);
+            }
+        }
+        None
+    }
+}
+
+struct GattLink<const MTU: usize> {
+    connected: bool,
+    /// Number of connections opened so far, identifying the current one.
+    connections: u32,
+    connectable: bool,
+    adv_data: Option<Vec<u8, 64>>,
+    to_node: Queue<MTU>,
+    to_client: Queue<MTU>,
+}
+
+struct SimulatedNode<const MTU: usize> {
+    position: Position,
+    enabled: bool,
+    inbound: Queue<PB_ADV_MTU>,
+    gatt: GattLink<MTU>,
+}
+
+struct MediumInner<const MTU: usize> {
+    config: MediumConfig,
+    rng: u64,
+    nodes: std::vec::Vec<SimulatedNode<MTU>>,
+    transmitted: usize,
+    delivered: usize,
+    dropped: usize,
+}
+
+impl<const MTU: usize> MediumInner<MTU> {
+    fn next_random(&mut self) -> f32 {
+        // xorshift64*, good enough to decide packet loss.
+        let mut x = self.rng;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng = x;
+        let value = x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40;
+        value as f32 / (1u64 << 24) as f32
+    }
+
+    fn broadcast(&mut self, from: usize, data: &Vec<u8, PB_ADV_MTU>) {
+        self.transmitted += 1;
+        let origin = self.nodes[from].position;
+        let deliver_at = Instant::now() + self.config.latency;
+        for index in 0..self.nodes.len() {
+            if index == from || !self.nodes[index].enabled {
+                continue;
+            }
+            if self.nodes[index].position.distance(&origin) > self.config.range {
+                continue;
+            }
+            if self.config.loss > 0.0 && self.next_random() < self.config.loss {
+                self.dropped += 1;
+                continue;
+            }
+            self.delivered += 1;
+            self.nodes[index].inbound.push(deliver_at, data.clone());
+        }
+    }
+}
+
+/// Counters describing the traffic that crossed the medium.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MediumStatistics {
+    pub transmitted: usize,
+    pub delivered: usize,
+    pub dropped: usize,
+}
+
+/// Shared broadcast channel connecting simulated nodes.
+///
+/// Cloning the medium yields another handle onto the same channel.
+pub struct SimulatedMedium<const MTU: usize = 66> {
+    inner: Arc<Mutex<MediumInner<MTU>>>,
+}
+
+impl<const MTU: usize> Clone for SimulatedMedium<MTU> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<const MTU: usize> SimulatedMedium<MTU> {
+    pub fn new(config: MediumConfig) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MediumInner {
+                config,
+                rng: config.seed | 1,
+                nodes: std::vec::Vec::new(),
+                transmitted: 0,
+                delivered: 0,
+                dropped: 0,
+            })),
+        }
+    }
+
+    /// Attach a new node at the given position.
+    pub fn add_node(&self, position: Position) -> NodeId {
+        let mut inner = self.inner.lock().unwrap();
+        inner.nodes.push(SimulatedNode {
+            position,
+            enabled: true,
+            inbound: Queue::new(),
+            gatt: GattLink {
+                connected: false,
+                connections: 0,
+                connectable: false,
+                adv_data: None,
+                to_node: Queue::new(),
+                to_client: Queue::new(),
+            },
+        });
+        NodeId(inner.nodes.len() - 1)
+    }
+
+    /// Advertising bearer for the given node.
+    pub fn advertising_bearer(&self, node: NodeId) -> SimulatedAdvertisingBearer<MTU> {
+        SimulatedAdvertisingBearer {
+            medium: self.clone(),
+            node,
+        }
+    }
+
+    /// GATT bearer for the given node.
+    pub fn gatt_bearer(&self, node: NodeId) -> SimulatedGattBearer<MTU> {
+        SimulatedGattBearer {
+            medium: self.clone(),
+            node,
+        }
+    }
+
+    /// Move a node, affecting which peers are in range from now on.
+    pub fn set_position(&self, node: NodeId, position: Position) {
+        self.inner.lock().unwrap().nodes[node.0].position = position;
+    }
+
+    /// Power a node's radio on or off. A disabled node neither sends nor receives.
+    pub fn set_enabled(&self, node: NodeId, enabled: bool) {
+        self.inner.lock().unwrap().nodes[node.0].enabled = enabled;
+    }
+
+    pub fn set_loss(&self, loss: f32) {
+        self.inner.lock().unwrap().config.loss = loss;
+    }
+
+    pub fn set_latency(&self, latency: Duration) {
+        self.inner.lock().unwrap().config.latency = latency;
+    }
+
+    pub fn set_range(&self, range: f32) {
+        self.inner.lock().unwrap().config.range = range;
+    }
+
+    pub fn statistics(&self) -> MediumStatistics {
+        let inner = self.inner.lock().unwrap();
+        MediumStatistics {
+            transmitted: inner.transmitted,
+            delivered: inner.delivered,
+            dropped: inner.dropped,
+        }
+    }
+
+    /// Most recent connectable advertisement of the node, if it is currently advertising.
+    pub fn gatt_advertisement(&self, node: NodeId) -> Option<Vec<u8, 64>> {
+        let inner = self.inner.lock().unwrap();
+        let link = &inner.nodes[node.0].gatt;
+        if link.connectable && !link.connected {
+            link.adv_data.clone()
+        } else {
+            None
+        }
+    }
+
+    /// Open a GATT connection to a node which is advertising as connectable.
+    pub fn connect(&self, node: NodeId) -> Result<SimulatedGattClient<MTU>, BearerError> {
+        let mut inner = self.inner.lock().unwrap();
+        let link = &mut inner.nodes[node.0].gatt;
+        if !link.connectable || link.connected {
+            return Err(BearerError::InvalidLink);
+        }
+        link.connected = true;
+        link.connections += 1;
+        Ok(SimulatedGattClient {
+            medium: self.clone(),
+            node,
+        })
+    }
+
+    fn try_receive_adv(&self, node: NodeId) -> Option<Vec<u8, PB_ADV_MTU>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.nodes[node.0].inbound.pop_due(Instant::now())
+    }
+
+    fn latency(&self) -> Duration {
+        self.inner.lock().unwrap().config.latency
+    }
+}
+
+/// Advertising bearer attached to a [`SimulatedMedium`].
+pub struct SimulatedAdvertisingBearer<const MTU: usize = 66> {
+    medium: SimulatedMedium<MTU>,
+    node: NodeId,
+}
+
+impl<const MTU: usize> AdvertisingBearer for SimulatedAdvertisingBearer<MTU> {
+    fn set_state(&self, _state: State) {
+        // ignored.
+    }
+
+    fn set_network_id(&self, _network_id: NetworkId) {
+        // ignored
+    }
+
+    type ReceiveFuture<'m> = impl Future<Output=Result<Vec<u8, PB_ADV_MTU>, BearerError>> + 'm
+    where
+    Self: 'm;
+
+    fn receive<'m>(&'m self) -> Self::ReceiveFuture<'m> {
+        async move {
+            loop {
+                if let Some(data) = self.medium.try_receive_adv(self.node) {
+                    return Ok(data);
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        }
+    }
+
+    type TransmitFuture<'m> = impl Future<Output = Result<(), BearerError>> + 'm;
+
+    fn transmit<'m>(&'m self, pdu: &'m Vec<u8, PB_ADV_MTU>) -> Self::TransmitFuture<'m> {
+        async move {
+            let mut inner = self.medium.inner.lock().unwrap();
+            if inner.nodes[self.node.0].enabled {
+                inner.broadcast(self.node.0, pdu);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// GATT bearer attached to a [`SimulatedMedium`].
+pub struct SimulatedGattBearer<const MTU: usize = 66> {
+    medium: SimulatedMedium<MTU>,
+    node: NodeId,
+}
+
+impl<const MTU: usize> SimulatedGattBearer<MTU> {
+    /// Follow the connections opened by clients, until the end of time.
+    async fn run(&self) -> Result<(), BearerError> {
+        loop {
+            while !self.is_connected() {
+                Timer::after(POLL_INTERVAL).await;
+            }
+            while self.is_connected() {
+                Timer::after(POLL_INTERVAL).await;
+            }
+            // as on a real radio, advertising stopped once the connection was made.
+            let mut inner = self.medium.inner.lock().unwrap();
+            let link = &mut inner.nodes[self.node.0].gatt;
+            link.connectable = false;
+            link.to_node.packets.clear();
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        self.medium.inner.lock().unwrap().nodes[self.node.0]
+            .gatt
+            .connected
+    }
+}
+
+impl<const MTU: usize> GattBearer<MTU> for SimulatedGattBearer<MTU> {
+    fn set_state(&self, _state: State) {
+        // ignored.
+    }
+
+    fn set_network_id(&self, _network_id: NetworkId) {
+        // ignored
+    }
+
+    type RunFuture<'m> = impl Future<Output=Result<(), BearerError>> + 'm
+    where
+    Self: 'm;
+
+    fn run<'m>(&'m self) -> Self::RunFuture<'m> {
+        SimulatedGattBearer::run(self)
+    }
+
+    type ReceiveFuture<'m> = impl Future<Output=Result<Vec<u8, MTU>, BearerError>> + 'm
+    where
+    Self: 'm;
+
+    fn receive<'m>(&'m self) -> Self::ReceiveFuture<'m> {
+        async move {
+            loop {
+                let data = {
+                    let mut inner = self.medium.inner.lock().unwrap();
+                    inner.nodes[self.node.0]
+                        .gatt
+                        .to_node
+                        .pop_due(Instant::now())
+                };
+                if let Some(data) = data {
+                    return Ok(data);
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        }
+    }
+
+    type TransmitFuture<'m> = impl Future<Output = Result<(), BearerError>> + 'm;
+
+    fn transmit<'m>(&'m self, pdu: &'m Vec<u8, MTU>) -> Self::TransmitFuture<'m> {
+        async move {
+            let deliver_at = Instant::now() + self.medium.latency();
+            let mut inner = self.medium.inner.lock().unwrap();
+            let link = &mut inner.nodes[self.node.0].gatt;
+            if link.connected {
+                link.to_client.push(deliver_at, pdu.clone());
+            }
+            Ok(())
+        }
+    }
+
+    type AdvertiseFuture<'m> = impl Future<Output = Result<(), BearerError>> + 'm;
+
+    fn advertise<'m>(&'m self, adv_data: &'m Vec<u8, 64>) -> Self::AdvertiseFuture<'m> {
+        async move {
+            let mut inner = self.medium.inner.lock().unwrap();
+            let link = &mut inner.nodes[self.node.0].gatt;
+            link.connectable = true;
+            link.adv_data.replace(adv_data.clone());
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "ble-mesh-proxy")]
+    fn connection(&self) -> Option<u32> {
+        let inner = self.medium.inner.lock().unwrap();
+        let link = &inner.nodes[self.node.0].gatt;
+        if link.connected {
+            Some(link.connections)
+        } else {
+            None
+        }
+    }
+}
+
+/// The remote (phone or gateway) end of a simulated GATT connection.
+pub struct SimulatedGattClient<const MTU: usize = 66> {
+    medium: SimulatedMedium<MTU>,
+    node: NodeId,
+}
+
+impl<const MTU: usize> SimulatedGattClient<MTU> {
+    /// Write a PDU to the node's data-in characteristic.
+    pub fn write(&self, pdu: &Vec<u8, MTU>) {
+        let deliver_at = Instant::now() + self.medium.latency();
+        let mut inner = self.medium.inner.lock().unwrap();
+        inner.nodes[self.node.0]
+            .gatt
+            .to_node
+            .push(deliver_at, pdu.clone());
+    }
+
+    /// Wait for the next notification on the node's data-out characteristic.
+    pub async fn notified(&self) -> Vec<u8, MTU> {
+        loop {
+            let data = {
+                let mut inner = self.medium.inner.lock().unwrap();
+                inner.nodes[self.node.0]
+                    .gatt
+                    .to_client
+                    .pop_due(Instant::now())
+            };
+            if let Some(data) = data {
+                return data;
+            }
+            Timer::after(POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl<const MTU: usize> Drop for SimulatedGattClient<MTU> {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.medium.inner.lock() {
+            let link = &mut inner.nodes[self.node.0].gatt;
+            link.connected = false;
+            link.to_node.packets.clear();
+            link.to_client.packets.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::composition::{
+        CompanyIdentifier, Composition, ElementDescriptor, ElementsHandler, Features, Location,
+        ProductIdentifier, VersionIdentifier,
+    };
+    use crate::drivers::ble::mesh::config::configuration_manager::ConfigurationManager;
+    use crate::drivers::ble::mesh::device::Uuid;
+    use crate::drivers::ble::mesh::driver::elements::configuration_client::RemoteNode;
+    use crate::drivers::ble::mesh::driver::elements::AppElementsContext;
+    use crate::drivers::ble::mesh::driver::node::oob::{NoOob, OobHandler, OobValue};
+    use crate::drivers::ble::mesh::driver::node::{MeshNodeMessage, Node};
+    use crate::drivers::ble::mesh::driver::provisioner::{
+        Authentication, Provisioner, ProvisionerOobHandler,
+    };
+    use crate::drivers::ble::mesh::driver::DeviceError;
+    use crate::drivers::ble::mesh::interface::AdvertisingOnlyNetworkInterfaces;
+    use crate::drivers::ble::mesh::model::foundation::configuration::{
+        AppKeyIndex, NetKeyIndex, CONFIGURATION_CLIENT,
+    };
+    use crate::drivers::ble::mesh::model::{ModelIdentifier, Status};
+    use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+    use crate::drivers::ble::mesh::provisioning::{
+        Capabilities, InputOOBAction, InputOOBActions, OOBSize, OutputOOBAction, OutputOOBActions,
+        PublicKey,
+    };
+    use crate::drivers::ble::mesh::storage::FlashStorage;
+    use crate::drivers::ble::mesh::vault::{FixedKeyVaultFactory, StorageVaultFactory};
+    use crate::flash::ram::RamFlash;
+    use core::cell::RefCell;
+    use core::future::{ready, Future, Ready};
+    use embassy::blocking_mutex::raw::NoopRawMutex;
+    use embassy::channel::mpmc::Channel;
+    use embassy::util::{select, Either};
+    use futures::executor::block_on;
+    use futures::future::join;
+    use rand_core::{CryptoRng, Error, RngCore};
+    use std::rc::Rc;
+
+    fn packet(data: &[u8]) -> Vec<u8, PB_ADV_MTU> {
+        Vec::from_slice(data).unwrap()
+    }
+
+    #[test]
+    fn test_broadcast_reaches_all_peers() {
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig::default());
+        let a = medium.add_node(Position::new(0.0, 0.0));
+        let b = medium.add_node(Position::new(1.0, 0.0));
+        let c = medium.add_node(Position::new(2.0, 0.0));
+
+        let a = medium.advertising_bearer(a);
+        let b = medium.advertising_bearer(b);
+        let c = medium.advertising_bearer(c);
+
+        block_on(async {
+            a.transmit(&packet(&[1, 2, 3])).await.unwrap();
+            assert_eq!(&[1, 2, 3], &*b.receive().await.unwrap());
+            assert_eq!(&[1, 2, 3], &*c.receive().await.unwrap());
+        });
+        assert!(medium.try_receive_adv(NodeId(0)).is_none());
+        assert_eq!(
+            MediumStatistics {
+                transmitted: 1,
+                delivered: 2,
+                dropped: 0
+            },
+            medium.statistics()
+        );
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig {
+            range: 5.0,
+            ..Default::default()
+        });
+        let a = medium.add_node(Position::new(0.0, 0.0));
+        let near = medium.add_node(Position::new(3.0, 4.0));
+        let far = medium.add_node(Position::new(6.0, 0.0));
+
+        block_on(medium.advertising_bearer(a).transmit(&packet(&[42]))).unwrap();
+        assert!(medium.try_receive_adv(near).is_some());
+        assert!(medium.try_receive_adv(far).is_none());
+    }
+
+    #[test]
+    fn test_loss() {
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig {
+            loss: 1.0,
+            ..Default::default()
+        });
+        let a = medium.add_node(Position::default());
+        let b = medium.add_node(Position::default());
+
+        block_on(medium.advertising_bearer(a).transmit(&packet(&[42]))).unwrap();
+        assert!(medium.try_receive_adv(b).is_none());
+        assert_eq!(1, medium.statistics().dropped);
+
+        medium.set_loss(0.5);
+        for _ in 0..100 {
+            block_on(medium.advertising_bearer(a).transmit(&packet(&[42]))).unwrap();
+        }
+        let stats = medium.statistics();
+        assert!(stats.delivered > 20 && stats.delivered < 80);
+    }
+
+    #[test]
+    fn test_latency() {
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig {
+            latency: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let a = medium.add_node(Position::default());
+        let b = medium.add_node(Position::default());
+
+        let start = Instant::now();
+        block_on(async {
+            medium
+                .advertising_bearer(a)
+                .transmit(&packet(&[42]))
+                .await
+                .unwrap();
+            assert!(medium.try_receive_adv(b).is_none());
+            medium.advertising_bearer(b).receive().await.unwrap();
+        });
+        assert!(Instant::now() - start >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_gatt_connection() {
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig::default());
+        let node = medium.add_node(Position::default());
+        let bearer = medium.gatt_bearer(node);
+
+        assert!(medium.connect(node).is_err());
+
+        block_on(async {
+            let adv: Vec<u8, 64> = Vec::from_slice(&[0x02, 0x01, 0x06]).unwrap();
+            bearer.advertise(&adv).await.unwrap();
+            assert_eq!(Some(adv), medium.gatt_advertisement(node));
+
+            let client = medium.connect(node).unwrap();
+            assert!(medium.connect(node).is_err());
+
+            client.write(&Vec::from_slice(&[1, 2]).unwrap());
+            assert_eq!(&[1, 2], &*bearer.receive().await.unwrap());
+
+            bearer
+                .transmit(&Vec::from_slice(&[3, 4]).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(&[3, 4], &*client.notified().await);
+        });
+    }
+
+    #[test]
+    fn test_gatt_connection_lifecycle() {
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig::default());
+        let node = medium.add_node(Position::default());
+        let bearer = medium.gatt_bearer(node);
+
+        let test = async {
+            let adv: Vec<u8, 64> = Vec::from_slice(&[0x02, 0x01, 0x06]).unwrap();
+            for _ in 0..2 {
+                bearer.advertise(&adv).await.unwrap();
+                let client = medium.connect(node).unwrap();
+                Timer::after(Duration::from_millis(10)).await;
+                drop(client);
+                Timer::after(Duration::from_millis(10)).await;
+                // the node has to advertise again before accepting another connection.
+                assert!(medium.connect(node).is_err());
+            }
+        };
+        match block_on(select(bearer.run(), test)) {
+            Either::First(_) => panic!("bearer stopped running"),
+            Either::Second(_) => {}
+        }
+    }
+
+    struct TestRng(u64);
+
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for b in dest.iter_mut() {
+                *b = self.next_u64() as u8;
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for TestRng {}
+
+    /// Hands the context given to the elements on provisioning to the test.
+    struct TestElements<'a> {
+        composition: Composition,
+        ctx: Rc<RefCell<Option<AppElementsContext<'a>>>>,
+    }
+
+    impl<'a> ElementsHandler<'a> for TestElements<'a> {
+        fn composition(&self) -> &Composition {
+            &self.composition
+        }
+
+        fn connect(&mut self, ctx: AppElementsContext<'a>) {
+            self.ctx.borrow_mut().replace(ctx);
+        }
+
+        type DispatchFuture<'m> = Ready<Result<(), DeviceError>>
+        where
+            Self: 'm;
+
+        fn dispatch<'m>(
+            &'m mut self,
+            _element: u8,
+            _model_identifier: &'m ModelIdentifier,
+            _message: &'m AccessMessage,
+        ) -> Self::DispatchFuture<'m> {
+            ready(Ok(()))
+        }
+    }
+
+    fn composition() -> Composition {
+        let mut composition = Composition::new(
+            CompanyIdentifier(0x0003),
+            ProductIdentifier(0x0001),
+            VersionIdentifier(0x0001),
+            Features {
+                relay: false,
+                proxy: false,
+                friend: false,
+                low_power: false,
+            },
+        );
+        composition
+            .add_element(
+                ElementDescriptor::new(Location(0x0001))
+                    .add_model(CONFIGURATION_CLIENT)
+                    .unwrap(),
+            )
+            .ok();
+        composition
+    }
+
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            number_of_elements: 1,
+            algorithms: Default::default(),
+            public_key_type: Default::default(),
+            static_oob_type: Default::default(),
+            output_oob_size: OOBSize::NotSupported,
+            output_oob_action: Default::default(),
+            input_oob_size: OOBSize::NotSupported,
+            input_oob_action: Default::default(),
+        }
+    }
+
+    // the channels of a node may only be used from the thread embassy
+    // considers to be in thread mode, which on std is the one named main.
+    fn in_thread_mode(f: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .name("main".into())
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_provision_and_configure() {
+        in_thread_mode(provision_and_configure);
+    }
+
+    fn provision_and_configure() {
+        const FLASH_SIZE: usize = 8192;
+
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig::default());
+        let provisioner = Provisioner::new(
+            medium.advertising_bearer(medium.add_node(Position::default())),
+            FlashStorage::new(0, FLASH_SIZE, RamFlash::<FLASH_SIZE>::new()),
+            TestRng(0x2545F4914F6CDD1D),
+        );
+
+        let ctx_a = Rc::new(RefCell::new(None));
+        let ctx_b = Rc::new(RefCell::new(None));
+        let node = |ctx, seed| {
+            Node::new(
+                TestElements {
+                    composition: composition(),
+                    ctx,
+                },
+                capabilities(),
+                AdvertisingOnlyNetworkInterfaces::new(
+                    medium.advertising_bearer(medium.add_node(Position::default())),
+                ),
+                ConfigurationManager::new(
+                    FlashStorage::new(0, FLASH_SIZE, RamFlash::<FLASH_SIZE>::new()),
+                    composition(),
+                    false,
+                )
+                .unwrap(),
+                TestRng(seed),
+                NoOob,
+                StorageVaultFactory,
+            )
+        };
+        let a = node(ctx_a.clone(), 0x0123456789ABCDEF);
+        let b = node(ctx_b.clone(), 0xFEDCBA9876543210);
+
+        let control_a: Channel<NoopRawMutex, MeshNodeMessage, 1> = Channel::new();
+        let control_b: Channel<NoopRawMutex, MeshNodeMessage, 1> = Channel::new();
+
+        let test = async {
+            provisioner.initialize().await.unwrap();
+            for _ in 0..2 {
+                let device = provisioner
+                    .scan(Duration::from_secs(10))
+                    .await
+                    .unwrap()
+                    .expect("no unprovisioned device found");
+                provisioner.provision(device.uuid).await.unwrap();
+            }
+
+            // both nodes hand their elements a context once provisioned.
+            while ctx_a.borrow().is_none() || ctx_b.borrow().is_none() {
+                Timer::after(POLL_INTERVAL).await;
+            }
+            let address_a = ctx_a.borrow().as_ref().unwrap().address();
+            let database = provisioner.database();
+            let remote_b = database
+                .nodes()
+                .iter()
+                .find(|node| node.unicast_address != address_a)
+                .map(RemoteNode::from)
+                .unwrap();
+            let net_key_index = NetKeyIndex::new(database.key_index);
+            drop(database);
+
+            // node A configures node B through B's device key.
+            let client = ctx_a.borrow().as_ref().unwrap().configuration_client();
+            client
+                .app_key_add(&remote_b, net_key_index, AppKeyIndex::new(1), [0x11; 16])
+                .await
+                .unwrap();
+            // B stored the key, so a different one under the same index is refused.
+            let result = client
+                .app_key_add(&remote_b, net_key_index, AppKeyIndex::new(1), [0x22; 16])
+                .await;
+            assert!(matches!(
+                result,
+                Err(DeviceError::Status(Status::KeyIndexAlreadyStored))
+            ));
+        };
+
+        let nodes = join(
+            a.run(control_a.receiver().into()),
+            b.run(control_b.receiver().into()),
+        );
+        match block_on(select(nodes, test)) {
+            Either::First(_) => panic!("nodes stopped running"),
+            Either::Second(_) => {}
+        }
+        assert_eq!(2, provisioner.database().nodes().len());
+    }
+
+    /// The user carrying values between a device and the provisioner.
+    struct User(Rc<RefCell<Option<OobValue>>>);
+
+    impl User {
+        async fn read(&self) -> OobValue {
+            loop {
+                if let Some(value) = self.0.borrow_mut().take() {
+                    return value;
+                }
+                Timer::after(POLL_INTERVAL).await;
+            }
+        }
+    }
+
+    impl OobHandler for User {
+        type OutputFuture<'m> = Ready<Result<(), DeviceError>>
+        where
+            Self: 'm;
+
+        fn output<'m>(
+            &'m mut self,
+            _: OutputOOBAction,
+            value: &'m OobValue,
+        ) -> Self::OutputFuture<'m> {
+            self.0.borrow_mut().replace(value.clone());
+            ready(Ok(()))
+        }
+
+        type InputFuture<'m> = impl Future<Output = Result<OobValue, DeviceError>> + 'm
+        where
+            Self: 'm;
+
+        fn input<'m>(&'m mut self, _: InputOOBAction, _: u8) -> Self::InputFuture<'m> {
+            async move { Ok(self.read().await) }
+        }
+    }
+
+    impl ProvisionerOobHandler for User {
+        type OutputFuture<'m> = impl Future<Output = Result<OobValue, DeviceError>> + 'm
+        where
+            Self: 'm;
+
+        fn output<'m>(&'m mut self, _: OutputOOBAction, _: u8) -> Self::OutputFuture<'m> {
+            async move { Ok(self.read().await) }
+        }
+
+        type InputFuture<'m> = Ready<Result<(), DeviceError>>
+        where
+            Self: 'm;
+
+        fn input<'m>(
+            &'m mut self,
+            _: InputOOBAction,
+            value: &'m OobValue,
+        ) -> Self::InputFuture<'m> {
+            self.0.borrow_mut().replace(value.clone());
+            ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_provision_with_oob() {
+        in_thread_mode(provision_with_oob);
+    }
+
+    fn provision_with_oob() {
+        const FLASH_SIZE: usize = 8192;
+
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig::default());
+        let provisioner = Provisioner::new(
+            medium.advertising_bearer(medium.add_node(Position::default())),
+            FlashStorage::new(0, FLASH_SIZE, RamFlash::<FLASH_SIZE>::new()),
+            TestRng(0x2545F4914F6CDD1D),
+        );
+
+        let mut output_oob_action = OutputOOBActions::new();
+        output_oob_action.push(OutputOOBAction::OutputNumeric).ok();
+        let mut input_oob_action = InputOOBActions::new();
+        input_oob_action
+            .push(InputOOBAction::InputAlphanumeric)
+            .ok();
+        let capabilities = Capabilities {
+            output_oob_size: OOBSize::MaximumSize(6),
+            output_oob_action,
+            input_oob_size: OOBSize::MaximumSize(4),
+            input_oob_action,
+            ..capabilities()
+        };
+
+        let user = Rc::new(RefCell::new(None));
+        let ctx_a = Rc::new(RefCell::new(None));
+        let ctx_b = Rc::new(RefCell::new(None));
+        let node = |ctx, seed| {
+            Node::new(
+                TestElements {
+                    composition: composition(),
+                    ctx,
+                },
+                capabilities.clone(),
+                AdvertisingOnlyNetworkInterfaces::new(
+                    medium.advertising_bearer(medium.add_node(Position::default())),
+                ),
+                ConfigurationManager::new(
+                    FlashStorage::new(0, FLASH_SIZE, RamFlash::<FLASH_SIZE>::new()),
+                    composition(),
+                    false,
+                )
+                .unwrap(),
+                TestRng(seed),
+                User(user.clone()),
+                StorageVaultFactory,
+            )
+        };
+        let a = node(ctx_a.clone(), 0x0123456789ABCDEF);
+        let b = node(ctx_b.clone(), 0xFEDCBA9876543210);
+
+        let control_a: Channel<NoopRawMutex, MeshNodeMessage, 1> = Channel::new();
+        let control_b: Channel<NoopRawMutex, MeshNodeMessage, 1> = Channel::new();
+
+        let test = async {
+            provisioner.initialize().await.unwrap();
+            // the user enters the number output by one device, and inputs
+            // the characters presented by the provisioner on the other.
+            for authentication in [
+                Authentication::Output(OutputOOBAction::OutputNumeric, 6),
+                Authentication::Input(InputOOBAction::InputAlphanumeric, 4),
+            ] {
+                let device = provisioner
+                    .scan(Duration::from_secs(10))
+                    .await
+                    .unwrap()
+                    .expect("no unprovisioned device found");
+                provisioner
+                    .provision_authenticated(device.uuid, authentication, &mut User(user.clone()))
+                    .await
+                    .unwrap();
+            }
+
+            // both devices confirmed the values, and took the network.
+            while ctx_a.borrow().is_none() || ctx_b.borrow().is_none() {
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+
+        let nodes = join(
+            a.run(control_a.receiver().into()),
+            b.run(control_b.receiver().into()),
+        );
+        match block_on(select(nodes, test)) {
+            Either::First(_) => panic!("nodes stopped running"),
+            Either::Second(_) => {}
+        }
+        assert_eq!(2, provisioner.database().nodes().len());
+    }
+
+    /// The public key of a device, as scanned from its QR code.
+    struct QrCode(PublicKey);
+
+    impl ProvisionerOobHandler for QrCode {
+        type OutputFuture<'m> = Ready<Result<OobValue, DeviceError>>
+        where
+            Self: 'm;
+
+        fn output<'m>(&'m mut self, _: OutputOOBAction, _: u8) -> Self::OutputFuture<'m> {
+            ready(Err(DeviceError::InvalidState))
+        }
+
+        type InputFuture<'m> = Ready<Result<(), DeviceError>>
+        where
+            Self: 'm;
+
+        fn input<'m>(&'m mut self, _: InputOOBAction, _: &'m OobValue) -> Self::InputFuture<'m> {
+            ready(Err(DeviceError::InvalidState))
+        }
+
+        fn public_key(&mut self, _: &Uuid) -> Option<PublicKey> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn test_provision_with_oob_public_key() {
+        in_thread_mode(provision_with_oob_public_key);
+    }
+
+    fn provision_with_oob_public_key() {
+        const FLASH_SIZE: usize = 8192;
+
+        let medium: SimulatedMedium = SimulatedMedium::new(MediumConfig::default());
+        let provisioner = Provisioner::new(
+            medium.advertising_bearer(medium.add_node(Position::default())),
+            FlashStorage::new(0, FLASH_SIZE, RamFlash::<FLASH_SIZE>::new()),
+            TestRng(0x2545F4914F6CDD1D),
+        );
+
+        let vault = FixedKeyVaultFactory::new(&[0x42; 32]).unwrap();
+        let public_key = vault.public_key().unwrap();
+        let ctx = Rc::new(RefCell::new(None));
+        let node = Node::new(
+            TestElements {
+                composition: composition(),
+                ctx: ctx.clone(),
+            },
+            capabilities(),
+            AdvertisingOnlyNetworkInterfaces::new(
+                medium.advertising_bearer(medium.add_node(Position::default())),
+            ),
+            ConfigurationManager::new(
+                FlashStorage::new(0, FLASH_SIZE, RamFlash::<FLASH_SIZE>::new()),
+                composition(),
+                false,
+            )
+            .unwrap(),
+            TestRng(0x0123456789ABCDEF),
+            NoOob,
+            vault,
+        );
+        let control: Channel<NoopRawMutex, MeshNodeMessage, 1> = Channel::new();
+
+        let test = async {
+            provisioner.initialize().await.unwrap();
+            let device = provisioner
+                .scan(Duration::from_secs(10))
+                .await
+                .unwrap()
+                .expect("no unprovisioned device found");
+            // the device skips sending its public key, which would otherwise
+            // arrive in place of its confirmation, and both agree on the
+            // secret from the key of the QR code.
+            provisioner
+                .provision_authenticated(
+                    device.uuid,
+                    Authentication::NoOob,
+                    &mut QrCode(public_key),
+                )
+                .await
+                .unwrap();
+
+            while ctx.borrow().is_none() {
+                Timer::after(POLL_INTERVAL).await;
+            }
+        };
+
+        match block_on(select(node.run(control.receiver().into()), test)) {
+            Either::First(_) => panic!("node stopped running"),
+            Either::Second(_) => {}
+        }
+        assert_eq!(1, provisioner.database().nodes().len());
+    }
+}
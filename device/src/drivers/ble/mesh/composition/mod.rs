@@ -16,6 +16,17 @@ pub trait ElementsHandler<'a> {
 
     fn configure(&mut self, _: &ConfigurationModel) {}
 
+    /// Draw attention to the device, such as by blinking, for `seconds`,
+    /// or stop doing so if zero.
+    fn attention(&mut self, _seconds: u8) {}
+
+    /// Run the self-test identified by `test_id`, registering any faults
+    /// found. Returns whether the test is supported, test 0 doing nothing
+    /// unless overridden.
+    fn fault_test(&mut self, test_id: u8) -> bool {
+        test_id == 0
+    }
+
     type DispatchFuture<'m>: Future<Output = Result<(), DeviceError>> + 'm
     where
         Self: 'm;
@@ -60,6 +71,20 @@ pub struct Features {
 }
 
 impl Features {
+    pub(crate) fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 2 {
+            let val = parameters[0];
+            Ok(Self {
+                relay: val & 0b0001 != 0,
+                proxy: val & 0b0010 != 0,
+                friend: val & 0b0100 != 0,
+                low_power: val & 0b1000 != 0,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
     pub(crate) fn emit<const N: usize>(
         &self,
         xmit: &mut Vec<u8, N>,
@@ -115,17 +140,45 @@ impl Composition {
     pub fn add_element(&mut self, element: ElementDescriptor) -> Result<(), ElementDescriptor> {
         self.elements.push(element)
     }
+
+    pub fn cid(&self) -> CompanyIdentifier {
+        self.cid
+    }
+
+    pub fn pid(&self) -> ProductIdentifier {
+        self.pid
+    }
+
+    pub fn vid(&self) -> VersionIdentifier {
+        self.vid
+    }
+
+    pub fn crpl(&self) -> u16 {
+        self.crpl
+    }
+
+    pub fn features(&self) -> Features {
+        self.features
+    }
+
+    pub fn elements(&self) -> &[ElementDescriptor] {
+        &self.elements
+    }
 }
 
 #[derive(Copy, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Location(pub u16);
 
+/// Models an element may hold, including the configuration and health
+/// servers the node adds to the primary element.
+pub const MAX_MODELS: usize = 16;
+
 #[derive(Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ElementDescriptor {
     pub(crate) loc: Location,
-    pub(crate) models: Vec<ModelIdentifier, 4>,
+    pub(crate) models: Vec<ModelIdentifier, MAX_MODELS>,
 }
 
 impl ElementDescriptor {
@@ -136,8 +189,16 @@ impl ElementDescriptor {
         }
     }
 
-    pub fn add_model(mut self, model: ModelIdentifier) -> Self {
-        self.models.push(model).ok();
-        self
+    pub fn add_model(mut self, model: ModelIdentifier) -> Result<Self, InsufficientBuffer> {
+        self.models.push(model).map_err(|_| InsufficientBuffer)?;
+        Ok(self)
+    }
+
+    pub fn loc(&self) -> Location {
+        self.loc
+    }
+
+    pub fn models(&self) -> &[ModelIdentifier] {
+        &self.models
     }
 }
@@ -27,7 +27,12 @@ impl AppKeys {
                 .map_err(|_| Status::UnspecifiedError)?
                 .into();
             self.keys
-                .push(AppKeyDetails { aid, key, index })
+                .push(AppKeyDetails {
+                    aid,
+                    key,
+                    index,
+                    updated: None,
+                })
                 .map_err(|_| Status::InsufficientResources)?;
             Ok(())
         }
@@ -59,15 +64,61 @@ pub struct AppKeyDetails {
     pub(crate) aid: ApplicationKeyIdentifier,
     pub(crate) key: AppKey,
     pub(crate) index: AppKeyIndex,
+    /// The replacement key distributed during a key refresh of the subnet.
+    pub(crate) updated: Option<UpdatedAppKey>,
 }
 
 impl AppKeyDetails {
+    pub(crate) fn new(index: AppKeyIndex, key: [u8; 16]) -> Result<Self, Status> {
+        let aid = crypto::k4(&key).map_err(|_| Status::UnspecifiedError)?;
+        Ok(Self {
+            aid: aid.into(),
+            key: key.into(),
+            index,
+            updated: None,
+        })
+    }
+
+    /// Store the new key of a key refresh; storing the same key again is idempotent.
+    pub(crate) fn update(&mut self, key: [u8; 16]) -> Result<(), Status> {
+        match &self.updated {
+            Some(updated) if *updated.key.as_ref() == key => Ok(()),
+            Some(_) => Err(Status::CannotUpdate),
+            None => {
+                let aid = crypto::k4(&key).map_err(|_| Status::UnspecifiedError)?;
+                self.updated.replace(UpdatedAppKey {
+                    aid: aid.into(),
+                    key: key.into(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /// Replace the old key with the new one at the end of a key refresh.
+    pub(crate) fn revoke_old_key(&mut self) {
+        if let Some(updated) = self.updated.take() {
+            self.aid = updated.aid;
+            self.key = updated.key;
+        }
+    }
+
     #[cfg(feature = "defmt")]
     pub(crate) fn display_configuration(&self) {
-        info!("  {}: {} [aid={}]", self.index, self.key, self.aid)
+        info!("  {}: {} [aid={}]", self.index, self.key, self.aid);
+        if let Some(updated) = &self.updated {
+            info!("    updated: {} [aid={}]", updated.key, updated.aid);
+        }
     }
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UpdatedAppKey {
+    pub(crate) aid: ApplicationKeyIdentifier,
+    pub(crate) key: AppKey,
+}
+
 #[derive(Serialize, Deserialize, Copy, Clone, Default)]
 pub struct AppKey([u8; 16]);
 
@@ -77,24 +77,20 @@ impl Bindings {
         element_address: &UnicastAddress,
         model_identifier: &ModelIdentifier,
     ) -> Result<(), Status> {
-        let mut removed = false;
-        let mut bindings = Vec::new();
-        for binding in self.bindings.iter() {
-            if !binding.matches(element_address, model_identifier) {
-                bindings
-                    .push(*binding)
-                    .map_err(|_| Status::InsufficientResources)?;
-                removed = true
-            }
-        }
-
-        if removed {
-            self.bindings = bindings;
+        let before = self.bindings.len();
+        self.bindings
+            .retain(|binding| !binding.matches(element_address, model_identifier));
+        if self.bindings.len() < before {
             Ok(())
         } else {
             Err(Status::InvalidBinding)
         }
     }
+
+    /// Remove every binding to an application key that is being deleted.
+    pub(crate) fn unbind_app_key(&mut self, app_key_index: &AppKeyIndex) {
+        self.bindings.retain(|e| e.app_key_index != *app_key_index);
+    }
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone)]
@@ -130,3 +126,63 @@ impl Binding {
         &mut self.app_key_index
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: ModelIdentifier = ModelIdentifier::SIG(0x1000);
+
+    fn addr(value: u16) -> UnicastAddress {
+        UnicastAddress::parse(value.to_be_bytes()).unwrap()
+    }
+
+    fn bound(bindings: &Bindings, element_address: u16) -> Option<u16> {
+        bindings
+            .find(&addr(element_address), &MODEL)
+            .map(|binding| binding.app_key_index().value())
+    }
+
+    #[test]
+    fn bind_and_unbind() {
+        let mut bindings = Bindings::default();
+        bindings
+            .bind(&addr(0x0002), &MODEL, &AppKeyIndex::new(1))
+            .unwrap();
+        bindings
+            .bind(&addr(0x0003), &MODEL, &AppKeyIndex::new(1))
+            .unwrap();
+        assert_eq!(Some(1), bound(&bindings, 0x0002));
+
+        // binding again replaces the key.
+        bindings
+            .bind(&addr(0x0002), &MODEL, &AppKeyIndex::new(2))
+            .unwrap();
+        assert_eq!(Some(2), bound(&bindings, 0x0002));
+
+        bindings.unbind(&addr(0x0002), &MODEL).unwrap();
+        assert_eq!(None, bound(&bindings, 0x0002));
+        assert_eq!(Some(1), bound(&bindings, 0x0003));
+        assert!(matches!(
+            bindings.unbind(&addr(0x0002), &MODEL),
+            Err(Status::InvalidBinding)
+        ));
+
+        bindings.unbind(&addr(0x0003), &MODEL).unwrap();
+        assert_eq!(None, bound(&bindings, 0x0003));
+    }
+
+    #[test]
+    fn deleting_app_key_unbinds() {
+        let mut bindings = Bindings::default();
+        bindings
+            .bind(&addr(0x0002), &MODEL, &AppKeyIndex::new(1))
+            .unwrap();
+        bindings
+            .bind(&addr(0x0003), &MODEL, &AppKeyIndex::new(2))
+            .unwrap();
+        bindings.unbind_app_key(&AppKeyIndex::new(1));
+        assert_eq!(None, bound(&bindings, 0x0002));
+        assert_eq!(Some(2), bound(&bindings, 0x0003));
+    }
+}
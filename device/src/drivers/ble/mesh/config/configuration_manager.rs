@@ -1,18 +1,28 @@
 use crate::drivers::ble::mesh::address::Address;
+use crate::drivers::ble::mesh::beacon::SecureNetworkBeacon;
 use crate::drivers::ble::mesh::composition::{Composition, ElementDescriptor, Location};
+use crate::drivers::ble::mesh::config::iv_index::{IvIndexState, IvIndexTransition};
+use crate::drivers::ble::mesh::config::migration;
 use crate::drivers::ble::mesh::config::Configuration;
 use crate::drivers::ble::mesh::driver::DeviceError;
-use crate::drivers::ble::mesh::model::foundation::configuration::CONFIGURATION_SERVER;
-use crate::drivers::ble::mesh::storage::{Payload, Storage};
+use crate::drivers::ble::mesh::model::foundation::configuration::{
+    NetKeyIndex, CONFIGURATION_SERVER,
+};
+use crate::drivers::ble::mesh::model::foundation::health::HEALTH_SERVER;
+use crate::drivers::ble::mesh::model::Status;
+use crate::drivers::ble::mesh::provisioning::IVUpdateFlag;
+use crate::drivers::ble::mesh::storage::{Payload, Storage, PAYLOAD_SIZE};
 use atomic_polyfill::{AtomicBool, Ordering};
 use core::cell::Ref;
 use core::cell::RefCell;
 use heapless::Vec;
-use postcard::{from_bytes, to_slice};
 use rand_core::{CryptoRng, RngCore};
 
 pub(crate) const SEQUENCE_THRESHOLD: u32 = 100;
 
+/// Largest sequence number representable in a network PDU.
+const MAX_SEQUENCE: u32 = 0xFF_FFFF;
+
 pub struct ConfigurationManager<S: Storage> {
     storage: RefCell<S>,
     config: RefCell<Configuration>,
@@ -22,17 +32,26 @@ pub struct ConfigurationManager<S: Storage> {
 }
 
 impl<S: Storage> ConfigurationManager<S> {
-    pub fn new(storage: S, mut composition: Composition, force_reset: bool) -> Self {
+    pub fn new(
+        storage: S,
+        mut composition: Composition,
+        force_reset: bool,
+    ) -> Result<Self, DeviceError> {
         if composition.elements.is_empty() {
             let descriptor = ElementDescriptor::new(Location(0x0000));
             composition.add_element(descriptor).ok();
         }
 
         let mut models = Vec::new();
-        models.push(CONFIGURATION_SERVER).ok();
+        models
+            .push(CONFIGURATION_SERVER)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        models
+            .push(HEALTH_SERVER)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
         models
             .extend_from_slice(&composition.elements[0].models)
-            .ok();
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
         composition.elements[0].models = models;
 
         let me = Self {
@@ -57,18 +76,22 @@ impl<S: Storage> ConfigurationManager<S> {
             "CFG runtime_seq: {:?}",
             core::mem::size_of_val(&me.runtime_seq)
         );*/
-        me
+        Ok(me)
     }
 
+    /// Load the configuration, generating what is missing. The key pair of
+    /// the device is only generated and kept in the configuration if
+    /// `stores_key_pair`, as otherwise held by the vault.
     pub(crate) async fn initialize<R: RngCore + CryptoRng>(
         &self,
         rng: &mut R,
+        stores_key_pair: bool,
     ) -> Result<(), DeviceError> {
         if self.force_reset.load(Ordering::SeqCst) {
             info!("Performing FORCE RESET");
             self.update_configuration(|config| {
                 *config = Configuration::default();
-                config.validate(rng);
+                config.validate(rng, stores_key_pair);
                 Ok(())
             })
             .await
@@ -85,9 +108,12 @@ impl<S: Storage> ConfigurationManager<S> {
                     Err(DeviceError::StorageInitialization)
                 }
                 Some(payload) => {
-                    let mut config: Configuration =
-                        from_bytes(&payload.payload).map_err(|_| DeviceError::Serialization)?;
-                    if config.validate(rng) {
+                    let (mut config, migrated) = migration::decode(&payload.payload)?;
+                    if migrated {
+                        info!("migrating stored configuration");
+                    }
+                    // a migrated configuration is stored again in the current layout.
+                    if config.validate(rng, stores_key_pair) || migrated {
                         // we initialized some things that we should stuff away.
                         self.runtime_seq.replace(config.seq);
                         self.update_configuration(move |stored| {
@@ -165,15 +191,21 @@ impl<S: Storage> ConfigurationManager<S> {
     ) -> Result<(), DeviceError> {
         let mut config = self.config.borrow().clone();
         update(&mut config)?;
+        self.store(&config).await?;
         *self.config.borrow_mut() = config;
-        self.store().await
+        Ok(())
     }
 
-    async fn store(&self) -> Result<(), DeviceError> {
-        let mut payload = [0; 512];
-        let config = self.config.borrow();
-        to_slice(&*config, &mut payload)?;
-        let payload = Payload { payload };
+    /// Store `config`, which only then is to replace the configuration in
+    /// use, so a configuration failing to store is never acted upon.
+    async fn store(&self, config: &Configuration) -> Result<(), DeviceError> {
+        let mut payload = Payload {
+            payload: [0; PAYLOAD_SIZE],
+        };
+        if migration::encode(config, &mut payload.payload).is_err() {
+            warn!("configuration exceeds the storage payload");
+            return Err(Status::InsufficientResources.into());
+        }
         self.storage
             .borrow_mut()
             .store(&payload)
@@ -183,12 +215,29 @@ impl<S: Storage> ConfigurationManager<S> {
     }
 
     pub(crate) async fn next_sequence(&self) -> Result<u32, DeviceError> {
-        let mut runtime_seq = self.runtime_seq.borrow_mut();
-        let seq = *runtime_seq;
-        *runtime_seq = *runtime_seq + 1;
-        if *runtime_seq % SEQUENCE_THRESHOLD == 0 {
+        let seq = {
+            let mut runtime_seq = self.runtime_seq.borrow_mut();
+            let seq = *runtime_seq;
+            *runtime_seq = *runtime_seq + 1;
+            seq
+        };
+        if seq > MAX_SEQUENCE {
+            // the IV update that would reset the sequence hasn't completed yet.
+            return Err(DeviceError::InvalidState);
+        }
+        let update_due = self
+            .configuration()
+            .network()
+            .as_ref()
+            .map(|network| network.iv_index_state().update_due(seq))
+            .unwrap_or(false);
+        if update_due {
+            self.update_iv_index(false, |state| state.sequence_allocated(seq))
+                .await?;
+        }
+        if (seq + 1) % SEQUENCE_THRESHOLD == 0 {
             self.update_configuration(|config| {
-                config.seq = *runtime_seq;
+                config.seq = seq + 1;
                 Ok(())
             })
             .await?;
@@ -196,6 +245,96 @@ impl<S: Storage> ConfigurationManager<S> {
         Ok(seq)
     }
 
+    /// Account for another hour spent in the current IV Update state.
+    pub(crate) async fn iv_index_hour_elapsed(&self) -> Result<(), DeviceError> {
+        // always store, so the time spent in a state survives a restart.
+        self.update_iv_index(true, |state| state.hour_elapsed())
+            .await
+    }
+
+    /// Follow the IV index state advertised by an authenticated secure network beacon.
+    pub(crate) async fn iv_index_beacon(
+        &self,
+        iv_index: u32,
+        iv_update_flag: IVUpdateFlag,
+    ) -> Result<(), DeviceError> {
+        self.update_iv_index(false, |state| {
+            state.beacon_received(iv_index, iv_update_flag)
+        })
+        .await
+    }
+
+    /// Follow the key refresh and IV index state advertised by a secure
+    /// network beacon, ignoring it unless authenticated by one of our subnets.
+    pub(crate) async fn secure_network_beacon(
+        &self,
+        beacon: &SecureNetworkBeacon,
+    ) -> Result<(), DeviceError> {
+        let mut config = self.config.borrow().clone();
+        let (key_index, key_refresh_phase, follow_iv_index) =
+            if let Some(network) = config.network_mut() {
+                let primary = NetKeyIndex::new(0);
+                let member_of_primary = network.find_by_net_key_index(&primary).is_ok();
+                if let Some(subnet) = network.authenticate_beacon(beacon) {
+                    (
+                        subnet.key_index(),
+                        subnet.key_refresh_phase(),
+                        // a member of the primary subnet only follows its IV index.
+                        !member_of_primary || subnet.key_index() == primary,
+                    )
+                } else {
+                    return Ok(());
+                }
+            } else {
+                return Ok(());
+            };
+
+        let key_refresh_changed = match self.configuration().network() {
+            Some(network) => network
+                .find_by_net_key_index(&key_index)
+                .map(|subnet| subnet.key_refresh_phase() != key_refresh_phase)
+                .unwrap_or(false),
+            None => false,
+        };
+        if key_refresh_changed {
+            self.store(&config).await?;
+            *self.config.borrow_mut() = config;
+        }
+
+        if follow_iv_index {
+            let iv_update_flag = if beacon.flags.iv_update {
+                IVUpdateFlag::UpdateActive
+            } else {
+                IVUpdateFlag::NormalOperation
+            };
+            self.iv_index_beacon(beacon.iv_index, iv_update_flag)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn update_iv_index<F: FnOnce(&mut IvIndexState) -> IvIndexTransition>(
+        &self,
+        always_store: bool,
+        update: F,
+    ) -> Result<(), DeviceError> {
+        let mut config = self.config.borrow().clone();
+        let transition = if let Some(network) = config.network_mut() {
+            update(network.iv_index_state_mut())
+        } else {
+            return Ok(());
+        };
+        if transition.resets_sequence() {
+            *self.runtime_seq.borrow_mut() = 0;
+            config.seq = 0;
+        }
+        if always_store || transition != IvIndexTransition::None {
+            self.store(&config).await?;
+            *self.config.borrow_mut() = config;
+        }
+        Ok(())
+    }
+
     pub(crate) fn reset(&self) {
         self.force_reset.store(true, Ordering::SeqCst);
     }
@@ -1,5 +1,7 @@
+use crate::drivers::ble::mesh::address::UnicastAddress;
 use crate::drivers::ble::mesh::driver::DeviceError;
 use core::convert::TryInto;
+use heapless::Vec;
 use p256::ecdh::SharedSecret;
 use p256::elliptic_curve::generic_array::{typenum::consts::U32, GenericArray};
 use p256::{PublicKey, SecretKey};
@@ -14,6 +16,19 @@ pub struct DeviceKeys {
 }
 
 impl DeviceKeys {
+    /// Keys as stored by an earlier layout of the configuration.
+    pub(crate) fn restore(
+        private_key: Option<[u8; 32]>,
+        shared_secret: Option<[u8; 32]>,
+        device_key: Option<[u8; 16]>,
+    ) -> Self {
+        Self {
+            private_key,
+            shared_secret,
+            device_key: device_key.map(DeviceKey::new),
+        }
+    }
+
     #[cfg(feature = "defmt")]
     pub(crate) fn display_configuration(&self) {
         if let Some(key) = self.device_key {
@@ -138,3 +153,80 @@ impl defmt::Format for DeviceKey {
         )
     }
 }
+
+/// Selects the device key protecting an access message sent without an application key.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DeviceKeyHandle {
+    /// This node's own device key, as used by the configuration server.
+    Local,
+    /// The device key of the remote node with this primary address, as used
+    /// when acting as a configuration client.
+    Remote(UnicastAddress),
+}
+
+pub(crate) const MAX_REMOTE_DEVICE_KEYS: usize = 4;
+
+/// Device keys of remote nodes recently addressed by the configuration client.
+///
+/// These are not persisted; the client supplies the key with every request,
+/// and it only needs to be remembered long enough to decrypt the response.
+/// Only the last [`MAX_REMOTE_DEVICE_KEYS`] nodes addressed are remembered,
+/// so responses to requests outstanding to more nodes than that at once are
+/// dropped, and those requests time out.
+#[derive(Default)]
+pub(crate) struct RemoteDeviceKeys {
+    keys: Vec<(UnicastAddress, DeviceKey), MAX_REMOTE_DEVICE_KEYS>,
+}
+
+impl RemoteDeviceKeys {
+    /// Remember the key of `addr`, forgetting the least recently addressed
+    /// node when already holding [`MAX_REMOTE_DEVICE_KEYS`] keys.
+    pub(crate) fn insert(&mut self, addr: UnicastAddress, key: DeviceKey) {
+        if let Some(index) = self.keys.iter().position(|(e, _)| *e == addr) {
+            self.keys.remove(index);
+        } else if self.keys.is_full() {
+            self.keys.remove(0);
+        }
+        self.keys.push((addr, key)).ok();
+    }
+
+    pub(crate) fn get(&self, addr: &UnicastAddress) -> Option<DeviceKey> {
+        self.keys
+            .iter()
+            .find(|(e, _)| e == addr)
+            .map(|(_, key)| *key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(value: u16) -> UnicastAddress {
+        UnicastAddress::parse(value.to_be_bytes()).unwrap()
+    }
+
+    fn key(value: u8) -> Option<[u8; 16]> {
+        Some([value; 16])
+    }
+
+    #[test]
+    fn remote_device_keys_forget_least_recently_addressed() {
+        let mut keys = RemoteDeviceKeys::default();
+        for n in 1..=MAX_REMOTE_DEVICE_KEYS as u16 {
+            keys.insert(addr(n), DeviceKey::new([n as u8; 16]));
+        }
+        // addressing a node again makes it the most recent.
+        keys.insert(addr(1), DeviceKey::new([0x11; 16]));
+        keys.insert(addr(0x100), DeviceKey::new([0xFF; 16]));
+
+        let get = |n| keys.get(&addr(n)).map(|key| *key.as_ref());
+        assert_eq!(key(0x11), get(1));
+        assert_eq!(None, get(2));
+        for n in 3..=MAX_REMOTE_DEVICE_KEYS as u16 {
+            assert_eq!(key(n as u8), get(n));
+        }
+        assert_eq!(key(0xFF), get(0x100));
+    }
+}
@@ -1,5 +1,8 @@
+use crate::drivers::ble::mesh::config::publications::publish_period_duration;
+use crate::drivers::ble::mesh::model::foundation::configuration::friend::Friend;
+use crate::drivers::ble::mesh::model::foundation::configuration::gatt_proxy::GattProxy;
+use crate::drivers::ble::mesh::model::foundation::configuration::heartbeat::HeartbeatPublication;
 use crate::drivers::ble::mesh::model::foundation::configuration::network_transmit::NetworkTransmitConfig;
-#[cfg(feature = "ble-mesh-relay")]
 use crate::drivers::ble::mesh::model::foundation::configuration::relay::RelayConfig;
 use embassy::time::Duration;
 use serde::{Deserialize, Serialize};
@@ -27,8 +30,12 @@ pub struct ConfigurationModel {
     default_ttl: u8,
     publish_period: u8,
     network_transmit: NetworkTransmitConfig,
-    #[cfg(feature = "ble-mesh-relay")]
+    heartbeat_publication: HeartbeatPublication,
+    // stored whether or not the features are enabled, so the layout is the
+    // same for all builds, keeping their defaults when disabled.
     relay: RelayConfig,
+    friend: Friend,
+    gatt_proxy: GattProxy,
 }
 
 impl ConfigurationModel {
@@ -57,20 +64,7 @@ impl ConfigurationModel {
     }
 
     pub fn publish_period_duration(&self) -> Option<Duration> {
-        let steps = (self.publish_period & 0x3F) as u64;
-        let res = (self.publish_period & 0xC0) >> 6;
-
-        if steps == 0 {
-            return None;
-        }
-
-        match res {
-            0b00 => Some(Duration::from_millis(100 * steps)),
-            0b01 => Some(Duration::from_secs(steps)),
-            0b10 => Some(Duration::from_secs(10 * steps)),
-            0b11 => Some(Duration::from_secs(600 * steps)),
-            _ => None,
-        }
+        publish_period_duration(self.publish_period)
     }
 
     #[cfg(feature = "ble-mesh-relay")]
@@ -83,6 +77,26 @@ impl ConfigurationModel {
         &mut self.relay
     }
 
+    #[cfg(feature = "ble-mesh-friend")]
+    pub fn friend(&self) -> Friend {
+        self.friend
+    }
+
+    #[cfg(feature = "ble-mesh-friend")]
+    pub fn friend_mut(&mut self) -> &mut Friend {
+        &mut self.friend
+    }
+
+    #[cfg(feature = "ble-mesh-proxy")]
+    pub fn gatt_proxy(&self) -> GattProxy {
+        self.gatt_proxy
+    }
+
+    #[cfg(feature = "ble-mesh-proxy")]
+    pub fn gatt_proxy_mut(&mut self) -> &mut GattProxy {
+        &mut self.gatt_proxy
+    }
+
     pub fn network_transmit(&self) -> &NetworkTransmitConfig {
         &self.network_transmit
     }
@@ -90,6 +104,14 @@ impl ConfigurationModel {
     pub fn network_transmit_mut(&mut self) -> &mut NetworkTransmitConfig {
         &mut self.network_transmit
     }
+
+    pub fn heartbeat_publication(&self) -> &HeartbeatPublication {
+        &self.heartbeat_publication
+    }
+
+    pub fn heartbeat_publication_mut(&mut self) -> &mut HeartbeatPublication {
+        &mut self.heartbeat_publication
+    }
 }
 
 impl Default for ConfigurationModel {
@@ -98,9 +120,11 @@ impl Default for ConfigurationModel {
             secure_beacon: true,
             default_ttl: 127,
             publish_period: 0,
-            #[cfg(feature = "ble-mesh-relay")]
             relay: RelayConfig::default(),
+            friend: Friend::default(),
+            gatt_proxy: GattProxy::default(),
             network_transmit: NetworkTransmitConfig::default(),
+            heartbeat_publication: HeartbeatPublication::default(),
         }
     }
 }
@@ -0,0 +1,248 @@
+use crate::drivers::ble::mesh::provisioning::IVUpdateFlag;
+use serde::{Deserialize, Serialize};
+
+/// Hours a node must spend in either IV Update state before leaving it.
+pub(crate) const IV_UPDATE_MIN_HOURS: u16 = 96;
+
+/// Sequence number past which the node starts an IV Update itself, leaving
+/// plenty of the 24-bit space to finish it before running out.
+pub(crate) const IV_UPDATE_SEQUENCE_THRESHOLD: u32 = 0x80_0000;
+
+/// Furthest ahead of the current IV index a beacon may be for the node to
+/// recover to it.
+const IV_INDEX_RECOVERY_LIMIT: u32 = 42;
+
+/// The outcome of feeding an event to the IV Update state machine.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum IvIndexTransition {
+    /// Nothing changed.
+    None,
+    /// Entered the IV Update in Progress state with an incremented IV index.
+    UpdateStarted,
+    /// Returned to Normal Operation; the sequence number must restart at zero.
+    UpdateCompleted,
+    /// Jumped to an IV index learned from the network; the sequence number
+    /// must restart at zero.
+    Recovered,
+}
+
+impl IvIndexTransition {
+    pub(crate) fn resets_sequence(&self) -> bool {
+        matches!(self, Self::UpdateCompleted | Self::Recovered)
+    }
+}
+
+/// IV index and IV Update procedure state, persisted as part of the network
+/// configuration so a restart neither rewinds the index nor shortens the
+/// time spent in a state.
+///
+/// `iv_index` is always the newest known index; while an update is in
+/// progress the node still transmits using the previous one.
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct IvIndexState {
+    iv_index: u32,
+    iv_update_flag: IVUpdateFlag,
+    hours_in_state: u16,
+}
+
+impl IvIndexState {
+    pub(crate) fn new(iv_index: u32, iv_update_flag: IVUpdateFlag) -> Self {
+        Self {
+            iv_index,
+            iv_update_flag,
+            // a freshly provisioned node has no history to wait out.
+            hours_in_state: IV_UPDATE_MIN_HOURS,
+        }
+    }
+
+    pub fn iv_index(&self) -> u32 {
+        self.iv_index
+    }
+
+    pub fn iv_update_flag(&self) -> IVUpdateFlag {
+        self.iv_update_flag
+    }
+
+    pub fn in_progress(&self) -> bool {
+        matches!(self.iv_update_flag, IVUpdateFlag::UpdateActive)
+    }
+
+    /// The IV index used for outbound network PDUs.
+    pub fn transmit_iv_index(&self) -> u32 {
+        if self.in_progress() {
+            self.iv_index.saturating_sub(1)
+        } else {
+            self.iv_index
+        }
+    }
+
+    /// The IV index an inbound network PDU was sent with, identified by its
+    /// IVI bit as either the current index or the one before it.
+    pub fn receive_iv_index(&self, ivi: u8) -> u32 {
+        if (self.iv_index & 1) as u8 == ivi & 1 {
+            self.iv_index
+        } else {
+            self.iv_index.saturating_sub(1)
+        }
+    }
+
+    /// Account for another hour in the current state, completing an update
+    /// that has been in progress for long enough.
+    pub(crate) fn hour_elapsed(&mut self) -> IvIndexTransition {
+        self.hours_in_state = self.hours_in_state.saturating_add(1);
+        if self.in_progress() && self.hours_in_state >= IV_UPDATE_MIN_HOURS {
+            self.transition(self.iv_index, IVUpdateFlag::NormalOperation);
+            IvIndexTransition::UpdateCompleted
+        } else {
+            IvIndexTransition::None
+        }
+    }
+
+    /// Whether allocating `seq` should start an IV Update.
+    pub(crate) fn update_due(&self, seq: u32) -> bool {
+        !self.in_progress()
+            && seq >= IV_UPDATE_SEQUENCE_THRESHOLD
+            && self.hours_in_state >= IV_UPDATE_MIN_HOURS
+    }
+
+    /// Start an IV Update once the sequence number nears exhaustion.
+    pub(crate) fn sequence_allocated(&mut self, seq: u32) -> IvIndexTransition {
+        if self.update_due(seq) {
+            info!("initiating IV update to {}", self.iv_index + 1);
+            self.transition(self.iv_index + 1, IVUpdateFlag::UpdateActive);
+            IvIndexTransition::UpdateStarted
+        } else {
+            IvIndexTransition::None
+        }
+    }
+
+    /// Follow the IV index and IV Update flag of an authenticated secure
+    /// network beacon.
+    pub(crate) fn beacon_received(
+        &mut self,
+        iv_index: u32,
+        iv_update_flag: IVUpdateFlag,
+    ) -> IvIndexTransition {
+        let update_active = matches!(iv_update_flag, IVUpdateFlag::UpdateActive);
+        if self.in_progress() {
+            if iv_index == self.iv_index && !update_active {
+                self.transition(iv_index, IVUpdateFlag::NormalOperation);
+                IvIndexTransition::UpdateCompleted
+            } else {
+                IvIndexTransition::None
+            }
+        } else if iv_index <= self.iv_index
+            || iv_index > self.iv_index + IV_INDEX_RECOVERY_LIMIT
+            || self.hours_in_state < IV_UPDATE_MIN_HOURS
+        {
+            IvIndexTransition::None
+        } else if iv_index == self.iv_index + 1 && update_active {
+            self.transition(iv_index, IVUpdateFlag::UpdateActive);
+            IvIndexTransition::UpdateStarted
+        } else {
+            // we missed at least one whole update.
+            warn!("recovering IV index {}", iv_index);
+            self.transition(iv_index, iv_update_flag);
+            IvIndexTransition::Recovered
+        }
+    }
+
+    fn transition(&mut self, iv_index: u32, iv_update_flag: IVUpdateFlag) {
+        self.iv_index = iv_index;
+        self.iv_update_flag = iv_update_flag;
+        self.hours_in_state = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_exhaustion_starts_update() {
+        let mut state = IvIndexState::new(5, IVUpdateFlag::NormalOperation);
+        assert_eq!(
+            state.sequence_allocated(IV_UPDATE_SEQUENCE_THRESHOLD - 1),
+            IvIndexTransition::None
+        );
+        assert_eq!(
+            state.sequence_allocated(IV_UPDATE_SEQUENCE_THRESHOLD),
+            IvIndexTransition::UpdateStarted
+        );
+        assert_eq!(state.iv_index(), 6);
+        assert_eq!(state.transmit_iv_index(), 5);
+        assert_eq!(state.receive_iv_index(0), 6);
+        assert_eq!(state.receive_iv_index(1), 5);
+    }
+
+    #[test]
+    fn update_completes_after_minimum_duration() {
+        let mut state = IvIndexState::new(5, IVUpdateFlag::NormalOperation);
+        state.sequence_allocated(IV_UPDATE_SEQUENCE_THRESHOLD);
+        for _ in 1..IV_UPDATE_MIN_HOURS {
+            assert_eq!(state.hour_elapsed(), IvIndexTransition::None);
+        }
+        let transition = state.hour_elapsed();
+        assert_eq!(transition, IvIndexTransition::UpdateCompleted);
+        assert!(transition.resets_sequence());
+        assert!(!state.in_progress());
+        assert_eq!(state.transmit_iv_index(), 6);
+
+        // too soon to start another one.
+        assert_eq!(
+            state.sequence_allocated(IV_UPDATE_SEQUENCE_THRESHOLD),
+            IvIndexTransition::None
+        );
+    }
+
+    #[test]
+    fn beacons_drive_update() {
+        let mut state = IvIndexState::new(5, IVUpdateFlag::NormalOperation);
+        assert_eq!(
+            state.beacon_received(5, IVUpdateFlag::NormalOperation),
+            IvIndexTransition::None
+        );
+        assert_eq!(
+            state.beacon_received(6, IVUpdateFlag::UpdateActive),
+            IvIndexTransition::UpdateStarted
+        );
+        assert_eq!(
+            state.beacon_received(6, IVUpdateFlag::UpdateActive),
+            IvIndexTransition::None
+        );
+        assert_eq!(
+            state.beacon_received(6, IVUpdateFlag::NormalOperation),
+            IvIndexTransition::UpdateCompleted
+        );
+        assert_eq!(state.transmit_iv_index(), 6);
+    }
+
+    #[test]
+    fn recovery() {
+        let mut state = IvIndexState::new(5, IVUpdateFlag::NormalOperation);
+        assert_eq!(
+            state.beacon_received(
+                5 + IV_INDEX_RECOVERY_LIMIT + 1,
+                IVUpdateFlag::NormalOperation
+            ),
+            IvIndexTransition::None
+        );
+        assert_eq!(
+            state.beacon_received(4, IVUpdateFlag::NormalOperation),
+            IvIndexTransition::None
+        );
+        assert_eq!(
+            state.beacon_received(20, IVUpdateFlag::NormalOperation),
+            IvIndexTransition::Recovered
+        );
+        assert_eq!(state.iv_index(), 20);
+
+        // at most one recovery per state duration.
+        assert_eq!(
+            state.beacon_received(30, IVUpdateFlag::NormalOperation),
+            IvIndexTransition::None
+        );
+    }
+}
@@ -0,0 +1,706 @@
+//! Versioning of the configuration persisted to storage.
+//!
+//! The configuration is stored as a header tagging the layout version,
+//! followed by the configuration serialized with postcard:
+//!
+//! ```text
+//! [0x00, 0xFF, version (u16, little-endian), configuration...]
+//! ```
+//!
+//! Configurations stored before the header was introduced start directly
+//! with the varint of the sequence number, which is only ever zero when
+//! followed by the tag of the optional UUID, never `0xFF`. Such untagged
+//! payloads are decoded as version 0.
+//!
+//! Changing the serialized layout of anything within [`Configuration`]
+//! requires freezing a copy of the current layout into a module named for
+//! its version, as done for [`v0`], converting it into the new layout, and
+//! bumping [`CONFIGURATION_VERSION`]. A fixture under `fixtures/`, as written
+//! to storage by the last release of the frozen layout, keeps the conversion
+//! tested.
+
+use crate::drivers::ble::mesh::config::Configuration;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use postcard::{from_bytes, to_slice};
+
+/// Version of the layout of the configuration as currently stored.
+pub(crate) const CONFIGURATION_VERSION: u16 = 1;
+
+const MAGIC: [u8; 2] = [0x00, 0xFF];
+const HEADER_LEN: usize = 4;
+
+/// Serialize `config` into `payload`, tagged with the current version.
+pub(crate) fn encode(config: &Configuration, payload: &mut [u8]) -> Result<(), DeviceError> {
+    if payload.len() < HEADER_LEN {
+        return Err(DeviceError::InsufficientBuffer);
+    }
+    payload[0..2].copy_from_slice(&MAGIC);
+    payload[2..HEADER_LEN].copy_from_slice(&CONFIGURATION_VERSION.to_le_bytes());
+    to_slice(config, &mut payload[HEADER_LEN..])?;
+    Ok(())
+}
+
+/// Deserialize a configuration stored by any known version, upgrading it to
+/// the current layout. Also returns whether it was upgraded, in which case
+/// it should be stored again.
+pub(crate) fn decode(payload: &[u8]) -> Result<(Configuration, bool), DeviceError> {
+    let (version, body) = if payload.len() >= HEADER_LEN && payload[0..2] == MAGIC {
+        (
+            u16::from_le_bytes([payload[2], payload[3]]),
+            &payload[HEADER_LEN..],
+        )
+    } else {
+        (0, payload)
+    };
+
+    match version {
+        0 => {
+            let config: v0::Configuration = from_bytes(body)?;
+            Ok((config.migrate()?, true))
+        }
+        CONFIGURATION_VERSION => Ok((from_bytes(body)?, false)),
+        _ => {
+            warn!("unsupported configuration version {}", version);
+            Err(DeviceError::Serialization)
+        }
+    }
+}
+
+/// The layout of the configuration before it was versioned, holding a
+/// single subnet without key refresh state, and no heartbeat publication.
+///
+/// Everything serialized is frozen here, down to the types holding plain
+/// keys, addresses and indexes, so changes to the current layout leave it
+/// intact.
+mod v0 {
+    use crate::drivers::ble::mesh::address::{Address, GroupAddress, LabelUuid, UnicastAddress};
+    use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+    use crate::drivers::ble::mesh::composition::CompanyIdentifier;
+    use crate::drivers::ble::mesh::config::app_keys::{self, AppKey};
+    use crate::drivers::ble::mesh::config::{
+        bindings, device_keys, foundation_models, network, publications,
+    };
+    use crate::drivers::ble::mesh::device::Uuid;
+    use crate::drivers::ble::mesh::driver::DeviceError;
+    use crate::drivers::ble::mesh::model;
+    use crate::drivers::ble::mesh::model::foundation::configuration::{
+        model_subscription, network_transmit, relay,
+    };
+    use crate::drivers::ble::mesh::model::foundation::configuration::{AppKeyIndex, NetKeyIndex};
+    use crate::drivers::ble::mesh::provisioning::IVUpdateFlag;
+    use heapless::Vec;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Configuration {
+        seq: u32,
+        uuid: Option<Uuid>,
+        device_keys: DeviceKeys,
+        network: Option<Network>,
+        foundation_models: FoundationModels,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct DeviceKeys {
+        private_key: Option<[u8; 32]>,
+        shared_secret: Option<[u8; 32]>,
+        device_key: Option<[u8; 16]>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Network {
+        networks: Vec<NetworkDetails, 1>,
+        iv_update_flag: IVUpdateFlag,
+        iv_index: u32,
+        unicast_address: UnicastAddress,
+        subscriptions: Vec<Subscription, 2>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct NetworkDetails {
+        network_key: [u8; 16],
+        key_index: NetKeyIndex,
+        nid: u8,
+        encryption_key: [u8; 16],
+        privacy_key: [u8; 16],
+        app_keys: Vec<AppKeyDetails, 1>,
+        bindings: Vec<Binding, 10>,
+        publications: Vec<Publication, 2>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct AppKeyDetails {
+        aid: ApplicationKeyIdentifier,
+        key: AppKey,
+        index: AppKeyIndex,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Binding {
+        model_identifier: ModelIdentifier,
+        element_address: UnicastAddress,
+        app_key_index: AppKeyIndex,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Publication {
+        element_address: UnicastAddress,
+        publish_address: Address,
+        app_key_index: AppKeyIndex,
+        credential_flag: bool,
+        publish_ttl: Option<u8>,
+        publish_period: u8,
+        publish_retransmit_count: u8,
+        publish_retransmit_interval_steps: u8,
+        model_identifier: ModelIdentifier,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Subscription {
+        element_address: UnicastAddress,
+        subscription_address: SubscriptionAddress,
+        model_identifier: ModelIdentifier,
+    }
+
+    #[derive(Serialize, Deserialize, Copy, Clone)]
+    enum SubscriptionAddress {
+        Unicast(UnicastAddress),
+        Group(GroupAddress),
+        Virtual(LabelUuid),
+    }
+
+    #[derive(Serialize, Deserialize, Copy, Clone)]
+    enum ModelIdentifier {
+        SIG(u16),
+        Vendor(u16, u16),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct FoundationModels {
+        configuration: ConfigurationModel,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct ConfigurationModel {
+        secure_beacon: bool,
+        default_ttl: u8,
+        publish_period: u8,
+        network_transmit: NetworkTransmitConfig,
+        // only stored by builds with the relay feature.
+        #[cfg(feature = "ble-mesh-relay")]
+        relay: RelayConfig,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct NetworkTransmitConfig {
+        network_retransmit_count: u8,
+        network_retransmit_interval_steps: u8,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct RelayConfig {
+        relay: Relay,
+        relay_retransmit_count: u8,
+        relay_retransmit_interval_steps: u8,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    enum Relay {
+        SupportedDisabled,
+        SupportedEnabled,
+        NotSupported,
+    }
+
+    impl From<SubscriptionAddress> for model_subscription::SubscriptionAddress {
+        fn from(address: SubscriptionAddress) -> Self {
+            match address {
+                SubscriptionAddress::Unicast(address) => Self::Unicast(address),
+                SubscriptionAddress::Group(address) => Self::Group(address),
+                SubscriptionAddress::Virtual(label) => Self::Virtual(label),
+            }
+        }
+    }
+
+    impl From<ModelIdentifier> for model::ModelIdentifier {
+        fn from(model_identifier: ModelIdentifier) -> Self {
+            match model_identifier {
+                ModelIdentifier::SIG(id) => Self::SIG(id),
+                ModelIdentifier::Vendor(company, id) => {
+                    Self::Vendor(CompanyIdentifier(company), id)
+                }
+            }
+        }
+    }
+
+    impl From<RelayConfig> for relay::RelayConfig {
+        fn from(config: RelayConfig) -> Self {
+            Self {
+                relay: match config.relay {
+                    Relay::SupportedDisabled => relay::Relay::SupportedDisabled,
+                    Relay::SupportedEnabled => relay::Relay::SupportedEnabled,
+                    Relay::NotSupported => relay::Relay::NotSupported,
+                },
+                relay_retransmit_count: config.relay_retransmit_count,
+                relay_retransmit_interval_steps: config.relay_retransmit_interval_steps,
+            }
+        }
+    }
+
+    impl Configuration {
+        pub(super) fn migrate(self) -> Result<super::Configuration, DeviceError> {
+            let device_keys = device_keys::DeviceKeys::restore(
+                self.device_keys.private_key,
+                self.device_keys.shared_secret,
+                self.device_keys.device_key,
+            );
+            let network = match self.network {
+                Some(network) => network.migrate()?,
+                None => None,
+            };
+            Ok(super::Configuration {
+                seq: self.seq,
+                uuid: self.uuid,
+                device_keys,
+                network,
+                foundation_models: self.foundation_models.configuration.migrate(),
+            })
+        }
+    }
+
+    impl Network {
+        fn migrate(self) -> Result<Option<network::Network>, DeviceError> {
+            // a network without any subnet was never stored.
+            let details = match self.networks.into_iter().next() {
+                Some(details) => details,
+                None => return Ok(None),
+            };
+
+            let mut bindings = bindings::Bindings::default();
+            for binding in details.bindings {
+                bindings
+                    .bind(
+                        &binding.element_address,
+                        &binding.model_identifier.into(),
+                        &binding.app_key_index,
+                    )
+                    .map_err(|_| DeviceError::InsufficientBuffer)?;
+            }
+
+            let mut publications = publications::Publications::default();
+            for publication in details.publications {
+                publications
+                    .set(
+                        publication.element_address,
+                        publication.publish_address,
+                        publication.app_key_index,
+                        publication.credential_flag,
+                        publication.publish_ttl,
+                        publication.publish_period,
+                        publication.publish_retransmit_count,
+                        publication.publish_retransmit_interval_steps,
+                        publication.model_identifier.into(),
+                    )
+                    .map_err(|_| DeviceError::InsufficientBuffer)?;
+            }
+
+            let mut primary = network::NetworkDetails::new(
+                network::NetworkKey::new(details.network_key),
+                details.key_index,
+                details.nid,
+                details.encryption_key,
+                details.privacy_key,
+            );
+            primary.restore(
+                details
+                    .app_keys
+                    .into_iter()
+                    .map(|key| app_keys::AppKeyDetails {
+                        aid: key.aid,
+                        key: key.key,
+                        index: key.index,
+                        updated: None,
+                    })
+                    .collect(),
+                bindings,
+                publications,
+            );
+
+            let mut network = network::Network::new(
+                primary,
+                self.iv_update_flag,
+                self.iv_index,
+                self.unicast_address,
+            );
+            let subscriptions = network.subscriptions_mut();
+            for subscription in self.subscriptions {
+                subscriptions
+                    .add(
+                        subscription.element_address,
+                        subscription.subscription_address.into(),
+                        subscription.model_identifier.into(),
+                    )
+                    .map_err(|_| DeviceError::InsufficientBuffer)?;
+            }
+            Ok(Some(network))
+        }
+    }
+
+    impl ConfigurationModel {
+        fn migrate(self) -> foundation_models::FoundationModels {
+            let mut foundation_models = foundation_models::FoundationModels::default();
+            let model = foundation_models.configuration_model_mut();
+            *model.secure_beacon_mut() = self.secure_beacon;
+            *model.default_ttl_mut() = self.default_ttl;
+            *model.publish_period_mut() = self.publish_period;
+            *model.network_transmit_mut() = network_transmit::NetworkTransmitConfig {
+                network_retransmit_count: self.network_transmit.network_retransmit_count,
+                network_retransmit_interval_steps: self
+                    .network_transmit
+                    .network_retransmit_interval_steps,
+            };
+            #[cfg(feature = "ble-mesh-relay")]
+            {
+                *model.relay_mut() = self.relay.into();
+            }
+            foundation_models
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::address::{Address, GroupAddress, LabelUuid, UnicastAddress};
+    use crate::drivers::ble::mesh::composition::CompanyIdentifier;
+    use crate::drivers::ble::mesh::config::device_keys::DeviceKeys;
+    use crate::drivers::ble::mesh::config::foundation_models::FoundationModels;
+    use crate::drivers::ble::mesh::config::network::{
+        Network, NetworkDetails, NetworkKey, MAX_APP_KEYS, MAX_NETWORK_KEYS,
+    };
+    use crate::drivers::ble::mesh::device::Uuid;
+    use crate::drivers::ble::mesh::model::foundation::configuration::heartbeat::{
+        Features, HeartbeatPublication,
+    };
+    use crate::drivers::ble::mesh::model::foundation::configuration::model_subscription::SubscriptionAddress;
+    use crate::drivers::ble::mesh::model::foundation::configuration::{AppKeyIndex, NetKeyIndex};
+    use crate::drivers::ble::mesh::model::ModelIdentifier;
+    use crate::drivers::ble::mesh::provisioning::IVUpdateFlag;
+    use crate::drivers::ble::mesh::storage::PAYLOAD_SIZE;
+    use heapless::Vec;
+
+    /// A provisioned node with one application key, as stored by version 0.
+    fn provisioned_v0() -> Vec<u8, 512> {
+        let mut fixture: Vec<u8, 512> = Vec::new();
+        let mut extend = |bytes: &[u8]| fixture.extend_from_slice(bytes).unwrap();
+        // seq
+        extend(&[0x64]);
+        // uuid
+        extend(&[0x01]);
+        extend(&[0x11; 16]);
+        // device keys, with only the device key set
+        extend(&[0x00, 0x00, 0x01]);
+        extend(&[0x22; 16]);
+        // network with a single subnet
+        extend(&[0x01, 0x01]);
+        extend(&[0x33; 16]);
+        // key index, nid, encryption and privacy keys
+        extend(&[0x00, 0x44]);
+        extend(&[0x55; 16]);
+        extend(&[0x66; 16]);
+        // a single app key with aid 0x0A and index 1
+        extend(&[0x01, 0x0A]);
+        extend(&[0x77; 16]);
+        extend(&[0x01]);
+        // no bindings nor publications
+        extend(&[0x00, 0x00]);
+        // iv update flag, iv index 0x1234, unicast address 0x0002, no subscriptions
+        extend(&[0x00, 0xB4, 0x24, 0x02, 0x00]);
+        // secure beacon, default ttl, publish period, network transmit
+        extend(&[0x01, 0x05, 0x00, 0x02, 0x03]);
+        #[cfg(feature = "ble-mesh-relay")]
+        extend(&[0x01, 0x02, 0x04]);
+        fixture
+    }
+
+    fn assert_provisioned(config: &Configuration) {
+        assert_eq!(100, config.seq);
+        assert_eq!([0x11; 16], config.uuid().unwrap().0);
+        assert!(config.device_keys().device_key().is_some());
+
+        let network = config.network().as_ref().unwrap();
+        assert_eq!(0x1234, network.iv_index());
+        assert!(!network.iv_index_state().in_progress());
+        assert_eq!(0x0002, u16::from(*network.unicast_address()));
+
+        let details = network.find_by_net_key_index(&NetKeyIndex::new(0)).unwrap();
+        assert_eq!(0x44, details.nid);
+        assert_eq!([0x55; 16], details.encryption_key);
+        let app_keys = details.app_key_indexes();
+        assert_eq!(&app_keys[..], &[AppKeyIndex::new(1)]);
+
+        let model = config.foundation_models().configuration_model();
+        assert!(model.secure_beacon());
+        assert_eq!(5, model.default_ttl());
+        assert_eq!(2, model.network_transmit().network_retransmit_count);
+        assert_eq!(
+            3,
+            model.network_transmit().network_retransmit_interval_steps
+        );
+        #[cfg(feature = "ble-mesh-relay")]
+        assert_eq!(2, model.relay().relay_retransmit_count);
+    }
+
+    #[test]
+    fn migrate_provisioned_v0() {
+        let (config, migrated) = decode(&provisioned_v0()).unwrap();
+        assert!(migrated);
+        assert_provisioned(&config);
+
+        let mut payload = [0; 512];
+        encode(&config, &mut payload).unwrap();
+        assert_eq!(&payload[..4], &[0x00, 0xFF, 0x01, 0x00]);
+        let (config, migrated) = decode(&payload).unwrap();
+        assert!(!migrated);
+        assert_provisioned(&config);
+    }
+
+    #[test]
+    fn migrate_unprovisioned_v0() {
+        // seq 0 without uuid, keys nor network, padded as read from storage.
+        let mut fixture = [0; 512];
+        fixture[..9].copy_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x7F, 0x00]);
+        let (config, migrated) = decode(&fixture).unwrap();
+        assert!(migrated);
+        assert_eq!(0, config.seq);
+        assert!(config.uuid().is_none());
+        assert!(config.network().is_none());
+        assert_eq!(
+            127,
+            config
+                .foundation_models()
+                .configuration_model()
+                .default_ttl()
+        );
+    }
+
+    /// A provisioned node with bindings, a publication and subscriptions, as
+    /// written to storage by the release preceding the versioned layout.
+    #[cfg(not(feature = "ble-mesh-relay"))]
+    const PROVISIONED_V0: &[u8; 512] = include_bytes!("fixtures/v0_provisioned.bin");
+    #[cfg(feature = "ble-mesh-relay")]
+    const PROVISIONED_V0: &[u8; 512] = include_bytes!("fixtures/v0_provisioned_relay.bin");
+
+    #[test]
+    fn migrate_fixture_v0() {
+        let (config, migrated) = decode(PROVISIONED_V0).unwrap();
+        assert!(migrated);
+        assert_eq!(200, config.seq);
+        assert_eq!([0x11; 16], config.uuid().unwrap().0);
+
+        let device_keys = config.device_keys();
+        assert!(device_keys.private_key().unwrap().is_some());
+        assert!(device_keys.shared_secret().unwrap().is_some());
+        assert_eq!([0x22; 16], *device_keys.device_key().unwrap().as_ref());
+
+        let network = config.network().as_ref().unwrap();
+        assert_eq!(0x1234, network.iv_index());
+        let primary = UnicastAddress::parse([0x00, 0x02]).unwrap();
+        let secondary = UnicastAddress::parse([0x00, 0x03]).unwrap();
+        assert_eq!(primary, *network.unicast_address());
+
+        let sensor = ModelIdentifier::SIG(0x1100);
+        let vendor = ModelIdentifier::Vendor(CompanyIdentifier(0x0003), 0x0001);
+        let details = network.find_by_net_key_index(&NetKeyIndex::new(0)).unwrap();
+        assert_eq!(0x44, details.nid);
+        assert_eq!(&details.app_key_indexes()[..], &[AppKeyIndex::new(1)]);
+
+        // bound models can be unbound, others not.
+        let mut bindings = details.clone();
+        assert!(bindings.unbind(&primary, &sensor).is_ok());
+        assert!(bindings.unbind(&secondary, &vendor).is_ok());
+        assert!(bindings.unbind(&primary, &vendor).is_err());
+
+        let publication = details.find_publication(&primary, &sensor).unwrap();
+        assert_eq!(
+            Address::Group(GroupAddress::parse([0xC0, 0x01]).unwrap()),
+            publication.publish_address
+        );
+        assert_eq!(AppKeyIndex::new(1), publication.app_key_index);
+        assert_eq!(Some(7), publication.publish_ttl);
+        assert_eq!(0x4A, publication.publish_period);
+        assert_eq!(2, publication.publish_retransmit_count);
+        assert_eq!(3, publication.publish_retransmit_interval_steps);
+        assert!(details.find_publication(&secondary, &vendor).is_none());
+
+        let subscriptions = network.subscriptions();
+        assert!(subscriptions.has_subscription(
+            &primary,
+            &SubscriptionAddress::Group(GroupAddress::parse([0xC0, 0x02]).unwrap()),
+            &sensor
+        ));
+        assert!(subscriptions.has_subscription(
+            &secondary,
+            &SubscriptionAddress::Group(GroupAddress::AllNodes),
+            &vendor
+        ));
+
+        let model = config.foundation_models().configuration_model();
+        assert!(model.secure_beacon());
+        assert_eq!(5, model.default_ttl());
+        assert_eq!(2, model.network_transmit().network_retransmit_count);
+        assert_eq!(
+            3,
+            model.network_transmit().network_retransmit_interval_steps
+        );
+        #[cfg(feature = "ble-mesh-relay")]
+        assert_eq!(4, model.relay().relay_retransmit_interval_steps);
+    }
+
+    /// A configuration with every capacity filled, holding the values taking
+    /// the most room to encode.
+    fn fully_populated() -> Configuration {
+        let label = LabelUuid::new([0xFF; 16]).unwrap();
+        let element = |i: u16| UnicastAddress(0x7FFF - i);
+        let model = |i: u16| ModelIdentifier::Vendor(CompanyIdentifier(0xFFFF), 0xFFFF - i);
+
+        let mut network = Network::new(
+            NetworkDetails::new(
+                NetworkKey::new([0xFF; 16]),
+                NetKeyIndex::new(0xFFFF),
+                0xFF,
+                [0xFF; 16],
+                [0xFF; 16],
+            ),
+            IVUpdateFlag::UpdateActive,
+            u32::MAX,
+            element(0),
+        );
+        for i in 1u16.. {
+            if network
+                .add_network_key(NetKeyIndex::new(0xFFFF - i), [0xFF; 16])
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        let mut app_key_index = 0xFFFF;
+        for net_key_index in network.net_key_indexes() {
+            let details = network.find_by_net_key_index_mut(&net_key_index).unwrap();
+            // in key refresh phase 1, holding the updated keys as well.
+            details.update_key([0xEE; 16]).unwrap();
+            while details
+                .add_app_key(AppKeyIndex::new(app_key_index), [0xFF; 16])
+                .is_ok()
+            {
+                details
+                    .update_app_key(&AppKeyIndex::new(app_key_index), [0xEE; 16])
+                    .unwrap();
+                app_key_index -= 1;
+            }
+            // bindings and publications use the last key added.
+            let app_key = AppKeyIndex::new(app_key_index + 1);
+            for i in 0u16.. {
+                if details.bind(&element(i), &model(i), &app_key).is_err() {
+                    break;
+                }
+            }
+            for i in 0u16.. {
+                if details
+                    .publications_mut()
+                    .set(
+                        element(i),
+                        Address::LabelUuid(label),
+                        app_key,
+                        true,
+                        Some(0xFF),
+                        0xFF,
+                        0xFF,
+                        0xFF,
+                        model(i),
+                    )
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+        for i in 0u16.. {
+            if network
+                .subscriptions_mut()
+                .add(element(i), SubscriptionAddress::Virtual(label), model(i))
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        let mut foundation_models = FoundationModels::default();
+        *foundation_models
+            .configuration_model_mut()
+            .heartbeat_publication_mut() = HeartbeatPublication {
+            destination: Address::LabelUuid(label),
+            count_log: 0xFF,
+            period_log: 0xFF,
+            ttl: 0xFF,
+            features: Features {
+                relay: true,
+                proxy: true,
+                friend: true,
+                low_power: true,
+            },
+            net_key_index: NetKeyIndex::new(0xFFFF),
+        };
+
+        Configuration {
+            seq: u32::MAX,
+            uuid: Some(Uuid([0xFF; 16])),
+            device_keys: DeviceKeys::restore(Some([0xFF; 32]), Some([0xFF; 32]), Some([0xFF; 16])),
+            network: Some(network),
+            foundation_models,
+        }
+    }
+
+    #[test]
+    fn fully_populated_configuration_fits_payload() {
+        let mut payload = [0; PAYLOAD_SIZE];
+        encode(&fully_populated(), &mut payload).unwrap();
+
+        let (config, migrated) = decode(&payload).unwrap();
+        assert!(!migrated);
+        let network = config.network().as_ref().unwrap();
+        assert_eq!(MAX_NETWORK_KEYS, network.net_key_indexes().len());
+        for details in network.iter() {
+            assert_eq!(MAX_APP_KEYS, details.app_key_indexes().len());
+        }
+    }
+
+    #[test]
+    fn layout_independent_of_features() {
+        let mut payload = [0; PAYLOAD_SIZE];
+        encode(&Configuration::default(), &mut payload).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            // header
+            0x00, 0xFF, 0x01, 0x00,
+            // seq, uuid, device keys and network
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // secure beacon, default ttl, publish period, network transmit
+            0x01, 0x7F, 0x00, 0x02, 0x0A,
+            // heartbeat publication
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // relay, friend and gatt proxy, whether enabled or not
+            0x01, 0x01, 0x14, 0x01, 0x01,
+        ];
+        assert_eq!(&expected[..], &payload[..expected.len()]);
+        assert!(payload[expected.len()..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let mut payload = [0; 512];
+        encode(&Configuration::default(), &mut payload).unwrap();
+        payload[2..4].copy_from_slice(&(CONFIGURATION_VERSION + 1).to_le_bytes());
+        assert!(decode(&payload).is_err());
+    }
+}
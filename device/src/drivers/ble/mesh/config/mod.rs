@@ -3,6 +3,8 @@ pub(crate) mod bindings;
 pub(crate) mod configuration_manager;
 pub(crate) mod device_keys;
 pub(crate) mod foundation_models;
+pub(crate) mod iv_index;
+pub(crate) mod migration;
 pub(crate) mod network;
 pub(crate) mod publications;
 pub(crate) mod subcriptions;
@@ -32,7 +34,7 @@ pub struct Configuration {
 }
 
 impl Configuration {
-    fn validate<R: CryptoRng + RngCore>(&mut self, rng: &mut R) -> bool {
+    fn validate<R: CryptoRng + RngCore>(&mut self, rng: &mut R, stores_key_pair: bool) -> bool {
         let mut changed = false;
 
         if self.uuid.is_none() {
@@ -46,7 +48,13 @@ impl Configuration {
             changed = true;
         }
 
-        if let Ok(None) = self.device_keys.private_key() {
+        if !stores_key_pair {
+            // the vault holds the key pair, so none is kept here.
+            if let Ok(Some(_)) = self.device_keys.private_key() {
+                let _ = self.device_keys_mut().set_private_key(&None);
+                changed = true;
+            }
+        } else if let Ok(None) = self.device_keys.private_key() {
             let secret_key = SecretKey::random(rng);
             let _ = self.device_keys_mut().set_private_key(&Some(secret_key));
             changed = true;
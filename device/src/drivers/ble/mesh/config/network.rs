@@ -1,28 +1,51 @@
 use crate::drivers::ble::mesh::address::UnicastAddress;
 use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+use crate::drivers::ble::mesh::beacon::{Flags, SecureNetworkBeacon};
 #[cfg(feature = "defmt")]
 use crate::drivers::ble::mesh::composition::Composition;
-use crate::drivers::ble::mesh::config::app_keys::AppKeyDetails;
+use crate::drivers::ble::mesh::config::app_keys::{AppKey, AppKeyDetails};
 use crate::drivers::ble::mesh::config::bindings::Bindings;
+use crate::drivers::ble::mesh::config::iv_index::IvIndexState;
 use crate::drivers::ble::mesh::config::publications::{Publication, Publications};
 use crate::drivers::ble::mesh::config::subcriptions::Subscriptions;
+use crate::drivers::ble::mesh::crypto;
 use crate::drivers::ble::mesh::crypto::k3;
 use crate::drivers::ble::mesh::driver::node::NetworkId;
 use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::key_refresh_phase::{
+    KeyRefreshPhase, KeyRefreshTransition,
+};
 use crate::drivers::ble::mesh::model::foundation::configuration::{AppKeyIndex, NetKeyIndex};
 use crate::drivers::ble::mesh::model::{ModelIdentifier, Status};
-use crate::drivers::ble::mesh::provisioning::IVUpdateFlag;
-use crate::drivers::ble::mesh::{crypto, InsufficientBuffer};
+use crate::drivers::ble::mesh::provisioning::{IVUpdateFlag, KeyRefreshFlag};
 use core::slice::Iter;
 use heapless::Vec;
 use serde::{Deserialize, Serialize};
 
+/// Number of subnets, and so network keys, a node can be a member of.
+///
+/// Together with [`MAX_APP_KEYS`], sized so that a fully populated
+/// configuration fits a single storage [payload](crate::drivers::ble::mesh::storage::PAYLOAD_SIZE).
+#[cfg(not(feature = "ble-mesh-extended-keys"))]
+pub const MAX_NETWORK_KEYS: usize = 2;
+#[cfg(feature = "ble-mesh-extended-keys")]
+pub const MAX_NETWORK_KEYS: usize = 3;
+
+/// Number of application keys that can be bound to each subnet.
+#[cfg(not(feature = "ble-mesh-extended-keys"))]
+pub const MAX_APP_KEYS: usize = 4;
+#[cfg(feature = "ble-mesh-extended-keys")]
+pub const MAX_APP_KEYS: usize = 6;
+
+/// Number of network keys a node may hold at once, as each subnet carries a
+/// second key while it undergoes key refresh.
+pub const MAX_NETWORK_KEY_MATERIAL: usize = 2 * MAX_NETWORK_KEYS;
+
 #[derive(Serialize, Deserialize, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Network {
     networks: Networks,
-    iv_update_flag: IVUpdateFlag,
-    iv_index: u32,
+    iv_index: IvIndexState,
     unicast_address: UnicastAddress,
     subscriptions: Subscriptions,
 }
@@ -36,8 +59,7 @@ impl Network {
     ) -> Self {
         Self {
             networks: Networks::new(primary_network_details),
-            iv_update_flag,
-            iv_index,
+            iv_index: IvIndexState::new(iv_index, iv_update_flag),
             unicast_address,
             subscriptions: Default::default(),
         }
@@ -84,11 +106,20 @@ impl Network {
         self.networks.find_by_app_key_index_mut(app_key_index)
     }
 
-    pub(crate) fn find_app_key_by_aid(
-        &self,
-        aid: &ApplicationKeyIdentifier,
-    ) -> Option<&AppKeyDetails> {
-        self.networks.find_app_key_by_aid(aid)
+    pub(crate) fn add_network_key(
+        &mut self,
+        net_key_index: NetKeyIndex,
+        network_key: [u8; 16],
+    ) -> Result<(), Status> {
+        self.networks.add(net_key_index, network_key)
+    }
+
+    pub(crate) fn delete_network_key(&mut self, net_key_index: &NetKeyIndex) -> Result<(), Status> {
+        self.networks.delete(net_key_index)
+    }
+
+    pub(crate) fn net_key_indexes(&self) -> Vec<NetKeyIndex, MAX_NETWORK_KEYS> {
+        self.networks.iter().map(|e| e.key_index).collect()
     }
 
     pub(crate) fn find_publication(
@@ -100,13 +131,18 @@ impl Network {
             .find_publication(element_address, model_identifier)
     }
 
-    pub(crate) fn find_by_nid(
-        &self,
-        nid: u8,
-    ) -> Result<Vec<NetworkDetails, 10>, InsufficientBuffer> {
+    pub(crate) fn find_by_nid(&self, nid: u8) -> Vec<NetworkKeyHandle, MAX_NETWORK_KEY_MATERIAL> {
         self.networks.find_by_nid(nid)
     }
 
+    /// The subnet a secure network beacon was authenticated by, if any.
+    pub(crate) fn authenticate_beacon(
+        &mut self,
+        beacon: &SecureNetworkBeacon,
+    ) -> Option<&NetworkDetails> {
+        self.networks.authenticate_beacon(beacon)
+    }
+
     pub(crate) fn iter(&self) -> Iter<'_, NetworkDetails> {
         self.networks.iter()
     }
@@ -114,7 +150,11 @@ impl Network {
     #[cfg(feature = "defmt")]
     pub(crate) fn display_configuration(&self, composition: &Composition) {
         info!("Primary unicast address: {}", self.unicast_address);
-        info!("IV index: {:x}", self.iv_index);
+        info!(
+            "IV index: {:x} [update in progress={}]",
+            self.iv_index.iv_index(),
+            self.iv_index.in_progress()
+        );
 
         self.networks.display_configuration();
 
@@ -133,7 +173,15 @@ impl Network {
     }
 
     pub fn iv_index(&self) -> u32 {
-        self.iv_index
+        self.iv_index.iv_index()
+    }
+
+    pub fn iv_index_state(&self) -> &IvIndexState {
+        &self.iv_index
+    }
+
+    pub(crate) fn iv_index_state_mut(&mut self) -> &mut IvIndexState {
+        &mut self.iv_index
     }
 
     pub fn unicast_address(&self) -> &UnicastAddress {
@@ -144,7 +192,7 @@ impl Network {
 #[derive(Serialize, Deserialize, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Networks {
-    networks: Vec<NetworkDetails, 1>,
+    networks: Vec<NetworkDetails, MAX_NETWORK_KEYS>,
 }
 
 impl Networks {
@@ -155,7 +203,8 @@ impl Networks {
     }
 
     pub(crate) fn network_id(&self) -> Result<NetworkId, DeviceError> {
-        Ok(NetworkId(k3(&self.networks[0].network_key.0)?))
+        let network_key = self.networks[0].transmit_key().network_key;
+        Ok(NetworkId(k3(&network_key.0)?))
     }
 
     #[cfg(feature = "defmt")]
@@ -187,17 +236,49 @@ impl Networks {
         }
     }
 
-    pub(crate) fn find_app_key_by_aid(
-        &self,
-        aid: &ApplicationKeyIdentifier,
-    ) -> Option<&AppKeyDetails> {
-        for network in &self.networks {
-            if let Some(app_key_details) = network.find_app_key_by_aid(aid) {
-                return Some(app_key_details);
+    fn add(&mut self, net_key_index: NetKeyIndex, network_key: [u8; 16]) -> Result<(), Status> {
+        if let Ok(existing) = self.find_by_index(&net_key_index) {
+            // adding the same key again is idempotent.
+            if existing.network_key.0 == network_key {
+                Ok(())
+            } else {
+                Err(Status::KeyIndexAlreadyStored)
             }
+        } else {
+            let (nid, encryption_key, privacy_key) =
+                crypto::k2(&network_key, &[0x00]).map_err(|_| Status::UnspecifiedError)?;
+            self.networks
+                .push(NetworkDetails::new(
+                    network_key.into(),
+                    net_key_index,
+                    nid,
+                    encryption_key,
+                    privacy_key,
+                ))
+                .map_err(|_| Status::InsufficientResources)
         }
+    }
 
-        None
+    fn delete(&mut self, net_key_index: &NetKeyIndex) -> Result<(), Status> {
+        if let Some(position) = self
+            .networks
+            .iter()
+            .position(|e| e.key_index == *net_key_index)
+        {
+            if self.networks.len() == 1 {
+                return Err(Status::CannotRemove);
+            }
+            // the app keys, bindings and publications of the subnet go with it.
+            self.networks.remove(position);
+        }
+        Ok(())
+    }
+
+    fn authenticate_beacon(&mut self, beacon: &SecureNetworkBeacon) -> Option<&NetworkDetails> {
+        self.networks
+            .iter_mut()
+            .find(|network| network.authenticate_beacon(beacon))
+            .map(|network| &*network)
     }
 
     pub(crate) fn find_publication(
@@ -218,20 +299,15 @@ impl Networks {
         self.networks.iter()
     }
 
-    pub(crate) fn find_by_nid(
-        &self,
-        nid: u8,
-    ) -> Result<Vec<NetworkDetails, 10>, InsufficientBuffer> {
-        let mut found = Vec::new();
-        for network in &self.networks {
-            if network.nid == nid {
-                // todo: remove this clone
-                found
-                    .push(network.clone())
-                    .map_err(|_| InsufficientBuffer)?
-            }
-        }
-        Ok(found)
+    /// The keys of every subnet whose NID matches, as NIDs are only 7 bits
+    /// and may collide across subnets, including both keys of a subnet
+    /// undergoing key refresh.
+    pub(crate) fn find_by_nid(&self, nid: u8) -> Vec<NetworkKeyHandle, MAX_NETWORK_KEY_MATERIAL> {
+        self.networks
+            .iter()
+            .flat_map(|e| e.keys())
+            .filter(|e| e.nid == nid)
+            .collect()
     }
 
     pub(crate) fn find_by_index(
@@ -319,9 +395,12 @@ pub struct NetworkDetails {
     pub(crate) nid: u8,
     pub(crate) encryption_key: [u8; 16],
     pub(crate) privacy_key: [u8; 16],
-    app_keys: Vec<AppKeyDetails, 1>,
+    app_keys: Vec<AppKeyDetails, MAX_APP_KEYS>,
     bindings: Bindings,
     publications: Publications,
+    key_refresh_phase: KeyRefreshPhase,
+    /// The replacement key distributed during key refresh.
+    updated_key: Option<NetworkKeyHandle>,
 }
 
 impl NetworkDetails {
@@ -341,9 +420,24 @@ impl NetworkDetails {
             app_keys: Default::default(),
             bindings: Default::default(),
             publications: Default::default(),
+            key_refresh_phase: Default::default(),
+            updated_key: None,
         }
     }
 
+    /// Carry over the keys, bindings and publications of a subnet decoded
+    /// from an older storage layout.
+    pub(super) fn restore(
+        &mut self,
+        app_keys: Vec<AppKeyDetails, MAX_APP_KEYS>,
+        bindings: Bindings,
+        publications: Publications,
+    ) {
+        self.app_keys = app_keys;
+        self.bindings = bindings;
+        self.publications = publications;
+    }
+
     pub fn matches_nid(&self, nid: u8) -> bool {
         self.nid == nid
     }
@@ -364,11 +458,199 @@ impl NetworkDetails {
         &mut self.publications
     }
 
-    pub(crate) fn find_app_key_by_aid(
+    pub fn key_index(&self) -> NetKeyIndex {
+        self.key_index
+    }
+
+    pub fn key_refresh_phase(&self) -> KeyRefreshPhase {
+        self.key_refresh_phase
+    }
+
+    /// A node provisioned during phase 2 of a key refresh is only given the
+    /// new key, which becomes its current key, and follows the rest of the
+    /// procedure from there.
+    pub(crate) fn set_key_refresh_flag(&mut self, key_refresh_flag: KeyRefreshFlag) {
+        if let KeyRefreshFlag::Phase2 = key_refresh_flag {
+            self.key_refresh_phase = KeyRefreshPhase::Phase2;
+        }
+    }
+
+    /// The Key Refresh flag advertised in secure network beacons of this subnet.
+    pub(crate) fn key_refresh_flag(&self) -> bool {
+        self.key_refresh_phase == KeyRefreshPhase::Phase2
+    }
+
+    /// Every key accepted on receipt: the current one, plus the new one
+    /// during key refresh.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = NetworkKeyHandle> {
+        core::iter::once(NetworkKeyHandle::from(self)).chain(self.updated_key)
+    }
+
+    /// The key used for transmitting, which is the new one once key refresh
+    /// reaches phase 2.
+    pub(crate) fn transmit_key(&self) -> NetworkKeyHandle {
+        match (self.key_refresh_phase, self.updated_key) {
+            (KeyRefreshPhase::Phase2, Some(updated_key)) => updated_key,
+            _ => NetworkKeyHandle::from(self),
+        }
+    }
+
+    /// Store the new key of a key refresh, entering phase 1.
+    pub(crate) fn update_key(&mut self, network_key: [u8; 16]) -> Result<(), Status> {
+        match (self.key_refresh_phase, &self.updated_key) {
+            (KeyRefreshPhase::Phase0, _) => {
+                let (nid, encryption_key, privacy_key) =
+                    crypto::k2(&network_key, &[0x00]).map_err(|_| Status::UnspecifiedError)?;
+                self.updated_key.replace(NetworkKeyHandle {
+                    network_key: network_key.into(),
+                    key_index: self.key_index,
+                    nid,
+                    encryption_key,
+                    privacy_key,
+                });
+                self.key_refresh_phase = KeyRefreshPhase::Phase1;
+                Ok(())
+            }
+            // receiving the same key again is idempotent.
+            (KeyRefreshPhase::Phase1, Some(updated_key))
+                if updated_key.network_key.0 == network_key =>
+            {
+                Ok(())
+            }
+            _ => Err(Status::CannotUpdate),
+        }
+    }
+
+    /// Apply a transition requested by a configuration client. Prohibited
+    /// transitions leave the phase unchanged.
+    pub(crate) fn key_refresh_transition(&mut self, transition: KeyRefreshTransition) {
+        match (self.key_refresh_phase, transition) {
+            (KeyRefreshPhase::Phase1, KeyRefreshTransition::UseNewKeys) => {
+                self.key_refresh_phase = KeyRefreshPhase::Phase2;
+            }
+            (
+                KeyRefreshPhase::Phase1 | KeyRefreshPhase::Phase2,
+                KeyRefreshTransition::RevokeOldKeys,
+            ) => {
+                self.revoke_old_keys();
+            }
+            _ => {}
+        }
+    }
+
+    /// Follow the key refresh flag of a secure network beacon authenticated
+    /// with the new key of this subnet.
+    pub(crate) fn key_refresh_beacon(&mut self, key_refresh: bool) {
+        match (self.key_refresh_phase, key_refresh) {
+            (KeyRefreshPhase::Phase1, true) => {
+                self.key_refresh_phase = KeyRefreshPhase::Phase2;
+            }
+            (KeyRefreshPhase::Phase1 | KeyRefreshPhase::Phase2, false) => {
+                self.revoke_old_keys();
+            }
+            _ => {}
+        }
+    }
+
+    /// The secure network beacon advertising this subnet.
+    pub(crate) fn secure_network_beacon(
         &self,
-        aid: &ApplicationKeyIdentifier,
-    ) -> Option<&AppKeyDetails> {
-        self.app_keys.iter().find(|e| e.aid == *aid)
+        iv_index_state: &IvIndexState,
+    ) -> Result<SecureNetworkBeacon, DeviceError> {
+        let flags = Flags {
+            key_refresh: self.key_refresh_flag(),
+            iv_update: iv_index_state.in_progress(),
+        };
+        Ok(SecureNetworkBeacon::new(
+            flags,
+            &self.transmit_key().network_key.0,
+            iv_index_state.iv_index(),
+        )?)
+    }
+
+    /// Whether a secure network beacon was secured with one of the keys of
+    /// this subnet, following its key refresh flag if it was the new key.
+    pub(crate) fn authenticate_beacon(&mut self, beacon: &SecureNetworkBeacon) -> bool {
+        if let Some(updated_key) = self.updated_key {
+            if beacon.authenticate(&updated_key.network_key.0) {
+                self.key_refresh_beacon(beacon.flags.key_refresh);
+                return true;
+            }
+        }
+        if beacon.authenticate(&self.network_key.0) {
+            // provisioned during a key refresh, the current key is the new one.
+            if self.updated_key.is_none() {
+                self.key_refresh_beacon(beacon.flags.key_refresh);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Phase 3: replace every old key of the subnet with its new one and
+    /// return to normal operation.
+    fn revoke_old_keys(&mut self) {
+        if let Some(updated_key) = self.updated_key.take() {
+            self.network_key = updated_key.network_key;
+            self.nid = updated_key.nid;
+            self.encryption_key = updated_key.encryption_key;
+            self.privacy_key = updated_key.privacy_key;
+        }
+        for app_key in self.app_keys.iter_mut() {
+            app_key.revoke_old_key();
+        }
+        self.key_refresh_phase = KeyRefreshPhase::Phase0;
+    }
+
+    /// Application keys of this subnet with the given AID, including new
+    /// keys distributed by a key refresh, along with their index. The AID is
+    /// only 6 bits, so more than one key may match.
+    pub(crate) fn find_app_keys_by_aid<'m>(
+        &'m self,
+        aid: &'m ApplicationKeyIdentifier,
+    ) -> impl Iterator<Item = (AppKeyIndex, &'m AppKey)> + 'm {
+        self.app_keys.iter().flat_map(move |e| {
+            let current = Some(&e.key).filter(|_| e.aid == *aid);
+            let updated = e
+                .updated
+                .as_ref()
+                .filter(|updated| updated.aid == *aid)
+                .map(|updated| &updated.key);
+            current
+                .into_iter()
+                .chain(updated)
+                .map(move |key| (e.index, key))
+        })
+    }
+
+    /// The key and AID to transmit with for the application key at
+    /// `app_key_index`. Once key refresh reaches phase 2, the new key is used.
+    pub(crate) fn transmit_app_key(
+        &self,
+        app_key_index: &AppKeyIndex,
+    ) -> Option<(ApplicationKeyIdentifier, &AppKey)> {
+        let app_key = self.find_app_key_by_index(app_key_index)?;
+        match (self.key_refresh_phase, &app_key.updated) {
+            (KeyRefreshPhase::Phase2, Some(updated)) => Some((updated.aid, &updated.key)),
+            _ => Some((app_key.aid, &app_key.key)),
+        }
+    }
+
+    /// Store the new application key of a key refresh, which is only
+    /// possible while the subnet is in phase 1.
+    pub(crate) fn update_app_key(
+        &mut self,
+        app_key_index: &AppKeyIndex,
+        app_key: [u8; 16],
+    ) -> Result<(), Status> {
+        if self.key_refresh_phase != KeyRefreshPhase::Phase1 {
+            return Err(Status::CannotUpdate);
+        }
+        self.app_keys
+            .iter_mut()
+            .find(|e| e.index == *app_key_index)
+            .ok_or(Status::InvalidAppKeyIndex)?
+            .update(app_key)
     }
 
     pub(crate) fn find_app_key_by_index(
@@ -378,10 +660,6 @@ impl NetworkDetails {
         self.app_keys.iter().find(|e| e.index == *app_key_index)
     }
 
-    pub(crate) fn app_keys_iter(&self) -> Iter<'_, AppKeyDetails> {
-        self.app_keys.iter()
-    }
-
     pub(crate) fn bind(
         &mut self,
         element_address: &UnicastAddress,
@@ -424,9 +702,15 @@ impl NetworkDetails {
     pub(crate) fn display_configuration(&self) {
         info!("Network Keys:");
         info!(
-            "  {}: {} [nid={}]",
-            self.key_index, self.network_key, self.nid
+            "  {}: {} [nid={}, key refresh={}]",
+            self.key_index, self.network_key, self.nid, self.key_refresh_phase
         );
+        if let Some(updated_key) = &self.updated_key {
+            info!(
+                "    updated: {} [nid={}]",
+                updated_key.network_key, updated_key.nid
+            );
+        }
         info!("Application Keys:");
         for app_key in &self.app_keys {
             app_key.display_configuration();
@@ -438,22 +722,30 @@ impl NetworkDetails {
         app_key_index: AppKeyIndex,
         app_key: [u8; 16],
     ) -> Result<(), Status> {
-        if let Some(_) = self.app_keys.iter().find(|e| e.index == app_key_index) {
-            Err(Status::KeyIndexAlreadyStored)
+        if let Some(existing) = self.app_keys.iter().find(|e| e.index == app_key_index) {
+            // adding the same key again is idempotent.
+            if *existing.key.as_ref() == app_key {
+                Ok(())
+            } else {
+                Err(Status::KeyIndexAlreadyStored)
+            }
         } else {
-            let aid = crypto::k4(&app_key).map_err(|_| Status::UnspecifiedError)?;
             self.app_keys
-                .push(AppKeyDetails {
-                    aid: aid.into(),
-                    key: app_key.into(),
-                    index: app_key_index,
-                })
+                .push(AppKeyDetails::new(app_key_index, app_key)?)
                 .map_err(|_| Status::InsufficientResources)?;
             Ok(())
         }
     }
 
-    fn app_key_indexes(&self) -> Vec<AppKeyIndex, 10> {
+    pub(crate) fn delete_app_key(&mut self, app_key_index: &AppKeyIndex) {
+        if let Some(position) = self.app_keys.iter().position(|e| e.index == *app_key_index) {
+            self.app_keys.remove(position);
+            self.bindings.unbind_app_key(app_key_index);
+            self.publications.remove_by_app_key(app_key_index);
+        }
+    }
+
+    pub(crate) fn app_key_indexes(&self) -> Vec<AppKeyIndex, MAX_APP_KEYS> {
         self.app_keys.iter().map(|e| e.index).collect()
     }
 
@@ -475,6 +767,33 @@ pub struct NetworkKeyHandle {
     pub(crate) privacy_key: [u8; 16],
 }
 
+impl NetworkKeyHandle {
+    /// Friendship security credentials, derived from this key for the
+    /// messages exchanged between a Low Power node and its Friend.
+    pub(crate) fn friendship_credentials(
+        &self,
+        lpn_address: UnicastAddress,
+        friend_address: UnicastAddress,
+        lpn_counter: u16,
+        friend_counter: u16,
+    ) -> Result<NetworkKeyHandle, DeviceError> {
+        let mut p = [0; 9];
+        p[0] = 0x01;
+        p[1..3].copy_from_slice(&lpn_address.as_bytes());
+        p[3..5].copy_from_slice(&friend_address.as_bytes());
+        p[5..7].copy_from_slice(&lpn_counter.to_be_bytes());
+        p[7..9].copy_from_slice(&friend_counter.to_be_bytes());
+        let (nid, encryption_key, privacy_key) = crypto::k2(&self.network_key.0, &p)?;
+        Ok(NetworkKeyHandle {
+            network_key: self.network_key,
+            key_index: self.key_index,
+            nid,
+            encryption_key,
+            privacy_key,
+        })
+    }
+}
+
 impl From<NetworkDetails> for NetworkKeyHandle {
     fn from(key: NetworkDetails) -> Self {
         Self {
@@ -498,3 +817,177 @@ impl From<&NetworkDetails> for NetworkKeyHandle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_refresh() {
+        let old_key = [0x01; 16];
+        let new_key = [0x02; 16];
+        let (nid, encryption_key, privacy_key) = crypto::k2(&old_key, &[0x00]).unwrap();
+        let mut details = NetworkDetails::new(
+            old_key.into(),
+            NetKeyIndex::new(0),
+            nid,
+            encryption_key,
+            privacy_key,
+        );
+        details
+            .add_app_key(AppKeyIndex::new(0), [0x03; 16])
+            .unwrap();
+
+        // app keys can only be updated once the network key is.
+        assert!(matches!(
+            details.update_app_key(&AppKeyIndex::new(0), [0x04; 16]),
+            Err(Status::CannotUpdate)
+        ));
+
+        details.update_key(new_key).unwrap();
+        details.update_key(new_key).unwrap();
+        assert!(matches!(
+            details.update_key([0x05; 16]),
+            Err(Status::CannotUpdate)
+        ));
+        details
+            .update_app_key(&AppKeyIndex::new(0), [0x04; 16])
+            .unwrap();
+        assert_eq!(details.key_refresh_phase(), KeyRefreshPhase::Phase1);
+        assert_eq!(details.keys().count(), 2);
+        assert_eq!(details.transmit_key().network_key.0, old_key);
+
+        details.key_refresh_transition(KeyRefreshTransition::UseNewKeys);
+        assert_eq!(details.key_refresh_phase(), KeyRefreshPhase::Phase2);
+        assert_eq!(details.transmit_key().network_key.0, new_key);
+        let (aid, app_key) = details.transmit_app_key(&AppKeyIndex::new(0)).unwrap();
+        assert_eq!(*app_key.as_ref(), [0x04; 16]);
+        assert_eq!(aid, details.app_keys[0].updated.as_ref().unwrap().aid);
+
+        details.key_refresh_transition(KeyRefreshTransition::RevokeOldKeys);
+        assert_eq!(details.key_refresh_phase(), KeyRefreshPhase::Phase0);
+        assert_eq!(details.keys().count(), 1);
+        assert_eq!(details.transmit_key().network_key.0, new_key);
+        assert_eq!(*details.app_keys[0].key.as_ref(), [0x04; 16]);
+    }
+
+    fn details() -> NetworkDetails {
+        let network_key = [0x01; 16];
+        let (nid, encryption_key, privacy_key) = crypto::k2(&network_key, &[0x00]).unwrap();
+        NetworkDetails::new(
+            network_key.into(),
+            NetKeyIndex::new(0),
+            nid,
+            encryption_key,
+            privacy_key,
+        )
+    }
+
+    #[test]
+    fn provisioned_during_key_refresh() {
+        let mut details = details();
+        details.set_key_refresh_flag(KeyRefreshFlag::Phase2);
+        assert_eq!(details.key_refresh_phase(), KeyRefreshPhase::Phase2);
+        assert_eq!(details.keys().count(), 1);
+        assert_eq!(details.transmit_key().network_key.0, [0x01; 16]);
+        assert!(details.key_refresh_flag());
+
+        // the rest of the network is still refreshing.
+        let beacon = |key_refresh| {
+            let flags = Flags {
+                key_refresh,
+                iv_update: false,
+            };
+            SecureNetworkBeacon::new(flags, &[0x01; 16], 0).unwrap()
+        };
+        assert!(details.authenticate_beacon(&beacon(true)));
+        assert_eq!(details.key_refresh_phase(), KeyRefreshPhase::Phase2);
+
+        // and is done.
+        assert!(details.authenticate_beacon(&beacon(false)));
+        assert_eq!(details.key_refresh_phase(), KeyRefreshPhase::Phase0);
+        assert_eq!(details.transmit_key().network_key.0, [0x01; 16]);
+
+        // so another refresh can start.
+        details.update_key([0x02; 16]).unwrap();
+        assert_eq!(details.key_refresh_phase(), KeyRefreshPhase::Phase1);
+    }
+
+    #[test]
+    fn provisioned_during_key_refresh_revoked_by_client() {
+        let mut details = details();
+        details.set_key_refresh_flag(KeyRefreshFlag::Phase2);
+        details.key_refresh_transition(KeyRefreshTransition::RevokeOldKeys);
+        assert_eq!(details.key_refresh_phase(), KeyRefreshPhase::Phase0);
+        assert_eq!(details.transmit_key().network_key.0, [0x01; 16]);
+        details.update_key([0x02; 16]).unwrap();
+    }
+
+    #[test]
+    fn app_key_add_update_delete() {
+        let mut details = details();
+        details
+            .add_app_key(AppKeyIndex::new(1), [0x03; 16])
+            .unwrap();
+        // adding the same key again is idempotent, but not another one.
+        details
+            .add_app_key(AppKeyIndex::new(1), [0x03; 16])
+            .unwrap();
+        assert!(matches!(
+            details.add_app_key(AppKeyIndex::new(1), [0x04; 16]),
+            Err(Status::KeyIndexAlreadyStored)
+        ));
+        assert!(matches!(
+            details.update_app_key(&AppKeyIndex::new(2), [0x04; 16]),
+            Err(Status::CannotUpdate)
+        ));
+
+        details.update_key([0x02; 16]).unwrap();
+        assert!(matches!(
+            details.update_app_key(&AppKeyIndex::new(2), [0x04; 16]),
+            Err(Status::InvalidAppKeyIndex)
+        ));
+        details
+            .update_app_key(&AppKeyIndex::new(1), [0x04; 16])
+            .unwrap();
+        let app_key = details.find_app_key_by_index(&AppKeyIndex::new(1)).unwrap();
+        assert_eq!(*app_key.key.as_ref(), [0x03; 16]);
+        assert_eq!(*app_key.updated.unwrap().key.as_ref(), [0x04; 16]);
+
+        details.delete_app_key(&AppKeyIndex::new(1));
+        assert!(details
+            .find_app_key_by_index(&AppKeyIndex::new(1))
+            .is_none());
+        assert!(details.app_key_indexes().is_empty());
+    }
+
+    #[test]
+    fn app_key_selected_by_index() {
+        // the AID is only 6 bits, so two of these keys share one.
+        let aid = |n: u8| ApplicationKeyIdentifier::from(crypto::k4(&[n; 16]).unwrap());
+        let (first, second) = (0..=u8::MAX)
+            .flat_map(|a| (0..a).map(move |b| (b, a)))
+            .find(|(a, b)| aid(*a) == aid(*b))
+            .unwrap();
+
+        let mut details = details();
+        details
+            .add_app_key(AppKeyIndex::new(1), [first; 16])
+            .unwrap();
+        details
+            .add_app_key(AppKeyIndex::new(2), [second; 16])
+            .unwrap();
+
+        let (_, app_key) = details.transmit_app_key(&AppKeyIndex::new(1)).unwrap();
+        assert_eq!(*app_key.as_ref(), [first; 16]);
+        let (_, app_key) = details.transmit_app_key(&AppKeyIndex::new(2)).unwrap();
+        assert_eq!(*app_key.as_ref(), [second; 16]);
+        assert!(details.transmit_app_key(&AppKeyIndex::new(3)).is_none());
+
+        let shared = aid(first);
+        let mut candidates = details.find_app_keys_by_aid(&shared);
+        assert_eq!(1, candidates.next().unwrap().0.value());
+        assert_eq!(2, candidates.next().unwrap().0.value());
+        assert!(candidates.next().is_none());
+    }
+}
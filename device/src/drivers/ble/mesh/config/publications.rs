@@ -1,6 +1,7 @@
 use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
 use crate::drivers::ble::mesh::model::foundation::configuration::AppKeyIndex;
 use crate::drivers::ble::mesh::model::{ModelIdentifier, Status};
+use embassy::time::Duration;
 use heapless::Vec;
 use serde::{Deserialize, Serialize};
 
@@ -84,6 +85,12 @@ impl Publications {
         }
         Ok(())
     }
+
+    /// Disable every publication using an application key that is being deleted.
+    pub(crate) fn remove_by_app_key(&mut self, app_key_index: &AppKeyIndex) {
+        self.publications
+            .retain(|e| e.app_key_index != *app_key_index);
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -99,3 +106,28 @@ pub struct Publication {
     pub(crate) publish_retransmit_interval_steps: u8,
     pub(crate) model_identifier: ModelIdentifier,
 }
+
+impl Publication {
+    /// The period of periodic publication, or `None` when disabled.
+    pub(crate) fn publish_period(&self) -> Option<Duration> {
+        publish_period_duration(self.publish_period)
+    }
+}
+
+/// Decode a publish period of number of steps and step resolution.
+pub(crate) fn publish_period_duration(publish_period: u8) -> Option<Duration> {
+    let steps = (publish_period & 0x3F) as u64;
+    let res = (publish_period & 0xC0) >> 6;
+
+    if steps == 0 {
+        return None;
+    }
+
+    match res {
+        0b00 => Some(Duration::from_millis(100 * steps)),
+        0b01 => Some(Duration::from_secs(steps)),
+        0b10 => Some(Duration::from_secs(10 * steps)),
+        0b11 => Some(Duration::from_secs(600 * steps)),
+        _ => None,
+    }
+}
@@ -102,6 +102,25 @@ impl Subscriptions {
         }
     }
 
+    /// Group and virtual addresses subscribed to by any model.
+    #[cfg(feature = "ble-mesh-lpn")]
+    pub(crate) fn addresses<const N: usize>(&self) -> Vec<Address, N> {
+        let mut addresses = Vec::new();
+        for subscription in &self.subscriptions {
+            let address = match subscription.subscription_address {
+                SubscriptionAddress::Unicast(_) => continue,
+                SubscriptionAddress::Group(addr) => Address::Group(addr),
+                SubscriptionAddress::Virtual(label_uuid) => {
+                    Address::Virtual(label_uuid.virtual_address())
+                }
+            };
+            if !addresses.contains(&address) {
+                addresses.push(address).ok();
+            }
+        }
+        addresses
+    }
+
     pub(crate) fn find_label_uuids_by_address(
         &self,
         addr: VirtualAddress,
@@ -155,6 +155,15 @@ pub fn k3(n: &[u8; 16]) -> Result<[u8; 8], InvalidKeyLength> {
     }
 }
 
+const ID128: [u8; 6] = [b'i', b'd', b'1', b'2', b'8', 0x01];
+
+/// The key authenticating secure network beacons of a subnet.
+pub fn beacon_key(n: &[u8; 16]) -> Result<[u8; 16], InvalidKeyLength> {
+    let salt = s1(b"nkbk")?;
+    let result = k1(n, &salt.into_bytes(), &ID128)?.into_bytes();
+    result.try_into().map_err(|_| InvalidKeyLength)
+}
+
 const ID6: [u8; 4] = [b'i', b'd', b'6', 0x01];
 
 pub fn k4(n: &[u8]) -> Result<u8, InvalidKeyLength> {
@@ -16,6 +16,12 @@ pub(crate) async fn dispatch<C: PrimaryElementContext>(
             let result = ctx
                 .update_configuration(|config| {
                     if let Some(network) = config.network_mut() {
+                        if let Ok(bound) = network.find_by_app_key_index(&add.indexes.app_key()) {
+                            // an app key index belongs to exactly one subnet.
+                            if bound.key_index() != add.indexes.net_key() {
+                                Err(Status::InvalidNetKeyIndex)?
+                            }
+                        }
                         if let Ok(network) =
                             network.find_by_net_key_index_mut(&add.indexes.net_key())
                         {
@@ -33,6 +39,7 @@ pub(crate) async fn dispatch<C: PrimaryElementContext>(
             let status = match result {
                 Ok(_) => Status::Success,
                 Err(DeviceError::Status(status)) => status,
+                Err(DeviceError::Storage) => Status::StorageFailure,
                 Err(all_others) => Err(all_others)?,
             };
 
@@ -47,10 +54,86 @@ pub(crate) async fn dispatch<C: PrimaryElementContext>(
             )?)
             .await?;
         }
+        AppKeyMessage::Delete(delete) => {
+            let result = ctx
+                .update_configuration(|config| {
+                    if let Some(network) = config.network_mut() {
+                        match network.find_by_app_key_index_mut(&delete.indexes.app_key()) {
+                            Ok(network) if network.key_index() == delete.indexes.net_key() => {
+                                network.delete_app_key(&delete.indexes.app_key());
+                                Ok(())
+                            }
+                            Ok(_) => Err(Status::InvalidBinding)?,
+                            // deleting a key that is not stored is not an error.
+                            Err(_) => Ok(()),
+                        }
+                    } else {
+                        Err(DeviceError::NotProvisioned)
+                    }
+                })
+                .await;
+
+            let status = match result {
+                Ok(_) => Status::Success,
+                Err(DeviceError::Status(status)) => status,
+                Err(DeviceError::Storage) => Status::StorageFailure,
+                Err(all_others) => Err(all_others)?,
+            };
+
+            let response = AppKeyStatusMessage {
+                status,
+                indexes: delete.indexes,
+            };
+
+            ctx.transmit(access.create_response(
+                ctx.address().ok_or(DeviceError::NotProvisioned)?,
+                AppKeyMessage::Status(response),
+            )?)
+            .await?;
+        }
+        AppKeyMessage::Update(update) => {
+            let result = ctx
+                .update_configuration(|config| {
+                    if let Some(network) = config.network_mut() {
+                        network.find_by_net_key_index(&update.indexes.net_key())?;
+                        match network.find_by_app_key_index_mut(&update.indexes.app_key()) {
+                            Ok(network) if network.key_index() == update.indexes.net_key() => {
+                                // only permitted while the subnet is in key refresh phase 1.
+                                network
+                                    .update_app_key(&update.indexes.app_key(), update.app_key)?;
+                                Ok(())
+                            }
+                            Ok(_) => Err(Status::InvalidBinding)?,
+                            Err(status) => Err(status)?,
+                        }
+                    } else {
+                        Err(DeviceError::NotProvisioned)
+                    }
+                })
+                .await;
+
+            let status = match result {
+                Ok(_) => Status::Success,
+                Err(DeviceError::Status(status)) => status,
+                Err(DeviceError::Storage) => Status::StorageFailure,
+                Err(all_others) => Err(all_others)?,
+            };
+
+            let response = AppKeyStatusMessage {
+                status,
+                indexes: update.indexes,
+            };
+
+            ctx.transmit(access.create_response(
+                ctx.address().ok_or(DeviceError::NotProvisioned)?,
+                AppKeyMessage::Status(response),
+            )?)
+            .await?;
+        }
         AppKeyMessage::Get(get) => {
             let result = if let Some(networks) = ctx.configuration().network() {
                 if let Ok(network) = networks.find_by_net_key_index(&get.net_key_index) {
-                    Ok(network.app_keys_iter().map(|e| e.index).collect())
+                    Ok(network.app_key_indexes())
                 } else {
                     Err(Status::InvalidNetKeyIndex)?
                 }
@@ -77,25 +160,6 @@ pub(crate) async fn dispatch<C: PrimaryElementContext>(
             )?)
             .await?;
         }
-        /*
-        AppKeyMessage::Delete(delete) => {
-            ctx.network_details(delete.net_key_index)
-                .delete_app_key(delete.app_key_index).await;
-        }
-        AppKeyMessage::Get(get) => {
-            ctx.network_details(get.net_key_index)
-                .get_app_key(get.app_key_index);
-        }
-        AppKeyMessage::List(list) => {
-            let keys = ctx.network_details(delete.net_key_index)
-                .app_keys(delete.app_key_index);
-
-        }
-        AppKeyMessage::Update(update) => {
-            let result = ctx.network_details(delete.net_key_index)
-                .update_app_key(update.app_key_index, update.app_key).await;
-        }
-         */
         _ => {
             // not applicable to server role
         }
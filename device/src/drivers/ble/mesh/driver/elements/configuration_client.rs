@@ -0,0 +1,548 @@
+use crate::drivers::ble::mesh::address::{GroupAddress, UnicastAddress};
+use crate::drivers::ble::mesh::composition::Composition;
+use crate::drivers::ble::mesh::config::device_keys::{DeviceKey, DeviceKeyHandle};
+use crate::drivers::ble::mesh::driver::elements::PrimaryElementContext;
+use crate::drivers::ble::mesh::driver::node::outbound::OutboundDeviceKeyMessage;
+use crate::drivers::ble::mesh::driver::provisioner::ProvisionedNode;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::app_key::{
+    AppKeyAddMessage, AppKeyMessage, AppKeyUpdateMessage,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::composition_data::CompositionDataMessage;
+use crate::drivers::ble::mesh::model::foundation::configuration::key_refresh_phase::{
+    KeyRefreshPhase, KeyRefreshPhaseMessage, KeyRefreshPhaseSetMessage, KeyRefreshTransition,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::model_app::{
+    ModelAppMessage, ModelAppPayload,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::model_publication::{
+    ModelPublicationMessage, ModelPublicationSetMessage, PublishAddress,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::model_subscription::{
+    ModelSubscriptionAddMessage, ModelSubscriptionMessage, SubscriptionAddress,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::net_key::{
+    NetKeyAddMessage, NetKeyDeleteMessage, NetKeyMessage, NetKeyUpdateMessage,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::{
+    AppKeyIndex, ConfigurationClient, ConfigurationMessage, NetKeyAppKeyIndexesPair, NetKeyIndex,
+};
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier, Status};
+use crate::drivers::ble::mesh::pdu::access::{AccessMessage, AccessPayload};
+use embassy::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy::channel::mpmc::{Channel, Receiver as ChannelReceiver, Sender as ChannelSender};
+use embassy::time::{Duration, Instant, Timer};
+use embassy::util::{select, Either};
+use heapless::Vec;
+
+/// How long to wait for a status before sending the request again.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_ATTEMPTS: u8 = 3;
+
+/// A node to be configured, identified by its primary element address and device key.
+#[derive(Copy, Clone)]
+pub struct RemoteNode {
+    pub address: UnicastAddress,
+    pub device_key: [u8; 16],
+}
+
+impl From<&ProvisionedNode> for RemoteNode {
+    fn from(node: &ProvisionedNode) -> Self {
+        Self {
+            address: node.unicast_address,
+            device_key: node.device_key,
+        }
+    }
+}
+
+pub(crate) struct ConfigurationResponse {
+    src: UnicastAddress,
+    message: ConfigurationMessage,
+}
+
+pub(crate) type ConfigurationResponseChannel =
+    Channel<ThreadModeRawMutex, ConfigurationResponse, 1>;
+
+/// Configures remote nodes through their configuration server, using their
+/// device keys. Requests are retried a few times and each waits for the
+/// matching status; only one request should be outstanding at a time.
+#[derive(Clone)]
+pub struct ConfigurationClientContext<'a> {
+    pub(crate) sender: ChannelSender<'a, ThreadModeRawMutex, OutboundDeviceKeyMessage, 1>,
+    pub(crate) responses: ChannelReceiver<'a, ThreadModeRawMutex, ConfigurationResponse, 1>,
+}
+
+impl<'a> ConfigurationClientContext<'a> {
+    pub async fn composition_data_get(
+        &self,
+        node: &RemoteNode,
+        page: u8,
+    ) -> Result<Composition, DeviceError> {
+        self.request(
+            node,
+            CompositionDataMessage::Get(page),
+            |response| match response {
+                ConfigurationMessage::CompositionData(CompositionDataMessage::Status(status))
+                    if status.page == page =>
+                {
+                    Some(Ok(status.data))
+                }
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    pub async fn net_key_add(
+        &self,
+        node: &RemoteNode,
+        net_key_index: NetKeyIndex,
+        net_key: [u8; 16],
+    ) -> Result<(), DeviceError> {
+        let request = NetKeyMessage::Add(NetKeyAddMessage {
+            net_key_index,
+            net_key,
+        });
+        self.request(node, request, |response| match response {
+            ConfigurationMessage::NetKey(NetKeyMessage::Status(status))
+                if status.net_key_index == net_key_index =>
+            {
+                Some(check(status.status))
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    pub async fn net_key_delete(
+        &self,
+        node: &RemoteNode,
+        net_key_index: NetKeyIndex,
+    ) -> Result<(), DeviceError> {
+        let request = NetKeyMessage::Delete(NetKeyDeleteMessage { net_key_index });
+        self.request(node, request, |response| match response {
+            ConfigurationMessage::NetKey(NetKeyMessage::Status(status))
+                if status.net_key_index == net_key_index =>
+            {
+                Some(check(status.status))
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    /// Distribute the new key of a subnet, starting its key refresh.
+    pub async fn net_key_update(
+        &self,
+        node: &RemoteNode,
+        net_key_index: NetKeyIndex,
+        net_key: [u8; 16],
+    ) -> Result<(), DeviceError> {
+        let request = NetKeyMessage::Update(NetKeyUpdateMessage {
+            net_key_index,
+            net_key,
+        });
+        self.request(node, request, |response| match response {
+            ConfigurationMessage::NetKey(NetKeyMessage::Status(status))
+                if status.net_key_index == net_key_index =>
+            {
+                Some(check(status.status))
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    pub async fn key_refresh_phase_get(
+        &self,
+        node: &RemoteNode,
+        net_key_index: NetKeyIndex,
+    ) -> Result<KeyRefreshPhase, DeviceError> {
+        let request = KeyRefreshPhaseMessage::Get(net_key_index);
+        self.request(node, request, |response| {
+            Self::key_refresh_phase_status(response, net_key_index)
+        })
+        .await
+    }
+
+    pub async fn key_refresh_phase_set(
+        &self,
+        node: &RemoteNode,
+        net_key_index: NetKeyIndex,
+        transition: KeyRefreshTransition,
+    ) -> Result<KeyRefreshPhase, DeviceError> {
+        let request = KeyRefreshPhaseMessage::Set(KeyRefreshPhaseSetMessage {
+            net_key_index,
+            transition,
+        });
+        self.request(node, request, |response| {
+            Self::key_refresh_phase_status(response, net_key_index)
+        })
+        .await
+    }
+
+    fn key_refresh_phase_status(
+        response: ConfigurationMessage,
+        net_key_index: NetKeyIndex,
+    ) -> Option<Result<KeyRefreshPhase, DeviceError>> {
+        match response {
+            ConfigurationMessage::KeyRefreshPhase(KeyRefreshPhaseMessage::Status(status))
+                if status.net_key_index == net_key_index =>
+            {
+                Some(check(status.status).map(|_| status.phase))
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn app_key_add(
+        &self,
+        node: &RemoteNode,
+        net_key_index: NetKeyIndex,
+        app_key_index: AppKeyIndex,
+        app_key: [u8; 16],
+    ) -> Result<(), DeviceError> {
+        let request = AppKeyMessage::Add(AppKeyAddMessage {
+            indexes: NetKeyAppKeyIndexesPair::new(net_key_index, app_key_index),
+            app_key,
+        });
+        self.request(node, request, |response| {
+            Self::app_key_status(response, net_key_index, app_key_index)
+        })
+        .await
+    }
+
+    /// Distribute the new key of an application key during key refresh of its subnet.
+    pub async fn app_key_update(
+        &self,
+        node: &RemoteNode,
+        net_key_index: NetKeyIndex,
+        app_key_index: AppKeyIndex,
+        app_key: [u8; 16],
+    ) -> Result<(), DeviceError> {
+        let request = AppKeyMessage::Update(AppKeyUpdateMessage {
+            indexes: NetKeyAppKeyIndexesPair::new(net_key_index, app_key_index),
+            app_key,
+        });
+        self.request(node, request, |response| {
+            Self::app_key_status(response, net_key_index, app_key_index)
+        })
+        .await
+    }
+
+    fn app_key_status(
+        response: ConfigurationMessage,
+        net_key_index: NetKeyIndex,
+        app_key_index: AppKeyIndex,
+    ) -> Option<Result<(), DeviceError>> {
+        match response {
+            ConfigurationMessage::AppKey(AppKeyMessage::Status(status))
+                if status.indexes.net_key() == net_key_index
+                    && status.indexes.app_key() == app_key_index =>
+            {
+                Some(check(status.status))
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn model_app_bind(
+        &self,
+        node: &RemoteNode,
+        element_address: UnicastAddress,
+        app_key_index: AppKeyIndex,
+        model_identifier: ModelIdentifier,
+    ) -> Result<(), DeviceError> {
+        let request = ModelAppMessage::Bind(ModelAppPayload {
+            element_address,
+            app_key_index,
+            model_identifier,
+        });
+        self.request(node, request, |response| match response {
+            ConfigurationMessage::ModelApp(ModelAppMessage::Status(status))
+                if status.payload.element_address == element_address
+                    && status.payload.app_key_index == app_key_index
+                    && status.payload.model_identifier == model_identifier =>
+            {
+                Some(check(status.status))
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    pub async fn model_publication_set(
+        &self,
+        node: &RemoteNode,
+        set: ModelPublicationSetMessage,
+    ) -> Result<(), DeviceError> {
+        let element_address = set.element_address;
+        let model_identifier = set.model_identifier;
+        let request = match set.publish_address {
+            PublishAddress::Virtual(_) => ModelPublicationMessage::VirtualAddressSet(set),
+            _ => ModelPublicationMessage::Set(set),
+        };
+        self.request(node, request, |response| match response {
+            ConfigurationMessage::ModelPublication(ModelPublicationMessage::Status(status))
+                if status.element_address == element_address
+                    && status.model_identifier == model_identifier =>
+            {
+                Some(check(status.status))
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    pub async fn model_subscription_add(
+        &self,
+        node: &RemoteNode,
+        element_address: UnicastAddress,
+        subscription_address: GroupAddress,
+        model_identifier: ModelIdentifier,
+    ) -> Result<(), DeviceError> {
+        let request = ModelSubscriptionMessage::Add(ModelSubscriptionAddMessage {
+            element_address,
+            subscription_address: SubscriptionAddress::Group(subscription_address),
+            model_identifier,
+        });
+        self.request(node, request, |response| match response {
+            ConfigurationMessage::ModelSubscription(ModelSubscriptionMessage::Status(status))
+                if status.element_address == element_address
+                    && status.model_identifier == model_identifier =>
+            {
+                Some(check(status.status))
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    /// Send `request` to `node`, and hand each status received from it to
+    /// `matches` until one is accepted or every attempt times out.
+    async fn request<M, T, F>(
+        &self,
+        node: &RemoteNode,
+        request: M,
+        matches: F,
+    ) -> Result<T, DeviceError>
+    where
+        M: Message,
+        F: Fn(ConfigurationMessage) -> Option<Result<T, DeviceError>>,
+    {
+        let mut parameters = Vec::new();
+        request.emit_parameters(&mut parameters)?;
+        let message = OutboundDeviceKeyMessage {
+            dst: node.address,
+            device_key: DeviceKey::new(node.device_key),
+            payload: AccessPayload {
+                opcode: request.opcode(),
+                parameters,
+            },
+        };
+
+        // discard any status that arrived after an earlier request gave up.
+        while self.responses.try_recv().is_ok() {}
+
+        for _ in 0..REQUEST_ATTEMPTS {
+            self.sender.send(message.clone()).await;
+            let deadline = Instant::now() + RESPONSE_TIMEOUT;
+            loop {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                match select(self.responses.recv(), Timer::after(deadline - now)).await {
+                    Either::First(response) => {
+                        if response.src == node.address {
+                            if let Some(result) = matches(response.message) {
+                                return result;
+                            }
+                        }
+                    }
+                    Either::Second(_) => break,
+                }
+            }
+            debug!("no response from {:x}", u16::from(node.address));
+        }
+        Err(DeviceError::Timeout)
+    }
+}
+
+fn check(status: Status) -> Result<(), DeviceError> {
+    match status {
+        Status::Success => Ok(()),
+        status => Err(DeviceError::Status(status)),
+    }
+}
+
+/// Hand a status message protected by a remote node's device key to the
+/// configuration client awaiting it.
+pub(crate) fn dispatch<C: PrimaryElementContext>(
+    ctx: &C,
+    access: &AccessMessage,
+) -> Result<(), DeviceError> {
+    if let DeviceKeyHandle::Remote(src) = access.device_key {
+        if let Some(message) =
+            ConfigurationClient::parse(access.payload.opcode, &access.payload.parameters)?
+        {
+            ctx.configuration_response(ConfigurationResponse { src, message });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::model::foundation::configuration::app_key::AppKeyStatusMessage;
+    use crate::drivers::ble::mesh::model::foundation::configuration::ConfigurationServer;
+    use futures::executor::block_on;
+    use futures::future::join;
+
+    // the channels may only be used from the thread embassy considers to be
+    // in thread mode, which on std is the one named main.
+    fn in_thread_mode(f: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .name("main".into())
+            .spawn(f)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    fn addr(value: u16) -> UnicastAddress {
+        UnicastAddress::parse(value.to_be_bytes()).unwrap()
+    }
+
+    fn node() -> RemoteNode {
+        RemoteNode {
+            address: addr(0x0002),
+            device_key: [0xDD; 16],
+        }
+    }
+
+    fn app_key_status(src: u16, status: Status) -> ConfigurationResponse {
+        other_app_key_status(src, 0, 1, status)
+    }
+
+    fn other_app_key_status(
+        src: u16,
+        net_key_index: u16,
+        app_key_index: u16,
+        status: Status,
+    ) -> ConfigurationResponse {
+        ConfigurationResponse {
+            src: addr(src),
+            message: ConfigurationMessage::AppKey(AppKeyMessage::Status(AppKeyStatusMessage {
+                status,
+                indexes: NetKeyAppKeyIndexesPair::new(
+                    NetKeyIndex::new(net_key_index),
+                    AppKeyIndex::new(app_key_index),
+                ),
+            })),
+        }
+    }
+
+    /// Add an application key to `node()`, with `stale` already waiting
+    /// before the request is sent, and `responses` answering the request.
+    fn app_key_add(
+        stale: Option<ConfigurationResponse>,
+        responses: std::vec::Vec<ConfigurationResponse>,
+    ) -> Result<(), DeviceError> {
+        let requests: Channel<ThreadModeRawMutex, OutboundDeviceKeyMessage, 1> = Channel::new();
+        let statuses = ConfigurationResponseChannel::new();
+        if let Some(stale) = stale {
+            assert!(statuses.try_send(stale).is_ok());
+        }
+        let client = ConfigurationClientContext {
+            sender: requests.sender(),
+            responses: statuses.receiver(),
+        };
+
+        let server = async {
+            let request = requests.recv().await;
+            assert_eq!(node().address, request.dst);
+            assert_eq!(&node().device_key, request.device_key.as_ref());
+            match ConfigurationServer::parse(request.payload.opcode, &request.payload.parameters) {
+                Ok(Some(ConfigurationMessage::AppKey(AppKeyMessage::Add(add)))) => {
+                    assert_eq!(0, add.indexes.net_key().value());
+                    assert_eq!(1, add.indexes.app_key().value());
+                    assert_eq!([0xAA; 16], add.app_key);
+                }
+                _ => panic!("not an appkey add"),
+            }
+            for response in responses {
+                statuses.send(response).await;
+            }
+        };
+        let request = client.app_key_add(
+            &node(),
+            NetKeyIndex::new(0),
+            AppKeyIndex::new(1),
+            [0xAA; 16],
+        );
+        block_on(join(request, server)).0
+    }
+
+    #[test]
+    fn request_succeeds() {
+        in_thread_mode(|| {
+            assert!(app_key_add(None, vec![app_key_status(0x0002, Status::Success)]).is_ok());
+        });
+    }
+
+    #[test]
+    fn request_fails_with_status() {
+        in_thread_mode(|| {
+            let result = app_key_add(
+                None,
+                vec![app_key_status(0x0002, Status::InvalidNetKeyIndex)],
+            );
+            assert!(matches!(
+                result,
+                Err(DeviceError::Status(Status::InvalidNetKeyIndex))
+            ));
+        });
+    }
+
+    #[test]
+    fn request_ignores_other_nodes() {
+        in_thread_mode(|| {
+            let result = app_key_add(
+                None,
+                vec![
+                    app_key_status(0x0003, Status::InvalidNetKeyIndex),
+                    app_key_status(0x0002, Status::Success),
+                ],
+            );
+            assert!(result.is_ok());
+        });
+    }
+
+    #[test]
+    fn request_ignores_other_keys() {
+        in_thread_mode(|| {
+            let result = app_key_add(
+                None,
+                vec![
+                    other_app_key_status(0x0002, 0, 2, Status::InvalidAppKeyIndex),
+                    other_app_key_status(0x0002, 1, 1, Status::InvalidNetKeyIndex),
+                    app_key_status(0x0002, Status::Success),
+                ],
+            );
+            assert!(result.is_ok());
+        });
+    }
+
+    #[test]
+    fn request_discards_stale_status() {
+        in_thread_mode(|| {
+            let result = app_key_add(
+                Some(app_key_status(0x0002, Status::KeyIndexAlreadyStored)),
+                vec![app_key_status(0x0002, Status::Success)],
+            );
+            assert!(result.is_ok());
+        });
+    }
+}
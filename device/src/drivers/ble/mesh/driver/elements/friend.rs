@@ -0,0 +1,44 @@
+use crate::drivers::ble::mesh::driver::elements::PrimaryElementContext;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::friend::FriendMessage;
+use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+
+pub(crate) async fn dispatch<C: PrimaryElementContext>(
+    ctx: &C,
+    access: &AccessMessage,
+    message: &FriendMessage,
+) -> Result<(), DeviceError> {
+    match message {
+        FriendMessage::Get => {
+            let val = ctx
+                .configuration()
+                .foundation_models()
+                .configuration_model()
+                .friend();
+            ctx.transmit(access.create_response(
+                ctx.address().ok_or(DeviceError::NotProvisioned)?,
+                FriendMessage::Status(val),
+            )?)
+            .await?;
+        }
+        FriendMessage::Set(val) => {
+            ctx.update_configuration(|config| {
+                *config
+                    .foundation_models_mut()
+                    .configuration_model_mut()
+                    .friend_mut() = *val;
+                Ok(())
+            })
+            .await?;
+            ctx.transmit(access.create_response(
+                ctx.address().ok_or(DeviceError::NotProvisioned)?,
+                FriendMessage::Status(*val),
+            )?)
+            .await?;
+        }
+        _ => {
+            // not applicable to server role
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use crate::drivers::ble::mesh::composition::ElementsHandler;
+use crate::drivers::ble::mesh::driver::elements::PrimaryElementContext;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::health::{HealthMessage, HealthServer};
+use crate::drivers::ble::mesh::model::Model;
+use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+
+/// Dispatch a message to the health server of the primary element,
+/// returning whether it was a health server message.
+pub(crate) async fn dispatch<'a, C: PrimaryElementContext, E: ElementsHandler<'a>>(
+    ctx: &C,
+    elements: &mut E,
+    access: &AccessMessage,
+) -> Result<bool, DeviceError> {
+    let message = if let Ok(Some(message)) =
+        HealthServer::parse(access.payload.opcode, &access.payload.parameters)
+    {
+        message
+    } else {
+        return Ok(false);
+    };
+
+    let company_id = ctx.composition().cid();
+    let response = match &message {
+        HealthMessage::FaultGet(id) if *id == company_id => Some(HealthMessage::FaultStatus(
+            ctx.health().fault_status(company_id),
+        )),
+        HealthMessage::FaultClear(id) if *id == company_id => {
+            let mut health = ctx.health();
+            health.clear_registered();
+            Some(HealthMessage::FaultStatus(health.fault_status(company_id)))
+        }
+        HealthMessage::FaultClearUnacknowledged(id) if *id == company_id => {
+            ctx.health().clear_registered();
+            None
+        }
+        HealthMessage::FaultTest(test) | HealthMessage::FaultTestUnacknowledged(test)
+            if test.company_id == company_id =>
+        {
+            if elements.fault_test(test.test_id) {
+                let mut health = ctx.health();
+                health.test_performed(test.test_id);
+                if let HealthMessage::FaultTest(_) = message {
+                    Some(HealthMessage::FaultStatus(health.fault_status(company_id)))
+                } else {
+                    None
+                }
+            } else {
+                // unsupported tests are ignored.
+                None
+            }
+        }
+        HealthMessage::PeriodGet => Some(HealthMessage::PeriodStatus(
+            ctx.health().fast_period_divisor(),
+        )),
+        HealthMessage::PeriodSet(divisor) => {
+            ctx.health().set_fast_period_divisor(*divisor);
+            Some(HealthMessage::PeriodStatus(*divisor))
+        }
+        HealthMessage::PeriodSetUnacknowledged(divisor) => {
+            ctx.health().set_fast_period_divisor(*divisor);
+            None
+        }
+        HealthMessage::AttentionGet => {
+            Some(HealthMessage::AttentionStatus(ctx.health().attention()))
+        }
+        HealthMessage::AttentionSet(seconds) => {
+            ctx.health().set_attention(*seconds);
+            elements.attention(*seconds);
+            Some(HealthMessage::AttentionStatus(*seconds))
+        }
+        HealthMessage::AttentionSetUnacknowledged(seconds) => {
+            ctx.health().set_attention(*seconds);
+            elements.attention(*seconds);
+            None
+        }
+        _ => {
+            // not applicable to server role, or for faults of another company.
+            None
+        }
+    };
+
+    if let Some(response) = response {
+        ctx.transmit(
+            access.create_response(ctx.address().ok_or(DeviceError::NotProvisioned)?, response)?,
+        )
+        .await?;
+    }
+    Ok(true)
+}
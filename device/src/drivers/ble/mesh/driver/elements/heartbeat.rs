@@ -0,0 +1,85 @@
+use crate::drivers::ble::mesh::driver::elements::PrimaryElementContext;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::heartbeat::{
+    HeartbeatPublicationMessage, HeartbeatSubscriptionMessage,
+};
+use crate::drivers::ble::mesh::model::Status;
+use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+
+pub(crate) async fn dispatch_publication<C: PrimaryElementContext>(
+    ctx: &C,
+    access: &AccessMessage,
+    message: &HeartbeatPublicationMessage,
+) -> Result<(), DeviceError> {
+    match message {
+        HeartbeatPublicationMessage::Get => {
+            let publication = *ctx
+                .configuration()
+                .foundation_models()
+                .configuration_model()
+                .heartbeat_publication();
+            let status = ctx.heartbeat().publication_status(&publication);
+            ctx.transmit(access.create_response(
+                ctx.address().ok_or(DeviceError::NotProvisioned)?,
+                HeartbeatPublicationMessage::Status(Status::Success, status),
+            )?)
+            .await?;
+        }
+        HeartbeatPublicationMessage::Set(publication) => {
+            let known = if let Some(network) = ctx.configuration().network() {
+                network
+                    .find_by_net_key_index(&publication.net_key_index)
+                    .is_ok()
+            } else {
+                return Err(DeviceError::NotProvisioned);
+            };
+            let status = if known {
+                ctx.update_configuration(|config| {
+                    *config
+                        .foundation_models_mut()
+                        .configuration_model_mut()
+                        .heartbeat_publication_mut() = *publication;
+                    Ok(())
+                })
+                .await?;
+                ctx.heartbeat().publication_set(publication);
+                Status::Success
+            } else {
+                Status::InvalidNetKeyIndex
+            };
+            ctx.transmit(access.create_response(
+                ctx.address().ok_or(DeviceError::NotProvisioned)?,
+                HeartbeatPublicationMessage::Status(status, *publication),
+            )?)
+            .await?;
+        }
+        _ => {
+            // not applicable to server role
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn dispatch_subscription<C: PrimaryElementContext>(
+    ctx: &C,
+    access: &AccessMessage,
+    message: &HeartbeatSubscriptionMessage,
+) -> Result<(), DeviceError> {
+    match message {
+        HeartbeatSubscriptionMessage::Get => {}
+        HeartbeatSubscriptionMessage::Set(set) => {
+            ctx.heartbeat().subscription_set(set);
+        }
+        _ => {
+            // not applicable to server role
+            return Ok(());
+        }
+    }
+    let status = ctx.heartbeat().subscription_status();
+    ctx.transmit(access.create_response(
+        ctx.address().ok_or(DeviceError::NotProvisioned)?,
+        HeartbeatSubscriptionMessage::Status(Status::Success, status),
+    )?)
+    .await?;
+    Ok(())
+}
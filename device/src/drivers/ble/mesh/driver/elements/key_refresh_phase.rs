@@ -0,0 +1,71 @@
+use crate::drivers::ble::mesh::driver::elements::PrimaryElementContext;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::key_refresh_phase::{
+    KeyRefreshPhase, KeyRefreshPhaseMessage, KeyRefreshPhaseStatusMessage,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::NetKeyIndex;
+use crate::drivers::ble::mesh::model::Status;
+use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+
+pub(crate) async fn dispatch<C: PrimaryElementContext>(
+    ctx: &C,
+    access: &AccessMessage,
+    message: &KeyRefreshPhaseMessage,
+) -> Result<(), DeviceError> {
+    match message {
+        KeyRefreshPhaseMessage::Get(net_key_index) => {
+            respond(ctx, access, *net_key_index).await?;
+        }
+        KeyRefreshPhaseMessage::Set(set) => {
+            let result = ctx
+                .update_configuration(|config| {
+                    if let Some(network) = config.network_mut() {
+                        network
+                            .find_by_net_key_index_mut(&set.net_key_index)?
+                            .key_refresh_transition(set.transition);
+                        Ok(())
+                    } else {
+                        Err(DeviceError::NotProvisioned)
+                    }
+                })
+                .await;
+            match result {
+                // an unknown index is reported by the status below.
+                Ok(_) | Err(DeviceError::Status(_)) => {}
+                Err(all_others) => Err(all_others)?,
+            }
+            respond(ctx, access, set.net_key_index).await?;
+        }
+        _ => {
+            // not applicable to server role
+        }
+    }
+    Ok(())
+}
+
+async fn respond<C: PrimaryElementContext>(
+    ctx: &C,
+    access: &AccessMessage,
+    net_key_index: NetKeyIndex,
+) -> Result<(), DeviceError> {
+    let (status, phase) = if let Some(network) = ctx.configuration().network() {
+        match network.find_by_net_key_index(&net_key_index) {
+            Ok(network) => (Status::Success, network.key_refresh_phase()),
+            Err(status) => (status, KeyRefreshPhase::Phase0),
+        }
+    } else {
+        Err(DeviceError::NotProvisioned)?
+    };
+
+    let response = KeyRefreshPhaseStatusMessage {
+        status,
+        net_key_index,
+        phase,
+    };
+
+    ctx.transmit(access.create_response(
+        ctx.address().ok_or(DeviceError::NotProvisioned)?,
+        KeyRefreshPhaseMessage::Status(response),
+    )?)
+    .await
+}
@@ -1,37 +1,67 @@
 mod app_key;
 mod beacon;
 mod composition_data;
+pub(crate) mod configuration_client;
 mod default_ttl;
+#[cfg(feature = "ble-mesh-friend")]
+mod friend;
+#[cfg(feature = "ble-mesh-proxy")]
+mod gatt_proxy;
+mod health;
+mod heartbeat;
+mod key_refresh_phase;
 mod model_app;
 mod model_publication;
 mod model_subscription;
+mod net_key;
 mod node_reset;
 #[cfg(feature = "ble-mesh-relay")]
 mod relay;
 
+pub use configuration_client::{ConfigurationClientContext, RemoteNode};
+
 use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
 use crate::drivers::ble::mesh::composition::{Composition, ElementsHandler};
+use crate::drivers::ble::mesh::config::device_keys::DeviceKeyHandle;
 use crate::drivers::ble::mesh::config::Configuration;
-use crate::drivers::ble::mesh::driver::node::outbound::OutboundPublishMessage;
+use crate::drivers::ble::mesh::driver::elements::configuration_client::ConfigurationResponse;
+use crate::drivers::ble::mesh::driver::node::health::{HealthFault, HealthState};
+use crate::drivers::ble::mesh::driver::node::heartbeat::HeartbeatState;
+use crate::drivers::ble::mesh::driver::node::outbound::{
+    OutboundDeviceKeyMessage, OutboundPublishMessage,
+};
+use crate::drivers::ble::mesh::driver::node::sar::{
+    MessageId, MessageIds, TransmissionFailure, MAX_OUTBOUND_IN_FLIGHT,
+};
 use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::model_subscription::SubscriptionAddress;
 use crate::drivers::ble::mesh::model::foundation::configuration::{
     ConfigurationMessage, ConfigurationServer,
 };
+use crate::drivers::ble::mesh::model::foundation::health::HEALTH_SERVER;
 use crate::drivers::ble::mesh::model::Message;
 use crate::drivers::ble::mesh::model::Model;
 use crate::drivers::ble::mesh::pdu::access::{AccessMessage, AccessPayload};
-use core::cell::Ref;
+use core::cell::{Ref, RefMut};
 use core::convert::TryInto;
 use core::future::Future;
 use core::marker::PhantomData;
 use embassy::blocking_mutex::raw::ThreadModeRawMutex;
-use embassy::channel::mpmc::Sender as ChannelSender;
+use embassy::channel::mpmc::{Receiver as ChannelReceiver, Sender as ChannelSender};
 use heapless::Vec;
 
 #[derive(Clone)]
 pub struct AppElementsContext<'a> {
     pub(crate) sender: ChannelSender<'a, ThreadModeRawMutex, OutboundPublishMessage, 1>,
     pub(crate) access_sender: ChannelSender<'a, ThreadModeRawMutex, AccessMessage, 1>,
+    pub(crate) device_key_sender:
+        ChannelSender<'a, ThreadModeRawMutex, OutboundDeviceKeyMessage, 1>,
+    pub(crate) health_sender: ChannelSender<'a, ThreadModeRawMutex, HealthFault, 1>,
+    pub(crate) configuration_responses:
+        ChannelReceiver<'a, ThreadModeRawMutex, ConfigurationResponse, 1>,
+    pub(crate) transmission_failures:
+        ChannelReceiver<'a, ThreadModeRawMutex, TransmissionFailure, MAX_OUTBOUND_IN_FLIGHT>,
+    pub(crate) message_ids: &'a MessageIds,
     pub(crate) address: UnicastAddress,
 }
 
@@ -39,6 +69,7 @@ impl<'a> AppElementsContext<'a> {
     pub fn for_element_model<M: Model>(&self, element_number: u8) -> AppElementContext<'a, M> {
         AppElementContext {
             sender: self.sender.clone(),
+            message_ids: self.message_ids,
             address: self.address + element_number,
             _message: PhantomData,
         }
@@ -48,6 +79,34 @@ impl<'a> AppElementsContext<'a> {
         self.address
     }
 
+    /// A configuration client for configuring other nodes.
+    pub fn configuration_client(&self) -> ConfigurationClientContext<'a> {
+        ConfigurationClientContext {
+            sender: self.device_key_sender.clone(),
+            responses: self.configuration_responses.clone(),
+        }
+    }
+
+    /// Wait for a segmented message sent by any of the elements to fail,
+    /// either unacknowledged after all retransmissions or cancelled by its
+    /// destination. A published message is told apart by the [`MessageId`]
+    /// returned when publishing it.
+    pub async fn transmission_failure(&self) -> TransmissionFailure {
+        self.transmission_failures.recv().await
+    }
+
+    /// Report a fault condition to the health server, which registers it
+    /// and publishes the current faults.
+    pub async fn raise_fault(&self, fault: u8) {
+        self.health_sender.send(HealthFault::Raise(fault)).await;
+    }
+
+    /// Report a fault condition as no longer present. It remains registered
+    /// until cleared by a health client.
+    pub async fn resolve_fault(&self, fault: u8) {
+        self.health_sender.send(HealthFault::Resolve(fault)).await;
+    }
+
     pub async fn respond<M: Message>(
         &self,
         access: &AccessMessage,
@@ -62,6 +121,7 @@ impl<'a> AppElementsContext<'a> {
 #[derive(Clone)]
 pub struct AppElementContext<'a, M: Model> {
     sender: ChannelSender<'a, ThreadModeRawMutex, OutboundPublishMessage, 1>,
+    message_ids: &'a MessageIds,
     address: UnicastAddress,
     _message: PhantomData<M>,
 }
@@ -72,10 +132,14 @@ impl<'a, M: Model> AppElementContext<'a, M> {
         Ok(())
     }
 
-    pub async fn publish<'m>(&self, message: M::Message<'m>) -> Result<(), DeviceError> {
+    /// Publish a message, returning the identifier carried by its
+    /// [`TransmissionFailure`] should it not be delivered.
+    pub async fn publish<'m>(&self, message: M::Message<'m>) -> Result<MessageId, DeviceError> {
         let mut parameters = Vec::new();
         message.emit_parameters(&mut parameters)?;
+        let id = self.message_ids.next();
         let publish = OutboundPublishMessage {
+            id: Some(id),
             element_address: self.address,
             model_identifier: M::IDENTIFIER,
             payload: AccessPayload {
@@ -83,7 +147,8 @@ impl<'a, M: Model> AppElementContext<'a, M> {
                 parameters,
             },
         };
-        self.transmit(publish).await
+        self.transmit(publish).await?;
+        Ok(id)
     }
 
     pub fn address(&self) -> UnicastAddress {
@@ -123,6 +188,12 @@ pub trait PrimaryElementContext: ElementContext {
     ) -> Self::UpdateConfigurationFuture<'_, F>;
 
     fn is_local(&self, addr: &UnicastAddress) -> bool;
+
+    fn configuration_response(&self, response: ConfigurationResponse);
+
+    fn heartbeat(&self) -> RefMut<'_, HeartbeatState>;
+
+    fn health(&self) -> RefMut<'_, HealthState>;
 }
 
 pub struct Elements<'a, E: ElementsHandler<'a>> {
@@ -146,6 +217,13 @@ impl<'a, E: ElementsHandler<'a>> Elements<'a, E> {
         message: &AccessMessage,
     ) -> Result<(), DeviceError> {
         info!("d>");
+        if let DeviceKeyHandle::Remote(_) = message.device_key {
+            // only status messages for our configuration client are protected
+            // by another node's device key.
+            self::configuration_client::dispatch(ctx, message)?;
+            info!("d<");
+            return Ok(());
+        }
         let composition = self.elements.composition().clone();
         let unicast_element_index = match &message.dst {
             Address::Unicast(addr) => {
@@ -175,6 +253,10 @@ impl<'a, E: ElementsHandler<'a>> Elements<'a, E> {
                     );
                     return Ok(());
                 }
+                if self::health::dispatch(ctx, &mut self.elements, message).await? {
+                    info!("d<");
+                    return Ok(());
+                }
             }
             let element = &composition.elements[element_index as usize];
             for model in &element.models {
@@ -188,19 +270,27 @@ impl<'a, E: ElementsHandler<'a>> Elements<'a, E> {
 
         if let Some(network) = ctx.configuration().network() {
             let primary_addr = ctx.address().ok_or(DeviceError::NotProvisioned)?;
+            let dst: SubscriptionAddress = message
+                .dst
+                .try_into()
+                .map_err(|_| DeviceError::InvalidDstAddress)?;
+            if network
+                .subscriptions()
+                .has_subscription(&primary_addr, &dst, &HEALTH_SERVER)
+                && self::health::dispatch(ctx, &mut self.elements, message).await?
+            {
+                info!("d<");
+                return Ok(());
+            }
             // non-unicast, give everyone a chance to respond
             for (element_index, element) in composition.elements.iter().enumerate() {
                 // non-unicast, check every element.
                 let element_address = primary_addr + element_index as u8;
                 for model in &element.models {
-                    if network.subscriptions().has_subscription(
-                        &element_address,
-                        &message
-                            .dst
-                            .try_into()
-                            .map_err(|_| DeviceError::InvalidDstAddress)?,
-                        model,
-                    ) {
+                    if network
+                        .subscriptions()
+                        .has_subscription(&element_address, &dst, model)
+                    {
                         self.elements
                             .dispatch(element_index as u8, model, message)
                             .await?;
@@ -216,6 +306,10 @@ impl<'a, E: ElementsHandler<'a>> Elements<'a, E> {
     pub(crate) fn connect(&mut self, ctx: AppElementsContext<'a>) {
         self.elements.connect(ctx);
     }
+
+    pub(crate) fn attention(&mut self, seconds: u8) {
+        self.elements.attention(seconds);
+    }
 }
 
 pub struct ElementZero {}
@@ -248,9 +342,15 @@ impl ElementZero {
                 ConfigurationMessage::CompositionData(message) => {
                     self::composition_data::dispatch(ctx, access, message).await?;
                 }
+                ConfigurationMessage::NetKey(message) => {
+                    self::net_key::dispatch(ctx, access, message).await?;
+                }
                 ConfigurationMessage::AppKey(message) => {
                     self::app_key::dispatch(ctx, access, message).await?;
                 }
+                ConfigurationMessage::KeyRefreshPhase(message) => {
+                    self::key_refresh_phase::dispatch(ctx, access, message).await?;
+                }
                 ConfigurationMessage::ModelApp(message) => {
                     self::model_app::dispatch(ctx, access, message).await?;
                 }
@@ -260,10 +360,24 @@ impl ElementZero {
                 ConfigurationMessage::ModelSubscription(message) => {
                     self::model_subscription::dispatch(ctx, access, message).await?;
                 }
+                ConfigurationMessage::HeartbeatPublication(message) => {
+                    self::heartbeat::dispatch_publication(ctx, access, message).await?;
+                }
+                ConfigurationMessage::HeartbeatSubscription(message) => {
+                    self::heartbeat::dispatch_subscription(ctx, access, message).await?;
+                }
                 #[cfg(feature = "ble-mesh-relay")]
                 ConfigurationMessage::Relay(message) => {
                     self::relay::dispatch(ctx, access, message).await?;
                 }
+                #[cfg(feature = "ble-mesh-friend")]
+                ConfigurationMessage::Friend(message) => {
+                    self::friend::dispatch(ctx, access, message).await?;
+                }
+                #[cfg(feature = "ble-mesh-proxy")]
+                ConfigurationMessage::GattProxy(message) => {
+                    self::gatt_proxy::dispatch(ctx, access, message).await?;
+                }
             }
             Ok(true)
         } else {
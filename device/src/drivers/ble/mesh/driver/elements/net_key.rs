@@ -0,0 +1,105 @@
+use crate::drivers::ble::mesh::driver::elements::PrimaryElementContext;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::net_key::{
+    NetKeyListMessage, NetKeyMessage, NetKeyStatusMessage,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::NetKeyIndex;
+use crate::drivers::ble::mesh::model::Status;
+use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+
+pub(crate) async fn dispatch<C: PrimaryElementContext>(
+    ctx: &C,
+    access: &AccessMessage,
+    message: &NetKeyMessage,
+) -> Result<(), DeviceError> {
+    match message {
+        NetKeyMessage::Add(add) => {
+            let result = ctx
+                .update_configuration(|config| {
+                    if let Some(network) = config.network_mut() {
+                        network.add_network_key(add.net_key_index, add.net_key)?;
+                        Ok(())
+                    } else {
+                        Err(DeviceError::NotProvisioned)
+                    }
+                })
+                .await;
+            respond(ctx, access, result, add.net_key_index).await?;
+        }
+        NetKeyMessage::Delete(delete) => {
+            let result = if delete.net_key_index == access.network_key.key_index {
+                // removing the key this request arrived on would strand the configurer.
+                Err(Status::CannotRemove.into())
+            } else {
+                ctx.update_configuration(|config| {
+                    if let Some(network) = config.network_mut() {
+                        network.delete_network_key(&delete.net_key_index)?;
+                        Ok(())
+                    } else {
+                        Err(DeviceError::NotProvisioned)
+                    }
+                })
+                .await
+            };
+            respond(ctx, access, result, delete.net_key_index).await?;
+        }
+        NetKeyMessage::Get => {
+            let net_key_indexes = if let Some(network) = ctx.configuration().network() {
+                network.net_key_indexes()
+            } else {
+                Err(DeviceError::NotProvisioned)?
+            };
+
+            ctx.transmit(access.create_response(
+                ctx.address().ok_or(DeviceError::NotProvisioned)?,
+                NetKeyMessage::List(NetKeyListMessage { net_key_indexes }),
+            )?)
+            .await?;
+        }
+        NetKeyMessage::Update(update) => {
+            // starts key refresh of the subnet, entering phase 1.
+            let result = ctx
+                .update_configuration(|config| {
+                    if let Some(network) = config.network_mut() {
+                        network
+                            .find_by_net_key_index_mut(&update.net_key_index)?
+                            .update_key(update.net_key)?;
+                        Ok(())
+                    } else {
+                        Err(DeviceError::NotProvisioned)
+                    }
+                })
+                .await;
+            respond(ctx, access, result, update.net_key_index).await?;
+        }
+        _ => {
+            // not applicable to server role
+        }
+    }
+    Ok(())
+}
+
+async fn respond<C: PrimaryElementContext>(
+    ctx: &C,
+    access: &AccessMessage,
+    result: Result<(), DeviceError>,
+    net_key_index: NetKeyIndex,
+) -> Result<(), DeviceError> {
+    let status = match result {
+        Ok(_) => Status::Success,
+        Err(DeviceError::Status(status)) => status,
+        Err(DeviceError::Storage) => Status::StorageFailure,
+        Err(all_others) => Err(all_others)?,
+    };
+
+    let response = NetKeyStatusMessage {
+        status,
+        net_key_index,
+    };
+
+    ctx.transmit(access.create_response(
+        ctx.address().ok_or(DeviceError::NotProvisioned)?,
+        NetKeyMessage::Status(response),
+    )?)
+    .await
+}
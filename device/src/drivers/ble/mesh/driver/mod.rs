@@ -1,6 +1,7 @@
-use crate::drivers::ble::mesh::interface::NetworkError;
+use crate::drivers::ble::mesh::interface::{BearerError, NetworkError};
 use crate::drivers::ble::mesh::model::Status;
 use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::provisioning::ErrorCode;
 use crate::drivers::ble::mesh::InsufficientBuffer;
 use cmac::crypto_mac::InvalidKeyLength;
 use postcard::Error;
@@ -8,6 +9,7 @@ use postcard::Error;
 pub mod elements;
 pub mod node;
 mod pipeline;
+pub mod provisioner;
 
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -35,6 +37,8 @@ pub enum DeviceError {
     NotProvisioned,
     Status(Status),
     Network(NetworkError),
+    ProvisioningFailed(ErrorCode),
+    Timeout,
 }
 
 impl From<NetworkError> for DeviceError {
@@ -43,6 +47,12 @@ impl From<NetworkError> for DeviceError {
     }
 }
 
+impl From<BearerError> for DeviceError {
+    fn from(err: BearerError) -> Self {
+        Self::Network(err.into())
+    }
+}
+
 impl From<Status> for DeviceError {
     fn from(status: Status) -> Self {
         Self::Status(status)
@@ -1,18 +1,33 @@
 use crate::drivers::ble::mesh::address::{Address, LabelUuid, UnicastAddress};
 use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+#[cfg(feature = "ble-mesh-friend")]
+use crate::drivers::ble::mesh::beacon::Flags;
 use crate::drivers::ble::mesh::composition::{Composition, ElementsHandler};
-use crate::drivers::ble::mesh::config::network::NetworkDetails;
+use crate::drivers::ble::mesh::config::network::{NetworkKeyHandle, MAX_NETWORK_KEY_MATERIAL};
 use crate::drivers::ble::mesh::config::Configuration;
 use crate::drivers::ble::mesh::crypto;
 use crate::drivers::ble::mesh::crypto::nonce::{ApplicationNonce, DeviceNonce};
 use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::driver::elements::configuration_client::ConfigurationResponse;
 use crate::drivers::ble::mesh::driver::elements::{ElementContext, PrimaryElementContext};
+use crate::drivers::ble::mesh::driver::node::health::HealthState;
+use crate::drivers::ble::mesh::driver::node::heartbeat::HeartbeatState;
+use crate::drivers::ble::mesh::driver::node::oob::OobHandler;
 use crate::drivers::ble::mesh::driver::node::outbound::OutboundPublishMessage;
+use crate::drivers::ble::mesh::driver::node::sar::{SarConfig, TransmissionFailure};
 use crate::drivers::ble::mesh::driver::node::Node;
 use crate::drivers::ble::mesh::driver::pipeline::mesh::{MeshContext, NetworkRetransmitDetails};
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::access::AccessContext;
+#[cfg(feature = "ble-mesh-friend")]
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::friend::FriendContext;
+#[cfg(feature = "ble-mesh-lpn")]
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::low_power::{
+    Listen, LowPowerContext, LPN_SUBSCRIPTION_LIST_SIZE,
+};
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::LowerContext;
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::authentication::AuthenticationContext;
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::proxy::ProxyContext;
 #[cfg(feature = "ble-mesh-relay")]
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::relay::RelayContext;
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::NetworkContext;
@@ -22,17 +37,23 @@ use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::U
 use crate::drivers::ble::mesh::driver::pipeline::PipelineContext;
 use crate::drivers::ble::mesh::driver::DeviceError;
 use crate::drivers::ble::mesh::interface::{NetworkInterfaces, PDU};
+#[cfg(feature = "ble-mesh-friend")]
+use crate::drivers::ble::mesh::model::foundation::configuration::friend::Friend;
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::model::foundation::configuration::gatt_proxy::GattProxy;
 #[cfg(feature = "ble-mesh-relay")]
 use crate::drivers::ble::mesh::model::foundation::configuration::relay::Relay;
+use crate::drivers::ble::mesh::model::foundation::configuration::{AppKeyIndex, NetKeyIndex};
 use crate::drivers::ble::mesh::pdu::access::AccessMessage;
 use crate::drivers::ble::mesh::provisioning::ProvisioningData;
 use crate::drivers::ble::mesh::storage::Storage;
-use crate::drivers::ble::mesh::vault::Vault;
+use crate::drivers::ble::mesh::vault::{Vault, VaultFactory};
 use aes::Aes128;
 use cmac::crypto_mac::Output;
 use cmac::Cmac;
-use core::cell::Ref;
+use core::cell::{Ref, RefMut};
 use core::future::Future;
+use embassy::channel::mpmc::TrySendError;
 use embassy::time::Instant;
 use heapless::Vec;
 use p256::PublicKey;
@@ -42,17 +63,24 @@ use rand_core::{CryptoRng, RngCore};
 // Unprovisioned pipeline context
 // ------------------------------------------------------------------------
 
-impl<'a, E, N, S, R> UnprovisionedContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> UnprovisionedContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     S: Storage + 'a,
     R: RngCore + CryptoRng + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     fn rng_fill(&self, dest: &mut [u8]) {
         self.rng.borrow_mut().fill_bytes(dest);
     }
 
+    fn attention(&self, seconds: u8) {
+        self.health.borrow_mut().set_attention(seconds);
+        self.elements.borrow_mut().attention(seconds);
+    }
+
     type SetPeerPublicKeyFuture<'m> = impl Future<Output = Result<(), DeviceError>>
     where
         Self: 'm;
@@ -61,8 +89,16 @@ where
         async move { self.vault().set_peer_public_key(pk).await }
     }
 
-    fn public_key(&self) -> Result<PublicKey, DeviceError> {
-        self.vault().public_key()
+    type PublicKeyFuture<'m> = impl Future<Output = Result<PublicKey, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn public_key<'m>(&'m self) -> Self::PublicKeyFuture<'m> {
+        async move { self.vault().public_key().await }
+    }
+
+    fn oob_public_key(&self) -> bool {
+        self.vault().oob_public_key()
     }
 
     type SetProvisioningDataFuture<'m> = impl Future<Output = Result<(), DeviceError>>
@@ -90,16 +126,12 @@ where
         crypto::s1(input).map_err(|_| DeviceError::InvalidKeyLength)
     }
 
-    fn prsk(&self, salt: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError> {
-        self.vault().prsk(salt)
-    }
-
-    fn prsn(&self, salt: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError> {
-        self.vault().prsn(salt)
-    }
+    type KeyFuture<'m> = impl Future<Output = Result<Output<Cmac<Aes128>>, DeviceError>> + 'm
+    where
+        Self: 'm;
 
-    fn prck(&self, salt: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError> {
-        self.vault().prck(salt)
+    fn n_k1<'m>(&'m self, salt: &'m [u8], p: &'m [u8]) -> Self::KeyFuture<'m> {
+        async move { self.vault().n_k1(salt, p).await }
     }
 
     fn aes_ccm_decrypt(
@@ -121,14 +153,21 @@ where
     fn rng_u32(&self) -> u32 {
         self.rng.borrow_mut().next_u32()
     }
+
+    fn static_oob(&self) -> Option<[u8; 16]> {
+        // only asked for before interacting with the user.
+        self.oob.try_borrow().ok().and_then(|oob| oob.static_oob())
+    }
 }
 
-impl<'a, E, N, S, R> MeshContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> MeshContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     S: Storage + 'a,
     R: RngCore + CryptoRng + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     fn uuid(&self) -> Uuid {
         self.vault().uuid()
@@ -196,21 +235,25 @@ where
 // Provisioned pipeline context
 // ------------------------------------------------------------------------
 
-impl<'a, E, N, S, R> ProvisionedContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> ProvisionedContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     R: CryptoRng + RngCore + 'a,
     S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
 }
 
-impl<'a, E, N, S, R> NetworkContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> NetworkContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     R: CryptoRng + RngCore + 'a,
     S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     fn network_deadline(&self, deadline: Option<Instant>) {
         self.deadline.borrow_mut().network(deadline)
@@ -218,12 +261,14 @@ where
 }
 
 #[cfg(feature = "ble-mesh-relay")]
-impl<'a, E, N, S, R> RelayContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> RelayContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     R: CryptoRng + RngCore + 'a,
     S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     #[cfg(feature = "ble-mesh-relay")]
     fn is_relay_enabled(&self) -> bool {
@@ -254,32 +299,88 @@ where
     }
 }
 
-impl<'a, E, N, S, R> AuthenticationContext for Node<'a, E, N, S, R>
+#[cfg(feature = "ble-mesh-proxy")]
+impl<'a, E, N, S, R, O, V> ProxyContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     R: CryptoRng + RngCore + 'a,
     S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
-    fn iv_index(&self) -> Option<u32> {
-        self.vault().iv_index()
+    fn is_proxy_enabled(&self) -> bool {
+        matches!(
+            self.configuration_manager
+                .configuration()
+                .foundation_models()
+                .configuration
+                .gatt_proxy(),
+            GattProxy::Enabled
+        )
+    }
+
+    fn proxy_connection(&self) -> Option<u32> {
+        self.network.proxy_connection()
     }
+}
 
-    fn find_network_keys_by_nid(&self, nid: u8) -> Result<Vec<NetworkDetails, 10>, DeviceError> {
+impl<'a, E, N, S, R, O, V> AuthenticationContext for Node<'a, E, N, S, R, O, V>
+where
+    E: ElementsHandler<'a> + 'a,
+    N: NetworkInterfaces + 'a,
+    R: CryptoRng + RngCore + 'a,
+    S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
+{
+    fn iv_index(&self, ivi: u8) -> Option<u32> {
+        self.configuration_manager
+            .configuration()
+            .network()
+            .as_ref()
+            .map(|network| network.iv_index_state().receive_iv_index(ivi))
+    }
+
+    fn transmit_iv_index(&self) -> Option<u32> {
+        self.configuration_manager
+            .configuration()
+            .network()
+            .as_ref()
+            .map(|network| network.iv_index_state().transmit_iv_index())
+    }
+
+    fn find_network_keys_by_nid(
+        &self,
+        nid: u8,
+    ) -> Result<Vec<NetworkKeyHandle, MAX_NETWORK_KEY_MATERIAL>, DeviceError> {
         if let Some(networks) = self.configuration_manager.configuration().network() {
-            Ok(networks.find_by_nid(nid)?)
+            Ok(networks.find_by_nid(nid))
+        } else {
+            Err(DeviceError::NotProvisioned)
+        }
+    }
+
+    fn transmit_network_key(
+        &self,
+        net_key_index: &NetKeyIndex,
+    ) -> Result<NetworkKeyHandle, DeviceError> {
+        if let Some(network) = self.configuration_manager.configuration().network() {
+            Ok(network.find_by_net_key_index(net_key_index)?.transmit_key())
         } else {
             Err(DeviceError::NotProvisioned)
         }
     }
 }
 
-impl<'a, E, N, S, R> LowerContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> LowerContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     R: CryptoRng + RngCore + 'a,
     S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     fn find_label_uuids_by_address(
         &self,
@@ -300,46 +401,100 @@ where
         }
     }
 
-    fn decrypt_device_key(
+    type DecryptDeviceKeyFuture<'m> = impl Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn decrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m [u8],
+    ) -> Self::DecryptDeviceKeyFuture<'m> {
+        async move { self.vault().decrypt_device_key(nonce, bytes, mic).await }
+    }
+
+    type EncryptDeviceKeyFuture<'m> = impl Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn encrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m mut [u8],
+    ) -> Self::EncryptDeviceKeyFuture<'m> {
+        async move { self.vault().encrypt_device_key(nonce, bytes, mic).await }
+    }
+
+    fn decrypt_remote_device_key(
         &self,
+        addr: &UnicastAddress,
         nonce: DeviceNonce,
         bytes: &mut [u8],
         mic: &[u8],
     ) -> Result<(), DeviceError> {
-        self.vault().decrypt_device_key(nonce, bytes, mic)
+        let device_key = self
+            .remote_device_keys
+            .borrow()
+            .get(addr)
+            .ok_or(DeviceError::CryptoError("decrypt remote device key"))?;
+        crypto::aes_ccm_decrypt_detached(device_key.as_ref(), &*nonce, bytes, mic, None)
+            .map_err(|_| DeviceError::CryptoError("decrypt remote device key"))
     }
 
-    fn encrypt_device_key(
+    fn encrypt_remote_device_key(
         &self,
+        addr: &UnicastAddress,
         nonce: DeviceNonce,
         bytes: &mut [u8],
         mic: &mut [u8],
     ) -> Result<(), DeviceError> {
-        self.vault().encrypt_device_key(nonce, bytes, mic)
+        let device_key = self
+            .remote_device_keys
+            .borrow()
+            .get(addr)
+            .ok_or(DeviceError::CryptoError("encrypt remote device key"))?;
+        crypto::aes_ccm_encrypt_detached(device_key.as_ref(), &*nonce, bytes, mic, None)
+            .map_err(|_| DeviceError::CryptoError("encrypt remote device key"))
     }
 
     fn encrypt_application_key(
         &self,
-        aid: ApplicationKeyIdentifier,
+        net_key_index: &NetKeyIndex,
+        app_key_index: &AppKeyIndex,
         nonce: ApplicationNonce,
         bytes: &mut [u8],
         mic: &mut [u8],
         additional_data: Option<&[u8]>,
-    ) -> Result<(), DeviceError> {
-        self.vault()
-            .encrypt_application_key(&aid, nonce, bytes, mic, additional_data)
+    ) -> Result<ApplicationKeyIdentifier, DeviceError> {
+        self.vault().encrypt_application_key(
+            net_key_index,
+            app_key_index,
+            nonce,
+            bytes,
+            mic,
+            additional_data,
+        )
     }
 
     fn decrypt_application_key(
         &self,
+        net_key_index: &NetKeyIndex,
         aid: ApplicationKeyIdentifier,
         nonce: ApplicationNonce,
         bytes: &mut [u8],
         mic: &[u8],
         additional_data: Option<&[u8]>,
-    ) -> Result<(), DeviceError> {
-        self.vault()
-            .decrypt_application_key(&aid, nonce, bytes, mic, additional_data)
+    ) -> Result<AppKeyIndex, DeviceError> {
+        self.vault().decrypt_application_key(
+            net_key_index,
+            &aid,
+            nonce,
+            bytes,
+            mic,
+            additional_data,
+        )
     }
 
     type NextSequenceFuture<'m> = impl Future<Output = Result<u32, DeviceError>> + 'm
@@ -376,14 +531,103 @@ where
     fn ack_deadline(&self, deadline: Option<Instant>) {
         self.deadline.borrow_mut().ack(deadline);
     }
+
+    fn sar_config(&self) -> SarConfig {
+        self.sar_config
+    }
+
+    fn transmission_failure(&self, failure: TransmissionFailure) {
+        // the oldest failure not yet collected by the elements makes room.
+        if let Err(TrySendError::Full(failure)) = self.transmission_failures.try_send(failure) {
+            warn!("transmission failure not collected, dropping the oldest");
+            self.transmission_failures.try_recv().ok();
+            self.transmission_failures.try_send(failure).ok();
+        }
+    }
 }
 
-impl<'a, E, N, S, R> UpperContext for Node<'a, E, N, S, R>
+#[cfg(feature = "ble-mesh-friend")]
+impl<'a, E, N, S, R, O, V> FriendContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     R: CryptoRng + RngCore + 'a,
     S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
+{
+    fn is_friend_enabled(&self) -> bool {
+        matches!(
+            self.configuration_manager
+                .configuration()
+                .foundation_models()
+                .configuration
+                .friend(),
+            Friend::SupportedEnabled
+        )
+    }
+
+    fn friend_deadline(&self, deadline: Option<Instant>) {
+        self.deadline.borrow_mut().friend(deadline);
+    }
+
+    fn beacon_state(&self, net_key_index: &NetKeyIndex) -> Result<(Flags, u32), DeviceError> {
+        if let Some(network) = self.configuration_manager.configuration().network() {
+            let iv_index = network.iv_index_state();
+            Ok((
+                Flags {
+                    key_refresh: network
+                        .find_by_net_key_index(net_key_index)?
+                        .key_refresh_flag(),
+                    iv_update: iv_index.in_progress(),
+                },
+                iv_index.iv_index(),
+            ))
+        } else {
+            Err(DeviceError::NotProvisioned)
+        }
+    }
+}
+
+#[cfg(feature = "ble-mesh-lpn")]
+impl<'a, E, N, S, R, O, V> LowPowerContext for Node<'a, E, N, S, R, O, V>
+where
+    E: ElementsHandler<'a> + 'a,
+    N: NetworkInterfaces + 'a,
+    R: CryptoRng + RngCore + 'a,
+    S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
+{
+    fn number_of_elements(&self) -> u8 {
+        self.configuration_manager.composition().elements.len() as u8
+    }
+
+    fn subscription_addresses(&self) -> Vec<Address, LPN_SUBSCRIPTION_LIST_SIZE> {
+        if let Some(network) = self.configuration_manager.configuration().network() {
+            network.subscriptions().addresses()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn low_power_listen(&self, listen: Listen) {
+        self.listen.set(listen);
+    }
+
+    fn low_power_deadline(&self, deadline: Option<Instant>) {
+        self.deadline.borrow_mut().low_power(deadline);
+    }
+}
+
+impl<'a, E, N, S, R, O, V> UpperContext for Node<'a, E, N, S, R, O, V>
+where
+    E: ElementsHandler<'a> + 'a,
+    N: NetworkInterfaces + 'a,
+    R: CryptoRng + RngCore + 'a,
+    S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     fn publish_deadline(&self, deadline: Option<Instant>) {
         self.deadline.borrow_mut().publish(deadline);
@@ -394,14 +638,20 @@ where
     fn republish<'m>(&'m self, message: OutboundPublishMessage) -> Self::RepublishFuture<'m> {
         self.outbound.publish.send(message)
     }
+
+    fn receive_heartbeat(&self, src: UnicastAddress, dst: Address, hops: u8) {
+        self.heartbeat.borrow_mut().receive(src, dst, hops);
+    }
 }
 
-impl<'a, E, N, S, R> AccessContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> AccessContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     R: CryptoRng + RngCore + 'a,
     S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     type DispatchFuture<'m> = impl Future<Output = Result<(), DeviceError>> + 'm
     where
@@ -412,21 +662,25 @@ where
     }
 }
 
-impl<'a, E, N, S, R> PipelineContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> PipelineContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     S: Storage + 'a,
     R: RngCore + CryptoRng + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
 }
 
-impl<'a, E, N, S, R> ElementContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> ElementContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     R: CryptoRng + RngCore + 'a,
     S: Storage + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     type TransmitFuture<'m> = impl Future<Output = Result<(), DeviceError>> + 'm
     where
@@ -448,12 +702,14 @@ where
     }
 }
 
-impl<'a, E, N, S, R> PrimaryElementContext for Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> PrimaryElementContext for Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a> + 'a,
     N: NetworkInterfaces + 'a,
     S: Storage + 'a,
     R: RngCore + CryptoRng + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     type NodeResetFuture<'m> = impl Future<Output = ()>
     where
@@ -486,4 +742,17 @@ where
     fn is_local(&self, addr: &UnicastAddress) -> bool {
         self.is_local_unicast(&Address::Unicast(*addr))
     }
+
+    fn configuration_response(&self, response: ConfigurationResponse) {
+        // dropped if the client has stopped waiting for it.
+        self.configuration_responses.try_send(response).ok();
+    }
+
+    fn heartbeat(&self) -> RefMut<'_, HeartbeatState> {
+        self.heartbeat.borrow_mut()
+    }
+
+    fn health(&self) -> RefMut<'_, HealthState> {
+        self.health.borrow_mut()
+    }
 }
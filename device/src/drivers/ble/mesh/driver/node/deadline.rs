@@ -6,12 +6,20 @@ pub enum Expiration {
     Network,
     Publish,
     Ack,
+    #[cfg(feature = "ble-mesh-friend")]
+    Friend,
+    #[cfg(feature = "ble-mesh-lpn")]
+    LowPower,
 }
 
 pub struct Deadline {
     network: Option<Instant>,
     publish: Option<Instant>,
     ack: Option<Instant>,
+    #[cfg(feature = "ble-mesh-friend")]
+    friend: Option<Instant>,
+    #[cfg(feature = "ble-mesh-lpn")]
+    low_power: Option<Instant>,
 }
 
 impl Default for Deadline {
@@ -20,6 +28,11 @@ impl Default for Deadline {
             network: None,
             publish: None,
             ack: None,
+            #[cfg(feature = "ble-mesh-friend")]
+            friend: None,
+            // a low power node looks for a friend as soon as it is provisioned.
+            #[cfg(feature = "ble-mesh-lpn")]
+            low_power: Some(Instant::now()),
         }
     }
 }
@@ -76,6 +89,42 @@ impl Deadline {
         }
     }
 
+    #[cfg(feature = "ble-mesh-friend")]
+    pub fn friend(&mut self, deadline: Option<Instant>) {
+        match (self.friend, deadline) {
+            (Some(a), Some(b)) if b < a => {
+                self.friend.replace(b);
+            }
+            (None, Some(b)) => {
+                self.friend.replace(b);
+            }
+            (Some(_), None) => {
+                self.friend.take();
+            }
+            _ => {
+                // earliest deadline already set
+            }
+        }
+    }
+
+    #[cfg(feature = "ble-mesh-lpn")]
+    pub fn low_power(&mut self, deadline: Option<Instant>) {
+        match (self.low_power, deadline) {
+            (Some(a), Some(b)) if b < a => {
+                self.low_power.replace(b);
+            }
+            (None, Some(b)) => {
+                self.low_power.replace(b);
+            }
+            (Some(_), None) => {
+                self.low_power.take();
+            }
+            _ => {
+                // earliest deadline already set
+            }
+        }
+    }
+
     /// Wait for the next earliest deadline, knowing which deadline passed
     /// when this method returns.
     ///
@@ -102,6 +151,14 @@ impl Deadline {
             Expiration::Ack => {
                 self.ack.take();
             }
+            #[cfg(feature = "ble-mesh-friend")]
+            Expiration::Friend => {
+                self.friend.take();
+            }
+            #[cfg(feature = "ble-mesh-lpn")]
+            Expiration::LowPower => {
+                self.low_power.take();
+            }
         }
     }
 
@@ -138,6 +195,28 @@ impl Deadline {
             }
         }
 
+        #[cfg(feature = "ble-mesh-friend")]
+        if let Some(friend) = self.friend {
+            if let Some(prev) = &result {
+                if prev.1 > friend {
+                    result.replace((Expiration::Friend, friend));
+                }
+            } else {
+                result.replace((Expiration::Friend, friend));
+            }
+        }
+
+        #[cfg(feature = "ble-mesh-lpn")]
+        if let Some(low_power) = self.low_power {
+            if let Some(prev) = &result {
+                if prev.1 > low_power {
+                    result.replace((Expiration::LowPower, low_power));
+                }
+            } else {
+                result.replace((Expiration::LowPower, low_power));
+            }
+        }
+
         result
     }
 }
@@ -0,0 +1,235 @@
+use crate::drivers::ble::mesh::composition::CompanyIdentifier;
+use crate::drivers::ble::mesh::model::foundation::health::{FaultStatus, MAX_FAULTS};
+use embassy::time::{Duration, Instant};
+use heapless::Vec;
+
+/// A change to the faults of the node, as registered by the application.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HealthFault {
+    /// A fault condition has been detected.
+    Raise(u8),
+    /// A fault condition is no longer present. It remains registered until
+    /// cleared by a health client.
+    Resolve(u8),
+}
+
+/// Runtime state of the health server of the primary element.
+#[derive(Default)]
+pub struct HealthState {
+    test_id: u8,
+    current: Vec<u8, MAX_FAULTS>,
+    registered: Vec<u8, MAX_FAULTS>,
+    fast_period_divisor: u8,
+    attention_until: Option<Instant>,
+    last_published: Option<Instant>,
+}
+
+impl HealthState {
+    /// Apply a fault change, returning whether the current faults changed.
+    pub fn apply(&mut self, fault: HealthFault) -> bool {
+        match fault {
+            HealthFault::Raise(fault) => {
+                if !self.registered.contains(&fault) {
+                    // faults beyond the capacity of the array are dropped.
+                    self.registered.push(fault).ok();
+                }
+                if !self.current.contains(&fault) {
+                    return self.current.push(fault).is_ok();
+                }
+                false
+            }
+            HealthFault::Resolve(fault) => {
+                let len = self.current.len();
+                self.current.retain(|e| *e != fault);
+                self.current.len() != len
+            }
+        }
+    }
+
+    pub fn current_status(&self, company_id: CompanyIdentifier) -> FaultStatus {
+        FaultStatus {
+            test_id: self.test_id,
+            company_id,
+            faults: self.current.clone(),
+        }
+    }
+
+    pub fn fault_status(&self, company_id: CompanyIdentifier) -> FaultStatus {
+        FaultStatus {
+            test_id: self.test_id,
+            company_id,
+            faults: self.registered.clone(),
+        }
+    }
+
+    pub fn clear_registered(&mut self) {
+        self.registered.clear();
+    }
+
+    pub fn test_performed(&mut self, test_id: u8) {
+        self.test_id = test_id;
+    }
+
+    pub fn fast_period_divisor(&self) -> u8 {
+        self.fast_period_divisor
+    }
+
+    pub fn set_fast_period_divisor(&mut self, divisor: u8) {
+        self.fast_period_divisor = divisor;
+    }
+
+    /// The period of the current status publication, shortened by the fast
+    /// period divisor while any fault is present.
+    pub fn publish_period(&self, period: Duration) -> Duration {
+        if self.current.is_empty() {
+            period
+        } else {
+            // the divisor is at most 15, as checked when parsed.
+            period / (1 << self.fast_period_divisor)
+        }
+    }
+
+    /// Whether the current status is due to be published periodically, with
+    /// the `period` of the publication of the health server, if any.
+    pub fn publication_due(&mut self, period: Option<Duration>, now: Instant) -> bool {
+        match (period, self.last_published) {
+            (None, _) => {
+                self.last_published.take();
+                false
+            }
+            (Some(_), None) => {
+                self.last_published.replace(now);
+                false
+            }
+            (Some(period), Some(last)) => {
+                if now >= last + self.publish_period(period) {
+                    self.last_published.replace(now);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    /// Seconds of attention remaining.
+    pub fn attention(&self) -> u8 {
+        match self.attention_until {
+            Some(until) => {
+                let now = Instant::now();
+                if until > now {
+                    (((until - now).as_millis() + 999) / 1000) as u8
+                } else {
+                    0
+                }
+            }
+            None => 0,
+        }
+    }
+
+    pub fn set_attention(&mut self, seconds: u8) {
+        self.attention_until = if seconds == 0 {
+            None
+        } else {
+            Some(Instant::now() + Duration::from_secs(seconds as u64))
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPANY_ID: CompanyIdentifier = CompanyIdentifier(0x0003);
+
+    #[test]
+    fn raise_and_resolve() {
+        let mut state = HealthState::default();
+        assert!(state.apply(HealthFault::Raise(0x01)));
+        assert!(!state.apply(HealthFault::Raise(0x01)));
+        assert!(state.apply(HealthFault::Raise(0x02)));
+        assert_eq!(&[0x01, 0x02], &state.current_status(COMPANY_ID).faults[..]);
+
+        assert!(state.apply(HealthFault::Resolve(0x01)));
+        assert!(!state.apply(HealthFault::Resolve(0x01)));
+        assert_eq!(&[0x02], &state.current_status(COMPANY_ID).faults[..]);
+        // resolved faults remain registered.
+        assert_eq!(&[0x01, 0x02], &state.fault_status(COMPANY_ID).faults[..]);
+    }
+
+    #[test]
+    fn clear_registered() {
+        let mut state = HealthState::default();
+        state.apply(HealthFault::Raise(0x01));
+        state.apply(HealthFault::Raise(0x02));
+        state.apply(HealthFault::Resolve(0x02));
+        state.test_performed(0x05);
+
+        state.clear_registered();
+        assert!(state.fault_status(COMPANY_ID).faults.is_empty());
+        assert_eq!(&[0x01], &state.current_status(COMPANY_ID).faults[..]);
+        assert_eq!(0x05, state.fault_status(COMPANY_ID).test_id);
+    }
+
+    #[test]
+    fn faults_beyond_capacity() {
+        let mut state = HealthState::default();
+        for fault in 0..MAX_FAULTS as u8 {
+            assert!(state.apply(HealthFault::Raise(fault)));
+        }
+        assert!(!state.apply(HealthFault::Raise(0xFF)));
+        assert_eq!(MAX_FAULTS, state.current_status(COMPANY_ID).faults.len());
+        assert_eq!(MAX_FAULTS, state.fault_status(COMPANY_ID).faults.len());
+    }
+
+    #[test]
+    fn attention() {
+        let mut state = HealthState::default();
+        assert_eq!(0, state.attention());
+
+        state.set_attention(5);
+        assert_eq!(5, state.attention());
+
+        state.set_attention(0);
+        assert_eq!(0, state.attention());
+    }
+
+    #[test]
+    fn fast_period_divisor() {
+        let mut state = HealthState::default();
+        state.set_fast_period_divisor(2);
+        let period = Duration::from_secs(8);
+
+        // the divisor only applies while a fault is present.
+        assert_eq!(period, state.publish_period(period));
+        state.apply(HealthFault::Raise(0x01));
+        assert_eq!(Duration::from_secs(2), state.publish_period(period));
+        state.apply(HealthFault::Resolve(0x01));
+        assert_eq!(period, state.publish_period(period));
+    }
+
+    #[test]
+    fn publication_due() {
+        let mut state = HealthState::default();
+        state.set_fast_period_divisor(1);
+        let period = Some(Duration::from_secs(10));
+        let start = Instant::from_millis(1_000);
+
+        assert!(!state.publication_due(None, start));
+        assert!(!state.publication_due(period, start));
+        assert!(!state.publication_due(period, start + Duration::from_secs(5)));
+        assert!(state.publication_due(period, start + Duration::from_secs(10)));
+
+        // published twice as often while faulted.
+        state.apply(HealthFault::Raise(0x01));
+        let last = start + Duration::from_secs(10);
+        assert!(!state.publication_due(period, last + Duration::from_secs(4)));
+        assert!(state.publication_due(period, last + Duration::from_secs(5)));
+
+        // disabling the publication restarts the period.
+        let last = last + Duration::from_secs(5);
+        assert!(!state.publication_due(None, last + Duration::from_secs(5)));
+        assert!(!state.publication_due(period, last + Duration::from_secs(5)));
+    }
+}
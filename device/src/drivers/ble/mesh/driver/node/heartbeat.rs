@@ -0,0 +1,150 @@
+use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
+use crate::drivers::ble::mesh::model::foundation::configuration::heartbeat::{
+    from_log, to_log, Features, HeartbeatPublication, HeartbeatSubscription,
+    HeartbeatSubscriptionSet,
+};
+use embassy::time::{Duration, Instant};
+
+/// Runtime state of heartbeat publication and subscription. Only the
+/// publication parameters are persisted, as part of the configuration.
+pub struct HeartbeatState {
+    /// Periodic heartbeats left to publish, or `None` until started from
+    /// the configured publication.
+    remaining: Option<u16>,
+    next: Option<Instant>,
+    features: Option<Features>,
+    subscription: Subscription,
+}
+
+struct Subscription {
+    source: Address,
+    destination: Address,
+    expires: Option<Instant>,
+    count: u16,
+    min_hops: u8,
+    max_hops: u8,
+}
+
+impl Default for HeartbeatState {
+    fn default() -> Self {
+        Self {
+            remaining: None,
+            next: None,
+            features: None,
+            subscription: Subscription {
+                source: Address::Unassigned,
+                destination: Address::Unassigned,
+                expires: None,
+                count: 0,
+                min_hops: 0,
+                max_hops: 0,
+            },
+        }
+    }
+}
+
+impl HeartbeatState {
+    /// Restart periodic publication after the publication has been set.
+    pub fn publication_set(&mut self, publication: &HeartbeatPublication) {
+        self.remaining.replace(publication.count());
+        self.next = publication.period().map(|_| Instant::now());
+    }
+
+    /// The configured publication, reporting the heartbeats left to publish.
+    pub fn publication_status(&self, publication: &HeartbeatPublication) -> HeartbeatPublication {
+        let mut status = *publication;
+        if let Some(remaining) = self.remaining {
+            status.count_log = to_log(remaining);
+        }
+        status
+    }
+
+    /// Whether a heartbeat is due, either periodically or because the state of
+    /// a feature `publication` reports changes has changed.
+    pub fn publish(&mut self, publication: &HeartbeatPublication, features: Features) -> bool {
+        if self.remaining.is_none() {
+            self.publication_set(publication);
+        }
+
+        let changed = matches!(self.features.replace(features), Some(previous)
+            if (previous.bits() ^ features.bits()) & publication.features.bits() != 0);
+
+        let mut due = false;
+        let now = Instant::now();
+        if let (Some(remaining), Some(next), Some(period)) =
+            (self.remaining, self.next, publication.period())
+        {
+            if remaining == 0 {
+                self.next.take();
+            } else if now >= next {
+                due = true;
+                if remaining != 0xFFFF {
+                    self.remaining.replace(remaining - 1);
+                }
+                self.next.replace(next + period);
+            }
+        }
+
+        (due || changed) && publication.is_enabled()
+    }
+
+    pub fn subscription_set(&mut self, set: &HeartbeatSubscriptionSet) {
+        let subscription = &mut self.subscription;
+        if set.period_log == 0
+            || matches!(set.source, Address::Unassigned)
+            || matches!(set.destination, Address::Unassigned)
+        {
+            // disabled, while retaining the statistics gathered.
+            subscription.source = Address::Unassigned;
+            subscription.destination = Address::Unassigned;
+            subscription.expires.take();
+        } else {
+            subscription.source = set.source;
+            subscription.destination = set.destination;
+            subscription
+                .expires
+                .replace(Instant::now() + Duration::from_secs(from_log(set.period_log) as u64));
+            subscription.count = 0;
+            subscription.min_hops = 0x7F;
+            subscription.max_hops = 0;
+        }
+    }
+
+    pub fn subscription_status(&self) -> HeartbeatSubscription {
+        let subscription = &self.subscription;
+        let remaining = match subscription.expires {
+            Some(expires) => {
+                let now = Instant::now();
+                if expires > now {
+                    // a remaining period of 0xFFFF seconds must not read as indefinite.
+                    (expires - now).as_secs().min(0xFFFE) as u16
+                } else {
+                    0
+                }
+            }
+            None => 0,
+        };
+        HeartbeatSubscription {
+            source: subscription.source,
+            destination: subscription.destination,
+            period_log: to_log(remaining),
+            count_log: to_log(subscription.count),
+            min_hops: subscription.min_hops,
+            max_hops: subscription.max_hops,
+        }
+    }
+
+    /// Account for a heartbeat received while subscribed to its source and destination.
+    pub fn receive(&mut self, src: UnicastAddress, dst: Address, hops: u8) {
+        let subscription = &mut self.subscription;
+        if !matches!(subscription.expires, Some(expires) if Instant::now() < expires) {
+            return;
+        }
+        if subscription.source != Address::Unicast(src) || subscription.destination != dst {
+            return;
+        }
+        subscription.count = subscription.count.saturating_add(1);
+        subscription.min_hops = subscription.min_hops.min(hops);
+        subscription.max_hops = subscription.max_hops.max(hops);
+    }
+}
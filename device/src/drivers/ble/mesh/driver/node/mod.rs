@@ -1,37 +1,78 @@
 use crate::drivers::ble::mesh::composition::ElementsHandler;
 use crate::drivers::ble::mesh::config::configuration_manager::ConfigurationManager;
+use crate::drivers::ble::mesh::config::device_keys::{DeviceKeyHandle, RemoteDeviceKeys};
 use crate::drivers::ble::mesh::config::network::NetworkKeyHandle;
+use crate::drivers::ble::mesh::driver::elements::configuration_client::ConfigurationResponseChannel;
 use crate::drivers::ble::mesh::driver::elements::{
     AppElementsContext, ElementContext, Elements, PrimaryElementContext,
 };
 use crate::drivers::ble::mesh::driver::node::deadline::Deadline;
+use crate::drivers::ble::mesh::driver::node::health::HealthState;
+use crate::drivers::ble::mesh::driver::node::heartbeat::HeartbeatState;
+use crate::drivers::ble::mesh::driver::node::oob::OobHandler;
 use crate::drivers::ble::mesh::driver::node::outbound::{
-    Outbound, OutboundEvent, OutboundPublishMessage,
+    Outbound, OutboundDeviceKeyMessage, OutboundEvent, OutboundPublishMessage,
+};
+use crate::drivers::ble::mesh::driver::node::sar::{
+    MessageIds, SarConfig, TransmissionFailure, MAX_OUTBOUND_IN_FLIGHT,
 };
 use crate::drivers::ble::mesh::driver::pipeline::mesh::MeshContext;
+#[cfg(feature = "ble-mesh-lpn")]
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::low_power::Listen;
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::authentication::AuthenticationContext;
+use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::OobRequest;
 use crate::drivers::ble::mesh::driver::pipeline::Pipeline;
 use crate::drivers::ble::mesh::driver::DeviceError;
-use crate::drivers::ble::mesh::interface::{Beacon, NetworkInterfaces};
-use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+use crate::drivers::ble::mesh::interface::{Beacon, NetworkError, NetworkInterfaces, PDU};
+#[cfg(feature = "ble-mesh-friend")]
+use crate::drivers::ble::mesh::model::foundation::configuration::friend::Friend;
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::model::foundation::configuration::gatt_proxy::GattProxy;
+use crate::drivers::ble::mesh::model::foundation::configuration::heartbeat::Features;
+#[cfg(feature = "ble-mesh-relay")]
+use crate::drivers::ble::mesh::model::foundation::configuration::relay::Relay;
+use crate::drivers::ble::mesh::model::foundation::configuration::AppKeyIndex;
+use crate::drivers::ble::mesh::model::foundation::health::{HealthMessage, HEALTH_SERVER};
+use crate::drivers::ble::mesh::model::Message;
+use crate::drivers::ble::mesh::pdu::access::{AccessMessage, AccessPayload};
+use crate::drivers::ble::mesh::pdu::heartbeat::Heartbeat;
+use crate::drivers::ble::mesh::pdu::lower::Opcode;
+use crate::drivers::ble::mesh::pdu::upper::UpperControl;
 use crate::drivers::ble::mesh::provisioning::Capabilities;
 use crate::drivers::ble::mesh::storage::Storage;
-use crate::drivers::ble::mesh::vault::StorageVault;
+use crate::drivers::ble::mesh::vault::VaultFactory;
 use core::cell::{Cell, RefCell};
 use embassy::blocking_mutex::raw::ThreadModeRawMutex;
-use embassy::channel::mpmc::DynamicReceiver as ChannelReceiver;
-use embassy::time::{Duration, Ticker};
+use embassy::channel::mpmc::{Channel, DynamicReceiver as ChannelReceiver};
+use embassy::time::{Duration, Instant, Ticker};
 use embassy::util::{select, select4, Either, Either4};
 use futures::future::join;
+#[cfg(feature = "ble-mesh-lpn")]
+use futures::future::pending;
 use futures::StreamExt;
+use heapless::Vec;
 use rand_core::{CryptoRng, RngCore};
 //use crate::drivers::ble::mesh::model::foundation::configuration::ConfigurationMessage::Beacon;
 
 pub(crate) mod context;
 pub(crate) mod deadline;
+pub(crate) mod health;
+pub(crate) mod heartbeat;
+pub mod oob;
 pub(crate) mod outbound;
+pub mod sar;
 
 type NodeMutex = ThreadModeRawMutex;
 
+pub(crate) type TransmissionFailureChannel =
+    Channel<NodeMutex, TransmissionFailure, MAX_OUTBOUND_IN_FLIGHT>;
+
+/// How often time spent in the current IV Update state is accounted for.
+const IV_UPDATE_HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// How often a secure network beacon is broadcast for each subnet.
+const SECURE_BEACON_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Copy, Clone)]
 pub struct NetworkId(pub [u8; 8]);
 
@@ -48,12 +89,14 @@ pub enum MeshNodeMessage {
     Shutdown,
 }
 
-pub struct Node<'a, E, N, S, R>
+pub struct Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a>,
     N: NetworkInterfaces + 'a,
     S: Storage + 'a,
     R: RngCore + CryptoRng + 'a,
+    O: OobHandler + 'a,
+    V: VaultFactory<S> + 'a,
 {
     //
     state: Cell<State>,
@@ -61,19 +104,34 @@ where
     network: N,
     configuration_manager: ConfigurationManager<S>,
     rng: RefCell<R>,
+    oob: RefCell<O>,
+    vault: V,
     pipeline: RefCell<Pipeline>,
     pub(crate) deadline: RefCell<Deadline>,
+    iv_update_hour: Cell<Option<Instant>>,
+    secure_beacon: Cell<Option<Instant>>,
+    pub(crate) heartbeat: RefCell<HeartbeatState>,
+    pub(crate) health: RefCell<HealthState>,
+    #[cfg(feature = "ble-mesh-lpn")]
+    pub(crate) listen: Cell<Listen>,
     //
     pub(crate) elements: RefCell<Elements<'a, E>>,
     pub(crate) outbound: Outbound<'a>,
+    pub(crate) remote_device_keys: RefCell<RemoteDeviceKeys>,
+    pub(crate) configuration_responses: ConfigurationResponseChannel,
+    pub(crate) sar_config: SarConfig,
+    pub(crate) transmission_failures: TransmissionFailureChannel,
+    pub(crate) message_ids: MessageIds,
 }
 
-impl<'a, E, N, S, R> Node<'a, E, N, S, R>
+impl<'a, E, N, S, R, O, V> Node<'a, E, N, S, R, O, V>
 where
     E: ElementsHandler<'a>,
     N: NetworkInterfaces,
     S: Storage,
     R: RngCore + CryptoRng,
+    O: OobHandler,
+    V: VaultFactory<S>,
 {
     pub fn new(
         app_elements: E,
@@ -81,17 +139,32 @@ where
         network: N,
         configuration_manager: ConfigurationManager<S>,
         rng: R,
+        oob: O,
+        vault: V,
     ) -> Self {
         let me = Self {
             state: Cell::new(State::Unprovisioned),
             network,
             configuration_manager,
             rng: RefCell::new(rng),
+            oob: RefCell::new(oob),
+            vault,
             pipeline: RefCell::new(Pipeline::new(capabilities)),
             deadline: RefCell::new(Default::default()),
+            iv_update_hour: Cell::new(None),
+            secure_beacon: Cell::new(None),
+            heartbeat: RefCell::new(Default::default()),
+            health: RefCell::new(Default::default()),
+            #[cfg(feature = "ble-mesh-lpn")]
+            listen: Cell::new(Listen::Always),
             //
             elements: RefCell::new(Elements::new(app_elements)),
             outbound: Default::default(),
+            remote_device_keys: Default::default(),
+            configuration_responses: ConfigurationResponseChannel::new(),
+            sar_config: Default::default(),
+            transmission_failures: Channel::new(),
+            message_ids: Default::default(),
         };
         info!("State: {:?}", core::mem::size_of_val(&me.state));
         info!("Network: {:?}", core::mem::size_of_val(&me.network));
@@ -107,8 +180,13 @@ where
         me
     }
 
-    pub(crate) fn vault(&self) -> StorageVault<S> {
-        StorageVault::new(&self.configuration_manager)
+    /// Use `sar_config` for the segmentation and reassembly of messages.
+    pub fn with_sar_config(self, sar_config: SarConfig) -> Self {
+        Self { sar_config, ..self }
+    }
+
+    pub(crate) fn vault(&self) -> V::Vault<'_> {
+        self.vault.vault(&self.configuration_manager)
     }
 
     async fn publish(&self, publish: OutboundPublishMessage) -> Result<(), DeviceError> {
@@ -124,13 +202,15 @@ where
                     let message = AccessMessage {
                         ttl: publication.publish_ttl,
                         network_key: NetworkKeyHandle::from(network),
-                        // todo: ivi isn't always zero.
+                        // set from the transmit IV index by the lower transport.
                         ivi: 0,
                         nid: network.nid,
                         akf: true,
                         aid: app_key_details.aid,
+                        app_key_index: publication.app_key_index,
                         src: publish.element_address,
                         dst: publication.publish_address,
+                        device_key: DeviceKeyHandle::Local,
                         payload: publish.payload,
                     };
                     self.pipeline
@@ -139,6 +219,7 @@ where
                             self,
                             &message,
                             Some((model_key, publication.into())),
+                            publish.id,
                             self.network_retransmit(),
                         )
                         .await?;
@@ -149,6 +230,40 @@ where
         Ok(())
     }
 
+    async fn send_device_key_message(
+        &self,
+        message: OutboundDeviceKeyMessage,
+    ) -> Result<(), DeviceError> {
+        // remembered so the response can be decrypted once it arrives.
+        self.remote_device_keys
+            .borrow_mut()
+            .insert(message.dst, message.device_key);
+        let network = self.configuration_manager.configuration().network().clone();
+        if let Some(network) = network {
+            if let Some(primary) = network.iter().next() {
+                let message = AccessMessage {
+                    ttl: None,
+                    network_key: NetworkKeyHandle::from(primary),
+                    // set from the transmit IV index by the lower transport.
+                    ivi: 0,
+                    nid: primary.nid,
+                    akf: false,
+                    aid: 0.into(),
+                    app_key_index: AppKeyIndex::new(0),
+                    src: *network.unicast_address(),
+                    dst: message.dst.into(),
+                    device_key: DeviceKeyHandle::Remote(message.dst),
+                    payload: message.payload,
+                };
+                self.pipeline
+                    .borrow_mut()
+                    .process_outbound(self, &message, None, None, self.network_retransmit())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     async fn loop_unprovisioned(&self) -> Result<Option<State>, DeviceError> {
         debug!("State: unprovisioned");
 
@@ -183,6 +298,48 @@ where
 
     async fn loop_provisioning(&self) -> Result<Option<State>, DeviceError> {
         debug!("State: provisioning");
+        let request = self.pipeline.borrow().oob_request();
+        let request = match request {
+            Some(request) => request,
+            None => return self.step_provisioning().await,
+        };
+
+        // keep provisioning while interacting with the user, until the
+        // provisioner is done with the value, or the user input it.
+        let protocol_fut = self.step_provisioning_during_oob();
+        let interaction_fut = async {
+            let mut oob = self.oob.borrow_mut();
+            match &request {
+                OobRequest::Output(action, value) => oob.output(*action, value).await.map(|_| None),
+                OobRequest::Input(action, size) => oob.input(*action, *size).await.map(Some),
+            }
+        };
+
+        match select(protocol_fut, interaction_fut).await {
+            Either::First(next_state) => next_state,
+            Either::Second(Ok(input)) => {
+                self.pipeline.borrow_mut().oob_complete(self, input).await?;
+                Ok(Some(State::Provisioning))
+            }
+            Either::Second(Err(e)) => {
+                warn!("out-of-band interaction failed: {:?}", e);
+                self.pipeline.borrow_mut().oob_complete(self, None).await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn step_provisioning_during_oob(&self) -> Result<Option<State>, DeviceError> {
+        loop {
+            match self.step_provisioning().await? {
+                Some(State::Provisioning) | None
+                    if self.pipeline.borrow().oob_request().is_some() => {}
+                next_state => return Ok(next_state),
+            }
+        }
+    }
+
+    async fn step_provisioning(&self) -> Result<Option<State>, DeviceError> {
         let receive_fut = self.network.receive();
         let mut ticker = Ticker::every(Duration::from_secs(1));
         let ticker_fut = ticker.next();
@@ -229,13 +386,191 @@ where
         Ok(())
     }
 
+    async fn transmit_secure_beacons(&self) -> Result<(), DeviceError> {
+        let now = Instant::now();
+        if matches!(self.secure_beacon.get(), Some(next) if now < next) {
+            return Ok(());
+        }
+        self.secure_beacon.set(Some(now + SECURE_BEACON_INTERVAL));
+
+        let secure_beacon = self
+            .configuration_manager
+            .configuration()
+            .foundation_models()
+            .configuration_model()
+            .secure_beacon();
+        if !secure_beacon {
+            return Ok(());
+        }
+        let network = self.configuration_manager.configuration().network().clone();
+        if let Some(network) = network {
+            for subnet in network.iter() {
+                let beacon = subnet.secure_network_beacon(network.iv_index_state())?;
+                self.network.beacon(Beacon::Secure(beacon)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Features currently in use, as reported by heartbeats.
+    fn features(&self) -> Features {
+        #[allow(unused_mut)]
+        let mut features = Features::default();
+        #[cfg(feature = "ble-mesh-relay")]
+        {
+            features.relay = matches!(
+                self.configuration_manager
+                    .configuration()
+                    .foundation_models()
+                    .configuration_model()
+                    .relay()
+                    .relay,
+                Relay::SupportedEnabled
+            );
+        }
+        #[cfg(feature = "ble-mesh-friend")]
+        {
+            features.friend = matches!(
+                self.configuration_manager
+                    .configuration()
+                    .foundation_models()
+                    .configuration_model()
+                    .friend(),
+                Friend::SupportedEnabled
+            );
+        }
+        #[cfg(feature = "ble-mesh-proxy")]
+        {
+            features.proxy = matches!(
+                self.configuration_manager
+                    .configuration()
+                    .foundation_models()
+                    .configuration_model()
+                    .gatt_proxy(),
+                GattProxy::Enabled
+            );
+        }
+        #[cfg(feature = "ble-mesh-lpn")]
+        {
+            // the radio only sleeps while befriended.
+            features.low_power = matches!(self.listen.get(), Listen::Until(_));
+        }
+        features
+    }
+
+    async fn transmit_heartbeat(&self) -> Result<(), DeviceError> {
+        let publication = *self
+            .configuration_manager
+            .configuration()
+            .foundation_models()
+            .configuration_model()
+            .heartbeat_publication();
+        let features = self.features();
+        if !self.heartbeat.borrow_mut().publish(&publication, features) {
+            return Ok(());
+        }
+
+        let src = self.primary_unicast_address()?;
+        let network_key = self.transmit_network_key(&publication.net_key_index)?;
+        let mut data = Vec::new();
+        Heartbeat {
+            init_ttl: publication.ttl,
+            features,
+        }
+        .emit(&mut data)?;
+
+        let control = UpperControl {
+            ttl: publication.ttl,
+            network_key,
+            // set from the transmit IV index by the lower transport.
+            ivi: 0,
+            nid: network_key.nid,
+            src,
+            dst: publication.destination,
+            opcode: Opcode::Heatbeat,
+            data,
+        };
+        self.pipeline
+            .borrow_mut()
+            .process_outbound_control(self, control, self.network_retransmit())
+            .await
+    }
+
+    /// Publish the current faults of the health server, following a change.
+    async fn publish_health_current_status(&self) -> Result<(), DeviceError> {
+        let company_id = self.configuration_manager.composition().cid;
+        let message = HealthMessage::CurrentStatus(self.health.borrow().current_status(company_id));
+        let mut parameters = Vec::new();
+        message.emit_parameters(&mut parameters)?;
+        self.publish(OutboundPublishMessage {
+            id: None,
+            element_address: self.primary_unicast_address()?,
+            model_identifier: HEALTH_SERVER,
+            payload: AccessPayload {
+                opcode: message.opcode(),
+                parameters,
+            },
+        })
+        .await
+    }
+
+    /// Publish the current faults of the health server periodically, when
+    /// its publication has a period.
+    async fn transmit_health_current_status(&self) -> Result<(), DeviceError> {
+        let element_address = self.primary_unicast_address()?;
+        let period = self
+            .configuration_manager
+            .configuration()
+            .network()
+            .as_ref()
+            .and_then(|network| network.find_publication(&element_address, &HEALTH_SERVER))
+            .and_then(|(_, publication)| publication.publish_period());
+        if self
+            .health
+            .borrow_mut()
+            .publication_due(period, Instant::now())
+        {
+            self.publish_health_current_status().await?;
+        }
+        Ok(())
+    }
+
+    async fn iv_update_hour_elapsed(&self) -> Result<(), DeviceError> {
+        let now = Instant::now();
+        match self.iv_update_hour.get() {
+            None => self.iv_update_hour.set(Some(now + IV_UPDATE_HOUR)),
+            Some(next) if now >= next => {
+                self.iv_update_hour.set(Some(next + IV_UPDATE_HOUR));
+                self.configuration_manager.iv_index_hour_elapsed().await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Receive from the network interfaces, unless the radio sleeps while
+    /// the friend of this low power node queues messages for it.
+    async fn receive_provisioned(&self) -> Result<PDU, NetworkError> {
+        #[cfg(feature = "ble-mesh-lpn")]
+        if let Listen::Until(until) = self.listen.get() {
+            if Instant::now() >= until {
+                return pending().await;
+            }
+        }
+        self.network.receive().await
+    }
+
     async fn loop_provisioned(&self) -> Result<Option<State>, DeviceError> {
         debug!("State: provisioned");
         self.transmit_provisioned_beacon().await.ok();
+        self.transmit_secure_beacons().await.ok();
+        self.transmit_heartbeat().await.ok();
+        self.transmit_health_current_status().await.ok();
+        self.iv_update_hour_elapsed().await?;
 
         let mut deadline = self.deadline.borrow_mut();
         let deadline_fut = deadline.next();
-        let receive_fut = self.network.receive();
+        let receive_fut = self.receive_provisioned();
         let outbound_fut = self.outbound.next();
 
         let mut ticker = Ticker::every(Duration::from_millis(100));
@@ -246,6 +581,12 @@ where
         drop(deadline);
 
         match result {
+            Either4::First(Ok(PDU::Beacon(beacon))) => {
+                self.configuration_manager
+                    .secure_network_beacon(&beacon)
+                    .await?;
+                Ok(None)
+            }
             Either4::First(Ok(inbound)) => {
                 self.pipeline
                     .borrow_mut()
@@ -256,7 +597,7 @@ where
                 OutboundEvent::Access(access) => {
                     self.pipeline
                         .borrow_mut()
-                        .process_outbound(self, &access, None, self.network_retransmit())
+                        .process_outbound(self, &access, None, None, self.network_retransmit())
                         .await?;
                     Ok(None)
                 }
@@ -264,6 +605,17 @@ where
                     self.publish(publish).await?;
                     Ok(None)
                 }
+                OutboundEvent::DeviceKey(message) => {
+                    self.send_device_key_message(message).await?;
+                    Ok(None)
+                }
+                OutboundEvent::Health(fault) => {
+                    let changed = self.health.borrow_mut().apply(fault);
+                    if changed {
+                        self.publish_health_current_status().await?;
+                    }
+                    Ok(None)
+                }
             },
             Either4::Third(expiration) => {
                 self.pipeline
@@ -304,6 +656,11 @@ where
         let ctx: AppElementsContext<'a> = AppElementsContext {
             access_sender: self.outbound.access.sender(),
             sender: self.outbound.publish.sender(),
+            device_key_sender: self.outbound.device_key.sender(),
+            health_sender: self.outbound.health.sender(),
+            configuration_responses: self.configuration_responses.receiver(),
+            transmission_failures: self.transmission_failures.receiver(),
+            message_ids: &self.message_ids,
             address: self.address().unwrap(),
         };
         self.elements.borrow_mut().connect(ctx);
@@ -314,12 +671,19 @@ where
         control: ChannelReceiver<'_, MeshNodeMessage>,
     ) -> Result<(), DeviceError> {
         let mut rng = self.rng.borrow_mut();
-        if let Err(e) = self.configuration_manager.initialize(&mut *rng).await {
+        let stores_key_pair = self.vault.stores_key_pair();
+        if let Err(e) = self
+            .configuration_manager
+            .initialize(&mut *rng, stores_key_pair)
+            .await
+        {
             // try again as a force reset
             error!("Error loading configuration {:?}", e);
             warn!("Unable to load configuration; attempting reset.");
             self.configuration_manager.reset();
-            self.configuration_manager.initialize(&mut *rng).await?
+            self.configuration_manager
+                .initialize(&mut *rng, stores_key_pair)
+                .await?
         }
 
         drop(rng);
@@ -0,0 +1,226 @@
+//! Out-of-band authentication of the provisioner, presenting values to and
+//! collecting values from the user of the device.
+
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::provisioning::{InputOOBAction, OutputOOBAction};
+use core::future::{ready, Future, Ready};
+use heapless::Vec;
+
+#[cfg(feature = "time")]
+use crate::traits::{button::Button, led::TextDisplay};
+#[cfg(feature = "time")]
+use embassy::time::{with_timeout, Duration};
+
+/// A value exchanged out-of-band while provisioning.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OobValue {
+    /// A number, or a count of events such as blinks or button pushes.
+    Numeric(u32),
+    /// Digits and capital letters, as ASCII.
+    Alphanumeric(Vec<u8, 8>),
+}
+
+/// Interaction with the user of the device to authenticate the provisioner,
+/// for the out-of-band actions advertised in the provisioning capabilities.
+pub trait OobHandler {
+    type OutputFuture<'m>: Future<Output = Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    /// Present `value` to the user by means of `action`, for them to enter
+    /// on the provisioner. The value may be presented repeatedly, as the
+    /// future is dropped once the provisioner has confirmed it.
+    fn output<'m>(
+        &'m mut self,
+        action: OutputOOBAction,
+        value: &'m OobValue,
+    ) -> Self::OutputFuture<'m>;
+
+    type InputFuture<'m>: Future<Output = Result<OobValue, DeviceError>>
+    where
+        Self: 'm;
+
+    /// Collect the value presented by the provisioner from the user by means
+    /// of `action`, being at most `size` digits or characters.
+    fn input<'m>(&'m mut self, action: InputOOBAction, size: u8) -> Self::InputFuture<'m>;
+
+    /// The static out-of-band value of the device, if it has one.
+    fn static_oob(&self) -> Option<[u8; 16]> {
+        None
+    }
+}
+
+/// For devices advertising no out-of-band actions.
+pub struct NoOob;
+
+impl OobHandler for NoOob {
+    type OutputFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn output<'m>(&'m mut self, _: OutputOOBAction, _: &'m OobValue) -> Self::OutputFuture<'m> {
+        ready(Err(DeviceError::InvalidState))
+    }
+
+    type InputFuture<'m> = Ready<Result<OobValue, DeviceError>>
+    where
+        Self: 'm;
+
+    fn input<'m>(&'m mut self, _: InputOOBAction, _: u8) -> Self::InputFuture<'m> {
+        ready(Err(DeviceError::InvalidState))
+    }
+}
+
+/// Time without a push after which the pushes counted so far are taken as
+/// the input.
+#[cfg(feature = "time")]
+pub const PUSH_INPUT_IDLE: Duration = Duration::from_secs(3);
+
+/// Presents output values on a text display, and counts pushes of a button
+/// as input.
+#[cfg(feature = "time")]
+pub struct DisplayButtonOob<D, B>
+where
+    D: TextDisplay,
+    B: Button,
+{
+    display: D,
+    button: B,
+    static_oob: Option<[u8; 16]>,
+}
+
+#[cfg(feature = "time")]
+impl<D, B> DisplayButtonOob<D, B>
+where
+    D: TextDisplay,
+    B: Button,
+{
+    pub fn new(display: D, button: B) -> Self {
+        Self {
+            display,
+            button,
+            static_oob: None,
+        }
+    }
+
+    pub fn with_static_oob(self, static_oob: [u8; 16]) -> Self {
+        Self {
+            static_oob: Some(static_oob),
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl<D, B> OobHandler for DisplayButtonOob<D, B>
+where
+    D: TextDisplay,
+    B: Button,
+{
+    type OutputFuture<'m> = impl Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn output<'m>(
+        &'m mut self,
+        action: OutputOOBAction,
+        value: &'m OobValue,
+    ) -> Self::OutputFuture<'m> {
+        async move { present_repeatedly(&mut self.display, action, value).await }
+    }
+
+    type InputFuture<'m> = impl Future<Output = Result<OobValue, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn input<'m>(&'m mut self, action: InputOOBAction, _: u8) -> Self::InputFuture<'m> {
+        async move {
+            match action {
+                InputOOBAction::Push | InputOOBAction::Twist => Ok(OobValue::Numeric(
+                    count_pushes(&mut self.button, PUSH_INPUT_IDLE).await,
+                )),
+                _ => Err(DeviceError::InvalidState),
+            }
+        }
+    }
+
+    fn static_oob(&self) -> Option<[u8; 16]> {
+        self.static_oob
+    }
+}
+
+/// Present `value` once on `display`, flashing a star for each event of a
+/// blink, beep or vibrate action, or scrolling a number or text.
+#[cfg(feature = "time")]
+pub async fn present<D: TextDisplay>(
+    display: &mut D,
+    action: OutputOOBAction,
+    value: &OobValue,
+) -> Result<(), DeviceError> {
+    let blink = Duration::from_millis(400);
+    match (action, value) {
+        (
+            OutputOOBAction::Blink | OutputOOBAction::Beep | OutputOOBAction::Vibrate,
+            OobValue::Numeric(events),
+        ) => {
+            for _ in 0..*events {
+                display
+                    .display('*', blink)
+                    .await
+                    .map_err(|_| DeviceError::TransmitError)?;
+                pause(display, blink).await?;
+            }
+        }
+        (_, OobValue::Numeric(number)) => {
+            let mut text: heapless::String<10> = heapless::String::new();
+            core::fmt::write(&mut text, format_args!("{}", number))
+                .map_err(|_| DeviceError::InsufficientBuffer)?;
+            display
+                .scroll(&text)
+                .await
+                .map_err(|_| DeviceError::TransmitError)?;
+        }
+        (_, OobValue::Alphanumeric(chars)) => {
+            let text = core::str::from_utf8(chars).map_err(|_| DeviceError::InvalidState)?;
+            display
+                .scroll(text)
+                .await
+                .map_err(|_| DeviceError::TransmitError)?;
+        }
+    }
+    Ok(())
+}
+
+/// Present `value` on `display` over and over, until dropped.
+#[cfg(feature = "time")]
+async fn present_repeatedly<D: TextDisplay>(
+    display: &mut D,
+    action: OutputOOBAction,
+    value: &OobValue,
+) -> Result<(), DeviceError> {
+    loop {
+        present(display, action, value).await?;
+        pause(display, Duration::from_secs(2)).await?;
+    }
+}
+
+/// Blank `display` for `duration`.
+#[cfg(feature = "time")]
+async fn pause<D: TextDisplay>(display: &mut D, duration: Duration) -> Result<(), DeviceError> {
+    display
+        .display(' ', duration)
+        .await
+        .map_err(|_| DeviceError::TransmitError)
+}
+
+/// Count pushes of `button`, until none for `idle` following the first.
+#[cfg(feature = "time")]
+pub async fn count_pushes<B: Button>(button: &mut B, idle: Duration) -> u32 {
+    button.wait_pressed().await;
+    let mut pushes = 1;
+    while with_timeout(idle, button.wait_pressed()).await.is_ok() {
+        pushes += 1;
+    }
+    pushes
+}
@@ -1,4 +1,7 @@
 use crate::drivers::ble::mesh::address::UnicastAddress;
+use crate::drivers::ble::mesh::config::device_keys::DeviceKey;
+use crate::drivers::ble::mesh::driver::node::health::HealthFault;
+use crate::drivers::ble::mesh::driver::node::sar::MessageId;
 use crate::drivers::ble::mesh::driver::node::NodeMutex;
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::transmit::ModelKey;
 use crate::drivers::ble::mesh::model::ModelIdentifier;
@@ -6,7 +9,7 @@ use crate::drivers::ble::mesh::pdu::access::{AccessMessage, AccessPayload};
 use core::marker::PhantomData;
 use embassy::channel::mpmc::{Channel, Sender};
 
-use embassy::util::{select, Either};
+use embassy::util::{select4, Either4};
 
 const MAX_MESSAGE: usize = 1;
 
@@ -40,6 +43,8 @@ impl<'a> OutboundAccessChannel<'a> {
 
 #[derive(Clone)]
 pub struct OutboundPublishMessage {
+    /// Set when published by an element, to report a failure to deliver it.
+    pub(crate) id: Option<MessageId>,
     pub(crate) element_address: UnicastAddress,
     pub(crate) model_identifier: ModelIdentifier,
     pub(crate) payload: AccessPayload,
@@ -78,9 +83,67 @@ impl<'a> OutboundPublishChannel<'a> {
 }
 // --
 
+/// A configuration client request, to be protected by the device key of the remote node.
+#[derive(Clone)]
+pub struct OutboundDeviceKeyMessage {
+    pub(crate) dst: UnicastAddress,
+    pub(crate) device_key: DeviceKey,
+    pub(crate) payload: AccessPayload,
+}
+
+pub(crate) struct OutboundDeviceKeyChannel<'a> {
+    channel: Channel<NodeMutex, OutboundDeviceKeyMessage, MAX_MESSAGE>,
+    _a: PhantomData<&'a ()>,
+}
+
+impl<'a> OutboundDeviceKeyChannel<'a> {
+    fn new() -> Self {
+        Self {
+            channel: Channel::new(),
+            _a: PhantomData,
+        }
+    }
+
+    async fn next(&self) -> OutboundDeviceKeyMessage {
+        self.channel.recv().await
+    }
+
+    pub(crate) fn sender(&'a self) -> Sender<'a, NodeMutex, OutboundDeviceKeyMessage, MAX_MESSAGE> {
+        self.channel.sender()
+    }
+}
+
+// --
+
+pub(crate) struct OutboundHealthChannel<'a> {
+    channel: Channel<NodeMutex, HealthFault, MAX_MESSAGE>,
+    _a: PhantomData<&'a ()>,
+}
+
+impl<'a> OutboundHealthChannel<'a> {
+    fn new() -> Self {
+        Self {
+            channel: Channel::new(),
+            _a: PhantomData,
+        }
+    }
+
+    async fn next(&self) -> HealthFault {
+        self.channel.recv().await
+    }
+
+    pub(crate) fn sender(&'a self) -> Sender<'a, NodeMutex, HealthFault, MAX_MESSAGE> {
+        self.channel.sender()
+    }
+}
+
+// --
+
 pub struct Outbound<'a> {
     pub(crate) access: OutboundAccessChannel<'a>,
     pub(crate) publish: OutboundPublishChannel<'a>,
+    pub(crate) device_key: OutboundDeviceKeyChannel<'a>,
+    pub(crate) health: OutboundHealthChannel<'a>,
 }
 
 impl<'a> Default for Outbound<'a> {
@@ -88,6 +151,8 @@ impl<'a> Default for Outbound<'a> {
         Self {
             access: OutboundAccessChannel::new(),
             publish: OutboundPublishChannel::new(),
+            device_key: OutboundDeviceKeyChannel::new(),
+            health: OutboundHealthChannel::new(),
         }
     }
 }
@@ -95,16 +160,22 @@ impl<'a> Default for Outbound<'a> {
 pub enum OutboundEvent {
     Access(AccessMessage),
     Publish(OutboundPublishMessage),
+    DeviceKey(OutboundDeviceKeyMessage),
+    Health(HealthFault),
 }
 
 impl<'a> Outbound<'a> {
     pub async fn next(&self) -> OutboundEvent {
         let access_fut = self.access.next();
         let publish_fut = self.publish.next();
-
-        match select(access_fut, publish_fut).await {
-            Either::First(access) => OutboundEvent::Access(access),
-            Either::Second(publish) => OutboundEvent::Publish(publish),
+        let device_key_fut = self.device_key.next();
+        let health_fut = self.health.next();
+
+        match select4(access_fut, publish_fut, device_key_fut, health_fut).await {
+            Either4::First(access) => OutboundEvent::Access(access),
+            Either4::Second(publish) => OutboundEvent::Publish(publish),
+            Either4::Third(device_key) => OutboundEvent::DeviceKey(device_key),
+            Either4::Fourth(fault) => OutboundEvent::Health(fault),
         }
     }
 }
@@ -0,0 +1,98 @@
+use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
+use crate::drivers::ble::mesh::driver::DeviceError;
+use atomic_polyfill::{AtomicU16, Ordering};
+use embassy::time::Duration;
+
+/// Maximum number of segments of a single segmented message, enough for the
+/// largest upper transport PDU.
+pub const MAX_SEGMENTS: usize = 32;
+
+/// Maximum number of outbound segmented messages awaiting acknowledgement.
+pub const MAX_OUTBOUND_IN_FLIGHT: usize = 3;
+
+/// Maximum number of inbound segmented messages being reassembled.
+pub const MAX_INBOUND_IN_FLIGHT: usize = 3;
+
+/// Timers and retry limits of the segmentation and reassembly (SAR) of
+/// lower transport messages.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SarConfig {
+    /// Delay between the segments of a message as they are first sent.
+    pub segment_interval: Duration,
+    /// Delay before retransmitting unacknowledged segments to a unicast address.
+    pub unicast_retransmit_interval: Duration,
+    /// Added to `unicast_retransmit_interval` for each hop allowed by the TTL.
+    pub unicast_retransmit_ttl_increment: Duration,
+    /// Retransmissions to a unicast address before the message has failed.
+    pub unicast_retransmit_count: u8,
+    /// Retransmissions to a unicast address without any segment newly
+    /// acknowledged before the message has failed.
+    pub unicast_retransmit_without_progress_count: u8,
+    /// Delay between the unacknowledged retransmissions to a group or virtual address.
+    pub multicast_retransmit_interval: Duration,
+    /// Retransmissions to a group or virtual address, which are never acknowledged.
+    pub multicast_retransmit_count: u8,
+    /// Inactivity after which an incomplete inbound message is discarded.
+    pub incomplete_timeout: Duration,
+}
+
+impl Default for SarConfig {
+    fn default() -> Self {
+        Self {
+            segment_interval: Duration::from_millis(20),
+            unicast_retransmit_interval: Duration::from_millis(200),
+            unicast_retransmit_ttl_increment: Duration::from_millis(50),
+            unicast_retransmit_count: 4,
+            unicast_retransmit_without_progress_count: 2,
+            multicast_retransmit_interval: Duration::from_millis(250),
+            multicast_retransmit_count: 2,
+            incomplete_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl SarConfig {
+    pub(crate) fn retransmit_interval(&self, dst: &Address, ttl: u8) -> Duration {
+        if let Address::Unicast(_) = dst {
+            self.unicast_retransmit_interval + self.unicast_retransmit_ttl_increment * ttl as u32
+        } else {
+            self.multicast_retransmit_interval
+        }
+    }
+}
+
+/// Identifies a message published by an element, as returned when
+/// publishing it and carried by its [`TransmissionFailure`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MessageId(pub(crate) u16);
+
+/// Hands out the identifiers of published messages, shared by the contexts
+/// of all the elements.
+pub(crate) struct MessageIds(AtomicU16);
+
+impl Default for MessageIds {
+    fn default() -> Self {
+        Self(AtomicU16::new(0))
+    }
+}
+
+impl MessageIds {
+    pub(crate) fn next(&self) -> MessageId {
+        MessageId(self.0.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A segmented message which could not be delivered, reported to the
+/// elements of the node.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TransmissionFailure {
+    /// The published message, if it was published by an element.
+    pub id: Option<MessageId>,
+    /// The element which sent the message.
+    pub src: UnicastAddress,
+    pub dst: Address,
+    pub error: DeviceError,
+}
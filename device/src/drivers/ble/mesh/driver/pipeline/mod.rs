@@ -1,17 +1,24 @@
 use crate::drivers::ble::mesh::driver::node::deadline::Expiration;
+use crate::drivers::ble::mesh::driver::node::oob::OobValue;
+use crate::drivers::ble::mesh::driver::node::sar::MessageId;
 use crate::drivers::ble::mesh::driver::node::State;
 use crate::drivers::ble::mesh::driver::pipeline::mesh::{
     NetworkRetransmitDetails, PublishRetransmitDetails,
 };
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::transmit::ModelKey;
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::{
-    ProvisionedContext, ProvisionedPipeline,
+    Bearer, ProvisionedContext, ProvisionedPipeline,
+};
+use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::{
+    OobRequest, UnprovisionedContext,
 };
-use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::UnprovisionedContext;
 use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::UnprovisionedPipeline;
 use crate::drivers::ble::mesh::driver::DeviceError;
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::interface::ProxyMessage;
 use crate::drivers::ble::mesh::interface::PDU;
 use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+use crate::drivers::ble::mesh::pdu::upper::UpperControl;
 use crate::drivers::ble::mesh::provisioning::Capabilities;
 
 pub mod mesh;
@@ -46,13 +53,21 @@ impl PipelineInner {
                     Ok(None)
                 }
             }
-            PipelineInner::Provisioned(inner) => {
-                if let PDU::Network(ref mut pdu) = message {
-                    inner.process_inbound(ctx, pdu).await
-                } else {
+            PipelineInner::Provisioned(inner) => match message {
+                PDU::Network(ref mut pdu) => {
+                    inner.process_inbound(ctx, pdu, Bearer::Advertising).await
+                }
+                #[cfg(feature = "ble-mesh-proxy")]
+                PDU::Proxy(ProxyMessage::Network(ref mut pdu)) => {
+                    inner.process_inbound(ctx, pdu, Bearer::Proxy).await
+                }
+                #[cfg(feature = "ble-mesh-proxy")]
+                PDU::Proxy(ProxyMessage::Configuration(ref pdu)) => {
+                    inner.process_proxy_configuration(ctx, pdu).await?;
                     Ok(None)
                 }
-            }
+                _ => Ok(None),
+            },
         }
     }
 
@@ -61,6 +76,24 @@ impl PipelineInner {
         ctx: &C,
         message: &AccessMessage,
         publish: Option<(ModelKey, PublishRetransmitDetails)>,
+        id: Option<MessageId>,
+        network_retransmit: NetworkRetransmitDetails,
+    ) -> Result<(), DeviceError> {
+        match self {
+            PipelineInner::Unconfigured => Err(DeviceError::NotProvisioned),
+            PipelineInner::Unprovisioned(_) => Err(DeviceError::NotProvisioned),
+            PipelineInner::Provisioned(inner) => {
+                inner
+                    .process_outbound(ctx, message, publish, id, network_retransmit)
+                    .await
+            }
+        }
+    }
+
+    async fn process_outbound_control<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        control: UpperControl,
         network_retransmit: NetworkRetransmitDetails,
     ) -> Result<(), DeviceError> {
         match self {
@@ -68,7 +101,7 @@ impl PipelineInner {
             PipelineInner::Unprovisioned(_) => Err(DeviceError::NotProvisioned),
             PipelineInner::Provisioned(inner) => {
                 inner
-                    .process_outbound(ctx, message, publish, network_retransmit)
+                    .process_outbound_control(ctx, control, network_retransmit)
                     .await
             }
         }
@@ -140,10 +173,22 @@ impl Pipeline {
         ctx: &C,
         message: &AccessMessage,
         publish: Option<(ModelKey, PublishRetransmitDetails)>,
+        id: Option<MessageId>,
+        network_retransmit: NetworkRetransmitDetails,
+    ) -> Result<(), DeviceError> {
+        self.inner
+            .process_outbound(ctx, message, publish, id, network_retransmit)
+            .await
+    }
+
+    pub async fn process_outbound_control<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        control: UpperControl,
         network_retransmit: NetworkRetransmitDetails,
     ) -> Result<(), DeviceError> {
         self.inner
-            .process_outbound(ctx, message, publish, network_retransmit)
+            .process_outbound_control(ctx, control, network_retransmit)
             .await
     }
 
@@ -154,4 +199,23 @@ impl Pipeline {
     ) -> Result<(), DeviceError> {
         self.inner.retransmit(ctx, expiration).await
     }
+
+    /// Interaction with the user awaited while being provisioned.
+    pub(crate) fn oob_request(&self) -> Option<OobRequest> {
+        match &self.inner {
+            PipelineInner::Unprovisioned(inner) => inner.oob_request().cloned(),
+            _ => None,
+        }
+    }
+
+    pub(crate) async fn oob_complete<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        input: Option<OobValue>,
+    ) -> Result<(), DeviceError> {
+        match &mut self.inner {
+            PipelineInner::Unprovisioned(inner) => inner.oob_complete(ctx, input).await,
+            _ => Ok(()),
+        }
+    }
 }
@@ -0,0 +1,943 @@
+use crate::drivers::ble::mesh::address::{Address, GroupAddress, UnicastAddress};
+use crate::drivers::ble::mesh::beacon::Flags;
+use crate::drivers::ble::mesh::config::network::NetworkKeyHandle;
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::{control_pdu, LowerContext};
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::NetKeyIndex;
+use crate::drivers::ble::mesh::pdu::friend::{
+    FriendClear, FriendOffer, FriendPoll, FriendRequest, FriendSubscriptionList,
+    FriendSubscriptionListConfirm, FriendUpdate,
+};
+use crate::drivers::ble::mesh::pdu::lower::{LowerControl, LowerControlMessage, LowerPDU, Opcode};
+use crate::drivers::ble::mesh::pdu::network::CleartextNetworkPDU;
+use embassy::time::{Duration, Instant};
+use heapless::Vec;
+
+/// Low Power nodes befriended at once.
+const MAX_FRIENDSHIPS: usize = 2;
+
+/// Network PDUs buffered for each Low Power node.
+const FRIEND_QUEUE_SIZE: usize = 8;
+
+/// Addresses each Low Power node may add to its subscription list.
+const FRIEND_SUBSCRIPTION_LIST_SIZE: usize = 8;
+
+/// Milliseconds a Low Power node is asked to listen for a response.
+const RECEIVE_WINDOW: u8 = 100;
+
+/// Shortest delay before offering friendship.
+const MIN_OFFER_DELAY: Duration = Duration::from_millis(100);
+
+/// How long an offer stands without a poll establishing the friendship.
+const OFFER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Delay before the first repetition of a Friend Clear, doubled after each.
+const FRIEND_CLEAR_REPEAT: Duration = Duration::from_secs(1);
+
+/// Messages sent at once when deadlines are processed.
+const MAX_RESPONSES: usize = 2 * MAX_FRIENDSHIPS;
+
+pub trait FriendContext: LowerContext {
+    fn is_friend_enabled(&self) -> bool;
+
+    fn friend_deadline(&self, deadline: Option<Instant>);
+
+    /// The flags and IV index a secure network beacon of the subnet carries.
+    fn beacon_state(&self, net_key_index: &NetKeyIndex) -> Result<(Flags, u32), DeviceError>;
+}
+
+#[derive(Copy, Clone)]
+enum Response {
+    Offer,
+    Poll,
+    SubscriptionListConfirm(u8),
+}
+
+struct Friendship {
+    lpn_address: UnicastAddress,
+    num_elements: u8,
+    lpn_counter: u16,
+    friend_counter: u16,
+    previous_address: Option<UnicastAddress>,
+    receive_delay: Duration,
+    poll_timeout: Duration,
+    network_key: NetworkKeyHandle,
+    credentials: NetworkKeyHandle,
+    established: bool,
+    expires: Instant,
+    response: Option<(Instant, Response)>,
+    fsn: Option<bool>,
+    /// The head of the queue was delivered, and is dropped once the next
+    /// poll acknowledges it.
+    delivered: bool,
+    transaction_number: Option<u8>,
+    subscriptions: Vec<Address, FRIEND_SUBSCRIPTION_LIST_SIZE>,
+    queue: Vec<CleartextNetworkPDU, FRIEND_QUEUE_SIZE>,
+}
+
+impl Friendship {
+    fn is_lpn_address(&self, addr: &Address) -> bool {
+        if let Address::Unicast(addr) = addr {
+            *addr >= self.lpn_address && *addr < self.lpn_address + self.num_elements
+        } else {
+            false
+        }
+    }
+
+    fn is_destined(&self, dst: &Address) -> bool {
+        self.is_lpn_address(dst) || self.subscriptions.contains(dst)
+    }
+
+    fn poll(&mut self, now: Instant, poll: FriendPoll) {
+        if self.fsn.is_some() && self.fsn != Some(poll.fsn) && self.delivered {
+            // the previous message made it to the Low Power node.
+            self.queue.remove(0);
+        }
+        self.delivered = false;
+        self.fsn = Some(poll.fsn);
+        self.polled(now, Response::Poll);
+    }
+
+    fn subscription_list(
+        &mut self,
+        now: Instant,
+        opcode: Opcode,
+        list: FriendSubscriptionList,
+    ) -> Result<(), DeviceError> {
+        if self.transaction_number != Some(list.transaction_number) {
+            self.transaction_number = Some(list.transaction_number);
+            for address in list.addresses {
+                if opcode == Opcode::FriendSubscriptionListAdd {
+                    if !self.subscriptions.contains(&address) {
+                        self.subscriptions
+                            .push(address)
+                            .map_err(|_| DeviceError::InsufficientBuffer)?;
+                    }
+                } else if let Some(position) = self.subscriptions.iter().position(|e| *e == address)
+                {
+                    self.subscriptions.remove(position);
+                }
+            }
+        }
+        // confirmed again if the Low Power node missed the previous one.
+        self.polled(
+            now,
+            Response::SubscriptionListConfirm(list.transaction_number),
+        );
+        Ok(())
+    }
+
+    fn polled(&mut self, now: Instant, response: Response) {
+        self.established = true;
+        self.expires = now + self.poll_timeout;
+        self.response = Some((now + self.receive_delay, response));
+    }
+
+    fn enqueue(&mut self, pdu: &CleartextNetworkPDU) {
+        if self.queue.is_full() {
+            // the oldest message is discarded to make room.
+            self.queue.remove(0);
+            self.delivered = false;
+        }
+        let mut pdu = pdu.clone();
+        pdu.ttl -= 1;
+        pdu.network_key = self.credentials;
+        pdu.nid = self.credentials.nid;
+        self.queue.push(pdu).ok();
+    }
+
+    fn deadline(&self) -> Instant {
+        match self.response {
+            Some((at, _)) if at < self.expires => at,
+            _ => self.expires,
+        }
+    }
+}
+
+/// A Friend Clear repeated to the previous friend of a Low Power node until
+/// it confirms, or twice the poll timeout has passed.
+struct Clear {
+    network_key: NetworkKeyHandle,
+    previous_address: UnicastAddress,
+    clear: FriendClear,
+    next: Instant,
+    repeat: Duration,
+    expires: Instant,
+}
+
+impl Clear {
+    async fn pdu<C: FriendContext>(&self, ctx: &C) -> Result<CleartextNetworkPDU, DeviceError> {
+        let mut parameters = Vec::new();
+        self.clear.emit(&mut parameters)?;
+        let network_key = ctx.transmit_network_key(&self.network_key.key_index)?;
+        control_pdu(
+            ctx,
+            network_key,
+            self.previous_address.into(),
+            ctx.default_ttl(),
+            Opcode::FriendClear,
+            parameters,
+        )
+        .await
+    }
+
+    fn deadline(&self) -> Instant {
+        if self.next < self.expires {
+            self.next
+        } else {
+            self.expires
+        }
+    }
+}
+
+/// The friend side of friendships with Low Power nodes, queueing the
+/// messages destined to each until it polls for them.
+pub struct Friendships {
+    friendships: Vec<Friendship, MAX_FRIENDSHIPS>,
+    clears: Vec<Clear, MAX_FRIENDSHIPS>,
+    friend_counter: u16,
+}
+
+impl Default for Friendships {
+    fn default() -> Self {
+        Self {
+            friendships: Vec::new(),
+            clears: Vec::new(),
+            friend_counter: 0,
+        }
+    }
+}
+
+impl Friendships {
+    /// Friendship credentials a Low Power node may secure its messages with.
+    pub fn credentials(&self) -> Vec<NetworkKeyHandle, MAX_FRIENDSHIPS> {
+        self.friendships.iter().map(|e| e.credentials).collect()
+    }
+
+    /// Process an inbound network PDU, returning whether it was a friendship
+    /// message consumed here, along with any response to send right away.
+    pub async fn process_inbound<C: FriendContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &CleartextNetworkPDU,
+    ) -> Result<(bool, Option<CleartextNetworkPDU>), DeviceError> {
+        if !ctx.is_friend_enabled() {
+            self.friendships.clear();
+            self.clears.clear();
+            return Ok((false, None));
+        }
+
+        let now = Instant::now();
+        if let LowerPDU::Control(LowerControl {
+            opcode,
+            message: LowerControlMessage::Unsegmented { parameters },
+        }) = &pdu.transport_pdu
+        {
+            match opcode {
+                Opcode::FriendRequest => {
+                    if pdu.dst == Address::Group(GroupAddress::AllFriends) {
+                        self.request(ctx, now, pdu, FriendRequest::parse(parameters)?)?;
+                    }
+                    return Ok((true, None));
+                }
+                Opcode::FriendPoll => {
+                    let poll = FriendPoll::parse(parameters)?;
+                    let mut clear_previous = None;
+                    if let Some(friendship) = self.find_by_credentials(pdu) {
+                        if !friendship.established {
+                            clear_previous = friendship
+                                .previous_address
+                                .filter(|previous| {
+                                    !ctx.is_local_unicast(&Address::Unicast(*previous))
+                                })
+                                .map(|previous_address| Clear {
+                                    network_key: friendship.network_key,
+                                    previous_address,
+                                    clear: FriendClear {
+                                        lpn_address: friendship.lpn_address,
+                                        lpn_counter: friendship.lpn_counter,
+                                    },
+                                    next: now + FRIEND_CLEAR_REPEAT,
+                                    repeat: FRIEND_CLEAR_REPEAT,
+                                    expires: now + friendship.poll_timeout * 2,
+                                });
+                        }
+                        friendship.poll(now, poll);
+                    }
+                    let mut response = None;
+                    if let Some(clear) = clear_previous {
+                        // have the previous friend let go of the Low Power node.
+                        response.replace(clear.pdu(ctx).await?);
+                        if self.clears.is_full() {
+                            self.clears.remove(0);
+                        }
+                        self.clears.push(clear).ok();
+                    }
+                    self.reschedule(ctx);
+                    return Ok((true, response));
+                }
+                Opcode::FriendSubscriptionListAdd | Opcode::FriendSubscriptionListRemove => {
+                    let list = FriendSubscriptionList::parse(parameters)?;
+                    if let Some(friendship) = self.find_by_credentials(pdu) {
+                        friendship.subscription_list(now, *opcode, list)?;
+                    }
+                    self.reschedule(ctx);
+                    return Ok((true, None));
+                }
+                Opcode::FriendClear => {
+                    let clear = FriendClear::parse(parameters)?;
+                    if let Some(position) = self.friendships.iter().position(|e| {
+                        e.lpn_address == clear.lpn_address
+                            && clear.lpn_counter.wrapping_sub(e.lpn_counter) <= 255
+                    }) {
+                        self.friendships.remove(position);
+                        if ctx.is_local_unicast(&Address::Unicast(pdu.src)) {
+                            return Ok((true, None));
+                        }
+                        let mut parameters = Vec::new();
+                        clear.emit(&mut parameters)?;
+                        let network_key = ctx.transmit_network_key(&pdu.network_key.key_index)?;
+                        let confirm = control_pdu(
+                            ctx,
+                            network_key,
+                            pdu.src.into(),
+                            ctx.default_ttl(),
+                            Opcode::FriendClearConfirm,
+                            parameters,
+                        )
+                        .await?;
+                        return Ok((true, Some(confirm)));
+                    }
+                    return Ok((true, None));
+                }
+                Opcode::FriendClearConfirm => {
+                    let confirm = FriendClear::parse(parameters)?;
+                    if let Some(position) = self.clears.iter().position(|e| {
+                        e.previous_address == pdu.src
+                            && e.clear.lpn_address == confirm.lpn_address
+                            && e.clear.lpn_counter == confirm.lpn_counter
+                    }) {
+                        self.clears.remove(position);
+                        self.reschedule(ctx);
+                    }
+                    return Ok((true, None));
+                }
+                _ => {}
+            }
+        }
+
+        if pdu.ttl >= 2 {
+            for friendship in self
+                .friendships
+                .iter_mut()
+                .filter(|e| e.established && !e.is_lpn_address(&Address::Unicast(pdu.src)))
+            {
+                if friendship.is_destined(&pdu.dst) {
+                    friendship.enqueue(pdu);
+                }
+            }
+        }
+        Ok((false, None))
+    }
+
+    /// Send the responses that are due, and let go of Low Power nodes that
+    /// stopped polling.
+    pub async fn process_deadline<C: FriendContext>(
+        &mut self,
+        ctx: &C,
+    ) -> Result<Vec<CleartextNetworkPDU, MAX_RESPONSES>, DeviceError> {
+        let now = Instant::now();
+        let mut responses = Vec::new();
+        for friendship in self.friendships.iter_mut() {
+            match friendship.response {
+                Some((at, response)) if at <= now => {
+                    friendship.response.take();
+                    if let Some(pdu) = Self::respond(ctx, friendship, response).await? {
+                        responses
+                            .push(pdu)
+                            .map_err(|_| DeviceError::InsufficientBuffer)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        while let Some(position) = self
+            .friendships
+            .iter()
+            .position(|e| e.response.is_none() && e.expires <= now)
+        {
+            debug!(
+                "friendship with {:?} ended",
+                self.friendships[position].lpn_address
+            );
+            self.friendships.remove(position);
+        }
+
+        self.clears.retain(|e| e.expires > now);
+        for clear in self.clears.iter_mut().filter(|e| e.next <= now) {
+            responses
+                .push(clear.pdu(ctx).await?)
+                .map_err(|_| DeviceError::InsufficientBuffer)?;
+            clear.next = now + clear.repeat;
+            clear.repeat = clear.repeat * 2;
+        }
+
+        self.reschedule(ctx);
+        Ok(responses)
+    }
+
+    fn request<C: FriendContext>(
+        &mut self,
+        ctx: &C,
+        now: Instant,
+        pdu: &CleartextNetworkPDU,
+        request: FriendRequest,
+    ) -> Result<(), DeviceError> {
+        if request.criteria.min_queue_size() > FRIEND_QUEUE_SIZE {
+            return Ok(());
+        }
+
+        // a repeated request replaces any friendship with the Low Power node.
+        if let Some(position) = self
+            .friendships
+            .iter()
+            .position(|e| e.lpn_address == pdu.src)
+        {
+            self.friendships.remove(position);
+        }
+        if self.friendships.is_full() {
+            return Ok(());
+        }
+
+        let friend_counter = self.friend_counter;
+        self.friend_counter = self.friend_counter.wrapping_add(1);
+
+        let credentials = pdu.network_key.friendship_credentials(
+            pdu.src,
+            ctx.primary_unicast_address()?,
+            request.lpn_counter,
+            friend_counter,
+        )?;
+
+        // without the RSSI of the request, only the receive window is weighed.
+        let offer_delay = Duration::from_millis(
+            request
+                .criteria
+                .receive_window_factor
+                .apply(RECEIVE_WINDOW as u32) as u64,
+        );
+        let offer_delay = if offer_delay < MIN_OFFER_DELAY {
+            MIN_OFFER_DELAY
+        } else {
+            offer_delay
+        };
+
+        self.friendships
+            .push(Friendship {
+                lpn_address: pdu.src,
+                num_elements: request.num_elements,
+                lpn_counter: request.lpn_counter,
+                friend_counter,
+                previous_address: request.previous_address,
+                receive_delay: request.receive_delay(),
+                poll_timeout: request.poll_timeout(),
+                network_key: pdu.network_key,
+                credentials,
+                established: false,
+                expires: now + offer_delay + OFFER_TIMEOUT,
+                response: Some((now + offer_delay, Response::Offer)),
+                fsn: None,
+                delivered: false,
+                transaction_number: None,
+                subscriptions: Vec::new(),
+                queue: Vec::new(),
+            })
+            .ok();
+        self.reschedule(ctx);
+        Ok(())
+    }
+
+    async fn respond<C: FriendContext>(
+        ctx: &C,
+        friendship: &mut Friendship,
+        response: Response,
+    ) -> Result<Option<CleartextNetworkPDU>, DeviceError> {
+        let mut parameters = Vec::new();
+        match response {
+            Response::Offer => {
+                FriendOffer {
+                    receive_window: RECEIVE_WINDOW,
+                    queue_size: FRIEND_QUEUE_SIZE as u8,
+                    subscription_list_size: FRIEND_SUBSCRIPTION_LIST_SIZE as u8,
+                    rssi: 0,
+                    friend_counter: friendship.friend_counter,
+                }
+                .emit(&mut parameters)?;
+                let network_key = ctx.transmit_network_key(&friendship.network_key.key_index)?;
+                Ok(Some(
+                    control_pdu(
+                        ctx,
+                        network_key,
+                        friendship.lpn_address.into(),
+                        0,
+                        Opcode::FriendOffer,
+                        parameters,
+                    )
+                    .await?,
+                ))
+            }
+            Response::Poll => {
+                if let Some(pdu) = friendship.queue.first() {
+                    friendship.delivered = true;
+                    Ok(Some(pdu.clone()))
+                } else {
+                    let (flags, iv_index) = ctx.beacon_state(&friendship.network_key.key_index)?;
+                    FriendUpdate {
+                        flags,
+                        iv_index,
+                        md: false,
+                    }
+                    .emit(&mut parameters)?;
+                    Ok(Some(
+                        control_pdu(
+                            ctx,
+                            friendship.credentials,
+                            friendship.lpn_address.into(),
+                            0,
+                            Opcode::FriendUpdate,
+                            parameters,
+                        )
+                        .await?,
+                    ))
+                }
+            }
+            Response::SubscriptionListConfirm(transaction_number) => {
+                FriendSubscriptionListConfirm { transaction_number }.emit(&mut parameters)?;
+                Ok(Some(
+                    control_pdu(
+                        ctx,
+                        friendship.credentials,
+                        friendship.lpn_address.into(),
+                        0,
+                        Opcode::FriendSubscriptionListConfirm,
+                        parameters,
+                    )
+                    .await?,
+                ))
+            }
+        }
+    }
+
+    fn find_by_credentials(&mut self, pdu: &CleartextNetworkPDU) -> Option<&mut Friendship> {
+        self.friendships.iter_mut().find(|e| {
+            e.lpn_address == pdu.src
+                && e.credentials.nid == pdu.network_key.nid
+                && e.credentials.encryption_key == pdu.network_key.encryption_key
+        })
+    }
+
+    fn reschedule<C: FriendContext>(&self, ctx: &C) {
+        ctx.friend_deadline(
+            self.friendships
+                .iter()
+                .map(|e| e.deadline())
+                .chain(self.clears.iter().map(|e| e.deadline()))
+                .min(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::driver::pipeline::provisioned::test_context::{
+        TestContext, DEFAULT_TTL,
+    };
+    use crate::drivers::ble::mesh::pdu::friend::{Factor, FriendCriteria};
+    use crate::drivers::ble::mesh::pdu::lower::{LowerAccess, LowerAccessMessage};
+    use futures::executor::block_on;
+
+    const FRIEND: u16 = 0x0001;
+    const LPN: u16 = 0x0100;
+    const PREVIOUS_FRIEND: u16 = 0x0200;
+
+    impl FriendContext for TestContext {
+        fn is_friend_enabled(&self) -> bool {
+            true
+        }
+
+        fn friend_deadline(&self, deadline: Option<Instant>) {
+            self.deadline.set(deadline);
+        }
+
+        fn beacon_state(&self, _: &NetKeyIndex) -> Result<(Flags, u32), DeviceError> {
+            Ok((
+                Flags {
+                    key_refresh: false,
+                    iv_update: false,
+                },
+                0,
+            ))
+        }
+    }
+
+    fn addr(value: u16) -> UnicastAddress {
+        UnicastAddress::parse(value.to_be_bytes()).unwrap()
+    }
+
+    fn control(
+        network_key: NetworkKeyHandle,
+        src: u16,
+        dst: Address,
+        opcode: Opcode,
+        parameters: Vec<u8, 11>,
+    ) -> CleartextNetworkPDU {
+        CleartextNetworkPDU {
+            network_key,
+            ivi: 0,
+            nid: network_key.nid,
+            ttl: 0,
+            seq: 0,
+            src: addr(src),
+            dst,
+            transport_pdu: LowerPDU::Control(LowerControl {
+                opcode,
+                message: LowerControlMessage::Unsegmented { parameters },
+            }),
+        }
+    }
+
+    fn access(ctx: &TestContext, src: u16, dst: u16, ttl: u8, seq: u32) -> CleartextNetworkPDU {
+        CleartextNetworkPDU {
+            network_key: ctx.network_key,
+            ivi: 0,
+            nid: ctx.network_key.nid,
+            ttl,
+            seq,
+            src: addr(src),
+            dst: addr(dst).into(),
+            transport_pdu: LowerPDU::Access(LowerAccess {
+                akf: true,
+                aid: 0x01u8.into(),
+                message: LowerAccessMessage::Unsegmented(Vec::from_slice(&[0x00; 8]).unwrap()),
+            }),
+        }
+    }
+
+    fn opcode(pdu: &CleartextNetworkPDU) -> Option<(Opcode, &[u8])> {
+        match &pdu.transport_pdu {
+            LowerPDU::Control(LowerControl {
+                opcode,
+                message: LowerControlMessage::Unsegmented { parameters },
+            }) => Some((*opcode, parameters)),
+            _ => None,
+        }
+    }
+
+    /// Process the responses due once the Low Power node's receive delay passed.
+    fn respond(
+        ctx: &TestContext,
+        friendships: &mut Friendships,
+    ) -> Vec<CleartextNetworkPDU, MAX_RESPONSES> {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        block_on(friendships.process_deadline(ctx)).unwrap()
+    }
+
+    /// Befriend the Low Power node, returning the friendship credentials.
+    fn befriend(
+        ctx: &TestContext,
+        friendships: &mut Friendships,
+        previous_address: Option<u16>,
+    ) -> NetworkKeyHandle {
+        let mut parameters = Vec::new();
+        FriendRequest {
+            criteria: FriendCriteria {
+                rssi_factor: Factor::Ten,
+                receive_window_factor: Factor::Ten,
+                min_queue_size_log: 2,
+            },
+            receive_delay: 10,
+            poll_timeout: 10,
+            previous_address: previous_address.map(addr),
+            num_elements: 1,
+            lpn_counter: 7,
+        }
+        .emit(&mut parameters)
+        .unwrap();
+        let request = control(
+            ctx.network_key,
+            LPN,
+            Address::Group(GroupAddress::AllFriends),
+            Opcode::FriendRequest,
+            parameters,
+        );
+        assert!(matches!(
+            block_on(friendships.process_inbound(ctx, &request)),
+            Ok((true, None))
+        ));
+
+        // the offer is only sent once the offer delay has passed.
+        assert!(block_on(friendships.process_deadline(ctx))
+            .unwrap()
+            .is_empty());
+        std::thread::sleep(std::time::Duration::from_millis(110));
+        let responses = block_on(friendships.process_deadline(ctx)).unwrap();
+        assert_eq!(responses.len(), 1);
+        let (opcode, parameters) = opcode(&responses[0]).unwrap();
+        assert!(opcode == Opcode::FriendOffer);
+        assert_eq!(responses[0].ttl, 0);
+        let offer = FriendOffer::parse(parameters).unwrap();
+        ctx.network_key
+            .friendship_credentials(addr(LPN), ctx.address, 7, offer.friend_counter)
+            .unwrap()
+    }
+
+    fn poll(
+        ctx: &TestContext,
+        friendships: &mut Friendships,
+        credentials: NetworkKeyHandle,
+        fsn: bool,
+    ) -> Option<CleartextNetworkPDU> {
+        let mut parameters = Vec::new();
+        FriendPoll { fsn }.emit(&mut parameters).unwrap();
+        let poll = control(
+            credentials,
+            LPN,
+            addr(FRIEND).into(),
+            Opcode::FriendPoll,
+            parameters,
+        );
+        let (consumed, response) = block_on(friendships.process_inbound(ctx, &poll)).unwrap();
+        assert!(consumed);
+        response
+    }
+
+    fn polled(
+        ctx: &TestContext,
+        friendships: &mut Friendships,
+        credentials: NetworkKeyHandle,
+        fsn: bool,
+    ) -> CleartextNetworkPDU {
+        assert!(poll(ctx, friendships, credentials, fsn).is_none());
+        let mut responses = respond(ctx, friendships);
+        assert_eq!(responses.len(), 1);
+        let response = responses.pop().unwrap();
+        // secured with the friendship credentials.
+        assert_eq!(response.nid, credentials.nid);
+        assert_eq!(
+            response.network_key.encryption_key,
+            credentials.encryption_key
+        );
+        response
+    }
+
+    fn is_update(pdu: &CleartextNetworkPDU) -> bool {
+        match opcode(pdu) {
+            Some((Opcode::FriendUpdate, parameters)) => {
+                !FriendUpdate::parse(parameters).unwrap().md
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn poll_and_update() {
+        let ctx = TestContext::new(FRIEND);
+        let mut friendships = Friendships::default();
+        let credentials = befriend(&ctx, &mut friendships, None);
+        assert_eq!(friendships.credentials().len(), 1);
+
+        let update = polled(&ctx, &mut friendships, credentials, false);
+        assert!(is_update(&update));
+        assert_eq!(update.ttl, 0);
+        assert_eq!(update.dst, Address::Unicast(addr(LPN)));
+        assert!(friendships.friendships[0].established);
+
+        // nothing is sent until the next poll.
+        assert!(respond(&ctx, &mut friendships).is_empty());
+        let update = polled(&ctx, &mut friendships, credentials, true);
+        assert!(is_update(&update));
+
+        // a poll secured with the subnet's credentials is not the friend's.
+        assert!(poll(&ctx, &mut friendships, ctx.network_key, false).is_none());
+        assert!(respond(&ctx, &mut friendships).is_empty());
+    }
+
+    #[test]
+    fn queueing() {
+        let ctx = TestContext::new(FRIEND);
+        let mut friendships = Friendships::default();
+        let credentials = befriend(&ctx, &mut friendships, None);
+        assert!(is_update(&polled(
+            &ctx,
+            &mut friendships,
+            credentials,
+            false
+        )));
+
+        let destined = |seq| access(&ctx, 0x0300, LPN, 5, seq);
+        for seq in 1..=2 {
+            assert!(matches!(
+                block_on(friendships.process_inbound(&ctx, &destined(seq))),
+                Ok((false, None))
+            ));
+        }
+        // neither messages that can't be relayed, nor those of the Low
+        // Power node itself, nor those destined to other nodes are queued.
+        for pdu in [
+            access(&ctx, 0x0300, LPN, 1, 3),
+            access(&ctx, LPN, 0x0300, 5, 4),
+            access(&ctx, 0x0300, 0x0400, 5, 5),
+        ] {
+            assert!(matches!(
+                block_on(friendships.process_inbound(&ctx, &pdu)),
+                Ok((false, None))
+            ));
+        }
+        assert_eq!(friendships.friendships[0].queue.len(), 2);
+
+        // queued messages are relayed with the friendship credentials.
+        let first = polled(&ctx, &mut friendships, credentials, true);
+        assert_eq!(first.seq, 1);
+        assert_eq!(first.ttl, 4);
+        assert_eq!(first.nid, credentials.nid);
+
+        // sent again if the Low Power node did not toggle its FSN.
+        assert_eq!(polled(&ctx, &mut friendships, credentials, true).seq, 1);
+        assert_eq!(polled(&ctx, &mut friendships, credentials, false).seq, 2);
+        assert!(is_update(&polled(
+            &ctx,
+            &mut friendships,
+            credentials,
+            true
+        )));
+        assert!(friendships.friendships[0].queue.is_empty());
+
+        // the oldest message is discarded once the queue is full.
+        for seq in 10..10 + FRIEND_QUEUE_SIZE as u32 + 1 {
+            block_on(friendships.process_inbound(&ctx, &destined(seq))).unwrap();
+        }
+        assert_eq!(polled(&ctx, &mut friendships, credentials, false).seq, 11);
+    }
+
+    #[test]
+    fn clear() {
+        let ctx = TestContext::new(FRIEND);
+        let mut friendships = Friendships::default();
+        let credentials = befriend(&ctx, &mut friendships, None);
+        assert!(is_update(&polled(
+            &ctx,
+            &mut friendships,
+            credentials,
+            false
+        )));
+
+        // the new friend of the Low Power node has it let go.
+        let mut parameters = Vec::new();
+        FriendClear {
+            lpn_address: addr(LPN),
+            lpn_counter: 8,
+        }
+        .emit(&mut parameters)
+        .unwrap();
+        let clear = control(
+            ctx.network_key,
+            0x0300,
+            addr(FRIEND).into(),
+            Opcode::FriendClear,
+            parameters,
+        );
+        let (consumed, confirm) = block_on(friendships.process_inbound(&ctx, &clear)).unwrap();
+        assert!(consumed);
+        let confirm = confirm.unwrap();
+        let (opcode, parameters) = opcode(&confirm).unwrap();
+        assert!(opcode == Opcode::FriendClearConfirm);
+        assert_eq!(FriendClear::parse(parameters).unwrap().lpn_counter, 8);
+        assert_eq!(confirm.ttl, DEFAULT_TTL);
+        assert_eq!(confirm.dst, Address::Unicast(addr(0x0300)));
+        assert_eq!(confirm.nid, ctx.network_key.nid);
+
+        assert!(friendships.credentials().is_empty());
+        assert!(poll(&ctx, &mut friendships, credentials, true).is_none());
+        assert!(respond(&ctx, &mut friendships).is_empty());
+    }
+
+    #[test]
+    fn clear_previous_friend() {
+        let ctx = TestContext::new(FRIEND);
+        let mut friendships = Friendships::default();
+        let credentials = befriend(&ctx, &mut friendships, Some(PREVIOUS_FRIEND));
+
+        let is_clear = |pdu: &CleartextNetworkPDU| match opcode(pdu) {
+            Some((Opcode::FriendClear, parameters)) => {
+                let clear = FriendClear::parse(parameters).unwrap();
+                pdu.dst == Address::Unicast(addr(PREVIOUS_FRIEND))
+                    && pdu.ttl == DEFAULT_TTL
+                    && pdu.nid == ctx.network_key.nid
+                    && clear.lpn_address == addr(LPN)
+                    && clear.lpn_counter == 7
+            }
+            _ => false,
+        };
+
+        // the first poll establishes the friendship.
+        assert!(is_clear(
+            &poll(&ctx, &mut friendships, credentials, false).unwrap()
+        ));
+        let responses = respond(&ctx, &mut friendships);
+        assert_eq!(responses.len(), 1);
+        assert!(is_update(&responses[0]));
+
+        // repeated, backing off, until confirmed.
+        assert_eq!(friendships.clears.len(), 1);
+        friendships.clears[0].next = Instant::now();
+        let responses = respond(&ctx, &mut friendships);
+        assert_eq!(responses.len(), 1);
+        assert!(is_clear(&responses[0]));
+        assert_eq!(friendships.clears[0].repeat, FRIEND_CLEAR_REPEAT * 2);
+        assert!(ctx.deadline.get().unwrap() <= friendships.clears[0].next);
+
+        // a confirmation from another node is not the one waited for.
+        let mut parameters = Vec::new();
+        FriendClear {
+            lpn_address: addr(LPN),
+            lpn_counter: 7,
+        }
+        .emit(&mut parameters)
+        .unwrap();
+        let confirm = |src| {
+            control(
+                ctx.network_key,
+                src,
+                addr(FRIEND).into(),
+                Opcode::FriendClearConfirm,
+                parameters.clone(),
+            )
+        };
+        assert!(matches!(
+            block_on(friendships.process_inbound(&ctx, &confirm(0x0300))),
+            Ok((true, None))
+        ));
+        assert_eq!(friendships.clears.len(), 1);
+        assert!(matches!(
+            block_on(friendships.process_inbound(&ctx, &confirm(PREVIOUS_FRIEND))),
+            Ok((true, None))
+        ));
+        assert!(friendships.clears.is_empty());
+
+        // nor repeated past twice the poll timeout without a confirmation.
+        let mut friendships = Friendships::default();
+        let credentials = befriend(&ctx, &mut friendships, Some(PREVIOUS_FRIEND));
+        assert!(poll(&ctx, &mut friendships, credentials, false).is_some());
+        assert_eq!(
+            friendships.clears[0].expires,
+            friendships.clears[0].next - FRIEND_CLEAR_REPEAT + Duration::from_secs(2)
+        );
+        friendships.clears[0].next = Instant::now();
+        friendships.clears[0].expires = Instant::now();
+        let responses = respond(&ctx, &mut friendships);
+        assert!(responses.iter().all(|e| !is_clear(e)));
+        assert!(friendships.clears.is_empty());
+    }
+}
@@ -1,10 +1,13 @@
 use crate::drivers::ble::mesh::address::UnicastAddress;
+use crate::drivers::ble::mesh::driver::node::sar::{MAX_INBOUND_IN_FLIGHT, MAX_SEGMENTS};
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::MAX_SEGMENTED_ACCESS_PDU;
 use crate::drivers::ble::mesh::driver::DeviceError;
 use crate::drivers::ble::mesh::InsufficientBuffer;
+use embassy::time::{Duration, Instant};
 use heapless::Vec;
 
 pub struct InboundSegmentation {
-    in_flight: [Option<InFlight>; 3],
+    in_flight: [Option<InFlight>; MAX_INBOUND_IN_FLIGHT],
 }
 
 impl Default for InboundSegmentation {
@@ -23,10 +26,20 @@ impl InboundSegmentation {
         seg_o: u8,
         seg_n: u8,
         segment_m: &Vec<u8, 12>,
-    ) -> Result<(u32, Option<Vec<u8, 380>>), DeviceError> {
+        incomplete_timeout: Duration,
+    ) -> Result<(u32, Option<Vec<u8, MAX_SEGMENTED_ACCESS_PDU>>), DeviceError> {
+        let now = Instant::now();
+        // discard messages which stopped receiving segments.
+        for entry in self.in_flight.iter_mut() {
+            if matches!(entry, Some(in_flight) if in_flight.expires < now) {
+                entry.take();
+            }
+        }
+
         let in_flight_index = self.find_or_create_in_flight(src, seq_zero, seg_n)?;
 
         if let Some(in_flight) = &mut self.in_flight[in_flight_index] {
+            in_flight.expires = now + incomplete_timeout;
             if let Some(all) = in_flight.process_inbound(seg_o, segment_m)? {
                 let block_ack = in_flight.block_ack();
                 self.in_flight[in_flight_index] = None;
@@ -74,7 +87,8 @@ struct InFlight {
     src: UnicastAddress,
     seq_zero: u16,
     seg_n: u8,
-    segments: Vec<Option<Vec<u8, 12>>, 16>,
+    segments: Vec<Option<Vec<u8, 12>>, MAX_SEGMENTS>,
+    expires: Instant,
 }
 
 impl InFlight {
@@ -89,6 +103,7 @@ impl InFlight {
             seq_zero,
             seg_n,
             segments,
+            expires: Instant::now(),
         }
     }
 
@@ -107,7 +122,10 @@ impl InFlight {
         &mut self,
         seg_n: u8,
         segment_m: &Vec<u8, 12>,
-    ) -> Result<Option<Vec<u8, 380>>, InsufficientBuffer> {
+    ) -> Result<Option<Vec<u8, MAX_SEGMENTED_ACCESS_PDU>>, InsufficientBuffer> {
+        if seg_n as usize >= self.segments.len() {
+            return Err(InsufficientBuffer);
+        }
         if matches!(self.segments[seg_n as usize], None) {
             let mut inner = Vec::new();
             inner
@@ -131,3 +149,84 @@ impl InFlight {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    const SEQ_ZERO: u16 = 0x0010;
+
+    fn src() -> UnicastAddress {
+        UnicastAddress::parse([0x00, 0x02]).unwrap()
+    }
+
+    fn segment(seg_o: u8) -> Vec<u8, 12> {
+        Vec::from_slice(&[seg_o; 12]).unwrap()
+    }
+
+    #[test]
+    fn reassemble_largest() {
+        let mut inbound = InboundSegmentation::default();
+        let seg_n = (MAX_SEGMENTS - 1) as u8;
+        for seg_o in (0..=seg_n).rev() {
+            let (block_ack, payload) = inbound
+                .process_inbound(
+                    src(),
+                    SEQ_ZERO,
+                    seg_o,
+                    seg_n,
+                    &segment(seg_o),
+                    Duration::from_secs(10),
+                )
+                .unwrap();
+            if seg_o == 0 {
+                assert_eq!(block_ack, u32::MAX);
+                let payload = payload.unwrap();
+                assert_eq!(payload.len(), MAX_SEGMENTED_ACCESS_PDU);
+                assert_eq!(payload[12], 1);
+                assert_eq!(payload[MAX_SEGMENTED_ACCESS_PDU - 1], seg_n);
+            } else {
+                assert!(payload.is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_segment() {
+        let mut inbound = InboundSegmentation::default();
+        let timeout = Duration::from_secs(10);
+        let (block_ack, _) = inbound
+            .process_inbound(src(), SEQ_ZERO, 1, 2, &segment(1), timeout)
+            .unwrap();
+        assert_eq!(block_ack, 0b010);
+        let (block_ack, payload) = inbound
+            .process_inbound(src(), SEQ_ZERO, 1, 2, &segment(1), timeout)
+            .unwrap();
+        assert_eq!(block_ack, 0b010);
+        assert!(payload.is_none());
+
+        // a segment beyond the last one.
+        assert!(inbound
+            .process_inbound(src(), SEQ_ZERO, 3, 2, &segment(3), timeout)
+            .is_err());
+    }
+
+    #[test]
+    fn incomplete_timeout() {
+        let mut inbound = InboundSegmentation::default();
+        let timeout = Duration::from_millis(1);
+        inbound
+            .process_inbound(src(), SEQ_ZERO, 0, 1, &segment(0), timeout)
+            .unwrap();
+
+        sleep(std::time::Duration::from_millis(5));
+
+        // the first segment has been discarded along with the message.
+        let (block_ack, payload) = inbound
+            .process_inbound(src(), SEQ_ZERO, 1, 1, &segment(1), timeout)
+            .unwrap();
+        assert_eq!(block_ack, 0b10);
+        assert!(payload.is_none());
+    }
+}
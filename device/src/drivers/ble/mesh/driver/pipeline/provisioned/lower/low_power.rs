@@ -0,0 +1,729 @@
+use crate::drivers::ble::mesh::address::{Address, GroupAddress, UnicastAddress};
+use crate::drivers::ble::mesh::config::network::NetworkKeyHandle;
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::{control_pdu, LowerContext};
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::NetKeyIndex;
+use crate::drivers::ble::mesh::pdu::friend::{
+    Factor, FriendCriteria, FriendOffer, FriendPoll, FriendRequest, FriendSubscriptionList,
+    FriendSubscriptionListConfirm, FriendUpdate, MAX_SUBSCRIPTION_LIST_ADDRESSES,
+};
+use crate::drivers::ble::mesh::pdu::lower::{LowerControl, LowerControlMessage, LowerPDU, Opcode};
+use crate::drivers::ble::mesh::pdu::network::CleartextNetworkPDU;
+use embassy::time::{Duration, Instant};
+use heapless::Vec;
+
+/// Addresses the friend is asked to subscribe to on behalf of this node.
+pub const LPN_SUBSCRIPTION_LIST_SIZE: usize = 8;
+
+/// Milliseconds the friend waits before responding to a request or poll.
+const RECEIVE_DELAY: u8 = 100;
+
+/// Longest time the friend waits between polls, in units of 100 milliseconds.
+const POLL_TIMEOUT: u32 = 300;
+
+/// How often the friend is polled for queued messages.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Requests sent without a response before the friendship is considered lost.
+const POLL_ATTEMPTS: u8 = 3;
+
+/// How long offers are collected after requesting a friend.
+const OFFER_WINDOW: Duration = Duration::from_millis(1100);
+
+/// How long to wait before requesting a friend again when none offered.
+const REQUEST_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long to wait before trying again when a request could not be sent.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When the node listens to the bearers.
+#[derive(Copy, Clone)]
+pub enum Listen {
+    /// Without a friend, the node listens like any other.
+    Always,
+    /// Once befriended, the radio sleeps outside of the receive window.
+    Until(Instant),
+}
+
+pub trait LowPowerContext: LowerContext {
+    fn number_of_elements(&self) -> u8;
+
+    /// Group and virtual addresses any model of the node is subscribed to.
+    fn subscription_addresses(&self) -> Vec<Address, LPN_SUBSCRIPTION_LIST_SIZE>;
+
+    fn low_power_listen(&self, listen: Listen);
+
+    fn low_power_deadline(&self, deadline: Option<Instant>);
+}
+
+struct Offer {
+    friend_address: UnicastAddress,
+    network_key: NetworkKeyHandle,
+    offer: FriendOffer,
+}
+
+impl Offer {
+    fn is_better_than(&self, other: &Offer) -> bool {
+        // a shorter receive window keeps the radio asleep for longer.
+        self.offer.receive_window < other.offer.receive_window
+            || (self.offer.receive_window == other.offer.receive_window
+                && self.offer.rssi > other.offer.rssi)
+    }
+}
+
+#[derive(Clone)]
+enum Request {
+    Poll,
+    SubscriptionList(Opcode, FriendSubscriptionList),
+}
+
+struct Friendship {
+    friend_address: UnicastAddress,
+    credentials: NetworkKeyHandle,
+    receive_window: Duration,
+    fsn: bool,
+    transaction_number: u8,
+    /// Addresses the friend confirmed subscribing to.
+    subscriptions: Vec<Address, LPN_SUBSCRIPTION_LIST_SIZE>,
+    /// The request awaiting a response, along with how many times it was sent.
+    pending: Option<(Request, u8)>,
+}
+
+impl Friendship {
+    /// Whether the friend secured a PDU, which may be a queued message from
+    /// another node.
+    fn matches(&self, pdu: &CleartextNetworkPDU) -> bool {
+        self.credentials.nid == pdu.network_key.nid
+            && self.credentials.encryption_key == pdu.network_key.encryption_key
+    }
+
+    /// The subscription list change to make next, if any, otherwise a poll.
+    fn next_request<C: LowPowerContext>(&self, ctx: &C) -> Request {
+        let subscriptions = ctx.subscription_addresses();
+
+        let mut list = FriendSubscriptionList {
+            transaction_number: self.transaction_number,
+            addresses: Vec::new(),
+        };
+        for address in self
+            .subscriptions
+            .iter()
+            .filter(|e| !subscriptions.contains(e))
+            .take(MAX_SUBSCRIPTION_LIST_ADDRESSES)
+        {
+            list.addresses.push(*address).ok();
+        }
+        if !list.addresses.is_empty() {
+            return Request::SubscriptionList(Opcode::FriendSubscriptionListRemove, list);
+        }
+
+        for address in subscriptions
+            .iter()
+            .filter(|e| !self.subscriptions.contains(e))
+            .take(MAX_SUBSCRIPTION_LIST_ADDRESSES)
+        {
+            list.addresses.push(*address).ok();
+        }
+        if !list.addresses.is_empty() {
+            return Request::SubscriptionList(Opcode::FriendSubscriptionListAdd, list);
+        }
+
+        Request::Poll
+    }
+
+    fn confirm(&mut self, confirm: FriendSubscriptionListConfirm) {
+        if let Some((Request::SubscriptionList(opcode, list), _)) = &self.pending {
+            if list.transaction_number != confirm.transaction_number {
+                return;
+            }
+            if *opcode == Opcode::FriendSubscriptionListAdd {
+                for address in list.addresses.iter() {
+                    self.subscriptions.push(*address).ok();
+                }
+            } else {
+                self.subscriptions.retain(|e| !list.addresses.contains(e));
+            }
+            self.transaction_number = self.transaction_number.wrapping_add(1);
+            self.pending.take();
+        }
+    }
+}
+
+enum State {
+    Idle,
+    Requesting(Option<Offer>),
+    Befriended(Friendship),
+}
+
+/// The Low Power node side of a friendship, sleeping the radio and polling
+/// its friend for the messages it queued in the meantime.
+pub struct LowPower {
+    state: State,
+    next: Option<Instant>,
+    lpn_counter: u16,
+    previous_address: Option<UnicastAddress>,
+}
+
+impl Default for LowPower {
+    fn default() -> Self {
+        Self {
+            state: State::Idle,
+            next: None,
+            lpn_counter: 0,
+            previous_address: None,
+        }
+    }
+}
+
+impl LowPower {
+    /// Friendship credentials the friend secures its messages with.
+    pub fn credentials(&self) -> Vec<NetworkKeyHandle, 1> {
+        let mut credentials = Vec::new();
+        if let State::Befriended(friendship) = &self.state {
+            credentials.push(friendship.credentials).ok();
+        }
+        credentials
+    }
+
+    /// Process an inbound network PDU, returning whether it was a friendship
+    /// message consumed here, along with any request to send right away.
+    pub async fn process_inbound<C: LowPowerContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &CleartextNetworkPDU,
+    ) -> Result<(bool, Option<CleartextNetworkPDU>), DeviceError> {
+        let now = Instant::now();
+        let control = match &pdu.transport_pdu {
+            LowerPDU::Control(LowerControl {
+                opcode,
+                message: LowerControlMessage::Unsegmented { parameters },
+            }) => Some((*opcode, parameters)),
+            _ => None,
+        };
+
+        match &mut self.state {
+            State::Requesting(best) => {
+                if let Some((Opcode::FriendOffer, parameters)) = control {
+                    if pdu.dst == Address::Unicast(ctx.primary_unicast_address()?) {
+                        let offer = Offer {
+                            friend_address: pdu.src,
+                            network_key: pdu.network_key,
+                            offer: FriendOffer::parse(parameters)?,
+                        };
+                        if !matches!(best, Some(best) if !offer.is_better_than(best)) {
+                            best.replace(offer);
+                        }
+                    }
+                    return Ok((true, None));
+                }
+                Ok((false, None))
+            }
+            State::Befriended(friendship) if friendship.matches(pdu) => {
+                // only the responses to a poll move the friend sequence number on.
+                let (consumed, more) = match control {
+                    Some((Opcode::FriendUpdate, parameters)) => {
+                        let update = FriendUpdate::parse(parameters)?;
+                        friendship.pending.take();
+                        friendship.fsn = !friendship.fsn;
+                        (true, update.md)
+                    }
+                    Some((Opcode::FriendSubscriptionListConfirm, parameters)) => {
+                        friendship.confirm(FriendSubscriptionListConfirm::parse(parameters)?);
+                        (true, false)
+                    }
+                    _ => {
+                        // a queued message, and possibly more behind it.
+                        friendship.pending.take();
+                        friendship.fsn = !friendship.fsn;
+                        (false, true)
+                    }
+                };
+
+                let request = friendship.next_request(ctx);
+                let pdu = if more || !matches!(request, Request::Poll) {
+                    Some(self.request(ctx, now, request, 0).await?)
+                } else {
+                    self.sleep(ctx, now);
+                    None
+                };
+                Ok((consumed, pdu))
+            }
+            _ => Ok((false, None)),
+        }
+    }
+
+    /// Request a friend, settle on an offer, or poll the friend, depending
+    /// on where the friendship stands.
+    pub async fn process_deadline<C: LowPowerContext>(
+        &mut self,
+        ctx: &C,
+    ) -> Result<Option<CleartextNetworkPDU>, DeviceError> {
+        let now = Instant::now();
+        if matches!(self.next, Some(next) if now < next) {
+            // woken by an earlier deadline.
+            self.reschedule(ctx);
+            return Ok(None);
+        }
+        self.next.take();
+
+        let result = self.process_expired(ctx, now).await;
+        if result.is_err() && self.next.is_none() {
+            self.next.replace(now + RETRY_INTERVAL);
+            self.reschedule(ctx);
+        }
+        result
+    }
+
+    async fn process_expired<C: LowPowerContext>(
+        &mut self,
+        ctx: &C,
+        now: Instant,
+    ) -> Result<Option<CleartextNetworkPDU>, DeviceError> {
+        match &mut self.state {
+            State::Idle => Ok(Some(self.request_friend(ctx, now).await?)),
+            State::Requesting(best) => {
+                if let Some(offer) = best.take() {
+                    debug!("befriended by {:?}", offer.friend_address);
+                    let credentials = offer.network_key.friendship_credentials(
+                        ctx.primary_unicast_address()?,
+                        offer.friend_address,
+                        self.lpn_counter.wrapping_sub(1),
+                        offer.offer.friend_counter,
+                    )?;
+                    self.state = State::Befriended(Friendship {
+                        friend_address: offer.friend_address,
+                        credentials,
+                        receive_window: Duration::from_millis(offer.offer.receive_window as u64),
+                        fsn: false,
+                        transaction_number: 0,
+                        subscriptions: Vec::new(),
+                        pending: None,
+                    });
+                    // the first poll establishes the friendship.
+                    Ok(Some(self.request(ctx, now, Request::Poll, 0).await?))
+                } else {
+                    self.state = State::Idle;
+                    self.next.replace(now + REQUEST_INTERVAL);
+                    self.reschedule(ctx);
+                    Ok(None)
+                }
+            }
+            State::Befriended(friendship) => match friendship.pending.clone() {
+                Some((_, attempts)) if attempts >= POLL_ATTEMPTS => {
+                    debug!("friendship with {:?} lost", friendship.friend_address);
+                    self.previous_address.replace(friendship.friend_address);
+                    Ok(Some(self.request_friend(ctx, now).await?))
+                }
+                Some((request, attempts)) => {
+                    // no response within the receive window.
+                    Ok(Some(self.request(ctx, now, request, attempts).await?))
+                }
+                None => {
+                    let request = friendship.next_request(ctx);
+                    Ok(Some(self.request(ctx, now, request, 0).await?))
+                }
+            },
+        }
+    }
+
+    async fn request_friend<C: LowPowerContext>(
+        &mut self,
+        ctx: &C,
+        now: Instant,
+    ) -> Result<CleartextNetworkPDU, DeviceError> {
+        let lpn_counter = self.lpn_counter;
+        self.lpn_counter = self.lpn_counter.wrapping_add(1);
+
+        let mut parameters = Vec::new();
+        FriendRequest {
+            criteria: FriendCriteria {
+                rssi_factor: Factor::Ten,
+                receive_window_factor: Factor::Ten,
+                min_queue_size_log: 2,
+            },
+            receive_delay: RECEIVE_DELAY,
+            poll_timeout: POLL_TIMEOUT,
+            previous_address: self.previous_address,
+            num_elements: ctx.number_of_elements(),
+            lpn_counter,
+        }
+        .emit(&mut parameters)?;
+
+        let network_key = ctx.transmit_network_key(&NetKeyIndex::new(0))?;
+        let pdu = control_pdu(
+            ctx,
+            network_key,
+            Address::Group(GroupAddress::AllFriends),
+            0,
+            Opcode::FriendRequest,
+            parameters,
+        )
+        .await?;
+
+        self.state = State::Requesting(None);
+        self.next.replace(now + OFFER_WINDOW);
+        ctx.low_power_listen(Listen::Always);
+        self.reschedule(ctx);
+        Ok(pdu)
+    }
+
+    async fn request<C: LowPowerContext>(
+        &mut self,
+        ctx: &C,
+        now: Instant,
+        request: Request,
+        attempts: u8,
+    ) -> Result<CleartextNetworkPDU, DeviceError> {
+        if let State::Befriended(friendship) = &mut self.state {
+            let mut parameters = Vec::new();
+            let opcode = match &request {
+                Request::Poll => {
+                    FriendPoll {
+                        fsn: friendship.fsn,
+                    }
+                    .emit(&mut parameters)?;
+                    Opcode::FriendPoll
+                }
+                Request::SubscriptionList(opcode, list) => {
+                    list.emit(&mut parameters)?;
+                    *opcode
+                }
+            };
+            let pdu = control_pdu(
+                ctx,
+                friendship.credentials,
+                friendship.friend_address.into(),
+                0,
+                opcode,
+                parameters,
+            )
+            .await?;
+
+            // listen from now on, a little earlier than the friend responds.
+            let window_end =
+                now + Duration::from_millis(RECEIVE_DELAY as u64) + friendship.receive_window;
+            friendship.pending.replace((request, attempts + 1));
+            self.next.replace(window_end);
+            ctx.low_power_listen(Listen::Until(window_end));
+            self.reschedule(ctx);
+            Ok(pdu)
+        } else {
+            Err(DeviceError::InvalidState)
+        }
+    }
+
+    fn sleep<C: LowPowerContext>(&mut self, ctx: &C, now: Instant) {
+        self.next.replace(now + POLL_INTERVAL);
+        ctx.low_power_listen(Listen::Until(now));
+        self.reschedule(ctx);
+    }
+
+    fn reschedule<C: LowPowerContext>(&self, ctx: &C) {
+        ctx.low_power_deadline(self.next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::beacon::Flags;
+    use crate::drivers::ble::mesh::driver::pipeline::provisioned::test_context::TestContext;
+    use crate::drivers::ble::mesh::pdu::lower::{LowerAccess, LowerAccessMessage};
+    use futures::executor::block_on;
+
+    const LPN: u16 = 0x0100;
+    const FRIEND: u16 = 0x0001;
+
+    impl LowPowerContext for TestContext {
+        fn number_of_elements(&self) -> u8 {
+            self.number_of_elements
+        }
+
+        fn subscription_addresses(&self) -> Vec<Address, LPN_SUBSCRIPTION_LIST_SIZE> {
+            self.subscriptions.borrow().iter().cloned().collect()
+        }
+
+        fn low_power_listen(&self, _: Listen) {}
+
+        fn low_power_deadline(&self, deadline: Option<Instant>) {
+            self.deadline.set(deadline);
+        }
+    }
+
+    fn addr(value: u16) -> UnicastAddress {
+        UnicastAddress::parse(value.to_be_bytes()).unwrap()
+    }
+
+    fn group() -> Address {
+        Address::parse([0xC0, 0x00])
+    }
+
+    fn control(
+        network_key: NetworkKeyHandle,
+        src: u16,
+        opcode: Opcode,
+        parameters: Vec<u8, 11>,
+    ) -> CleartextNetworkPDU {
+        CleartextNetworkPDU {
+            network_key,
+            ivi: 0,
+            nid: network_key.nid,
+            ttl: 0,
+            seq: 0,
+            src: addr(src),
+            dst: addr(LPN).into(),
+            transport_pdu: LowerPDU::Control(LowerControl {
+                opcode,
+                message: LowerControlMessage::Unsegmented { parameters },
+            }),
+        }
+    }
+
+    fn offer(
+        ctx: &TestContext,
+        src: u16,
+        receive_window: u8,
+        rssi: i8,
+        friend_counter: u16,
+    ) -> CleartextNetworkPDU {
+        let mut parameters = Vec::new();
+        FriendOffer {
+            receive_window,
+            queue_size: 8,
+            subscription_list_size: 8,
+            rssi,
+            friend_counter,
+        }
+        .emit(&mut parameters)
+        .unwrap();
+        control(ctx.network_key, src, Opcode::FriendOffer, parameters)
+    }
+
+    fn update(credentials: NetworkKeyHandle, md: bool) -> CleartextNetworkPDU {
+        let mut parameters = Vec::new();
+        FriendUpdate {
+            flags: Flags {
+                key_refresh: false,
+                iv_update: false,
+            },
+            iv_index: 0,
+            md,
+        }
+        .emit(&mut parameters)
+        .unwrap();
+        control(credentials, FRIEND, Opcode::FriendUpdate, parameters)
+    }
+
+    fn confirm(credentials: NetworkKeyHandle, transaction_number: u8) -> CleartextNetworkPDU {
+        let mut parameters = Vec::new();
+        FriendSubscriptionListConfirm { transaction_number }
+            .emit(&mut parameters)
+            .unwrap();
+        control(
+            credentials,
+            FRIEND,
+            Opcode::FriendSubscriptionListConfirm,
+            parameters,
+        )
+    }
+
+    fn request(pdu: &CleartextNetworkPDU) -> (Opcode, Vec<u8, 11>) {
+        match &pdu.transport_pdu {
+            LowerPDU::Control(LowerControl {
+                opcode,
+                message: LowerControlMessage::Unsegmented { parameters },
+            }) => (*opcode, parameters.clone()),
+            _ => panic!("not an unsegmented control message"),
+        }
+    }
+
+    fn fsn(pdu: &CleartextNetworkPDU) -> bool {
+        let (opcode, parameters) = request(pdu);
+        assert!(opcode == Opcode::FriendPoll);
+        FriendPoll::parse(&parameters).unwrap().fsn
+    }
+
+    /// Process the deadline as if it had passed.
+    fn expire(ctx: &TestContext, low_power: &mut LowPower) -> Option<CleartextNetworkPDU> {
+        low_power.next.replace(Instant::now());
+        block_on(low_power.process_deadline(ctx)).unwrap()
+    }
+
+    /// Be befriended by `FRIEND`, returning the friendship credentials and
+    /// the first poll.
+    fn befriend(
+        ctx: &TestContext,
+        low_power: &mut LowPower,
+    ) -> (NetworkKeyHandle, CleartextNetworkPDU) {
+        expire(ctx, low_power).unwrap();
+        block_on(low_power.process_inbound(ctx, &offer(ctx, FRIEND, 100, -50, 3))).unwrap();
+        let poll = expire(ctx, low_power).unwrap();
+        let credentials = ctx
+            .network_key
+            .friendship_credentials(ctx.address, addr(FRIEND), 0, 3)
+            .unwrap();
+        (credentials, poll)
+    }
+
+    #[test]
+    fn offer_selection() {
+        let ctx = TestContext::new(LPN);
+        let mut low_power = LowPower::default();
+
+        let friend_request = block_on(low_power.process_deadline(&ctx)).unwrap().unwrap();
+        let (opcode, parameters) = request(&friend_request);
+        assert!(opcode == Opcode::FriendRequest);
+        assert_eq!(friend_request.ttl, 0);
+        assert_eq!(friend_request.dst, Address::Group(GroupAddress::AllFriends));
+        let friend_request = FriendRequest::parse(&parameters).unwrap();
+        assert_eq!(friend_request.lpn_counter, 0);
+        assert_eq!(friend_request.previous_address, None);
+        assert!(ctx.deadline.get().is_some());
+
+        // the shortest receive window wins, then the strongest signal.
+        for (src, receive_window, rssi) in
+            [(0x0002, 100, -40), (0x0003, 50, -80), (0x0004, 50, -60)]
+        {
+            let offer = offer(&ctx, src, receive_window, rssi, src);
+            assert!(matches!(
+                block_on(low_power.process_inbound(&ctx, &offer)),
+                Ok((true, None))
+            ));
+        }
+        // offers to other Low Power nodes are not for us.
+        let mut other = offer(&ctx, 0x0005, 10, 0, 0x0005);
+        other.dst = addr(0x0200).into();
+        block_on(low_power.process_inbound(&ctx, &other)).unwrap();
+
+        let poll = expire(&ctx, &mut low_power).unwrap();
+        let credentials = ctx
+            .network_key
+            .friendship_credentials(ctx.address, addr(0x0004), 0, 0x0004)
+            .unwrap();
+        assert_eq!(poll.dst, Address::Unicast(addr(0x0004)));
+        assert_eq!(poll.nid, credentials.nid);
+        assert_eq!(poll.ttl, 0);
+        assert!(!fsn(&poll));
+        assert_eq!(low_power.credentials().len(), 1);
+    }
+
+    #[test]
+    fn no_offer() {
+        let ctx = TestContext::new(LPN);
+        let mut low_power = LowPower::default();
+        assert!(expire(&ctx, &mut low_power).is_some());
+        let now = Instant::now();
+        assert!(expire(&ctx, &mut low_power).is_none());
+        assert!(matches!(low_power.state, State::Idle));
+        assert!(ctx.deadline.get().unwrap() >= now + REQUEST_INTERVAL);
+
+        // the next request counts up.
+        let (_, parameters) = request(&expire(&ctx, &mut low_power).unwrap());
+        assert_eq!(FriendRequest::parse(&parameters).unwrap().lpn_counter, 1);
+    }
+
+    #[test]
+    fn poll_and_fsn() {
+        let ctx = TestContext::new(LPN);
+        let mut low_power = LowPower::default();
+        let (credentials, poll) = befriend(&ctx, &mut low_power);
+        assert!(!fsn(&poll));
+
+        // nothing queued, so the node sleeps until the next poll.
+        let now = Instant::now();
+        assert!(matches!(
+            block_on(low_power.process_inbound(&ctx, &update(credentials, false))),
+            Ok((true, None))
+        ));
+        assert!(ctx.deadline.get().unwrap() >= now + POLL_INTERVAL);
+        let poll = expire(&ctx, &mut low_power).unwrap();
+        assert!(fsn(&poll));
+
+        // a queued message is passed on, and polled past right away.
+        let queued = CleartextNetworkPDU {
+            network_key: credentials,
+            ivi: 0,
+            nid: credentials.nid,
+            ttl: 4,
+            seq: 1,
+            src: addr(0x0300),
+            dst: addr(LPN).into(),
+            transport_pdu: LowerPDU::Access(LowerAccess {
+                akf: true,
+                aid: 0x01u8.into(),
+                message: LowerAccessMessage::Unsegmented(Vec::from_slice(&[0x00; 8]).unwrap()),
+            }),
+        };
+        let (consumed, poll) = block_on(low_power.process_inbound(&ctx, &queued)).unwrap();
+        assert!(!consumed);
+        assert!(!fsn(&poll.unwrap()));
+
+        // more data makes the node poll again.
+        let (consumed, poll) =
+            block_on(low_power.process_inbound(&ctx, &update(credentials, true))).unwrap();
+        assert!(consumed);
+        assert!(fsn(&poll.unwrap()));
+
+        // without a response, the same poll is repeated.
+        let poll = expire(&ctx, &mut low_power).unwrap();
+        assert!(fsn(&poll));
+        assert!(fsn(&expire(&ctx, &mut low_power).unwrap()));
+
+        // until the friendship is considered lost.
+        let (opcode, parameters) = request(&expire(&ctx, &mut low_power).unwrap());
+        assert!(opcode == Opcode::FriendRequest);
+        assert_eq!(
+            FriendRequest::parse(&parameters).unwrap().previous_address,
+            Some(addr(FRIEND))
+        );
+        assert!(low_power.credentials().is_empty());
+    }
+
+    #[test]
+    fn subscription_list() {
+        let ctx = TestContext::new(LPN);
+        ctx.subscriptions.borrow_mut().push(group()).unwrap();
+        let mut low_power = LowPower::default();
+        let (credentials, _) = befriend(&ctx, &mut low_power);
+
+        // once established, the friend is asked to subscribe.
+        let (consumed, add) =
+            block_on(low_power.process_inbound(&ctx, &update(credentials, false))).unwrap();
+        assert!(consumed);
+        let (opcode, parameters) = request(&add.unwrap());
+        assert!(opcode == Opcode::FriendSubscriptionListAdd);
+        let list = FriendSubscriptionList::parse(&parameters).unwrap();
+        assert_eq!(list.transaction_number, 0);
+        assert_eq!(&list.addresses[..], &[group()]);
+
+        // a confirmation of another transaction does not count.
+        let (_, add) = block_on(low_power.process_inbound(&ctx, &confirm(credentials, 7))).unwrap();
+        assert!(request(&add.unwrap()).0 == Opcode::FriendSubscriptionListAdd);
+        assert!(matches!(
+            block_on(low_power.process_inbound(&ctx, &confirm(credentials, 0))),
+            Ok((true, None))
+        ));
+        if let State::Befriended(friendship) = &low_power.state {
+            assert_eq!(&friendship.subscriptions[..], &[group()]);
+        } else {
+            panic!("not befriended");
+        }
+
+        // confirmations are not responses to a poll.
+        assert!(fsn(&expire(&ctx, &mut low_power).unwrap()));
+
+        // and unsubscribing removes the address again.
+        ctx.subscriptions.borrow_mut().clear();
+        block_on(low_power.process_inbound(&ctx, &update(credentials, false))).unwrap();
+        let (opcode, parameters) = request(&expire(&ctx, &mut low_power).unwrap());
+        assert!(opcode == Opcode::FriendSubscriptionListRemove);
+        assert_eq!(
+            FriendSubscriptionList::parse(&parameters)
+                .unwrap()
+                .transaction_number,
+            1
+        );
+        block_on(low_power.process_inbound(&ctx, &confirm(credentials, 1))).unwrap();
+        assert!(!fsn(&expire(&ctx, &mut low_power).unwrap()));
+    }
+}
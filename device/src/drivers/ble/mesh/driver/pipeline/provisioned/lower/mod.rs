@@ -1,6 +1,11 @@
 mod inbound_segmentation;
 mod outbound_segmentation;
 
+#[cfg(feature = "ble-mesh-friend")]
+pub mod friend;
+#[cfg(feature = "ble-mesh-lpn")]
+pub mod low_power;
+
 use crate::drivers::ble::mesh::driver::DeviceError;
 use crate::drivers::ble::mesh::pdu::lower::{
     LowerAccess, LowerAccessMessage, LowerControl, LowerControlMessage, LowerPDU, Opcode, SzMic,
@@ -9,13 +14,20 @@ use crate::drivers::ble::mesh::pdu::network::CleartextNetworkPDU;
 use ccm::aead::Buffer;
 
 use self::inbound_segmentation::InboundSegmentation;
-use crate::drivers::ble::mesh::address::{Address, LabelUuid};
+use crate::drivers::ble::mesh::address::{Address, LabelUuid, UnicastAddress};
 use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+use crate::drivers::ble::mesh::config::device_keys::DeviceKeyHandle;
+#[cfg(any(feature = "ble-mesh-friend", feature = "ble-mesh-lpn"))]
+use crate::drivers::ble::mesh::config::network::NetworkKeyHandle;
 use crate::drivers::ble::mesh::crypto::nonce::{ApplicationNonce, DeviceNonce};
+use crate::drivers::ble::mesh::driver::node::sar::{
+    MessageId, SarConfig, TransmissionFailure, MAX_SEGMENTS,
+};
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::outbound_segmentation::OutboundSegmentation;
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::authentication::AuthenticationContext;
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::replay_cache::ReplayCache;
-use crate::drivers::ble::mesh::pdu::upper::{UpperAccess, UpperPDU};
+use crate::drivers::ble::mesh::model::foundation::configuration::{AppKeyIndex, NetKeyIndex};
+use crate::drivers::ble::mesh::pdu::upper::{UpperAccess, UpperControl, UpperPDU};
 use core::future::Future;
 use embassy::time::Instant;
 use heapless::Vec;
@@ -26,37 +38,67 @@ pub trait LowerContext: AuthenticationContext {
         addr: Address,
     ) -> Result<Option<Vec<LabelUuid, 3>>, DeviceError>;
 
-    fn decrypt_device_key(
+    type DecryptDeviceKeyFuture<'m>: Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn decrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m [u8],
+    ) -> Self::DecryptDeviceKeyFuture<'m>;
+
+    type EncryptDeviceKeyFuture<'m>: Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn encrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m mut [u8],
+    ) -> Self::EncryptDeviceKeyFuture<'m>;
+
+    fn decrypt_remote_device_key(
         &self,
+        addr: &UnicastAddress,
         nonce: DeviceNonce,
         bytes: &mut [u8],
         mic: &[u8],
     ) -> Result<(), DeviceError>;
 
-    fn encrypt_device_key(
+    fn encrypt_remote_device_key(
         &self,
+        addr: &UnicastAddress,
         nonce: DeviceNonce,
         bytes: &mut [u8],
         mic: &mut [u8],
     ) -> Result<(), DeviceError>;
 
+    /// Encrypt with the application key at `app_key_index`, returning the
+    /// AID of the key actually used, as key refresh may substitute a new key.
     fn encrypt_application_key(
         &self,
-        aid: ApplicationKeyIdentifier,
+        net_key_index: &NetKeyIndex,
+        app_key_index: &AppKeyIndex,
         nonce: ApplicationNonce,
         bytes: &mut [u8],
         mic: &mut [u8],
         additional_data: Option<&[u8]>,
-    ) -> Result<(), DeviceError>;
+    ) -> Result<ApplicationKeyIdentifier, DeviceError>;
 
+    /// Decrypt with an application key identified by `aid`, returning the
+    /// index of the key which authenticated.
     fn decrypt_application_key(
         &self,
+        net_key_index: &NetKeyIndex,
         aid: ApplicationKeyIdentifier,
         nonce: ApplicationNonce,
         bytes: &mut [u8],
         mic: &[u8],
         additional_data: Option<&[u8]>,
-    ) -> Result<(), DeviceError>;
+    ) -> Result<AppKeyIndex, DeviceError>;
 
     type NextSequenceFuture<'m>: Future<Output = Result<u32, DeviceError>> + 'm
     where
@@ -70,6 +112,11 @@ pub trait LowerContext: AuthenticationContext {
     fn is_locally_relevant(&self, dst: &Address) -> bool;
 
     fn ack_deadline(&self, deadline: Option<Instant>);
+
+    fn sar_config(&self) -> SarConfig;
+
+    /// Report a segmented message which was given up on.
+    fn transmission_failure(&self, failure: TransmissionFailure);
 }
 
 pub struct Lower {
@@ -91,8 +138,16 @@ impl Default for Lower {
 const SEGMENTED_ACCESS_MTU: usize = 12;
 const NONSEGMENTED_ACCESS_MUT: usize = 15;
 
+/// The largest upper transport access PDU, including its TransMIC, which
+/// fills every segment of a segmented message.
+pub(crate) const MAX_SEGMENTED_ACCESS_PDU: usize = MAX_SEGMENTS * SEGMENTED_ACCESS_MTU;
+
+/// The network PDUs of a locally originated message, up to a segmented one
+/// of the largest size.
+pub type OutboundSegments = CleartextNetworkPDUSegments<MAX_SEGMENTS>;
+
 impl Lower {
-    fn decrypt_payload<C: LowerContext>(
+    async fn decrypt_payload<C: LowerContext>(
         &mut self,
         ctx: &C,
         pdu: &CleartextNetworkPDU,
@@ -102,34 +157,34 @@ impl Lower {
         trans_mic: &[u8],
         mut payload: Vec<u8, 380>,
     ) -> Result<Option<UpperPDU>, DeviceError> {
-        let dst = if access.akf {
+        let (dst, device_key, app_key_index) = if access.akf {
             // decrypt with aid key
             let nonce = ApplicationNonce::new(
                 szmic,
                 seq,
                 pdu.src,
                 pdu.dst,
-                ctx.iv_index().ok_or(DeviceError::CryptoError(
+                ctx.iv_index(pdu.ivi).ok_or(DeviceError::CryptoError(
                     "inbound unsegmented akf access pdu",
                 ))?,
             );
             if let Some(label_uuids) = ctx.find_label_uuids_by_address(pdu.dst)? {
                 let mut temp_payload = Vec::<u8, 380>::new();
-                if let Some(label_uuid) = label_uuids.iter().find(|label_uuid| {
+                let mut app_key_index = None;
+                let dst = if let Some(label_uuid) = label_uuids.iter().find(|label_uuid| {
                     temp_payload.clear();
                     if let Err(_) = temp_payload.extend_from_slice(&payload) {
                         false
                     } else {
-                        if matches!(
-                            ctx.decrypt_application_key(
-                                access.aid,
-                                nonce,
-                                &mut temp_payload,
-                                &trans_mic,
-                                Some(&label_uuid.uuid),
-                            ),
-                            Ok(_)
+                        if let Ok(index) = ctx.decrypt_application_key(
+                            &pdu.network_key.key_index,
+                            access.aid,
+                            nonce,
+                            &mut temp_payload,
+                            &trans_mic,
+                            Some(&label_uuid.uuid),
                         ) {
+                            app_key_index.replace(index);
                             payload.clear();
                             if let Err(_) = payload.extend_from_slice(&temp_payload) {
                                 false
@@ -144,26 +199,48 @@ impl Lower {
                     Address::LabelUuid(*label_uuid)
                 } else {
                     return Err(DeviceError::CryptoError("inbound label-uuid access pdu"));
-                }
+                };
+                let app_key_index = app_key_index
+                    .ok_or(DeviceError::CryptoError("inbound label-uuid access pdu"))?;
+                (dst, DeviceKeyHandle::Local, app_key_index)
             } else {
-                ctx.decrypt_application_key(access.aid, nonce, &mut payload, &trans_mic, None)?;
-                pdu.dst
+                let app_key_index = ctx.decrypt_application_key(
+                    &pdu.network_key.key_index,
+                    access.aid,
+                    nonce,
+                    &mut payload,
+                    &trans_mic,
+                    None,
+                )?;
+                (pdu.dst, DeviceKeyHandle::Local, app_key_index)
             }
         } else {
             // decrypt with device key
             if !ctx.is_local_unicast(&pdu.dst) {
                 return Ok(None);
             }
-            let nonce = DeviceNonce::new(
-                szmic,
-                seq,
-                pdu.src,
-                pdu.dst,
-                ctx.iv_index()
-                    .ok_or(DeviceError::CryptoError("inbound device access pdu"))?,
-            );
-            ctx.decrypt_device_key(nonce, &mut payload, &trans_mic)?;
-            pdu.dst
+            let iv_index = ctx
+                .iv_index(pdu.ivi)
+                .ok_or(DeviceError::CryptoError("inbound device access pdu"))?;
+            let nonce = || DeviceNonce::new(szmic, seq, pdu.src, pdu.dst, iv_index);
+            let mut temp_payload = payload.clone();
+            if ctx
+                .decrypt_device_key(nonce(), &mut temp_payload, &trans_mic)
+                .await
+                .is_ok()
+            {
+                payload = temp_payload;
+                (pdu.dst, DeviceKeyHandle::Local, AppKeyIndex::new(0))
+            } else {
+                // not for our configuration server, so possibly a response to
+                // our configuration client, protected by the sender's device key.
+                ctx.decrypt_remote_device_key(&pdu.src, nonce(), &mut payload, &trans_mic)?;
+                (
+                    pdu.dst,
+                    DeviceKeyHandle::Remote(pdu.src),
+                    AppKeyIndex::new(0),
+                )
+            }
         };
         Ok(Some(UpperPDU::Access(UpperAccess {
             ttl: Some(pdu.ttl),
@@ -172,8 +249,10 @@ impl Lower {
             nid: pdu.nid,
             akf: access.akf,
             aid: access.aid,
+            app_key_index,
             src: pdu.src,
             dst,
+            device_key,
             payload,
         })))
     }
@@ -192,22 +271,25 @@ impl Lower {
                         let payload = Vec::from_slice(payload)
                             .map_err(|_| DeviceError::InsufficientBuffer)?;
 
-                        if self
-                            .replay_cache
-                            .has_seen(ctx.iv_index().unwrap_or(0), pdu.seq, pdu.src)
-                        {
+                        if self.replay_cache.has_seen(
+                            ctx.iv_index(pdu.ivi).unwrap_or(0),
+                            pdu.seq,
+                            pdu.src,
+                        ) {
                             return Ok((None, None));
                         }
 
-                        let upper = self.decrypt_payload(
-                            ctx,
-                            pdu,
-                            access,
-                            SzMic::Bit32,
-                            pdu.seq,
-                            trans_mic,
-                            payload,
-                        )?;
+                        let upper = self
+                            .decrypt_payload(
+                                ctx,
+                                pdu,
+                                access,
+                                SzMic::Bit32,
+                                pdu.seq,
+                                trans_mic,
+                                payload,
+                            )
+                            .await?;
                         Ok((None, upper))
                     }
                     LowerAccessMessage::Segmented {
@@ -217,9 +299,14 @@ impl Lower {
                         seg_n,
                         segment_m,
                     } => {
-                        let (block_ack, payload) = self
-                            .inbound_segmentation
-                            .process_inbound(pdu.src, *seq_zero, *seg_o, *seg_n, segment_m)?;
+                        let (block_ack, payload) = self.inbound_segmentation.process_inbound(
+                            pdu.src,
+                            *seq_zero,
+                            *seg_o,
+                            *seg_n,
+                            segment_m,
+                            ctx.sar_config().incomplete_timeout,
+                        )?;
 
                         let mut parameters = Vec::new();
                         let ack_seq_zero = (seq_zero << 2).to_be_bytes();
@@ -243,18 +330,23 @@ impl Lower {
                             .push(block_ack[3])
                             .map_err(|_| DeviceError::InsufficientBuffer)?;
 
-                        let ack = CleartextNetworkPDU {
-                            network_key: pdu.network_key,
-                            ivi: pdu.ivi,
-                            nid: pdu.nid,
-                            ttl: 1,
-                            seq: ctx.next_sequence().await?,
-                            src: ctx.primary_unicast_address()?,
-                            dst: pdu.src.into(),
-                            transport_pdu: LowerPDU::Control(LowerControl {
-                                opcode: Opcode::SegmentedAcknowledgement,
-                                message: LowerControlMessage::Unsegmented { parameters },
-                            }),
+                        // segments to group and virtual addresses are never acknowledged.
+                        let ack = if let Address::Unicast(_) = pdu.dst {
+                            Some(CleartextNetworkPDU {
+                                network_key: pdu.network_key,
+                                ivi: pdu.ivi,
+                                nid: pdu.nid,
+                                ttl: 1,
+                                seq: ctx.next_sequence().await?,
+                                src: ctx.primary_unicast_address()?,
+                                dst: pdu.src.into(),
+                                transport_pdu: LowerPDU::Control(LowerControl {
+                                    opcode: Opcode::SegmentedAcknowledgement,
+                                    message: LowerControlMessage::Unsegmented { parameters },
+                                }),
+                            })
+                        } else {
+                            None
                         };
 
                         if let Some(payload) = payload {
@@ -268,7 +360,7 @@ impl Lower {
                                 .map_err(|_| DeviceError::InsufficientBuffer)?;
 
                             let seq_auth = Self::seq_auth(
-                                ctx.iv_index().ok_or(DeviceError::CryptoError(
+                                ctx.iv_index(pdu.ivi).ok_or(DeviceError::CryptoError(
                                     "inbound segmented access pdu",
                                 ))?,
                                 pdu.seq,
@@ -276,19 +368,21 @@ impl Lower {
                             );
 
                             if self.replay_cache.has_seen(
-                                ctx.iv_index().unwrap_or(0),
+                                ctx.iv_index(pdu.ivi).unwrap_or(0),
                                 pdu.seq,
                                 pdu.src,
                             ) {
                                 return Ok((None, None));
                             }
 
-                            let upper = self.decrypt_payload(
-                                ctx, pdu, access, *szmic, seq_auth, trans_mic, payload,
-                            )?;
-                            Ok((Some(ack), upper))
+                            let upper = self
+                                .decrypt_payload(
+                                    ctx, pdu, access, *szmic, seq_auth, trans_mic, payload,
+                                )
+                                .await?;
+                            Ok((ack, upper))
                         } else {
-                            Ok((Some(ack), None))
+                            Ok((ack, None))
                         }
                     }
                 }
@@ -304,7 +398,29 @@ impl Lower {
                             parameters[5],
                         ]);
 
-                        self.outbound_segmentation.ack(seq_zero, block_ack);
+                        self.outbound_segmentation.ack(ctx, seq_zero, block_ack);
+                    } else if control.opcode == Opcode::Heatbeat {
+                        if self.replay_cache.has_seen(
+                            ctx.iv_index(pdu.ivi).unwrap_or(0),
+                            pdu.seq,
+                            pdu.src,
+                        ) {
+                            return Ok((None, None));
+                        }
+                        return Ok((
+                            None,
+                            Some(UpperPDU::Control(UpperControl {
+                                ttl: pdu.ttl,
+                                network_key: pdu.network_key,
+                                ivi: pdu.ivi,
+                                nid: pdu.nid,
+                                src: pdu.src,
+                                dst: pdu.dst,
+                                opcode: control.opcode,
+                                data: Vec::from_slice(parameters)
+                                    .map_err(|_| DeviceError::InsufficientBuffer)?,
+                            })),
+                        ));
                     }
                     Ok((None, None))
                 }
@@ -325,19 +441,45 @@ impl Lower {
         }
     }
 
+    /// Segment an upper transport PDU as required. A segmented message is
+    /// retransmitted until acknowledged, its failure carrying `id`.
     pub async fn process_outbound<C: LowerContext>(
         &mut self,
         ctx: &C,
         pdu: UpperPDU,
-    ) -> Result<Option<CleartextNetworkPDUSegments>, DeviceError> {
+        id: Option<MessageId>,
+    ) -> Result<Option<OutboundSegments>, DeviceError> {
         match pdu {
-            UpperPDU::Control(_control) => Ok(None),
+            UpperPDU::Control(control) => {
+                // locally originated control messages always fit a single segment.
+                let parameters =
+                    Vec::from_slice(&control.data).map_err(|_| DeviceError::InsufficientBuffer)?;
+                let iv_index = ctx.transmit_iv_index().ok_or(DeviceError::NotProvisioned)?;
+                Ok(Some(OutboundSegments::new(CleartextNetworkPDU {
+                    network_key: control.network_key,
+                    ivi: (iv_index & 1) as u8,
+                    nid: control.nid,
+                    ttl: control.ttl,
+                    seq: ctx.next_sequence().await?,
+                    src: control.src,
+                    dst: control.dst,
+                    transport_pdu: LowerPDU::Control(LowerControl {
+                        opcode: control.opcode,
+                        message: LowerControlMessage::Unsegmented { parameters },
+                    }),
+                })))
+            }
             UpperPDU::Access(access) => {
-                let mut payload: Vec<u8, 380> = Vec::from_slice(&access.payload)
-                    .map_err(|_| DeviceError::InsufficientBuffer)?;
+                let mut payload: Vec<u8, MAX_SEGMENTED_ACCESS_PDU> =
+                    Vec::from_slice(&access.payload)
+                        .map_err(|_| DeviceError::InsufficientBuffer)?;
 
                 let seq_zero = ctx.next_sequence().await?;
 
+                // locally originated messages always use the current transmit IV index.
+                let iv_index = ctx.transmit_iv_index().ok_or(DeviceError::NotProvisioned)?;
+                let ivi = (iv_index & 1) as u8;
+
                 let ttl = access.ttl.unwrap_or(ctx.default_ttl());
 
                 let (akf, aid) = if access.akf {
@@ -346,13 +488,13 @@ impl Lower {
                         seq_zero,
                         access.src,
                         access.dst,
-                        ctx.iv_index()
-                            .ok_or(DeviceError::CryptoError("application nonce"))?,
+                        iv_index,
                     );
                     let mut trans_mic = [0; 4];
 
-                    ctx.encrypt_application_key(
-                        access.aid,
+                    let aid = ctx.encrypt_application_key(
+                        &access.network_key.key_index,
+                        &access.app_key_index,
                         nonce,
                         &mut payload,
                         &mut trans_mic,
@@ -364,19 +506,24 @@ impl Lower {
                     payload
                         .extend_from_slice(&trans_mic)
                         .map_err(|_| DeviceError::InsufficientBuffer)?;
-                    (true, access.aid)
+                    (true, aid)
                 } else {
                     // encrypt device key
-                    let nonce = DeviceNonce::new(
-                        SzMic::Bit32,
-                        seq_zero,
-                        access.src,
-                        access.dst,
-                        ctx.iv_index()
-                            .ok_or(DeviceError::CryptoError("device nonce"))?,
-                    );
+                    let nonce =
+                        DeviceNonce::new(SzMic::Bit32, seq_zero, access.src, access.dst, iv_index);
                     let mut trans_mic = [0; 4];
-                    ctx.encrypt_device_key(nonce, &mut payload, &mut trans_mic)?;
+                    match &access.device_key {
+                        DeviceKeyHandle::Local => {
+                            ctx.encrypt_device_key(nonce, &mut payload, &mut trans_mic)
+                                .await?
+                        }
+                        DeviceKeyHandle::Remote(addr) => ctx.encrypt_remote_device_key(
+                            addr,
+                            nonce,
+                            &mut payload,
+                            &mut trans_mic,
+                        )?,
+                    }
                     payload
                         .extend_from_slice(&trans_mic)
                         .map_err(|_| DeviceError::InsufficientBuffer)?;
@@ -386,7 +533,7 @@ impl Lower {
                 if payload.len() > NONSEGMENTED_ACCESS_MUT {
                     let payload = payload.chunks(SEGMENTED_ACCESS_MTU);
 
-                    let mut segments = CleartextNetworkPDUSegments::new_empty();
+                    let mut segments = OutboundSegments::new_empty();
 
                     let seg_n = payload.len() - 1;
 
@@ -398,7 +545,7 @@ impl Lower {
                         };
                         segments.add(CleartextNetworkPDU {
                             network_key: access.network_key,
-                            ivi: access.ivi,
+                            ivi,
                             nid: access.nid,
                             ttl,
                             seq,
@@ -417,29 +564,32 @@ impl Lower {
                             }),
                         })?;
                     }
-                    self.outbound_segmentation
-                        .register(seq_zero as u16, ttl, segments.clone())?;
+                    self.outbound_segmentation.register(
+                        ctx,
+                        id,
+                        seq_zero as u16,
+                        ttl,
+                        segments.clone(),
+                    )?;
                     Ok(Some(segments))
                 } else {
                     let payload =
                         Vec::from_slice(&payload).map_err(|_| DeviceError::InsufficientBuffer)?;
                     // can ship unsegmented
-                    Ok(Some(CleartextNetworkPDUSegments::new(
-                        CleartextNetworkPDU {
-                            network_key: access.network_key,
-                            ivi: access.ivi,
-                            nid: access.nid,
-                            ttl,
-                            seq: seq_zero,
-                            src: access.src,
-                            dst: access.dst,
-                            transport_pdu: LowerPDU::Access(LowerAccess {
-                                akf,
-                                aid,
-                                message: LowerAccessMessage::Unsegmented(payload),
-                            }),
-                        },
-                    )))
+                    Ok(Some(OutboundSegments::new(CleartextNetworkPDU {
+                        network_key: access.network_key,
+                        ivi,
+                        nid: access.nid,
+                        ttl,
+                        seq: seq_zero,
+                        src: access.src,
+                        dst: access.dst,
+                        transport_pdu: LowerPDU::Access(LowerAccess {
+                            akf,
+                            aid,
+                            message: LowerAccessMessage::Unsegmented(payload),
+                        }),
+                    })))
                 }
             }
         }
@@ -448,11 +598,39 @@ impl Lower {
     pub fn retransmit<C: LowerContext>(
         &mut self,
         ctx: &C,
-    ) -> Result<Option<CleartextNetworkPDUSegments<64>>, DeviceError> {
+    ) -> Result<Option<OutboundSegments>, DeviceError> {
         self.outbound_segmentation.retransmit(ctx)
     }
 }
 
+/// An unsegmented transport control PDU originated by this node. Messages
+/// between a Low Power node and its friend are sent with a TTL of 0, so that
+/// they reach neighbouring nodes only.
+#[cfg(any(feature = "ble-mesh-friend", feature = "ble-mesh-lpn"))]
+pub(crate) async fn control_pdu<C: LowerContext>(
+    ctx: &C,
+    network_key: NetworkKeyHandle,
+    dst: Address,
+    ttl: u8,
+    opcode: Opcode,
+    parameters: Vec<u8, 11>,
+) -> Result<CleartextNetworkPDU, DeviceError> {
+    let iv_index = ctx.transmit_iv_index().ok_or(DeviceError::NotProvisioned)?;
+    Ok(CleartextNetworkPDU {
+        network_key,
+        ivi: (iv_index & 1) as u8,
+        nid: network_key.nid,
+        ttl,
+        seq: ctx.next_sequence().await?,
+        src: ctx.primary_unicast_address()?,
+        dst,
+        transport_pdu: LowerPDU::Control(LowerControl {
+            opcode,
+            message: LowerControlMessage::Unsegmented { parameters },
+        }),
+    })
+}
+
 #[derive(Clone)]
 pub struct CleartextNetworkPDUSegments<const N: usize = 3> {
     segments: Vec<Option<CleartextNetworkPDU>, N>,
@@ -482,6 +660,11 @@ impl<const N: usize> CleartextNetworkPDUSegments<N> {
         self.segments.iter().all(|e| matches!(e, None))
     }
 
+    /// The number of segments not yet acknowledged.
+    fn remaining(&self) -> usize {
+        self.segments.iter().filter(|e| e.is_some()).count()
+    }
+
     fn add(&mut self, pdu: CleartextNetworkPDU) -> Result<(), DeviceError> {
         self.segments
             .push(Some(pdu))
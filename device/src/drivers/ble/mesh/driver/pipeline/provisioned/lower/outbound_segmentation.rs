@@ -1,18 +1,45 @@
+use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
+use crate::drivers::ble::mesh::driver::node::sar::{
+    MessageId, TransmissionFailure, MAX_OUTBOUND_IN_FLIGHT,
+};
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::{
-    CleartextNetworkPDUSegments, LowerContext,
+    LowerContext, OutboundSegments,
 };
 use crate::drivers::ble::mesh::driver::DeviceError;
-use embassy::time::{Duration, Instant};
+use embassy::time::Instant;
 use heapless::Vec;
 
 struct Entry {
+    id: Option<MessageId>,
     ttl: u8,
     seq_zero: u16,
-    segments: CleartextNetworkPDUSegments,
+    src: UnicastAddress,
+    dst: Address,
+    segments: OutboundSegments,
     deadline: Instant,
+    /// Retransmissions left before giving up.
+    retransmissions: u8,
+    /// Retransmissions left without any segment newly acknowledged, for
+    /// unicast destinations only.
+    without_progress: u8,
+}
+
+impl Entry {
+    fn is_unicast(&self) -> bool {
+        matches!(self.dst, Address::Unicast(_))
+    }
+
+    fn failure(&self, error: DeviceError) -> TransmissionFailure {
+        TransmissionFailure {
+            id: self.id,
+            src: self.src,
+            dst: self.dst,
+            error,
+        }
+    }
 }
 
-pub struct OutboundSegmentation<const N: usize = 3> {
+pub struct OutboundSegmentation<const N: usize = MAX_OUTBOUND_IN_FLIGHT> {
     in_flight: Vec<Option<Entry>, N>,
 }
 
@@ -27,39 +54,71 @@ impl<const N: usize> Default for OutboundSegmentation<N> {
 }
 
 impl<const N: usize> OutboundSegmentation<N> {
-    pub fn register(
+    pub fn register<C: LowerContext>(
         &mut self,
+        ctx: &C,
+        id: Option<MessageId>,
         seq_zero: u16,
         ttl: u8,
-        segments: CleartextNetworkPDUSegments,
+        segments: OutboundSegments,
     ) -> Result<(), DeviceError> {
+        let (src, dst) = segments
+            .iter()
+            .next()
+            .map(|segment| (segment.src, segment.dst))
+            .ok_or(DeviceError::InvalidPacket)?;
+
         if let Some(entry) = self.in_flight.iter_mut().find(|e| matches!(e, None)) {
+            let sar = ctx.sar_config();
+            let retransmissions = if let Address::Unicast(_) = dst {
+                sar.unicast_retransmit_count
+            } else {
+                sar.multicast_retransmit_count
+            };
             *entry = Some(Entry {
+                id,
                 seq_zero,
+                src,
+                dst,
                 segments,
                 ttl,
-                deadline: Instant::now() + Duration::from_millis(200 + 50 * ttl as u64),
+                deadline: Instant::now() + sar.retransmit_interval(&dst, ttl),
+                retransmissions,
+                without_progress: sar.unicast_retransmit_without_progress_count,
             });
+            ctx.ack_deadline(self.next_deadline());
             Ok(())
         } else {
             Err(DeviceError::InsufficientBuffer)
         }
     }
 
-    pub fn ack(&mut self, seq_zero: u16, block_ack: u32) {
+    pub fn ack<C: LowerContext>(&mut self, ctx: &C, seq_zero: u16, block_ack: u32) {
         if let Some(entry) = self.in_flight.iter_mut().find(|e| {
             if let Some(entry) = e {
-                entry.seq_zero == seq_zero
+                entry.seq_zero == seq_zero && entry.is_unicast()
             } else {
                 false
             }
         }) {
             if let Some(inner) = entry {
-                inner.deadline =
-                    Instant::now() + Duration::from_millis(200 + 50 * inner.ttl as u64);
+                if block_ack == 0 {
+                    // the receiver is busy or has cancelled the message.
+                    ctx.transmission_failure(inner.failure(DeviceError::TransmitError));
+                    *entry = None;
+                    return;
+                }
+
+                let sar = ctx.sar_config();
+                let remaining = inner.segments.remaining();
                 if inner.segments.ack(block_ack) {
                     *entry = None;
+                    return;
+                }
+                if inner.segments.remaining() < remaining {
+                    inner.without_progress = sar.unicast_retransmit_without_progress_count;
                 }
+                inner.deadline = Instant::now() + sar.retransmit_interval(&inner.dst, inner.ttl);
             }
         }
     }
@@ -85,20 +144,40 @@ impl<const N: usize> OutboundSegmentation<N> {
     pub fn retransmit<C: LowerContext>(
         &mut self,
         ctx: &C,
-    ) -> Result<Option<CleartextNetworkPDUSegments<64>>, DeviceError> {
+    ) -> Result<Option<OutboundSegments>, DeviceError> {
         let now = Instant::now();
+        let sar = ctx.sar_config();
 
-        let mut segments = CleartextNetworkPDUSegments::new_empty();
+        let mut segments = OutboundSegments::new_empty();
 
         for e in self.in_flight.iter_mut() {
             if let Some(entry) = e {
                 if entry.deadline < now {
+                    let exhausted = entry.retransmissions == 0
+                        || (entry.is_unicast() && entry.without_progress == 0);
+                    if exhausted {
+                        if entry.is_unicast() {
+                            warn!("segmented message unacknowledged, giving up");
+                            ctx.transmission_failure(entry.failure(DeviceError::Timeout));
+                        }
+                        // group and virtual destinations never acknowledge,
+                        // so having retransmitted is all there is to it.
+                        *e = None;
+                        continue;
+                    }
+
+                    entry.retransmissions -= 1;
+                    entry.without_progress = entry.without_progress.saturating_sub(1);
+                    entry.deadline = now + sar.retransmit_interval(&entry.dst, entry.ttl);
+
                     for s in &entry.segments.segments {
                         if let Some(segment) = s {
                             info!("rxmt!");
                             segments.add(segment.clone())?;
                         }
                     }
+                    // one message at a time, any others being due immediately.
+                    break;
                 }
             }
         }
@@ -108,3 +187,192 @@ impl<const N: usize> OutboundSegmentation<N> {
         Ok(Some(segments))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::driver::pipeline::provisioned::test_context::TestContext;
+    use crate::drivers::ble::mesh::pdu::lower::{LowerAccess, LowerAccessMessage, LowerPDU, SzMic};
+    use crate::drivers::ble::mesh::pdu::network::CleartextNetworkPDU;
+    use embassy::time::Duration;
+    use std::thread::sleep;
+
+    const SRC: u16 = 0x0001;
+    const DST: u16 = 0x0002;
+    const GROUP: u16 = 0xC000;
+    const SEQ_ZERO: u16 = 0x0010;
+
+    fn context() -> TestContext {
+        let mut ctx = TestContext::new(SRC);
+        ctx.sar.unicast_retransmit_interval = Duration::from_millis(1);
+        ctx.sar.unicast_retransmit_ttl_increment = Duration::from_millis(0);
+        ctx.sar.multicast_retransmit_interval = Duration::from_millis(1);
+        ctx
+    }
+
+    fn expire() {
+        sleep(std::time::Duration::from_millis(5));
+    }
+
+    fn segments(ctx: &TestContext, dst: u16, seg_n: u8) -> OutboundSegments {
+        let mut segments = OutboundSegments::new_empty();
+        for seg_o in 0..=seg_n {
+            segments
+                .add(CleartextNetworkPDU {
+                    network_key: ctx.network_key,
+                    ivi: 0,
+                    nid: ctx.network_key.nid,
+                    ttl: 5,
+                    seq: SEQ_ZERO as u32 + seg_o as u32,
+                    src: UnicastAddress::parse(SRC.to_be_bytes()).unwrap(),
+                    dst: Address::parse(dst.to_be_bytes()),
+                    transport_pdu: LowerPDU::Access(LowerAccess {
+                        akf: true,
+                        aid: 0x01u8.into(),
+                        message: LowerAccessMessage::Segmented {
+                            szmic: SzMic::Bit32,
+                            seq_zero: SEQ_ZERO,
+                            seg_o,
+                            seg_n,
+                            segment_m: Vec::from_slice(&[seg_o; 12]).unwrap(),
+                        },
+                    }),
+                })
+                .unwrap();
+        }
+        segments
+    }
+
+    fn register(
+        ctx: &TestContext,
+        outbound: &mut OutboundSegmentation,
+        dst: u16,
+        seg_n: u8,
+    ) -> MessageId {
+        let id = MessageId(7);
+        outbound
+            .register(ctx, Some(id), SEQ_ZERO, 5, segments(ctx, dst, seg_n))
+            .unwrap();
+        id
+    }
+
+    fn retransmitted(ctx: &TestContext, outbound: &mut OutboundSegmentation) -> usize {
+        outbound.retransmit(ctx).unwrap().unwrap().iter().count()
+    }
+
+    #[test]
+    fn retransmit_until_acknowledged() {
+        let ctx = context();
+        let mut outbound = OutboundSegmentation::default();
+        register(&ctx, &mut outbound, DST, 2);
+
+        expire();
+        assert_eq!(3, retransmitted(&ctx, &mut outbound));
+
+        // only the segments not acknowledged yet.
+        outbound.ack(&ctx, SEQ_ZERO, 0b011);
+        expire();
+        assert_eq!(1, retransmitted(&ctx, &mut outbound));
+
+        outbound.ack(&ctx, SEQ_ZERO, 0b111);
+        expire();
+        assert_eq!(0, retransmitted(&ctx, &mut outbound));
+        assert!(ctx.failures.borrow().is_empty());
+    }
+
+    #[test]
+    fn no_progress() {
+        let mut ctx = context();
+        ctx.sar.unicast_retransmit_count = 4;
+        ctx.sar.unicast_retransmit_without_progress_count = 2;
+        let mut outbound = OutboundSegmentation::default();
+        let id = register(&ctx, &mut outbound, DST, 1);
+
+        expire();
+        assert_eq!(2, retransmitted(&ctx, &mut outbound));
+        // progress restores the retransmissions without progress.
+        outbound.ack(&ctx, SEQ_ZERO, 0b01);
+        expire();
+        assert_eq!(1, retransmitted(&ctx, &mut outbound));
+        expire();
+        assert_eq!(1, retransmitted(&ctx, &mut outbound));
+        assert!(ctx.failures.borrow().is_empty());
+
+        expire();
+        assert_eq!(0, retransmitted(&ctx, &mut outbound));
+        let failures = ctx.failures.borrow();
+        assert_eq!(1, failures.len());
+        assert_eq!(Some(id), failures[0].id);
+        assert!(matches!(failures[0].error, DeviceError::Timeout));
+    }
+
+    #[test]
+    fn retransmissions_exhausted() {
+        let mut ctx = context();
+        ctx.sar.unicast_retransmit_count = 2;
+        ctx.sar.unicast_retransmit_without_progress_count = 2;
+        let mut outbound = OutboundSegmentation::default();
+        let id = register(&ctx, &mut outbound, DST, 2);
+
+        expire();
+        assert_eq!(3, retransmitted(&ctx, &mut outbound));
+        outbound.ack(&ctx, SEQ_ZERO, 0b001);
+        expire();
+        assert_eq!(2, retransmitted(&ctx, &mut outbound));
+        outbound.ack(&ctx, SEQ_ZERO, 0b011);
+        expire();
+        assert_eq!(0, retransmitted(&ctx, &mut outbound));
+
+        let failures = ctx.failures.borrow();
+        assert_eq!(1, failures.len());
+        assert_eq!(Some(id), failures[0].id);
+        assert!(matches!(failures[0].error, DeviceError::Timeout));
+    }
+
+    #[test]
+    fn cancelled() {
+        let ctx = context();
+        let mut outbound = OutboundSegmentation::default();
+        let id = register(&ctx, &mut outbound, DST, 1);
+
+        outbound.ack(&ctx, SEQ_ZERO, 0);
+        expire();
+        assert_eq!(0, retransmitted(&ctx, &mut outbound));
+
+        let failures = ctx.failures.borrow();
+        assert_eq!(1, failures.len());
+        assert_eq!(Some(id), failures[0].id);
+        assert!(matches!(failures[0].error, DeviceError::TransmitError));
+    }
+
+    #[test]
+    fn multicast() {
+        let mut ctx = context();
+        ctx.sar.multicast_retransmit_count = 2;
+        let mut outbound = OutboundSegmentation::default();
+        register(&ctx, &mut outbound, GROUP, 1);
+
+        // never acknowledged, so neither is it cancelled.
+        outbound.ack(&ctx, SEQ_ZERO, 0);
+        for _ in 0..2 {
+            expire();
+            assert_eq!(2, retransmitted(&ctx, &mut outbound));
+        }
+        expire();
+        assert_eq!(0, retransmitted(&ctx, &mut outbound));
+        assert!(ctx.failures.borrow().is_empty());
+    }
+
+    #[test]
+    fn in_flight_limit() {
+        let ctx = context();
+        let mut outbound = OutboundSegmentation::default();
+        for _ in 0..MAX_OUTBOUND_IN_FLIGHT {
+            register(&ctx, &mut outbound, DST, 1);
+        }
+        assert!(matches!(
+            outbound.register(&ctx, None, SEQ_ZERO, 5, segments(&ctx, DST, 1)),
+            Err(DeviceError::InsufficientBuffer)
+        ));
+    }
+}
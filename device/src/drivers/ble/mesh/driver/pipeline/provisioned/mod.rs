@@ -1,13 +1,28 @@
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::address::Address;
 use crate::drivers::ble::mesh::driver::node::deadline::Expiration;
+use crate::drivers::ble::mesh::driver::node::sar::MessageId;
 use crate::drivers::ble::mesh::driver::node::State;
 use crate::drivers::ble::mesh::driver::pipeline::mesh::{
     NetworkRetransmitDetails, PublishRetransmitDetails,
 };
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::access::AccessContext;
+#[cfg(feature = "ble-mesh-friend")]
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::friend::{
+    FriendContext, Friendships,
+};
+#[cfg(feature = "ble-mesh-lpn")]
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::low_power::{
+    LowPower, LowPowerContext,
+};
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::{Lower, LowerContext};
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::authentication::{
     Authentication, AuthenticationContext,
 };
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::proxy::{
+    Proxy, ProxyContext,
+};
 #[cfg(feature = "ble-mesh-relay")]
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::relay::{
     Relay, RelayContext,
@@ -19,26 +34,94 @@ use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::NetworkCo
 use crate::drivers::ble::mesh::driver::pipeline::provisioned::upper::{Upper, UpperContext};
 use crate::drivers::ble::mesh::driver::pipeline::PipelineContext;
 use crate::drivers::ble::mesh::driver::DeviceError;
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::interface::ProxyMessage;
 use crate::drivers::ble::mesh::interface::PDU;
 use crate::drivers::ble::mesh::pdu::access::AccessMessage;
-use crate::drivers::ble::mesh::pdu::network::ObfuscatedAndEncryptedNetworkPDU;
+use crate::drivers::ble::mesh::pdu::network::{
+    CleartextNetworkPDU, ObfuscatedAndEncryptedNetworkPDU,
+};
+use crate::drivers::ble::mesh::pdu::upper::{UpperControl, UpperPDU};
+use embassy::time::Timer;
 use futures::{join, pin_mut};
 
 pub mod access;
 pub mod lower;
 pub mod network;
+#[cfg(test)]
+pub(crate) mod test_context;
 pub mod upper;
 
-#[cfg(feature = "ble-mesh-relay")]
 pub trait ProvisionedContext:
-    AuthenticationContext + RelayContext + LowerContext + UpperContext + AccessContext + NetworkContext
+    AuthenticationContext
+    + RelayFeatureContext
+    + ProxyFeatureContext
+    + LowerContext
+    + FriendFeatureContext
+    + LowPowerFeatureContext
+    + UpperContext
+    + AccessContext
+    + NetworkContext
 {
 }
 
+// Each optional feature contributes its context only when enabled.
+
+#[cfg(feature = "ble-mesh-relay")]
+pub trait RelayFeatureContext: RelayContext {}
+
+#[cfg(feature = "ble-mesh-relay")]
+impl<C: RelayContext> RelayFeatureContext for C {}
+
 #[cfg(not(feature = "ble-mesh-relay"))]
-pub trait ProvisionedContext:
-    AuthenticationContext + LowerContext + UpperContext + AccessContext + NetworkContext
-{
+pub trait RelayFeatureContext {}
+
+#[cfg(not(feature = "ble-mesh-relay"))]
+impl<C> RelayFeatureContext for C {}
+
+#[cfg(feature = "ble-mesh-proxy")]
+pub trait ProxyFeatureContext: ProxyContext {}
+
+#[cfg(feature = "ble-mesh-proxy")]
+impl<C: ProxyContext> ProxyFeatureContext for C {}
+
+#[cfg(not(feature = "ble-mesh-proxy"))]
+pub trait ProxyFeatureContext {}
+
+#[cfg(not(feature = "ble-mesh-proxy"))]
+impl<C> ProxyFeatureContext for C {}
+
+#[cfg(feature = "ble-mesh-friend")]
+pub trait FriendFeatureContext: FriendContext {}
+
+#[cfg(feature = "ble-mesh-friend")]
+impl<C: FriendContext> FriendFeatureContext for C {}
+
+#[cfg(not(feature = "ble-mesh-friend"))]
+pub trait FriendFeatureContext {}
+
+#[cfg(not(feature = "ble-mesh-friend"))]
+impl<C> FriendFeatureContext for C {}
+
+#[cfg(feature = "ble-mesh-lpn")]
+pub trait LowPowerFeatureContext: LowPowerContext {}
+
+#[cfg(feature = "ble-mesh-lpn")]
+impl<C: LowPowerContext> LowPowerFeatureContext for C {}
+
+#[cfg(not(feature = "ble-mesh-lpn"))]
+pub trait LowPowerFeatureContext {}
+
+#[cfg(not(feature = "ble-mesh-lpn"))]
+impl<C> LowPowerFeatureContext for C {}
+
+/// The bearer an inbound network PDU was received on.
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) enum Bearer {
+    Advertising,
+    /// Over GATT, from the connected proxy client.
+    #[cfg(feature = "ble-mesh-proxy")]
+    Proxy,
 }
 
 pub(crate) struct ProvisionedPipeline {
@@ -46,7 +129,13 @@ pub(crate) struct ProvisionedPipeline {
     authentication: Authentication,
     #[cfg(feature = "ble-mesh-relay")]
     relay: Relay,
+    #[cfg(feature = "ble-mesh-proxy")]
+    proxy: Proxy,
     lower: Lower,
+    #[cfg(feature = "ble-mesh-friend")]
+    friendships: Friendships,
+    #[cfg(feature = "ble-mesh-lpn")]
+    low_power: LowPower,
     upper: Upper,
 }
 
@@ -57,7 +146,13 @@ impl ProvisionedPipeline {
             authentication: Default::default(),
             #[cfg(feature = "ble-mesh-relay")]
             relay: Default::default(),
+            #[cfg(feature = "ble-mesh-proxy")]
+            proxy: Default::default(),
             lower: Default::default(),
+            #[cfg(feature = "ble-mesh-friend")]
+            friendships: Default::default(),
+            #[cfg(feature = "ble-mesh-lpn")]
+            low_power: Default::default(),
             upper: Default::default(),
         }
     }
@@ -66,8 +161,32 @@ impl ProvisionedPipeline {
         &mut self,
         ctx: &C,
         pdu: &mut ObfuscatedAndEncryptedNetworkPDU,
+        bearer: Bearer,
     ) -> Result<Option<State>, DeviceError> {
-        if let Some(inboud_pdu) = self.authentication.process_inbound(ctx, pdu)? {
+        if let Some(inboud_pdu) = self.authenticate_inbound(ctx, pdu)? {
+            #[cfg(feature = "ble-mesh-friend")]
+            {
+                let (consumed, response) =
+                    self.friendships.process_inbound(ctx, &inboud_pdu).await?;
+                if let Some(response) = response {
+                    self.transmit_with_credentials(ctx, &response).await?;
+                }
+                if consumed {
+                    return Ok(None);
+                }
+            }
+
+            #[cfg(feature = "ble-mesh-lpn")]
+            {
+                let (consumed, request) = self.low_power.process_inbound(ctx, &inboud_pdu).await?;
+                if let Some(request) = request {
+                    self.transmit_with_credentials(ctx, &request).await?;
+                }
+                if consumed {
+                    return Ok(None);
+                }
+            }
+
             let result = self.lower.process_inbound(ctx, &inboud_pdu).await;
             let mut error = None;
             match result {
@@ -79,10 +198,12 @@ impl ProvisionedPipeline {
                     }
 
                     if let Some(ack) = ack {
-                        if let Some(ack) = self.authentication.process_outbound(ctx, &ack)? {
+                        if let Some(encrypted) = self.authentication.process_outbound(ctx, &ack)? {
+                            #[cfg(feature = "ble-mesh-proxy")]
+                            self.forward_to_proxy(ctx, &ack.dst, &encrypted).await;
                             // don't fail if we fail to transmit the ack.
                             //ctx.transmit_mesh_pdu(&ack).await.ok();
-                            ctx.transmit(&PDU::Network(ack)).await.ok();
+                            ctx.transmit(&PDU::Network(encrypted)).await.ok();
                         }
                     }
                 }
@@ -92,19 +213,52 @@ impl ProvisionedPipeline {
                 }
             }
 
-            #[cfg(feature = "ble-mesh-relay")]
-            if let Some(outbound) = self.relay.process_inbound(ctx, &inboud_pdu)? {
-                // Relaying is independent from processing it locally
-                // don't fail if we fail to encrypt a relay.
-                if let Ok(Some(outbound)) = self.authentication.process_outbound(ctx, &outbound) {
-                    // don't fail if we fail to retransmit.
-                    //ctx.transmit_mesh_pdu(&outbound).await.ok();
-                    //ctx.enqueue_transmit(&outbound, ctx.relay_retransmit() ).await;
-                    self.transmit
-                        .process_outbound(ctx, outbound, &ctx.relay_retransmit())
-                        .await?;
+            match bearer {
+                Bearer::Advertising => {
+                    #[cfg(feature = "ble-mesh-relay")]
+                    if let Some(outbound) = self.relay.process_inbound(ctx, &inboud_pdu)? {
+                        // Relaying is independent from processing it locally
+                        // don't fail if we fail to encrypt a relay.
+                        if let Ok(Some(outbound)) =
+                            self.authentication.process_outbound(ctx, &outbound)
+                        {
+                            // don't fail if we fail to retransmit.
+                            //ctx.transmit_mesh_pdu(&outbound).await.ok();
+                            //ctx.enqueue_transmit(&outbound, ctx.relay_retransmit() ).await;
+                            self.transmit
+                                .process_outbound(ctx, outbound, &ctx.relay_retransmit())
+                                .await?;
+                        }
+                    }
+
+                    #[cfg(feature = "ble-mesh-proxy")]
+                    if let Some(outbound) = self.proxy.forward_to_client(ctx, &inboud_pdu) {
+                        if let Ok(Some(outbound)) =
+                            self.authentication.process_outbound(ctx, &outbound)
+                        {
+                            // don't fail if we fail to forward to the proxy client.
+                            ctx.transmit(&PDU::Proxy(ProxyMessage::Network(outbound)))
+                                .await
+                                .ok();
+                        }
+                    }
+                }
+                #[cfg(feature = "ble-mesh-proxy")]
+                Bearer::Proxy => {
+                    // the proxy client reaches the rest of the network over
+                    // the advertising bearer, whether relaying or not.
+                    if let Some(outbound) = self.proxy.forward_from_client(ctx, &inboud_pdu) {
+                        if let Ok(Some(outbound)) =
+                            self.authentication.process_outbound(ctx, &outbound)
+                        {
+                            self.transmit
+                                .process_outbound(ctx, outbound, &ctx.network_retransmit())
+                                .await?;
+                        }
+                    }
                 }
             }
+
             if let Some(err) = error {
                 return Err(err);
             }
@@ -112,11 +266,100 @@ impl ProvisionedPipeline {
         Ok(None)
     }
 
+    /// Apply a proxy configuration message from the connected proxy client,
+    /// responding with the resulting filter status.
+    #[cfg(feature = "ble-mesh-proxy")]
+    pub(crate) async fn process_proxy_configuration<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &ObfuscatedAndEncryptedNetworkPDU,
+    ) -> Result<(), DeviceError> {
+        if let Some((network_key, message)) = self.authentication.process_inbound_proxy(ctx, pdu)? {
+            if let Some(status) = self.proxy.process_inbound(ctx, &message) {
+                let network_key = ctx.transmit_network_key(&network_key.key_index)?;
+                let seq = ctx.next_sequence().await?;
+                let src = ctx.primary_unicast_address()?;
+                let status = self.authentication.process_outbound_proxy(
+                    ctx,
+                    &network_key,
+                    seq,
+                    &src,
+                    &status,
+                )?;
+                ctx.transmit(&PDU::Proxy(ProxyMessage::Configuration(status)))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forward a locally originated network PDU to the connected proxy
+    /// client, if its proxy filter accepts it.
+    #[cfg(feature = "ble-mesh-proxy")]
+    async fn forward_to_proxy<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        dst: &Address,
+        pdu: &ObfuscatedAndEncryptedNetworkPDU,
+    ) {
+        if self.proxy.accepts(ctx, dst) {
+            // don't fail if we fail to forward to the proxy client.
+            ctx.transmit(&PDU::Proxy(ProxyMessage::Network(pdu.clone())))
+                .await
+                .ok();
+        }
+    }
+
+    fn authenticate_inbound<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &mut ObfuscatedAndEncryptedNetworkPDU,
+    ) -> Result<Option<CleartextNetworkPDU>, DeviceError> {
+        #[cfg(feature = "ble-mesh-friend")]
+        if let Some(pdu) = self.authentication.process_inbound_with_credentials(
+            ctx,
+            pdu,
+            &self.friendships.credentials(),
+        )? {
+            return Ok(Some(pdu));
+        }
+
+        #[cfg(feature = "ble-mesh-lpn")]
+        if let Some(pdu) = self.authentication.process_inbound_with_credentials(
+            ctx,
+            pdu,
+            &self.low_power.credentials(),
+        )? {
+            return Ok(Some(pdu));
+        }
+
+        self.authentication.process_inbound(ctx, pdu)
+    }
+
+    /// Transmit a friendship message secured with the credentials it carries.
+    #[cfg(any(feature = "ble-mesh-friend", feature = "ble-mesh-lpn"))]
+    async fn transmit_with_credentials<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &CleartextNetworkPDU,
+    ) -> Result<(), DeviceError> {
+        if let Some(pdu) = self
+            .authentication
+            .process_outbound_with_credentials(ctx, pdu)?
+        {
+            // friendship messages are not retransmitted, and
+            // don't fail if we fail to transmit them.
+            ctx.transmit(&PDU::Network(pdu)).await.ok();
+        }
+        Ok(())
+    }
+
     pub(crate) async fn process_outbound<C: PipelineContext>(
         &mut self,
         ctx: &C,
         message: &AccessMessage,
         publish: Option<(ModelKey, PublishRetransmitDetails)>,
+        id: Option<MessageId>,
         network_retransmit: NetworkRetransmitDetails,
     ) -> Result<(), DeviceError> {
         trace!("outbound <<<< {:?}", message);
@@ -131,11 +374,18 @@ impl ProvisionedPipeline {
 
         let transmit_fut = async move {
             if let Some(message) = self.upper.process_outbound(ctx, message, publish)? {
-                if let Some(message) = self.lower.process_outbound(ctx, message).await? {
-                    for message in message.iter() {
-                        if let Some(message) = self.authentication.process_outbound(ctx, message)? {
+                if let Some(message) = self.lower.process_outbound(ctx, message, id).await? {
+                    for (index, message) in message.iter().enumerate() {
+                        if index > 0 {
+                            Timer::after(ctx.sar_config().segment_interval).await;
+                        }
+                        if let Some(encrypted) =
+                            self.authentication.process_outbound(ctx, message)?
+                        {
+                            #[cfg(feature = "ble-mesh-proxy")]
+                            self.forward_to_proxy(ctx, &message.dst, &encrypted).await;
                             self.transmit
-                                .process_outbound(ctx, message, &network_retransmit)
+                                .process_outbound(ctx, encrypted, &network_retransmit)
                                 .await?;
                         }
                     }
@@ -156,6 +406,31 @@ impl ProvisionedPipeline {
         }
     }
 
+    /// Transmit a transport control message originated by this node.
+    pub(crate) async fn process_outbound_control<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        control: UpperControl,
+        network_retransmit: NetworkRetransmitDetails,
+    ) -> Result<(), DeviceError> {
+        if let Some(message) = self
+            .lower
+            .process_outbound(ctx, UpperPDU::Control(control), None)
+            .await?
+        {
+            for message in message.iter() {
+                if let Some(encrypted) = self.authentication.process_outbound(ctx, message)? {
+                    #[cfg(feature = "ble-mesh-proxy")]
+                    self.forward_to_proxy(ctx, &message.dst, &encrypted).await;
+                    self.transmit
+                        .process_outbound(ctx, encrypted, &network_retransmit)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub async fn retransmit<C: PipelineContext>(
         &mut self,
         ctx: &C,
@@ -166,16 +441,37 @@ impl ProvisionedPipeline {
             Expiration::Publish => self.upper.retransmit(ctx).await,
             Expiration::Ack => {
                 if let Some(message) = self.lower.retransmit(ctx)? {
-                    for message in message.iter() {
-                        if let Some(message) = self.authentication.process_outbound(ctx, message)? {
+                    for (index, message) in message.iter().enumerate() {
+                        if index > 0 {
+                            Timer::after(ctx.sar_config().segment_interval).await;
+                        }
+                        if let Some(encrypted) =
+                            self.authentication.process_outbound(ctx, message)?
+                        {
+                            #[cfg(feature = "ble-mesh-proxy")]
+                            self.forward_to_proxy(ctx, &message.dst, &encrypted).await;
                             self.transmit
-                                .process_outbound(ctx, message, &ctx.network_retransmit())
+                                .process_outbound(ctx, encrypted, &ctx.network_retransmit())
                                 .await?;
                         }
                     }
                 }
                 Ok(())
             }
+            #[cfg(feature = "ble-mesh-friend")]
+            Expiration::Friend => {
+                for message in self.friendships.process_deadline(ctx).await?.iter() {
+                    self.transmit_with_credentials(ctx, message).await?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "ble-mesh-lpn")]
+            Expiration::LowPower => {
+                if let Some(message) = self.low_power.process_deadline(ctx).await? {
+                    self.transmit_with_credentials(ctx, &message).await?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -1,20 +1,38 @@
 use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
-use crate::drivers::ble::mesh::config::network::NetworkDetails;
+use crate::drivers::ble::mesh::config::network::{NetworkKeyHandle, MAX_NETWORK_KEY_MATERIAL};
 use crate::drivers::ble::mesh::crypto::nonce::NetworkNonce;
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::crypto::nonce::ProxyNonce;
 use crate::drivers::ble::mesh::crypto::{aes_ccm_decrypt_detached, aes_ccm_encrypt_detached, e};
 use crate::drivers::ble::mesh::driver::pipeline::mesh::MeshContext;
 use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::NetKeyIndex;
 use crate::drivers::ble::mesh::pdu::lower;
 use crate::drivers::ble::mesh::pdu::lower::LowerPDU;
 use crate::drivers::ble::mesh::pdu::network::{
     CleartextNetworkPDU, ObfuscatedAndEncryptedNetworkPDU,
 };
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::pdu::proxy::ProxyConfigurationMessage;
 use heapless::Vec;
 
 pub trait AuthenticationContext: MeshContext {
-    fn iv_index(&self) -> Option<u32>;
-
-    fn find_network_keys_by_nid(&self, nid: u8) -> Result<Vec<NetworkDetails, 10>, DeviceError>;
+    /// The IV index identified by a network PDU's IVI bit.
+    fn iv_index(&self, ivi: u8) -> Option<u32>;
+
+    /// The IV index for locally originated network PDUs.
+    fn transmit_iv_index(&self) -> Option<u32>;
+
+    fn find_network_keys_by_nid(
+        &self,
+        nid: u8,
+    ) -> Result<Vec<NetworkKeyHandle, MAX_NETWORK_KEY_MATERIAL>, DeviceError>;
+
+    /// The key to transmit with on a subnet, which depends on its key refresh phase.
+    fn transmit_network_key(
+        &self,
+        net_key_index: &NetKeyIndex,
+    ) -> Result<NetworkKeyHandle, DeviceError>;
 }
 
 pub struct Authentication {}
@@ -31,10 +49,34 @@ impl Authentication {
         ctx: &C,
         pdu: &mut ObfuscatedAndEncryptedNetworkPDU,
     ) -> Result<Option<CleartextNetworkPDU>, DeviceError> {
-        if let Some(iv_index) = ctx.iv_index() {
+        let networks = ctx.find_network_keys_by_nid(pdu.nid)?;
+        let result = self.decrypt(ctx, pdu, &networks)?;
+        if result.is_none() && !networks.is_empty() {
+            return Err(DeviceError::CryptoError("inbound network pdu"));
+        }
+        Ok(result)
+    }
+
+    /// Attempt to decrypt with security credentials other than those of a
+    /// subnet, such as friendship credentials.
+    pub fn process_inbound_with_credentials<C: AuthenticationContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &ObfuscatedAndEncryptedNetworkPDU,
+        credentials: &[NetworkKeyHandle],
+    ) -> Result<Option<CleartextNetworkPDU>, DeviceError> {
+        self.decrypt(ctx, pdu, credentials)
+    }
+
+    fn decrypt<C: AuthenticationContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &ObfuscatedAndEncryptedNetworkPDU,
+        networks: &[NetworkKeyHandle],
+    ) -> Result<Option<CleartextNetworkPDU>, DeviceError> {
+        if let Some(iv_index) = ctx.iv_index(pdu.ivi) {
             let privacy_plaintext = Self::privacy_plaintext(iv_index, &pdu.encrypted_and_mic);
-            let networks = ctx.find_network_keys_by_nid(pdu.nid)?;
-            for network_key in networks {
+            for network_key in networks.iter().filter(|e| e.nid == pdu.nid) {
                 let pecb = e(&network_key.privacy_key, privacy_plaintext)
                     .map_err(|_| DeviceError::InvalidKeyLength)?;
 
@@ -51,23 +93,28 @@ impl Authentication {
                     iv_index,
                 );
 
-                let encrypted_len = pdu.encrypted_and_mic.len();
+                // decrypted in a copy, as a failed attempt leaves the buffer
+                // unusable for the next subnet sharing this NID.
+                let mut encrypted_and_mic = pdu.encrypted_and_mic.clone();
+                let encrypted_len = encrypted_and_mic.len();
 
                 let (payload, mic) = if !ctl {
                     // 32 bit mic
-                    pdu.encrypted_and_mic.split_at_mut(encrypted_len - 4)
+                    encrypted_and_mic.split_at_mut(encrypted_len - 4)
                 } else {
                     // 64 bit mic
-                    pdu.encrypted_and_mic.split_at_mut(encrypted_len - 8)
+                    encrypted_and_mic.split_at_mut(encrypted_len - 8)
                 };
 
-                if let Ok(_) = aes_ccm_decrypt_detached(
+                if aes_ccm_decrypt_detached(
                     &network_key.encryption_key,
                     &nonce.into_bytes(),
                     payload,
                     mic,
                     None,
-                ) {
+                )
+                .is_ok()
+                {
                     let ttl = unobfuscated[0] & 0b01111111;
                     let seq =
                         u32::from_be_bytes([0, unobfuscated[1], unobfuscated[2], unobfuscated[3]]);
@@ -80,7 +127,7 @@ impl Authentication {
                     let transport_pdu = lower::LowerPDU::parse(ctl, &payload[2..])?;
 
                     return Ok(Some(CleartextNetworkPDU {
-                        network_key: network_key.into(),
+                        network_key: *network_key,
                         ivi: pdu.ivi,
                         nid: pdu.nid,
                         ttl,
@@ -89,8 +136,6 @@ impl Authentication {
                         dst,
                         transport_pdu,
                     }));
-                } else {
-                    return Err(DeviceError::CryptoError("inbound network pdu"));
                 }
             }
         }
@@ -102,7 +147,29 @@ impl Authentication {
         ctx: &C,
         pdu: &CleartextNetworkPDU,
     ) -> Result<Option<ObfuscatedAndEncryptedNetworkPDU>, DeviceError> {
-        if let Some(iv_index) = ctx.iv_index() {
+        // relayed and locally originated PDUs alike follow the key refresh
+        // phase of their subnet.
+        let network_key = ctx.transmit_network_key(&pdu.network_key.key_index)?;
+        self.encrypt(ctx, pdu, &network_key)
+    }
+
+    /// Encrypt with the exact security credentials carried by the PDU, such
+    /// as friendship credentials, rather than those of its subnet.
+    pub fn process_outbound_with_credentials<C: AuthenticationContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &CleartextNetworkPDU,
+    ) -> Result<Option<ObfuscatedAndEncryptedNetworkPDU>, DeviceError> {
+        self.encrypt(ctx, pdu, &pdu.network_key)
+    }
+
+    fn encrypt<C: AuthenticationContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &CleartextNetworkPDU,
+        network_key: &NetworkKeyHandle,
+    ) -> Result<Option<ObfuscatedAndEncryptedNetworkPDU>, DeviceError> {
+        if let Some(iv_index) = ctx.iv_index(pdu.ivi) {
             let ctl = match &pdu.transport_pdu {
                 LowerPDU::Access(_) => false,
                 LowerPDU::Control(_) => true,
@@ -123,7 +190,7 @@ impl Authentication {
                 let mut mic = [0; 8];
 
                 aes_ccm_encrypt_detached(
-                    &pdu.network_key.encryption_key,
+                    &network_key.encryption_key,
                     &nonce.into_bytes(),
                     &mut encrypted_and_mic,
                     &mut mic,
@@ -137,7 +204,7 @@ impl Authentication {
                 let mut mic = [0; 4];
 
                 aes_ccm_encrypt_detached(
-                    &pdu.network_key.encryption_key,
+                    &network_key.encryption_key,
                     &nonce.into_bytes(),
                     &mut encrypted_and_mic,
                     &mut mic,
@@ -149,33 +216,154 @@ impl Authentication {
                     .map_err(|_| DeviceError::InsufficientBuffer)?;
             }
 
-            let privacy_plaintext = Self::privacy_plaintext(iv_index, &encrypted_and_mic);
+            Ok(Some(Self::obfuscate(
+                iv_index,
+                pdu.ivi,
+                network_key,
+                ctl_ttl,
+                pdu.seq,
+                &pdu.src,
+                encrypted_and_mic,
+            )?))
+        } else {
+            Err(DeviceError::NotProvisioned)
+        }
+    }
+
+    /// Decrypt a proxy configuration message received from a proxy client,
+    /// along with the subnet it was secured with.
+    #[cfg(feature = "ble-mesh-proxy")]
+    pub fn process_inbound_proxy<C: AuthenticationContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &ObfuscatedAndEncryptedNetworkPDU,
+    ) -> Result<Option<(NetworkKeyHandle, ProxyConfigurationMessage)>, DeviceError> {
+        if let Some(iv_index) = ctx.iv_index(pdu.ivi) {
+            let networks = ctx.find_network_keys_by_nid(pdu.nid)?;
+            let privacy_plaintext = Self::privacy_plaintext(iv_index, &pdu.encrypted_and_mic);
+            for network_key in networks.iter() {
+                let pecb = e(&network_key.privacy_key, privacy_plaintext)
+                    .map_err(|_| DeviceError::InvalidKeyLength)?;
 
-            let pecb = e(&pdu.network_key.privacy_key, privacy_plaintext)
-                .map_err(|_| DeviceError::InvalidKeyLength)?;
+                let unobfuscated = Self::xor(pecb, pdu.obfuscated);
 
-            let mut unobfuscated = [0; 6];
-            unobfuscated[0] = ctl_ttl;
+                // proxy configuration messages are always CTL=1, TTL=0.
+                if unobfuscated[0] != 0b10000000 || pdu.encrypted_and_mic.len() < 8 {
+                    continue;
+                }
 
-            let seq_bytes = pdu.seq.to_be_bytes();
-            unobfuscated[1] = seq_bytes[1];
-            unobfuscated[2] = seq_bytes[2];
-            unobfuscated[3] = seq_bytes[3];
+                let seq =
+                    u32::from_be_bytes([0, unobfuscated[1], unobfuscated[2], unobfuscated[3]]);
 
-            let src_bytes = pdu.src.as_bytes();
-            unobfuscated[4] = src_bytes[0];
-            unobfuscated[5] = src_bytes[1];
-            let obfuscated = Self::xor(pecb, unobfuscated);
+                let nonce = ProxyNonce::new(seq, [unobfuscated[4], unobfuscated[5]], iv_index);
 
-            Ok(Some(ObfuscatedAndEncryptedNetworkPDU {
-                ivi: pdu.ivi,
-                nid: pdu.nid,
-                obfuscated,
-                encrypted_and_mic,
-            }))
-        } else {
-            Err(DeviceError::NotProvisioned)
+                let mut encrypted_and_mic = pdu.encrypted_and_mic.clone();
+                let encrypted_len = encrypted_and_mic.len();
+                let (payload, mic) = encrypted_and_mic.split_at_mut(encrypted_len - 8);
+
+                if aes_ccm_decrypt_detached(
+                    &network_key.encryption_key,
+                    &nonce.into_bytes(),
+                    payload,
+                    mic,
+                    None,
+                )
+                .is_ok()
+                {
+                    if payload.len() < 2
+                        || !matches!(
+                            Address::parse([payload[0], payload[1]]),
+                            Address::Unassigned
+                        )
+                    {
+                        return Err(DeviceError::InvalidDstAddress);
+                    }
+                    let message = ProxyConfigurationMessage::parse(&payload[2..])?;
+                    return Ok(Some((*network_key, message)));
+                }
+            }
         }
+        Ok(None)
+    }
+
+    /// Encrypt a proxy configuration message for a proxy client.
+    #[cfg(feature = "ble-mesh-proxy")]
+    pub fn process_outbound_proxy<C: AuthenticationContext>(
+        &mut self,
+        ctx: &C,
+        network_key: &NetworkKeyHandle,
+        seq: u32,
+        src: &UnicastAddress,
+        message: &ProxyConfigurationMessage,
+    ) -> Result<ObfuscatedAndEncryptedNetworkPDU, DeviceError> {
+        let iv_index = ctx.transmit_iv_index().ok_or(DeviceError::NotProvisioned)?;
+        let ivi = (iv_index & 1) as u8;
+
+        let nonce = ProxyNonce::new(seq, src.as_bytes(), iv_index);
+
+        let mut encrypted_and_mic = Vec::new();
+        encrypted_and_mic
+            .extend_from_slice(&Address::Unassigned.as_bytes())
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        message.emit(&mut encrypted_and_mic)?;
+
+        let mut mic = [0; 8];
+        aes_ccm_encrypt_detached(
+            &network_key.encryption_key,
+            &nonce.into_bytes(),
+            &mut encrypted_and_mic,
+            &mut mic,
+            None,
+        )
+        .map_err(|_| DeviceError::CryptoError("outbound proxy configuration"))?;
+        encrypted_and_mic
+            .extend_from_slice(&mic)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+
+        Self::obfuscate(
+            iv_index,
+            ivi,
+            network_key,
+            0b10000000,
+            seq,
+            src,
+            encrypted_and_mic,
+        )
+    }
+
+    fn obfuscate(
+        iv_index: u32,
+        ivi: u8,
+        network_key: &NetworkKeyHandle,
+        ctl_ttl: u8,
+        seq: u32,
+        src: &UnicastAddress,
+        encrypted_and_mic: Vec<u8, 28>,
+    ) -> Result<ObfuscatedAndEncryptedNetworkPDU, DeviceError> {
+        let privacy_plaintext = Self::privacy_plaintext(iv_index, &encrypted_and_mic);
+
+        let pecb = e(&network_key.privacy_key, privacy_plaintext)
+            .map_err(|_| DeviceError::InvalidKeyLength)?;
+
+        let mut unobfuscated = [0; 6];
+        unobfuscated[0] = ctl_ttl;
+
+        let seq_bytes = seq.to_be_bytes();
+        unobfuscated[1] = seq_bytes[1];
+        unobfuscated[2] = seq_bytes[2];
+        unobfuscated[3] = seq_bytes[3];
+
+        let src_bytes = src.as_bytes();
+        unobfuscated[4] = src_bytes[0];
+        unobfuscated[5] = src_bytes[1];
+        let obfuscated = Self::xor(pecb, unobfuscated);
+
+        Ok(ObfuscatedAndEncryptedNetworkPDU {
+            ivi,
+            nid: network_key.nid,
+            obfuscated,
+            encrypted_and_mic,
+        })
     }
 
     fn privacy_plaintext(iv_index: u32, encrypted_and_mic: &[u8]) -> [u8; 16] {
@@ -3,6 +3,8 @@ use embassy::time::Instant;
 
 pub mod authentication;
 pub mod network_message_cache;
+#[cfg(feature = "ble-mesh-proxy")]
+pub mod proxy;
 #[cfg(feature = "ble-mesh-relay")]
 pub mod relay;
 pub mod replay_cache;
@@ -7,7 +7,7 @@ use uluru::LRUCache;
 struct CacheEntry {
     seq: u32,
     src: UnicastAddress,
-    iv_index: u16,
+    iv_index: u32,
 }
 
 pub struct NetworkMessageCache {
@@ -27,7 +27,7 @@ impl NetworkMessageCache {
         let entry = CacheEntry {
             seq: pdu.seq,
             src: pdu.src,
-            iv_index: (iv_index & 0xFFFF) as u16,
+            iv_index,
         };
         if let None = self.lru.find(|e| *e == entry) {
             self.lru.insert(entry);
@@ -0,0 +1,298 @@
+use crate::drivers::ble::mesh::address::Address;
+use crate::drivers::ble::mesh::driver::pipeline::mesh::MeshContext;
+use crate::drivers::ble::mesh::pdu::network::CleartextNetworkPDU;
+use crate::drivers::ble::mesh::pdu::proxy::{FilterType, ProxyConfigurationMessage};
+use heapless::Vec;
+
+/// Maximum number of addresses held by the proxy filter of a connection.
+pub const PROXY_FILTER_SIZE: usize = 8;
+
+pub trait ProxyContext: MeshContext {
+    fn is_proxy_enabled(&self) -> bool;
+
+    /// The connected proxy client, if any.
+    fn proxy_connection(&self) -> Option<u32>;
+}
+
+/// The proxy filter of the connected proxy client, which starts out as an
+/// empty accept list with every new connection.
+pub struct Proxy {
+    connection: Option<u32>,
+    filter_type: FilterType,
+    addresses: Vec<Address, PROXY_FILTER_SIZE>,
+}
+
+impl Default for Proxy {
+    fn default() -> Self {
+        Self {
+            connection: None,
+            filter_type: FilterType::AcceptList,
+            addresses: Vec::new(),
+        }
+    }
+}
+
+impl Proxy {
+    /// Apply a proxy configuration message from the proxy client, returning
+    /// the filter status to respond with.
+    pub fn process_inbound<C: ProxyContext>(
+        &mut self,
+        ctx: &C,
+        message: &ProxyConfigurationMessage,
+    ) -> Option<ProxyConfigurationMessage> {
+        if !ctx.is_proxy_enabled() {
+            return None;
+        }
+        self.sync_connection(ctx);
+
+        match message {
+            ProxyConfigurationMessage::SetFilterType(filter_type) => {
+                self.filter_type = *filter_type;
+                self.addresses.clear();
+            }
+            ProxyConfigurationMessage::AddAddresses(addresses) => {
+                for address in addresses {
+                    if !matches!(address, Address::Unassigned) && !self.addresses.contains(address)
+                    {
+                        // addresses beyond the capacity of the filter are ignored.
+                        self.addresses.push(*address).ok();
+                    }
+                }
+            }
+            ProxyConfigurationMessage::RemoveAddresses(addresses) => {
+                self.addresses.retain(|e| !addresses.contains(e));
+            }
+            ProxyConfigurationMessage::FilterStatus { .. } => {
+                // not applicable to server role
+                return None;
+            }
+        }
+
+        Some(ProxyConfigurationMessage::FilterStatus {
+            filter_type: self.filter_type,
+            list_size: self.addresses.len() as u16,
+        })
+    }
+
+    /// Whether a network PDU destined to `dst` is to be forwarded to the
+    /// connected proxy client.
+    pub fn accepts<C: ProxyContext>(&mut self, ctx: &C, dst: &Address) -> bool {
+        if !ctx.is_proxy_enabled() {
+            return false;
+        }
+        self.sync_connection(ctx);
+        if self.connection.is_none() {
+            return false;
+        }
+
+        let listed = self.addresses.contains(dst);
+        match self.filter_type {
+            FilterType::AcceptList => listed,
+            FilterType::RejectList => !listed,
+        }
+    }
+
+    /// The copy of a network PDU received over advertising to forward to
+    /// the connected proxy client, if its TTL and proxy filter allow.
+    pub fn forward_to_client<C: ProxyContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &CleartextNetworkPDU,
+    ) -> Option<CleartextNetworkPDU> {
+        if pdu.ttl >= 2 && self.accepts(ctx, &pdu.dst) {
+            Some(Self::relayed(pdu))
+        } else {
+            None
+        }
+    }
+
+    /// The copy of a network PDU received from the connected proxy client to
+    /// forward over advertising, if its TTL allows.
+    pub fn forward_from_client<C: ProxyContext>(
+        &mut self,
+        ctx: &C,
+        pdu: &CleartextNetworkPDU,
+    ) -> Option<CleartextNetworkPDU> {
+        if !ctx.is_proxy_enabled() {
+            return None;
+        }
+        self.sync_connection(ctx);
+
+        // the client hears what is sent back to it.
+        let src = Address::Unicast(pdu.src);
+        match self.filter_type {
+            FilterType::AcceptList => {
+                if !self.addresses.contains(&src) {
+                    self.addresses.push(src).ok();
+                }
+            }
+            FilterType::RejectList => {
+                self.addresses.retain(|e| *e != src);
+            }
+        }
+
+        if pdu.ttl >= 2 && !ctx.is_local_unicast(&pdu.dst) {
+            Some(Self::relayed(pdu))
+        } else {
+            None
+        }
+    }
+
+    fn relayed(pdu: &CleartextNetworkPDU) -> CleartextNetworkPDU {
+        CleartextNetworkPDU {
+            ttl: pdu.ttl - 1,
+            transport_pdu: pdu.transport_pdu.clone(),
+            ..*pdu
+        }
+    }
+
+    fn sync_connection<C: ProxyContext>(&mut self, ctx: &C) {
+        let connection = ctx.proxy_connection();
+        if connection != self.connection {
+            self.connection = connection;
+            self.filter_type = FilterType::AcceptList;
+            self.addresses.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::address::UnicastAddress;
+    use crate::drivers::ble::mesh::driver::pipeline::provisioned::test_context::TestContext;
+    use crate::drivers::ble::mesh::pdu::lower::{LowerAccess, LowerAccessMessage, LowerPDU};
+
+    const NODE: u16 = 0x0001;
+    const CLIENT: u16 = 0x0100;
+    const OTHER: u16 = 0x0200;
+
+    impl ProxyContext for TestContext {
+        fn is_proxy_enabled(&self) -> bool {
+            true
+        }
+
+        fn proxy_connection(&self) -> Option<u32> {
+            self.connection.get()
+        }
+    }
+
+    fn addr(value: u16) -> UnicastAddress {
+        UnicastAddress::parse(value.to_be_bytes()).unwrap()
+    }
+
+    fn access(ctx: &TestContext, src: u16, dst: u16, ttl: u8) -> CleartextNetworkPDU {
+        CleartextNetworkPDU {
+            network_key: ctx.network_key,
+            ivi: 0,
+            nid: ctx.network_key.nid,
+            ttl,
+            seq: 1,
+            src: addr(src),
+            dst: addr(dst).into(),
+            transport_pdu: LowerPDU::Access(LowerAccess {
+                akf: true,
+                aid: 0x01u8.into(),
+                message: LowerAccessMessage::Unsegmented(Vec::from_slice(&[0x00; 8]).unwrap()),
+            }),
+        }
+    }
+
+    fn connected() -> TestContext {
+        let ctx = TestContext::new(NODE);
+        ctx.connection.set(Some(1));
+        ctx
+    }
+
+    #[test]
+    fn forward_to_client() {
+        let ctx = connected();
+        let mut proxy = Proxy::default();
+        proxy.process_inbound(
+            &ctx,
+            &ProxyConfigurationMessage::AddAddresses(
+                Vec::from_slice(&[addr(CLIENT).into()]).unwrap(),
+            ),
+        );
+
+        let forwarded = proxy
+            .forward_to_client(&ctx, &access(&ctx, OTHER, CLIENT, 5))
+            .unwrap();
+        assert_eq!(forwarded.ttl, 4);
+        assert_eq!(forwarded.seq, 1);
+        assert_eq!(forwarded.src, addr(OTHER));
+
+        // a TTL of 1 may not be relayed.
+        assert!(proxy
+            .forward_to_client(&ctx, &access(&ctx, OTHER, CLIENT, 1))
+            .is_none());
+        // not on the accept list.
+        assert!(proxy
+            .forward_to_client(&ctx, &access(&ctx, CLIENT, OTHER, 5))
+            .is_none());
+
+        // a new connection starts with an empty filter.
+        ctx.connection.set(Some(2));
+        assert!(proxy
+            .forward_to_client(&ctx, &access(&ctx, OTHER, CLIENT, 5))
+            .is_none());
+
+        ctx.connection.set(None);
+        assert!(proxy
+            .forward_to_client(&ctx, &access(&ctx, OTHER, CLIENT, 5))
+            .is_none());
+    }
+
+    #[test]
+    fn forward_from_client() {
+        let ctx = connected();
+        let mut proxy = Proxy::default();
+
+        let forwarded = proxy
+            .forward_from_client(&ctx, &access(&ctx, CLIENT, OTHER, 5))
+            .unwrap();
+        assert_eq!(forwarded.ttl, 4);
+        assert_eq!(forwarded.dst, Address::from(addr(OTHER)));
+
+        // the source of the client has been added to the accept list.
+        assert!(proxy
+            .forward_to_client(&ctx, &access(&ctx, OTHER, CLIENT, 5))
+            .is_some());
+
+        // a TTL of 1 may not be relayed.
+        assert!(proxy
+            .forward_from_client(&ctx, &access(&ctx, CLIENT, OTHER, 1))
+            .is_none());
+        // destined to this node only.
+        assert!(proxy
+            .forward_from_client(&ctx, &access(&ctx, CLIENT, NODE, 5))
+            .is_none());
+    }
+
+    #[test]
+    fn forward_from_client_with_reject_list() {
+        let ctx = connected();
+        let mut proxy = Proxy::default();
+        proxy.process_inbound(
+            &ctx,
+            &ProxyConfigurationMessage::SetFilterType(FilterType::RejectList),
+        );
+        proxy.process_inbound(
+            &ctx,
+            &ProxyConfigurationMessage::AddAddresses(
+                Vec::from_slice(&[addr(CLIENT).into()]).unwrap(),
+            ),
+        );
+        assert!(proxy
+            .forward_to_client(&ctx, &access(&ctx, OTHER, CLIENT, 5))
+            .is_none());
+
+        // the source of the client has been removed from the reject list.
+        assert!(proxy
+            .forward_from_client(&ctx, &access(&ctx, CLIENT, OTHER, 5))
+            .is_some());
+        assert!(proxy
+            .forward_to_client(&ctx, &access(&ctx, OTHER, CLIENT, 5))
+            .is_some());
+    }
+}
@@ -40,9 +40,10 @@ impl Relay {
         if !ctx.is_local_unicast(&pdu.dst) {
             // only relay if there's TTL remaining.
             if pdu.ttl >= 2
-                && !self
-                    .cache
-                    .has_seen(ctx.iv_index().ok_or(DeviceError::NotProvisioned)?, pdu)
+                && !self.cache.has_seen(
+                    ctx.iv_index(pdu.ivi).ok_or(DeviceError::NotProvisioned)?,
+                    pdu,
+                )
             {
                 info!("relay");
                 // decrease TTL and send a copy along.
@@ -5,7 +5,7 @@ use uluru::LRUCache;
 struct CacheEntry {
     seq: u32,
     src: UnicastAddress,
-    iv_index: u16,
+    iv_index: u32,
 }
 
 pub struct ReplayCache {
@@ -22,8 +22,6 @@ impl Default for ReplayCache {
 
 impl ReplayCache {
     pub fn has_seen(&mut self, iv_index: u32, seq: u32, src: UnicastAddress) -> bool {
-        let iv_index = (iv_index & 0xFFFF) as u16;
-
         if let Some(entry) = self.lru.find(|e| e.src == src) {
             if iv_index < entry.iv_index {
                 true
@@ -0,0 +1,229 @@
+//! A node with a single subnet, for exercising the stages of the provisioned
+//! pipeline without a full [`Node`](crate::drivers::ble::mesh::driver::node::Node).
+
+use crate::drivers::ble::mesh::address::{Address, LabelUuid, UnicastAddress};
+use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+use crate::drivers::ble::mesh::config::network::{NetworkKeyHandle, MAX_NETWORK_KEY_MATERIAL};
+use crate::drivers::ble::mesh::crypto;
+use crate::drivers::ble::mesh::crypto::nonce::{ApplicationNonce, DeviceNonce};
+use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::driver::node::sar::{SarConfig, TransmissionFailure};
+use crate::drivers::ble::mesh::driver::pipeline::mesh::{MeshContext, NetworkRetransmitDetails};
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::lower::LowerContext;
+use crate::drivers::ble::mesh::driver::pipeline::provisioned::network::authentication::AuthenticationContext;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::interface::PDU;
+use crate::drivers::ble::mesh::model::foundation::configuration::{AppKeyIndex, NetKeyIndex};
+use core::cell::{Cell, RefCell};
+use core::future::{ready, Ready};
+use embassy::time::{Duration, Instant};
+use heapless::Vec;
+
+pub(crate) const DEFAULT_TTL: u8 = 5;
+
+pub(crate) struct TestContext {
+    pub(crate) address: UnicastAddress,
+    pub(crate) number_of_elements: u8,
+    pub(crate) network_key: NetworkKeyHandle,
+    pub(crate) sequence: Cell<u32>,
+    pub(crate) subscriptions: RefCell<Vec<Address, 8>>,
+    /// The last deadline the stage under test asked to be woken up at.
+    pub(crate) deadline: Cell<Option<Instant>>,
+    /// The connected proxy client, if any.
+    pub(crate) connection: Cell<Option<u32>>,
+    pub(crate) sar: SarConfig,
+    /// The segmented messages given up on.
+    pub(crate) failures: RefCell<Vec<TransmissionFailure, 4>>,
+}
+
+impl TestContext {
+    pub(crate) fn new(address: u16) -> Self {
+        let network_key = [0x01; 16];
+        let (nid, encryption_key, privacy_key) = crypto::k2(&network_key, &[0x00]).unwrap();
+        Self {
+            address: UnicastAddress::parse(address.to_be_bytes()).unwrap(),
+            number_of_elements: 1,
+            network_key: NetworkKeyHandle {
+                network_key: network_key.into(),
+                key_index: NetKeyIndex::new(0),
+                nid,
+                encryption_key,
+                privacy_key,
+            },
+            sequence: Cell::new(0),
+            subscriptions: RefCell::new(Vec::new()),
+            deadline: Cell::new(None),
+            connection: Cell::new(None),
+            sar: SarConfig::default(),
+            failures: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl MeshContext for TestContext {
+    fn uuid(&self) -> Uuid {
+        Uuid([0; 16])
+    }
+
+    fn network_retransmit(&self) -> NetworkRetransmitDetails {
+        NetworkRetransmitDetails {
+            count: 0,
+            interval: Duration::from_millis(10),
+        }
+    }
+
+    type TransmitFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn transmit<'m>(&'m self, _: &'m PDU) -> Self::TransmitFuture<'m> {
+        ready(Ok(()))
+    }
+
+    fn primary_unicast_address(&self) -> Result<UnicastAddress, DeviceError> {
+        Ok(self.address)
+    }
+
+    fn is_local_unicast(&self, addr: &Address) -> bool {
+        match addr {
+            Address::Unicast(addr) => {
+                *addr >= self.address && *addr < self.address + self.number_of_elements
+            }
+            _ => false,
+        }
+    }
+}
+
+impl AuthenticationContext for TestContext {
+    fn iv_index(&self, _: u8) -> Option<u32> {
+        Some(0)
+    }
+
+    fn transmit_iv_index(&self) -> Option<u32> {
+        Some(0)
+    }
+
+    fn find_network_keys_by_nid(
+        &self,
+        nid: u8,
+    ) -> Result<Vec<NetworkKeyHandle, MAX_NETWORK_KEY_MATERIAL>, DeviceError> {
+        Ok(core::iter::once(self.network_key)
+            .filter(|e| e.nid == nid)
+            .collect())
+    }
+
+    fn transmit_network_key(&self, _: &NetKeyIndex) -> Result<NetworkKeyHandle, DeviceError> {
+        Ok(self.network_key)
+    }
+}
+
+impl LowerContext for TestContext {
+    fn find_label_uuids_by_address(
+        &self,
+        _: Address,
+    ) -> Result<Option<Vec<LabelUuid, 3>>, DeviceError> {
+        Ok(None)
+    }
+
+    type DecryptDeviceKeyFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn decrypt_device_key<'m>(
+        &'m self,
+        _: DeviceNonce,
+        _: &'m mut [u8],
+        _: &'m [u8],
+    ) -> Self::DecryptDeviceKeyFuture<'m> {
+        ready(Err(DeviceError::CryptoError("device key")))
+    }
+
+    type EncryptDeviceKeyFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn encrypt_device_key<'m>(
+        &'m self,
+        _: DeviceNonce,
+        _: &'m mut [u8],
+        _: &'m mut [u8],
+    ) -> Self::EncryptDeviceKeyFuture<'m> {
+        ready(Err(DeviceError::CryptoError("device key")))
+    }
+
+    fn decrypt_remote_device_key(
+        &self,
+        _: &UnicastAddress,
+        _: DeviceNonce,
+        _: &mut [u8],
+        _: &[u8],
+    ) -> Result<(), DeviceError> {
+        Err(DeviceError::CryptoError("remote device key"))
+    }
+
+    fn encrypt_remote_device_key(
+        &self,
+        _: &UnicastAddress,
+        _: DeviceNonce,
+        _: &mut [u8],
+        _: &mut [u8],
+    ) -> Result<(), DeviceError> {
+        Err(DeviceError::CryptoError("remote device key"))
+    }
+
+    fn encrypt_application_key(
+        &self,
+        _: &NetKeyIndex,
+        _: &AppKeyIndex,
+        _: ApplicationNonce,
+        _: &mut [u8],
+        _: &mut [u8],
+        _: Option<&[u8]>,
+    ) -> Result<ApplicationKeyIdentifier, DeviceError> {
+        Err(DeviceError::CryptoError("application key"))
+    }
+
+    fn decrypt_application_key(
+        &self,
+        _: &NetKeyIndex,
+        _: ApplicationKeyIdentifier,
+        _: ApplicationNonce,
+        _: &mut [u8],
+        _: &[u8],
+        _: Option<&[u8]>,
+    ) -> Result<AppKeyIndex, DeviceError> {
+        Err(DeviceError::CryptoError("application key"))
+    }
+
+    type NextSequenceFuture<'m> = Ready<Result<u32, DeviceError>>
+    where
+        Self: 'm;
+
+    fn next_sequence<'m>(&'m self) -> Self::NextSequenceFuture<'m> {
+        let seq = self.sequence.get();
+        self.sequence.set(seq + 1);
+        ready(Ok(seq))
+    }
+
+    fn default_ttl(&self) -> u8 {
+        DEFAULT_TTL
+    }
+
+    fn has_any_subscription(&self, dst: &Address) -> bool {
+        self.subscriptions.borrow().contains(dst)
+    }
+
+    fn is_locally_relevant(&self, dst: &Address) -> bool {
+        self.is_local_unicast(dst) || self.has_any_subscription(dst)
+    }
+
+    fn ack_deadline(&self, _: Option<Instant>) {}
+
+    fn sar_config(&self) -> SarConfig {
+        self.sar
+    }
+
+    fn transmission_failure(&self, failure: TransmissionFailure) {
+        self.failures.borrow_mut().push(failure).ok();
+    }
+}
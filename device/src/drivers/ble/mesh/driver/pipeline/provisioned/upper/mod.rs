@@ -1,5 +1,8 @@
+use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
 use crate::drivers::ble::mesh::driver::DeviceError;
 use crate::drivers::ble::mesh::pdu::access::AccessMessage;
+use crate::drivers::ble::mesh::pdu::heartbeat::Heartbeat;
+use crate::drivers::ble::mesh::pdu::lower::Opcode;
 use crate::drivers::ble::mesh::pdu::upper::{UpperAccess, UpperPDU};
 use embassy::time::Instant;
 
@@ -20,6 +23,9 @@ pub trait UpperContext {
         Self: 'm;
 
     fn republish<'m>(&'m self, message: OutboundPublishMessage) -> Self::RepublishFuture<'m>;
+
+    /// Account for a heartbeat received from `src`, which took `hops` to arrive.
+    fn receive_heartbeat(&self, src: UnicastAddress, dst: Address, hops: u8);
 }
 
 pub struct Upper {
@@ -37,13 +43,17 @@ impl Default for Upper {
 impl Upper {
     pub fn process_inbound<C: UpperContext>(
         &mut self,
-        _ctx: &C,
+        ctx: &C,
         pdu: UpperPDU,
     ) -> Result<Option<AccessMessage>, DeviceError> {
         // todo: split access and control handling, wrap with an enum, I guess.
         match pdu {
-            UpperPDU::Control(_control) => {
-                todo!("inbound upper pdu control")
+            UpperPDU::Control(control) => {
+                if control.opcode == Opcode::Heatbeat {
+                    let heartbeat = Heartbeat::parse(&control.data)?;
+                    ctx.receive_heartbeat(control.src, control.dst, heartbeat.hops(control.ttl));
+                }
+                Ok(None)
             }
             UpperPDU::Access(access) => {
                 let message = AccessMessage::parse(&access)?;
@@ -70,8 +80,10 @@ impl Upper {
             nid: message.nid,
             akf: message.akf,
             aid: message.aid,
+            app_key_index: message.app_key_index,
             src: message.src,
             dst: message.dst,
+            device_key: message.device_key,
             payload,
         })))
     }
@@ -65,6 +65,7 @@ impl<const N: usize> Publish<N> {
             }) {
                 prev.replace(Entry {
                     message: OutboundPublishMessage {
+                        id: None,
                         element_address: publish.0.unicast_address(),
                         model_identifier: publish.0.model_identifier(),
                         payload: message.payload.clone(),
@@ -77,6 +78,7 @@ impl<const N: usize> Publish<N> {
                 if let Some(empty) = self.cache.iter_mut().find(|e| matches!(e, None)) {
                     empty.replace(Entry {
                         message: OutboundPublishMessage {
+                            id: None,
                             element_address: publish.0.unicast_address(),
                             model_identifier: publish.0.model_identifier(),
                             payload: message.payload.clone(),
@@ -1,5 +1,8 @@
+use crate::drivers::ble::mesh::driver::node::oob::OobValue;
 use crate::drivers::ble::mesh::driver::node::State;
-use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::Provisionable;
+use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::{
+    OobRequest, Provisionable,
+};
 use crate::drivers::ble::mesh::driver::pipeline::PipelineContext;
 use crate::drivers::ble::mesh::driver::DeviceError;
 use crate::drivers::ble::mesh::interface::PDU;
@@ -30,4 +33,19 @@ impl UnprovisionedPipeline {
             Ok(None)
         }
     }
+
+    pub(crate) fn oob_request(&self) -> Option<&OobRequest> {
+        self.provisionable.oob_request()
+    }
+
+    pub(crate) async fn oob_complete<C: PipelineContext>(
+        &mut self,
+        ctx: &C,
+        input: Option<OobValue>,
+    ) -> Result<(), DeviceError> {
+        if let Some(response) = self.provisionable.oob_complete(input) {
+            ctx.transmit(&PDU::Provisioning(response)).await?;
+        }
+        Ok(())
+    }
 }
@@ -1,18 +1,15 @@
+use crate::drivers::ble::mesh::driver::node::oob::OobValue;
 use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::UnprovisionedContext;
 use crate::drivers::ble::mesh::driver::DeviceError;
 use crate::drivers::ble::mesh::provisioning::{
-    InputOOBAction, OOBAction, OOBSize, OutputOOBAction, Start,
+    AuthenticationMethod, InputOOBAction, OOBAction, OOBSize, OutputOOBAction, Start,
 };
 use heapless::Vec;
 
 pub enum AuthValue {
     None,
-    InputEvents(u32),
-    OutputEvents(u32),
-    InputNumeric(u32),
-    OutputNumeric(u32),
-    InputAlphanumeric(Vec<u8, 8>),
-    OutputAlphanumeric(Vec<u8, 8>),
+    Static([u8; 16]),
+    Oob(OobValue),
 }
 
 impl AuthValue {
@@ -22,17 +19,11 @@ impl AuthValue {
             AuthValue::None => {
                 // all zeros
             }
-            AuthValue::InputEvents(num)
-            | AuthValue::OutputEvents(num)
-            | AuthValue::InputNumeric(num)
-            | AuthValue::OutputNumeric(num) => {
-                let num_bytes = num.to_be_bytes();
-                bytes[12] = num_bytes[0];
-                bytes[13] = num_bytes[1];
-                bytes[14] = num_bytes[2];
-                bytes[15] = num_bytes[3];
+            AuthValue::Static(value) => bytes = *value,
+            AuthValue::Oob(OobValue::Numeric(num)) => {
+                bytes[12..16].copy_from_slice(&num.to_be_bytes());
             }
-            AuthValue::InputAlphanumeric(chars) | AuthValue::OutputAlphanumeric(chars) => {
+            AuthValue::Oob(OobValue::Alphanumeric(chars)) => {
                 for (i, byte) in chars.iter().enumerate() {
                     bytes[i] = *byte
                 }
@@ -43,146 +34,135 @@ impl AuthValue {
     }
 }
 
+/// Interaction with the user of the device required to authenticate.
+#[derive(Clone)]
+pub enum OobRequest {
+    Output(OutputOOBAction, OobValue),
+    Input(InputOOBAction, u8),
+}
+
+/// The auth value selected by the provisioner, unless only known once input
+/// by the user, along with any interaction with the user it requires.
 pub fn determine_auth_value<C: UnprovisionedContext>(
     ctx: &C,
     start: &Start,
-) -> Result<AuthValue, DeviceError> {
+) -> Result<(Option<AuthValue>, Option<OobRequest>), DeviceError> {
     Ok(
-        match (&start.authentication_action, &start.authentication_size) {
-            (
-                OOBAction::Output(OutputOOBAction::Blink)
-                | OOBAction::Output(OutputOOBAction::Beep)
-                | OOBAction::Output(OutputOOBAction::Vibrate),
-                OOBSize::MaximumSize(size),
-            ) => {
-                let auth_raw = random_physical_oob(ctx, *size);
-                AuthValue::OutputEvents(auth_raw)
+        match (
+            &start.authentication_method,
+            &start.authentication_action,
+            &start.authentication_size,
+        ) {
+            (AuthenticationMethod::StaticOOBAuthentication, _, _) => {
+                let value = ctx.static_oob().ok_or(DeviceError::InvalidState)?;
+                (Some(AuthValue::Static(value)), None)
             }
             (
-                OOBAction::Input(InputOOBAction::Push) | OOBAction::Input(InputOOBAction::Twist),
+                _,
+                OOBAction::Output(
+                    action @ (OutputOOBAction::Blink
+                    | OutputOOBAction::Beep
+                    | OutputOOBAction::Vibrate),
+                ),
                 OOBSize::MaximumSize(size),
             ) => {
-                let auth_raw = random_physical_oob(ctx, *size);
-                AuthValue::InputEvents(auth_raw)
-            }
-            (OOBAction::Output(OutputOOBAction::OutputNumeric), OOBSize::MaximumSize(size)) => {
-                let auth_raw = random_numeric(ctx, *size);
-                AuthValue::OutputNumeric(auth_raw)
-            }
-            // TODO actually dispatch to device/app/thing's UI for inputs instead of just making up shit.
-            (OOBAction::Input(InputOOBAction::InputNumeric), OOBSize::MaximumSize(size)) => {
-                let auth_raw = random_numeric(ctx, *size);
-                AuthValue::InputNumeric(auth_raw)
+                let value = OobValue::Numeric(random_physical_oob(&mut || ctx.rng_u32(), *size));
+                (
+                    Some(AuthValue::Oob(value.clone())),
+                    Some(OobRequest::Output(*action, value)),
+                )
+            }
+            (_, OOBAction::Output(OutputOOBAction::OutputNumeric), OOBSize::MaximumSize(size)) => {
+                let value = OobValue::Numeric(random_numeric(&mut || ctx.rng_u32(), *size));
+                (
+                    Some(AuthValue::Oob(value.clone())),
+                    Some(OobRequest::Output(OutputOOBAction::OutputNumeric, value)),
+                )
             }
             (
+                _,
                 OOBAction::Output(OutputOOBAction::OutputAlphanumeric),
                 OOBSize::MaximumSize(size),
             ) => {
-                let auth_raw = random_alphanumeric(ctx, *size)?;
-                AuthValue::OutputAlphanumeric(auth_raw)
-            }
-            (OOBAction::Input(InputOOBAction::InputAlphanumeric), OOBSize::MaximumSize(size)) => {
-                let auth_raw = random_alphanumeric(ctx, *size)?;
-                AuthValue::InputAlphanumeric(auth_raw)
+                let value =
+                    OobValue::Alphanumeric(random_alphanumeric(&mut || ctx.rng_u32(), *size)?);
+                (
+                    Some(AuthValue::Oob(value.clone())),
+                    Some(OobRequest::Output(
+                        OutputOOBAction::OutputAlphanumeric,
+                        value,
+                    )),
+                )
+            }
+            (_, OOBAction::Input(action), OOBSize::MaximumSize(size)) => {
+                // known once the user has input what the provisioner presents.
+                (None, Some(OobRequest::Input(*action, *size)))
             }
             _ => {
                 // zeros!
-                AuthValue::None
+                (Some(AuthValue::None), None)
             }
         },
     )
 }
 
-fn random_physical_oob<C: UnprovisionedContext>(ctx: &C, size: u8) -> u32 {
-    // "select a random integer between 0 and 10 to the power of the Authentication Size exclusive"
-    //
-    // ... which could be an absolute metric tonne of beeps/twists/pushes if AuthSize is large-ish.
-    let mut max = 1;
-    for _ in 0..size {
-        max = max * 10;
-    }
+fn max_value(size: u8) -> u32 {
+    10u32.pow(size.clamp(1, 8) as u32)
+}
 
-    loop {
-        let candidate = ctx.rng_u32();
-        if candidate > 0 && candidate < max {
-            return candidate;
+/// A random value for the user to input on the device by means of `action`,
+/// as presented by the provisioner.
+pub(crate) fn random_input(
+    rng_u32: &mut impl FnMut() -> u32,
+    action: InputOOBAction,
+    size: u8,
+) -> Result<OobValue, DeviceError> {
+    Ok(match action {
+        InputOOBAction::Push | InputOOBAction::Twist => {
+            OobValue::Numeric(random_physical_oob(rng_u32, size))
         }
-    }
+        InputOOBAction::InputNumeric => OobValue::Numeric(random_numeric(rng_u32, size)),
+        InputOOBAction::InputAlphanumeric => {
+            OobValue::Alphanumeric(random_alphanumeric(rng_u32, size)?)
+        }
+    })
 }
 
-fn random_numeric<C: UnprovisionedContext>(ctx: &C, size: u8) -> u32 {
-    loop {
-        let candidate = ctx.rng_u32();
+fn random_physical_oob(rng_u32: &mut impl FnMut() -> u32, size: u8) -> u32 {
+    // "select a random integer between 0 and 10 to the power of the Authentication Size exclusive"
+    //
+    // ... which could be an absolute metric tonne of beeps/twists/pushes if AuthSize is large-ish.
+    // zero events could not be told apart from nothing happening.
+    1 + random_below(rng_u32, max_value(size) - 1)
+}
 
-        match size {
-            1 => {
-                if candidate < 10 {
-                    return candidate;
-                }
-            }
-            2 => {
-                if candidate < 100 {
-                    return candidate;
-                }
-            }
-            3 => {
-                if candidate < 1_000 {
-                    return candidate;
-                }
-            }
-            4 => {
-                if candidate < 10_000 {
-                    return candidate;
-                }
-            }
-            5 => {
-                if candidate < 100_000 {
-                    return candidate;
-                }
-            }
-            6 => {
-                if candidate < 1_000_000 {
-                    return candidate;
-                }
-            }
-            7 => {
-                if candidate < 10_000_000 {
-                    return candidate;
-                }
-            }
-            8 => {
-                if candidate < 100_000_000 {
-                    return candidate;
-                }
-            }
-            _ => {
-                // should never get here, but...
-                return 0;
-            }
-        }
-    }
+fn random_numeric(rng_u32: &mut impl FnMut() -> u32, size: u8) -> u32 {
+    random_below(rng_u32, max_value(size))
 }
 
-fn random_alphanumeric<C: UnprovisionedContext>(
-    ctx: &C,
+fn random_alphanumeric(
+    rng_u32: &mut impl FnMut() -> u32,
     size: u8,
 ) -> Result<Vec<u8, 8>, DeviceError> {
+    // Capital ASCII letters A-Z and ASCII numbers 0-9
+    const CHARACTERS: &[u8; 36] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
     let mut random = Vec::new();
     for _ in 0..size {
-        loop {
-            let candidate = ctx.rng_u8();
-            if candidate >= 64 && candidate <= 90 {
-                // Capital ASCII letters A-Z
-                random
-                    .push(candidate)
-                    .map_err(|_| DeviceError::InsufficientBuffer)?;
-            } else if candidate >= 48 && candidate <= 57 {
-                // ASCII numbers 0-9
-                random
-                    .push(candidate)
-                    .map_err(|_| DeviceError::InsufficientBuffer)?;
-            }
-        }
+        random
+            .push(CHARACTERS[random_below(rng_u32, CHARACTERS.len() as u32) as usize])
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
     }
     Ok(random)
 }
+
+/// Uniformly random in `0..bound`, rejecting candidates from the incomplete
+/// top of the range rather than biasing the modulo towards low values.
+fn random_below(rng_u32: &mut impl FnMut() -> u32, bound: u32) -> u32 {
+    let limit = u32::MAX - u32::MAX % bound;
+    loop {
+        let candidate = rng_u32();
+        if candidate < limit {
+            return candidate % bound;
+        }
+    }
+}
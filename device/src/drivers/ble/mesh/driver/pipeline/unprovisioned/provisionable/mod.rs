@@ -1,6 +1,9 @@
-mod auth_value;
-mod transcript;
+pub(crate) mod auth_value;
+pub(crate) mod transcript;
 
+pub use auth_value::OobRequest;
+
+use crate::drivers::ble::mesh::driver::node::oob::OobValue;
 use crate::drivers::ble::mesh::driver::pipeline::mesh::MeshContext;
 use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::auth_value::{
     determine_auth_value, AuthValue,
@@ -8,7 +11,8 @@ use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::a
 use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::transcript::Transcript;
 use crate::drivers::ble::mesh::driver::DeviceError;
 use crate::drivers::ble::mesh::provisioning::{
-    Capabilities, Confirmation, ProvisioningData, ProvisioningPDU, PublicKey, Random,
+    Capabilities, Confirmation, ProvisioningData, ProvisioningPDU, PublicKey, PublicKeySelected,
+    Random,
 };
 use aes::Aes128;
 use cmac::crypto_mac::Output;
@@ -22,13 +26,24 @@ use p256::EncodedPoint;
 pub trait UnprovisionedContext: MeshContext {
     fn rng_fill(&self, dest: &mut [u8]);
 
+    /// Draw attention to the device for `seconds` while being provisioned.
+    fn attention(&self, seconds: u8);
+
     type SetPeerPublicKeyFuture<'m>: Future<Output = Result<(), DeviceError>>
     where
         Self: 'm;
 
     fn set_peer_public_key<'m>(&'m self, pk: p256::PublicKey) -> Self::SetPeerPublicKeyFuture<'m>;
 
-    fn public_key(&self) -> Result<p256::PublicKey, DeviceError>;
+    type PublicKeyFuture<'m>: Future<Output = Result<p256::PublicKey, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn public_key<'m>(&'m self) -> Self::PublicKeyFuture<'m>;
+
+    /// Whether the public key of the device is known to the provisioner
+    /// out-of-band, and not to be sent while provisioning.
+    fn oob_public_key(&self) -> bool;
 
     type SetProvisioningDataFuture<'m>: Future<Output = Result<(), DeviceError>>
     where
@@ -44,9 +59,23 @@ pub trait UnprovisionedContext: MeshContext {
 
     fn s1(&self, input: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError>;
 
-    fn prsk(&self, salt: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError>;
-    fn prsn(&self, salt: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError>;
-    fn prck(&self, salt: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError>;
+    type KeyFuture<'m>: Future<Output = Result<Output<Cmac<Aes128>>, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn n_k1<'m>(&'m self, salt: &'m [u8], p: &'m [u8]) -> Self::KeyFuture<'m>;
+
+    fn prsk<'m>(&'m self, salt: &'m [u8]) -> Self::KeyFuture<'m> {
+        self.n_k1(salt, b"prsk")
+    }
+
+    fn prsn<'m>(&'m self, salt: &'m [u8]) -> Self::KeyFuture<'m> {
+        self.n_k1(salt, b"prsn")
+    }
+
+    fn prck<'m>(&'m self, salt: &'m [u8]) -> Self::KeyFuture<'m> {
+        self.n_k1(salt, b"prck")
+    }
 
     fn aes_ccm_decrypt(
         &self,
@@ -59,12 +88,18 @@ pub trait UnprovisionedContext: MeshContext {
 
     fn rng_u8(&self) -> u8;
     fn rng_u32(&self) -> u32;
+
+    /// The static out-of-band value of the device, if it has one.
+    fn static_oob(&self) -> Option<[u8; 16]>;
 }
 
 pub struct Provisionable {
     capabilities: Capabilities,
     transcript: Transcript,
     auth_value: Option<AuthValue>,
+    oob_request: Option<OobRequest>,
+    pending_oob: Option<OobRequest>,
+    oob_public_key: bool,
     random_device: Option<[u8; 16]>,
     random_provisioner: Option<[u8; 16]>,
 }
@@ -75,6 +110,9 @@ impl Provisionable {
             capabilities,
             transcript: Transcript::default(),
             auth_value: None,
+            oob_request: None,
+            pending_oob: None,
+            oob_public_key: false,
             random_device: None,
             random_provisioner: None,
         }
@@ -83,6 +121,9 @@ impl Provisionable {
     pub fn reset(&mut self) {
         self.transcript.reset();
         self.auth_value.take();
+        self.oob_request.take();
+        self.pending_oob.take();
+        self.oob_public_key = false;
         self.random_device.take();
         self.random_provisioner.take();
     }
@@ -95,6 +136,9 @@ impl Provisionable {
         match pdu {
             ProvisioningPDU::Invite(invite) => {
                 info!("invite");
+                ctx.attention(invite.attention_duration);
+                self.capabilities.public_key_type.available = ctx.oob_public_key();
+                self.capabilities.static_oob_type.available = ctx.static_oob().is_some();
                 self.transcript.add_invite(&invite)?;
                 self.transcript.add_capabilities(&self.capabilities)?;
                 Ok(Some(ProvisioningPDU::Capabilities(
@@ -104,10 +148,15 @@ impl Provisionable {
             ProvisioningPDU::Capabilities(_) => Ok(None),
             ProvisioningPDU::Start(start) => {
                 info!("start");
+                self.oob_public_key = matches!(start.public_key, PublicKeySelected::OOBPublicKey);
+                if self.oob_public_key && !self.capabilities.public_key_type.available {
+                    warn!("provisioner selected an out-of-band public key, which is not available");
+                    return Err(DeviceError::InvalidState);
+                }
                 self.transcript.add_start(&start)?;
-                let auth_value = determine_auth_value(ctx, &start)?;
-                // TODO actually let the device/app/thingy know what it is so that it can blink/flash/accept input
-                self.auth_value.replace(auth_value);
+                let (auth_value, oob_request) = determine_auth_value(ctx, &start)?;
+                self.auth_value = auth_value;
+                self.oob_request = oob_request;
                 Ok(None)
             }
             ProvisioningPDU::PublicKey(public_key) => {
@@ -126,7 +175,7 @@ impl Provisionable {
                     .unwrap();
 
                 ctx.set_peer_public_key(peer_pk).await?;
-                let pk = ctx.public_key()?;
+                let pk = ctx.public_key().await?;
                 let xy = pk.to_encoded_point(false);
                 let x = xy.x().unwrap();
                 let y = xy.y().unwrap();
@@ -137,16 +186,29 @@ impl Provisionable {
                         .map_err(|_| DeviceError::InsufficientBuffer)?,
                 };
                 self.transcript.add_pubkey_device(&pk)?;
-                Ok(Some(ProvisioningPDU::PublicKey(pk)))
+                // interaction with the user starts once public keys are exchanged.
+                self.pending_oob = self.oob_request.take();
+                if self.oob_public_key {
+                    // the provisioner already has it.
+                    Ok(None)
+                } else {
+                    Ok(Some(ProvisioningPDU::PublicKey(pk)))
+                }
             }
             ProvisioningPDU::InputComplete => Ok(None),
             ProvisioningPDU::Confirmation(_confirmation) => {
                 info!("confirmation");
+                // the provisioner has what the user entered, or presented.
+                if let Some(OobRequest::Input(..)) = self.pending_oob {
+                    warn!("confirmation received before the user completed input");
+                    return Err(DeviceError::InvalidState);
+                }
+                self.pending_oob.take();
                 // todo verify the confirmation from the provisioner.
                 let mut random_device = [0; 16];
                 ctx.rng_fill(&mut random_device);
                 self.random_device.replace(random_device);
-                let confirmation_device = self.confirmation_device(ctx)?;
+                let confirmation_device = self.confirmation_device(ctx).await?;
                 Ok(Some(ProvisioningPDU::Confirmation(confirmation_device)))
             }
             ProvisioningPDU::Random(random) => {
@@ -168,8 +230,8 @@ impl Provisionable {
                 provisioning_salt[32..48].copy_from_slice(self.random_device.as_ref().unwrap());
                 let provisioning_salt = &ctx.s1(&provisioning_salt)?.into_bytes()[0..];
 
-                let session_key = &ctx.prsk(&provisioning_salt)?.into_bytes()[0..];
-                let session_nonce = &ctx.prsn(&provisioning_salt)?.into_bytes()[3..];
+                let session_key = &ctx.prsk(&provisioning_salt).await?.into_bytes()[0..];
+                let session_nonce = &ctx.prsn(&provisioning_salt).await?.into_bytes()[3..];
 
                 let result = ctx.aes_ccm_decrypt(
                     &session_key,
@@ -195,13 +257,30 @@ impl Provisionable {
         }
     }
 
-    fn confirmation_device<C: UnprovisionedContext>(
+    /// Interaction with the user awaited to authenticate the provisioner.
+    pub fn oob_request(&self) -> Option<&OobRequest> {
+        self.pending_oob.as_ref()
+    }
+
+    /// Conclude the interaction with the user, with the value they input if
+    /// requested. Returns the PDU to notify the provisioner of the input.
+    pub fn oob_complete(&mut self, input: Option<OobValue>) -> Option<ProvisioningPDU> {
+        match (self.pending_oob.take(), input) {
+            (Some(OobRequest::Input(..)), Some(value)) => {
+                self.auth_value.replace(AuthValue::Oob(value));
+                Some(ProvisioningPDU::InputComplete)
+            }
+            _ => None,
+        }
+    }
+
+    async fn confirmation_device<C: UnprovisionedContext>(
         &self,
         ctx: &C,
     ) -> Result<Confirmation, DeviceError> {
         let salt = self.transcript.confirmation_salt()?;
         //let confirmation_key = device.key_manager.borrow().k1(&*salt.into_bytes(), b"prck")?;
-        let confirmation_key = ctx.prck(&*salt.into_bytes())?;
+        let confirmation_key = ctx.prck(&*salt.into_bytes()).await?;
         let mut bytes: Vec<u8, 32> = Vec::new();
         bytes
             .extend_from_slice(&self.random_device.unwrap())
@@ -211,7 +290,7 @@ impl Provisionable {
                 &self
                     .auth_value
                     .as_ref()
-                    .ok_or(DeviceError::InsufficientBuffer)?
+                    .ok_or(DeviceError::InvalidState)?
                     .get_bytes(),
             )
             .map_err(|_| DeviceError::InsufficientBuffer)?;
@@ -225,3 +304,356 @@ impl Provisionable {
         Ok(Confirmation { confirmation })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
+    use crate::drivers::ble::mesh::crypto;
+    use crate::drivers::ble::mesh::device::Uuid;
+    use crate::drivers::ble::mesh::driver::pipeline::mesh::NetworkRetransmitDetails;
+    use crate::drivers::ble::mesh::interface::PDU;
+    use crate::drivers::ble::mesh::provisioning::{
+        Algorithm, AuthenticationMethod, InputOOBAction, InputOOBActions, OOBAction, OOBSize,
+        OutputOOBAction, OutputOOBActions, Start,
+    };
+    use core::cell::{Cell, RefCell};
+    use core::future::{ready, Ready};
+    use embassy::time::Duration;
+    use futures::executor::block_on;
+
+    /// An unprovisioned device with a fixed shared secret, drawing its
+    /// random values from those queued by the test.
+    struct OobContext {
+        static_oob: Option<[u8; 16]>,
+        queued: RefCell<Vec<u32, 8>>,
+        counter: Cell<u32>,
+    }
+
+    impl OobContext {
+        fn new(static_oob: Option<[u8; 16]>) -> Self {
+            Self {
+                static_oob,
+                queued: RefCell::new(Vec::new()),
+                counter: Cell::new(0),
+            }
+        }
+
+        fn queue(&self, values: &[u32]) {
+            let mut queued = self.queued.borrow_mut();
+            for value in values.iter().rev() {
+                queued.push(*value).unwrap();
+            }
+        }
+    }
+
+    impl MeshContext for OobContext {
+        fn uuid(&self) -> Uuid {
+            Uuid([0; 16])
+        }
+
+        fn network_retransmit(&self) -> NetworkRetransmitDetails {
+            NetworkRetransmitDetails {
+                count: 0,
+                interval: Duration::from_millis(10),
+            }
+        }
+
+        type TransmitFuture<'m> = Ready<Result<(), DeviceError>>
+        where
+            Self: 'm;
+
+        fn transmit<'m>(&'m self, _: &'m PDU) -> Self::TransmitFuture<'m> {
+            ready(Ok(()))
+        }
+
+        fn primary_unicast_address(&self) -> Result<UnicastAddress, DeviceError> {
+            Err(DeviceError::NotProvisioned)
+        }
+
+        fn is_local_unicast(&self, _: &Address) -> bool {
+            false
+        }
+    }
+
+    impl UnprovisionedContext for OobContext {
+        fn rng_fill(&self, dest: &mut [u8]) {
+            for b in dest.iter_mut() {
+                *b = self.rng_u8();
+            }
+        }
+
+        fn attention(&self, _: u8) {}
+
+        type SetPeerPublicKeyFuture<'m> = Ready<Result<(), DeviceError>>
+        where
+            Self: 'm;
+
+        fn set_peer_public_key<'m>(
+            &'m self,
+            _: p256::PublicKey,
+        ) -> Self::SetPeerPublicKeyFuture<'m> {
+            ready(Ok(()))
+        }
+
+        type PublicKeyFuture<'m> = Ready<Result<p256::PublicKey, DeviceError>>
+        where
+            Self: 'm;
+
+        fn public_key<'m>(&'m self) -> Self::PublicKeyFuture<'m> {
+            ready(Err(DeviceError::KeyInitialization))
+        }
+
+        fn oob_public_key(&self) -> bool {
+            false
+        }
+
+        type SetProvisioningDataFuture<'m> = Ready<Result<(), DeviceError>>
+        where
+            Self: 'm;
+
+        fn set_provisioning_data<'m>(
+            &'m self,
+            _: &'m [u8],
+            _: &'m ProvisioningData,
+        ) -> Self::SetProvisioningDataFuture<'m> {
+            ready(Ok(()))
+        }
+
+        fn aes_cmac(&self, key: &[u8], input: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError> {
+            Ok(crypto::aes_cmac(key, input)?)
+        }
+
+        fn s1(&self, input: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError> {
+            Ok(crypto::s1(input)?)
+        }
+
+        type KeyFuture<'m> = Ready<Result<Output<Cmac<Aes128>>, DeviceError>>
+        where
+            Self: 'm;
+
+        fn n_k1<'m>(&'m self, salt: &'m [u8], p: &'m [u8]) -> Self::KeyFuture<'m> {
+            ready(crypto::k1(&[0x55; 32], salt, p).map_err(Into::into))
+        }
+
+        fn aes_ccm_decrypt(
+            &self,
+            _: &[u8],
+            _: &[u8],
+            _: &mut [u8],
+            _: &[u8],
+            _: Option<&[u8]>,
+        ) -> Result<(), DeviceError> {
+            Err(DeviceError::CryptoError("aes_ccm_decrypt"))
+        }
+
+        fn rng_u8(&self) -> u8 {
+            self.rng_u32() as u8
+        }
+
+        fn rng_u32(&self) -> u32 {
+            self.queued.borrow_mut().pop().unwrap_or_else(|| {
+                let value = self.counter.get().wrapping_add(0x9E3779B9);
+                self.counter.set(value);
+                value
+            })
+        }
+
+        fn static_oob(&self) -> Option<[u8; 16]> {
+            self.static_oob
+        }
+    }
+
+    fn start(method: AuthenticationMethod, action: OOBAction, size: OOBSize) -> Start {
+        Start {
+            algorithm: Algorithm::P256,
+            public_key: PublicKeySelected::NoPublicKey,
+            authentication_method: method,
+            authentication_action: action,
+            authentication_size: size,
+        }
+    }
+
+    fn output(action: OutputOOBAction, size: u8) -> Start {
+        start(
+            AuthenticationMethod::OutputOOBAuthentication,
+            OOBAction::Output(action),
+            OOBSize::MaximumSize(size),
+        )
+    }
+
+    fn input(action: InputOOBAction, size: u8) -> Start {
+        start(
+            AuthenticationMethod::InputOOBAuthentication,
+            OOBAction::Input(action),
+            OOBSize::MaximumSize(size),
+        )
+    }
+
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            number_of_elements: 1,
+            algorithms: Default::default(),
+            public_key_type: Default::default(),
+            static_oob_type: Default::default(),
+            output_oob_size: OOBSize::NotSupported,
+            output_oob_action: OutputOOBActions::default(),
+            input_oob_size: OOBSize::NotSupported,
+            input_oob_action: InputOOBActions::default(),
+        }
+    }
+
+    #[test]
+    fn static_auth_value() {
+        let start = start(
+            AuthenticationMethod::StaticOOBAuthentication,
+            OOBAction::None,
+            OOBSize::NotSupported,
+        );
+        let (auth_value, request) =
+            determine_auth_value(&OobContext::new(Some([0xA5; 16])), &start).unwrap();
+        assert_eq!(auth_value.unwrap().get_bytes(), [0xA5; 16]);
+        assert!(request.is_none());
+
+        assert!(matches!(
+            determine_auth_value(&OobContext::new(None), &start),
+            Err(DeviceError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn output_numeric_auth_value() {
+        let ctx = OobContext::new(None);
+        // the incomplete top of the range is rejected rather than wrapped.
+        ctx.queue(&[u32::MAX, 1_234_567]);
+        let (auth_value, request) =
+            determine_auth_value(&ctx, &output(OutputOOBAction::OutputNumeric, 4)).unwrap();
+        let mut expected = [0; 16];
+        expected[12..16].copy_from_slice(&4567u32.to_be_bytes());
+        assert_eq!(auth_value.unwrap().get_bytes(), expected);
+        assert!(matches!(
+            request,
+            Some(OobRequest::Output(
+                OutputOOBAction::OutputNumeric,
+                OobValue::Numeric(4567)
+            ))
+        ));
+
+        for _ in 0..32 {
+            match determine_auth_value(&ctx, &output(OutputOOBAction::Blink, 1)).unwrap() {
+                (Some(_), Some(OobRequest::Output(_, OobValue::Numeric(blinks)))) => {
+                    assert!((1..10).contains(&blinks))
+                }
+                _ => panic!("expected blinks"),
+            }
+        }
+    }
+
+    #[test]
+    fn output_alphanumeric_auth_value() {
+        let ctx = OobContext::new(None);
+        ctx.queue(&[u32::MAX, 0, 35, 36]);
+        let (auth_value, request) =
+            determine_auth_value(&ctx, &output(OutputOOBAction::OutputAlphanumeric, 3)).unwrap();
+        let mut expected = [0; 16];
+        expected[0..3].copy_from_slice(b"A9A");
+        assert_eq!(auth_value.unwrap().get_bytes(), expected);
+        match request {
+            Some(OobRequest::Output(
+                OutputOOBAction::OutputAlphanumeric,
+                OobValue::Alphanumeric(chars),
+            )) => assert_eq!(&chars[..], b"A9A"),
+            _ => panic!("expected alphanumeric output"),
+        }
+    }
+
+    #[test]
+    fn input_auth_value_completes() {
+        let ctx = OobContext::new(None);
+        let (auth_value, request) =
+            determine_auth_value(&ctx, &input(InputOOBAction::InputNumeric, 6)).unwrap();
+        assert!(auth_value.is_none());
+        assert!(matches!(
+            request,
+            Some(OobRequest::Input(InputOOBAction::InputNumeric, 6))
+        ));
+
+        let mut provisionable = Provisionable::new(capabilities());
+        provisionable.auth_value = auth_value;
+        provisionable.pending_oob = request;
+        assert!(matches!(
+            provisionable.oob_complete(Some(OobValue::Numeric(123456))),
+            Some(ProvisioningPDU::InputComplete)
+        ));
+        assert!(provisionable.oob_request().is_none());
+        let mut expected = [0; 16];
+        expected[12..16].copy_from_slice(&123456u32.to_be_bytes());
+        assert_eq!(
+            provisionable.auth_value.as_ref().unwrap().get_bytes(),
+            expected
+        );
+
+        let confirmation = Confirmation {
+            confirmation: [0; 16],
+        };
+        assert!(matches!(
+            block_on(
+                provisionable.process_inbound(&ctx, ProvisioningPDU::Confirmation(confirmation))
+            ),
+            Ok(Some(ProvisioningPDU::Confirmation(_)))
+        ));
+    }
+
+    #[test]
+    fn output_completes_without_input_complete() {
+        let mut provisionable = Provisionable::new(capabilities());
+        provisionable.pending_oob = Some(OobRequest::Output(
+            OutputOOBAction::Blink,
+            OobValue::Numeric(3),
+        ));
+        assert!(provisionable.oob_complete(None).is_none());
+        assert!(provisionable.oob_request().is_none());
+    }
+
+    #[test]
+    fn confirmation_before_input() {
+        let ctx = OobContext::new(None);
+        let mut provisionable = Provisionable::new(capabilities());
+        provisionable.pending_oob = Some(OobRequest::Input(InputOOBAction::Push, 2));
+        let confirmation = Confirmation {
+            confirmation: [0; 16],
+        };
+        assert!(matches!(
+            block_on(
+                provisionable.process_inbound(&ctx, ProvisioningPDU::Confirmation(confirmation))
+            ),
+            Err(DeviceError::InvalidState)
+        ));
+        assert!(provisionable.random_device.is_none());
+    }
+
+    #[test]
+    fn static_oob_capability_follows_handler() {
+        let invite = ProvisioningPDU::Invite(crate::drivers::ble::mesh::provisioning::Invite {
+            attention_duration: 0,
+        });
+
+        let mut provisionable = Provisionable::new(capabilities());
+        match block_on(
+            provisionable.process_inbound(&OobContext::new(Some([1; 16])), invite.clone()),
+        ) {
+            Ok(Some(ProvisioningPDU::Capabilities(capabilities))) => {
+                assert!(capabilities.static_oob_type.available)
+            }
+            _ => panic!("expected capabilities"),
+        }
+
+        let mut provisionable = Provisionable::new(capabilities());
+        match block_on(provisionable.process_inbound(&OobContext::new(None), invite)) {
+            Ok(Some(ProvisioningPDU::Capabilities(capabilities))) => {
+                assert!(!capabilities.static_oob_type.available)
+            }
+            _ => panic!("expected capabilities"),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::drivers::ble::mesh::address::UnicastAddress;
+use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::storage::{load_versioned, store_versioned, Storage};
+use heapless::Vec;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_NODES: usize = 8;
+
+/// Version of the layout of the database as stored.
+const DATABASE_VERSION: u16 = 1;
+
+/// The first unicast address handed out to provisioned nodes.
+/// The provisioner itself occupies `0x0001`.
+const FIRST_NODE_ADDRESS: u16 = 0x0002;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProvisionedNode {
+    pub uuid: Uuid,
+    pub unicast_address: UnicastAddress,
+    pub number_of_elements: u8,
+    pub device_key: [u8; 16],
+}
+
+/// Network-wide state owned by a provisioner: the network key it hands out
+/// and the set of nodes it has provisioned so far.
+#[derive(Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProvisionerDatabase {
+    pub network_key: [u8; 16],
+    pub key_index: u16,
+    pub iv_index: u32,
+    next_address: u16,
+    nodes: Vec<ProvisionedNode, MAX_NODES>,
+}
+
+impl ProvisionerDatabase {
+    pub fn new<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut network_key = [0; 16];
+        rng.fill_bytes(&mut network_key);
+        Self {
+            network_key,
+            key_index: 0,
+            iv_index: 0,
+            next_address: FIRST_NODE_ADDRESS,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Retrieve the stored database, or `None` if none has been stored yet.
+    /// A database failing to decode is an error, never replaced by a new one.
+    pub(crate) async fn load<S: Storage>(storage: &mut S) -> Result<Option<Self>, DeviceError> {
+        load_versioned(storage, DATABASE_VERSION).await
+    }
+
+    pub(crate) async fn store<S: Storage>(&self, storage: &mut S) -> Result<(), DeviceError> {
+        store_versioned(storage, DATABASE_VERSION, self).await
+    }
+
+    pub fn nodes(&self) -> &[ProvisionedNode] {
+        &self.nodes
+    }
+
+    pub fn node(&self, uuid: &Uuid) -> Option<&ProvisionedNode> {
+        self.nodes.iter().find(|node| node.uuid == *uuid)
+    }
+
+    pub fn node_by_address(&self, addr: UnicastAddress) -> Option<&ProvisionedNode> {
+        let addr = u16::from(addr);
+        self.nodes.iter().find(|node| {
+            let primary = u16::from(node.unicast_address);
+            addr >= primary && addr < primary + node.number_of_elements as u16
+        })
+    }
+
+    /// Determine the primary address for a node with the given number of elements
+    /// without committing to it.
+    pub(crate) fn allocate(&self, number_of_elements: u8) -> Result<UnicastAddress, DeviceError> {
+        if self.nodes.is_full() || number_of_elements == 0 {
+            return Err(DeviceError::InsufficientBuffer);
+        }
+        let last = self.next_address as u32 + number_of_elements as u32 - 1;
+        if last > 0x7FFF {
+            return Err(DeviceError::InsufficientBuffer);
+        }
+        Ok(UnicastAddress(self.next_address))
+    }
+
+    pub(crate) fn add_node(&mut self, node: ProvisionedNode) -> Result<(), DeviceError> {
+        if let Some(index) = self.nodes.iter().position(|e| e.uuid == node.uuid) {
+            self.nodes.remove(index);
+        }
+        let next = u16::from(node.unicast_address) + node.number_of_elements as u16;
+        if next > self.next_address {
+            self.next_address = next;
+        }
+        self.nodes
+            .push(node)
+            .map_err(|_| DeviceError::InsufficientBuffer)
+    }
+
+    pub(crate) fn remove_node(&mut self, uuid: &Uuid) -> Option<ProvisionedNode> {
+        let index = self.nodes.iter().position(|node| node.uuid == *uuid)?;
+        Some(self.nodes.remove(index))
+    }
+}
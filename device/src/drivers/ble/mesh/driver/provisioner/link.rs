@@ -0,0 +1,228 @@
+use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::generic_provisioning::{
+    GenericProvisioningPDU, ProvisioningBearerControl, Reason,
+};
+use crate::drivers::ble::mesh::interface::advertising::segmentation::outbound::OutboundSegments;
+use crate::drivers::ble::mesh::interface::advertising::segmentation::Segmentation;
+use crate::drivers::ble::mesh::interface::{AdvertisingBearer, PB_ADV_MTU};
+use crate::drivers::ble::mesh::pdu::bearer::advertising::AdvertisingPDU;
+use crate::drivers::ble::mesh::provisioning::ProvisioningPDU;
+use core::cell::{Cell, RefCell};
+use embassy::time::{Duration, Instant, Timer};
+use embassy::util::{select, Either};
+use heapless::Vec;
+
+const LINK_OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+const RETRANSMIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Transaction numbers chosen by the provisioner, per PB-ADV.
+const PROVISIONER_TRANSACTIONS: u8 = 0x7F;
+
+/// Provisioner side of a PB-ADV link to a single device.
+pub struct ProvisioningLink<'b, B: AdvertisingBearer> {
+    bearer: &'b B,
+    link_id: u32,
+    segmentation: Segmentation,
+    outbound: RefCell<Option<(u8, OutboundSegments)>>,
+    next_transaction_number: Cell<u8>,
+    acked_inbound_transaction_number: Cell<Option<u8>>,
+    pending: RefCell<Option<ProvisioningPDU>>,
+}
+
+impl<'b, B: AdvertisingBearer> ProvisioningLink<'b, B> {
+    /// Open a link to the device identified by `uuid`, retrying the `LinkOpen`
+    /// until the device acknowledges it or the link establishment times out.
+    pub async fn open(bearer: &'b B, uuid: Uuid, link_id: u32) -> Result<Self, DeviceError> {
+        let link = Self {
+            bearer,
+            link_id,
+            segmentation: Default::default(),
+            outbound: RefCell::new(None),
+            next_transaction_number: Cell::new(0),
+            acked_inbound_transaction_number: Cell::new(None),
+            pending: RefCell::new(None),
+        };
+
+        let deadline = Instant::now() + LINK_OPEN_TIMEOUT;
+        while Instant::now() < deadline {
+            link.transmit_advertising_pdu(&AdvertisingPDU {
+                link_id,
+                transaction_number: 0,
+                pdu: GenericProvisioningPDU::ProvisioningBearerControl(
+                    ProvisioningBearerControl::LinkOpen(uuid),
+                ),
+            })
+            .await?;
+
+            let retry = Instant::now() + RETRANSMIT_INTERVAL;
+            while let Some(pdu) = link.receive_advertising_pdu(retry).await? {
+                if let GenericProvisioningPDU::ProvisioningBearerControl(
+                    ProvisioningBearerControl::LinkAck,
+                ) = pdu.pdu
+                {
+                    debug!("link {} open", link_id);
+                    return Ok(link);
+                }
+            }
+        }
+        Err(DeviceError::Timeout)
+    }
+
+    /// Send a provisioning PDU and wait for the device to acknowledge the transaction.
+    pub async fn send(&self, pdu: &ProvisioningPDU) -> Result<(), DeviceError> {
+        let transaction_number = self.next_transaction_number.get();
+        self.next_transaction_number
+            .replace((transaction_number + 1) & PROVISIONER_TRANSACTIONS);
+        let segments = self.segmentation.process_outbound(pdu)?;
+        self.outbound.replace(Some((transaction_number, segments)));
+
+        let deadline = Instant::now() + TRANSACTION_TIMEOUT;
+        while Instant::now() < deadline {
+            self.retransmit().await?;
+            let retry = Instant::now() + RETRANSMIT_INTERVAL;
+            while let Some(pdu) = self.receive_advertising_pdu(retry).await? {
+                match &pdu.pdu {
+                    GenericProvisioningPDU::TransactionAck
+                        if pdu.transaction_number == transaction_number =>
+                    {
+                        return Ok(());
+                    }
+                    GenericProvisioningPDU::TransactionStart(_)
+                    | GenericProvisioningPDU::TransactionContinuation(_) => {
+                        // The device only answers once it has our PDU, so a
+                        // response implies the acknowledgement we missed.
+                        if let Some(response) = self.process_transaction(&pdu).await? {
+                            self.pending.replace(Some(response));
+                            return Ok(());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Err(DeviceError::Timeout)
+    }
+
+    /// Receive the next provisioning PDU from the device, nudging it by
+    /// retransmitting our last PDU while it stays quiet.
+    pub async fn receive(&self) -> Result<ProvisioningPDU, DeviceError> {
+        if let Some(pdu) = self.pending.borrow_mut().take() {
+            return Ok(pdu);
+        }
+        let deadline = Instant::now() + TRANSACTION_TIMEOUT;
+        while Instant::now() < deadline {
+            let retry = Instant::now() + RETRANSMIT_INTERVAL;
+            while let Some(pdu) = self.receive_advertising_pdu(retry).await? {
+                if let Some(response) = self.process_transaction(&pdu).await? {
+                    return Ok(response);
+                }
+            }
+            self.retransmit().await?;
+        }
+        Err(DeviceError::Timeout)
+    }
+
+    /// Close the link. `LinkClose` is unacknowledged, so it is sent a few times.
+    pub async fn close(&self, reason: Reason) -> Result<(), DeviceError> {
+        for _ in 0..3 {
+            self.transmit_advertising_pdu(&AdvertisingPDU {
+                link_id: self.link_id,
+                transaction_number: 0,
+                pdu: GenericProvisioningPDU::ProvisioningBearerControl(
+                    ProvisioningBearerControl::LinkClose(reason),
+                ),
+            })
+            .await?;
+            Timer::after(Duration::from_millis(100)).await;
+        }
+        Ok(())
+    }
+
+    async fn process_transaction(
+        &self,
+        pdu: &AdvertisingPDU,
+    ) -> Result<Option<ProvisioningPDU>, DeviceError> {
+        match &pdu.pdu {
+            GenericProvisioningPDU::TransactionStart(_)
+            | GenericProvisioningPDU::TransactionContinuation(_) => {}
+            GenericProvisioningPDU::ProvisioningBearerControl(
+                ProvisioningBearerControl::LinkClose(_),
+            ) => return Err(DeviceError::InvalidLink),
+            _ => return Ok(None),
+        }
+
+        if let Some(acked) = self.acked_inbound_transaction_number.get() {
+            if acked == pdu.transaction_number {
+                // our acknowledgement was lost, send it again.
+                self.ack(pdu.transaction_number).await?;
+                return Ok(None);
+            }
+        }
+
+        if let Some(result) = self.segmentation.process_inbound(&pdu.pdu)? {
+            self.acked_inbound_transaction_number
+                .replace(Some(pdu.transaction_number));
+            self.ack(pdu.transaction_number).await?;
+            Ok(Some(result))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn ack(&self, transaction_number: u8) -> Result<(), DeviceError> {
+        self.transmit_advertising_pdu(&AdvertisingPDU {
+            link_id: self.link_id,
+            transaction_number,
+            pdu: GenericProvisioningPDU::TransactionAck,
+        })
+        .await
+    }
+
+    async fn retransmit(&self) -> Result<(), DeviceError> {
+        if let Some((transaction_number, segments)) = &*self.outbound.borrow() {
+            for pdu in segments.iter() {
+                self.transmit_advertising_pdu(&AdvertisingPDU {
+                    link_id: self.link_id,
+                    transaction_number: *transaction_number,
+                    pdu,
+                })
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Receive the next PB-ADV PDU on this link, or `None` once `until` passes.
+    async fn receive_advertising_pdu(
+        &self,
+        until: Instant,
+    ) -> Result<Option<AdvertisingPDU>, DeviceError> {
+        loop {
+            let now = Instant::now();
+            if now >= until {
+                return Ok(None);
+            }
+            let receive_fut = self.bearer.receive();
+            let timer_fut = Timer::after(until - now);
+            match select(receive_fut, timer_fut).await {
+                Either::First(Ok(data)) => {
+                    if let Ok(pdu) = AdvertisingPDU::parse(&data) {
+                        if pdu.link_id == self.link_id {
+                            return Ok(Some(pdu));
+                        }
+                    }
+                }
+                Either::First(Err(e)) => return Err(e.into()),
+                Either::Second(_) => return Ok(None),
+            }
+        }
+    }
+
+    async fn transmit_advertising_pdu(&self, pdu: &AdvertisingPDU) -> Result<(), DeviceError> {
+        let mut bytes: Vec<u8, PB_ADV_MTU> = Vec::new();
+        pdu.emit(&mut bytes)?;
+        Ok(self.bearer.transmit(&bytes).await?)
+    }
+}
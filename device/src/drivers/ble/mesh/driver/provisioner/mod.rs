@@ -0,0 +1,359 @@
+//! Provisioner role.
+//!
+//! Discovers unprovisioned devices by their beacons and provisions them over
+//! PB-ADV onto the network held in the `ProvisionerDatabase`, authenticating
+//! them by the method selected with an [`Authentication`].
+
+mod database;
+mod link;
+mod oob;
+mod session;
+
+pub use database::{ProvisionedNode, ProvisionerDatabase, MAX_NODES};
+pub use oob::{Authentication, ProvisionerOobHandler};
+
+use crate::drivers::ble::mesh::beacon::{Beacon, OobInformation};
+use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::driver::node::oob::NoOob;
+use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::auth_value::{
+    random_input, AuthValue,
+};
+use crate::drivers::ble::mesh::driver::provisioner::link::ProvisioningLink;
+use crate::drivers::ble::mesh::driver::provisioner::session::ProvisioningSession;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::generic_provisioning::Reason;
+use crate::drivers::ble::mesh::interface::AdvertisingBearer;
+use crate::drivers::ble::mesh::model::foundation::configuration::NetKeyIndex;
+use crate::drivers::ble::mesh::provisioning::{
+    Algorithm, AuthenticationMethod, Capabilities, ErrorCode, IVUpdateFlag, Invite, KeyRefreshFlag,
+    OOBAction, OOBSize, ProvisioningData, ProvisioningPDU, PublicKeySelected, Start,
+};
+use crate::drivers::ble::mesh::storage::Storage;
+use crate::drivers::ble::mesh::MESH_BEACON;
+use core::cell::{Ref, RefCell};
+use embassy::time::{Duration, Timer};
+use embassy::util::{select, Either};
+use rand_core::{CryptoRng, RngCore};
+
+/// A device announcing itself with an unprovisioned device beacon.
+pub struct UnprovisionedDevice {
+    pub uuid: Uuid,
+    pub oob: OobInformation,
+    pub uri_hash: Option<[u8; 4]>,
+}
+
+pub struct Provisioner<B, S, R>
+where
+    B: AdvertisingBearer,
+    S: Storage,
+    R: RngCore + CryptoRng,
+{
+    bearer: B,
+    storage: RefCell<S>,
+    rng: RefCell<R>,
+    database: RefCell<ProvisionerDatabase>,
+    attention_duration: u8,
+}
+
+impl<B, S, R> Provisioner<B, S, R>
+where
+    B: AdvertisingBearer,
+    S: Storage,
+    R: RngCore + CryptoRng,
+{
+    pub fn new(bearer: B, storage: S, mut rng: R) -> Self {
+        let database = ProvisionerDatabase::new(&mut rng);
+        Self {
+            bearer,
+            storage: RefCell::new(storage),
+            rng: RefCell::new(rng),
+            database: RefCell::new(database),
+            attention_duration: 0,
+        }
+    }
+
+    /// Seconds the device should draw attention to itself during provisioning.
+    pub fn set_attention_duration(&mut self, seconds: u8) {
+        self.attention_duration = seconds;
+    }
+
+    /// Load a previously stored database, or persist a freshly generated
+    /// network if none was stored. A stored database that fails to decode is
+    /// reported, as replacing it would lose the network and its device keys.
+    pub async fn initialize(&self) -> Result<(), DeviceError> {
+        let stored = ProvisionerDatabase::load(&mut *self.storage.borrow_mut()).await?;
+        match stored {
+            Some(database) => {
+                info!("loaded provisioner database");
+                self.database.replace(database);
+                Ok(())
+            }
+            None => {
+                info!("creating provisioner database");
+                self.store().await
+            }
+        }
+    }
+
+    pub fn database(&self) -> Ref<'_, ProvisionerDatabase> {
+        self.database.borrow()
+    }
+
+    /// Forget a node, making its addresses unavailable for reuse until the
+    /// database is reset.
+    pub async fn remove_node(&self, uuid: &Uuid) -> Result<Option<ProvisionedNode>, DeviceError> {
+        let removed = self.database.borrow_mut().remove_node(uuid);
+        if removed.is_some() {
+            self.store().await?;
+        }
+        Ok(removed)
+    }
+
+    /// Listen for the first unprovisioned device beacon from a device not
+    /// already in the database.
+    pub async fn scan(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<UnprovisionedDevice>, DeviceError> {
+        let scan_fut = async {
+            loop {
+                let data = self.bearer.receive().await?;
+                if data.len() >= 2 && data[1] == MESH_BEACON {
+                    if let Ok(Beacon::Unprovisioned {
+                        uuid,
+                        oob,
+                        uri_hash,
+                    }) = Beacon::parse(&data[2..])
+                    {
+                        if self.database.borrow().node(&uuid).is_none() {
+                            return Ok(UnprovisionedDevice {
+                                uuid,
+                                oob,
+                                uri_hash,
+                            });
+                        }
+                    }
+                }
+            }
+        };
+        match select(scan_fut, Timer::after(timeout)).await {
+            Either::First(Ok(device)) => Ok(Some(device)),
+            Either::First(Err(e)) => Err(e),
+            Either::Second(_) => Ok(None),
+        }
+    }
+
+    /// Provision the device with the given UUID without out-of-band
+    /// authentication, recording it in the database on success.
+    pub async fn provision(&self, uuid: Uuid) -> Result<ProvisionedNode, DeviceError> {
+        self.provision_authenticated(uuid, Authentication::NoOob, &mut NoOob)
+            .await
+    }
+
+    /// Provision the device with the given UUID, authenticating it as
+    /// selected with the help of the user through `oob`.
+    pub async fn provision_authenticated<O: ProvisionerOobHandler>(
+        &self,
+        uuid: Uuid,
+        authentication: Authentication,
+        oob: &mut O,
+    ) -> Result<ProvisionedNode, DeviceError> {
+        let link_id = self.rng.borrow_mut().next_u32();
+        let link = ProvisioningLink::open(&self.bearer, uuid, link_id).await?;
+
+        let result = self.run_session(&link, uuid, authentication, oob).await;
+        let reason = match &result {
+            Ok(_) => Reason::Success,
+            Err(DeviceError::Timeout) => Reason::Timeout,
+            Err(_) => Reason::Fail,
+        };
+        link.close(reason).await.ok();
+
+        let node = result?;
+        info!("provisioned node at {:x}", u16::from(node.unicast_address));
+        self.database.borrow_mut().add_node(node.clone())?;
+        self.store().await?;
+        Ok(node)
+    }
+
+    async fn run_session<O: ProvisionerOobHandler>(
+        &self,
+        link: &ProvisioningLink<'_, B>,
+        uuid: Uuid,
+        authentication: Authentication,
+        oob: &mut O,
+    ) -> Result<ProvisionedNode, DeviceError> {
+        let mut session = ProvisioningSession::new(&mut *self.rng.borrow_mut());
+
+        let invite = Invite {
+            attention_duration: self.attention_duration,
+        };
+        session.add_invite(&invite)?;
+        link.send(&ProvisioningPDU::Invite(invite)).await?;
+
+        let capabilities = match link.receive().await? {
+            ProvisioningPDU::Capabilities(capabilities) => capabilities,
+            pdu => return Err(unexpected(pdu)),
+        };
+        session.add_capabilities(&capabilities)?;
+        let number_of_elements = capabilities.number_of_elements;
+        let unicast_address = self
+            .database
+            .borrow()
+            .allocate(number_of_elements)
+            .map_err(|_| DeviceError::ProvisioningFailed(ErrorCode::CannotAssignAddresses))?;
+
+        let device_public_key = oob.public_key(&uuid);
+        let start = start(&authentication, &capabilities, device_public_key.is_some())?;
+        session.add_start(&start)?;
+        link.send(&ProvisioningPDU::Start(start)).await?;
+
+        let public_key = session.public_key()?;
+        link.send(&ProvisioningPDU::PublicKey(public_key)).await?;
+        match device_public_key {
+            // the device does not send the key it knows we have.
+            Some(public_key) => session.set_peer_public_key(&public_key)?,
+            None => match link.receive().await? {
+                ProvisioningPDU::PublicKey(public_key) => {
+                    session.set_peer_public_key(&public_key)?
+                }
+                pdu => return Err(unexpected(pdu)),
+            },
+        }
+
+        match &authentication {
+            Authentication::NoOob => {}
+            Authentication::Static(value) => session.set_auth_value(*value),
+            Authentication::Output(action, size) => {
+                let value = oob.output(*action, *size).await?;
+                session.set_auth_value(AuthValue::Oob(value).get_bytes());
+            }
+            Authentication::Input(action, size) => {
+                let value = random_input(&mut || self.rng.borrow_mut().next_u32(), *action, *size)?;
+                oob.input(*action, &value).await?;
+                session.set_auth_value(AuthValue::Oob(value).get_bytes());
+                match link.receive().await? {
+                    ProvisioningPDU::InputComplete => {}
+                    pdu => return Err(unexpected(pdu)),
+                }
+            }
+        }
+
+        link.send(&ProvisioningPDU::Confirmation(session.confirmation()?))
+            .await?;
+        match link.receive().await? {
+            ProvisioningPDU::Confirmation(confirmation) => {
+                session.set_confirmation_device(&confirmation)
+            }
+            pdu => return Err(unexpected(pdu)),
+        }
+
+        link.send(&ProvisioningPDU::Random(session.random()))
+            .await?;
+        match link.receive().await? {
+            ProvisioningPDU::Random(random) => session.verify_random_device(&random)?,
+            pdu => return Err(unexpected(pdu)),
+        }
+
+        let provisioning_data = {
+            let database = self.database.borrow();
+            ProvisioningData {
+                network_key: database.network_key,
+                key_index: NetKeyIndex::new(database.key_index),
+                key_refresh_flag: KeyRefreshFlag::Phase0,
+                iv_update_flag: IVUpdateFlag::NormalOperation,
+                iv_index: database.iv_index,
+                unicast_address,
+            }
+        };
+        let (data, device_key) = session.encrypt(&provisioning_data)?;
+        link.send(&ProvisioningPDU::Data(data)).await?;
+        match link.receive().await? {
+            ProvisioningPDU::Complete => {}
+            pdu => return Err(unexpected(pdu)),
+        }
+
+        Ok(ProvisionedNode {
+            uuid,
+            unicast_address,
+            number_of_elements,
+            device_key,
+        })
+    }
+
+    async fn store(&self) -> Result<(), DeviceError> {
+        let database = self.database.borrow().clone();
+        database.store(&mut *self.storage.borrow_mut()).await
+    }
+}
+
+/// The Start PDU for the selected authentication and public key, provided
+/// the device advertised support for them.
+fn start(
+    authentication: &Authentication,
+    capabilities: &Capabilities,
+    oob_public_key: bool,
+) -> Result<Start, DeviceError> {
+    if oob_public_key && !capabilities.public_key_type.available {
+        warn!("device does not advertise its public key out-of-band");
+        return Err(DeviceError::InvalidState);
+    }
+    let (authentication_method, authentication_action, authentication_size) = match authentication {
+        Authentication::NoOob => (
+            AuthenticationMethod::NoOOBAuthentication,
+            OOBAction::None,
+            OOBSize::NotSupported,
+        ),
+        Authentication::Static(_) if capabilities.static_oob_type.available => (
+            AuthenticationMethod::StaticOOBAuthentication,
+            OOBAction::None,
+            OOBSize::NotSupported,
+        ),
+        Authentication::Output(action, size)
+            if capabilities.output_oob_action.contains(*action)
+                && supports_size(&capabilities.output_oob_size, *size) =>
+        {
+            (
+                AuthenticationMethod::OutputOOBAuthentication,
+                OOBAction::Output(*action),
+                OOBSize::MaximumSize(*size),
+            )
+        }
+        Authentication::Input(action, size)
+            if capabilities.input_oob_action.contains(*action)
+                && supports_size(&capabilities.input_oob_size, *size) =>
+        {
+            (
+                AuthenticationMethod::InputOOBAuthentication,
+                OOBAction::Input(*action),
+                OOBSize::MaximumSize(*size),
+            )
+        }
+        _ => {
+            warn!("device does not support the selected authentication");
+            return Err(DeviceError::InvalidState);
+        }
+    };
+    Ok(Start {
+        algorithm: Algorithm::P256,
+        public_key: if oob_public_key {
+            PublicKeySelected::OOBPublicKey
+        } else {
+            PublicKeySelected::NoPublicKey
+        },
+        authentication_method,
+        authentication_action,
+        authentication_size,
+    })
+}
+
+fn supports_size(supported: &OOBSize, size: u8) -> bool {
+    matches!(supported, OOBSize::MaximumSize(max) if size >= 1 && size <= *max)
+}
+
+fn unexpected(pdu: ProvisioningPDU) -> DeviceError {
+    match pdu {
+        ProvisioningPDU::Failed(failed) => DeviceError::ProvisioningFailed(failed.error_code),
+        _ => DeviceError::ProvisioningFailed(ErrorCode::UnexpectedPDU),
+    }
+}
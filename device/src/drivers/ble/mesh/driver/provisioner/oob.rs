@@ -0,0 +1,68 @@
+use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::driver::node::oob::{NoOob, OobValue};
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::provisioning::{InputOOBAction, OutputOOBAction, PublicKey};
+use core::future::{ready, Future, Ready};
+
+/// How the provisioner authenticates the device it provisions.
+#[derive(Clone)]
+pub enum Authentication {
+    /// An AuthValue of zeros, as used by devices without out-of-band means.
+    NoOob,
+    /// The static out-of-band value of the device.
+    Static([u8; 16]),
+    /// The device presents a value of at most `size` digits or characters,
+    /// which the user enters on the provisioner.
+    Output(OutputOOBAction, u8),
+    /// The provisioner presents a value of at most `size` digits or
+    /// characters, which the user inputs on the device.
+    Input(InputOOBAction, u8),
+}
+
+/// Interaction with the user of the provisioner to authenticate a device.
+pub trait ProvisionerOobHandler {
+    type OutputFuture<'m>: Future<Output = Result<OobValue, DeviceError>>
+    where
+        Self: 'm;
+
+    /// Collect from the user the value the device presented by means of
+    /// `action`, being at most `size` digits or characters.
+    fn output<'m>(&'m mut self, action: OutputOOBAction, size: u8) -> Self::OutputFuture<'m>;
+
+    type InputFuture<'m>: Future<Output = Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    /// Present `value` to the user, for them to input on the device by means
+    /// of `action`. Completes once presented, before the device reports the
+    /// input complete.
+    fn input<'m>(
+        &'m mut self,
+        action: InputOOBAction,
+        value: &'m OobValue,
+    ) -> Self::InputFuture<'m>;
+
+    /// The public key of the device with the given UUID, if known
+    /// out-of-band, such as scanned from a QR code.
+    fn public_key(&mut self, _uuid: &Uuid) -> Option<PublicKey> {
+        None
+    }
+}
+
+impl ProvisionerOobHandler for NoOob {
+    type OutputFuture<'m> = Ready<Result<OobValue, DeviceError>>
+    where
+        Self: 'm;
+
+    fn output<'m>(&'m mut self, _: OutputOOBAction, _: u8) -> Self::OutputFuture<'m> {
+        ready(Err(DeviceError::InvalidState))
+    }
+
+    type InputFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn input<'m>(&'m mut self, _: InputOOBAction, _: &'m OobValue) -> Self::InputFuture<'m> {
+        ready(Err(DeviceError::InvalidState))
+    }
+}
@@ -0,0 +1,342 @@
+use crate::drivers::ble::mesh::crypto;
+use crate::drivers::ble::mesh::driver::pipeline::unprovisioned::provisionable::transcript::Transcript;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::provisioning::{
+    Capabilities, Confirmation, Data, ErrorCode, Invite, ProvisioningData, PublicKey, Random, Start,
+};
+use core::convert::TryFrom;
+use heapless::Vec;
+use p256::ecdh::SharedSecret;
+use p256::elliptic_curve::ecdh::diffie_hellman;
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::{EncodedPoint, SecretKey};
+use rand_core::{CryptoRng, RngCore};
+
+/// Provisioner-side cryptographic state for a single provisioning attempt.
+///
+/// The AuthValue is zero, as for No-OOB authentication, unless set once
+/// known.
+pub struct ProvisioningSession {
+    secret_key: SecretKey,
+    shared_secret: Option<SharedSecret>,
+    transcript: Transcript,
+    auth_value: [u8; 16],
+    random_provisioner: [u8; 16],
+    confirmation_device: Option<[u8; 16]>,
+    random_device: Option<[u8; 16]>,
+}
+
+impl ProvisioningSession {
+    pub fn new<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut random_provisioner = [0; 16];
+        rng.fill_bytes(&mut random_provisioner);
+        Self {
+            secret_key: SecretKey::random(rng),
+            shared_secret: None,
+            transcript: Transcript::default(),
+            auth_value: [0; 16],
+            random_provisioner,
+            confirmation_device: None,
+            random_device: None,
+        }
+    }
+
+    pub fn add_invite(&mut self, invite: &Invite) -> Result<(), DeviceError> {
+        Ok(self.transcript.add_invite(invite)?)
+    }
+
+    pub fn add_capabilities(&mut self, capabilities: &Capabilities) -> Result<(), DeviceError> {
+        Ok(self.transcript.add_capabilities(capabilities)?)
+    }
+
+    pub fn add_start(&mut self, start: &Start) -> Result<(), DeviceError> {
+        Ok(self.transcript.add_start(start)?)
+    }
+
+    /// Our ephemeral public key, recorded in the transcript as it is handed out.
+    pub fn public_key(&mut self) -> Result<PublicKey, DeviceError> {
+        let xy = self.secret_key.public_key().to_encoded_point(false);
+        let pk = PublicKey {
+            x: <[u8; 32]>::try_from(xy.x().ok_or(DeviceError::KeyInitialization)?.as_slice())
+                .map_err(|_| DeviceError::InsufficientBuffer)?,
+            y: <[u8; 32]>::try_from(xy.y().ok_or(DeviceError::KeyInitialization)?.as_slice())
+                .map_err(|_| DeviceError::InsufficientBuffer)?,
+        };
+        self.transcript.add_pubkey_provisioner(&pk)?;
+        Ok(pk)
+    }
+
+    pub fn set_peer_public_key(&mut self, pk: &PublicKey) -> Result<(), DeviceError> {
+        let peer_pk: Option<p256::PublicKey> = p256::PublicKey::from_encoded_point(
+            &EncodedPoint::from_affine_coordinates(&pk.x.into(), &pk.y.into(), false),
+        )
+        .into();
+        let peer_pk = peer_pk.ok_or(DeviceError::ProvisioningFailed(ErrorCode::InvalidFormat))?;
+        self.transcript.add_pubkey_device(pk)?;
+        self.shared_secret.replace(diffie_hellman(
+            self.secret_key.to_nonzero_scalar(),
+            peer_pk.as_affine(),
+        ));
+        Ok(())
+    }
+
+    /// The AuthValue agreed out-of-band, set before confirming.
+    pub fn set_auth_value(&mut self, auth_value: [u8; 16]) {
+        self.auth_value = auth_value;
+    }
+
+    pub fn confirmation(&self) -> Result<Confirmation, DeviceError> {
+        Ok(Confirmation {
+            confirmation: self.confirm(&self.random_provisioner)?,
+        })
+    }
+
+    pub fn random(&self) -> Random {
+        Random {
+            random: self.random_provisioner,
+        }
+    }
+
+    pub fn set_confirmation_device(&mut self, confirmation: &Confirmation) {
+        self.confirmation_device.replace(confirmation.confirmation);
+    }
+
+    /// Check the device's revealed random against the confirmation it sent earlier.
+    pub fn verify_random_device(&mut self, random: &Random) -> Result<(), DeviceError> {
+        let expected = self.confirmation_device.ok_or(DeviceError::InvalidState)?;
+        if self.confirm(&random.random)? != expected {
+            return Err(DeviceError::ProvisioningFailed(
+                ErrorCode::ConfirmationFailed,
+            ));
+        }
+        self.random_device.replace(random.random);
+        Ok(())
+    }
+
+    /// Encrypt the provisioning data with the session key, returning the
+    /// `Data` PDU along with the device key the node will derive.
+    pub fn encrypt(
+        &self,
+        provisioning_data: &ProvisioningData,
+    ) -> Result<(Data, [u8; 16]), DeviceError> {
+        let provisioning_salt = self.provisioning_salt()?;
+        let session_key = self.n_k1(&provisioning_salt, b"prsk")?;
+        let session_nonce = self.n_k1(&provisioning_salt, b"prsn")?;
+        let device_key = self.n_k1(&provisioning_salt, b"prdk")?;
+
+        let mut plaintext: Vec<u8, 25> = Vec::new();
+        provisioning_data.emit(&mut plaintext)?;
+
+        let mut data = Data {
+            encrypted: [0; 25],
+            mic: [0; 8],
+        };
+        data.encrypted.copy_from_slice(&plaintext);
+        crypto::aes_ccm_encrypt_detached(
+            &session_key,
+            &session_nonce[3..],
+            &mut data.encrypted,
+            &mut data.mic,
+            None,
+        )
+        .map_err(|_| DeviceError::CryptoError("provisioning data"))?;
+
+        Ok((data, device_key))
+    }
+
+    fn confirm(&self, random: &[u8; 16]) -> Result<[u8; 16], DeviceError> {
+        let salt = self.transcript.confirmation_salt()?.into_bytes();
+        let confirmation_key = self.n_k1(&salt, b"prck")?;
+        let mut bytes: Vec<u8, 32> = Vec::new();
+        bytes
+            .extend_from_slice(random)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        bytes
+            .extend_from_slice(&self.auth_value)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        let confirmation = crypto::aes_cmac(&confirmation_key, &bytes)?.into_bytes();
+        let mut result = [0; 16];
+        result.copy_from_slice(&confirmation);
+        Ok(result)
+    }
+
+    fn provisioning_salt(&self) -> Result<[u8; 16], DeviceError> {
+        let mut input = [0; 48];
+        input[0..16].copy_from_slice(&self.transcript.confirmation_salt()?.into_bytes());
+        input[16..32].copy_from_slice(&self.random_provisioner);
+        input[32..48].copy_from_slice(&self.random_device.ok_or(DeviceError::InvalidState)?);
+        let mut salt = [0; 16];
+        salt.copy_from_slice(&crypto::s1(&input)?.into_bytes());
+        Ok(salt)
+    }
+
+    fn n_k1(&self, salt: &[u8], p: &[u8]) -> Result<[u8; 16], DeviceError> {
+        let shared_secret = self
+            .shared_secret
+            .as_ref()
+            .ok_or(DeviceError::NoSharedSecret)?;
+        let key = crypto::k1(shared_secret.as_bytes(), salt, p)?.into_bytes();
+        let mut result = [0; 16];
+        result.copy_from_slice(&key);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::address::UnicastAddress;
+    use crate::drivers::ble::mesh::model::foundation::configuration::NetKeyIndex;
+    use crate::drivers::ble::mesh::provisioning::{IVUpdateFlag, KeyRefreshFlag};
+    use rand_core::{CryptoRng, Error, RngCore};
+
+    struct TestRng(u64);
+
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for b in dest.iter_mut() {
+                *b = self.next_u64() as u8;
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for TestRng {}
+
+    fn public_key(secret_key: &SecretKey) -> PublicKey {
+        let xy = secret_key.public_key().to_encoded_point(false);
+        PublicKey {
+            x: <[u8; 32]>::try_from(xy.x().unwrap().as_slice()).unwrap(),
+            y: <[u8; 32]>::try_from(xy.y().unwrap().as_slice()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn device_can_verify_and_decrypt() {
+        let mut rng = TestRng(0x2545F4914F6CDD1D);
+        let mut session = ProvisioningSession::new(&mut rng);
+        let mut device_transcript = Transcript::default();
+
+        let device_secret = SecretKey::random(&mut rng);
+        let device_pk = public_key(&device_secret);
+
+        let invite = Invite {
+            attention_duration: 0,
+        };
+        session.add_invite(&invite).unwrap();
+        device_transcript.add_invite(&invite).unwrap();
+
+        let provisioner_pk = session.public_key().unwrap();
+        device_transcript
+            .add_pubkey_provisioner(&provisioner_pk)
+            .unwrap();
+        session.set_peer_public_key(&device_pk).unwrap();
+        device_transcript.add_pubkey_device(&device_pk).unwrap();
+
+        let peer: Option<p256::PublicKey> =
+            p256::PublicKey::from_encoded_point(&EncodedPoint::from_affine_coordinates(
+                &provisioner_pk.x.into(),
+                &provisioner_pk.y.into(),
+                false,
+            ))
+            .into();
+        let ecdh = diffie_hellman(device_secret.to_nonzero_scalar(), peer.unwrap().as_affine());
+        let ecdh = ecdh.as_bytes();
+
+        // device side confirmation of the provisioner's values
+        let confirmation_salt = device_transcript.confirmation_salt().unwrap().into_bytes();
+        let confirmation_key = crypto::k1(ecdh, &confirmation_salt, b"prck")
+            .unwrap()
+            .into_bytes();
+        let mut input = [0; 32];
+        input[0..16].copy_from_slice(&session.random().random);
+        assert_eq!(
+            &crypto::aes_cmac(&confirmation_key, &input)
+                .unwrap()
+                .into_bytes()[..],
+            &session.confirmation().unwrap().confirmation[..]
+        );
+
+        // and the provisioner side verification of the device
+        let random_device = [0x42; 16];
+        input[0..16].copy_from_slice(&random_device);
+        let mut confirmation_device = [0; 16];
+        confirmation_device.copy_from_slice(
+            &crypto::aes_cmac(&confirmation_key, &input)
+                .unwrap()
+                .into_bytes(),
+        );
+        session.set_confirmation_device(&Confirmation {
+            confirmation: confirmation_device,
+        });
+        assert!(session
+            .verify_random_device(&Random { random: [0x24; 16] })
+            .is_err());
+        session
+            .verify_random_device(&Random {
+                random: random_device,
+            })
+            .unwrap();
+
+        let (mut data, device_key) = session
+            .encrypt(&ProvisioningData {
+                network_key: [0x7F; 16],
+                key_index: NetKeyIndex::new(0x0123),
+                key_refresh_flag: KeyRefreshFlag::Phase0,
+                iv_update_flag: IVUpdateFlag::UpdateActive,
+                iv_index: 0x12345678,
+                unicast_address: UnicastAddress(0x0B0C),
+            })
+            .unwrap();
+
+        let mut salt_input = [0; 48];
+        salt_input[0..16].copy_from_slice(&confirmation_salt);
+        salt_input[16..32].copy_from_slice(&session.random().random);
+        salt_input[32..48].copy_from_slice(&random_device);
+        let provisioning_salt = crypto::s1(&salt_input).unwrap().into_bytes();
+        let session_key = crypto::k1(ecdh, &provisioning_salt, b"prsk")
+            .unwrap()
+            .into_bytes();
+        let session_nonce = crypto::k1(ecdh, &provisioning_salt, b"prsn")
+            .unwrap()
+            .into_bytes();
+        crypto::aes_ccm_decrypt_detached(
+            &session_key,
+            &session_nonce[3..],
+            &mut data.encrypted,
+            &data.mic,
+            None,
+        )
+        .unwrap();
+
+        let decrypted = ProvisioningData::parse(&data.encrypted).unwrap();
+        assert_eq!(decrypted.network_key, [0x7F; 16]);
+        assert_eq!(decrypted.key_index.value(), 0x0123);
+        assert!(matches!(
+            decrypted.iv_update_flag,
+            IVUpdateFlag::UpdateActive
+        ));
+        assert_eq!(decrypted.iv_index, 0x12345678);
+        assert_eq!(u16::from(decrypted.unicast_address), 0x0B0C);
+
+        let expected_device_key = crypto::k1(ecdh, &provisioning_salt, b"prdk")
+            .unwrap()
+            .into_bytes();
+        assert_eq!(&device_key[..], &expected_device_key[..]);
+    }
+}
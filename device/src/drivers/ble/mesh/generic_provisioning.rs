@@ -156,12 +156,21 @@ impl ProvisioningBearerControl {
 
     pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
         match self {
-            ProvisioningBearerControl::LinkOpen(_) => {}
+            ProvisioningBearerControl::LinkOpen(uuid) => {
+                xmit.push(0x00 << 2 | 0b11)
+                    .map_err(|_| InsufficientBuffer)?;
+                xmit.extend_from_slice(&uuid.0)
+                    .map_err(|_| InsufficientBuffer)?;
+            }
             ProvisioningBearerControl::LinkAck => {
                 xmit.push(0x01 << 2 | 0b11)
                     .map_err(|_| InsufficientBuffer)?;
             }
-            ProvisioningBearerControl::LinkClose(_) => {}
+            ProvisioningBearerControl::LinkClose(reason) => {
+                xmit.push(0x02 << 2 | 0b11)
+                    .map_err(|_| InsufficientBuffer)?;
+                xmit.push(*reason as u8).map_err(|_| InsufficientBuffer)?;
+            }
         }
 
         Ok(())
@@ -1,3 +1,4 @@
+use crate::drivers::ble::mesh::beacon;
 use crate::drivers::ble::mesh::device::Uuid;
 use crate::drivers::ble::mesh::driver::node::{NetworkId, State};
 use crate::drivers::ble::mesh::generic_provisioning::{
@@ -18,7 +19,7 @@ use core::cell::RefCell;
 use core::iter::Iterator;
 use heapless::Vec;
 
-mod segmentation;
+pub(crate) mod segmentation;
 
 pub struct AdvertisingBearerNetworkInterface<B: AdvertisingBearer> {
     uuid: Cell<Option<Uuid>>,
@@ -71,8 +72,13 @@ impl<B: AdvertisingBearer> AdvertisingBearerNetworkInterface<B> {
             Beacon::Provisioned(_) => {
                 // not applicable to this role
             }
-            Beacon::Secure => {
-                // nothing yet.
+            Beacon::Secure(secure) => {
+                let mut adv_data: Vec<u8, PB_ADV_MTU> = Vec::new();
+                adv_data.push(0x00)?;
+                adv_data.push(MESH_BEACON)?;
+                secure.emit(&mut adv_data)?;
+                adv_data[0] = adv_data.len() as u8 - 1;
+                self.bearer.transmit(&adv_data).await?;
             }
         }
         Ok(())
@@ -82,6 +88,9 @@ impl<B: AdvertisingBearer> AdvertisingBearerNetworkInterface<B> {
         match pdu {
             PDU::Provisioning(pdu) => self.transmit_provisioning_pdu(&pdu).await,
             PDU::Network(pdu) => self.transmit_network_pdu(&pdu).await,
+            PDU::Beacon(secure) => self.beacon(Beacon::Secure(*secure)).await,
+            #[cfg(feature = "ble-mesh-proxy")]
+            PDU::Proxy(_) => Ok(()),
         }
     }
 
@@ -134,6 +143,13 @@ impl<B: AdvertisingBearer> AdvertisingBearerNetworkInterface<B> {
                             return Ok(PDU::Network(pdu));
                         }
                     }
+                    MESH_BEACON => {
+                        if let Ok(beacon::Beacon::SecureNetwork(secure)) =
+                            beacon::Beacon::parse(&data[2..])
+                        {
+                            return Ok(PDU::Beacon(secure));
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -1,5 +1,8 @@
+use crate::drivers::ble::mesh::beacon;
 use crate::drivers::ble::mesh::device::Uuid;
 use crate::drivers::ble::mesh::driver::node::{NetworkId, State};
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::interface::ProxyMessage;
 use crate::drivers::ble::mesh::interface::{Beacon, BearerError, GattBearer, NetworkError, PDU};
 use crate::drivers::ble::mesh::pdu::network::ObfuscatedAndEncryptedNetworkPDU;
 use crate::drivers::ble::mesh::pdu::proxy::{MessageType, ProxyPDU, SAR};
@@ -32,6 +35,11 @@ impl<B: GattBearer<MTU>, const MTU: usize> GattBearerNetworkInterface<B, MTU> {
         self.bearer.set_state(state);
     }
 
+    #[cfg(feature = "ble-mesh-proxy")]
+    pub(super) fn connection(&self) -> Option<u32> {
+        self.bearer.connection()
+    }
+
     pub async fn run(&self) -> Result<(), NetworkError> {
         self.bearer.run().await?;
         Ok(())
@@ -45,9 +53,25 @@ impl<B: GattBearer<MTU>, const MTU: usize> GattBearerNetworkInterface<B, MTU> {
                 match proxy_pdu.message_type {
                     MessageType::NetworkPDU => {
                         let pdu = ObfuscatedAndEncryptedNetworkPDU::parse(&proxy_pdu.data)?;
+                        // tagged with the bearer, so as not to be forwarded back.
+                        #[cfg(feature = "ble-mesh-proxy")]
+                        return Ok(PDU::Proxy(ProxyMessage::Network(pdu)));
+                        #[cfg(not(feature = "ble-mesh-proxy"))]
                         return Ok(PDU::Network(pdu));
                     }
-                    MessageType::MeshBeacon => {}
+                    MessageType::MeshBeacon => {
+                        if let Ok(beacon::Beacon::SecureNetwork(secure)) =
+                            beacon::Beacon::parse(&proxy_pdu.data)
+                        {
+                            return Ok(PDU::Beacon(secure));
+                        }
+                    }
+                    #[cfg(feature = "ble-mesh-proxy")]
+                    MessageType::ProxyConfiguration => {
+                        let pdu = ObfuscatedAndEncryptedNetworkPDU::parse(&proxy_pdu.data)?;
+                        return Ok(PDU::Proxy(ProxyMessage::Configuration(pdu)));
+                    }
+                    #[cfg(not(feature = "ble-mesh-proxy"))]
                     MessageType::ProxyConfiguration => {}
                     MessageType::ProvisioningPDU => {
                         let pdu = ProvisioningPDU::parse(&proxy_pdu.data)?;
@@ -73,22 +97,45 @@ impl<B: GattBearer<MTU>, const MTU: usize> GattBearerNetworkInterface<B, MTU> {
 
                 self.transmit_proxy_pdu(&proxy_pdu).await
             }
+            // with the proxy feature, only network PDUs which passed the
+            // proxy filter of the client are forwarded.
+            #[cfg(not(feature = "ble-mesh-proxy"))]
             PDU::Network(pdu) => {
-                let mut all_proxy_data = Vec::<u8, 384>::new();
-                pdu.emit(&mut all_proxy_data)?;
-                let mut data = Vec::new();
-                data.extend_from_slice(&all_proxy_data)?;
-                let proxy_pdu = ProxyPDU {
-                    sar: SAR::Complete,
-                    message_type: MessageType::NetworkPDU,
-                    data,
-                };
-
-                self.transmit_proxy_pdu(&proxy_pdu).await
+                self.transmit_network_pdu(pdu, MessageType::NetworkPDU)
+                    .await
+            }
+            #[cfg(feature = "ble-mesh-proxy")]
+            PDU::Network(_) => Ok(()),
+            PDU::Beacon(secure) => self.beacon(Beacon::Secure(*secure)).await,
+            #[cfg(feature = "ble-mesh-proxy")]
+            PDU::Proxy(ProxyMessage::Network(pdu)) => {
+                self.transmit_network_pdu(pdu, MessageType::NetworkPDU)
+                    .await
+            }
+            #[cfg(feature = "ble-mesh-proxy")]
+            PDU::Proxy(ProxyMessage::Configuration(pdu)) => {
+                self.transmit_network_pdu(pdu, MessageType::ProxyConfiguration)
+                    .await
             }
         }
     }
 
+    async fn transmit_network_pdu(
+        &self,
+        pdu: &ObfuscatedAndEncryptedNetworkPDU,
+        message_type: MessageType,
+    ) -> Result<(), BearerError> {
+        let mut data = Vec::new();
+        pdu.emit(&mut data)?;
+        let proxy_pdu = ProxyPDU {
+            sar: SAR::Complete,
+            message_type,
+            data,
+        };
+
+        self.transmit_proxy_pdu(&proxy_pdu).await
+    }
+
     async fn transmit_proxy_pdu(&self, pdu: &ProxyPDU) -> Result<(), BearerError> {
         let mut bytes = Vec::new();
         pdu.emit(&mut bytes)?;
@@ -131,8 +178,15 @@ impl<B: GattBearer<MTU>, const MTU: usize> GattBearerNetworkInterface<B, MTU> {
                 adv_data.extend_from_slice(&network_id.0)?;
                 self.bearer.advertise(&adv_data).await?;
             }
-            Beacon::Secure => {
-                // nothing yet
+            Beacon::Secure(secure) => {
+                let mut data = Vec::new();
+                secure.emit(&mut data)?;
+                let proxy_pdu = ProxyPDU {
+                    sar: SAR::Complete,
+                    message_type: MessageType::MeshBeacon,
+                    data,
+                };
+                self.transmit_proxy_pdu(&proxy_pdu).await?;
             }
         }
 
@@ -6,6 +6,7 @@ use core::future::Future;
 use embassy::util::{select, Either};
 use futures::future::join;
 
+use crate::drivers::ble::mesh::beacon::SecureNetworkBeacon;
 use crate::drivers::ble::mesh::device::Uuid;
 use crate::drivers::ble::mesh::driver::node::{NetworkId, State};
 use crate::drivers::ble::mesh::interface::advertising::AdvertisingBearerNetworkInterface;
@@ -34,13 +35,27 @@ impl From<BearerError> for NetworkError {
 pub enum Beacon {
     Unprovisioned,
     Provisioned(NetworkId),
-    Secure,
+    Secure(SecureNetworkBeacon),
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum PDU {
     Provisioning(ProvisioningPDU),
     Network(ObfuscatedAndEncryptedNetworkPDU),
+    Beacon(SecureNetworkBeacon),
+    /// Exchanged with proxy clients over GATT only.
+    #[cfg(feature = "ble-mesh-proxy")]
+    Proxy(ProxyMessage),
+}
+
+#[cfg(feature = "ble-mesh-proxy")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProxyMessage {
+    /// A network PDU exchanged with the connected client, which passed its
+    /// proxy filter when sent to it.
+    Network(ObfuscatedAndEncryptedNetworkPDU),
+    /// A proxy configuration message, secured with the proxy nonce.
+    Configuration(ObfuscatedAndEncryptedNetworkPDU),
 }
 
 /// A possibly plurality of network interfaces covering one or more bearers.
@@ -86,6 +101,11 @@ pub trait NetworkInterfaces {
 
     /// Perform beaconing on all of the network interfaces.
     fn beacon<'m>(&'m self, beacon: Beacon) -> Self::BeaconFuture<'m>;
+
+    /// Identifies the connected proxy client, if any, changing with every
+    /// new connection.
+    #[cfg(feature = "ble-mesh-proxy")]
+    fn proxy_connection(&self) -> Option<u32>;
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -175,6 +195,10 @@ pub trait GattBearer<const MTU: usize> {
 
     /// Transmit data on the bearer.
     fn advertise<'m>(&'m self, adv_data: &'m Vec<u8, 64>) -> Self::AdvertiseFuture<'m>;
+
+    /// Identifies the current connection, if any, changing with every new connection.
+    #[cfg(feature = "ble-mesh-proxy")]
+    fn connection(&self) -> Option<u32>;
 }
 
 pub struct AdvertisingAndGattNetworkInterfaces<
@@ -270,6 +294,11 @@ impl<AB: AdvertisingBearer, GB: GattBearer<MTU>, const MTU: usize> NetworkInterf
             Ok(())
         }
     }
+
+    #[cfg(feature = "ble-mesh-proxy")]
+    fn proxy_connection(&self) -> Option<u32> {
+        self.gatt_interface.connection()
+    }
 }
 
 pub struct AdvertisingOnlyNetworkInterfaces<B: AdvertisingBearer> {
@@ -335,4 +364,9 @@ impl<B: AdvertisingBearer> NetworkInterfaces for AdvertisingOnlyNetworkInterface
     fn beacon<'m>(&'m self, beacon: Beacon) -> Self::BeaconFuture<'m> {
         async move { Ok(self.interface.beacon(beacon).await?) }
     }
+
+    #[cfg(feature = "ble-mesh-proxy")]
+    fn proxy_connection(&self) -> Option<u32> {
+        None
+    }
 }
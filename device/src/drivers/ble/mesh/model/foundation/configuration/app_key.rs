@@ -1,3 +1,4 @@
+use crate::drivers::ble::mesh::config::network::MAX_APP_KEYS;
 use crate::drivers::ble::mesh::model::foundation::configuration::{
     AppKeyIndex, KeyIndex, NetKeyAppKeyIndexesPair, NetKeyIndex,
 };
@@ -39,6 +40,27 @@ impl AppKeyMessage {
         }
     }
 
+    pub fn parse_delete(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 3 {
+            let indexes = NetKeyAppKeyIndexesPair::parse(parameters)?;
+            Ok(Self::Delete(AppKeyDeleteMessage { indexes }))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_update(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 19 {
+            let indexes = NetKeyAppKeyIndexesPair::parse(&parameters[0..=2])?;
+            let app_key = parameters[3..]
+                .try_into()
+                .map_err(|_| ParseError::InvalidLength)?;
+            Ok(Self::Update(AppKeyUpdateMessage { indexes, app_key }))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
     pub fn parse_get(parameters: &[u8]) -> Result<Self, ParseError> {
         if parameters.len() == 2 {
             let net_key_index = NetKeyIndex(KeyIndex::parse_one(parameters)?);
@@ -47,6 +69,16 @@ impl AppKeyMessage {
             Err(ParseError::InvalidLength)
         }
     }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 4 {
+            let status = Status::parse(parameters[0])?;
+            let indexes = NetKeyAppKeyIndexesPair::parse(&parameters[1..])?;
+            Ok(Self::Status(AppKeyStatusMessage { status, indexes }))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
 }
 
 impl Message for AppKeyMessage {
@@ -57,7 +89,7 @@ impl Message for AppKeyMessage {
             Self::Get(_) => CONFIG_APPKEY_GET,
             Self::List(_) => CONFIG_APPKEY_LIST,
             Self::Status(_) => CONFIG_APPKEY_STATUS,
-            Self::Update(_) => CONFIG_APPKEY_STATUS,
+            Self::Update(_) => CONFIG_APPKEY_UPDATE,
         }
     }
 
@@ -85,9 +117,12 @@ pub struct AppKeyAddMessage {
 impl AppKeyAddMessage {
     fn emit_parameters<const N: usize>(
         &self,
-        _xmit: &mut Vec<u8, N>,
+        xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
-        todo!();
+        self.indexes.emit(xmit)?;
+        xmit.extend_from_slice(&self.app_key)
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
     }
 }
 
@@ -99,9 +134,9 @@ pub struct AppKeyDeleteMessage {
 impl AppKeyDeleteMessage {
     fn emit_parameters<const N: usize>(
         &self,
-        _xmit: &mut Vec<u8, N>,
+        xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
-        todo!();
+        self.indexes.emit(xmit)
     }
 }
 
@@ -113,9 +148,9 @@ pub struct AppKeyGetMessage {
 impl AppKeyGetMessage {
     fn emit_parameters<const N: usize>(
         &self,
-        _xmit: &mut Vec<u8, N>,
+        xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
-        todo!()
+        self.net_key_index.emit(xmit)
     }
 }
 
@@ -123,7 +158,7 @@ impl AppKeyGetMessage {
 pub struct AppKeyListMessage {
     pub(crate) status: Status,
     pub(crate) net_key_index: NetKeyIndex,
-    pub(crate) app_key_indexes: Vec<AppKeyIndex, 10>,
+    pub(crate) app_key_indexes: Vec<AppKeyIndex, MAX_APP_KEYS>,
 }
 
 impl AppKeyListMessage {
@@ -176,16 +211,86 @@ impl AppKeyStatusMessage {
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct AppKeyUpdateMessage {
-    pub(crate) net_key_index: NetKeyIndex,
-    pub(crate) app_key_index: AppKeyIndex,
+    pub(crate) indexes: NetKeyAppKeyIndexesPair,
     pub(crate) app_key: [u8; 16],
 }
 
 impl AppKeyUpdateMessage {
     fn emit_parameters<const N: usize>(
         &self,
-        _xmit: &mut Vec<u8, N>,
+        xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
-        todo!()
+        self.indexes.emit(xmit)?;
+        xmit.extend_from_slice(&self.app_key)
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::model::foundation::configuration::{
+        ConfigurationMessage, ConfigurationServer,
+    };
+    use crate::drivers::ble::mesh::model::Model;
+
+    fn indexes() -> NetKeyAppKeyIndexesPair {
+        NetKeyAppKeyIndexesPair::new(NetKeyIndex::new(0x123), AppKeyIndex::new(0x456))
+    }
+
+    fn round_trip(message: AppKeyMessage) -> AppKeyMessage {
+        let mut parameters: Vec<u8, 32> = Vec::new();
+        message.emit_parameters(&mut parameters).unwrap();
+        match ConfigurationServer::parse(message.opcode(), &parameters) {
+            Ok(Some(ConfigurationMessage::AppKey(message))) => message,
+            _ => panic!("not an appkey message"),
+        }
+    }
+
+    #[test]
+    fn add_round_trip() {
+        let message = round_trip(AppKeyMessage::Add(AppKeyAddMessage {
+            indexes: indexes(),
+            app_key: [0xAA; 16],
+        }));
+        match message {
+            AppKeyMessage::Add(add) => {
+                assert_eq!(0x123, add.indexes.net_key().value());
+                assert_eq!(0x456, add.indexes.app_key().value());
+                assert_eq!([0xAA; 16], add.app_key);
+            }
+            _ => panic!("not an add"),
+        }
+    }
+
+    #[test]
+    fn update_round_trip() {
+        let message = round_trip(AppKeyMessage::Update(AppKeyUpdateMessage {
+            indexes: indexes(),
+            app_key: [0xBB; 16],
+        }));
+        match message {
+            AppKeyMessage::Update(update) => {
+                assert_eq!(0x123, update.indexes.net_key().value());
+                assert_eq!(0x456, update.indexes.app_key().value());
+                assert_eq!([0xBB; 16], update.app_key);
+            }
+            _ => panic!("not an update"),
+        }
+    }
+
+    #[test]
+    fn delete_round_trip() {
+        let message = round_trip(AppKeyMessage::Delete(AppKeyDeleteMessage {
+            indexes: indexes(),
+        }));
+        match message {
+            AppKeyMessage::Delete(delete) => {
+                assert_eq!(0x123, delete.indexes.net_key().value());
+                assert_eq!(0x456, delete.indexes.app_key().value());
+            }
+            _ => panic!("not a delete"),
+        }
     }
 }
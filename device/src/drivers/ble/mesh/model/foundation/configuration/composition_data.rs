@@ -1,4 +1,7 @@
-use crate::drivers::ble::mesh::composition::Composition;
+use crate::drivers::ble::mesh::composition::{
+    CompanyIdentifier, Composition, ElementDescriptor, Features, Location, ProductIdentifier,
+    VersionIdentifier,
+};
 use crate::drivers::ble::mesh::model::{Message, ModelIdentifier};
 use crate::drivers::ble::mesh::pdu::access::Opcode;
 use crate::drivers::ble::mesh::pdu::ParseError;
@@ -24,6 +27,10 @@ impl CompositionDataMessage {
             Err(ParseError::InvalidLength)
         }
     }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        Ok(Self::Status(CompositionStatus::parse(parameters)?))
+    }
 }
 
 impl Message for CompositionDataMessage {
@@ -55,18 +62,64 @@ pub struct CompositionStatus {
 }
 
 impl CompositionStatus {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() < 11 {
+            return Err(ParseError::InvalidLength);
+        }
+        let page = parameters[0];
+        let mut data = Composition::new(
+            CompanyIdentifier::parse(&parameters[1..=2])?,
+            ProductIdentifier(u16::from_le_bytes([parameters[3], parameters[4]])),
+            VersionIdentifier(u16::from_le_bytes([parameters[5], parameters[6]])),
+            Features::parse(&parameters[9..=10])?,
+        );
+        data.crpl = u16::from_le_bytes([parameters[7], parameters[8]]);
+
+        let mut elements = &parameters[11..];
+        while !elements.is_empty() {
+            if elements.len() < 4 {
+                return Err(ParseError::InvalidLength);
+            }
+            let loc = Location(u16::from_le_bytes([elements[0], elements[1]]));
+            let num_sig = elements[2] as usize;
+            let num_vendor = elements[3] as usize;
+            let len = 4 + (num_sig * 2) + (num_vendor * 4);
+            if elements.len() < len {
+                return Err(ParseError::InvalidLength);
+            }
+
+            let mut element = ElementDescriptor::new(loc);
+            let (sig_models, vendor_models) = elements[4..len].split_at(num_sig * 2);
+            for model in sig_models.chunks(2).chain(vendor_models.chunks(4)) {
+                element
+                    .models
+                    .push(ModelIdentifier::parse(model)?)
+                    .map_err(|_| ParseError::InsufficientBuffer)?;
+            }
+            data.elements
+                .push(element)
+                .map_err(|_| ParseError::InsufficientBuffer)?;
+            elements = &elements[len..];
+        }
+
+        Ok(Self { page, data })
+    }
+
     fn emit_parameters<const N: usize>(
         &self,
         xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
         xmit.push(self.page).map_err(|_| InsufficientBuffer)?;
-        xmit.extend_from_slice(&self.data.cid.0.to_be_bytes())
+        // like every other multi-octet field of the page, CID, PID, VID and
+        // CRPL are little-endian. they used to be emitted big-endian, which
+        // provisioners displayed byte-swapped.
+        xmit.extend_from_slice(&self.data.cid.0.to_le_bytes())
             .map_err(|_| InsufficientBuffer)?;
-        xmit.extend_from_slice(&self.data.pid.0.to_be_bytes())
+        xmit.extend_from_slice(&self.data.pid.0.to_le_bytes())
             .map_err(|_| InsufficientBuffer)?;
-        xmit.extend_from_slice(&self.data.vid.0.to_be_bytes())
+        xmit.extend_from_slice(&self.data.vid.0.to_le_bytes())
             .map_err(|_| InsufficientBuffer)?;
-        xmit.extend_from_slice(&self.data.crpl.to_be_bytes())
+        xmit.extend_from_slice(&self.data.crpl.to_le_bytes())
             .map_err(|_| InsufficientBuffer)?;
         self.data.features.emit(xmit)?;
         for element in self.data.elements.iter() {
@@ -99,3 +152,87 @@ impl CompositionStatus {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn composition() -> Composition {
+        let mut composition = Composition::new(
+            CompanyIdentifier(0x000C),
+            ProductIdentifier(0x001A),
+            VersionIdentifier(0x0001),
+            Features {
+                relay: true,
+                proxy: true,
+                friend: false,
+                low_power: false,
+            },
+        );
+        composition.crpl = 0x0008;
+        composition
+            .add_element(
+                ElementDescriptor::new(Location(0x0100))
+                    .add_model(ModelIdentifier::SIG(0x0000))
+                    .unwrap()
+                    .add_model(ModelIdentifier::Vendor(CompanyIdentifier(0x000C), 0x1234))
+                    .unwrap()
+                    .add_model(ModelIdentifier::SIG(0x1000))
+                    .unwrap(),
+            )
+            .ok();
+        composition
+    }
+
+    #[test]
+    fn emit_status() {
+        let status = CompositionDataMessage::Status(CompositionStatus {
+            page: 0,
+            data: composition(),
+        });
+        let mut parameters: Vec<u8, 64> = Vec::new();
+        status.emit_parameters(&mut parameters).unwrap();
+        assert_eq!(
+            &[
+                0x00, // page
+                0x0C, 0x00, // cid
+                0x1A, 0x00, // pid
+                0x01, 0x00, // vid
+                0x08, 0x00, // crpl
+                0x03, 0x00, // features
+                0x00, 0x01, // loc
+                0x02, 0x01, // num_s, num_v
+                0x00, 0x00, 0x00, 0x10, // sig models
+                0x0C, 0x00, 0x34, 0x12, // vendor models
+            ],
+            &*parameters
+        );
+    }
+
+    #[test]
+    fn parse_emitted_status() {
+        let status = CompositionStatus {
+            page: 0,
+            data: composition(),
+        };
+        let mut parameters: Vec<u8, 64> = Vec::new();
+        status.emit_parameters(&mut parameters).unwrap();
+
+        let parsed = CompositionStatus::parse(&parameters).unwrap();
+        assert_eq!(0, parsed.page);
+        assert!(parsed.data.cid == CompanyIdentifier(0x000C));
+        assert_eq!(0x001A, parsed.data.pid.0);
+        assert_eq!(0x0001, parsed.data.vid.0);
+        assert_eq!(0x0008, parsed.data.crpl);
+        assert_eq!(1, parsed.data.elements.len());
+        assert_eq!(0x0100, parsed.data.elements[0].loc.0);
+        assert!(
+            parsed.data.elements[0].models
+                == [
+                    ModelIdentifier::SIG(0x0000),
+                    ModelIdentifier::SIG(0x1000),
+                    ModelIdentifier::Vendor(CompanyIdentifier(0x000C), 0x1234),
+                ]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use crate::drivers::ble::mesh::model::Message;
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+opcode!( CONFIG_FRIEND_GET 0x80, 0x0F );
+opcode!( CONFIG_FRIEND_SET 0x80, 0x10 );
+opcode!( CONFIG_FRIEND_STATUS 0x80, 0x11 );
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Friend {
+    SupportedDisabled = 0x00,
+    SupportedEnabled = 0x01,
+    NotSupported = 0x02,
+}
+
+impl Default for Friend {
+    fn default() -> Self {
+        Self::SupportedEnabled
+    }
+}
+
+impl Friend {
+    pub fn parse(data: u8) -> Result<Self, ParseError> {
+        match data {
+            0x00 => Ok(Self::SupportedDisabled),
+            0x01 => Ok(Self::SupportedEnabled),
+            0x02 => Ok(Self::NotSupported),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(*self as u8).map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FriendMessage {
+    Get,
+    Set(Friend),
+    Status(Friend),
+}
+
+impl Message for FriendMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => CONFIG_FRIEND_GET,
+            Self::Set(_) => CONFIG_FRIEND_SET,
+            Self::Status(_) => CONFIG_FRIEND_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get => {}
+            Self::Set(inner) => inner.emit(xmit)?,
+            Self::Status(inner) => inner.emit(xmit)?,
+        }
+        Ok(())
+    }
+}
+
+impl FriendMessage {
+    pub fn parse_get(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.is_empty() {
+            Ok(Self::Get)
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_set(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 1 {
+            Ok(Self::Set(Friend::parse(parameters[0])?))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 1 {
+            Ok(Self::Status(Friend::parse(parameters[0])?))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use crate::drivers::ble::mesh::address::Address;
+use crate::drivers::ble::mesh::model::foundation::configuration::{KeyIndex, NetKeyIndex};
+use crate::drivers::ble::mesh::model::{Message, Status};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use embassy::time::Duration;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+opcode!( CONFIG_HEARTBEAT_PUBLICATION_GET 0x80, 0x38 );
+opcode!( CONFIG_HEARTBEAT_PUBLICATION_SET 0x80, 0x39 );
+opcode!( CONFIG_HEARTBEAT_PUBLICATION_STATUS 0x06 );
+opcode!( CONFIG_HEARTBEAT_SUBSCRIPTION_GET 0x80, 0x3A );
+opcode!( CONFIG_HEARTBEAT_SUBSCRIPTION_SET 0x80, 0x3B );
+opcode!( CONFIG_HEARTBEAT_SUBSCRIPTION_STATUS 0x80, 0x3C );
+
+/// Largest log of a heartbeat count or period.
+const MAX_LOG: u8 = 0x11;
+
+/// Heartbeats are published indefinitely.
+const INDEFINITELY: u8 = 0xFF;
+
+/// Counts and periods are exchanged as `2^(log-1)`, with a log of 0 meaning none.
+pub fn from_log(log: u8) -> u16 {
+    match log {
+        0 => 0,
+        1..=0x10 => 1 << (log - 1),
+        MAX_LOG => 0xFFFE,
+        _ => 0xFFFF,
+    }
+}
+
+/// The log reported for a remaining count or period.
+pub fn to_log(value: u16) -> u8 {
+    match value {
+        0 => 0,
+        0xFFFF => INDEFINITELY,
+        _ => (16 - value.leading_zeros()) as u8,
+    }
+}
+
+fn parse_log(log: u8) -> Result<u8, ParseError> {
+    if log <= MAX_LOG {
+        Ok(log)
+    } else {
+        Err(ParseError::InvalidValue)
+    }
+}
+
+/// Node features, as both reported by heartbeats and triggering them when
+/// their state changes.
+#[derive(Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Features {
+    pub relay: bool,
+    pub proxy: bool,
+    pub friend: bool,
+    pub low_power: bool,
+}
+
+impl Features {
+    pub fn parse(bits: u16) -> Self {
+        Self {
+            relay: bits & 0b0001 != 0,
+            proxy: bits & 0b0010 != 0,
+            friend: bits & 0b0100 != 0,
+            low_power: bits & 0b1000 != 0,
+        }
+    }
+
+    pub fn bits(&self) -> u16 {
+        (self.relay as u16)
+            | (self.proxy as u16) << 1
+            | (self.friend as u16) << 2
+            | (self.low_power as u16) << 3
+    }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HeartbeatPublication {
+    pub destination: Address,
+    pub count_log: u8,
+    pub period_log: u8,
+    pub ttl: u8,
+    pub features: Features,
+    pub net_key_index: NetKeyIndex,
+}
+
+impl Default for HeartbeatPublication {
+    fn default() -> Self {
+        Self {
+            destination: Address::Unassigned,
+            count_log: 0,
+            period_log: 0,
+            ttl: 0,
+            features: Default::default(),
+            net_key_index: NetKeyIndex::new(0),
+        }
+    }
+}
+
+impl HeartbeatPublication {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 9 {
+            return Err(ParseError::InvalidLength);
+        }
+        let destination = Address::parse([parameters[1], parameters[0]]);
+        if let Address::Virtual(_) = destination {
+            return Err(ParseError::InvalidValue);
+        }
+        let count_log = match parameters[2] {
+            INDEFINITELY => INDEFINITELY,
+            log => parse_log(log)?,
+        };
+        let period_log = parse_log(parameters[3])?;
+        let ttl = parameters[4];
+        if ttl > 0x7F {
+            return Err(ParseError::InvalidValue);
+        }
+        Ok(Self {
+            destination,
+            count_log,
+            period_log,
+            ttl,
+            features: Features::parse(u16::from_le_bytes([parameters[5], parameters[6]])),
+            net_key_index: NetKeyIndex(KeyIndex::parse_one(&parameters[7..])?),
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        let destination = self.destination.as_bytes();
+        xmit.push(destination[1]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(destination[0]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.count_log).map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.period_log).map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.ttl).map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.features.bits().to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        self.net_key_index.emit(xmit)
+    }
+
+    /// Whether heartbeats are published at all.
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self.destination, Address::Unassigned)
+    }
+
+    /// Heartbeats left to publish periodically.
+    pub fn count(&self) -> u16 {
+        from_log(self.count_log)
+    }
+
+    pub fn period(&self) -> Option<Duration> {
+        match from_log(self.period_log) {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds as u64)),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HeartbeatSubscriptionSet {
+    pub source: Address,
+    pub destination: Address,
+    pub period_log: u8,
+}
+
+impl HeartbeatSubscriptionSet {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 5 {
+            return Err(ParseError::InvalidLength);
+        }
+        let source = Address::parse([parameters[1], parameters[0]]);
+        if !matches!(source, Address::Unassigned | Address::Unicast(_)) {
+            return Err(ParseError::InvalidValue);
+        }
+        let destination = Address::parse([parameters[3], parameters[2]]);
+        if let Address::Virtual(_) = destination {
+            return Err(ParseError::InvalidValue);
+        }
+        Ok(Self {
+            source,
+            destination,
+            period_log: parse_log(parameters[4])?,
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        let source = self.source.as_bytes();
+        xmit.push(source[1]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(source[0]).map_err(|_| InsufficientBuffer)?;
+        let destination = self.destination.as_bytes();
+        xmit.push(destination[1]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(destination[0]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.period_log).map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HeartbeatSubscription {
+    pub source: Address,
+    pub destination: Address,
+    /// Log of the remaining subscription period, in seconds.
+    pub period_log: u8,
+    /// Log of the number of heartbeats received.
+    pub count_log: u8,
+    pub min_hops: u8,
+    pub max_hops: u8,
+}
+
+impl HeartbeatSubscription {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 8 {
+            return Err(ParseError::InvalidLength);
+        }
+        let set = HeartbeatSubscriptionSet::parse(&parameters[0..5])?;
+        Ok(Self {
+            source: set.source,
+            destination: set.destination,
+            period_log: set.period_log,
+            count_log: parameters[5],
+            min_hops: parameters[6],
+            max_hops: parameters[7],
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        HeartbeatSubscriptionSet {
+            source: self.source,
+            destination: self.destination,
+            period_log: self.period_log,
+        }
+        .emit(xmit)?;
+        xmit.push(self.count_log).map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.min_hops).map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.max_hops).map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HeartbeatPublicationMessage {
+    Get,
+    Set(HeartbeatPublication),
+    Status(Status, HeartbeatPublication),
+}
+
+impl Message for HeartbeatPublicationMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => CONFIG_HEARTBEAT_PUBLICATION_GET,
+            Self::Set(_) => CONFIG_HEARTBEAT_PUBLICATION_SET,
+            Self::Status(..) => CONFIG_HEARTBEAT_PUBLICATION_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get => {}
+            Self::Set(inner) => inner.emit(xmit)?,
+            Self::Status(status, inner) => {
+                xmit.push(*status as u8).map_err(|_| InsufficientBuffer)?;
+                inner.emit(xmit)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HeartbeatPublicationMessage {
+    pub fn parse_get(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.is_empty() {
+            Ok(Self::Get)
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_set(parameters: &[u8]) -> Result<Self, ParseError> {
+        Ok(Self::Set(HeartbeatPublication::parse(parameters)?))
+    }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.is_empty() {
+            return Err(ParseError::InvalidLength);
+        }
+        Ok(Self::Status(
+            Status::parse(parameters[0])?,
+            HeartbeatPublication::parse(&parameters[1..])?,
+        ))
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HeartbeatSubscriptionMessage {
+    Get,
+    Set(HeartbeatSubscriptionSet),
+    Status(Status, HeartbeatSubscription),
+}
+
+impl Message for HeartbeatSubscriptionMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => CONFIG_HEARTBEAT_SUBSCRIPTION_GET,
+            Self::Set(_) => CONFIG_HEARTBEAT_SUBSCRIPTION_SET,
+            Self::Status(..) => CONFIG_HEARTBEAT_SUBSCRIPTION_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get => {}
+            Self::Set(inner) => inner.emit(xmit)?,
+            Self::Status(status, inner) => {
+                xmit.push(*status as u8).map_err(|_| InsufficientBuffer)?;
+                inner.emit(xmit)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HeartbeatSubscriptionMessage {
+    pub fn parse_get(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.is_empty() {
+            Ok(Self::Get)
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_set(parameters: &[u8]) -> Result<Self, ParseError> {
+        Ok(Self::Set(HeartbeatSubscriptionSet::parse(parameters)?))
+    }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.is_empty() {
+            return Err(ParseError::InvalidLength);
+        }
+        Ok(Self::Status(
+            Status::parse(parameters[0])?,
+            HeartbeatSubscription::parse(&parameters[1..])?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_log_boundaries() {
+        assert_eq!(from_log(0), 0);
+        assert_eq!(from_log(1), 1);
+        assert_eq!(from_log(2), 2);
+        assert_eq!(from_log(0x10), 0x8000);
+        assert_eq!(from_log(0x11), 0xFFFE);
+        assert_eq!(from_log(INDEFINITELY), 0xFFFF);
+    }
+
+    #[test]
+    fn to_log_boundaries() {
+        assert_eq!(to_log(0), 0);
+        assert_eq!(to_log(1), 1);
+        assert_eq!(to_log(2), 2);
+        assert_eq!(to_log(3), 2);
+        assert_eq!(to_log(0x7FFF), 0x0F);
+        assert_eq!(to_log(0x8000), 0x10);
+        assert_eq!(to_log(0xFFFE), 0x10);
+        assert_eq!(to_log(0xFFFF), INDEFINITELY);
+        for log in 0..=0x10 {
+            assert_eq!(to_log(from_log(log)), log);
+        }
+    }
+
+    #[test]
+    fn parse_log_boundaries() {
+        assert_eq!(parse_log(0).unwrap(), 0);
+        assert_eq!(parse_log(MAX_LOG).unwrap(), MAX_LOG);
+        assert!(matches!(parse_log(0x12), Err(ParseError::InvalidValue)));
+        assert!(matches!(
+            parse_log(INDEFINITELY),
+            Err(ParseError::InvalidValue)
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::drivers::ble::mesh::model::foundation::configuration::{KeyIndex, NetKeyIndex};
+use crate::drivers::ble::mesh::model::{Message, Status};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+opcode!( CONFIG_KEY_REFRESH_PHASE_GET 0x80, 0x15 );
+opcode!( CONFIG_KEY_REFRESH_PHASE_SET 0x80, 0x16 );
+opcode!( CONFIG_KEY_REFRESH_PHASE_STATUS 0x80, 0x17 );
+
+/// Key refresh state of a subnet.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyRefreshPhase {
+    /// Normal operation, using only the current keys.
+    Phase0,
+    /// New keys are being distributed; transmit with the old keys.
+    Phase1,
+    /// New keys are in use; the old keys are still accepted.
+    Phase2,
+}
+
+impl Default for KeyRefreshPhase {
+    fn default() -> Self {
+        Self::Phase0
+    }
+}
+
+impl KeyRefreshPhase {
+    pub fn parse(data: u8) -> Result<Self, ParseError> {
+        match data {
+            0x00 => Ok(Self::Phase0),
+            0x01 => Ok(Self::Phase1),
+            0x02 => Ok(Self::Phase2),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+
+    pub fn emit(&self) -> u8 {
+        match self {
+            Self::Phase0 => 0x00,
+            Self::Phase1 => 0x01,
+            Self::Phase2 => 0x02,
+        }
+    }
+}
+
+/// Transitions a configuration client may request.
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyRefreshTransition {
+    /// Start transmitting with the new keys.
+    UseNewKeys,
+    /// Revoke the old keys, returning to normal operation.
+    RevokeOldKeys,
+}
+
+impl KeyRefreshTransition {
+    pub fn parse(data: u8) -> Result<Self, ParseError> {
+        match data {
+            0x02 => Ok(Self::UseNewKeys),
+            0x03 => Ok(Self::RevokeOldKeys),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+
+    pub fn emit(&self) -> u8 {
+        match self {
+            Self::UseNewKeys => 0x02,
+            Self::RevokeOldKeys => 0x03,
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyRefreshPhaseMessage {
+    Get(NetKeyIndex),
+    Set(KeyRefreshPhaseSetMessage),
+    Status(KeyRefreshPhaseStatusMessage),
+}
+
+impl KeyRefreshPhaseMessage {
+    pub fn parse_get(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 2 {
+            Ok(Self::Get(NetKeyIndex(KeyIndex::parse_one(parameters)?)))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_set(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 3 {
+            let net_key_index = NetKeyIndex(KeyIndex::parse_one(parameters)?);
+            let transition = KeyRefreshTransition::parse(parameters[2])?;
+            Ok(Self::Set(KeyRefreshPhaseSetMessage {
+                net_key_index,
+                transition,
+            }))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 4 {
+            let status = Status::parse(parameters[0])?;
+            let net_key_index = NetKeyIndex(KeyIndex::parse_one(&parameters[1..])?);
+            let phase = KeyRefreshPhase::parse(parameters[3])?;
+            Ok(Self::Status(KeyRefreshPhaseStatusMessage {
+                status,
+                net_key_index,
+                phase,
+            }))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+}
+
+impl Message for KeyRefreshPhaseMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get(_) => CONFIG_KEY_REFRESH_PHASE_GET,
+            Self::Set(_) => CONFIG_KEY_REFRESH_PHASE_SET,
+            Self::Status(_) => CONFIG_KEY_REFRESH_PHASE_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get(net_key_index) => net_key_index.emit(xmit),
+            Self::Set(inner) => inner.emit_parameters(xmit),
+            Self::Status(inner) => inner.emit_parameters(xmit),
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyRefreshPhaseSetMessage {
+    pub(crate) net_key_index: NetKeyIndex,
+    pub(crate) transition: KeyRefreshTransition,
+}
+
+impl KeyRefreshPhaseSetMessage {
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        self.net_key_index.emit(xmit)?;
+        xmit.push(self.transition.emit())
+            .map_err(|_| InsufficientBuffer)
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyRefreshPhaseStatusMessage {
+    pub(crate) status: Status,
+    pub(crate) net_key_index: NetKeyIndex,
+    pub(crate) phase: KeyRefreshPhase,
+}
+
+impl KeyRefreshPhaseStatusMessage {
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.status as u8)
+            .map_err(|_| InsufficientBuffer)?;
+        self.net_key_index.emit(xmit)?;
+        xmit.push(self.phase.emit()).map_err(|_| InsufficientBuffer)
+    }
+}
@@ -1,25 +1,48 @@
 use crate::drivers::ble::mesh::model::foundation::configuration::app_key::{
-    AppKeyMessage, CONFIG_APPKEY_ADD, CONFIG_APPKEY_GET,
+    AppKeyMessage, CONFIG_APPKEY_ADD, CONFIG_APPKEY_DELETE, CONFIG_APPKEY_GET,
+    CONFIG_APPKEY_STATUS, CONFIG_APPKEY_UPDATE,
 };
 use crate::drivers::ble::mesh::model::foundation::configuration::beacon::{
     BeaconMessage, CONFIG_BEACON_GET, CONFIG_BEACON_SET,
 };
 use crate::drivers::ble::mesh::model::foundation::configuration::composition_data::{
-    CompositionDataMessage, CONFIG_COMPOSITION_DATA_GET,
+    CompositionDataMessage, CONFIG_COMPOSITION_DATA_GET, CONFIG_COMPOSITION_DATA_STATUS,
 };
 use crate::drivers::ble::mesh::model::foundation::configuration::default_ttl::{
     DefaultTTLMessage, CONFIG_DEFAULT_TTL_GET, CONFIG_DEFAULT_TTL_SET,
 };
+#[cfg(feature = "ble-mesh-friend")]
+use crate::drivers::ble::mesh::model::foundation::configuration::friend::{
+    FriendMessage, CONFIG_FRIEND_GET, CONFIG_FRIEND_SET,
+};
+#[cfg(feature = "ble-mesh-proxy")]
+use crate::drivers::ble::mesh::model::foundation::configuration::gatt_proxy::{
+    GattProxyMessage, CONFIG_GATT_PROXY_GET, CONFIG_GATT_PROXY_SET,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::heartbeat::{
+    HeartbeatPublicationMessage, HeartbeatSubscriptionMessage, CONFIG_HEARTBEAT_PUBLICATION_GET,
+    CONFIG_HEARTBEAT_PUBLICATION_SET, CONFIG_HEARTBEAT_PUBLICATION_STATUS,
+    CONFIG_HEARTBEAT_SUBSCRIPTION_GET, CONFIG_HEARTBEAT_SUBSCRIPTION_SET,
+    CONFIG_HEARTBEAT_SUBSCRIPTION_STATUS,
+};
+use crate::drivers::ble::mesh::model::foundation::configuration::key_refresh_phase::{
+    KeyRefreshPhaseMessage, CONFIG_KEY_REFRESH_PHASE_GET, CONFIG_KEY_REFRESH_PHASE_SET,
+    CONFIG_KEY_REFRESH_PHASE_STATUS,
+};
 use crate::drivers::ble::mesh::model::foundation::configuration::model_app::{
-    ModelAppMessage, CONFIG_MODEL_APP_BIND, CONFIG_MODEL_APP_UNBIND,
+    ModelAppMessage, CONFIG_MODEL_APP_BIND, CONFIG_MODEL_APP_STATUS, CONFIG_MODEL_APP_UNBIND,
 };
 use crate::drivers::ble::mesh::model::foundation::configuration::model_publication::{
-    ModelPublicationMessage, CONFIG_MODEL_PUBLICATION_SET,
+    ModelPublicationMessage, CONFIG_MODEL_PUBLICATION_SET, CONFIG_MODEL_PUBLICATION_STATUS,
     CONFIG_MODEL_PUBLICATION_VIRTUAL_ADDRESS_SET,
 };
+use crate::drivers::ble::mesh::model::foundation::configuration::net_key::{
+    NetKeyMessage, CONFIG_NETKEY_ADD, CONFIG_NETKEY_DELETE, CONFIG_NETKEY_GET, CONFIG_NETKEY_LIST,
+    CONFIG_NETKEY_STATUS, CONFIG_NETKEY_UPDATE,
+};
 
 use crate::drivers::ble::mesh::model::foundation::configuration::model_subscription::{
-    ModelSubscriptionMessage, CONFIG_MODEL_SUBSCRIPTION_ADD,
+    ModelSubscriptionMessage, CONFIG_MODEL_SUBSCRIPTION_ADD, CONFIG_MODEL_SUBSCRIPTION_STATUS,
     CONFIG_MODEL_SUBSCRIPTION_VIRTUAL_ADDRESS_ADD,
 };
 use crate::drivers::ble::mesh::model::foundation::configuration::node_reset::{
@@ -41,13 +64,18 @@ pub mod app_key;
 pub mod beacon;
 pub mod composition_data;
 pub mod default_ttl;
+// the states of the optional features are stored regardless of the
+// features enabled, only their messages are gated.
+pub mod friend;
+pub mod gatt_proxy;
+pub mod heartbeat;
+pub mod key_refresh_phase;
 pub mod model_app;
 pub mod model_publication;
 pub mod model_subscription;
+pub mod net_key;
 pub mod network_transmit;
 pub mod node_reset;
-
-#[cfg(feature = "ble-mesh-relay")]
 pub mod relay;
 
 pub const CONFIGURATION_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x0000);
@@ -59,12 +87,20 @@ pub enum ConfigurationMessage {
     DefaultTTL(DefaultTTLMessage),
     NodeReset(NodeResetMessage),
     CompositionData(CompositionDataMessage),
+    NetKey(NetKeyMessage),
     AppKey(AppKeyMessage),
+    KeyRefreshPhase(KeyRefreshPhaseMessage),
     ModelApp(ModelAppMessage),
     ModelPublication(ModelPublicationMessage),
     ModelSubscription(ModelSubscriptionMessage),
+    HeartbeatPublication(HeartbeatPublicationMessage),
+    HeartbeatSubscription(HeartbeatSubscriptionMessage),
     #[cfg(feature = "ble-mesh-relay")]
     Relay(RelayMessage),
+    #[cfg(feature = "ble-mesh-friend")]
+    Friend(FriendMessage),
+    #[cfg(feature = "ble-mesh-proxy")]
+    GattProxy(GattProxyMessage),
 }
 
 impl Message for ConfigurationMessage {
@@ -74,12 +110,20 @@ impl Message for ConfigurationMessage {
             ConfigurationMessage::DefaultTTL(inner) => inner.opcode(),
             ConfigurationMessage::NodeReset(inner) => inner.opcode(),
             ConfigurationMessage::CompositionData(inner) => inner.opcode(),
+            ConfigurationMessage::NetKey(inner) => inner.opcode(),
             ConfigurationMessage::AppKey(inner) => inner.opcode(),
+            ConfigurationMessage::KeyRefreshPhase(inner) => inner.opcode(),
             ConfigurationMessage::ModelApp(inner) => inner.opcode(),
             ConfigurationMessage::ModelPublication(inner) => inner.opcode(),
             ConfigurationMessage::ModelSubscription(inner) => inner.opcode(),
+            ConfigurationMessage::HeartbeatPublication(inner) => inner.opcode(),
+            ConfigurationMessage::HeartbeatSubscription(inner) => inner.opcode(),
             #[cfg(feature = "ble-mesh-relay")]
             ConfigurationMessage::Relay(inner) => inner.opcode(),
+            #[cfg(feature = "ble-mesh-friend")]
+            ConfigurationMessage::Friend(inner) => inner.opcode(),
+            #[cfg(feature = "ble-mesh-proxy")]
+            ConfigurationMessage::GattProxy(inner) => inner.opcode(),
         }
     }
 
@@ -92,12 +136,20 @@ impl Message for ConfigurationMessage {
             ConfigurationMessage::DefaultTTL(inner) => inner.emit_parameters(xmit),
             ConfigurationMessage::NodeReset(inner) => inner.emit_parameters(xmit),
             ConfigurationMessage::CompositionData(inner) => inner.emit_parameters(xmit),
+            ConfigurationMessage::NetKey(inner) => inner.emit_parameters(xmit),
             ConfigurationMessage::AppKey(inner) => inner.emit_parameters(xmit),
+            ConfigurationMessage::KeyRefreshPhase(inner) => inner.emit_parameters(xmit),
             ConfigurationMessage::ModelApp(inner) => inner.emit_parameters(xmit),
             ConfigurationMessage::ModelPublication(inner) => inner.emit_parameters(xmit),
             ConfigurationMessage::ModelSubscription(inner) => inner.emit_parameters(xmit),
+            ConfigurationMessage::HeartbeatPublication(inner) => inner.emit_parameters(xmit),
+            ConfigurationMessage::HeartbeatSubscription(inner) => inner.emit_parameters(xmit),
             #[cfg(feature = "ble-mesh-relay")]
             ConfigurationMessage::Relay(inner) => inner.emit_parameters(xmit),
+            #[cfg(feature = "ble-mesh-friend")]
+            ConfigurationMessage::Friend(inner) => inner.emit_parameters(xmit),
+            #[cfg(feature = "ble-mesh-proxy")]
+            ConfigurationMessage::GattProxy(inner) => inner.emit_parameters(xmit),
         }
     }
 }
@@ -139,13 +191,39 @@ impl Model for ConfigurationServer {
             CONFIG_COMPOSITION_DATA_GET => Ok(Some(ConfigurationMessage::CompositionData(
                 CompositionDataMessage::parse_get(parameters)?,
             ))),
+            // Net Key
+            CONFIG_NETKEY_ADD => Ok(Some(ConfigurationMessage::NetKey(
+                NetKeyMessage::parse_add(parameters)?,
+            ))),
+            CONFIG_NETKEY_DELETE => Ok(Some(ConfigurationMessage::NetKey(
+                NetKeyMessage::parse_delete(parameters)?,
+            ))),
+            CONFIG_NETKEY_GET => Ok(Some(ConfigurationMessage::NetKey(
+                NetKeyMessage::parse_get(parameters)?,
+            ))),
+            CONFIG_NETKEY_UPDATE => Ok(Some(ConfigurationMessage::NetKey(
+                NetKeyMessage::parse_update(parameters)?,
+            ))),
             // App Key
             CONFIG_APPKEY_ADD => Ok(Some(ConfigurationMessage::AppKey(
                 AppKeyMessage::parse_add(parameters)?,
             ))),
+            CONFIG_APPKEY_DELETE => Ok(Some(ConfigurationMessage::AppKey(
+                AppKeyMessage::parse_delete(parameters)?,
+            ))),
             CONFIG_APPKEY_GET => Ok(Some(ConfigurationMessage::AppKey(
                 AppKeyMessage::parse_get(parameters)?,
             ))),
+            CONFIG_APPKEY_UPDATE => Ok(Some(ConfigurationMessage::AppKey(
+                AppKeyMessage::parse_update(parameters)?,
+            ))),
+            // Key Refresh Phase
+            CONFIG_KEY_REFRESH_PHASE_GET => Ok(Some(ConfigurationMessage::KeyRefreshPhase(
+                KeyRefreshPhaseMessage::parse_get(parameters)?,
+            ))),
+            CONFIG_KEY_REFRESH_PHASE_SET => Ok(Some(ConfigurationMessage::KeyRefreshPhase(
+                KeyRefreshPhaseMessage::parse_set(parameters)?,
+            ))),
             // Model App
             CONFIG_MODEL_APP_BIND => Ok(Some(ConfigurationMessage::ModelApp(
                 ModelAppMessage::parse_bind(parameters)?,
@@ -171,6 +249,27 @@ impl Model for ConfigurationServer {
                     ModelSubscriptionMessage::parse_virtual_address_add(parameters)?,
                 )))
             }
+            // Heartbeat
+            CONFIG_HEARTBEAT_PUBLICATION_GET => {
+                Ok(Some(ConfigurationMessage::HeartbeatPublication(
+                    HeartbeatPublicationMessage::parse_get(parameters)?,
+                )))
+            }
+            CONFIG_HEARTBEAT_PUBLICATION_SET => {
+                Ok(Some(ConfigurationMessage::HeartbeatPublication(
+                    HeartbeatPublicationMessage::parse_set(parameters)?,
+                )))
+            }
+            CONFIG_HEARTBEAT_SUBSCRIPTION_GET => {
+                Ok(Some(ConfigurationMessage::HeartbeatSubscription(
+                    HeartbeatSubscriptionMessage::parse_get(parameters)?,
+                )))
+            }
+            CONFIG_HEARTBEAT_SUBSCRIPTION_SET => {
+                Ok(Some(ConfigurationMessage::HeartbeatSubscription(
+                    HeartbeatSubscriptionMessage::parse_set(parameters)?,
+                )))
+            }
             // Relay
             #[cfg(feature = "ble-mesh-relay")]
             CONFIG_RELAY_GET => Ok(Some(ConfigurationMessage::Relay(RelayMessage::parse_get(
@@ -180,6 +279,84 @@ impl Model for ConfigurationServer {
             CONFIG_RELAY_SET => Ok(Some(ConfigurationMessage::Relay(RelayMessage::parse_set(
                 parameters,
             )?))),
+            // Friend
+            #[cfg(feature = "ble-mesh-friend")]
+            CONFIG_FRIEND_GET => Ok(Some(ConfigurationMessage::Friend(
+                FriendMessage::parse_get(parameters)?,
+            ))),
+            #[cfg(feature = "ble-mesh-friend")]
+            CONFIG_FRIEND_SET => Ok(Some(ConfigurationMessage::Friend(
+                FriendMessage::parse_set(parameters)?,
+            ))),
+            // GATT Proxy
+            #[cfg(feature = "ble-mesh-proxy")]
+            CONFIG_GATT_PROXY_GET => Ok(Some(ConfigurationMessage::GattProxy(
+                GattProxyMessage::parse_get(parameters)?,
+            ))),
+            #[cfg(feature = "ble-mesh-proxy")]
+            CONFIG_GATT_PROXY_SET => Ok(Some(ConfigurationMessage::GattProxy(
+                GattProxyMessage::parse_set(parameters)?,
+            ))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The client side of the configuration model, which only ever receives the
+/// status messages sent back by a remote configuration server.
+pub struct ConfigurationClient;
+
+impl Default for ConfigurationClient {
+    fn default() -> Self {
+        Self
+    }
+}
+
+impl Model for ConfigurationClient {
+    const IDENTIFIER: ModelIdentifier = CONFIGURATION_CLIENT;
+    const SUPPORTS_SUBSCRIPTION: bool = false;
+    const SUPPORTS_PUBLICATION: bool = false;
+    type Message<'m> = ConfigurationMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            CONFIG_COMPOSITION_DATA_STATUS => Ok(Some(ConfigurationMessage::CompositionData(
+                CompositionDataMessage::parse_status(parameters)?,
+            ))),
+            CONFIG_NETKEY_STATUS => Ok(Some(ConfigurationMessage::NetKey(
+                NetKeyMessage::parse_status(parameters)?,
+            ))),
+            CONFIG_NETKEY_LIST => Ok(Some(ConfigurationMessage::NetKey(
+                NetKeyMessage::parse_list(parameters)?,
+            ))),
+            CONFIG_APPKEY_STATUS => Ok(Some(ConfigurationMessage::AppKey(
+                AppKeyMessage::parse_status(parameters)?,
+            ))),
+            CONFIG_KEY_REFRESH_PHASE_STATUS => Ok(Some(ConfigurationMessage::KeyRefreshPhase(
+                KeyRefreshPhaseMessage::parse_status(parameters)?,
+            ))),
+            CONFIG_MODEL_APP_STATUS => Ok(Some(ConfigurationMessage::ModelApp(
+                ModelAppMessage::parse_status(parameters)?,
+            ))),
+            CONFIG_MODEL_PUBLICATION_STATUS => Ok(Some(ConfigurationMessage::ModelPublication(
+                ModelPublicationMessage::parse_status(parameters)?,
+            ))),
+            CONFIG_MODEL_SUBSCRIPTION_STATUS => Ok(Some(ConfigurationMessage::ModelSubscription(
+                ModelSubscriptionMessage::parse_status(parameters)?,
+            ))),
+            CONFIG_HEARTBEAT_PUBLICATION_STATUS => {
+                Ok(Some(ConfigurationMessage::HeartbeatPublication(
+                    HeartbeatPublicationMessage::parse_status(parameters)?,
+                )))
+            }
+            CONFIG_HEARTBEAT_SUBSCRIPTION_STATUS => {
+                Ok(Some(ConfigurationMessage::HeartbeatSubscription(
+                    HeartbeatSubscriptionMessage::parse_status(parameters)?,
+                )))
+            }
             _ => Ok(None),
         }
     }
@@ -188,6 +365,8 @@ impl Model for ConfigurationServer {
 // ------------------------------------------------------------------------
 // ------------------------------------------------------------------------
 
+/// Key indexes are 12 bits wide. A single index is sent as two octets, and
+/// a pair is packed into three, the first index taking the low 12 bits.
 #[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
 pub struct KeyIndex(u16);
 
@@ -201,9 +380,7 @@ impl defmt::Format for KeyIndex {
 impl KeyIndex {
     fn parse_one(parameters: &[u8]) -> Result<Self, ParseError> {
         if parameters.len() >= 2 {
-            let byte1 = parameters[0];
-            let byte2 = parameters[1] & 0b11110000 >> 4;
-            let val = u16::from_be_bytes([byte2, byte1]);
+            let val = u16::from_le_bytes([parameters[0], parameters[1]]) & 0x0FFF;
             Ok(Self(val))
         } else {
             Err(ParseError::InvalidLength)
@@ -214,26 +391,17 @@ impl KeyIndex {
         index: &KeyIndex,
         xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
-        let bytes = index.0.to_be_bytes();
-        let byte1 = bytes[1];
-        let byte2 = bytes[0] << 4;
-        xmit.push(byte1).map_err(|_| InsufficientBuffer)?;
-        xmit.push(byte2).map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&(index.0 & 0x0FFF).to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
         Ok(())
     }
 
     fn parse_two(parameters: &[u8]) -> Result<(Self, Self), ParseError> {
         if parameters.len() >= 3 {
-            let byte1 = parameters[0];
-            let byte2 = (parameters[1] & 0b11110000) >> 4;
-
-            let index1 = u16::from_be_bytes([byte1, byte2]);
-
-            let byte1 = parameters[1] & 0b00001111;
-            let byte2 = parameters[2];
-
-            let index2 = u16::from_be_bytes([byte1, byte2]);
-            Ok((Self(index2), Self(index1)))
+            let packed = u32::from_le_bytes([parameters[0], parameters[1], parameters[2], 0]);
+            let index1 = (packed & 0x0FFF) as u16;
+            let index2 = (packed >> 12) as u16;
+            Ok((Self(index1), Self(index2)))
         } else {
             Err(ParseError::InvalidLength)
         }
@@ -243,19 +411,9 @@ impl KeyIndex {
         indexes: (&KeyIndex, &KeyIndex),
         xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
-        let bytes = indexes.1 .0.to_be_bytes();
-        let byte1 = bytes[0];
-        xmit.push(byte1).map_err(|_| InsufficientBuffer)?;
-
-        let byte2 = bytes[1] << 4;
-        let bytes = indexes.0 .0.to_be_bytes();
-        let byte1 = byte2 | bytes[0];
-
-        xmit.push(byte1).map_err(|_| InsufficientBuffer)?;
-
-        let byte2 = bytes[1];
-        xmit.push(byte2).map_err(|_| InsufficientBuffer)?;
-
+        let packed = (indexes.0 .0 as u32 & 0x0FFF) | ((indexes.1 .0 as u32 & 0x0FFF) << 12);
+        xmit.extend_from_slice(&packed.to_le_bytes()[0..3])
+            .map_err(|_| InsufficientBuffer)?;
         Ok(())
     }
 }
@@ -268,6 +426,10 @@ impl NetKeyIndex {
         Self(KeyIndex(index))
     }
 
+    pub fn value(&self) -> u16 {
+        self.0 .0
+    }
+
     fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
         KeyIndex::emit_one(&self.0, xmit)
     }
@@ -284,6 +446,14 @@ impl defmt::Format for NetKeyIndex {
 pub struct AppKeyIndex(KeyIndex);
 
 impl AppKeyIndex {
+    pub fn new(index: u16) -> Self {
+        Self(KeyIndex(index))
+    }
+
+    pub fn value(&self) -> u16 {
+        self.0 .0
+    }
+
     fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
         KeyIndex::emit_one(&self.0, xmit)
     }
@@ -301,6 +471,10 @@ impl defmt::Format for AppKeyIndex {
 pub struct NetKeyAppKeyIndexesPair(NetKeyIndex, AppKeyIndex);
 
 impl NetKeyAppKeyIndexesPair {
+    pub fn new(net_key: NetKeyIndex, app_key: AppKeyIndex) -> Self {
+        Self(net_key, app_key)
+    }
+
     fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
         KeyIndex::emit_two((&self.0 .0, &self.1 .0), xmit).map_err(|_| InsufficientBuffer)?;
         Ok(())
@@ -26,6 +26,16 @@ impl ModelAppMessage {
     pub fn parse_unbind(parameters: &[u8]) -> Result<Self, ParseError> {
         Ok(Self::Unbind(ModelAppPayload::parse(parameters)?))
     }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 7 {
+            let status = Status::parse(parameters[0])?;
+            let payload = ModelAppPayload::parse(&parameters[1..])?;
+            Ok(Self::Status(ModelAppStatusMessage { status, payload }))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
 }
 
 impl Message for ModelAppMessage {
@@ -104,3 +114,57 @@ impl ModelAppStatusMessage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drivers::ble::mesh::composition::CompanyIdentifier;
+    use crate::drivers::ble::mesh::model::foundation::configuration::{
+        ConfigurationMessage, ConfigurationServer,
+    };
+    use crate::drivers::ble::mesh::model::Model;
+
+    fn payload(model_identifier: ModelIdentifier) -> ModelAppPayload {
+        ModelAppPayload {
+            element_address: UnicastAddress::parse([0x12, 0x34]).unwrap(),
+            app_key_index: AppKeyIndex::new(0x456),
+            model_identifier,
+        }
+    }
+
+    fn round_trip(message: ModelAppMessage) -> ModelAppMessage {
+        let mut parameters: Vec<u8, 32> = Vec::new();
+        message.emit_parameters(&mut parameters).unwrap();
+        match ConfigurationServer::parse(message.opcode(), &parameters) {
+            Ok(Some(ConfigurationMessage::ModelApp(message))) => message,
+            _ => panic!("not a model app message"),
+        }
+    }
+
+    fn assert_payload(payload: &ModelAppPayload, model_identifier: ModelIdentifier) {
+        assert_eq!(
+            UnicastAddress::parse([0x12, 0x34]).unwrap(),
+            payload.element_address
+        );
+        assert_eq!(0x456, payload.app_key_index.value());
+        assert!(payload.model_identifier == model_identifier);
+    }
+
+    #[test]
+    fn bind_round_trip() {
+        let model = ModelIdentifier::SIG(0x1000);
+        match round_trip(ModelAppMessage::Bind(payload(model))) {
+            ModelAppMessage::Bind(bind) => assert_payload(&bind, model),
+            _ => panic!("not a bind"),
+        }
+    }
+
+    #[test]
+    fn unbind_round_trip() {
+        let model = ModelIdentifier::Vendor(CompanyIdentifier(0x000C), 0x0001);
+        match round_trip(ModelAppMessage::Unbind(payload(model))) {
+            ModelAppMessage::Unbind(unbind) => assert_payload(&unbind, model),
+            _ => panic!("not an unbind"),
+        }
+    }
+}
@@ -57,6 +57,12 @@ impl ModelPublicationMessage {
             ModelPublicationSetMessage::parse_virtual_address(parameters)?,
         ))
     }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        Ok(Self::Status(ModelPublicationStatusMessage::parse(
+            parameters,
+        )?))
+    }
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -82,6 +88,29 @@ pub enum PublishAddress {
     Virtual(LabelUuid),
 }
 
+impl PublishAddress {
+    /// Parse a 16-bit publish address, which is either unicast (including the
+    /// unassigned address, disabling publication) or a group address.
+    fn parse(data: [u8; 2]) -> Result<Self, ParseError> {
+        if GroupAddress::is_group_address(&data) {
+            Ok(PublishAddress::Group(GroupAddress::parse(data)?))
+        } else {
+            Ok(PublishAddress::Unicast(UnicastAddress::parse(data)?))
+        }
+    }
+
+    fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        let addr_bytes = match self {
+            PublishAddress::Unicast(addr) => addr.as_bytes(),
+            PublishAddress::Group(addr) => addr.as_bytes(),
+            PublishAddress::Virtual(addr) => addr.virtual_address().as_bytes(),
+        };
+        xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
 impl Into<Address> for PublishAddress {
     fn into(self) -> Address {
         match self {
@@ -108,18 +137,37 @@ pub struct ModelPublicationSetMessage {
 impl ModelPublicationSetMessage {
     fn emit_parameters<const N: usize>(
         &self,
-        _xmit: &mut Vec<u8, N>,
+        xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
-        todo!()
+        let addr_bytes = self.element_address.as_bytes();
+        xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
+        match &self.publish_address {
+            PublishAddress::Virtual(addr) => {
+                xmit.extend_from_slice(addr.label_uuid())
+                    .map_err(|_| InsufficientBuffer)?;
+            }
+            addr => addr.emit(xmit)?,
+        }
+        emit_publish_parameters(
+            xmit,
+            &self.app_key_index,
+            self.credential_flag,
+            self.publish_ttl,
+            self.publish_period,
+            self.publish_retransmit_count,
+            self.publish_retransmit_interval_steps,
+        )?;
+        self.model_identifier.emit(xmit)?;
+        Ok(())
     }
 
     fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
         if parameters.len() >= 11 {
             let element_address = UnicastAddress::parse([parameters[1], parameters[0]])?;
-            let publish_address =
-                PublishAddress::Unicast(UnicastAddress::parse([parameters[3], parameters[2]])?);
+            let publish_address = PublishAddress::parse([parameters[3], parameters[2]])?;
             let app_key_index = AppKeyIndex(KeyIndex::parse_one(&parameters[4..=5])?);
-            let credential_flag = (parameters[5] & 0b0001000) != 0;
+            let credential_flag = (parameters[5] & 0b00010000) != 0;
             let publish_ttl = parameters[6];
             let publish_ttl = if publish_ttl == 0xFF {
                 None
@@ -152,7 +200,7 @@ impl ModelPublicationSetMessage {
             let publish_address = PublishAddress::Virtual(LabelUuid::parse(&parameters[2..=17])?);
 
             let app_key_index = AppKeyIndex(KeyIndex::parse_one(&parameters[18..=19])?);
-            let credential_flag = (parameters[19] & 0b0001000) != 0;
+            let credential_flag = (parameters[19] & 0b00010000) != 0;
             let publish_ttl = parameters[20];
             let publish_ttl = if publish_ttl == 0xFF {
                 None
@@ -197,19 +245,53 @@ impl ModelPublicationSetMessage {
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ModelPublicationStatusMessage {
-    status: Status,
-    element_address: UnicastAddress,
-    publish_address: PublishAddress,
-    app_key_index: AppKeyIndex,
-    credential_flag: bool,
-    publish_ttl: Option<u8>,
-    publish_period: u8,
-    publish_retransmit_count: u8,
-    publish_retransmit_interval_steps: u8,
-    model_identifier: ModelIdentifier,
+    pub(crate) status: Status,
+    pub(crate) element_address: UnicastAddress,
+    pub(crate) publish_address: PublishAddress,
+    pub(crate) app_key_index: AppKeyIndex,
+    pub(crate) credential_flag: bool,
+    pub(crate) publish_ttl: Option<u8>,
+    pub(crate) publish_period: u8,
+    pub(crate) publish_retransmit_count: u8,
+    pub(crate) publish_retransmit_interval_steps: u8,
+    pub(crate) model_identifier: ModelIdentifier,
 }
 
 impl ModelPublicationStatusMessage {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 12 {
+            let status = Status::parse(parameters[0])?;
+            let element_address = UnicastAddress::parse([parameters[2], parameters[1]])?;
+            let publish_address = PublishAddress::parse([parameters[4], parameters[3]])?;
+            let app_key_index = AppKeyIndex(KeyIndex::parse_one(&parameters[5..=6])?);
+            let credential_flag = (parameters[6] & 0b00010000) != 0;
+            let publish_ttl = parameters[7];
+            let publish_ttl = if publish_ttl == 0xFF {
+                None
+            } else {
+                Some(publish_ttl)
+            };
+            let publish_period = parameters[8];
+            let publish_retransmit_count = (parameters[9] & 0b11100000) >> 5;
+            let publish_retransmit_interval_steps = parameters[9] & 0b00011111;
+            let model_identifier = ModelIdentifier::parse(&parameters[10..])?;
+            Ok(Self {
+                status,
+                element_address,
+                publish_address,
+                app_key_index,
+                credential_flag,
+                publish_ttl,
+                publish_period,
+                publish_retransmit_count,
+                publish_retransmit_interval_steps,
+                model_identifier,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
     fn emit_parameters<const N: usize>(
         &self,
         xmit: &mut Vec<u8, N>,
@@ -219,41 +301,47 @@ impl ModelPublicationStatusMessage {
         let addr_bytes = self.element_address.as_bytes();
         xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
         xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
-        match self.publish_address {
-            PublishAddress::Unicast(addr) => {
-                let addr_bytes = addr.as_bytes();
-                xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
-                xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
-            }
-            PublishAddress::Group(_addr) => {
-                todo!("group address")
-            }
-            PublishAddress::Virtual(addr) => {
-                let addr_bytes = addr.virtual_address().as_bytes();
-                xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
-                xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
-            }
-        }
-        self.app_key_index.emit(xmit)?;
-        if self.credential_flag {
-            if let Some(last) = xmit.last_mut() {
-                *last = *last | 0b00001000;
-            } else {
-                return Err(InsufficientBuffer);
-            }
-        }
-        if let Some(ttl) = self.publish_ttl {
-            xmit.push(ttl).map_err(|_| InsufficientBuffer)?;
-        } else {
-            xmit.push(0xFF).map_err(|_| InsufficientBuffer)?;
-        }
-        xmit.push(self.publish_period)
-            .map_err(|_| InsufficientBuffer)?;
-
-        let retransmit = (self.publish_retransmit_count << 5)
-            | (self.publish_retransmit_interval_steps & 0b00011111);
-        xmit.push(retransmit).map_err(|_| InsufficientBuffer)?;
+        self.publish_address.emit(xmit)?;
+        emit_publish_parameters(
+            xmit,
+            &self.app_key_index,
+            self.credential_flag,
+            self.publish_ttl,
+            self.publish_period,
+            self.publish_retransmit_count,
+            self.publish_retransmit_interval_steps,
+        )?;
         self.model_identifier.emit(xmit)?;
         Ok(())
     }
 }
+
+fn emit_publish_parameters<const N: usize>(
+    xmit: &mut Vec<u8, N>,
+    app_key_index: &AppKeyIndex,
+    credential_flag: bool,
+    publish_ttl: Option<u8>,
+    publish_period: u8,
+    publish_retransmit_count: u8,
+    publish_retransmit_interval_steps: u8,
+) -> Result<(), InsufficientBuffer> {
+    app_key_index.emit(xmit)?;
+    if credential_flag {
+        if let Some(last) = xmit.last_mut() {
+            *last = *last | 0b00010000;
+        } else {
+            return Err(InsufficientBuffer);
+        }
+    }
+    if let Some(ttl) = publish_ttl {
+        xmit.push(ttl).map_err(|_| InsufficientBuffer)?;
+    } else {
+        xmit.push(0xFF).map_err(|_| InsufficientBuffer)?;
+    }
+    xmit.push(publish_period).map_err(|_| InsufficientBuffer)?;
+
+    let retransmit =
+        (publish_retransmit_count << 5) | (publish_retransmit_interval_steps & 0b00011111);
+    xmit.push(retransmit).map_err(|_| InsufficientBuffer)?;
+    Ok(())
+}
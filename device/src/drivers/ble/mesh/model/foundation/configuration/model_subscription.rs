@@ -71,6 +71,12 @@ impl ModelSubscriptionMessage {
             ModelSubscriptionAddMessage::parse_virtual_address(parameters)?,
         ))
     }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        Ok(Self::Status(ModelSubscriptionStatusMessage::parse(
+            parameters,
+        )?))
+    }
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
@@ -81,6 +87,27 @@ pub enum SubscriptionAddress {
     Virtual(LabelUuid),
 }
 
+impl SubscriptionAddress {
+    fn parse(data: [u8; 2]) -> Result<Self, ParseError> {
+        if GroupAddress::is_group_address(&data) {
+            Ok(SubscriptionAddress::Group(GroupAddress::parse(data)?))
+        } else {
+            Ok(SubscriptionAddress::Unicast(UnicastAddress::parse(data)?))
+        }
+    }
+
+    fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        let addr_bytes = match self {
+            SubscriptionAddress::Unicast(addr) => addr.as_bytes(),
+            SubscriptionAddress::Group(addr) => addr.as_bytes(),
+            SubscriptionAddress::Virtual(addr) => addr.virtual_address().as_bytes(),
+        };
+        xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
 impl TryInto<SubscriptionAddress> for Address {
     type Error = ();
 
@@ -106,11 +133,8 @@ impl ModelSubscriptionAddMessage {
     pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
         if parameters.len() >= 6 {
             let element_address = UnicastAddress::parse([parameters[1], parameters[0]])?;
-            let subscription_address = SubscriptionAddress::Unicast(UnicastAddress::parse([
-                parameters[3],
-                parameters[2],
-            ])?);
-            let model_identifier = ModelIdentifier::parse(&parameters[9..])?;
+            let subscription_address = SubscriptionAddress::parse([parameters[3], parameters[2]])?;
+            let model_identifier = ModelIdentifier::parse(&parameters[4..])?;
             Ok(Self {
                 element_address,
                 subscription_address,
@@ -140,9 +164,20 @@ impl ModelSubscriptionAddMessage {
 
     pub fn emit_parameters<const N: usize>(
         &self,
-        _xmit: &mut Vec<u8, N>,
+        xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
-        todo!()
+        let addr_bytes = self.element_address.as_bytes();
+        xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
+        xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
+        match &self.subscription_address {
+            SubscriptionAddress::Virtual(addr) => {
+                xmit.extend_from_slice(addr.label_uuid())
+                    .map_err(|_| InsufficientBuffer)?;
+            }
+            addr => addr.emit(xmit)?,
+        }
+        self.model_identifier.emit(xmit)?;
+        Ok(())
     }
 
     pub fn create_status_response(&self, status: Status) -> ModelSubscriptionStatusMessage {
@@ -205,15 +240,31 @@ impl ModelSubscriptionOverwriteMessage {
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct ModelSubscriptionStatusMessage {
-    status: Status,
-    element_address: UnicastAddress,
-    subscription_address: SubscriptionAddress,
-    model_identifier: ModelIdentifier,
+    pub(crate) status: Status,
+    pub(crate) element_address: UnicastAddress,
+    pub(crate) subscription_address: SubscriptionAddress,
+    pub(crate) model_identifier: ModelIdentifier,
 }
 
 impl ModelSubscriptionStatusMessage {
-    pub fn parse(_parameters: &[u8]) -> Result<Self, ParseError> {
-        todo!("parse subscription status")
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 7 {
+            let status = Status::parse(parameters[0])?;
+            let element_address = UnicastAddress::parse([parameters[2], parameters[1]])?;
+            // a virtual subscription address is reported as its 16-bit hash, with
+            // no way back to the label UUID, so only unicast and group addresses
+            // can be represented.
+            let subscription_address = SubscriptionAddress::parse([parameters[4], parameters[3]])?;
+            let model_identifier = ModelIdentifier::parse(&parameters[5..])?;
+            Ok(Self {
+                status,
+                element_address,
+                subscription_address,
+                model_identifier,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
     }
 
     pub fn emit_parameters<const N: usize>(
@@ -225,21 +276,7 @@ impl ModelSubscriptionStatusMessage {
         let addr_bytes = self.element_address.as_bytes();
         xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
         xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
-        match self.subscription_address {
-            SubscriptionAddress::Unicast(addr) => {
-                let addr_bytes = addr.as_bytes();
-                xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
-                xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
-            }
-            SubscriptionAddress::Group(_addr) => {
-                todo!("group address")
-            }
-            SubscriptionAddress::Virtual(addr) => {
-                let addr_bytes = addr.virtual_address().as_bytes();
-                xmit.push(addr_bytes[1]).map_err(|_| InsufficientBuffer)?;
-                xmit.push(addr_bytes[0]).map_err(|_| InsufficientBuffer)?;
-            }
-        }
+        self.subscription_address.emit(xmit)?;
         self.model_identifier.emit(xmit)?;
         Ok(())
     }
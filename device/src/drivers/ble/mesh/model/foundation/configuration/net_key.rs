@@ -0,0 +1,224 @@
+use crate::drivers::ble::mesh::config::network::MAX_NETWORK_KEYS;
+use crate::drivers::ble::mesh::model::foundation::configuration::{KeyIndex, NetKeyIndex};
+use crate::drivers::ble::mesh::model::{Message, Status};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use core::convert::TryInto;
+use heapless::Vec;
+
+opcode!( CONFIG_NETKEY_ADD 0x80, 0x40 );
+opcode!( CONFIG_NETKEY_DELETE 0x80, 0x41 );
+opcode!( CONFIG_NETKEY_GET 0x80, 0x42 );
+opcode!( CONFIG_NETKEY_LIST 0x80, 0x43 );
+opcode!( CONFIG_NETKEY_STATUS 0x80, 0x44 );
+opcode!( CONFIG_NETKEY_UPDATE 0x80, 0x45 );
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NetKeyMessage {
+    Add(NetKeyAddMessage),
+    Delete(NetKeyDeleteMessage),
+    Get,
+    List(NetKeyListMessage),
+    Status(NetKeyStatusMessage),
+    Update(NetKeyUpdateMessage),
+}
+
+impl NetKeyMessage {
+    pub fn parse_add(parameters: &[u8]) -> Result<Self, ParseError> {
+        let (net_key_index, net_key) = Self::parse_index_and_key(parameters)?;
+        Ok(Self::Add(NetKeyAddMessage {
+            net_key_index,
+            net_key,
+        }))
+    }
+
+    pub fn parse_update(parameters: &[u8]) -> Result<Self, ParseError> {
+        let (net_key_index, net_key) = Self::parse_index_and_key(parameters)?;
+        Ok(Self::Update(NetKeyUpdateMessage {
+            net_key_index,
+            net_key,
+        }))
+    }
+
+    pub fn parse_delete(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 2 {
+            let net_key_index = NetKeyIndex(KeyIndex::parse_one(parameters)?);
+            Ok(Self::Delete(NetKeyDeleteMessage { net_key_index }))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_get(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.is_empty() {
+            Ok(Self::Get)
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_status(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 3 {
+            let status = Status::parse(parameters[0])?;
+            let net_key_index = NetKeyIndex(KeyIndex::parse_one(&parameters[1..])?);
+            Ok(Self::Status(NetKeyStatusMessage {
+                status,
+                net_key_index,
+            }))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    pub fn parse_list(parameters: &[u8]) -> Result<Self, ParseError> {
+        let mut net_key_indexes = Vec::new();
+        let mut remaining = parameters;
+        while !remaining.is_empty() {
+            if remaining.len() >= 3 {
+                let (first, second) = KeyIndex::parse_two(remaining)?;
+                net_key_indexes
+                    .push(NetKeyIndex(first))
+                    .map_err(|_| ParseError::InsufficientBuffer)?;
+                net_key_indexes
+                    .push(NetKeyIndex(second))
+                    .map_err(|_| ParseError::InsufficientBuffer)?;
+                remaining = &remaining[3..];
+            } else {
+                net_key_indexes
+                    .push(NetKeyIndex(KeyIndex::parse_one(remaining)?))
+                    .map_err(|_| ParseError::InsufficientBuffer)?;
+                remaining = &remaining[2..];
+            }
+        }
+        Ok(Self::List(NetKeyListMessage { net_key_indexes }))
+    }
+
+    fn parse_index_and_key(parameters: &[u8]) -> Result<(NetKeyIndex, [u8; 16]), ParseError> {
+        if parameters.len() == 18 {
+            let net_key_index = NetKeyIndex(KeyIndex::parse_one(parameters)?);
+            let net_key = parameters[2..]
+                .try_into()
+                .map_err(|_| ParseError::InvalidLength)?;
+            Ok((net_key_index, net_key))
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+}
+
+impl Message for NetKeyMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Add(_) => CONFIG_NETKEY_ADD,
+            Self::Delete(_) => CONFIG_NETKEY_DELETE,
+            Self::Get => CONFIG_NETKEY_GET,
+            Self::List(_) => CONFIG_NETKEY_LIST,
+            Self::Status(_) => CONFIG_NETKEY_STATUS,
+            Self::Update(_) => CONFIG_NETKEY_UPDATE,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            NetKeyMessage::Add(inner) => inner.emit_parameters(xmit),
+            NetKeyMessage::Delete(inner) => inner.emit_parameters(xmit),
+            NetKeyMessage::Get => Ok(()),
+            NetKeyMessage::List(inner) => inner.emit_parameters(xmit),
+            NetKeyMessage::Status(inner) => inner.emit_parameters(xmit),
+            NetKeyMessage::Update(inner) => inner.emit_parameters(xmit),
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NetKeyAddMessage {
+    pub(crate) net_key_index: NetKeyIndex,
+    pub(crate) net_key: [u8; 16],
+}
+
+impl NetKeyAddMessage {
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        self.net_key_index.emit(xmit)?;
+        xmit.extend_from_slice(&self.net_key)
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NetKeyDeleteMessage {
+    pub(crate) net_key_index: NetKeyIndex,
+}
+
+impl NetKeyDeleteMessage {
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        self.net_key_index.emit(xmit)
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NetKeyListMessage {
+    pub(crate) net_key_indexes: Vec<NetKeyIndex, MAX_NETWORK_KEYS>,
+}
+
+impl NetKeyListMessage {
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        for chunk in self.net_key_indexes.chunks(2) {
+            if chunk.len() == 2 {
+                KeyIndex::emit_two((&chunk[0].0, &chunk[1].0), xmit)?;
+            } else {
+                KeyIndex::emit_one(&chunk[0].0, xmit)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NetKeyStatusMessage {
+    pub(crate) status: Status,
+    pub(crate) net_key_index: NetKeyIndex,
+}
+
+impl NetKeyStatusMessage {
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.status as u8)
+            .map_err(|_| InsufficientBuffer)?;
+        self.net_key_index.emit(xmit)
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NetKeyUpdateMessage {
+    pub(crate) net_key_index: NetKeyIndex,
+    pub(crate) net_key: [u8; 16],
+}
+
+impl NetKeyUpdateMessage {
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        self.net_key_index.emit(xmit)?;
+        xmit.extend_from_slice(&self.net_key)
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use crate::drivers::ble::mesh::composition::CompanyIdentifier;
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use heapless::Vec;
+
+/// Maximum number of faults reported in a fault array.
+pub const MAX_FAULTS: usize = 8;
+
+/// Largest fast period divisor, publishing current status 2^15 times faster.
+pub const MAX_FAST_PERIOD_DIVISOR: u8 = 15;
+
+#[derive(Clone, Debug)]
+pub struct HealthServer;
+
+#[derive(Clone, Debug)]
+pub struct HealthClient;
+
+pub const HEALTH_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x0002);
+pub const HEALTH_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x0003);
+
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HealthMessage {
+    CurrentStatus(FaultStatus),
+    FaultStatus(FaultStatus),
+    FaultGet(CompanyIdentifier),
+    FaultClear(CompanyIdentifier),
+    FaultClearUnacknowledged(CompanyIdentifier),
+    FaultTest(FaultTest),
+    FaultTestUnacknowledged(FaultTest),
+    PeriodGet,
+    PeriodSet(u8),
+    PeriodSetUnacknowledged(u8),
+    PeriodStatus(u8),
+    AttentionGet,
+    AttentionSet(u8),
+    AttentionSetUnacknowledged(u8),
+    AttentionStatus(u8),
+}
+
+impl Message for HealthMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::CurrentStatus(_) => HEALTH_CURRENT_STATUS,
+            Self::FaultStatus(_) => HEALTH_FAULT_STATUS,
+            Self::FaultGet(_) => HEALTH_FAULT_GET,
+            Self::FaultClear(_) => HEALTH_FAULT_CLEAR,
+            Self::FaultClearUnacknowledged(_) => HEALTH_FAULT_CLEAR_UNACKNOWLEDGED,
+            Self::FaultTest(_) => HEALTH_FAULT_TEST,
+            Self::FaultTestUnacknowledged(_) => HEALTH_FAULT_TEST_UNACKNOWLEDGED,
+            Self::PeriodGet => HEALTH_PERIOD_GET,
+            Self::PeriodSet(_) => HEALTH_PERIOD_SET,
+            Self::PeriodSetUnacknowledged(_) => HEALTH_PERIOD_SET_UNACKNOWLEDGED,
+            Self::PeriodStatus(_) => HEALTH_PERIOD_STATUS,
+            Self::AttentionGet => HEALTH_ATTENTION_GET,
+            Self::AttentionSet(_) => HEALTH_ATTENTION_SET,
+            Self::AttentionSetUnacknowledged(_) => HEALTH_ATTENTION_SET_UNACKNOWLEDGED,
+            Self::AttentionStatus(_) => HEALTH_ATTENTION_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::CurrentStatus(inner) | Self::FaultStatus(inner) => inner.emit_parameters(xmit),
+            Self::FaultGet(company_id)
+            | Self::FaultClear(company_id)
+            | Self::FaultClearUnacknowledged(company_id) => xmit
+                .extend_from_slice(&company_id.0.to_le_bytes())
+                .map_err(|_| InsufficientBuffer),
+            Self::FaultTest(inner) | Self::FaultTestUnacknowledged(inner) => {
+                inner.emit_parameters(xmit)
+            }
+            Self::PeriodGet | Self::AttentionGet => Ok(()),
+            Self::PeriodSet(val)
+            | Self::PeriodSetUnacknowledged(val)
+            | Self::PeriodStatus(val)
+            | Self::AttentionSet(val)
+            | Self::AttentionSetUnacknowledged(val)
+            | Self::AttentionStatus(val) => xmit.push(*val).map_err(|_| InsufficientBuffer),
+        }
+    }
+}
+
+impl Model for HealthServer {
+    const IDENTIFIER: ModelIdentifier = HEALTH_SERVER;
+    type Message<'m> = HealthMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            HEALTH_FAULT_GET => Ok(Some(HealthMessage::FaultGet(CompanyIdentifier::parse(
+                parameters,
+            )?))),
+            HEALTH_FAULT_CLEAR => Ok(Some(HealthMessage::FaultClear(CompanyIdentifier::parse(
+                parameters,
+            )?))),
+            HEALTH_FAULT_CLEAR_UNACKNOWLEDGED => Ok(Some(HealthMessage::FaultClearUnacknowledged(
+                CompanyIdentifier::parse(parameters)?,
+            ))),
+            HEALTH_FAULT_TEST => Ok(Some(HealthMessage::FaultTest(FaultTest::parse(
+                parameters,
+            )?))),
+            HEALTH_FAULT_TEST_UNACKNOWLEDGED => Ok(Some(HealthMessage::FaultTestUnacknowledged(
+                FaultTest::parse(parameters)?,
+            ))),
+            HEALTH_PERIOD_GET => Ok(Some(HealthMessage::PeriodGet)),
+            HEALTH_PERIOD_SET => Ok(Some(HealthMessage::PeriodSet(parse_fast_period_divisor(
+                parameters,
+            )?))),
+            HEALTH_PERIOD_SET_UNACKNOWLEDGED => Ok(Some(HealthMessage::PeriodSetUnacknowledged(
+                parse_fast_period_divisor(parameters)?,
+            ))),
+            HEALTH_ATTENTION_GET => Ok(Some(HealthMessage::AttentionGet)),
+            HEALTH_ATTENTION_SET => Ok(Some(HealthMessage::AttentionSet(parse_u8(parameters)?))),
+            HEALTH_ATTENTION_SET_UNACKNOWLEDGED => Ok(Some(
+                HealthMessage::AttentionSetUnacknowledged(parse_u8(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for HealthClient {
+    const IDENTIFIER: ModelIdentifier = HEALTH_CLIENT;
+    type Message<'m> = HealthMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            HEALTH_CURRENT_STATUS => Ok(Some(HealthMessage::CurrentStatus(FaultStatus::parse(
+                parameters,
+            )?))),
+            HEALTH_FAULT_STATUS => Ok(Some(HealthMessage::FaultStatus(FaultStatus::parse(
+                parameters,
+            )?))),
+            HEALTH_PERIOD_STATUS => Ok(Some(HealthMessage::PeriodStatus(parse_u8(parameters)?))),
+            HEALTH_ATTENTION_STATUS => {
+                Ok(Some(HealthMessage::AttentionStatus(parse_u8(parameters)?)))
+            }
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn parse_u8(parameters: &[u8]) -> Result<u8, ParseError> {
+    if parameters.len() == 1 {
+        Ok(parameters[0])
+    } else {
+        Err(ParseError::InvalidLength)
+    }
+}
+
+fn parse_fast_period_divisor(parameters: &[u8]) -> Result<u8, ParseError> {
+    let divisor = parse_u8(parameters)?;
+    if divisor > MAX_FAST_PERIOD_DIVISOR {
+        Err(ParseError::InvalidValue)
+    } else {
+        Ok(divisor)
+    }
+}
+
+opcode!( HEALTH_CURRENT_STATUS 0x04 );
+opcode!( HEALTH_FAULT_STATUS 0x05 );
+opcode!( HEALTH_ATTENTION_GET 0x80, 0x04 );
+opcode!( HEALTH_ATTENTION_SET 0x80, 0x05 );
+opcode!( HEALTH_ATTENTION_SET_UNACKNOWLEDGED 0x80, 0x06 );
+opcode!( HEALTH_ATTENTION_STATUS 0x80, 0x07 );
+opcode!( HEALTH_FAULT_CLEAR 0x80, 0x2F );
+opcode!( HEALTH_FAULT_CLEAR_UNACKNOWLEDGED 0x80, 0x30 );
+opcode!( HEALTH_FAULT_GET 0x80, 0x31 );
+opcode!( HEALTH_FAULT_TEST 0x80, 0x32 );
+opcode!( HEALTH_FAULT_TEST_UNACKNOWLEDGED 0x80, 0x33 );
+opcode!( HEALTH_PERIOD_GET 0x80, 0x34 );
+opcode!( HEALTH_PERIOD_SET 0x80, 0x35 );
+opcode!( HEALTH_PERIOD_SET_UNACKNOWLEDGED 0x80, 0x36 );
+opcode!( HEALTH_PERIOD_STATUS 0x80, 0x37 );
+
+/// Run the self-test identified by `test_id` of a company.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultTest {
+    pub test_id: u8,
+    pub company_id: CompanyIdentifier,
+}
+
+impl FaultTest {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 3 {
+            Ok(Self {
+                test_id: parameters[0],
+                company_id: CompanyIdentifier::parse(&parameters[1..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.test_id).map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.company_id.0.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+/// The current faults, or those registered since last cleared, following
+/// the most recent self-test.
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FaultStatus {
+    pub test_id: u8,
+    pub company_id: CompanyIdentifier,
+    pub faults: Vec<u8, MAX_FAULTS>,
+}
+
+impl FaultStatus {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 3 {
+            Ok(Self {
+                test_id: parameters[0],
+                company_id: CompanyIdentifier::parse(&parameters[1..])?,
+                faults: Vec::from_slice(&parameters[3..])
+                    .map_err(|_| ParseError::InsufficientBuffer)?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.test_id).map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.company_id.0.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.faults)
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+//! Battery whose readings are set from the host, for exercising the battery
+//! monitor without hardware.
+
+use super::monitor::{BatteryReading, BatterySource};
+use super::GenericBatteryFlagsCharging;
+use core::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// Clones share the same battery, so a test can keep a handle to change the
+/// readings of the source given to the monitor.
+#[derive(Clone)]
+pub struct FakeBattery {
+    reading: Arc<Mutex<BatteryReading>>,
+}
+
+impl FakeBattery {
+    pub fn new(millivolts: u16, charging: GenericBatteryFlagsCharging) -> Self {
+        Self {
+            reading: Arc::new(Mutex::new(BatteryReading {
+                millivolts,
+                charging,
+            })),
+        }
+    }
+
+    pub fn set(&self, millivolts: u16, charging: GenericBatteryFlagsCharging) {
+        *self.reading.lock().unwrap() = BatteryReading {
+            millivolts,
+            charging,
+        };
+    }
+}
+
+impl BatterySource for FakeBattery {
+    type ReadFuture<'m> = impl Future<Output = Result<BatteryReading, ()>> + 'm
+    where
+        Self: 'm;
+
+    fn read<'m>(&'m mut self) -> Self::ReadFuture<'m> {
+        async move { Ok(*self.reading.lock().unwrap()) }
+    }
+}
@@ -5,6 +5,10 @@ use crate::drivers::ble::mesh::InsufficientBuffer;
 use crate::opcode;
 use heapless::Vec;
 
+#[cfg(feature = "std")]
+pub mod fake;
+pub mod monitor;
+
 pub struct GenericBatteryServer;
 
 pub const GENERIC_BATTERY_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x100C);
@@ -53,6 +57,13 @@ impl Model for GenericBatteryServer {
 opcode!( GENERIC_BATTERY_GET 0x82, 0x23 );
 opcode!( GENERIC_BATTERY_STATUS 0x82, 0x24 );
 
+/// Battery level of a battery of unknown level.
+pub const BATTERY_LEVEL_UNKNOWN: u8 = 0xFF;
+
+/// Time to discharge or charge of a battery, in minutes, when unknown.
+pub const BATTERY_TIME_UNKNOWN: u32 = 0xFF_FFFF;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct GenericBatteryFlags {
     pub presence: GenericBatteryFlagsPresence,
@@ -92,6 +103,7 @@ impl GenericBatteryFlags {
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum GenericBatteryFlagsPresence {
     NotPresent,
@@ -100,6 +112,7 @@ pub enum GenericBatteryFlagsPresence {
     Unknown,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum GenericBatteryFlagsIndicator {
     LowCritical,
@@ -108,6 +121,7 @@ pub enum GenericBatteryFlagsIndicator {
     Unknown,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum GenericBatteryFlagsCharging {
     NotChargeable,
@@ -116,6 +130,7 @@ pub enum GenericBatteryFlagsCharging {
     Unknown,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Status {
     battery_level: u8,
@@ -139,15 +154,31 @@ impl Status {
         }
     }
 
+    pub fn battery_level(&self) -> u8 {
+        self.battery_level
+    }
+
+    pub fn time_to_discharge(&self) -> u32 {
+        self.time_to_discharge
+    }
+
+    pub fn time_to_charge(&self) -> u32 {
+        self.time_to_charge
+    }
+
+    pub fn flags(&self) -> GenericBatteryFlags {
+        self.flags
+    }
+
     fn emit_parameters<const N: usize>(
         &self,
         xmit: &mut Vec<u8, N>,
     ) -> Result<(), InsufficientBuffer> {
         xmit.push(self.battery_level)
             .map_err(|_| InsufficientBuffer)?;
-        xmit.extend_from_slice(&self.time_to_discharge.to_be_bytes()[1..])
+        xmit.extend_from_slice(&self.time_to_discharge.to_le_bytes()[..3])
             .map_err(|_| InsufficientBuffer)?;
-        xmit.extend_from_slice(&self.time_to_charge.to_be_bytes()[1..])
+        xmit.extend_from_slice(&self.time_to_charge.to_le_bytes()[..3])
             .map_err(|_| InsufficientBuffer)?;
         self.flags.emit_parameters(xmit)?;
         Ok(())
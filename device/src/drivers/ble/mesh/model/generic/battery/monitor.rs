@@ -0,0 +1,338 @@
+//! Battery monitor backing the generic battery server.
+//!
+//! The monitor samples the voltage of a [`BatterySource`], converts it to a
+//! battery level along a [`DischargeCurve`], and estimates the time left to
+//! discharge or charge from the rate at which the level has been changing.
+
+use super::{
+    GenericBatteryFlags, GenericBatteryFlagsCharging, GenericBatteryFlagsIndicator,
+    GenericBatteryFlagsPresence, GenericBatteryMessage, Status, BATTERY_LEVEL_UNKNOWN,
+    BATTERY_TIME_UNKNOWN,
+};
+use core::future::Future;
+use embassy::time::Instant;
+
+/// A single measurement of a battery.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BatteryReading {
+    pub millivolts: u16,
+    pub charging: GenericBatteryFlagsCharging,
+}
+
+/// Source of the voltage of a battery, typically an ADC channel scaled to
+/// millivolts.
+pub trait BatterySource {
+    type ReadFuture<'m>: Future<Output = Result<BatteryReading, ()>>
+    where
+        Self: 'm;
+
+    fn read<'m>(&'m mut self) -> Self::ReadFuture<'m>;
+}
+
+/// Discharge curve of a battery, as points of voltage in millivolts and
+/// battery level in percent, from full to empty. Levels between two points
+/// are interpolated linearly. A curve that is not descending is not rejected,
+/// though its levels are only meaningful along descending points.
+#[derive(Copy, Clone, Debug)]
+pub struct DischargeCurve(pub &'static [(u16, u8)]);
+
+impl DischargeCurve {
+    /// A single lithium-ion or lithium-polymer cell.
+    pub const LITHIUM_ION: Self = Self(&[
+        (4200, 100),
+        (4100, 90),
+        (4000, 80),
+        (3900, 65),
+        (3800, 50),
+        (3700, 30),
+        (3600, 15),
+        (3500, 5),
+        (3300, 0),
+    ]);
+
+    /// A lithium coin cell, such as a CR2032.
+    pub const COIN_CELL: Self = Self(&[
+        (3000, 100),
+        (2900, 80),
+        (2800, 60),
+        (2700, 40),
+        (2600, 20),
+        (2500, 10),
+        (2000, 0),
+    ]);
+
+    /// Battery level in percent at `millivolts`.
+    pub fn level(&self, millivolts: u16) -> u8 {
+        let (full, empty) = match (self.0.first(), self.0.last()) {
+            (Some(full), Some(empty)) => (full, empty),
+            _ => return BATTERY_LEVEL_UNKNOWN,
+        };
+        if millivolts >= full.0 {
+            return full.1;
+        }
+        for pair in self.0.windows(2) {
+            let (high, low) = (pair[0], pair[1]);
+            if millivolts >= low.0 {
+                // points out of order count as a step to the lower level.
+                let span = high.0.saturating_sub(low.0) as i32;
+                if span == 0 {
+                    return low.1;
+                }
+                let above = (millivolts - low.0) as i32;
+                let level = low.1 as i32 + above * (high.1 as i32 - low.1 as i32) / span;
+                return level.max(0).min(u8::MAX as i32) as u8;
+            }
+        }
+        empty.1
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BatteryConfig {
+    pub curve: DischargeCurve,
+    pub presence: GenericBatteryFlagsPresence,
+    /// Battery level in percent at or below which the battery is low.
+    pub low: u8,
+    /// Battery level in percent at or below which the battery is critically low.
+    pub critical: u8,
+    /// Change of the battery level in percent worth publishing.
+    pub significant_change: u8,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            curve: DischargeCurve::LITHIUM_ION,
+            presence: GenericBatteryFlagsPresence::PresentRemovable,
+            low: 20,
+            critical: 5,
+            significant_change: 5,
+        }
+    }
+}
+
+pub struct BatteryMonitor<B>
+where
+    B: BatterySource,
+{
+    source: B,
+    config: BatteryConfig,
+    status: Option<Status>,
+    published: Option<Status>,
+    // start of the current discharge or charge, to estimate its rate.
+    reference: Option<(Instant, u8, GenericBatteryFlagsCharging)>,
+}
+
+impl<B> BatteryMonitor<B>
+where
+    B: BatterySource,
+{
+    pub fn new(source: B, config: BatteryConfig) -> Self {
+        Self {
+            source,
+            config,
+            status: None,
+            published: None,
+            reference: None,
+        }
+    }
+
+    /// The status of the battery as last sampled, unknown until sampled.
+    pub fn status(&self) -> Status {
+        self.status.unwrap_or_else(|| {
+            Status::new(
+                BATTERY_LEVEL_UNKNOWN,
+                BATTERY_TIME_UNKNOWN,
+                BATTERY_TIME_UNKNOWN,
+                GenericBatteryFlags {
+                    presence: self.config.presence,
+                    indicator: GenericBatteryFlagsIndicator::Unknown,
+                    charging: GenericBatteryFlagsCharging::Unknown,
+                },
+            )
+        })
+    }
+
+    /// Sample the battery, returning whether its status changed significantly
+    /// since last published, in which case it is to be published now.
+    pub async fn sample(&mut self) -> Result<bool, ()> {
+        let reading = self.source.read().await?;
+        Ok(self.update(reading, Instant::now()))
+    }
+
+    /// Answer a generic battery get with the status as last sampled.
+    pub fn process(&self, message: &GenericBatteryMessage) -> Option<GenericBatteryMessage> {
+        match message {
+            GenericBatteryMessage::Get => Some(GenericBatteryMessage::Status(self.status())),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, reading: BatteryReading, now: Instant) -> bool {
+        let level = self.config.curve.level(reading.millivolts);
+        let charging = reading.charging == GenericBatteryFlagsCharging::ChargeableCharging;
+
+        // restart the estimate whenever charging starts or stops, or the
+        // level moves against the direction of the charge.
+        let restart = match self.reference {
+            Some((_, reference, state)) => {
+                state != reading.charging
+                    || (charging && level < reference)
+                    || (!charging && level > reference)
+            }
+            None => true,
+        };
+        if restart {
+            self.reference.replace((now, level, reading.charging));
+        }
+
+        let mut time_to_discharge = BATTERY_TIME_UNKNOWN;
+        let mut time_to_charge = BATTERY_TIME_UNKNOWN;
+        if let Some((at, reference, _)) = self.reference {
+            let minutes = now.duration_since(at).as_secs() / 60;
+            let change = (level as i32 - reference as i32).unsigned_abs() as u64;
+            if level != BATTERY_LEVEL_UNKNOWN && minutes > 0 && change > 0 {
+                let remaining = if charging {
+                    100 - level.min(100)
+                } else {
+                    level
+                };
+                let estimate = (remaining as u64 * minutes / change)
+                    .min(BATTERY_TIME_UNKNOWN as u64 - 1) as u32;
+                if charging {
+                    time_to_charge = estimate;
+                } else {
+                    time_to_discharge = estimate;
+                }
+            }
+        }
+
+        let indicator = if level == BATTERY_LEVEL_UNKNOWN {
+            GenericBatteryFlagsIndicator::Unknown
+        } else if level <= self.config.critical {
+            GenericBatteryFlagsIndicator::LowCritical
+        } else if level <= self.config.low {
+            GenericBatteryFlagsIndicator::Low
+        } else {
+            GenericBatteryFlagsIndicator::Good
+        };
+
+        let status = Status::new(
+            level,
+            time_to_discharge,
+            time_to_charge,
+            GenericBatteryFlags {
+                presence: self.config.presence,
+                indicator,
+                charging: reading.charging,
+            },
+        );
+        self.status.replace(status);
+
+        let significant = match self.published {
+            Some(published) => {
+                published.flags() != status.flags()
+                    || (published.battery_level() as i16 - level as i16).unsigned_abs()
+                        >= self.config.significant_change as u16
+            }
+            None => true,
+        };
+        if significant {
+            self.published.replace(status);
+        }
+        significant
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake::FakeBattery;
+    use super::*;
+    use embassy::time::Duration;
+    use futures::executor::block_on;
+
+    #[test]
+    fn discharge_curve() {
+        let curve = DischargeCurve::LITHIUM_ION;
+        assert_eq!(100, curve.level(4300));
+        assert_eq!(100, curve.level(4200));
+        assert_eq!(95, curve.level(4150));
+        assert_eq!(50, curve.level(3800));
+        assert_eq!(0, curve.level(3000));
+        assert_eq!(BATTERY_LEVEL_UNKNOWN, DischargeCurve(&[]).level(3000));
+    }
+
+    #[test]
+    fn non_monotonic_curve() {
+        // a level rising as the voltage drops.
+        let curve = DischargeCurve(&[(4000, 50), (3800, 80), (3600, 0)]);
+        assert_eq!(65, curve.level(3900));
+        assert_eq!(40, curve.level(3700));
+
+        // a voltage rising towards empty.
+        let curve = DischargeCurve(&[(3800, 100), (4000, 50), (3600, 0)]);
+        assert_eq!(100, curve.level(3900));
+        assert_eq!(12, curve.level(3700));
+        assert_eq!(0, curve.level(3500));
+    }
+
+    #[test]
+    fn significant_change() {
+        let battery = FakeBattery::new(4200, GenericBatteryFlagsCharging::ChargeableNotCharging);
+        let mut monitor = BatteryMonitor::new(battery.clone(), BatteryConfig::default());
+        assert_eq!(BATTERY_LEVEL_UNKNOWN, monitor.status().battery_level());
+
+        assert!(block_on(monitor.sample()).unwrap());
+        assert_eq!(100, monitor.status().battery_level());
+
+        // 98 percent is not worth publishing.
+        battery.set(4180, GenericBatteryFlagsCharging::ChargeableNotCharging);
+        assert!(!block_on(monitor.sample()).unwrap());
+        assert_eq!(98, monitor.status().battery_level());
+
+        battery.set(4150, GenericBatteryFlagsCharging::ChargeableNotCharging);
+        assert!(block_on(monitor.sample()).unwrap());
+
+        // plugging in the charger changes the flags.
+        battery.set(4150, GenericBatteryFlagsCharging::ChargeableCharging);
+        assert!(block_on(monitor.sample()).unwrap());
+
+        battery.set(3550, GenericBatteryFlagsCharging::ChargeableNotCharging);
+        assert!(block_on(monitor.sample()).unwrap());
+        assert_eq!(
+            GenericBatteryFlagsIndicator::Low,
+            monitor.status().flags().indicator
+        );
+    }
+
+    #[test]
+    fn time_to_discharge() {
+        let battery = FakeBattery::new(4200, GenericBatteryFlagsCharging::ChargeableNotCharging);
+        let mut monitor = BatteryMonitor::new(battery, BatteryConfig::default());
+        let start = Instant::from_secs(0);
+        let reading = |millivolts| BatteryReading {
+            millivolts,
+            charging: GenericBatteryFlagsCharging::ChargeableNotCharging,
+        };
+
+        monitor.update(reading(4200), start);
+        assert_eq!(BATTERY_TIME_UNKNOWN, monitor.status().time_to_discharge());
+
+        // 10 percent in 60 minutes leaves 9 hours at 90 percent.
+        monitor.update(reading(4100), start + Duration::from_secs(3600));
+        assert_eq!(540, monitor.status().time_to_discharge());
+        assert_eq!(BATTERY_TIME_UNKNOWN, monitor.status().time_to_charge());
+
+        // charging restarts the estimate.
+        monitor.update(
+            BatteryReading {
+                millivolts: 4100,
+                charging: GenericBatteryFlagsCharging::ChargeableCharging,
+            },
+            start + Duration::from_secs(7200),
+        );
+        assert_eq!(BATTERY_TIME_UNKNOWN, monitor.status().time_to_discharge());
+        assert_eq!(BATTERY_TIME_UNKNOWN, monitor.status().time_to_charge());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::drivers::ble::mesh::model::generic::transition::TransitionTime;
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+
+#[derive(Clone, Debug)]
+pub struct GenericDefaultTransitionTimeServer;
+
+#[derive(Clone, Debug)]
+pub struct GenericDefaultTransitionTimeClient;
+
+pub const GENERIC_DEFAULT_TRANSITION_TIME_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1004);
+pub const GENERIC_DEFAULT_TRANSITION_TIME_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1005);
+
+/// The transition time used by the other models of the element for set
+/// messages carrying no transition time of their own.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GenericDefaultTransitionTimeMessage {
+    Get,
+    Set(TransitionTime),
+    SetUnacknowledged(TransitionTime),
+    Status(TransitionTime),
+}
+
+impl Message for GenericDefaultTransitionTimeMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => GENERIC_DEFAULT_TRANSITION_TIME_GET,
+            Self::Set(_) => GENERIC_DEFAULT_TRANSITION_TIME_SET,
+            Self::SetUnacknowledged(_) => GENERIC_DEFAULT_TRANSITION_TIME_SET_UNACKNOWLEDGED,
+            Self::Status(_) => GENERIC_DEFAULT_TRANSITION_TIME_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut heapless::Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get => Ok(()),
+            Self::Set(inner) | Self::SetUnacknowledged(inner) | Self::Status(inner) => {
+                xmit.push(inner.0).map_err(|_| InsufficientBuffer)
+            }
+        }
+    }
+}
+
+impl Model for GenericDefaultTransitionTimeServer {
+    const IDENTIFIER: ModelIdentifier = GENERIC_DEFAULT_TRANSITION_TIME_SERVER;
+    type Message<'m> = GenericDefaultTransitionTimeMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            GENERIC_DEFAULT_TRANSITION_TIME_GET => {
+                Ok(Some(GenericDefaultTransitionTimeMessage::Get))
+            }
+            GENERIC_DEFAULT_TRANSITION_TIME_SET => Ok(Some(
+                GenericDefaultTransitionTimeMessage::Set(parse_transition_time(parameters)?),
+            )),
+            GENERIC_DEFAULT_TRANSITION_TIME_SET_UNACKNOWLEDGED => Ok(Some(
+                GenericDefaultTransitionTimeMessage::SetUnacknowledged(parse_transition_time(
+                    parameters,
+                )?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for GenericDefaultTransitionTimeClient {
+    const IDENTIFIER: ModelIdentifier = GENERIC_DEFAULT_TRANSITION_TIME_CLIENT;
+    type Message<'m> = GenericDefaultTransitionTimeMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            GENERIC_DEFAULT_TRANSITION_TIME_STATUS => Ok(Some(
+                GenericDefaultTransitionTimeMessage::Status(parse_transition_time(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn parse_transition_time(parameters: &[u8]) -> Result<TransitionTime, ParseError> {
+    if parameters.len() == 1 {
+        let transition_time = TransitionTime(parameters[0]);
+        if transition_time.is_unknown() {
+            // prohibited as a default transition time.
+            Err(ParseError::InvalidValue)
+        } else {
+            Ok(transition_time)
+        }
+    } else {
+        Err(ParseError::InvalidLength)
+    }
+}
+
+opcode!( GENERIC_DEFAULT_TRANSITION_TIME_GET 0x82, 0x0D );
+opcode!( GENERIC_DEFAULT_TRANSITION_TIME_SET 0x82, 0x0E );
+opcode!( GENERIC_DEFAULT_TRANSITION_TIME_SET_UNACKNOWLEDGED 0x82, 0x0F );
+opcode!( GENERIC_DEFAULT_TRANSITION_TIME_STATUS 0x82, 0x10 );
@@ -0,0 +1,421 @@
+use crate::drivers::ble::mesh::model::generic::transition::{
+    Transition, TransitionTime, Transitioning,
+};
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use embassy::time::Instant;
+use heapless::Vec;
+
+#[derive(Clone, Debug)]
+pub struct GenericLevelServer;
+
+#[derive(Clone, Debug)]
+pub struct GenericLevelClient;
+
+pub const GENERIC_LEVEL_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1002);
+pub const GENERIC_LEVEL_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1003);
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GenericLevelMessage {
+    Get,
+    Set(Set),
+    SetUnacknowledged(Set),
+    DeltaSet(DeltaSet),
+    DeltaSetUnacknowledged(DeltaSet),
+    MoveSet(MoveSet),
+    MoveSetUnacknowledged(MoveSet),
+    Status(Status),
+}
+
+impl Message for GenericLevelMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => GENERIC_LEVEL_GET,
+            Self::Set(_) => GENERIC_LEVEL_SET,
+            Self::SetUnacknowledged(_) => GENERIC_LEVEL_SET_UNACKNOWLEDGED,
+            Self::DeltaSet(_) => GENERIC_DELTA_SET,
+            Self::DeltaSetUnacknowledged(_) => GENERIC_DELTA_SET_UNACKNOWLEDGED,
+            Self::MoveSet(_) => GENERIC_MOVE_SET,
+            Self::MoveSetUnacknowledged(_) => GENERIC_MOVE_SET_UNACKNOWLEDGED,
+            Self::Status(_) => GENERIC_LEVEL_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut heapless::Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get => Ok(()),
+            Self::Set(inner) => inner.emit_parameters(xmit),
+            Self::SetUnacknowledged(inner) => inner.emit_parameters(xmit),
+            Self::DeltaSet(inner) => inner.emit_parameters(xmit),
+            Self::DeltaSetUnacknowledged(inner) => inner.emit_parameters(xmit),
+            Self::MoveSet(inner) => inner.emit_parameters(xmit),
+            Self::MoveSetUnacknowledged(inner) => inner.emit_parameters(xmit),
+            Self::Status(inner) => inner.emit_parameters(xmit),
+        }
+    }
+}
+
+impl Model for GenericLevelServer {
+    const IDENTIFIER: ModelIdentifier = GENERIC_LEVEL_SERVER;
+    type Message<'m> = GenericLevelMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            GENERIC_LEVEL_GET => Ok(Some(GenericLevelMessage::Get)),
+            GENERIC_LEVEL_SET => Ok(Some(GenericLevelMessage::Set(Set::parse(parameters)?))),
+            GENERIC_LEVEL_SET_UNACKNOWLEDGED => Ok(Some(GenericLevelMessage::SetUnacknowledged(
+                Set::parse(parameters)?,
+            ))),
+            GENERIC_DELTA_SET => Ok(Some(GenericLevelMessage::DeltaSet(DeltaSet::parse(
+                parameters,
+            )?))),
+            GENERIC_DELTA_SET_UNACKNOWLEDGED => Ok(Some(
+                GenericLevelMessage::DeltaSetUnacknowledged(DeltaSet::parse(parameters)?),
+            )),
+            GENERIC_MOVE_SET => Ok(Some(GenericLevelMessage::MoveSet(MoveSet::parse(
+                parameters,
+            )?))),
+            GENERIC_MOVE_SET_UNACKNOWLEDGED => Ok(Some(
+                GenericLevelMessage::MoveSetUnacknowledged(MoveSet::parse(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for GenericLevelClient {
+    const IDENTIFIER: ModelIdentifier = GENERIC_LEVEL_CLIENT;
+    type Message<'m> = GenericLevelMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            GENERIC_LEVEL_STATUS => Ok(Some(GenericLevelMessage::Status(Status::parse(
+                parameters,
+            )?))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+opcode!( GENERIC_LEVEL_GET 0x82, 0x05 );
+opcode!( GENERIC_LEVEL_SET 0x82, 0x06 );
+opcode!( GENERIC_LEVEL_SET_UNACKNOWLEDGED 0x82, 0x07 );
+opcode!( GENERIC_LEVEL_STATUS 0x82, 0x08 );
+opcode!( GENERIC_DELTA_SET 0x82, 0x09 );
+opcode!( GENERIC_DELTA_SET_UNACKNOWLEDGED 0x82, 0x0A );
+opcode!( GENERIC_MOVE_SET 0x82, 0x0B );
+opcode!( GENERIC_MOVE_SET_UNACKNOWLEDGED 0x82, 0x0C );
+
+/// Set the level to an absolute value.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Set {
+    pub level: i16,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl Set {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 3 {
+            Ok(Self {
+                level: i16::from_le_bytes([parameters[0], parameters[1]]),
+                tid: parameters[2],
+                transition: Transition::parse(&parameters[3..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.level.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Change the level by a delta, relative to the level at the start of the
+/// transaction identified by `tid`.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeltaSet {
+    pub delta_level: i32,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl DeltaSet {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 5 {
+            Ok(Self {
+                delta_level: i32::from_le_bytes([
+                    parameters[0],
+                    parameters[1],
+                    parameters[2],
+                    parameters[3],
+                ]),
+                tid: parameters[4],
+                transition: Transition::parse(&parameters[5..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.delta_level.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Start moving the level by `delta_level` for every transition time
+/// elapsed, until stopped by another message or the level saturates.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MoveSet {
+    pub delta_level: i16,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl MoveSet {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 3 {
+            Ok(Self {
+                delta_level: i16::from_le_bytes([parameters[0], parameters[1]]),
+                tid: parameters[2],
+                transition: Transition::parse(&parameters[3..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.delta_level.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status {
+    pub present_level: i16,
+    /// The level being transitioned to and the time remaining, if in transition.
+    pub target: Option<(i16, TransitionTime)>,
+}
+
+impl Status {
+    /// The status of a level at `now`, reporting its target while in transition.
+    pub fn new(level: &Transitioning, now: Instant) -> Self {
+        Self {
+            present_level: level.value(now) as i16,
+            target: level
+                .remaining_time(now)
+                .map(|remaining_time| (level.target() as i16, remaining_time)),
+        }
+    }
+
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 2 || parameters.len() == 5 {
+            let present_level = i16::from_le_bytes([parameters[0], parameters[1]]);
+            let target = if parameters.len() == 5 {
+                Some((
+                    i16::from_le_bytes([parameters[2], parameters[3]]),
+                    TransitionTime(parameters[4]),
+                ))
+            } else {
+                None
+            };
+            Ok(Self {
+                present_level,
+                target,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.present_level.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        if let Some((target_level, remaining_time)) = &self.target {
+            xmit.extend_from_slice(&target_level.to_le_bytes())
+                .map_err(|_| InsufficientBuffer)?;
+            xmit.push(remaining_time.0)
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emit(message: GenericLevelMessage) -> Vec<u8, 16> {
+        let mut parameters = Vec::new();
+        message.emit_parameters(&mut parameters).unwrap();
+        parameters
+    }
+
+    #[test]
+    fn set() {
+        let transition = Some(Transition {
+            transition_time: TransitionTime(0x45),
+            delay: 10,
+        });
+        let parameters = emit(GenericLevelMessage::Set(Set {
+            level: -2,
+            tid: 7,
+            transition,
+        }));
+        assert_eq!(&[0xFE, 0xFF, 7, 0x45, 10], &parameters[..]);
+        match GenericLevelServer::parse(GENERIC_LEVEL_SET, &parameters) {
+            Ok(Some(GenericLevelMessage::Set(set))) => {
+                assert_eq!(-2, set.level);
+                assert_eq!(7, set.tid);
+                assert_eq!(transition, set.transition);
+            }
+            _ => panic!("not a set"),
+        }
+
+        // without a transition.
+        match GenericLevelServer::parse(GENERIC_LEVEL_SET_UNACKNOWLEDGED, &parameters[..3]) {
+            Ok(Some(GenericLevelMessage::SetUnacknowledged(set))) => {
+                assert_eq!(None, set.transition);
+            }
+            _ => panic!("not a set"),
+        }
+
+        assert!(matches!(
+            GenericLevelServer::parse(GENERIC_LEVEL_SET, &parameters[..4]),
+            Err(ParseError::InvalidLength)
+        ));
+        assert!(matches!(
+            GenericLevelServer::parse(GENERIC_LEVEL_SET, &[0xFE, 0xFF, 7, 0x3F, 10]),
+            Err(ParseError::InvalidValue)
+        ));
+    }
+
+    #[test]
+    fn delta_and_move_set() {
+        let parameters = emit(GenericLevelMessage::DeltaSet(DeltaSet {
+            delta_level: -70_000,
+            tid: 1,
+            transition: None,
+        }));
+        assert_eq!(5, parameters.len());
+        match GenericLevelServer::parse(GENERIC_DELTA_SET, &parameters) {
+            Ok(Some(GenericLevelMessage::DeltaSet(delta))) => {
+                assert_eq!(-70_000, delta.delta_level);
+                assert_eq!(1, delta.tid);
+            }
+            _ => panic!("not a delta set"),
+        }
+
+        let parameters = emit(GenericLevelMessage::MoveSet(MoveSet {
+            delta_level: 0x100,
+            tid: 2,
+            transition: Some(Transition {
+                transition_time: TransitionTime(0x01),
+                delay: 0,
+            }),
+        }));
+        assert_eq!(&[0x00, 0x01, 2, 0x01, 0], &parameters[..]);
+        match GenericLevelServer::parse(GENERIC_MOVE_SET_UNACKNOWLEDGED, &parameters) {
+            Ok(Some(GenericLevelMessage::MoveSetUnacknowledged(moving))) => {
+                assert_eq!(0x100, moving.delta_level);
+                assert_eq!(
+                    Some(TransitionTime(0x01)),
+                    moving.transition.map(|e| e.transition_time)
+                );
+            }
+            _ => panic!("not a move set"),
+        }
+    }
+
+    #[test]
+    fn status() {
+        let parameters = emit(GenericLevelMessage::Status(Status {
+            present_level: 100,
+            target: Some((200, TransitionTime(0x0B))),
+        }));
+        assert_eq!(&[100, 0, 200, 0, 0x0B], &parameters[..]);
+        match GenericLevelClient::parse(GENERIC_LEVEL_STATUS, &parameters) {
+            Ok(Some(GenericLevelMessage::Status(status))) => {
+                assert_eq!(100, status.present_level);
+                assert_eq!(Some((200, TransitionTime(0x0B))), status.target);
+            }
+            _ => panic!("not a status"),
+        }
+
+        match GenericLevelClient::parse(GENERIC_LEVEL_STATUS, &parameters[..2]) {
+            Ok(Some(GenericLevelMessage::Status(status))) => assert_eq!(None, status.target),
+            _ => panic!("not a status"),
+        }
+        assert!(matches!(
+            GenericLevelClient::parse(GENERIC_LEVEL_STATUS, &parameters[..3]),
+            Err(ParseError::InvalidLength)
+        ));
+    }
+
+    #[test]
+    fn status_in_transition() {
+        let now = Instant::from_millis(1_000);
+        let level = Transitioning::new(0, 1000, None, TransitionTime(0x0A), now);
+        let status = Status::new(&level, now + embassy::time::Duration::from_millis(400));
+        assert_eq!(400, status.present_level);
+        assert_eq!(Some((1000, TransitionTime(0x06))), status.target);
+
+        let status = Status::new(&level, now + embassy::time::Duration::from_secs(1));
+        assert_eq!(1000, status.present_level);
+        assert_eq!(None, status.target);
+    }
+}
@@ -1,2 +1,6 @@
 pub mod battery;
+pub mod default_transition_time;
+pub mod level;
 pub mod onoff;
+pub mod power_onoff;
+pub mod transition;
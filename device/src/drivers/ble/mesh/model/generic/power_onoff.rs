@@ -0,0 +1,191 @@
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::storage::{load_versioned, store_versioned, Storage};
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug)]
+pub struct GenericPowerOnOffServer;
+
+#[derive(Clone, Debug)]
+pub struct GenericPowerOnOffSetupServer;
+
+#[derive(Clone, Debug)]
+pub struct GenericPowerOnOffClient;
+
+pub const GENERIC_POWER_ONOFF_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1006);
+pub const GENERIC_POWER_ONOFF_SETUP_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1007);
+pub const GENERIC_POWER_ONOFF_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1008);
+
+/// The state of an element once powered up.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OnPowerUp {
+    /// Off.
+    Off = 0x00,
+    /// On, with the default state of any other model.
+    Default = 0x01,
+    /// As it was when powered down.
+    Restore = 0x02,
+}
+
+impl Default for OnPowerUp {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+impl OnPowerUp {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 1 {
+            match parameters[0] {
+                0x00 => Ok(Self::Off),
+                0x01 => Ok(Self::Default),
+                0x02 => Ok(Self::Restore),
+                _ => Err(ParseError::InvalidValue),
+            }
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GenericPowerOnOffMessage {
+    Get,
+    Set(OnPowerUp),
+    SetUnacknowledged(OnPowerUp),
+    Status(OnPowerUp),
+}
+
+impl Message for GenericPowerOnOffMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => GENERIC_ON_POWER_UP_GET,
+            Self::Set(_) => GENERIC_ON_POWER_UP_SET,
+            Self::SetUnacknowledged(_) => GENERIC_ON_POWER_UP_SET_UNACKNOWLEDGED,
+            Self::Status(_) => GENERIC_ON_POWER_UP_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut heapless::Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get => Ok(()),
+            Self::Set(inner) | Self::SetUnacknowledged(inner) | Self::Status(inner) => {
+                xmit.push(*inner as u8).map_err(|_| InsufficientBuffer)
+            }
+        }
+    }
+}
+
+impl Model for GenericPowerOnOffServer {
+    const IDENTIFIER: ModelIdentifier = GENERIC_POWER_ONOFF_SERVER;
+    type Message<'m> = GenericPowerOnOffMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        _parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            GENERIC_ON_POWER_UP_GET => Ok(Some(GenericPowerOnOffMessage::Get)),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for GenericPowerOnOffSetupServer {
+    const IDENTIFIER: ModelIdentifier = GENERIC_POWER_ONOFF_SETUP_SERVER;
+    const SUPPORTS_PUBLICATION: bool = false;
+    type Message<'m> = GenericPowerOnOffMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            GENERIC_ON_POWER_UP_SET => Ok(Some(GenericPowerOnOffMessage::Set(OnPowerUp::parse(
+                parameters,
+            )?))),
+            GENERIC_ON_POWER_UP_SET_UNACKNOWLEDGED => Ok(Some(
+                GenericPowerOnOffMessage::SetUnacknowledged(OnPowerUp::parse(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for GenericPowerOnOffClient {
+    const IDENTIFIER: ModelIdentifier = GENERIC_POWER_ONOFF_CLIENT;
+    type Message<'m> = GenericPowerOnOffMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            GENERIC_ON_POWER_UP_STATUS => Ok(Some(GenericPowerOnOffMessage::Status(
+                OnPowerUp::parse(parameters)?,
+            ))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+opcode!( GENERIC_ON_POWER_UP_GET 0x82, 0x11 );
+opcode!( GENERIC_ON_POWER_UP_STATUS 0x82, 0x12 );
+opcode!( GENERIC_ON_POWER_UP_SET 0x82, 0x13 );
+opcode!( GENERIC_ON_POWER_UP_SET_UNACKNOWLEDGED 0x82, 0x14 );
+
+/// Version of the layout of a stored [`PowerOnOffState`].
+const POWER_ON_OFF_VERSION: u16 = 1;
+
+/// The state of a power-controlled element which survives a power cycle,
+/// to be kept in a `Storage` of its own apart from the node configuration.
+#[derive(Serialize, Deserialize, Copy, Clone, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PowerOnOffState {
+    pub on_power_up: OnPowerUp,
+    /// The last known generic onoff state.
+    pub on_off: u8,
+    /// The last known generic level state.
+    pub level: i16,
+}
+
+impl PowerOnOffState {
+    /// Retrieve the stored state, or the default if none has been stored yet.
+    /// A stored state failing to decode is an error, rather than replaced.
+    pub async fn load<S: Storage>(storage: &mut S) -> Result<Self, DeviceError> {
+        Ok(load_versioned(storage, POWER_ON_OFF_VERSION)
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub async fn store<S: Storage>(&self, storage: &mut S) -> Result<(), DeviceError> {
+        store_versioned(storage, POWER_ON_OFF_VERSION, self).await
+    }
+
+    /// The generic onoff state to power up with.
+    pub fn power_up_on_off(&self) -> u8 {
+        match self.on_power_up {
+            OnPowerUp::Off => 0,
+            OnPowerUp::Default => 1,
+            OnPowerUp::Restore => self.on_off,
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use embassy::time::{Duration, Instant};
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+const STEPS_MASK: u8 = 0b0011_1111;
+const UNKNOWN_STEPS: u8 = 0x3F;
+
+/// Resolution of the steps of a transition time.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StepResolution {
+    HundredMilliseconds = 0b00,
+    Seconds = 0b01,
+    TenSeconds = 0b10,
+    TenMinutes = 0b11,
+}
+
+impl StepResolution {
+    /// The length of a single step.
+    pub fn step(&self) -> Duration {
+        match self {
+            Self::HundredMilliseconds => Duration::from_millis(100),
+            Self::Seconds => Duration::from_secs(1),
+            Self::TenSeconds => Duration::from_secs(10),
+            Self::TenMinutes => Duration::from_secs(600),
+        }
+    }
+}
+
+/// The time a state transition takes, as a number of steps of a given resolution.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TransitionTime(pub u8);
+
+impl TransitionTime {
+    /// An immediate transition.
+    pub const IMMEDIATE: Self = Self(0);
+
+    /// A transition of unknown, or for a default transition time prohibited, length.
+    pub const UNKNOWN: Self = Self(UNKNOWN_STEPS);
+
+    pub fn new(steps: u8, resolution: StepResolution) -> Self {
+        Self((steps & STEPS_MASK) | ((resolution as u8) << 6))
+    }
+
+    /// The nearest transition time not shorter than `duration`, saturating
+    /// at the longest representable one.
+    pub fn from_duration(duration: Duration) -> Self {
+        for resolution in [
+            StepResolution::HundredMilliseconds,
+            StepResolution::Seconds,
+            StepResolution::TenSeconds,
+            StepResolution::TenMinutes,
+        ] {
+            let step = resolution.step().as_millis();
+            let steps = (duration.as_millis() + step - 1) / step;
+            if steps < UNKNOWN_STEPS as u64 {
+                return Self::new(steps as u8, resolution);
+            }
+        }
+        Self::new(UNKNOWN_STEPS - 1, StepResolution::TenMinutes)
+    }
+
+    pub fn steps(&self) -> u8 {
+        self.0 & STEPS_MASK
+    }
+
+    pub fn resolution(&self) -> StepResolution {
+        match self.0 >> 6 {
+            0b00 => StepResolution::HundredMilliseconds,
+            0b01 => StepResolution::Seconds,
+            0b10 => StepResolution::TenSeconds,
+            _ => StepResolution::TenMinutes,
+        }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.steps() == UNKNOWN_STEPS
+    }
+
+    /// The length of the transition, unless unknown.
+    pub fn as_duration(&self) -> Option<Duration> {
+        if self.is_unknown() {
+            None
+        } else {
+            Some(self.resolution().step() * self.steps() as u32)
+        }
+    }
+}
+
+/// The optional transition time and delay trailing the parameters of
+/// the set messages of the generic models.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Transition {
+    pub transition_time: TransitionTime,
+    /// Delay before the transition starts, in 5 millisecond steps.
+    pub delay: u8,
+}
+
+impl Transition {
+    pub fn new(transition_time: TransitionTime, delay: Duration) -> Self {
+        Self {
+            transition_time,
+            delay: (delay.as_millis() / 5).min(u8::MAX as u64) as u8,
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay as u64 * 5)
+    }
+
+    /// Parse the parameters remaining after the fixed part of a message,
+    /// which are either absent or both present.
+    pub fn parse(parameters: &[u8]) -> Result<Option<Self>, ParseError> {
+        match parameters.len() {
+            0 => Ok(None),
+            2 => {
+                let transition_time = TransitionTime(parameters[0]);
+                if transition_time.is_unknown() {
+                    // prohibited in a set message.
+                    return Err(ParseError::InvalidValue);
+                }
+                Ok(Some(Self {
+                    transition_time,
+                    delay: parameters[1],
+                }))
+            }
+            _ => Err(ParseError::InvalidLength),
+        }
+    }
+
+    pub fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.transition_time.0)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.delay).map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+/// A state moving linearly from one value to another, once the delay of
+/// its transition has elapsed.
+#[derive(Copy, Clone, Debug)]
+pub struct Transitioning {
+    from: i32,
+    to: i32,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Transitioning {
+    /// Start moving from `from` to `to` at `now`, using the transition of a
+    /// set message if it has one, otherwise the default transition time
+    /// without any delay.
+    pub fn new(
+        from: i32,
+        to: i32,
+        transition: Option<Transition>,
+        default_transition_time: TransitionTime,
+        now: Instant,
+    ) -> Self {
+        let (transition_time, delay) = match transition {
+            Some(transition) => (transition.transition_time, transition.delay()),
+            None => (default_transition_time, Duration::from_millis(0)),
+        };
+        Self {
+            from,
+            to,
+            start: now + delay,
+            duration: transition_time
+                .as_duration()
+                .unwrap_or(Duration::from_millis(0)),
+        }
+    }
+
+    pub fn target(&self) -> i32 {
+        self.to
+    }
+
+    fn end(&self) -> Instant {
+        self.start + self.duration
+    }
+
+    pub fn is_complete(&self, now: Instant) -> bool {
+        now >= self.end()
+    }
+
+    /// The value at `now`, interpolated between the two.
+    pub fn value(&self, now: Instant) -> i32 {
+        if self.is_complete(now) {
+            self.to
+        } else if now <= self.start {
+            self.from
+        } else {
+            let elapsed = (now - self.start).as_millis() as i64;
+            let total = self.duration.as_millis() as i64;
+            let from = self.from as i64;
+            (from + (self.to as i64 - from) * elapsed / total) as i32
+        }
+    }
+
+    /// The time left until the target is reached, including any delay yet
+    /// to elapse, as reported by a status message. None once complete.
+    pub fn remaining_time(&self, now: Instant) -> Option<TransitionTime> {
+        if self.is_complete(now) {
+            None
+        } else {
+            Some(TransitionTime::from_duration(self.end() - now))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(value: u64) -> TransitionTime {
+        TransitionTime::from_duration(Duration::from_millis(value))
+    }
+
+    #[test]
+    fn from_duration_boundaries() {
+        assert_eq!(TransitionTime::IMMEDIATE, millis(0));
+        assert_eq!(TransitionTime(0x01), millis(1));
+        assert_eq!(TransitionTime(0x01), millis(100));
+        assert_eq!(TransitionTime(0x02), millis(101));
+        assert_eq!(TransitionTime(0x3E), millis(6_200));
+        // a step too many for 100 millisecond steps.
+        assert_eq!(TransitionTime(0x47), millis(6_300));
+        assert_eq!(TransitionTime(0x7E), millis(62_000));
+        assert_eq!(TransitionTime(0x87), millis(62_001));
+        assert_eq!(TransitionTime(0xBE), millis(620_000));
+        assert_eq!(TransitionTime(0xC2), millis(620_001));
+        assert_eq!(TransitionTime(0xFE), millis(37_200_000));
+        // saturating at the longest one.
+        assert_eq!(TransitionTime(0xFE), millis(37_200_001));
+        assert_eq!(TransitionTime(0xFE), millis(u32::MAX as u64));
+    }
+
+    #[test]
+    fn as_duration() {
+        assert_eq!(
+            Some(Duration::from_millis(6_200)),
+            TransitionTime::new(62, StepResolution::HundredMilliseconds).as_duration()
+        );
+        assert_eq!(
+            Some(Duration::from_secs(3 * 600)),
+            TransitionTime(0xC3).as_duration()
+        );
+        assert_eq!(None, TransitionTime::UNKNOWN.as_duration());
+        assert_eq!(None, TransitionTime(0xFF).as_duration());
+    }
+
+    #[test]
+    fn parse_and_emit() {
+        let transition = Transition::new(TransitionTime(0x45), Duration::from_millis(50));
+        let mut parameters: Vec<u8, 2> = Vec::new();
+        transition.emit_parameters(&mut parameters).unwrap();
+        assert_eq!(&[0x45, 10], &parameters[..]);
+        assert_eq!(Some(transition), Transition::parse(&parameters).unwrap());
+
+        assert_eq!(None, Transition::parse(&[]).unwrap());
+        assert!(matches!(
+            Transition::parse(&[0x45]),
+            Err(ParseError::InvalidLength)
+        ));
+        // unknown number of steps, at any resolution.
+        assert!(matches!(
+            Transition::parse(&[0x3F, 0x00]),
+            Err(ParseError::InvalidValue)
+        ));
+        assert!(matches!(
+            Transition::parse(&[0xFF, 0x00]),
+            Err(ParseError::InvalidValue)
+        ));
+    }
+
+    #[test]
+    fn interpolation() {
+        let now = Instant::from_millis(1_000);
+        // 1 second, after a 100 millisecond delay.
+        let transition = Transition::new(TransitionTime(0x0A), Duration::from_millis(100));
+        let level = Transitioning::new(-100, 100, Some(transition), TransitionTime::IMMEDIATE, now);
+
+        assert_eq!(100, level.target());
+        assert_eq!(-100, level.value(now));
+        assert_eq!(-100, level.value(now + Duration::from_millis(100)));
+        assert_eq!(0, level.value(now + Duration::from_millis(600)));
+        assert_eq!(50, level.value(now + Duration::from_millis(850)));
+        assert_eq!(100, level.value(now + Duration::from_millis(1_100)));
+        assert_eq!(100, level.value(now + Duration::from_secs(10)));
+
+        assert_eq!(Some(TransitionTime(0x0B)), level.remaining_time(now));
+        assert_eq!(
+            Some(TransitionTime(0x05)),
+            level.remaining_time(now + Duration::from_millis(600))
+        );
+        assert!(!level.is_complete(now + Duration::from_millis(1_099)));
+        assert!(level.is_complete(now + Duration::from_millis(1_100)));
+        assert_eq!(
+            None,
+            level.remaining_time(now + Duration::from_millis(1_100))
+        );
+    }
+
+    #[test]
+    fn default_transition_time() {
+        let now = Instant::from_millis(1_000);
+        let level = Transitioning::new(0, 10, None, TransitionTime(0x41), now);
+        assert_eq!(5, level.value(now + Duration::from_millis(500)));
+        assert_eq!(Some(TransitionTime(0x0A)), level.remaining_time(now));
+
+        let level = Transitioning::new(0, 10, None, TransitionTime::IMMEDIATE, now);
+        assert!(level.is_complete(now));
+        assert_eq!(10, level.value(now));
+        assert_eq!(None, level.remaining_time(now));
+    }
+}
@@ -0,0 +1,556 @@
+use crate::drivers::ble::mesh::model::generic::transition::{Transition, TransitionTime};
+use crate::drivers::ble::mesh::model::light::lightness::LightLightnessState;
+use crate::drivers::ble::mesh::model::light::{emit_u16, Range, RangeStatus};
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use heapless::Vec;
+
+#[derive(Clone, Debug)]
+pub struct LightCtlServer;
+
+#[derive(Clone, Debug)]
+pub struct LightCtlSetupServer;
+
+#[derive(Clone, Debug)]
+pub struct LightCtlClient;
+
+#[derive(Clone, Debug)]
+pub struct LightCtlTemperatureServer;
+
+pub const LIGHT_CTL_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1303);
+pub const LIGHT_CTL_SETUP_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1304);
+pub const LIGHT_CTL_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1305);
+pub const LIGHT_CTL_TEMPERATURE_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1306);
+
+/// The warmest color temperature, in Kelvin.
+pub const TEMPERATURE_MIN: u16 = 0x0320;
+/// The coldest color temperature, in Kelvin.
+pub const TEMPERATURE_MAX: u16 = 0x4E20;
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LightCtlMessage {
+    Get,
+    Set(Set),
+    SetUnacknowledged(Set),
+    Status(Status),
+    TemperatureGet,
+    TemperatureSet(TemperatureSet),
+    TemperatureSetUnacknowledged(TemperatureSet),
+    TemperatureStatus(TemperatureStatus),
+    TemperatureRangeGet,
+    TemperatureRangeSet(Range),
+    TemperatureRangeSetUnacknowledged(Range),
+    TemperatureRangeStatus(RangeStatus),
+    DefaultGet,
+    DefaultSet(CtlDefault),
+    DefaultSetUnacknowledged(CtlDefault),
+    DefaultStatus(CtlDefault),
+}
+
+impl Message for LightCtlMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => LIGHT_CTL_GET,
+            Self::Set(_) => LIGHT_CTL_SET,
+            Self::SetUnacknowledged(_) => LIGHT_CTL_SET_UNACKNOWLEDGED,
+            Self::Status(_) => LIGHT_CTL_STATUS,
+            Self::TemperatureGet => LIGHT_CTL_TEMPERATURE_GET,
+            Self::TemperatureSet(_) => LIGHT_CTL_TEMPERATURE_SET,
+            Self::TemperatureSetUnacknowledged(_) => LIGHT_CTL_TEMPERATURE_SET_UNACKNOWLEDGED,
+            Self::TemperatureStatus(_) => LIGHT_CTL_TEMPERATURE_STATUS,
+            Self::TemperatureRangeGet => LIGHT_CTL_TEMPERATURE_RANGE_GET,
+            Self::TemperatureRangeSet(_) => LIGHT_CTL_TEMPERATURE_RANGE_SET,
+            Self::TemperatureRangeSetUnacknowledged(_) => {
+                LIGHT_CTL_TEMPERATURE_RANGE_SET_UNACKNOWLEDGED
+            }
+            Self::TemperatureRangeStatus(_) => LIGHT_CTL_TEMPERATURE_RANGE_STATUS,
+            Self::DefaultGet => LIGHT_CTL_DEFAULT_GET,
+            Self::DefaultSet(_) => LIGHT_CTL_DEFAULT_SET,
+            Self::DefaultSetUnacknowledged(_) => LIGHT_CTL_DEFAULT_SET_UNACKNOWLEDGED,
+            Self::DefaultStatus(_) => LIGHT_CTL_DEFAULT_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut heapless::Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get | Self::TemperatureGet | Self::TemperatureRangeGet | Self::DefaultGet => {
+                Ok(())
+            }
+            Self::Set(inner) | Self::SetUnacknowledged(inner) => inner.emit_parameters(xmit),
+            Self::Status(inner) => inner.emit_parameters(xmit),
+            Self::TemperatureSet(inner) | Self::TemperatureSetUnacknowledged(inner) => {
+                inner.emit_parameters(xmit)
+            }
+            Self::TemperatureStatus(inner) => inner.emit_parameters(xmit),
+            Self::TemperatureRangeSet(inner) | Self::TemperatureRangeSetUnacknowledged(inner) => {
+                inner.emit_parameters(xmit)
+            }
+            Self::TemperatureRangeStatus(inner) => inner.emit_parameters(xmit),
+            Self::DefaultSet(inner)
+            | Self::DefaultSetUnacknowledged(inner)
+            | Self::DefaultStatus(inner) => inner.emit_parameters(xmit),
+        }
+    }
+}
+
+impl Model for LightCtlServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_CTL_SERVER;
+    type Message<'m> = LightCtlMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_CTL_GET => Ok(Some(LightCtlMessage::Get)),
+            LIGHT_CTL_SET => Ok(Some(LightCtlMessage::Set(Set::parse(parameters)?))),
+            LIGHT_CTL_SET_UNACKNOWLEDGED => Ok(Some(LightCtlMessage::SetUnacknowledged(
+                Set::parse(parameters)?,
+            ))),
+            LIGHT_CTL_TEMPERATURE_RANGE_GET => Ok(Some(LightCtlMessage::TemperatureRangeGet)),
+            LIGHT_CTL_DEFAULT_GET => Ok(Some(LightCtlMessage::DefaultGet)),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightCtlTemperatureServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_CTL_TEMPERATURE_SERVER;
+    type Message<'m> = LightCtlMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_CTL_TEMPERATURE_GET => Ok(Some(LightCtlMessage::TemperatureGet)),
+            LIGHT_CTL_TEMPERATURE_SET => Ok(Some(LightCtlMessage::TemperatureSet(
+                TemperatureSet::parse(parameters)?,
+            ))),
+            LIGHT_CTL_TEMPERATURE_SET_UNACKNOWLEDGED => Ok(Some(
+                LightCtlMessage::TemperatureSetUnacknowledged(TemperatureSet::parse(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightCtlSetupServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_CTL_SETUP_SERVER;
+    const SUPPORTS_PUBLICATION: bool = false;
+    type Message<'m> = LightCtlMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_CTL_DEFAULT_SET => Ok(Some(LightCtlMessage::DefaultSet(CtlDefault::parse(
+                parameters,
+            )?))),
+            LIGHT_CTL_DEFAULT_SET_UNACKNOWLEDGED => Ok(Some(
+                LightCtlMessage::DefaultSetUnacknowledged(CtlDefault::parse(parameters)?),
+            )),
+            LIGHT_CTL_TEMPERATURE_RANGE_SET => Ok(Some(LightCtlMessage::TemperatureRangeSet(
+                parse_range(parameters)?,
+            ))),
+            LIGHT_CTL_TEMPERATURE_RANGE_SET_UNACKNOWLEDGED => Ok(Some(
+                LightCtlMessage::TemperatureRangeSetUnacknowledged(parse_range(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightCtlClient {
+    const IDENTIFIER: ModelIdentifier = LIGHT_CTL_CLIENT;
+    type Message<'m> = LightCtlMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_CTL_STATUS => Ok(Some(LightCtlMessage::Status(Status::parse(parameters)?))),
+            LIGHT_CTL_TEMPERATURE_STATUS => Ok(Some(LightCtlMessage::TemperatureStatus(
+                TemperatureStatus::parse(parameters)?,
+            ))),
+            LIGHT_CTL_TEMPERATURE_RANGE_STATUS => Ok(Some(
+                LightCtlMessage::TemperatureRangeStatus(RangeStatus::parse(parameters)?),
+            )),
+            LIGHT_CTL_DEFAULT_STATUS => Ok(Some(LightCtlMessage::DefaultStatus(
+                CtlDefault::parse(parameters)?,
+            ))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn parse_temperature(parameters: &[u8]) -> Result<u16, ParseError> {
+    let temperature = u16::from_le_bytes([parameters[0], parameters[1]]);
+    if (TEMPERATURE_MIN..=TEMPERATURE_MAX).contains(&temperature) {
+        Ok(temperature)
+    } else {
+        Err(ParseError::InvalidValue)
+    }
+}
+
+fn parse_range(parameters: &[u8]) -> Result<Range, ParseError> {
+    let range = Range::parse(parameters)?;
+    if range.min < TEMPERATURE_MIN || range.max > TEMPERATURE_MAX {
+        Err(ParseError::InvalidValue)
+    } else {
+        Ok(range)
+    }
+}
+
+opcode!( LIGHT_CTL_GET 0x82, 0x5D );
+opcode!( LIGHT_CTL_SET 0x82, 0x5E );
+opcode!( LIGHT_CTL_SET_UNACKNOWLEDGED 0x82, 0x5F );
+opcode!( LIGHT_CTL_STATUS 0x82, 0x60 );
+opcode!( LIGHT_CTL_TEMPERATURE_GET 0x82, 0x61 );
+opcode!( LIGHT_CTL_TEMPERATURE_RANGE_GET 0x82, 0x62 );
+opcode!( LIGHT_CTL_TEMPERATURE_RANGE_STATUS 0x82, 0x63 );
+opcode!( LIGHT_CTL_TEMPERATURE_SET 0x82, 0x64 );
+opcode!( LIGHT_CTL_TEMPERATURE_SET_UNACKNOWLEDGED 0x82, 0x65 );
+opcode!( LIGHT_CTL_TEMPERATURE_STATUS 0x82, 0x66 );
+opcode!( LIGHT_CTL_DEFAULT_GET 0x82, 0x67 );
+opcode!( LIGHT_CTL_DEFAULT_STATUS 0x82, 0x68 );
+opcode!( LIGHT_CTL_DEFAULT_SET 0x82, 0x69 );
+opcode!( LIGHT_CTL_DEFAULT_SET_UNACKNOWLEDGED 0x82, 0x6A );
+opcode!( LIGHT_CTL_TEMPERATURE_RANGE_SET 0x82, 0x6B );
+opcode!( LIGHT_CTL_TEMPERATURE_RANGE_SET_UNACKNOWLEDGED 0x82, 0x6C );
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Set {
+    pub lightness: u16,
+    pub temperature: u16,
+    pub delta_uv: i16,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl Set {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 7 {
+            Ok(Self {
+                lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                temperature: parse_temperature(&parameters[2..4])?,
+                delta_uv: i16::from_le_bytes([parameters[4], parameters[5]]),
+                tid: parameters[6],
+                transition: Transition::parse(&parameters[7..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.lightness, xmit)?;
+        emit_u16(self.temperature, xmit)?;
+        xmit.extend_from_slice(&self.delta_uv.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status {
+    pub present_lightness: u16,
+    pub present_temperature: u16,
+    /// The lightness and temperature being transitioned to and the time
+    /// remaining, if in transition.
+    pub target: Option<(u16, u16, TransitionTime)>,
+}
+
+impl Status {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 4 || parameters.len() == 9 {
+            let target = if parameters.len() == 9 {
+                Some((
+                    u16::from_le_bytes([parameters[4], parameters[5]]),
+                    u16::from_le_bytes([parameters[6], parameters[7]]),
+                    TransitionTime(parameters[8]),
+                ))
+            } else {
+                None
+            };
+            Ok(Self {
+                present_lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                present_temperature: u16::from_le_bytes([parameters[2], parameters[3]]),
+                target,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.present_lightness, xmit)?;
+        emit_u16(self.present_temperature, xmit)?;
+        if let Some((target_lightness, target_temperature, remaining_time)) = &self.target {
+            emit_u16(*target_lightness, xmit)?;
+            emit_u16(*target_temperature, xmit)?;
+            xmit.push(remaining_time.0)
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TemperatureSet {
+    pub temperature: u16,
+    pub delta_uv: i16,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl TemperatureSet {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 5 {
+            Ok(Self {
+                temperature: parse_temperature(&parameters[0..2])?,
+                delta_uv: i16::from_le_bytes([parameters[2], parameters[3]]),
+                tid: parameters[4],
+                transition: Transition::parse(&parameters[5..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.temperature, xmit)?;
+        xmit.extend_from_slice(&self.delta_uv.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TemperatureStatus {
+    pub present_temperature: u16,
+    pub present_delta_uv: i16,
+    /// The temperature and delta UV being transitioned to and the time
+    /// remaining, if in transition.
+    pub target: Option<(u16, i16, TransitionTime)>,
+}
+
+impl TemperatureStatus {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 4 || parameters.len() == 9 {
+            let target = if parameters.len() == 9 {
+                Some((
+                    u16::from_le_bytes([parameters[4], parameters[5]]),
+                    i16::from_le_bytes([parameters[6], parameters[7]]),
+                    TransitionTime(parameters[8]),
+                ))
+            } else {
+                None
+            };
+            Ok(Self {
+                present_temperature: u16::from_le_bytes([parameters[0], parameters[1]]),
+                present_delta_uv: i16::from_le_bytes([parameters[2], parameters[3]]),
+                target,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.present_temperature, xmit)?;
+        xmit.extend_from_slice(&self.present_delta_uv.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        if let Some((target_temperature, target_delta_uv, remaining_time)) = &self.target {
+            emit_u16(*target_temperature, xmit)?;
+            xmit.extend_from_slice(&target_delta_uv.to_le_bytes())
+                .map_err(|_| InsufficientBuffer)?;
+            xmit.push(remaining_time.0)
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state to power up with, or to turn on with from off.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CtlDefault {
+    pub lightness: u16,
+    pub temperature: u16,
+    pub delta_uv: i16,
+}
+
+impl CtlDefault {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 6 {
+            Ok(Self {
+                lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                temperature: parse_temperature(&parameters[2..4])?,
+                delta_uv: i16::from_le_bytes([parameters[4], parameters[5]]),
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.lightness, xmit)?;
+        emit_u16(self.temperature, xmit)?;
+        xmit.extend_from_slice(&self.delta_uv.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+}
+
+/// The color temperature of an element, whose lightness is that of its
+/// `LightLightnessState`. The generic level of the element served by the
+/// temperature server is bound to the temperature within its range.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LightCtlState {
+    temperature: u16,
+    delta_uv: i16,
+    default_temperature: u16,
+    default_delta_uv: i16,
+    range: Range,
+}
+
+impl Default for LightCtlState {
+    fn default() -> Self {
+        Self {
+            temperature: TEMPERATURE_MIN,
+            delta_uv: 0,
+            default_temperature: TEMPERATURE_MIN,
+            default_delta_uv: 0,
+            range: Range {
+                min: TEMPERATURE_MIN,
+                max: TEMPERATURE_MAX,
+            },
+        }
+    }
+}
+
+impl LightCtlState {
+    /// Power up, or turn on with the default temperature.
+    pub fn power_up(&mut self) {
+        self.temperature = self.range.clamp(self.default_temperature);
+        self.delta_uv = self.default_delta_uv;
+    }
+
+    pub fn temperature(&self) -> u16 {
+        self.temperature
+    }
+
+    pub fn delta_uv(&self) -> i16 {
+        self.delta_uv
+    }
+
+    /// Set the temperature, restricted to the range.
+    pub fn set_temperature(&mut self, temperature: u16, delta_uv: i16) {
+        self.temperature = self.range.clamp(temperature);
+        self.delta_uv = delta_uv;
+    }
+
+    pub fn range(&self) -> Range {
+        self.range
+    }
+
+    pub fn set_range(&mut self, range: Range) {
+        self.range = range;
+        self.temperature = self.range.clamp(self.temperature);
+    }
+
+    pub fn defaults(&self, lightness: &LightLightnessState) -> CtlDefault {
+        CtlDefault {
+            lightness: lightness.default_lightness(),
+            temperature: self.default_temperature,
+            delta_uv: self.default_delta_uv,
+        }
+    }
+
+    pub fn set_defaults(&mut self, lightness: &mut LightLightnessState, defaults: &CtlDefault) {
+        lightness.set_default_lightness(defaults.lightness);
+        self.default_temperature = defaults.temperature;
+        self.default_delta_uv = defaults.delta_uv;
+    }
+
+    pub fn generic_level(&self) -> i16 {
+        let span = (self.range.max - self.range.min) as i32;
+        if span == 0 {
+            return i16::MIN;
+        }
+        let offset = (self.temperature - self.range.min) as i32;
+        (offset * u16::MAX as i32 / span + i16::MIN as i32) as i16
+    }
+
+    pub fn set_generic_level(&mut self, level: i16) {
+        let span = (self.range.max - self.range.min) as i32;
+        let offset = (level as i32 - i16::MIN as i32) * span / u16::MAX as i32;
+        self.temperature = self.range.min + offset as u16;
+    }
+
+    pub fn status(&self, lightness: &LightLightnessState) -> Status {
+        Status {
+            present_lightness: lightness.actual(),
+            present_temperature: self.temperature,
+            target: None,
+        }
+    }
+
+    pub fn temperature_status(&self) -> TemperatureStatus {
+        TemperatureStatus {
+            present_temperature: self.temperature,
+            present_delta_uv: self.delta_uv,
+            target: None,
+        }
+    }
+}
@@ -0,0 +1,675 @@
+use crate::drivers::ble::mesh::model::generic::transition::{Transition, TransitionTime};
+use crate::drivers::ble::mesh::model::light::lightness::LightLightnessState;
+use crate::drivers::ble::mesh::model::light::{emit_u16, Range, RangeStatusCode};
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use heapless::Vec;
+
+#[derive(Clone, Debug)]
+pub struct LightHslServer;
+
+#[derive(Clone, Debug)]
+pub struct LightHslSetupServer;
+
+#[derive(Clone, Debug)]
+pub struct LightHslClient;
+
+#[derive(Clone, Debug)]
+pub struct LightHslHueServer;
+
+#[derive(Clone, Debug)]
+pub struct LightHslSaturationServer;
+
+pub const LIGHT_HSL_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1307);
+pub const LIGHT_HSL_SETUP_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1308);
+pub const LIGHT_HSL_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1309);
+pub const LIGHT_HSL_HUE_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x130A);
+pub const LIGHT_HSL_SATURATION_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x130B);
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LightHslMessage {
+    Get,
+    Set(Set),
+    SetUnacknowledged(Set),
+    Status(Status),
+    TargetGet,
+    TargetStatus(Status),
+    HueGet,
+    HueSet(ComponentSet),
+    HueSetUnacknowledged(ComponentSet),
+    HueStatus(ComponentStatus),
+    SaturationGet,
+    SaturationSet(ComponentSet),
+    SaturationSetUnacknowledged(ComponentSet),
+    SaturationStatus(ComponentStatus),
+    DefaultGet,
+    DefaultSet(HslDefault),
+    DefaultSetUnacknowledged(HslDefault),
+    DefaultStatus(HslDefault),
+    RangeGet,
+    RangeSet(HslRange),
+    RangeSetUnacknowledged(HslRange),
+    RangeStatus(HslRangeStatus),
+}
+
+impl Message for LightHslMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => LIGHT_HSL_GET,
+            Self::Set(_) => LIGHT_HSL_SET,
+            Self::SetUnacknowledged(_) => LIGHT_HSL_SET_UNACKNOWLEDGED,
+            Self::Status(_) => LIGHT_HSL_STATUS,
+            Self::TargetGet => LIGHT_HSL_TARGET_GET,
+            Self::TargetStatus(_) => LIGHT_HSL_TARGET_STATUS,
+            Self::HueGet => LIGHT_HSL_HUE_GET,
+            Self::HueSet(_) => LIGHT_HSL_HUE_SET,
+            Self::HueSetUnacknowledged(_) => LIGHT_HSL_HUE_SET_UNACKNOWLEDGED,
+            Self::HueStatus(_) => LIGHT_HSL_HUE_STATUS,
+            Self::SaturationGet => LIGHT_HSL_SATURATION_GET,
+            Self::SaturationSet(_) => LIGHT_HSL_SATURATION_SET,
+            Self::SaturationSetUnacknowledged(_) => LIGHT_HSL_SATURATION_SET_UNACKNOWLEDGED,
+            Self::SaturationStatus(_) => LIGHT_HSL_SATURATION_STATUS,
+            Self::DefaultGet => LIGHT_HSL_DEFAULT_GET,
+            Self::DefaultSet(_) => LIGHT_HSL_DEFAULT_SET,
+            Self::DefaultSetUnacknowledged(_) => LIGHT_HSL_DEFAULT_SET_UNACKNOWLEDGED,
+            Self::DefaultStatus(_) => LIGHT_HSL_DEFAULT_STATUS,
+            Self::RangeGet => LIGHT_HSL_RANGE_GET,
+            Self::RangeSet(_) => LIGHT_HSL_RANGE_SET,
+            Self::RangeSetUnacknowledged(_) => LIGHT_HSL_RANGE_SET_UNACKNOWLEDGED,
+            Self::RangeStatus(_) => LIGHT_HSL_RANGE_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut heapless::Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get
+            | Self::TargetGet
+            | Self::HueGet
+            | Self::SaturationGet
+            | Self::DefaultGet
+            | Self::RangeGet => Ok(()),
+            Self::Set(inner) | Self::SetUnacknowledged(inner) => inner.emit_parameters(xmit),
+            Self::Status(inner) | Self::TargetStatus(inner) => inner.emit_parameters(xmit),
+            Self::HueSet(inner)
+            | Self::HueSetUnacknowledged(inner)
+            | Self::SaturationSet(inner)
+            | Self::SaturationSetUnacknowledged(inner) => inner.emit_parameters(xmit),
+            Self::HueStatus(inner) | Self::SaturationStatus(inner) => inner.emit_parameters(xmit),
+            Self::DefaultSet(inner)
+            | Self::DefaultSetUnacknowledged(inner)
+            | Self::DefaultStatus(inner) => inner.emit_parameters(xmit),
+            Self::RangeSet(inner) | Self::RangeSetUnacknowledged(inner) => {
+                inner.emit_parameters(xmit)
+            }
+            Self::RangeStatus(inner) => inner.emit_parameters(xmit),
+        }
+    }
+}
+
+impl Model for LightHslServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_HSL_SERVER;
+    type Message<'m> = LightHslMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_HSL_GET => Ok(Some(LightHslMessage::Get)),
+            LIGHT_HSL_SET => Ok(Some(LightHslMessage::Set(Set::parse(parameters)?))),
+            LIGHT_HSL_SET_UNACKNOWLEDGED => Ok(Some(LightHslMessage::SetUnacknowledged(
+                Set::parse(parameters)?,
+            ))),
+            LIGHT_HSL_TARGET_GET => Ok(Some(LightHslMessage::TargetGet)),
+            LIGHT_HSL_DEFAULT_GET => Ok(Some(LightHslMessage::DefaultGet)),
+            LIGHT_HSL_RANGE_GET => Ok(Some(LightHslMessage::RangeGet)),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightHslHueServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_HSL_HUE_SERVER;
+    type Message<'m> = LightHslMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_HSL_HUE_GET => Ok(Some(LightHslMessage::HueGet)),
+            LIGHT_HSL_HUE_SET => Ok(Some(LightHslMessage::HueSet(ComponentSet::parse(
+                parameters,
+            )?))),
+            LIGHT_HSL_HUE_SET_UNACKNOWLEDGED => Ok(Some(LightHslMessage::HueSetUnacknowledged(
+                ComponentSet::parse(parameters)?,
+            ))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightHslSaturationServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_HSL_SATURATION_SERVER;
+    type Message<'m> = LightHslMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_HSL_SATURATION_GET => Ok(Some(LightHslMessage::SaturationGet)),
+            LIGHT_HSL_SATURATION_SET => Ok(Some(LightHslMessage::SaturationSet(
+                ComponentSet::parse(parameters)?,
+            ))),
+            LIGHT_HSL_SATURATION_SET_UNACKNOWLEDGED => Ok(Some(
+                LightHslMessage::SaturationSetUnacknowledged(ComponentSet::parse(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightHslSetupServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_HSL_SETUP_SERVER;
+    const SUPPORTS_PUBLICATION: bool = false;
+    type Message<'m> = LightHslMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_HSL_DEFAULT_SET => Ok(Some(LightHslMessage::DefaultSet(HslDefault::parse(
+                parameters,
+            )?))),
+            LIGHT_HSL_DEFAULT_SET_UNACKNOWLEDGED => Ok(Some(
+                LightHslMessage::DefaultSetUnacknowledged(HslDefault::parse(parameters)?),
+            )),
+            LIGHT_HSL_RANGE_SET => Ok(Some(LightHslMessage::RangeSet(HslRange::parse(
+                parameters,
+            )?))),
+            LIGHT_HSL_RANGE_SET_UNACKNOWLEDGED => Ok(Some(
+                LightHslMessage::RangeSetUnacknowledged(HslRange::parse(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightHslClient {
+    const IDENTIFIER: ModelIdentifier = LIGHT_HSL_CLIENT;
+    type Message<'m> = LightHslMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_HSL_STATUS => Ok(Some(LightHslMessage::Status(Status::parse(parameters)?))),
+            LIGHT_HSL_TARGET_STATUS => Ok(Some(LightHslMessage::TargetStatus(Status::parse(
+                parameters,
+            )?))),
+            LIGHT_HSL_HUE_STATUS => Ok(Some(LightHslMessage::HueStatus(ComponentStatus::parse(
+                parameters,
+            )?))),
+            LIGHT_HSL_SATURATION_STATUS => Ok(Some(LightHslMessage::SaturationStatus(
+                ComponentStatus::parse(parameters)?,
+            ))),
+            LIGHT_HSL_DEFAULT_STATUS => Ok(Some(LightHslMessage::DefaultStatus(
+                HslDefault::parse(parameters)?,
+            ))),
+            LIGHT_HSL_RANGE_STATUS => Ok(Some(LightHslMessage::RangeStatus(
+                HslRangeStatus::parse(parameters)?,
+            ))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+opcode!( LIGHT_HSL_GET 0x82, 0x6D );
+opcode!( LIGHT_HSL_HUE_GET 0x82, 0x6E );
+opcode!( LIGHT_HSL_HUE_SET 0x82, 0x6F );
+opcode!( LIGHT_HSL_HUE_SET_UNACKNOWLEDGED 0x82, 0x70 );
+opcode!( LIGHT_HSL_HUE_STATUS 0x82, 0x71 );
+opcode!( LIGHT_HSL_SATURATION_GET 0x82, 0x72 );
+opcode!( LIGHT_HSL_SATURATION_SET 0x82, 0x73 );
+opcode!( LIGHT_HSL_SATURATION_SET_UNACKNOWLEDGED 0x82, 0x74 );
+opcode!( LIGHT_HSL_SATURATION_STATUS 0x82, 0x75 );
+opcode!( LIGHT_HSL_SET 0x82, 0x76 );
+opcode!( LIGHT_HSL_SET_UNACKNOWLEDGED 0x82, 0x77 );
+opcode!( LIGHT_HSL_STATUS 0x82, 0x78 );
+opcode!( LIGHT_HSL_TARGET_GET 0x82, 0x79 );
+opcode!( LIGHT_HSL_TARGET_STATUS 0x82, 0x7A );
+opcode!( LIGHT_HSL_DEFAULT_GET 0x82, 0x7B );
+opcode!( LIGHT_HSL_DEFAULT_STATUS 0x82, 0x7C );
+opcode!( LIGHT_HSL_RANGE_GET 0x82, 0x7D );
+opcode!( LIGHT_HSL_RANGE_STATUS 0x82, 0x7E );
+opcode!( LIGHT_HSL_DEFAULT_SET 0x82, 0x7F );
+opcode!( LIGHT_HSL_DEFAULT_SET_UNACKNOWLEDGED 0x82, 0x80 );
+opcode!( LIGHT_HSL_RANGE_SET 0x82, 0x81 );
+opcode!( LIGHT_HSL_RANGE_SET_UNACKNOWLEDGED 0x82, 0x82 );
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Set {
+    pub lightness: u16,
+    pub hue: u16,
+    pub saturation: u16,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl Set {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 7 {
+            Ok(Self {
+                lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                hue: u16::from_le_bytes([parameters[2], parameters[3]]),
+                saturation: u16::from_le_bytes([parameters[4], parameters[5]]),
+                tid: parameters[6],
+                transition: Transition::parse(&parameters[7..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.lightness, xmit)?;
+        emit_u16(self.hue, xmit)?;
+        emit_u16(self.saturation, xmit)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+/// The present, or for a target status the target, lightness, hue and saturation.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status {
+    pub lightness: u16,
+    pub hue: u16,
+    pub saturation: u16,
+    pub remaining_time: Option<TransitionTime>,
+}
+
+impl Status {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 6 || parameters.len() == 7 {
+            Ok(Self {
+                lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                hue: u16::from_le_bytes([parameters[2], parameters[3]]),
+                saturation: u16::from_le_bytes([parameters[4], parameters[5]]),
+                remaining_time: parameters.get(6).map(|t| TransitionTime(*t)),
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.lightness, xmit)?;
+        emit_u16(self.hue, xmit)?;
+        emit_u16(self.saturation, xmit)?;
+        if let Some(remaining_time) = &self.remaining_time {
+            xmit.push(remaining_time.0)
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Set either the hue or the saturation alone.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ComponentSet {
+    pub value: u16,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl ComponentSet {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 3 {
+            Ok(Self {
+                value: u16::from_le_bytes([parameters[0], parameters[1]]),
+                tid: parameters[2],
+                transition: Transition::parse(&parameters[3..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.value, xmit)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Either the hue or the saturation alone.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ComponentStatus {
+    pub present: u16,
+    /// The value being transitioned to and the time remaining, if in transition.
+    pub target: Option<(u16, TransitionTime)>,
+}
+
+impl ComponentStatus {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        match parameters.len() {
+            2 => Ok(Self {
+                present: u16::from_le_bytes([parameters[0], parameters[1]]),
+                target: None,
+            }),
+            5 => Ok(Self {
+                present: u16::from_le_bytes([parameters[0], parameters[1]]),
+                target: Some((
+                    u16::from_le_bytes([parameters[2], parameters[3]]),
+                    TransitionTime(parameters[4]),
+                )),
+            }),
+            _ => Err(ParseError::InvalidLength),
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.present, xmit)?;
+        if let Some((target, remaining_time)) = &self.target {
+            emit_u16(*target, xmit)?;
+            xmit.push(remaining_time.0)
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state to power up with.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HslDefault {
+    pub lightness: u16,
+    pub hue: u16,
+    pub saturation: u16,
+}
+
+impl HslDefault {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 6 {
+            Ok(Self {
+                lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                hue: u16::from_le_bytes([parameters[2], parameters[3]]),
+                saturation: u16::from_le_bytes([parameters[4], parameters[5]]),
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.lightness, xmit)?;
+        emit_u16(self.hue, xmit)?;
+        emit_u16(self.saturation, xmit)?;
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HslRange {
+    pub hue: Range,
+    pub saturation: Range,
+}
+
+impl HslRange {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 8 {
+            Ok(Self {
+                hue: Range::parse(&parameters[0..4])?,
+                saturation: Range::parse(&parameters[4..8])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        self.hue.emit_parameters(xmit)?;
+        self.saturation.emit_parameters(xmit)
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HslRangeStatus {
+    pub status_code: RangeStatusCode,
+    pub range: HslRange,
+}
+
+impl HslRangeStatus {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 9 {
+            Ok(Self {
+                status_code: RangeStatusCode::parse(parameters[0])?,
+                range: HslRange::parse(&parameters[1..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.status_code as u8)
+            .map_err(|_| InsufficientBuffer)?;
+        self.range.emit_parameters(xmit)
+    }
+}
+
+/// The hue and saturation of an element, whose lightness is that of its
+/// `LightLightnessState`. The generic levels of the elements served by the
+/// hue and saturation servers are bound to the hue and saturation.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LightHslState {
+    hue: u16,
+    saturation: u16,
+    default_hue: u16,
+    default_saturation: u16,
+    range: HslRange,
+}
+
+impl Default for LightHslState {
+    fn default() -> Self {
+        let full = Range {
+            min: 0,
+            max: u16::MAX,
+        };
+        Self {
+            hue: 0,
+            saturation: 0,
+            default_hue: 0,
+            default_saturation: 0,
+            range: HslRange {
+                hue: full,
+                saturation: full,
+            },
+        }
+    }
+}
+
+impl LightHslState {
+    /// Power up with the default hue and saturation.
+    pub fn power_up(&mut self) {
+        self.set_hue(self.default_hue);
+        self.set_saturation(self.default_saturation);
+    }
+
+    pub fn hue(&self) -> u16 {
+        self.hue
+    }
+
+    /// Set the hue, restricted to the range.
+    pub fn set_hue(&mut self, hue: u16) {
+        self.hue = self.range.hue.clamp(hue);
+    }
+
+    pub fn saturation(&self) -> u16 {
+        self.saturation
+    }
+
+    /// Set the saturation, restricted to the range.
+    pub fn set_saturation(&mut self, saturation: u16) {
+        self.saturation = self.range.saturation.clamp(saturation);
+    }
+
+    pub fn range(&self) -> HslRange {
+        self.range
+    }
+
+    pub fn set_range(&mut self, range: HslRange) {
+        self.range = range;
+        self.set_hue(self.hue);
+        self.set_saturation(self.saturation);
+    }
+
+    pub fn defaults(&self, lightness: &LightLightnessState) -> HslDefault {
+        HslDefault {
+            lightness: lightness.default_lightness(),
+            hue: self.default_hue,
+            saturation: self.default_saturation,
+        }
+    }
+
+    pub fn set_defaults(&mut self, lightness: &mut LightLightnessState, defaults: &HslDefault) {
+        lightness.set_default_lightness(defaults.lightness);
+        self.default_hue = defaults.hue;
+        self.default_saturation = defaults.saturation;
+    }
+
+    pub fn hue_generic_level(&self) -> i16 {
+        (self.hue as i32 + i16::MIN as i32) as i16
+    }
+
+    pub fn set_hue_generic_level(&mut self, level: i16) {
+        self.set_hue((level as i32 - i16::MIN as i32) as u16);
+    }
+
+    pub fn saturation_generic_level(&self) -> i16 {
+        (self.saturation as i32 + i16::MIN as i32) as i16
+    }
+
+    pub fn set_saturation_generic_level(&mut self, level: i16) {
+        self.set_saturation((level as i32 - i16::MIN as i32) as u16);
+    }
+
+    pub fn status(&self, lightness: &LightLightnessState) -> Status {
+        Status {
+            lightness: lightness.actual(),
+            hue: self.hue,
+            saturation: self.saturation,
+            remaining_time: None,
+        }
+    }
+
+    /// The 8-bit red, green and blue components of the color at `lightness`,
+    /// such as for driving an RGB LED.
+    pub fn to_rgb(&self, lightness: &LightLightnessState) -> (u8, u8, u8) {
+        const MAX: i64 = u16::MAX as i64;
+        let l = lightness.actual() as i64;
+        let s = self.saturation as i64;
+        let c = (MAX - (2 * l - MAX).abs()) * s / MAX;
+        // six sectors of the hue circle, each spanning 0x10000.
+        let h = self.hue as i64 * 6;
+        let x = c * (0x10000 - (h % 0x20000 - 0x10000).abs()) / 0x10000;
+        let m = l - c / 2;
+        let (r, g, b) = match h / 0x10000 {
+            0 => (c, x, 0),
+            1 => (x, c, 0),
+            2 => (0, c, x),
+            3 => (0, x, c),
+            4 => (x, 0, c),
+            _ => (c, 0, x),
+        };
+        (
+            ((r + m) >> 8) as u8,
+            ((g + m) >> 8) as u8,
+            ((b + m) >> 8) as u8,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primary_colors() {
+        let mut lightness = LightLightnessState::default();
+        lightness.set_actual(0x8000);
+        let mut hsl = LightHslState::default();
+        hsl.set_saturation(u16::MAX);
+
+        hsl.set_hue(0);
+        assert_eq!((0xFF, 0x00, 0x00), hsl.to_rgb(&lightness));
+        hsl.set_hue(0x5555);
+        assert_eq!((0x00, 0xFF, 0x00), hsl.to_rgb(&lightness));
+        hsl.set_hue(0xAAAA);
+        assert_eq!((0x00, 0x00, 0xFF), hsl.to_rgb(&lightness));
+
+        hsl.set_saturation(0);
+        lightness.set_actual(u16::MAX);
+        assert_eq!((0xFF, 0xFF, 0xFF), hsl.to_rgb(&lightness));
+    }
+}
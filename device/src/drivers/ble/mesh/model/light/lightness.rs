@@ -0,0 +1,482 @@
+use crate::drivers::ble::mesh::model::generic::power_onoff::{OnPowerUp, PowerOnOffState};
+use crate::drivers::ble::mesh::model::generic::transition::{Transition, TransitionTime};
+use crate::drivers::ble::mesh::model::light::{emit_u16, parse_u16, Range, RangeStatus};
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use heapless::Vec;
+
+#[derive(Clone, Debug)]
+pub struct LightLightnessServer;
+
+#[derive(Clone, Debug)]
+pub struct LightLightnessSetupServer;
+
+#[derive(Clone, Debug)]
+pub struct LightLightnessClient;
+
+pub const LIGHT_LIGHTNESS_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1300);
+pub const LIGHT_LIGHTNESS_SETUP_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1301);
+pub const LIGHT_LIGHTNESS_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1302);
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LightLightnessMessage {
+    Get,
+    Set(Set),
+    SetUnacknowledged(Set),
+    Status(Status),
+    LinearGet,
+    LinearSet(Set),
+    LinearSetUnacknowledged(Set),
+    LinearStatus(Status),
+    LastGet,
+    LastStatus(u16),
+    DefaultGet,
+    DefaultSet(u16),
+    DefaultSetUnacknowledged(u16),
+    DefaultStatus(u16),
+    RangeGet,
+    RangeSet(Range),
+    RangeSetUnacknowledged(Range),
+    RangeStatus(RangeStatus),
+}
+
+impl Message for LightLightnessMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => LIGHT_LIGHTNESS_GET,
+            Self::Set(_) => LIGHT_LIGHTNESS_SET,
+            Self::SetUnacknowledged(_) => LIGHT_LIGHTNESS_SET_UNACKNOWLEDGED,
+            Self::Status(_) => LIGHT_LIGHTNESS_STATUS,
+            Self::LinearGet => LIGHT_LIGHTNESS_LINEAR_GET,
+            Self::LinearSet(_) => LIGHT_LIGHTNESS_LINEAR_SET,
+            Self::LinearSetUnacknowledged(_) => LIGHT_LIGHTNESS_LINEAR_SET_UNACKNOWLEDGED,
+            Self::LinearStatus(_) => LIGHT_LIGHTNESS_LINEAR_STATUS,
+            Self::LastGet => LIGHT_LIGHTNESS_LAST_GET,
+            Self::LastStatus(_) => LIGHT_LIGHTNESS_LAST_STATUS,
+            Self::DefaultGet => LIGHT_LIGHTNESS_DEFAULT_GET,
+            Self::DefaultSet(_) => LIGHT_LIGHTNESS_DEFAULT_SET,
+            Self::DefaultSetUnacknowledged(_) => LIGHT_LIGHTNESS_DEFAULT_SET_UNACKNOWLEDGED,
+            Self::DefaultStatus(_) => LIGHT_LIGHTNESS_DEFAULT_STATUS,
+            Self::RangeGet => LIGHT_LIGHTNESS_RANGE_GET,
+            Self::RangeSet(_) => LIGHT_LIGHTNESS_RANGE_SET,
+            Self::RangeSetUnacknowledged(_) => LIGHT_LIGHTNESS_RANGE_SET_UNACKNOWLEDGED,
+            Self::RangeStatus(_) => LIGHT_LIGHTNESS_RANGE_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut heapless::Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get | Self::LinearGet | Self::LastGet | Self::DefaultGet | Self::RangeGet => {
+                Ok(())
+            }
+            Self::Set(inner)
+            | Self::SetUnacknowledged(inner)
+            | Self::LinearSet(inner)
+            | Self::LinearSetUnacknowledged(inner) => inner.emit_parameters(xmit),
+            Self::Status(inner) | Self::LinearStatus(inner) => inner.emit_parameters(xmit),
+            Self::LastStatus(inner)
+            | Self::DefaultSet(inner)
+            | Self::DefaultSetUnacknowledged(inner)
+            | Self::DefaultStatus(inner) => emit_u16(*inner, xmit),
+            Self::RangeSet(inner) | Self::RangeSetUnacknowledged(inner) => {
+                inner.emit_parameters(xmit)
+            }
+            Self::RangeStatus(inner) => inner.emit_parameters(xmit),
+        }
+    }
+}
+
+impl Model for LightLightnessServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_LIGHTNESS_SERVER;
+    type Message<'m> = LightLightnessMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_LIGHTNESS_GET => Ok(Some(LightLightnessMessage::Get)),
+            LIGHT_LIGHTNESS_SET => Ok(Some(LightLightnessMessage::Set(Set::parse(parameters)?))),
+            LIGHT_LIGHTNESS_SET_UNACKNOWLEDGED => Ok(Some(
+                LightLightnessMessage::SetUnacknowledged(Set::parse(parameters)?),
+            )),
+            LIGHT_LIGHTNESS_LINEAR_GET => Ok(Some(LightLightnessMessage::LinearGet)),
+            LIGHT_LIGHTNESS_LINEAR_SET => Ok(Some(LightLightnessMessage::LinearSet(Set::parse(
+                parameters,
+            )?))),
+            LIGHT_LIGHTNESS_LINEAR_SET_UNACKNOWLEDGED => Ok(Some(
+                LightLightnessMessage::LinearSetUnacknowledged(Set::parse(parameters)?),
+            )),
+            LIGHT_LIGHTNESS_LAST_GET => Ok(Some(LightLightnessMessage::LastGet)),
+            LIGHT_LIGHTNESS_DEFAULT_GET => Ok(Some(LightLightnessMessage::DefaultGet)),
+            LIGHT_LIGHTNESS_RANGE_GET => Ok(Some(LightLightnessMessage::RangeGet)),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightLightnessSetupServer {
+    const IDENTIFIER: ModelIdentifier = LIGHT_LIGHTNESS_SETUP_SERVER;
+    const SUPPORTS_PUBLICATION: bool = false;
+    type Message<'m> = LightLightnessMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_LIGHTNESS_DEFAULT_SET => Ok(Some(LightLightnessMessage::DefaultSet(parse_u16(
+                parameters,
+            )?))),
+            LIGHT_LIGHTNESS_DEFAULT_SET_UNACKNOWLEDGED => Ok(Some(
+                LightLightnessMessage::DefaultSetUnacknowledged(parse_u16(parameters)?),
+            )),
+            LIGHT_LIGHTNESS_RANGE_SET => Ok(Some(LightLightnessMessage::RangeSet(parse_range(
+                parameters,
+            )?))),
+            LIGHT_LIGHTNESS_RANGE_SET_UNACKNOWLEDGED => Ok(Some(
+                LightLightnessMessage::RangeSetUnacknowledged(parse_range(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for LightLightnessClient {
+    const IDENTIFIER: ModelIdentifier = LIGHT_LIGHTNESS_CLIENT;
+    type Message<'m> = LightLightnessMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            LIGHT_LIGHTNESS_STATUS => Ok(Some(LightLightnessMessage::Status(Status::parse(
+                parameters,
+            )?))),
+            LIGHT_LIGHTNESS_LINEAR_STATUS => Ok(Some(LightLightnessMessage::LinearStatus(
+                Status::parse(parameters)?,
+            ))),
+            LIGHT_LIGHTNESS_LAST_STATUS => Ok(Some(LightLightnessMessage::LastStatus(parse_u16(
+                parameters,
+            )?))),
+            LIGHT_LIGHTNESS_DEFAULT_STATUS => Ok(Some(LightLightnessMessage::DefaultStatus(
+                parse_u16(parameters)?,
+            ))),
+            LIGHT_LIGHTNESS_RANGE_STATUS => Ok(Some(LightLightnessMessage::RangeStatus(
+                RangeStatus::parse(parameters)?,
+            ))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// A lightness range excludes zero, which always means off.
+fn parse_range(parameters: &[u8]) -> Result<Range, ParseError> {
+    let range = Range::parse(parameters)?;
+    if range.min == 0 {
+        Err(ParseError::InvalidValue)
+    } else {
+        Ok(range)
+    }
+}
+
+opcode!( LIGHT_LIGHTNESS_GET 0x82, 0x4B );
+opcode!( LIGHT_LIGHTNESS_SET 0x82, 0x4C );
+opcode!( LIGHT_LIGHTNESS_SET_UNACKNOWLEDGED 0x82, 0x4D );
+opcode!( LIGHT_LIGHTNESS_STATUS 0x82, 0x4E );
+opcode!( LIGHT_LIGHTNESS_LINEAR_GET 0x82, 0x4F );
+opcode!( LIGHT_LIGHTNESS_LINEAR_SET 0x82, 0x50 );
+opcode!( LIGHT_LIGHTNESS_LINEAR_SET_UNACKNOWLEDGED 0x82, 0x51 );
+opcode!( LIGHT_LIGHTNESS_LINEAR_STATUS 0x82, 0x52 );
+opcode!( LIGHT_LIGHTNESS_LAST_GET 0x82, 0x53 );
+opcode!( LIGHT_LIGHTNESS_LAST_STATUS 0x82, 0x54 );
+opcode!( LIGHT_LIGHTNESS_DEFAULT_GET 0x82, 0x55 );
+opcode!( LIGHT_LIGHTNESS_DEFAULT_STATUS 0x82, 0x56 );
+opcode!( LIGHT_LIGHTNESS_RANGE_GET 0x82, 0x57 );
+opcode!( LIGHT_LIGHTNESS_RANGE_STATUS 0x82, 0x58 );
+opcode!( LIGHT_LIGHTNESS_DEFAULT_SET 0x82, 0x59 );
+opcode!( LIGHT_LIGHTNESS_DEFAULT_SET_UNACKNOWLEDGED 0x82, 0x5A );
+opcode!( LIGHT_LIGHTNESS_RANGE_SET 0x82, 0x5B );
+opcode!( LIGHT_LIGHTNESS_RANGE_SET_UNACKNOWLEDGED 0x82, 0x5C );
+
+/// Set the actual or linear lightness.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Set {
+    pub lightness: u16,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl Set {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 3 {
+            Ok(Self {
+                lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                tid: parameters[2],
+                transition: Transition::parse(&parameters[3..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.lightness, xmit)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+/// The actual or linear lightness.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status {
+    pub present_lightness: u16,
+    /// The lightness being transitioned to and the time remaining, if in transition.
+    pub target: Option<(u16, TransitionTime)>,
+}
+
+impl Status {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        match parameters.len() {
+            2 => Ok(Self {
+                present_lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                target: None,
+            }),
+            5 => Ok(Self {
+                present_lightness: u16::from_le_bytes([parameters[0], parameters[1]]),
+                target: Some((
+                    u16::from_le_bytes([parameters[2], parameters[3]]),
+                    TransitionTime(parameters[4]),
+                )),
+            }),
+            _ => Err(ParseError::InvalidLength),
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u16(self.present_lightness, xmit)?;
+        if let Some((target_lightness, remaining_time)) = &self.target {
+            emit_u16(*target_lightness, xmit)?;
+            xmit.push(remaining_time.0)
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+/// The lightness of an element, along with the states bound to it: the
+/// linear lightness, and the generic onoff and level of the same element.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LightLightnessState {
+    actual: u16,
+    last: u16,
+    default: u16,
+    range: Range,
+}
+
+impl Default for LightLightnessState {
+    fn default() -> Self {
+        Self {
+            actual: 0,
+            last: u16::MAX,
+            default: 0,
+            range: Range {
+                min: 1,
+                max: u16::MAX,
+            },
+        }
+    }
+}
+
+impl LightLightnessState {
+    /// Power up according to the stored power onoff state, whose level is
+    /// that bound to the lightness.
+    pub fn power_up(&mut self, state: &PowerOnOffState) {
+        match state.on_power_up {
+            OnPowerUp::Off => self.actual = 0,
+            OnPowerUp::Default => self.set_generic_on_off(1),
+            OnPowerUp::Restore => {
+                self.set_generic_level(state.level);
+                if state.on_off == 0 {
+                    self.actual = 0;
+                }
+            }
+        }
+    }
+
+    pub fn actual(&self) -> u16 {
+        self.actual
+    }
+
+    /// Set the lightness, restricted to the range unless off.
+    pub fn set_actual(&mut self, lightness: u16) {
+        if lightness == 0 {
+            self.actual = 0;
+        } else {
+            self.actual = self.range.clamp(lightness);
+            self.last = self.actual;
+        }
+    }
+
+    /// The perceptually uniform lightness converted to a measured one.
+    pub fn linear(&self) -> u16 {
+        let actual = self.actual as u32;
+        ((actual * actual + u16::MAX as u32 - 1) / u16::MAX as u32) as u16
+    }
+
+    pub fn set_linear(&mut self, linear: u16) {
+        self.set_actual(isqrt(linear as u32 * u16::MAX as u32) as u16);
+    }
+
+    /// The last lightness other than off.
+    pub fn last(&self) -> u16 {
+        self.last
+    }
+
+    /// The lightness to turn on with, zero meaning the last one.
+    pub fn default_lightness(&self) -> u16 {
+        self.default
+    }
+
+    pub fn set_default_lightness(&mut self, lightness: u16) {
+        self.default = lightness;
+    }
+
+    pub fn range(&self) -> Range {
+        self.range
+    }
+
+    pub fn set_range(&mut self, range: Range) {
+        self.range = range;
+        if self.actual != 0 {
+            self.actual = self.range.clamp(self.actual);
+        }
+    }
+
+    pub fn generic_on_off(&self) -> u8 {
+        if self.actual == 0 {
+            0
+        } else {
+            1
+        }
+    }
+
+    pub fn set_generic_on_off(&mut self, on_off: u8) {
+        if on_off == 0 {
+            self.actual = 0;
+        } else if self.default == 0 {
+            self.set_actual(self.last);
+        } else {
+            self.set_actual(self.default);
+        }
+    }
+
+    pub fn generic_level(&self) -> i16 {
+        (self.actual as i32 + i16::MIN as i32) as i16
+    }
+
+    pub fn set_generic_level(&mut self, level: i16) {
+        self.set_actual((level as i32 - i16::MIN as i32) as u16);
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            present_lightness: self.actual,
+            target: None,
+        }
+    }
+
+    pub fn linear_status(&self) -> Status {
+        Status {
+            present_lightness: self.linear(),
+            target: None,
+        }
+    }
+}
+
+fn isqrt(value: u32) -> u32 {
+    let mut root = 0;
+    let mut bit = 1 << 30;
+    let mut value = value;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if value >= root + bit {
+            value -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_round_trip() {
+        let mut state = LightLightnessState::default();
+        for actual in [0x7FFF, 0x8000, 0xC000, 0xFFFF] {
+            state.set_actual(actual);
+            let linear = state.linear();
+            state.set_linear(linear);
+            assert!(state.actual().abs_diff(actual) <= 1);
+        }
+        state.set_linear(0xFFFF);
+        assert_eq!(0xFFFF, state.actual());
+    }
+
+    #[test]
+    fn bound_generic_states() {
+        let mut state = LightLightnessState::default();
+        state.set_generic_level(i16::MIN);
+        assert_eq!(0, state.actual());
+        assert_eq!(0, state.generic_on_off());
+
+        state.set_generic_level(0);
+        assert_eq!(0x8000, state.actual());
+        state.set_generic_on_off(0);
+        assert_eq!(0, state.actual());
+        state.set_generic_on_off(1);
+        assert_eq!(0x8000, state.actual());
+
+        state.set_default_lightness(0x1000);
+        state.set_generic_on_off(0);
+        state.set_generic_on_off(1);
+        assert_eq!(0x1000, state.actual());
+        assert_eq!(0x1000 + i16::MIN as i32, state.generic_level() as i32);
+    }
+}
@@ -0,0 +1,547 @@
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::generic::transition::{Transition, TransitionTime};
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::storage::{load_versioned, store_versioned, Storage};
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of scenes held by the scene register.
+pub const MAX_SCENES: usize = 16;
+
+/// Maximum size of the state stored for a single scene.
+pub const MAX_SCENE_STATE: usize = 24;
+
+#[derive(Clone, Debug)]
+pub struct SceneServer;
+
+#[derive(Clone, Debug)]
+pub struct SceneSetupServer;
+
+#[derive(Clone, Debug)]
+pub struct SceneClient;
+
+pub const SCENE_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1203);
+pub const SCENE_SETUP_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1204);
+pub const SCENE_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1205);
+
+/// Implemented by the application to capture and restore the state of
+/// the models taking part in scenes.
+pub trait SceneHandler {
+    /// Capture the present state of the models into `state`.
+    fn snapshot(&self, state: &mut Vec<u8, MAX_SCENE_STATE>) -> Result<(), InsufficientBuffer>;
+
+    /// Restore the state previously captured by `snapshot`, over the
+    /// `transition` if any, or the default transition time otherwise.
+    fn restore(&mut self, state: &[u8], transition: Option<Transition>) -> Result<(), ParseError>;
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SceneMessage {
+    Get,
+    Recall(Recall),
+    RecallUnacknowledged(Recall),
+    Status(Status),
+    RegisterGet,
+    RegisterStatus(RegisterStatus),
+    Store(u16),
+    StoreUnacknowledged(u16),
+    Delete(u16),
+    DeleteUnacknowledged(u16),
+}
+
+impl Message for SceneMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => SCENE_GET,
+            Self::Recall(_) => SCENE_RECALL,
+            Self::RecallUnacknowledged(_) => SCENE_RECALL_UNACKNOWLEDGED,
+            Self::Status(_) => SCENE_STATUS,
+            Self::RegisterGet => SCENE_REGISTER_GET,
+            Self::RegisterStatus(_) => SCENE_REGISTER_STATUS,
+            Self::Store(_) => SCENE_STORE,
+            Self::StoreUnacknowledged(_) => SCENE_STORE_UNACKNOWLEDGED,
+            Self::Delete(_) => SCENE_DELETE,
+            Self::DeleteUnacknowledged(_) => SCENE_DELETE_UNACKNOWLEDGED,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get | Self::RegisterGet => Ok(()),
+            Self::Recall(inner) | Self::RecallUnacknowledged(inner) => inner.emit_parameters(xmit),
+            Self::Status(inner) => inner.emit_parameters(xmit),
+            Self::RegisterStatus(inner) => inner.emit_parameters(xmit),
+            Self::Store(scene_number)
+            | Self::StoreUnacknowledged(scene_number)
+            | Self::Delete(scene_number)
+            | Self::DeleteUnacknowledged(scene_number) => xmit
+                .extend_from_slice(&scene_number.to_le_bytes())
+                .map_err(|_| InsufficientBuffer),
+        }
+    }
+}
+
+impl Model for SceneServer {
+    const IDENTIFIER: ModelIdentifier = SCENE_SERVER;
+    type Message<'m> = SceneMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            SCENE_GET => Ok(Some(SceneMessage::Get)),
+            SCENE_RECALL => Ok(Some(SceneMessage::Recall(Recall::parse(parameters)?))),
+            SCENE_RECALL_UNACKNOWLEDGED => Ok(Some(SceneMessage::RecallUnacknowledged(
+                Recall::parse(parameters)?,
+            ))),
+            SCENE_REGISTER_GET => Ok(Some(SceneMessage::RegisterGet)),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for SceneSetupServer {
+    const IDENTIFIER: ModelIdentifier = SCENE_SETUP_SERVER;
+    const SUPPORTS_PUBLICATION: bool = false;
+    type Message<'m> = SceneMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            SCENE_STORE => Ok(Some(SceneMessage::Store(parse_scene_number(parameters)?))),
+            SCENE_STORE_UNACKNOWLEDGED => Ok(Some(SceneMessage::StoreUnacknowledged(
+                parse_scene_number(parameters)?,
+            ))),
+            SCENE_DELETE => Ok(Some(SceneMessage::Delete(parse_scene_number(parameters)?))),
+            SCENE_DELETE_UNACKNOWLEDGED => Ok(Some(SceneMessage::DeleteUnacknowledged(
+                parse_scene_number(parameters)?,
+            ))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for SceneClient {
+    const IDENTIFIER: ModelIdentifier = SCENE_CLIENT;
+    type Message<'m> = SceneMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            SCENE_STATUS => Ok(Some(SceneMessage::Status(Status::parse(parameters)?))),
+            SCENE_REGISTER_STATUS => Ok(Some(SceneMessage::RegisterStatus(RegisterStatus::parse(
+                parameters,
+            )?))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+opcode!( SCENE_STATUS 0x5E );
+opcode!( SCENE_GET 0x82, 0x41 );
+opcode!( SCENE_RECALL 0x82, 0x42 );
+opcode!( SCENE_RECALL_UNACKNOWLEDGED 0x82, 0x43 );
+opcode!( SCENE_REGISTER_GET 0x82, 0x44 );
+opcode!( SCENE_REGISTER_STATUS 0x82, 0x45 );
+opcode!( SCENE_STORE 0x82, 0x46 );
+opcode!( SCENE_STORE_UNACKNOWLEDGED 0x82, 0x47 );
+opcode!( SCENE_DELETE 0x82, 0x9E );
+opcode!( SCENE_DELETE_UNACKNOWLEDGED 0x82, 0x9F );
+
+/// Scene number 0x0000 is prohibited, meaning no scene.
+fn parse_scene_number(parameters: &[u8]) -> Result<u16, ParseError> {
+    if parameters.len() >= 2 {
+        match u16::from_le_bytes([parameters[0], parameters[1]]) {
+            0 => Err(ParseError::InvalidValue),
+            scene_number => Ok(scene_number),
+        }
+    } else {
+        Err(ParseError::InvalidLength)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StatusCode {
+    Success = 0x00,
+    RegisterFull = 0x01,
+    NotFound = 0x02,
+}
+
+impl StatusCode {
+    fn parse(val: u8) -> Result<Self, ParseError> {
+        match val {
+            0x00 => Ok(Self::Success),
+            0x01 => Ok(Self::RegisterFull),
+            0x02 => Ok(Self::NotFound),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+}
+
+/// Recall the scene numbered `scene_number`.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Recall {
+    pub scene_number: u16,
+    pub tid: u8,
+    pub transition: Option<Transition>,
+}
+
+impl Recall {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 3 {
+            Ok(Self {
+                scene_number: parse_scene_number(parameters)?,
+                tid: parameters[2],
+                transition: Transition::parse(&parameters[3..])?,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.scene_number.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.tid).map_err(|_| InsufficientBuffer)?;
+        if let Some(transition) = &self.transition {
+            transition.emit_parameters(xmit)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status {
+    pub status_code: StatusCode,
+    pub current_scene: u16,
+    /// The scene being transitioned to and the time remaining, if in transition.
+    pub target: Option<(u16, TransitionTime)>,
+}
+
+impl Status {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 3 || parameters.len() == 6 {
+            let target = if parameters.len() == 6 {
+                Some((
+                    u16::from_le_bytes([parameters[3], parameters[4]]),
+                    TransitionTime(parameters[5]),
+                ))
+            } else {
+                None
+            };
+            Ok(Self {
+                status_code: StatusCode::parse(parameters[0])?,
+                current_scene: u16::from_le_bytes([parameters[1], parameters[2]]),
+                target,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.status_code as u8)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.current_scene.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        if let Some((target_scene, remaining_time)) = &self.target {
+            xmit.extend_from_slice(&target_scene.to_le_bytes())
+                .map_err(|_| InsufficientBuffer)?;
+            xmit.push(remaining_time.0)
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterStatus {
+    pub status_code: StatusCode,
+    pub current_scene: u16,
+    pub scenes: Vec<u16, MAX_SCENES>,
+}
+
+impl RegisterStatus {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() >= 3 && parameters.len() % 2 == 1 {
+            let mut scenes = Vec::new();
+            for scene in parameters[3..].chunks_exact(2) {
+                scenes
+                    .push(u16::from_le_bytes([scene[0], scene[1]]))
+                    .map_err(|_| ParseError::InsufficientBuffer)?;
+            }
+            Ok(Self {
+                status_code: StatusCode::parse(parameters[0])?,
+                current_scene: u16::from_le_bytes([parameters[1], parameters[2]]),
+                scenes,
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.status_code as u8)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.current_scene.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        for scene in &self.scenes {
+            xmit.extend_from_slice(&scene.to_le_bytes())
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Scene {
+    number: u16,
+    state: Vec<u8, MAX_SCENE_STATE>,
+}
+
+/// Version of the layout of a stored [`SceneRegister`].
+const SCENE_REGISTER_VERSION: u16 = 1;
+
+/// The scenes stored on an element, to be kept in a `Storage` of its own
+/// apart from the node configuration.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SceneRegister {
+    current_scene: u16,
+    scenes: Vec<Scene, MAX_SCENES>,
+}
+
+impl SceneRegister {
+    /// Retrieve the stored register, or an empty one if none has been stored yet.
+    /// A stored register failing to decode is an error, rather than replaced.
+    pub async fn load<S: Storage>(storage: &mut S) -> Result<Self, DeviceError> {
+        Ok(load_versioned(storage, SCENE_REGISTER_VERSION)
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub async fn store<S: Storage>(&self, storage: &mut S) -> Result<(), DeviceError> {
+        store_versioned(storage, SCENE_REGISTER_VERSION, self).await
+    }
+
+    /// The most recently stored or recalled scene, or 0 if none.
+    pub fn current_scene(&self) -> u16 {
+        self.current_scene
+    }
+
+    /// To be called when the state of the models changes other than by
+    /// recalling a scene, which no longer matches the current scene.
+    pub fn invalidate(&mut self) {
+        self.current_scene = 0;
+    }
+
+    pub fn status(&self) -> Status {
+        Status {
+            status_code: StatusCode::Success,
+            current_scene: self.current_scene,
+            target: None,
+        }
+    }
+
+    pub fn register_status(&self, status_code: StatusCode) -> RegisterStatus {
+        RegisterStatus {
+            status_code,
+            current_scene: self.current_scene,
+            scenes: self.scenes.iter().map(|scene| scene.number).collect(),
+        }
+    }
+
+    /// Process a message of the scene server or scene setup server,
+    /// persisting any change to the register in `storage`. Returns the
+    /// response to an acknowledged message.
+    pub async fn process<H: SceneHandler, S: Storage>(
+        &mut self,
+        message: &SceneMessage,
+        handler: &mut H,
+        storage: &mut S,
+    ) -> Result<Option<SceneMessage>, DeviceError> {
+        match message {
+            SceneMessage::Get => Ok(Some(SceneMessage::Status(self.status()))),
+            SceneMessage::Recall(recall) => {
+                Ok(Some(SceneMessage::Status(self.recall(recall, handler)?)))
+            }
+            SceneMessage::RecallUnacknowledged(recall) => {
+                self.recall(recall, handler)?;
+                Ok(None)
+            }
+            SceneMessage::RegisterGet => Ok(Some(SceneMessage::RegisterStatus(
+                self.register_status(StatusCode::Success),
+            ))),
+            SceneMessage::Store(scene_number) => {
+                let status_code = self.store_scene(*scene_number, handler, storage).await?;
+                Ok(Some(SceneMessage::RegisterStatus(
+                    self.register_status(status_code),
+                )))
+            }
+            SceneMessage::StoreUnacknowledged(scene_number) => {
+                self.store_scene(*scene_number, handler, storage).await?;
+                Ok(None)
+            }
+            SceneMessage::Delete(scene_number) => {
+                self.delete_scene(*scene_number, storage).await?;
+                Ok(Some(SceneMessage::RegisterStatus(
+                    self.register_status(StatusCode::Success),
+                )))
+            }
+            SceneMessage::DeleteUnacknowledged(scene_number) => {
+                self.delete_scene(*scene_number, storage).await?;
+                Ok(None)
+            }
+            _ => {
+                // not applicable to server role
+                Ok(None)
+            }
+        }
+    }
+
+    fn recall<H: SceneHandler>(
+        &mut self,
+        recall: &Recall,
+        handler: &mut H,
+    ) -> Result<Status, DeviceError> {
+        if let Some(scene) = self
+            .scenes
+            .iter()
+            .find(|scene| scene.number == recall.scene_number)
+        {
+            handler.restore(&scene.state, recall.transition)?;
+            self.current_scene = recall.scene_number;
+            let target = recall
+                .transition
+                .filter(|transition| transition.transition_time != TransitionTime::IMMEDIATE)
+                .map(|transition| (recall.scene_number, transition.transition_time));
+            Ok(Status {
+                status_code: StatusCode::Success,
+                current_scene: self.current_scene,
+                target,
+            })
+        } else {
+            Ok(Status {
+                status_code: StatusCode::NotFound,
+                current_scene: self.current_scene,
+                target: None,
+            })
+        }
+    }
+
+    async fn store_scene<H: SceneHandler, S: Storage>(
+        &mut self,
+        scene_number: u16,
+        handler: &mut H,
+        storage: &mut S,
+    ) -> Result<StatusCode, DeviceError> {
+        let mut state = Vec::new();
+        handler.snapshot(&mut state)?;
+        if let Some(scene) = self
+            .scenes
+            .iter_mut()
+            .find(|scene| scene.number == scene_number)
+        {
+            scene.state = state;
+        } else if self
+            .scenes
+            .push(Scene {
+                number: scene_number,
+                state,
+            })
+            .is_err()
+        {
+            return Ok(StatusCode::RegisterFull);
+        }
+        self.current_scene = scene_number;
+        self.store(storage).await?;
+        Ok(StatusCode::Success)
+    }
+
+    async fn delete_scene<S: Storage>(
+        &mut self,
+        scene_number: u16,
+        storage: &mut S,
+    ) -> Result<(), DeviceError> {
+        let len = self.scenes.len();
+        self.scenes.retain(|scene| scene.number != scene_number);
+        if self.current_scene == scene_number {
+            self.current_scene = 0;
+        }
+        if self.scenes.len() != len {
+            self.store(storage).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_status_round_trip() {
+        let status = RegisterStatus {
+            status_code: StatusCode::RegisterFull,
+            current_scene: 2,
+            scenes: Vec::from_slice(&[1, 2, 0x1234]).unwrap(),
+        };
+        let mut xmit: Vec<u8, 32> = Vec::new();
+        status.emit_parameters(&mut xmit).unwrap();
+        assert_eq!(
+            &[0x01, 0x02, 0x00, 0x01, 0x00, 0x02, 0x00, 0x34, 0x12],
+            &xmit[..]
+        );
+
+        let parsed = RegisterStatus::parse(&xmit).unwrap();
+        assert_eq!(StatusCode::RegisterFull, parsed.status_code);
+        assert_eq!(2, parsed.current_scene);
+        assert_eq!(status.scenes, parsed.scenes);
+    }
+
+    #[test]
+    fn prohibited_scene_number() {
+        assert!(matches!(
+            SceneSetupServer::parse(SCENE_STORE, &[0x00, 0x00]),
+            Err(ParseError::InvalidValue)
+        ));
+        assert!(matches!(
+            SceneServer::parse(SCENE_RECALL, &[0x00, 0x00, 0x01]),
+            Err(ParseError::InvalidValue)
+        ));
+    }
+}
@@ -0,0 +1,515 @@
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::generic::transition::TransitionTime;
+use crate::drivers::ble::mesh::model::time::{DateTime, Time};
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::storage::{load_versioned, store_versioned, Storage};
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Number of entries of the schedule register.
+pub const MAX_SCHEDULES: usize = 16;
+
+const ANY_YEAR: u8 = 0x64;
+const ANY_DAY: u8 = 0x00;
+const ANY_HOUR: u8 = 0x18;
+const ONCE_A_DAY: u8 = 0x19;
+const ANY_MINUTE: u8 = 0x3C;
+const EVERY_15: u8 = 0x3D;
+const EVERY_20: u8 = 0x3E;
+const ONCE: u8 = 0x3F;
+
+#[derive(Clone, Debug)]
+pub struct SchedulerServer;
+
+#[derive(Clone, Debug)]
+pub struct SchedulerSetupServer;
+
+#[derive(Clone, Debug)]
+pub struct SchedulerClient;
+
+pub const SCHEDULER_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1206);
+pub const SCHEDULER_SETUP_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1207);
+pub const SCHEDULER_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1208);
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SchedulerMessage {
+    Get,
+    /// Bit field of the defined entries of the schedule register.
+    Status(u16),
+    ActionGet(u8),
+    ActionSet(SchedulerAction),
+    ActionSetUnacknowledged(SchedulerAction),
+    ActionStatus(SchedulerAction),
+}
+
+impl Message for SchedulerMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => SCHEDULER_GET,
+            Self::Status(_) => SCHEDULER_STATUS,
+            Self::ActionGet(_) => SCHEDULER_ACTION_GET,
+            Self::ActionSet(_) => SCHEDULER_ACTION_SET,
+            Self::ActionSetUnacknowledged(_) => SCHEDULER_ACTION_SET_UNACKNOWLEDGED,
+            Self::ActionStatus(_) => SCHEDULER_ACTION_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get => Ok(()),
+            Self::Status(schedules) => xmit
+                .extend_from_slice(&schedules.to_le_bytes())
+                .map_err(|_| InsufficientBuffer),
+            Self::ActionGet(index) => xmit.push(*index).map_err(|_| InsufficientBuffer),
+            Self::ActionSet(inner)
+            | Self::ActionSetUnacknowledged(inner)
+            | Self::ActionStatus(inner) => inner.emit_parameters(xmit),
+        }
+    }
+}
+
+impl Model for SchedulerServer {
+    const IDENTIFIER: ModelIdentifier = SCHEDULER_SERVER;
+    type Message<'m> = SchedulerMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            SCHEDULER_GET => Ok(Some(SchedulerMessage::Get)),
+            SCHEDULER_ACTION_GET => {
+                if parameters.len() == 1 {
+                    if (parameters[0] as usize) < MAX_SCHEDULES {
+                        Ok(Some(SchedulerMessage::ActionGet(parameters[0])))
+                    } else {
+                        Err(ParseError::InvalidValue)
+                    }
+                } else {
+                    Err(ParseError::InvalidLength)
+                }
+            }
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for SchedulerSetupServer {
+    const IDENTIFIER: ModelIdentifier = SCHEDULER_SETUP_SERVER;
+    const SUPPORTS_PUBLICATION: bool = false;
+    type Message<'m> = SchedulerMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            SCHEDULER_ACTION_SET => Ok(Some(SchedulerMessage::ActionSet(SchedulerAction::parse(
+                parameters,
+            )?))),
+            SCHEDULER_ACTION_SET_UNACKNOWLEDGED => Ok(Some(
+                SchedulerMessage::ActionSetUnacknowledged(SchedulerAction::parse(parameters)?),
+            )),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for SchedulerClient {
+    const IDENTIFIER: ModelIdentifier = SCHEDULER_CLIENT;
+    type Message<'m> = SchedulerMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            SCHEDULER_STATUS => {
+                if parameters.len() == 2 {
+                    Ok(Some(SchedulerMessage::Status(u16::from_le_bytes([
+                        parameters[0],
+                        parameters[1],
+                    ]))))
+                } else {
+                    Err(ParseError::InvalidLength)
+                }
+            }
+            SCHEDULER_ACTION_STATUS => Ok(Some(SchedulerMessage::ActionStatus(
+                SchedulerAction::parse(parameters)?,
+            ))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+opcode!( SCHEDULER_ACTION_STATUS 0x5F );
+opcode!( SCHEDULER_ACTION_SET 0x60 );
+opcode!( SCHEDULER_ACTION_SET_UNACKNOWLEDGED 0x61 );
+opcode!( SCHEDULER_ACTION_GET 0x82, 0x48 );
+opcode!( SCHEDULER_GET 0x82, 0x49 );
+opcode!( SCHEDULER_STATUS 0x82, 0x4A );
+
+/// The action performed when a schedule entry is due.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ScheduleAction {
+    TurnOff = 0x0,
+    TurnOn = 0x1,
+    SceneRecall = 0x2,
+    /// The entry is not defined.
+    NoAction = 0xF,
+}
+
+impl Default for ScheduleAction {
+    fn default() -> Self {
+        Self::NoAction
+    }
+}
+
+impl ScheduleAction {
+    fn parse(val: u8) -> Result<Self, ParseError> {
+        match val {
+            0x0 => Ok(Self::TurnOff),
+            0x1 => Ok(Self::TurnOn),
+            0x2 => Ok(Self::SceneRecall),
+            0xF => Ok(Self::NoAction),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+}
+
+/// An entry of the schedule register, matching the local time.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ScheduleEntry {
+    /// Years since 2000, or 0x64 for any year.
+    pub year: u8,
+    /// Bit field of months, bit 0 being January.
+    pub month: u16,
+    /// Day of the month, or 0 for any day.
+    pub day: u8,
+    /// Hour of the day, 0x18 for any hour or 0x19 for once a day.
+    pub hour: u8,
+    /// Minute of the hour, 0x3C for any minute, 0x3D for every 15 minutes,
+    /// 0x3E for every 20 minutes or 0x3F for once an hour.
+    pub minute: u8,
+    /// Second of the minute, as for the minute.
+    pub second: u8,
+    /// Bit field of days of the week, bit 0 being Monday.
+    pub day_of_week: u8,
+    pub action: ScheduleAction,
+    pub transition_time: TransitionTime,
+    /// The scene recalled by a scene recall action.
+    pub scene_number: u16,
+}
+
+impl Default for ScheduleEntry {
+    fn default() -> Self {
+        Self {
+            year: 0,
+            month: 0,
+            day: 0,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            day_of_week: 0,
+            action: ScheduleAction::NoAction,
+            transition_time: TransitionTime::IMMEDIATE,
+            scene_number: 0,
+        }
+    }
+}
+
+impl ScheduleEntry {
+    pub fn is_defined(&self) -> bool {
+        self.action != ScheduleAction::NoAction
+    }
+
+    /// Whether the entry is due at the local `time`. Entries to be run at
+    /// a random hour, minute or second instead run at one picked from the
+    /// `index` of the entry.
+    pub fn matches(&self, index: u8, time: &DateTime) -> bool {
+        let index = index as u16;
+        self.is_defined()
+            && (self.year == ANY_YEAR || self.year as u16 + 2000 == time.year)
+            && self.month & (1 << (time.month - 1)) != 0
+            && (self.day == ANY_DAY || self.day == time.day)
+            && self.day_of_week & (1 << time.day_of_week) != 0
+            && match self.hour {
+                ANY_HOUR => true,
+                ONCE_A_DAY => (index * 7 % 24) as u8 == time.hour,
+                hour => hour == time.hour,
+            }
+            && Self::matches_sixty(self.minute, (index * 13 % 60) as u8, time.minute)
+            && Self::matches_sixty(self.second, (index * 17 % 60) as u8, time.second)
+    }
+
+    fn matches_sixty(val: u8, once: u8, actual: u8) -> bool {
+        match val {
+            ANY_MINUTE => true,
+            EVERY_15 => actual % 15 == 0,
+            EVERY_20 => actual % 20 == 0,
+            ONCE => actual == once,
+            val => val == actual,
+        }
+    }
+}
+
+/// An entry of the schedule register along with its index.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SchedulerAction {
+    pub index: u8,
+    pub entry: ScheduleEntry,
+}
+
+impl SchedulerAction {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 10 {
+            let mut bytes = [0; 16];
+            bytes[0..10].copy_from_slice(parameters);
+            let mut bits = u128::from_le_bytes(bytes);
+            let mut take = |len: u32| {
+                let val = bits & ((1 << len) - 1);
+                bits >>= len;
+                val
+            };
+
+            let index = take(4) as u8;
+            let entry = ScheduleEntry {
+                year: take(7) as u8,
+                month: take(12) as u16,
+                day: take(5) as u8,
+                hour: take(5) as u8,
+                minute: take(6) as u8,
+                second: take(6) as u8,
+                day_of_week: take(7) as u8,
+                action: ScheduleAction::parse(take(4) as u8)?,
+                transition_time: TransitionTime(take(8) as u8),
+                scene_number: take(16) as u16,
+            };
+            if entry.year > ANY_YEAR || entry.hour > ONCE_A_DAY {
+                Err(ParseError::InvalidValue)
+            } else {
+                Ok(Self { index, entry })
+            }
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        let entry = &self.entry;
+        let fields: [(u128, u32); 11] = [
+            (self.index as u128, 4),
+            (entry.year as u128, 7),
+            (entry.month as u128, 12),
+            (entry.day as u128, 5),
+            (entry.hour as u128, 5),
+            (entry.minute as u128, 6),
+            (entry.second as u128, 6),
+            (entry.day_of_week as u128, 7),
+            (entry.action as u128, 4),
+            (entry.transition_time.0 as u128, 8),
+            (entry.scene_number as u128, 16),
+        ];
+        let mut bits = 0u128;
+        let mut offset = 0;
+        for (val, len) in fields {
+            bits |= (val & ((1 << len) - 1)) << offset;
+            offset += len;
+        }
+        xmit.extend_from_slice(&bits.to_le_bytes()[0..10])
+            .map_err(|_| InsufficientBuffer)
+    }
+}
+
+/// Version of the layout of a stored [`SchedulerRegister`].
+const SCHEDULER_REGISTER_VERSION: u16 = 1;
+
+/// The schedule register of an element, to be kept in a `Storage` of its
+/// own apart from the node configuration.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct SchedulerRegister {
+    entries: [ScheduleEntry; MAX_SCHEDULES],
+    /// The last TAI second checked for due entries.
+    #[serde(skip)]
+    last_checked: Option<u64>,
+}
+
+impl SchedulerRegister {
+    /// Retrieve the stored register, or an empty one if none has been stored yet.
+    /// A stored register failing to decode is an error, rather than replaced.
+    pub async fn load<S: Storage>(storage: &mut S) -> Result<Self, DeviceError> {
+        Ok(load_versioned(storage, SCHEDULER_REGISTER_VERSION)
+            .await?
+            .unwrap_or_default())
+    }
+
+    pub async fn store<S: Storage>(&self, storage: &mut S) -> Result<(), DeviceError> {
+        store_versioned(storage, SCHEDULER_REGISTER_VERSION, self).await
+    }
+
+    pub fn status(&self) -> u16 {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.is_defined())
+            .fold(0, |schedules, (index, _)| schedules | (1 << index))
+    }
+
+    pub fn action(&self, index: u8) -> SchedulerAction {
+        SchedulerAction {
+            index,
+            entry: self.entries[index as usize],
+        }
+    }
+
+    /// Process a message of the scheduler server or scheduler setup server,
+    /// persisting any change to the register in `storage`. Returns the
+    /// response to an acknowledged message.
+    pub async fn process<S: Storage>(
+        &mut self,
+        message: &SchedulerMessage,
+        storage: &mut S,
+    ) -> Result<Option<SchedulerMessage>, DeviceError> {
+        match message {
+            SchedulerMessage::Get => Ok(Some(SchedulerMessage::Status(self.status()))),
+            SchedulerMessage::ActionGet(index) => {
+                Ok(Some(SchedulerMessage::ActionStatus(self.action(*index))))
+            }
+            SchedulerMessage::ActionSet(action) => {
+                self.set(action, storage).await?;
+                Ok(Some(SchedulerMessage::ActionStatus(*action)))
+            }
+            SchedulerMessage::ActionSetUnacknowledged(action) => {
+                self.set(action, storage).await?;
+                Ok(None)
+            }
+            _ => {
+                // not applicable to server role
+                Ok(None)
+            }
+        }
+    }
+
+    async fn set<S: Storage>(
+        &mut self,
+        action: &SchedulerAction,
+        storage: &mut S,
+    ) -> Result<(), DeviceError> {
+        self.entries[action.index as usize] = action.entry;
+        self.store(storage).await
+    }
+
+    /// The entries which became due since last checked, up to a minute ago,
+    /// to be called at least every second with the present time of the
+    /// time server, such as from an `embassy::time::Ticker`.
+    pub fn due(&mut self, time: &Time) -> Vec<SchedulerAction, MAX_SCHEDULES> {
+        let mut due = Vec::new();
+        if time.tai_seconds == 0 {
+            return due;
+        }
+
+        let from = match self.last_checked {
+            Some(last_checked) if last_checked < time.tai_seconds => {
+                (last_checked + 1).max(time.tai_seconds.saturating_sub(59))
+            }
+            Some(_) => return due,
+            None => time.tai_seconds,
+        };
+        self.last_checked.replace(time.tai_seconds);
+
+        for tai_seconds in from..=time.tai_seconds {
+            let local = Time {
+                tai_seconds,
+                ..*time
+            }
+            .local();
+            if let Some(local) = local {
+                for (index, entry) in self.entries.iter().enumerate() {
+                    let index = index as u8;
+                    if entry.matches(index, &local) && !due.iter().any(|e| e.index == index) {
+                        // cannot exceed the number of entries.
+                        due.push(SchedulerAction {
+                            index,
+                            entry: *entry,
+                        })
+                        .ok();
+                    }
+                }
+            }
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_round_trip() {
+        let action = SchedulerAction {
+            index: 3,
+            entry: ScheduleEntry {
+                year: ANY_YEAR,
+                month: 0x0FFF,
+                day: ANY_DAY,
+                hour: 22,
+                minute: 0,
+                second: 0,
+                day_of_week: 0x7F,
+                action: ScheduleAction::TurnOff,
+                transition_time: TransitionTime(0x41),
+                scene_number: 0,
+            },
+        };
+        let mut xmit: Vec<u8, 16> = Vec::new();
+        action.emit_parameters(&mut xmit).unwrap();
+        assert_eq!(10, xmit.len());
+        let parsed = SchedulerAction::parse(&xmit).unwrap();
+        assert_eq!(action.index, parsed.index);
+        assert_eq!(action.entry, parsed.entry);
+    }
+
+    #[test]
+    fn every_day_at_ten_pm() {
+        let entry = ScheduleEntry {
+            year: ANY_YEAR,
+            month: 0x0FFF,
+            day: ANY_DAY,
+            hour: 22,
+            minute: 0,
+            second: 0,
+            day_of_week: 0x7F,
+            action: ScheduleAction::TurnOff,
+            transition_time: TransitionTime::IMMEDIATE,
+            scene_number: 0,
+        };
+        // 2024-02-29T22:00:00
+        assert!(entry.matches(0, &DateTime::from_seconds(762559200)));
+        assert!(!entry.matches(0, &DateTime::from_seconds(762559201)));
+        assert!(!ScheduleEntry::default().matches(0, &DateTime::from_seconds(762559200)));
+    }
+}
@@ -0,0 +1,213 @@
+use super::{CadenceSet, CadenceStatus, PropertyId, RawValue, StatusTriggerType};
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use embassy::time::Duration;
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Largest raw value of a sensor property kept by the sensor server.
+pub const MAX_RAW_VALUE: usize = 8;
+
+/// Largest fast cadence period divisor, publishing 2^15 times faster.
+pub const MAX_FAST_CADENCE_DIVISOR: u8 = 15;
+
+/// Largest status minimum interval, of 2^26 milliseconds.
+pub const MAX_STATUS_MIN_INTERVAL: u8 = 26;
+
+/// The cadence of the publication of a sensor, as configured by a sensor
+/// setup client.
+///
+/// Raw values are interpreted as little-endian integers, signed for the
+/// fast cadence range and unsigned for the trigger deltas.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cadence {
+    id: u16,
+    fast_cadence_divisor: u8,
+    status_trigger_type: StatusTriggerType,
+    status_trigger_delta_down: Vec<u8, MAX_RAW_VALUE>,
+    status_trigger_delta_up: Vec<u8, MAX_RAW_VALUE>,
+    status_min_interval: u8,
+    fast_cadence_low: Vec<u8, MAX_RAW_VALUE>,
+    fast_cadence_high: Vec<u8, MAX_RAW_VALUE>,
+}
+
+impl Cadence {
+    /// The cadence of a sensor never configured, publishing only at the
+    /// period of the model publication.
+    pub fn new(id: PropertyId, size: usize) -> Result<Self, InsufficientBuffer> {
+        let mut zero = Vec::new();
+        zero.resize(size, 0).map_err(|_| InsufficientBuffer)?;
+        Ok(Self {
+            id: id.0,
+            fast_cadence_divisor: 0,
+            status_trigger_type: StatusTriggerType::Property,
+            status_trigger_delta_down: zero.clone(),
+            status_trigger_delta_up: zero.clone(),
+            status_min_interval: 0,
+            fast_cadence_low: zero.clone(),
+            fast_cadence_high: zero,
+        })
+    }
+
+    pub fn id(&self) -> PropertyId {
+        PropertyId(self.id)
+    }
+
+    pub(crate) fn set(&mut self, set: &CadenceSet) -> Result<(), InsufficientBuffer> {
+        self.fast_cadence_divisor = set.fast_cadence_divisor.min(MAX_FAST_CADENCE_DIVISOR);
+        self.status_trigger_type = set.status_trigger_type;
+        self.status_trigger_delta_down =
+            Vec::from_slice(set.status_trigger_delta_down.0).map_err(|_| InsufficientBuffer)?;
+        self.status_trigger_delta_up =
+            Vec::from_slice(set.status_trigger_delta_up.0).map_err(|_| InsufficientBuffer)?;
+        self.status_min_interval = set.status_min_interval.min(MAX_STATUS_MIN_INTERVAL);
+        self.fast_cadence_low =
+            Vec::from_slice(set.fast_cadence_low.0).map_err(|_| InsufficientBuffer)?;
+        self.fast_cadence_high =
+            Vec::from_slice(set.fast_cadence_high.0).map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+
+    pub(crate) fn status(&self) -> CadenceStatus<'_> {
+        CadenceStatus {
+            id: self.id(),
+            fast_cadence_divisor: self.fast_cadence_divisor,
+            status_trigger_type: self.status_trigger_type,
+            status_trigger_delta_down: RawValue(&self.status_trigger_delta_down),
+            status_trigger_delta_up: RawValue(&self.status_trigger_delta_up),
+            status_min_interval: self.status_min_interval,
+            fast_cadence_low: RawValue(&self.fast_cadence_low),
+            fast_cadence_high: RawValue(&self.fast_cadence_high),
+        }
+    }
+
+    /// Whether `value` lies within the fast cadence range. A low bound above
+    /// the high bound selects the values outside of the range between them.
+    pub fn is_fast(&self, value: i64) -> bool {
+        let low = signed(&self.fast_cadence_low);
+        let high = signed(&self.fast_cadence_high);
+        if low <= high {
+            value >= low && value <= high
+        } else {
+            value >= low || value <= high
+        }
+    }
+
+    /// The period between publications while the sensor measures `value`,
+    /// being the period of the model publication divided by the fast
+    /// cadence divisor within the fast cadence range.
+    pub fn period(&self, publish_period: Duration, value: i64) -> Duration {
+        if self.is_fast(value) {
+            publish_period / (1 << self.fast_cadence_divisor)
+        } else {
+            publish_period
+        }
+    }
+
+    /// The shortest interval between two publications.
+    pub fn min_interval(&self) -> Duration {
+        Duration::from_millis(1 << self.status_min_interval)
+    }
+
+    /// Whether `value` has moved away from the `published` value by more
+    /// than either trigger delta. A delta of zero never triggers.
+    pub fn triggered(&self, published: i64, value: i64) -> bool {
+        let delta = |raw: &[u8]| match self.status_trigger_type {
+            StatusTriggerType::Property => unsigned(raw),
+            // in units of 0.01 percent of the published value.
+            StatusTriggerType::Unitless => published.abs() * unsigned(raw) / 10_000,
+        };
+        let down = delta(&self.status_trigger_delta_down);
+        let up = delta(&self.status_trigger_delta_up);
+        (down > 0 && value <= published - down) || (up > 0 && value >= published + up)
+    }
+}
+
+/// Little-endian signed integer of up to 8 octets.
+pub fn signed(raw: &[u8]) -> i64 {
+    let negative = raw.last().map(|last| last & 0x80 != 0).unwrap_or(false);
+    let mut bytes = if negative { [0xFF; 8] } else { [0; 8] };
+    let len = raw.len().min(8);
+    bytes[..len].copy_from_slice(&raw[..len]);
+    i64::from_le_bytes(bytes)
+}
+
+/// Little-endian unsigned integer of up to 8 octets.
+pub fn unsigned(raw: &[u8]) -> i64 {
+    let mut bytes = [0; 8];
+    let len = raw.len().min(8);
+    bytes[..len].copy_from_slice(&raw[..len]);
+    u64::from_le_bytes(bytes) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cadence(
+        divisor: u8,
+        trigger: StatusTriggerType,
+        down: i8,
+        up: i8,
+        low: i8,
+        high: i8,
+    ) -> Cadence {
+        let raw = |v: i8| Vec::from_slice(&v.to_le_bytes()).unwrap();
+        Cadence {
+            id: 0x004F,
+            fast_cadence_divisor: divisor,
+            status_trigger_type: trigger,
+            status_trigger_delta_down: raw(down),
+            status_trigger_delta_up: raw(up),
+            status_min_interval: 0,
+            fast_cadence_low: raw(low),
+            fast_cadence_high: raw(high),
+        }
+    }
+
+    #[test]
+    fn raw_values() {
+        assert_eq!(signed(&[0xFE]), -2);
+        assert_eq!(signed(&[0x34, 0x12]), 0x1234);
+        assert_eq!(unsigned(&[0xFE]), 0xFE);
+        assert_eq!(signed(&[]), 0);
+    }
+
+    #[test]
+    fn fast_cadence_range() {
+        let c = cadence(2, StatusTriggerType::Property, 0, 0, 10, 20);
+        assert!(!c.is_fast(9));
+        assert!(c.is_fast(10));
+        assert!(c.is_fast(20));
+        assert_eq!(
+            c.period(Duration::from_millis(4000), 15),
+            Duration::from_millis(1000)
+        );
+        assert_eq!(
+            c.period(Duration::from_millis(4000), 25),
+            Duration::from_millis(4000)
+        );
+
+        let c = cadence(2, StatusTriggerType::Property, 0, 0, 20, -10);
+        assert!(c.is_fast(-15));
+        assert!(c.is_fast(25));
+        assert!(!c.is_fast(0));
+    }
+
+    #[test]
+    fn trigger_deltas() {
+        let c = cadence(0, StatusTriggerType::Property, 5, 0, 0, 0);
+        assert!(!c.triggered(20, 16));
+        assert!(c.triggered(20, 15));
+        // a delta of zero never triggers.
+        assert!(!c.triggered(20, 100));
+
+        // 50.00 percent of the published value.
+        let c = cadence(0, StatusTriggerType::Unitless, 0, 0, 0, 0);
+        let c = Cadence {
+            status_trigger_delta_up: Vec::from_slice(&5000u16.to_le_bytes()).unwrap(),
+            ..c
+        };
+        assert!(!c.triggered(40, 59));
+        assert!(c.triggered(40, 60));
+    }
+}
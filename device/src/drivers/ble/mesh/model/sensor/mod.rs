@@ -6,6 +6,11 @@ use crate::opcode;
 use embassy::time::Duration;
 use heapless::Vec;
 use micromath::F32Ext;
+use serde::{Deserialize, Serialize};
+
+pub mod cadence;
+pub mod series;
+pub mod state;
 
 #[derive(Clone, Debug)]
 pub struct SensorClient<C, const NUM_SENSORS: usize, const NUM_COLUMNS: usize>
@@ -232,6 +237,7 @@ pub struct CadenceSet<'m> {
     fast_cadence_high: RawValue<'m>,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum StatusTriggerType {
     Property,
@@ -428,6 +434,12 @@ where
             SENSOR_SERIES_GET => Ok(Some(SensorMessage::SeriesGet(SeriesGet::parse::<C>(
                 parameters,
             )?))),
+            SENSOR_COLUMN_STATUS => Ok(Some(SensorMessage::ColumnStatus(
+                ColumnStatus::parse::<C>(parameters)?,
+            ))),
+            SENSOR_SERIES_STATUS => Ok(Some(SensorMessage::SeriesStatus(
+                SeriesStatus::parse::<C>(parameters)?,
+            ))),
             _ => Ok(None),
         }
     }
@@ -582,6 +594,22 @@ impl SensorDescriptor {
     }
 }
 
+impl CadenceDescriptor {
+    pub const fn new(id: PropertyId, size: usize) -> Self {
+        Self { id, size }
+    }
+}
+
+impl SettingDescriptor {
+    pub const fn new(sensor: PropertyId, setting: PropertyId, size: usize) -> Self {
+        Self {
+            sensor,
+            setting,
+            size,
+        }
+    }
+}
+
 impl SamplingFunction {
     fn emit_parameters<const N: usize>(
         &self,
@@ -767,6 +795,37 @@ impl<'a> ColumnGet<'a> {
 }
 
 impl<'a> ColumnStatus<'a> {
+    fn parse<C>(parameters: &'a [u8]) -> Result<Self, ParseError>
+    where
+        C: SensorConfig,
+    {
+        let id = PropertyId::parse(parameters)?;
+        if let Some(d) = lookup_descriptor::<C>(id) {
+            let x_len = d.x_size;
+            let parameters = &parameters[2..];
+            if parameters.len() == x_len {
+                Ok(Self {
+                    id,
+                    x: RawValue(parameters),
+                    values: None,
+                })
+            } else if parameters.len() == 2 * x_len + d.size {
+                Ok(Self {
+                    id,
+                    x: RawValue(&parameters[..x_len]),
+                    values: Some((
+                        RawValue(&parameters[x_len..2 * x_len]),
+                        RawValue(&parameters[2 * x_len..]),
+                    )),
+                })
+            } else {
+                Err(ParseError::InvalidLength)
+            }
+        } else {
+            Err(ParseError::InvalidValue)
+        }
+    }
+
     fn emit_parameters<const N: usize>(
         &self,
         xmit: &mut heapless::Vec<u8, N>,
@@ -820,6 +879,34 @@ impl<'a> SeriesGet<'a> {
 }
 
 impl<'a, const NUM_COLUMNS: usize> SeriesStatus<'a, NUM_COLUMNS> {
+    fn parse<C>(parameters: &'a [u8]) -> Result<Self, ParseError>
+    where
+        C: SensorConfig,
+    {
+        let id = PropertyId::parse(parameters)?;
+        if let Some(d) = lookup_descriptor::<C>(id) {
+            let x_len = d.x_size;
+            let column_len = 2 * x_len + d.size;
+            let parameters = &parameters[2..];
+            if column_len == 0 || parameters.len() % column_len != 0 {
+                return Err(ParseError::InvalidLength);
+            }
+            let mut values = Vec::new();
+            for column in parameters.chunks(column_len) {
+                values
+                    .push((
+                        RawValue(&column[..x_len]),
+                        RawValue(&column[x_len..2 * x_len]),
+                        RawValue(&column[2 * x_len..]),
+                    ))
+                    .map_err(|_| ParseError::InsufficientBuffer)?;
+            }
+            Ok(Self { id, values })
+        } else {
+            Err(ParseError::InvalidValue)
+        }
+    }
+
     fn emit_parameters<const N: usize>(
         &self,
         xmit: &mut heapless::Vec<u8, N>,
@@ -866,7 +953,10 @@ impl<'a> CadenceSet<'a> {
         C: SensorSetupConfig,
     {
         let id = PropertyId::parse(parameters)?;
-        let fast_cadence_divisor = parameters[2] & 0xF7 >> 1;
+        if parameters.len() < 3 {
+            return Err(ParseError::InvalidLength);
+        }
+        let fast_cadence_divisor = (parameters[2] & 0xFE) >> 1;
         let status_trigger_type = if parameters[2] & 0x01 == 1 {
             StatusTriggerType::Unitless
         } else {
@@ -875,6 +965,9 @@ impl<'a> CadenceSet<'a> {
 
         if let Some(d) = lookup_cadence_descriptor::<C>(id) {
             let c_len = d.size;
+            if parameters.len() != 3 + 4 * c_len + 1 {
+                return Err(ParseError::InvalidLength);
+            }
 
             let parameters = &parameters[3..];
             let status_trigger_delta_down = RawValue(&parameters[..c_len]);
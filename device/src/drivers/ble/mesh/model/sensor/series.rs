@@ -0,0 +1,158 @@
+use super::cadence::{unsigned, MAX_RAW_VALUE};
+use super::{ColumnGet, ColumnStatus, PropertyId, RawValue, SeriesGet, SeriesStatus};
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use heapless::Vec;
+
+/// A column of a sensor series, starting at `x` and spanning `width` along
+/// the x axis, holding the value `y`.
+pub struct Column {
+    x: Vec<u8, MAX_RAW_VALUE>,
+    width: Vec<u8, MAX_RAW_VALUE>,
+    y: Vec<u8, MAX_RAW_VALUE>,
+}
+
+impl Column {
+    pub fn new(x: &[u8], width: &[u8], y: &[u8]) -> Result<Self, InsufficientBuffer> {
+        Ok(Self {
+            x: Vec::from_slice(x).map_err(|_| InsufficientBuffer)?,
+            width: Vec::from_slice(width).map_err(|_| InsufficientBuffer)?,
+            y: Vec::from_slice(y).map_err(|_| InsufficientBuffer)?,
+        })
+    }
+
+    fn position(&self) -> i64 {
+        unsigned(&self.x)
+    }
+}
+
+/// Ring buffer of the most recent columns sampled for a sensor, replacing
+/// the oldest column once full.
+pub struct Series<const NUM_COLUMNS: usize> {
+    id: PropertyId,
+    columns: Vec<Column, NUM_COLUMNS>,
+    next: usize,
+}
+
+impl<const NUM_COLUMNS: usize> Series<NUM_COLUMNS> {
+    pub fn new(id: PropertyId) -> Self {
+        Self {
+            id,
+            columns: Vec::new(),
+            next: 0,
+        }
+    }
+
+    pub fn id(&self) -> PropertyId {
+        self.id
+    }
+
+    pub fn push(&mut self, column: Column) {
+        if NUM_COLUMNS == 0 {
+            return;
+        }
+        // replace any column sampled previously at the same position.
+        let position = column.position();
+        if let Some(existing) = self.columns.iter_mut().find(|c| c.position() == position) {
+            *existing = column;
+        } else if let Err(column) = self.columns.push(column) {
+            self.columns[self.next] = column;
+            self.next = (self.next + 1) % NUM_COLUMNS;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.columns.clear();
+        self.next = 0;
+    }
+
+    /// Answer a column get, with the column at exactly the requested
+    /// position, if sampled.
+    pub fn column<'m>(&'m self, get: &ColumnGet<'m>) -> ColumnStatus<'m> {
+        let position = unsigned(get.x.0);
+        let values = self
+            .columns
+            .iter()
+            .find(|c| c.position() == position)
+            .map(|c| (RawValue(&c.width), RawValue(&c.y)));
+        ColumnStatus {
+            id: self.id,
+            x: RawValue(get.x.0),
+            values,
+        }
+    }
+
+    /// Answer a series get, with the columns within the requested range,
+    /// inclusive, or all columns, ordered along the x axis.
+    pub fn series(&self, get: &SeriesGet) -> SeriesStatus<'_, NUM_COLUMNS> {
+        let range = get
+            .x
+            .as_ref()
+            .map(|(x1, x2)| (unsigned(x1.0), unsigned(x2.0)));
+        let mut columns: Vec<&Column, NUM_COLUMNS> = Vec::new();
+        for column in self.columns.iter() {
+            let position = column.position();
+            if range
+                .map(|(x1, x2)| position >= x1 && position <= x2)
+                .unwrap_or(true)
+            {
+                // never exceeds the capacity, holding no more than the series.
+                columns.push(column).ok();
+            }
+        }
+        columns.sort_unstable_by_key(|c| c.position());
+
+        let mut values = Vec::new();
+        for column in columns {
+            values
+                .push((
+                    RawValue(&column.x),
+                    RawValue(&column.width),
+                    RawValue(&column.y),
+                ))
+                .ok();
+        }
+        SeriesStatus {
+            id: self.id,
+            values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buffer() {
+        let mut series: Series<2> = Series::new(PropertyId(0x0001));
+        for (x, y) in [(1u8, 10u8), (2, 20), (3, 30)] {
+            series.push(Column::new(&[x], &[1], &[y]).unwrap());
+        }
+
+        let get = SeriesGet {
+            id: PropertyId(0x0001),
+            x: None,
+        };
+        let status = series.series(&get);
+        let xs: Vec<u8, 2> = status.values.iter().map(|(x, _, _)| x.0[0]).collect();
+        assert_eq!(&xs[..], &[2, 3]);
+
+        let get = ColumnGet {
+            id: PropertyId(0x0001),
+            x: RawValue(&[1]),
+        };
+        assert!(series.column(&get).values.is_none());
+        let get = ColumnGet {
+            id: PropertyId(0x0001),
+            x: RawValue(&[3]),
+        };
+        let (_, y) = series.column(&get).values.unwrap();
+        assert_eq!(y.0, &[30]);
+
+        let get = SeriesGet {
+            id: PropertyId(0x0001),
+            x: Some((RawValue(&[0]), RawValue(&[2]))),
+        };
+        assert_eq!(series.series(&get).values.len(), 1);
+    }
+}
@@ -0,0 +1,335 @@
+use super::cadence::{Cadence, MAX_RAW_VALUE};
+use super::series::{Column, Series};
+use super::{
+    PropertyId, RawValue, SensorMessage, SensorSettingAccess, SensorSetupConfig,
+    SensorSetupMessage, SettingStatus, SettingsStatus,
+};
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::storage::{load_versioned, store_versioned, Storage};
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use core::marker::PhantomData;
+use embassy::time::{Duration, Instant};
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of settings held across all sensors.
+pub const MAX_SETTINGS: usize = 8;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Setting {
+    sensor: u16,
+    setting: u16,
+    raw: Vec<u8, MAX_RAW_VALUE>,
+}
+
+/// Version of the layout of the stored [`Settings`].
+const SETTINGS_VERSION: u16 = 1;
+
+/// The cadences and settings configured by sensor setup clients, persisted
+/// to storage.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Settings<const NUM_SENSORS: usize> {
+    cadences: Vec<Cadence, NUM_SENSORS>,
+    settings: Vec<Setting, MAX_SETTINGS>,
+}
+
+/// The latest value measured by a sensor, and the value last published.
+struct Measurement {
+    id: PropertyId,
+    value: Option<i64>,
+    published: Option<(Instant, i64)>,
+}
+
+/// The state of the sensor server and sensor setup server, publishing the
+/// sensor status according to the configured cadences, answering column
+/// and series gets from the most recent samples, and keeping the settings.
+///
+/// All sensors are published together within a single sensor status, as
+/// soon as the cadence of any of them calls for it.
+pub struct SensorServerState<C, const NUM_SENSORS: usize, const NUM_COLUMNS: usize>
+where
+    C: SensorSetupConfig,
+{
+    settings: Settings<NUM_SENSORS>,
+    measurements: Vec<Measurement, NUM_SENSORS>,
+    series: Vec<Series<NUM_COLUMNS>, NUM_SENSORS>,
+    _config: PhantomData<C>,
+}
+
+impl<C, const NUM_SENSORS: usize, const NUM_COLUMNS: usize>
+    SensorServerState<C, NUM_SENSORS, NUM_COLUMNS>
+where
+    C: SensorSetupConfig,
+{
+    /// Restore the stored settings, or the defaults if none have been stored
+    /// yet. Stored settings failing to decode are an error, rather than replaced.
+    pub async fn load<S: Storage>(storage: &mut S) -> Result<Self, DeviceError> {
+        let settings = load_versioned(storage, SETTINGS_VERSION)
+            .await?
+            .unwrap_or_default();
+        Self::new(settings).map_err(|_| DeviceError::InsufficientBuffer)
+    }
+
+    pub async fn store<S: Storage>(&self, storage: &mut S) -> Result<(), DeviceError> {
+        store_versioned(storage, SETTINGS_VERSION, &self.settings).await
+    }
+
+    fn new(mut settings: Settings<NUM_SENSORS>) -> Result<Self, InsufficientBuffer> {
+        // settings of sensors no longer described are dropped, and those
+        // never configured start out zeroed.
+        settings
+            .cadences
+            .retain(|c| C::CADENCE_DESCRIPTORS.iter().any(|d| d.id == c.id()));
+        settings.settings.retain(|s| {
+            C::SETTING_DESCRIPTORS
+                .iter()
+                .any(|d| d.sensor.0 == s.sensor && d.setting.0 == s.setting)
+        });
+        for d in C::CADENCE_DESCRIPTORS {
+            if !settings.cadences.iter().any(|c| c.id() == d.id) {
+                settings
+                    .cadences
+                    .push(Cadence::new(d.id, d.size)?)
+                    .map_err(|_| InsufficientBuffer)?;
+            }
+        }
+        for d in C::SETTING_DESCRIPTORS {
+            if !settings
+                .settings
+                .iter()
+                .any(|s| s.sensor == d.sensor.0 && s.setting == d.setting.0)
+            {
+                let mut raw = Vec::new();
+                raw.resize(d.size, 0).map_err(|_| InsufficientBuffer)?;
+                settings
+                    .settings
+                    .push(Setting {
+                        sensor: d.sensor.0,
+                        setting: d.setting.0,
+                        raw,
+                    })
+                    .map_err(|_| InsufficientBuffer)?;
+            }
+        }
+
+        let mut measurements = Vec::new();
+        let mut series = Vec::new();
+        for d in C::DESCRIPTORS {
+            measurements
+                .push(Measurement {
+                    id: d.id,
+                    value: None,
+                    published: None,
+                })
+                .map_err(|_| InsufficientBuffer)?;
+            if d.x_size > 0 {
+                series
+                    .push(Series::new(d.id))
+                    .map_err(|_| InsufficientBuffer)?;
+            }
+        }
+
+        Ok(Self {
+            settings,
+            measurements,
+            series,
+            _config: PhantomData,
+        })
+    }
+
+    /// The value of a setting of a sensor, as last set.
+    pub fn setting(&self, sensor: PropertyId, setting: PropertyId) -> Option<&[u8]> {
+        self.settings
+            .settings
+            .iter()
+            .find(|s| s.sensor == sensor.0 && s.setting == setting.0)
+            .map(|s| &s.raw[..])
+    }
+
+    /// Record the latest `value` measured by a sensor, to be published
+    /// according to its cadence.
+    pub fn update(&mut self, id: PropertyId, value: i64) {
+        if let Some(m) = self.measurements.iter_mut().find(|m| m.id == id) {
+            m.value.replace(value);
+        }
+    }
+
+    /// Record a column sampled by a sensor, replacing the oldest column of
+    /// its series once full.
+    pub fn sample(
+        &mut self,
+        id: PropertyId,
+        x: &[u8],
+        width: &[u8],
+        y: &[u8],
+    ) -> Result<(), InsufficientBuffer> {
+        if let Some(series) = self.series.iter_mut().find(|s| s.id() == id) {
+            series.push(Column::new(x, width, y)?);
+        }
+        Ok(())
+    }
+
+    fn cadence(&self, id: PropertyId) -> Option<&Cadence> {
+        self.settings.cadences.iter().find(|c| c.id() == id)
+    }
+
+    /// When the sensor status is next due by cadence, given the period of
+    /// the model publication, or `None` if nothing has been measured yet.
+    pub fn next_publication(&self, publish_period: Duration) -> Option<Instant> {
+        let mut next: Option<Instant> = None;
+        for m in self.measurements.iter() {
+            let due = match (m.value, m.published) {
+                (Some(_), None) => Instant::now(),
+                (Some(value), Some((at, _))) => match self.cadence(m.id) {
+                    Some(cadence) => {
+                        at + cadence
+                            .period(publish_period, value)
+                            .max(cadence.min_interval())
+                    }
+                    None => at + publish_period,
+                },
+                _ => continue,
+            };
+            next = Some(next.map(|next| next.min(due)).unwrap_or(due));
+        }
+        next
+    }
+
+    /// Whether the sensor status should be published now, either due by
+    /// cadence or by a measurement crossing a trigger delta, recording the
+    /// measurements as published if so.
+    pub fn publication_due(&mut self, publish_period: Duration) -> bool {
+        let now = Instant::now();
+        let mut due = false;
+        for m in self.measurements.iter() {
+            due |= match (m.value, m.published) {
+                (Some(_), None) => true,
+                (Some(value), Some((at, published))) => {
+                    let elapsed = now.duration_since(at);
+                    match self.cadence(m.id) {
+                        Some(cadence) => {
+                            elapsed >= cadence.min_interval()
+                                && (elapsed >= cadence.period(publish_period, value)
+                                    || cadence.triggered(published, value))
+                        }
+                        None => elapsed >= publish_period,
+                    }
+                }
+                _ => false,
+            };
+        }
+        if due {
+            for m in self.measurements.iter_mut() {
+                if let Some(value) = m.value {
+                    m.published.replace((now, value));
+                }
+            }
+        }
+        due
+    }
+
+    /// Answer the column and series gets of the sensor server, leaving
+    /// other messages to the application.
+    pub fn process<'m>(
+        &'m self,
+        message: &SensorMessage<'m, C, NUM_SENSORS, NUM_COLUMNS>,
+    ) -> Option<SensorMessage<'m, C, NUM_SENSORS, NUM_COLUMNS>> {
+        match message {
+            SensorMessage::ColumnGet(get) => self
+                .series
+                .iter()
+                .find(|s| s.id() == get.id)
+                .map(|s| SensorMessage::ColumnStatus(s.column(get))),
+            SensorMessage::SeriesGet(get) => self
+                .series
+                .iter()
+                .find(|s| s.id() == get.id)
+                .map(|s| SensorMessage::SeriesStatus(s.series(get))),
+            _ => None,
+        }
+    }
+
+    /// Answer the messages of the sensor setup server, storing the cadences
+    /// and settings as they are set.
+    pub async fn process_setup<'m, S: Storage>(
+        &'m mut self,
+        message: &SensorSetupMessage<'m, C, NUM_SENSORS, NUM_COLUMNS>,
+        storage: &mut S,
+    ) -> Result<Option<SensorSetupMessage<'m, C, NUM_SENSORS, NUM_COLUMNS>>, DeviceError> {
+        match message {
+            SensorSetupMessage::Sensor(message) => {
+                Ok(self.process(message).map(SensorSetupMessage::Sensor))
+            }
+            SensorSetupMessage::CadenceGet(get) => Ok(self
+                .cadence(get.id)
+                .map(|c| SensorSetupMessage::CadenceStatus(c.status()))),
+            SensorSetupMessage::CadenceSet(set)
+            | SensorSetupMessage::CadenceSetUnacknowledged(set) => {
+                if let Some(cadence) = self.settings.cadences.iter_mut().find(|c| c.id() == set.id)
+                {
+                    cadence.set(set)?;
+                    self.store(storage).await?;
+                }
+                if let SensorSetupMessage::CadenceSet(_) = message {
+                    Ok(self
+                        .cadence(set.id)
+                        .map(|c| SensorSetupMessage::CadenceStatus(c.status())))
+                } else {
+                    Ok(None)
+                }
+            }
+            SensorSetupMessage::SettingsGet(get) => {
+                let mut settings = Vec::new();
+                for s in self
+                    .settings
+                    .settings
+                    .iter()
+                    .filter(|s| s.sensor == get.id.0)
+                {
+                    settings
+                        .push(PropertyId(s.setting))
+                        .map_err(|_| InsufficientBuffer)?;
+                }
+                Ok(Some(SensorSetupMessage::SettingsStatus(SettingsStatus {
+                    id: get.id,
+                    settings,
+                })))
+            }
+            SensorSetupMessage::SettingGet(get) => Ok(self
+                .setting_status(get.id, get.setting)
+                .map(SensorSetupMessage::SettingStatus)),
+            SensorSetupMessage::SettingSet(set)
+            | SensorSetupMessage::SettingSetUnacknowledged(set) => {
+                if let Some(setting) = self
+                    .settings
+                    .settings
+                    .iter_mut()
+                    .find(|s| s.sensor == set.id.0 && s.setting == set.setting.0)
+                {
+                    setting.raw = Vec::from_slice(set.raw.0).map_err(|_| InsufficientBuffer)?;
+                    self.store(storage).await?;
+                }
+                if let SensorSetupMessage::SettingSet(_) = message {
+                    Ok(self
+                        .setting_status(set.id, set.setting)
+                        .map(SensorSetupMessage::SettingStatus))
+                } else {
+                    Ok(None)
+                }
+            }
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+
+    fn setting_status(&self, sensor: PropertyId, setting: PropertyId) -> Option<SettingStatus<'_>> {
+        self.setting(sensor, setting).map(|raw| SettingStatus {
+            id: sensor,
+            setting,
+            access: SensorSettingAccess::ReadWrite,
+            raw: RawValue(raw),
+        })
+    }
+}
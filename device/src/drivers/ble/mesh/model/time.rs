@@ -0,0 +1,710 @@
+use crate::drivers::ble::mesh::model::{Message, Model, ModelIdentifier};
+use crate::drivers::ble::mesh::pdu::access::Opcode;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use crate::opcode;
+use embassy::time::Instant;
+use heapless::Vec;
+
+#[derive(Clone, Debug)]
+pub struct TimeServer;
+
+#[derive(Clone, Debug)]
+pub struct TimeSetupServer;
+
+#[derive(Clone, Debug)]
+pub struct TimeClient;
+
+pub const TIME_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1200);
+pub const TIME_SETUP_SERVER: ModelIdentifier = ModelIdentifier::SIG(0x1201);
+pub const TIME_CLIENT: ModelIdentifier = ModelIdentifier::SIG(0x1202);
+
+/// Encoded TAI-UTC delta of zero seconds.
+const TAI_UTC_DELTA_ZERO: u16 = 0xFF;
+
+/// Encoded time zone offset of UTC.
+const TIME_ZONE_OFFSET_ZERO: u8 = 0x40;
+
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimeMessage {
+    Get,
+    Set(Time),
+    Status(Time),
+    RoleGet,
+    RoleSet(TimeRole),
+    RoleStatus(TimeRole),
+    ZoneGet,
+    ZoneSet(TimeZoneSet),
+    ZoneStatus(TimeZoneStatus),
+    TaiUtcDeltaGet,
+    TaiUtcDeltaSet(TaiUtcDeltaSet),
+    TaiUtcDeltaStatus(TaiUtcDeltaStatus),
+}
+
+impl Message for TimeMessage {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Self::Get => TIME_GET,
+            Self::Set(_) => TIME_SET,
+            Self::Status(_) => TIME_STATUS,
+            Self::RoleGet => TIME_ROLE_GET,
+            Self::RoleSet(_) => TIME_ROLE_SET,
+            Self::RoleStatus(_) => TIME_ROLE_STATUS,
+            Self::ZoneGet => TIME_ZONE_GET,
+            Self::ZoneSet(_) => TIME_ZONE_SET,
+            Self::ZoneStatus(_) => TIME_ZONE_STATUS,
+            Self::TaiUtcDeltaGet => TAI_UTC_DELTA_GET,
+            Self::TaiUtcDeltaSet(_) => TAI_UTC_DELTA_SET,
+            Self::TaiUtcDeltaStatus(_) => TAI_UTC_DELTA_STATUS,
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::Get | Self::RoleGet | Self::ZoneGet | Self::TaiUtcDeltaGet => Ok(()),
+            Self::Set(inner) | Self::Status(inner) => inner.emit_parameters(xmit),
+            Self::RoleSet(inner) | Self::RoleStatus(inner) => {
+                xmit.push(*inner as u8).map_err(|_| InsufficientBuffer)
+            }
+            Self::ZoneSet(inner) => inner.emit_parameters(xmit),
+            Self::ZoneStatus(inner) => inner.emit_parameters(xmit),
+            Self::TaiUtcDeltaSet(inner) => inner.emit_parameters(xmit),
+            Self::TaiUtcDeltaStatus(inner) => inner.emit_parameters(xmit),
+        }
+    }
+}
+
+impl Model for TimeServer {
+    const IDENTIFIER: ModelIdentifier = TIME_SERVER;
+    type Message<'m> = TimeMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            TIME_GET => Ok(Some(TimeMessage::Get)),
+            // published by time authorities and relays
+            TIME_STATUS => Ok(Some(TimeMessage::Status(Time::parse(parameters)?))),
+            TIME_ZONE_GET => Ok(Some(TimeMessage::ZoneGet)),
+            TAI_UTC_DELTA_GET => Ok(Some(TimeMessage::TaiUtcDeltaGet)),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for TimeSetupServer {
+    const IDENTIFIER: ModelIdentifier = TIME_SETUP_SERVER;
+    const SUPPORTS_PUBLICATION: bool = false;
+    type Message<'m> = TimeMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            TIME_SET => Ok(Some(TimeMessage::Set(Time::parse(parameters)?))),
+            TIME_ROLE_GET => Ok(Some(TimeMessage::RoleGet)),
+            TIME_ROLE_SET => Ok(Some(TimeMessage::RoleSet(TimeRole::parse(parameters)?))),
+            TIME_ZONE_SET => Ok(Some(TimeMessage::ZoneSet(TimeZoneSet::parse(parameters)?))),
+            TAI_UTC_DELTA_SET => Ok(Some(TimeMessage::TaiUtcDeltaSet(TaiUtcDeltaSet::parse(
+                parameters,
+            )?))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Model for TimeClient {
+    const IDENTIFIER: ModelIdentifier = TIME_CLIENT;
+    type Message<'m> = TimeMessage;
+
+    fn parse<'m>(
+        opcode: Opcode,
+        parameters: &'m [u8],
+    ) -> Result<Option<Self::Message<'m>>, ParseError> {
+        match opcode {
+            TIME_STATUS => Ok(Some(TimeMessage::Status(Time::parse(parameters)?))),
+            TIME_ROLE_STATUS => Ok(Some(TimeMessage::RoleStatus(TimeRole::parse(parameters)?))),
+            TIME_ZONE_STATUS => Ok(Some(TimeMessage::ZoneStatus(TimeZoneStatus::parse(
+                parameters,
+            )?))),
+            TAI_UTC_DELTA_STATUS => Ok(Some(TimeMessage::TaiUtcDeltaStatus(
+                TaiUtcDeltaStatus::parse(parameters)?,
+            ))),
+            _ => {
+                // not applicable to this role
+                Ok(None)
+            }
+        }
+    }
+}
+
+opcode!( TIME_SET 0x5C );
+opcode!( TIME_STATUS 0x5D );
+opcode!( TIME_GET 0x82, 0x37 );
+opcode!( TIME_ROLE_GET 0x82, 0x38 );
+opcode!( TIME_ROLE_SET 0x82, 0x39 );
+opcode!( TIME_ROLE_STATUS 0x82, 0x3A );
+opcode!( TIME_ZONE_GET 0x82, 0x3B );
+opcode!( TIME_ZONE_SET 0x82, 0x3C );
+opcode!( TIME_ZONE_STATUS 0x82, 0x3D );
+opcode!( TAI_UTC_DELTA_GET 0x82, 0x3E );
+opcode!( TAI_UTC_DELTA_SET 0x82, 0x3F );
+opcode!( TAI_UTC_DELTA_STATUS 0x82, 0x40 );
+
+fn parse_u40(parameters: &[u8]) -> u64 {
+    u64::from_le_bytes([
+        parameters[0],
+        parameters[1],
+        parameters[2],
+        parameters[3],
+        parameters[4],
+        0,
+        0,
+        0,
+    ])
+}
+
+fn emit_u40<const N: usize>(val: u64, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+    xmit.extend_from_slice(&val.to_le_bytes()[0..5])
+        .map_err(|_| InsufficientBuffer)
+}
+
+/// The role of a node in the propagation of time through the network.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimeRole {
+    /// Neither publishes nor processes published time.
+    None = 0x00,
+    /// Publishes its own time, such as from a real-time clock or GPS.
+    Authority = 0x01,
+    /// Processes published time and publishes it again.
+    Relay = 0x02,
+    /// Processes published time.
+    Client = 0x03,
+}
+
+impl Default for TimeRole {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl TimeRole {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 1 {
+            match parameters[0] {
+                0x00 => Ok(Self::None),
+                0x01 => Ok(Self::Authority),
+                0x02 => Ok(Self::Relay),
+                0x03 => Ok(Self::Client),
+                _ => Err(ParseError::InvalidValue),
+            }
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+}
+
+/// A point in time, as seconds since 2000-01-01T00:00:00 TAI.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Time {
+    /// Seconds since the epoch, 0 if the time is not known.
+    pub tai_seconds: u64,
+    /// Fraction of a second, in 1/256 steps.
+    pub subsecond: u8,
+    /// Accuracy of the time, in 10 millisecond steps.
+    pub uncertainty: u8,
+    /// Whether the time was obtained from a reliable source.
+    pub time_authority: bool,
+    /// Current TAI-UTC delta, encoded with an offset of 255 seconds.
+    pub tai_utc_delta: u16,
+    /// Local time zone offset, encoded in 15 minute steps with an offset of 0x40.
+    pub time_zone_offset: u8,
+}
+
+impl Time {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 5 && parse_u40(parameters) == 0 {
+            Ok(Self {
+                tai_seconds: 0,
+                subsecond: 0,
+                uncertainty: 0,
+                time_authority: false,
+                tai_utc_delta: TAI_UTC_DELTA_ZERO,
+                time_zone_offset: TIME_ZONE_OFFSET_ZERO,
+            })
+        } else if parameters.len() == 10 {
+            let delta = u16::from_le_bytes([parameters[7], parameters[8]]);
+            Ok(Self {
+                tai_seconds: parse_u40(parameters),
+                subsecond: parameters[5],
+                uncertainty: parameters[6],
+                time_authority: delta & 0b1 != 0,
+                tai_utc_delta: delta >> 1,
+                time_zone_offset: parameters[9],
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        emit_u40(self.tai_seconds, xmit)?;
+        if self.tai_seconds != 0 {
+            xmit.push(self.subsecond).map_err(|_| InsufficientBuffer)?;
+            xmit.push(self.uncertainty)
+                .map_err(|_| InsufficientBuffer)?;
+            let delta = (self.tai_utc_delta << 1) | self.time_authority as u16;
+            xmit.extend_from_slice(&delta.to_le_bytes())
+                .map_err(|_| InsufficientBuffer)?;
+            xmit.push(self.time_zone_offset)
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+
+    /// Seconds to subtract from TAI to obtain UTC.
+    pub fn tai_utc_delta_seconds(&self) -> i32 {
+        self.tai_utc_delta as i32 - TAI_UTC_DELTA_ZERO as i32
+    }
+
+    /// Minutes to add to UTC to obtain local time.
+    pub fn time_zone_offset_minutes(&self) -> i32 {
+        (self.time_zone_offset as i32 - TIME_ZONE_OFFSET_ZERO as i32) * 15
+    }
+
+    /// The local date and time, or `None` if the time is not known.
+    pub fn local(&self) -> Option<DateTime> {
+        if self.tai_seconds == 0 {
+            None
+        } else {
+            let local = self.tai_seconds as i64 - self.tai_utc_delta_seconds() as i64
+                + self.time_zone_offset_minutes() as i64 * 60;
+            Some(DateTime::from_seconds(local))
+        }
+    }
+}
+
+/// Change the time zone offset once TAI reaches `tai_of_zone_change`.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimeZoneSet {
+    pub time_zone_offset_new: u8,
+    pub tai_of_zone_change: u64,
+}
+
+impl TimeZoneSet {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 6 {
+            Ok(Self {
+                time_zone_offset_new: parameters[0],
+                tai_of_zone_change: parse_u40(&parameters[1..]),
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.time_zone_offset_new)
+            .map_err(|_| InsufficientBuffer)?;
+        emit_u40(self.tai_of_zone_change, xmit)
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimeZoneStatus {
+    pub time_zone_offset_current: u8,
+    pub time_zone_offset_new: u8,
+    pub tai_of_zone_change: u64,
+}
+
+impl TimeZoneStatus {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 7 {
+            Ok(Self {
+                time_zone_offset_current: parameters[0],
+                time_zone_offset_new: parameters[1],
+                tai_of_zone_change: parse_u40(&parameters[2..]),
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.time_zone_offset_current)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.time_zone_offset_new)
+            .map_err(|_| InsufficientBuffer)?;
+        emit_u40(self.tai_of_zone_change, xmit)
+    }
+}
+
+/// Change the TAI-UTC delta once TAI reaches `tai_of_delta_change`, such as
+/// for an upcoming leap second.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TaiUtcDeltaSet {
+    pub tai_utc_delta_new: u16,
+    pub tai_of_delta_change: u64,
+}
+
+impl TaiUtcDeltaSet {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 7 {
+            Ok(Self {
+                tai_utc_delta_new: u16::from_le_bytes([parameters[0], parameters[1]]) & 0x7FFF,
+                tai_of_delta_change: parse_u40(&parameters[2..]),
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.tai_utc_delta_new.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        emit_u40(self.tai_of_delta_change, xmit)
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TaiUtcDeltaStatus {
+    pub tai_utc_delta_current: u16,
+    pub tai_utc_delta_new: u16,
+    pub tai_of_delta_change: u64,
+}
+
+impl TaiUtcDeltaStatus {
+    fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() == 9 {
+            Ok(Self {
+                tai_utc_delta_current: u16::from_le_bytes([parameters[0], parameters[1]]) & 0x7FFF,
+                tai_utc_delta_new: u16::from_le_bytes([parameters[2], parameters[3]]) & 0x7FFF,
+                tai_of_delta_change: parse_u40(&parameters[4..]),
+            })
+        } else {
+            Err(ParseError::InvalidLength)
+        }
+    }
+
+    fn emit_parameters<const N: usize>(
+        &self,
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.tai_utc_delta_current.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.tai_utc_delta_new.to_le_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        emit_u40(self.tai_of_delta_change, xmit)
+    }
+}
+
+/// A calendar date and time of day.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    pub year: u16,
+    /// 1 to 12.
+    pub month: u8,
+    /// 1 to 31.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// 0 for Monday to 6 for Sunday.
+    pub day_of_week: u8,
+}
+
+impl DateTime {
+    /// Date and time of the seconds elapsed since 2000-01-01T00:00:00.
+    pub fn from_seconds(seconds: i64) -> Self {
+        let days = seconds.div_euclid(86400);
+        let time = seconds.rem_euclid(86400);
+
+        // days since 0000-03-01, in 400 year eras of 146097 days.
+        let days = days + 730425;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+            // 2000-01-01 was a Saturday.
+            day_of_week: (seconds.div_euclid(86400) + 5).rem_euclid(7) as u8,
+        }
+    }
+}
+
+/// The result of processing a time message.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimeAction {
+    /// Respond to the acknowledged message.
+    Respond(TimeMessage),
+    /// Publish the time received as a relay.
+    Publish(TimeMessage),
+}
+
+/// The time of an element, kept running from the time last set or
+/// received using `embassy::time`.
+pub struct TimeState {
+    /// Milliseconds since the epoch at the instant of reference.
+    reference: Option<(Instant, u64)>,
+    uncertainty: u8,
+    time_authority: bool,
+    role: TimeRole,
+    tai_utc_delta: u16,
+    tai_utc_delta_new: u16,
+    tai_of_delta_change: u64,
+    time_zone_offset: u8,
+    time_zone_offset_new: u8,
+    tai_of_zone_change: u64,
+}
+
+impl Default for TimeState {
+    fn default() -> Self {
+        Self {
+            reference: None,
+            uncertainty: 0,
+            time_authority: false,
+            role: TimeRole::None,
+            tai_utc_delta: TAI_UTC_DELTA_ZERO,
+            tai_utc_delta_new: TAI_UTC_DELTA_ZERO,
+            tai_of_delta_change: 0,
+            time_zone_offset: TIME_ZONE_OFFSET_ZERO,
+            time_zone_offset_new: TIME_ZONE_OFFSET_ZERO,
+            tai_of_zone_change: 0,
+        }
+    }
+}
+
+impl TimeState {
+    pub fn role(&self) -> TimeRole {
+        self.role
+    }
+
+    pub fn set_role(&mut self, role: TimeRole) {
+        self.role = role;
+    }
+
+    /// The present time, with any scheduled time zone or TAI-UTC delta
+    /// change applied once due.
+    pub fn now(&self) -> Time {
+        let millis = self
+            .reference
+            .map(|(instant, millis)| millis + instant.elapsed().as_millis());
+        match millis {
+            Some(millis) => {
+                let tai_seconds = millis / 1000;
+                Time {
+                    tai_seconds,
+                    subsecond: ((millis % 1000) * 256 / 1000) as u8,
+                    uncertainty: self.uncertainty,
+                    time_authority: self.time_authority,
+                    tai_utc_delta: if self.tai_of_delta_change != 0
+                        && tai_seconds >= self.tai_of_delta_change
+                    {
+                        self.tai_utc_delta_new
+                    } else {
+                        self.tai_utc_delta
+                    },
+                    time_zone_offset: if self.tai_of_zone_change != 0
+                        && tai_seconds >= self.tai_of_zone_change
+                    {
+                        self.time_zone_offset_new
+                    } else {
+                        self.time_zone_offset
+                    },
+                }
+            }
+            None => Time {
+                tai_seconds: 0,
+                subsecond: 0,
+                uncertainty: 0,
+                time_authority: false,
+                tai_utc_delta: self.tai_utc_delta,
+                time_zone_offset: self.time_zone_offset,
+            },
+        }
+    }
+
+    pub fn set(&mut self, time: &Time) {
+        self.reference = if time.tai_seconds == 0 {
+            None
+        } else {
+            Some((
+                Instant::now(),
+                time.tai_seconds * 1000 + time.subsecond as u64 * 1000 / 256,
+            ))
+        };
+        self.uncertainty = time.uncertainty;
+        self.time_authority = time.time_authority;
+        self.tai_utc_delta = time.tai_utc_delta;
+        self.time_zone_offset = time.time_zone_offset;
+    }
+
+    pub fn zone_status(&self) -> TimeZoneStatus {
+        TimeZoneStatus {
+            time_zone_offset_current: self.now().time_zone_offset,
+            time_zone_offset_new: self.time_zone_offset_new,
+            tai_of_zone_change: self.tai_of_zone_change,
+        }
+    }
+
+    pub fn tai_utc_delta_status(&self) -> TaiUtcDeltaStatus {
+        TaiUtcDeltaStatus {
+            tai_utc_delta_current: self.now().tai_utc_delta,
+            tai_utc_delta_new: self.tai_utc_delta_new,
+            tai_of_delta_change: self.tai_of_delta_change,
+        }
+    }
+
+    /// Process a message of the time server or time setup server,
+    /// returning the response or the time to be published as a relay.
+    pub fn process(&mut self, message: &TimeMessage) -> Option<TimeAction> {
+        match message {
+            TimeMessage::Get => Some(TimeAction::Respond(TimeMessage::Status(self.now()))),
+            TimeMessage::Set(time) => {
+                self.set(time);
+                Some(TimeAction::Respond(TimeMessage::Status(self.now())))
+            }
+            TimeMessage::Status(time) => match self.role {
+                TimeRole::Relay | TimeRole::Client
+                    if time.tai_seconds != 0 && (time.time_authority || !self.time_authority) =>
+                {
+                    self.set(time);
+                    if self.role == TimeRole::Relay {
+                        Some(TimeAction::Publish(TimeMessage::Status(self.now())))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            },
+            TimeMessage::RoleGet => Some(TimeAction::Respond(TimeMessage::RoleStatus(self.role))),
+            TimeMessage::RoleSet(role) => {
+                self.role = *role;
+                Some(TimeAction::Respond(TimeMessage::RoleStatus(self.role)))
+            }
+            TimeMessage::ZoneGet => Some(TimeAction::Respond(TimeMessage::ZoneStatus(
+                self.zone_status(),
+            ))),
+            TimeMessage::ZoneSet(set) => {
+                // apply any change already due before scheduling the next.
+                self.time_zone_offset = self.now().time_zone_offset;
+                self.time_zone_offset_new = set.time_zone_offset_new;
+                self.tai_of_zone_change = set.tai_of_zone_change;
+                Some(TimeAction::Respond(TimeMessage::ZoneStatus(
+                    self.zone_status(),
+                )))
+            }
+            TimeMessage::TaiUtcDeltaGet => Some(TimeAction::Respond(
+                TimeMessage::TaiUtcDeltaStatus(self.tai_utc_delta_status()),
+            )),
+            TimeMessage::TaiUtcDeltaSet(set) => {
+                self.tai_utc_delta = self.now().tai_utc_delta;
+                self.tai_utc_delta_new = set.tai_utc_delta_new;
+                self.tai_of_delta_change = set.tai_of_delta_change;
+                Some(TimeAction::Respond(TimeMessage::TaiUtcDeltaStatus(
+                    self.tai_utc_delta_status(),
+                )))
+            }
+            _ => {
+                // not applicable to server role
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_time() {
+        assert_eq!(
+            DateTime {
+                year: 2000,
+                month: 1,
+                day: 1,
+                hour: 0,
+                minute: 0,
+                second: 0,
+                day_of_week: 5,
+            },
+            DateTime::from_seconds(0)
+        );
+        // 2024-02-29T22:00:05, a Thursday.
+        assert_eq!(
+            DateTime {
+                year: 2024,
+                month: 2,
+                day: 29,
+                hour: 22,
+                minute: 0,
+                second: 5,
+                day_of_week: 3,
+            },
+            DateTime::from_seconds(762559205)
+        );
+    }
+
+    #[test]
+    fn time_round_trip() {
+        let time = Time {
+            tai_seconds: 0x12_3456_789A,
+            subsecond: 0x80,
+            uncertainty: 2,
+            time_authority: true,
+            tai_utc_delta: TAI_UTC_DELTA_ZERO + 37,
+            time_zone_offset: TIME_ZONE_OFFSET_ZERO + 4,
+        };
+        let mut xmit: Vec<u8, 16> = Vec::new();
+        time.emit_parameters(&mut xmit).unwrap();
+        assert_eq!(10, xmit.len());
+        let parsed = Time::parse(&xmit).unwrap();
+        assert_eq!(time, parsed);
+        assert_eq!(37, parsed.tai_utc_delta_seconds());
+        assert_eq!(60, parsed.time_zone_offset_minutes());
+    }
+}
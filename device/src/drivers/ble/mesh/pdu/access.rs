@@ -1,7 +1,9 @@
 use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
 use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+use crate::drivers::ble::mesh::config::device_keys::DeviceKeyHandle;
 use crate::drivers::ble::mesh::config::network::NetworkKeyHandle;
 use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::AppKeyIndex;
 use crate::drivers::ble::mesh::model::Message;
 use crate::drivers::ble::mesh::pdu::upper::UpperAccess;
 use crate::drivers::ble::mesh::pdu::ParseError;
@@ -17,8 +19,11 @@ pub struct AccessMessage {
     pub(crate) nid: u8,
     pub(crate) akf: bool,
     pub(crate) aid: ApplicationKeyIdentifier,
+    /// The application key the message was received with, or is sent with.
+    pub(crate) app_key_index: AppKeyIndex,
     pub(crate) src: UnicastAddress,
     pub(crate) dst: Address,
+    pub(crate) device_key: DeviceKeyHandle,
     pub(crate) payload: AccessPayload,
 }
 
@@ -45,8 +50,10 @@ impl AccessMessage {
             nid: access.nid,
             akf: access.akf,
             aid: access.aid,
+            app_key_index: access.app_key_index,
             src: access.src,
             dst: access.dst,
+            device_key: access.device_key,
             payload: AccessPayload::parse(&access.payload)?,
         })
     }
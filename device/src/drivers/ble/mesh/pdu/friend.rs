@@ -0,0 +1,335 @@
+use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
+use crate::drivers::ble::mesh::beacon::Flags;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use embassy::time::Duration;
+use heapless::Vec;
+
+/// Addresses carried by a single unsegmented subscription list message.
+pub const MAX_SUBSCRIPTION_LIST_ADDRESSES: usize = 5;
+
+/// Multiplier applied by a Friend when weighing the receive window and RSSI
+/// of its offer, in tenths.
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Factor {
+    /// 1
+    Ten = 0b00,
+    /// 1.5
+    Fifteen = 0b01,
+    /// 2
+    Twenty = 0b10,
+    /// 2.5
+    TwentyFive = 0b11,
+}
+
+impl Factor {
+    fn parse(data: u8) -> Self {
+        match data & 0b11 {
+            0b00 => Self::Ten,
+            0b01 => Self::Fifteen,
+            0b10 => Self::Twenty,
+            _ => Self::TwentyFive,
+        }
+    }
+
+    /// Scale `val` by the factor.
+    pub fn apply(&self, val: u32) -> u32 {
+        let tenths = match self {
+            Self::Ten => 10,
+            Self::Fifteen => 15,
+            Self::Twenty => 20,
+            Self::TwentyFive => 25,
+        };
+        val * tenths / 10
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FriendCriteria {
+    pub rssi_factor: Factor,
+    pub receive_window_factor: Factor,
+    /// The smallest acceptable friend queue is `2^min_queue_size_log` entries.
+    pub min_queue_size_log: u8,
+}
+
+impl FriendCriteria {
+    pub fn parse(data: u8) -> Result<Self, ParseError> {
+        let min_queue_size_log = data & 0b111;
+        if min_queue_size_log == 0 {
+            // prohibited
+            return Err(ParseError::InvalidValue);
+        }
+        Ok(Self {
+            rssi_factor: Factor::parse(data >> 5),
+            receive_window_factor: Factor::parse(data >> 3),
+            min_queue_size_log,
+        })
+    }
+
+    pub fn emit(&self) -> u8 {
+        (self.rssi_factor as u8) << 5
+            | (self.receive_window_factor as u8) << 3
+            | (self.min_queue_size_log & 0b111)
+    }
+
+    pub fn min_queue_size(&self) -> usize {
+        1 << self.min_queue_size_log
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FriendRequest {
+    pub criteria: FriendCriteria,
+    /// Milliseconds between a request or poll and the start of the receive window.
+    pub receive_delay: u8,
+    /// Longest time between polls, in units of 100 milliseconds.
+    pub poll_timeout: u32,
+    pub previous_address: Option<UnicastAddress>,
+    pub num_elements: u8,
+    pub lpn_counter: u16,
+}
+
+impl FriendRequest {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 10 {
+            return Err(ParseError::InvalidLength);
+        }
+        let criteria = FriendCriteria::parse(parameters[0])?;
+        let receive_delay = parameters[1];
+        if receive_delay < 0x0A {
+            return Err(ParseError::InvalidValue);
+        }
+        let poll_timeout = u32::from_be_bytes([0, parameters[2], parameters[3], parameters[4]]);
+        if !(0x00000A..=0x34BBFF).contains(&poll_timeout) {
+            return Err(ParseError::InvalidValue);
+        }
+        let previous_address = match Address::parse([parameters[5], parameters[6]]) {
+            Address::Unicast(address) => Some(address),
+            _ => None,
+        };
+        let num_elements = parameters[7];
+        if num_elements == 0 {
+            return Err(ParseError::InvalidValue);
+        }
+        Ok(Self {
+            criteria,
+            receive_delay,
+            poll_timeout,
+            previous_address,
+            num_elements,
+            lpn_counter: u16::from_be_bytes([parameters[8], parameters[9]]),
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.criteria.emit())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.receive_delay)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.poll_timeout.to_be_bytes()[1..])
+            .map_err(|_| InsufficientBuffer)?;
+        let previous_address = match self.previous_address {
+            Some(address) => address.as_bytes(),
+            None => [0, 0],
+        };
+        xmit.extend_from_slice(&previous_address)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.num_elements)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.lpn_counter.to_be_bytes())
+            .map_err(|_| InsufficientBuffer)
+    }
+
+    pub fn receive_delay(&self) -> Duration {
+        Duration::from_millis(self.receive_delay as u64)
+    }
+
+    pub fn poll_timeout(&self) -> Duration {
+        Duration::from_millis(self.poll_timeout as u64 * 100)
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FriendOffer {
+    /// Milliseconds the Low Power node listens for once the receive delay has passed.
+    pub receive_window: u8,
+    pub queue_size: u8,
+    pub subscription_list_size: u8,
+    pub rssi: i8,
+    pub friend_counter: u16,
+}
+
+impl FriendOffer {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 6 {
+            return Err(ParseError::InvalidLength);
+        }
+        Ok(Self {
+            receive_window: parameters[0],
+            queue_size: parameters[1],
+            subscription_list_size: parameters[2],
+            rssi: parameters[3] as i8,
+            friend_counter: u16::from_be_bytes([parameters[4], parameters[5]]),
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.receive_window)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.queue_size).map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.subscription_list_size)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.rssi as u8).map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.friend_counter.to_be_bytes())
+            .map_err(|_| InsufficientBuffer)
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FriendPoll {
+    /// Friend sequence number, toggled once the previous message was received.
+    pub fsn: bool,
+}
+
+impl FriendPoll {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 1 {
+            return Err(ParseError::InvalidLength);
+        }
+        if parameters[0] & 0b11111110 != 0 {
+            return Err(ParseError::InvalidValue);
+        }
+        Ok(Self {
+            fsn: parameters[0] != 0,
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.fsn as u8).map_err(|_| InsufficientBuffer)
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FriendUpdate {
+    pub flags: Flags,
+    pub iv_index: u32,
+    /// More data is waiting in the friend queue.
+    pub md: bool,
+}
+
+impl FriendUpdate {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 6 {
+            return Err(ParseError::InvalidLength);
+        }
+        Ok(Self {
+            flags: Flags::parse(parameters[0]),
+            iv_index: u32::from_be_bytes([
+                parameters[1],
+                parameters[2],
+                parameters[3],
+                parameters[4],
+            ]),
+            md: parameters[5] != 0,
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.flags.emit())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.iv_index.to_be_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.md as u8).map_err(|_| InsufficientBuffer)
+    }
+}
+
+/// Parameters of both Friend Clear and Friend Clear Confirm.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FriendClear {
+    pub lpn_address: UnicastAddress,
+    pub lpn_counter: u16,
+}
+
+impl FriendClear {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 4 {
+            return Err(ParseError::InvalidLength);
+        }
+        Ok(Self {
+            lpn_address: UnicastAddress::parse([parameters[0], parameters[1]])?,
+            lpn_counter: u16::from_be_bytes([parameters[2], parameters[3]]),
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.lpn_address.as_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.lpn_counter.to_be_bytes())
+            .map_err(|_| InsufficientBuffer)
+    }
+}
+
+/// Parameters of both Friend Subscription List Add and Remove.
+#[derive(Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FriendSubscriptionList {
+    pub transaction_number: u8,
+    pub addresses: Vec<Address, MAX_SUBSCRIPTION_LIST_ADDRESSES>,
+}
+
+impl FriendSubscriptionList {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() < 3 || parameters.len() % 2 != 1 {
+            return Err(ParseError::InvalidLength);
+        }
+        let mut addresses = Vec::new();
+        for address in parameters[1..].chunks(2) {
+            addresses
+                .push(Address::parse([address[0], address[1]]))
+                .map_err(|_| ParseError::InsufficientBuffer)?;
+        }
+        Ok(Self {
+            transaction_number: parameters[0],
+            addresses,
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.transaction_number)
+            .map_err(|_| InsufficientBuffer)?;
+        for address in &self.addresses {
+            xmit.extend_from_slice(&address.as_bytes())
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FriendSubscriptionListConfirm {
+    pub transaction_number: u8,
+}
+
+impl FriendSubscriptionListConfirm {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 1 {
+            return Err(ParseError::InvalidLength);
+        }
+        Ok(Self {
+            transaction_number: parameters[0],
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.transaction_number)
+            .map_err(|_| InsufficientBuffer)
+    }
+}
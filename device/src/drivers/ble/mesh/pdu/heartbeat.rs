@@ -0,0 +1,81 @@
+use crate::drivers::ble::mesh::model::foundation::configuration::heartbeat::Features;
+use crate::drivers::ble::mesh::pdu::ParseError;
+use crate::drivers::ble::mesh::InsufficientBuffer;
+use heapless::Vec;
+
+/// Transport control message periodically sent to report a node is alive,
+/// and which of its features are in use.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Heartbeat {
+    /// TTL the heartbeat was sent with, to determine how many hops it took.
+    pub init_ttl: u8,
+    pub features: Features,
+}
+
+impl Heartbeat {
+    pub fn parse(parameters: &[u8]) -> Result<Self, ParseError> {
+        if parameters.len() != 3 {
+            return Err(ParseError::InvalidLength);
+        }
+        Ok(Self {
+            init_ttl: parameters[0] & 0x7F,
+            features: Features::parse(u16::from_be_bytes([parameters[1], parameters[2]])),
+        })
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(self.init_ttl & 0x7F)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.features.bits().to_be_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
+
+    /// Hops taken by a heartbeat received with `ttl`.
+    pub fn hops(&self, ttl: u8) -> u8 {
+        (self.init_ttl.saturating_sub(ttl) + 1).min(0x7F)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(init_ttl: u8) -> Heartbeat {
+        Heartbeat {
+            init_ttl,
+            features: Features::default(),
+        }
+    }
+
+    #[test]
+    fn hops() {
+        // received from a neighbour.
+        assert_eq!(heartbeat(0).hops(0), 1);
+        assert_eq!(heartbeat(1).hops(1), 1);
+        assert_eq!(heartbeat(1).hops(0), 2);
+        assert_eq!(heartbeat(0x11).hops(0x01), 0x11);
+        // a TTL above the initial one is not a negative hop count.
+        assert_eq!(heartbeat(0).hops(1), 1);
+        // nor more than the largest reportable.
+        assert_eq!(heartbeat(0x7F).hops(0), 0x7F);
+    }
+
+    #[test]
+    fn parse_and_emit() {
+        let mut xmit = Vec::<u8, 3>::new();
+        Heartbeat {
+            init_ttl: 0xFF,
+            features: Features::default(),
+        }
+        .emit(&mut xmit)
+        .unwrap();
+        assert_eq!(xmit[0], 0x7F);
+        assert_eq!(Heartbeat::parse(&xmit).unwrap().init_ttl, 0x7F);
+        assert!(matches!(
+            Heartbeat::parse(&xmit[..2]),
+            Err(ParseError::InvalidLength)
+        ));
+    }
+}
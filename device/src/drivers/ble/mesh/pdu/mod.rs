@@ -1,5 +1,7 @@
 pub mod access;
 pub mod bearer;
+pub mod friend;
+pub mod heartbeat;
 pub mod lower;
 pub mod network;
 pub mod proxy;
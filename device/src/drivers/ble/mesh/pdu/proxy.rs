@@ -1,3 +1,4 @@
+use crate::drivers::ble::mesh::address::Address;
 use crate::drivers::ble::mesh::pdu::ParseError;
 use crate::drivers::ble::mesh::InsufficientBuffer;
 use heapless::Vec;
@@ -101,3 +102,152 @@ impl ProxyPDU {
         })
     }
 }
+
+/// Maximum number of addresses carried by a single proxy configuration message.
+pub const MAX_PROXY_CONFIGURATION_ADDRESSES: usize = 8;
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq)]
+pub enum FilterType {
+    AcceptList = 0x00,
+    RejectList = 0x01,
+}
+
+impl FilterType {
+    pub fn parse(data: u8) -> Result<Self, ParseError> {
+        match data {
+            0x00 => Ok(Self::AcceptList),
+            0x01 => Ok(Self::RejectList),
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+}
+
+/// Messages configuring the proxy filter of a proxy server, exchanged with
+/// a proxy client over its connection.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProxyConfigurationMessage {
+    SetFilterType(FilterType),
+    AddAddresses(Vec<Address, MAX_PROXY_CONFIGURATION_ADDRESSES>),
+    RemoveAddresses(Vec<Address, MAX_PROXY_CONFIGURATION_ADDRESSES>),
+    FilterStatus {
+        filter_type: FilterType,
+        list_size: u16,
+    },
+}
+
+impl ProxyConfigurationMessage {
+    const SET_FILTER_TYPE: u8 = 0x00;
+    const ADD_ADDRESSES: u8 = 0x01;
+    const REMOVE_ADDRESSES: u8 = 0x02;
+    const FILTER_STATUS: u8 = 0x03;
+
+    pub fn parse(data: &[u8]) -> Result<Self, ParseError> {
+        if data.is_empty() {
+            return Err(ParseError::InvalidLength);
+        }
+        let parameters = &data[1..];
+        match data[0] {
+            Self::SET_FILTER_TYPE => {
+                if parameters.len() != 1 {
+                    return Err(ParseError::InvalidLength);
+                }
+                Ok(Self::SetFilterType(FilterType::parse(parameters[0])?))
+            }
+            Self::ADD_ADDRESSES => Ok(Self::AddAddresses(Self::parse_addresses(parameters)?)),
+            Self::REMOVE_ADDRESSES => Ok(Self::RemoveAddresses(Self::parse_addresses(parameters)?)),
+            Self::FILTER_STATUS => {
+                if parameters.len() != 3 {
+                    return Err(ParseError::InvalidLength);
+                }
+                Ok(Self::FilterStatus {
+                    filter_type: FilterType::parse(parameters[0])?,
+                    list_size: u16::from_be_bytes([parameters[1], parameters[2]]),
+                })
+            }
+            _ => Err(ParseError::InvalidValue),
+        }
+    }
+
+    fn parse_addresses(
+        parameters: &[u8],
+    ) -> Result<Vec<Address, MAX_PROXY_CONFIGURATION_ADDRESSES>, ParseError> {
+        if parameters.len() % 2 != 0 {
+            return Err(ParseError::InvalidLength);
+        }
+        let mut addresses = Vec::new();
+        for address in parameters.chunks(2) {
+            addresses
+                .push(Address::parse([address[0], address[1]]))
+                .map_err(|_| ParseError::InsufficientBuffer)?;
+        }
+        Ok(addresses)
+    }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        match self {
+            Self::SetFilterType(filter_type) => {
+                xmit.push(Self::SET_FILTER_TYPE)
+                    .map_err(|_| InsufficientBuffer)?;
+                xmit.push(*filter_type as u8)
+                    .map_err(|_| InsufficientBuffer)?;
+            }
+            Self::AddAddresses(addresses) => {
+                xmit.push(Self::ADD_ADDRESSES)
+                    .map_err(|_| InsufficientBuffer)?;
+                Self::emit_addresses(addresses, xmit)?;
+            }
+            Self::RemoveAddresses(addresses) => {
+                xmit.push(Self::REMOVE_ADDRESSES)
+                    .map_err(|_| InsufficientBuffer)?;
+                Self::emit_addresses(addresses, xmit)?;
+            }
+            Self::FilterStatus {
+                filter_type,
+                list_size,
+            } => {
+                xmit.push(Self::FILTER_STATUS)
+                    .map_err(|_| InsufficientBuffer)?;
+                xmit.push(*filter_type as u8)
+                    .map_err(|_| InsufficientBuffer)?;
+                xmit.extend_from_slice(&list_size.to_be_bytes())
+                    .map_err(|_| InsufficientBuffer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_addresses<const N: usize>(
+        addresses: &[Address],
+        xmit: &mut Vec<u8, N>,
+    ) -> Result<(), InsufficientBuffer> {
+        for address in addresses {
+            xmit.extend_from_slice(&address.as_bytes())
+                .map_err(|_| InsufficientBuffer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proxy_configuration_message() {
+        let message = ProxyConfigurationMessage::parse(&[0x01, 0x00, 0x01, 0xC0, 0x00]).unwrap();
+        let mut data: Vec<u8, 8> = Vec::new();
+        message.emit(&mut data).unwrap();
+        assert_eq!(&data[..], &[0x01, 0x00, 0x01, 0xC0, 0x00]);
+
+        if let ProxyConfigurationMessage::AddAddresses(addresses) = message {
+            assert_eq!(addresses.len(), 2);
+            assert!(matches!(addresses[1], Address::Group(_)));
+        } else {
+            panic!("not add addresses");
+        }
+
+        assert!(ProxyConfigurationMessage::parse(&[0x02, 0x00]).is_err());
+        assert!(ProxyConfigurationMessage::parse(&[0x00, 0x02]).is_err());
+    }
+}
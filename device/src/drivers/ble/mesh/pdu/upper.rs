@@ -1,6 +1,8 @@
 use crate::drivers::ble::mesh::address::{Address, UnicastAddress};
 use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+use crate::drivers::ble::mesh::config::device_keys::DeviceKeyHandle;
 use crate::drivers::ble::mesh::config::network::NetworkKeyHandle;
+use crate::drivers::ble::mesh::model::foundation::configuration::AppKeyIndex;
 use crate::drivers::ble::mesh::pdu::access::AccessMessage;
 use crate::drivers::ble::mesh::pdu::lower::Opcode;
 use crate::drivers::ble::mesh::pdu::ParseError;
@@ -33,8 +35,10 @@ pub struct UpperAccess {
     pub(crate) nid: u8,
     pub(crate) akf: bool,
     pub(crate) aid: ApplicationKeyIdentifier,
+    pub(crate) app_key_index: AppKeyIndex,
     pub(crate) src: UnicastAddress,
     pub(crate) dst: Address,
+    pub(crate) device_key: DeviceKeyHandle,
     pub(crate) payload: Vec<u8, 380>,
 }
 
@@ -284,6 +284,16 @@ impl Data {
             })
         }
     }
+
+    fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(ProvisioningPDU::DATA)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.encrypted)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.mic)
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
 }
 
 /// The decrypted provisioning data wrapped in `Data` above.
@@ -319,6 +329,26 @@ impl ProvisioningData {
             })
         }
     }
+
+    pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.extend_from_slice(&self.network_key)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.key_index.value().to_be_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        let mut flags = 0;
+        if let KeyRefreshFlag::Phase2 = self.key_refresh_flag {
+            flags |= 0b00000001;
+        }
+        if let IVUpdateFlag::UpdateActive = self.iv_update_flag {
+            flags |= 0b00000010;
+        }
+        xmit.push(flags).map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.iv_index.to_be_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.extend_from_slice(&self.unicast_address.as_bytes())
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
 }
 
 // TODO: probably move this elsewhere
@@ -399,6 +429,14 @@ impl Failed {
             })
         }
     }
+
+    fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
+        xmit.push(ProvisioningPDU::FAILED)
+            .map_err(|_| InsufficientBuffer)?;
+        xmit.push(self.error_code as u8)
+            .map_err(|_| InsufficientBuffer)?;
+        Ok(())
+    }
 }
 
 impl ProvisioningPDU {
@@ -435,26 +473,18 @@ impl ProvisioningPDU {
 
     pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
         match self {
-            ProvisioningPDU::Invite(_) => {
-                unimplemented!()
-            }
+            ProvisioningPDU::Invite(invite) => invite.emit(xmit),
             ProvisioningPDU::Capabilities(capabilities) => capabilities.emit(xmit),
-            ProvisioningPDU::Start(_) => {
-                unimplemented!()
-            }
+            ProvisioningPDU::Start(start) => start.emit(xmit),
             ProvisioningPDU::PublicKey(public_key) => public_key.emit(xmit),
             ProvisioningPDU::InputComplete => xmit
                 .push(Self::INPUT_COMPLETE)
                 .map_err(|_| InsufficientBuffer),
             ProvisioningPDU::Confirmation(confirmation) => confirmation.emit(xmit),
             ProvisioningPDU::Random(random) => random.emit(xmit),
-            ProvisioningPDU::Data(_) => {
-                unimplemented!()
-            }
+            ProvisioningPDU::Data(data) => data.emit(xmit),
             ProvisioningPDU::Complete => xmit.push(Self::COMPLETE).map_err(|_| InsufficientBuffer),
-            ProvisioningPDU::Failed(_) => {
-                unimplemented!()
-            }
+            ProvisioningPDU::Failed(failed) => failed.emit(xmit),
         }
     }
 
@@ -656,7 +686,7 @@ impl OOBSize {
     pub fn parse(octet: u8) -> Result<Self, ParseError> {
         if octet == 0 {
             Ok(Self::NotSupported)
-        } else if octet < 8 {
+        } else if octet <= 8 {
             Ok(Self::MaximumSize(octet))
         } else {
             Err(ParseError::InvalidValue)
@@ -712,37 +742,41 @@ impl OutputOOBActions {
         self.0.push(action)
     }
 
+    pub fn contains(&self, action: OutputOOBAction) -> bool {
+        self.0.iter().any(|e| *e as u16 == action as u16)
+    }
+
     pub fn parse(bits: u16) -> Result<Self, ParseError> {
         if bits & 0b1111111111100000 != 0 {
             return Err(ParseError::InvalidValue);
         }
 
         let mut actions = OutputOOBActions::new();
-        if bits & 0b00000001 == 1 {
+        if bits & 0b00000001 != 0 {
             actions
                 .push(OutputOOBAction::Blink)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
         }
 
-        if bits & 0b00000010 == 1 {
+        if bits & 0b00000010 != 0 {
             actions
                 .push(OutputOOBAction::Beep)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
         }
 
-        if bits & 0b00000100 == 1 {
+        if bits & 0b00000100 != 0 {
             actions
                 .push(OutputOOBAction::Vibrate)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
         }
 
-        if bits & 0b00001000 == 1 {
+        if bits & 0b00001000 != 0 {
             actions
                 .push(OutputOOBAction::OutputNumeric)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
         }
 
-        if bits & 0b00010000 == 1 {
+        if bits & 0b00010000 != 0 {
             actions
                 .push(OutputOOBAction::OutputAlphanumeric)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
@@ -810,31 +844,35 @@ impl InputOOBActions {
         self.0.push(action)
     }
 
+    pub fn contains(&self, action: InputOOBAction) -> bool {
+        self.0.iter().any(|e| *e as u16 == action as u16)
+    }
+
     pub fn parse(bits: u16) -> Result<Self, ParseError> {
         if bits & 0b1111111111110000 != 0 {
             return Err(ParseError::InvalidValue);
         }
 
         let mut actions = InputOOBActions::new();
-        if bits & 0b00000001 == 1 {
+        if bits & 0b00000001 != 0 {
             actions
                 .push(InputOOBAction::Push)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
         }
 
-        if bits & 0b00000010 == 1 {
+        if bits & 0b00000010 != 0 {
             actions
                 .push(InputOOBAction::Twist)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
         }
 
-        if bits & 0b00000100 == 1 {
+        if bits & 0b00000100 != 0 {
             actions
                 .push(InputOOBAction::InputNumeric)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
         }
 
-        if bits & 0b00001000 == 1 {
+        if bits & 0b00001000 != 0 {
             actions
                 .push(InputOOBAction::InputAlphanumeric)
                 .map_err(|_| ParseError::InsufficientBuffer)?;
@@ -891,18 +929,14 @@ impl OOBAction {
         }
     }
 
+    /// Emits the single-octet action index used by the Start PDU.
     pub fn emit<const N: usize>(&self, xmit: &mut Vec<u8, N>) -> Result<(), InsufficientBuffer> {
-        match self {
-            OOBAction::None => xmit.push(0x00).map_err(|_| InsufficientBuffer)?,
-            OOBAction::Output(action) => {
-                action.emit(xmit)?;
-            }
-            OOBAction::Input(action) => {
-                action.emit(xmit)?;
-            }
-        }
-
-        Ok(())
+        let index = match self {
+            OOBAction::None => 0x00,
+            OOBAction::Output(action) => (*action as u16).trailing_zeros() as u8,
+            OOBAction::Input(action) => (*action as u16).trailing_zeros() as u8,
+        };
+        xmit.push(index).map_err(|_| InsufficientBuffer)
     }
 }
 
@@ -945,7 +979,7 @@ impl AuthenticationMethod {
     }
 }
 
-#[derive(Clone)]
+#[derive(Copy, Clone, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ErrorCode {
     Prohibited = 0x00,
@@ -975,3 +1009,37 @@ impl ErrorCode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oob_actions_round_trip() {
+        for bits in 0..0b100000u16 {
+            let mut xmit: Vec<u8, 2> = Vec::new();
+            OutputOOBActions::parse(bits)
+                .unwrap()
+                .emit(&mut xmit)
+                .unwrap();
+            assert_eq!(u16::from_be_bytes([xmit[0], xmit[1]]), bits);
+        }
+        assert!(OutputOOBActions::parse(0b100000).is_err());
+
+        for bits in 0..0b10000u16 {
+            let mut xmit: Vec<u8, 2> = Vec::new();
+            InputOOBActions::parse(bits)
+                .unwrap()
+                .emit(&mut xmit)
+                .unwrap();
+            assert_eq!(u16::from_be_bytes([xmit[0], xmit[1]]), bits);
+        }
+        assert!(InputOOBActions::parse(0b10000).is_err());
+    }
+
+    #[test]
+    fn oob_size_up_to_eight() {
+        assert!(matches!(OOBSize::parse(8), Ok(OOBSize::MaximumSize(8))));
+        assert!(OOBSize::parse(9).is_err());
+    }
+}
@@ -1,5 +1,9 @@
+use crate::drivers::ble::mesh::driver::DeviceError;
 use core::future::Future;
 use embedded_storage_async::nor_flash::{AsyncNorFlash, AsyncReadNorFlash};
+use postcard::{from_bytes, to_slice};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 /*
 use p256::ecdh::SharedSecret;
 use p256::elliptic_curve::group::GroupEncoding;
@@ -33,9 +37,12 @@ impl Keys {
 }
  */
 
+/// Size of a payload, enough to hold a fully populated configuration.
+pub const PAYLOAD_SIZE: usize = 2032;
+
 #[repr(align(4))]
 pub struct Payload {
-    pub payload: [u8; 512],
+    pub payload: [u8; PAYLOAD_SIZE],
 }
 
 pub trait Storage {
@@ -52,21 +59,323 @@ pub trait Storage {
     fn retrieve<'m>(&'m mut self) -> Self::RetrieveFuture<'m>;
 }
 
-/// Flash storage implementation
+/// Magic marking a payload written by [`store_versioned`].
+const VERSIONED_MAGIC: [u8; 2] = [0xD5, 0x7A];
+const VERSIONED_HEADER_LEN: usize = 4;
+
+/// Store `value` serialized with postcard, behind a header tagging the
+/// `version` of its layout:
+///
+/// ```text
+/// [0xD5, 0x7A, version (u16, little-endian), value...]
+/// ```
+pub async fn store_versioned<S: Storage, T: Serialize>(
+    storage: &mut S,
+    version: u16,
+    value: &T,
+) -> Result<(), DeviceError> {
+    let mut payload = Payload {
+        payload: [0; PAYLOAD_SIZE],
+    };
+    payload.payload[0..2].copy_from_slice(&VERSIONED_MAGIC);
+    payload.payload[2..VERSIONED_HEADER_LEN].copy_from_slice(&version.to_le_bytes());
+    to_slice(value, &mut payload.payload[VERSIONED_HEADER_LEN..])?;
+    storage
+        .store(&payload)
+        .await
+        .map_err(|_| DeviceError::Storage)
+}
+
+/// Retrieve a value stored with [`store_versioned`], or `None` if nothing
+/// has been stored yet.
+///
+/// A payload of another version, or failing to decode, is reported as
+/// [`DeviceError::Serialization`] rather than taken as missing, so callers
+/// don't overwrite it unnoticed.
+pub async fn load_versioned<S: Storage, T: DeserializeOwned>(
+    storage: &mut S,
+    version: u16,
+) -> Result<Option<T>, DeviceError> {
+    match storage.retrieve().await.map_err(|_| DeviceError::Storage)? {
+        Some(payload) => decode_versioned(&payload.payload, version).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn decode_versioned<T: DeserializeOwned>(payload: &[u8], version: u16) -> Result<T, DeviceError> {
+    if payload[0..2] != VERSIONED_MAGIC {
+        warn!("stored payload is not versioned");
+        return Err(DeviceError::Serialization);
+    }
+    let stored = u16::from_le_bytes([payload[2], payload[3]]);
+    if stored != version {
+        warn!("unsupported stored payload version {}", stored);
+        return Err(DeviceError::Serialization);
+    }
+    Ok(from_bytes(&payload[VERSIONED_HEADER_LEN..])?)
+}
+
+/// Magic marking the start of a record written by [`FlashStorage`].
+const RECORD_MAGIC: [u8; 2] = [0xB7, 0x3E];
+
+/// Version of the layout of a record, records of other versions are ignored.
+const RECORD_VERSION: u8 = 1;
+
+const HEADER_SIZE: usize = 16;
+// two records fill a page of 4 KiB.
+const RECORD_SIZE: usize = HEADER_SIZE + PAYLOAD_SIZE;
+
+/// Largest slot, being a record padded to the write size of the flash.
+const MAX_SLOT_SIZE: usize = RECORD_SIZE + 48;
+
+/// Size of the payload written raw by earlier releases.
+const LEGACY_PAYLOAD_SIZE: usize = 512;
+
+#[repr(align(4))]
+struct Slot([u8; MAX_SLOT_SIZE]);
+
+/// Position of the journal, as found by scanning the flash.
+#[derive(Copy, Clone)]
+struct Cursor {
+    /// Slot holding the most recent valid record, if any.
+    latest: Option<u32>,
+    /// Sequence number of the next record.
+    seq: u32,
+    /// Slot following the most recent record.
+    next: u32,
+}
+
+/// Flash storage implementation, journaling payloads across the erase pages
+/// between `start` and `end`.
+///
+/// Every store appends a record, with a sequence number and CRC, to the next
+/// free slot of the current page, and moves on to the next page once full,
+/// erasing it beforehand. The page holding the most recent record is never
+/// erased, so the previous payload survives a power loss at any point of a
+/// store, and the erasures are spread evenly across the pages.
+///
+/// At least two erase pages are required.
+///
+/// Until the first record is stored, the payload written raw at a
+/// [legacy address](FlashStorage::with_legacy_address) by earlier releases is
+/// retrieved instead.
 pub struct FlashStorage<F>
 where
     F: AsyncNorFlash + AsyncReadNorFlash,
 {
-    address: usize,
+    start: u32,
+    pages: u32,
     flash: F,
+    cursor: Option<Cursor>,
+    legacy: Option<u32>,
 }
 
 impl<F> FlashStorage<F>
 where
     F: AsyncNorFlash + AsyncReadNorFlash,
 {
-    pub fn new(address: usize, flash: F) -> Self {
-        Self { address, flash }
+    pub fn new(start: usize, end: usize, flash: F) -> Self {
+        assert!(F::WRITE_SIZE <= MAX_SLOT_SIZE - RECORD_SIZE);
+        let pages = ((end - start) / F::ERASE_SIZE) as u32;
+        assert!(
+            pages >= 2,
+            "flash storage requires at least two erase pages"
+        );
+        let me = Self {
+            start: start as u32,
+            pages,
+            flash,
+            cursor: None,
+            legacy: None,
+        };
+        assert!(me.slots_per_page() > 0);
+        me
+    }
+
+    /// Retrieve the payload stored raw at `address` by earlier releases,
+    /// until a record is stored. When within the journal, the page holding
+    /// it is the last to be erased.
+    pub fn with_legacy_address(self, address: usize) -> Self {
+        Self {
+            legacy: Some(address as u32),
+            ..self
+        }
+    }
+
+    /// Release the underlying flash.
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    fn slot_size(&self) -> u32 {
+        let write_size = F::WRITE_SIZE.max(1);
+        (((RECORD_SIZE + write_size - 1) / write_size) * write_size) as u32
+    }
+
+    fn slots_per_page(&self) -> u32 {
+        F::ERASE_SIZE as u32 / self.slot_size()
+    }
+
+    fn slots(&self) -> u32 {
+        self.pages * self.slots_per_page()
+    }
+
+    fn address(&self, slot: u32) -> u32 {
+        let page = slot / self.slots_per_page();
+        let index = slot % self.slots_per_page();
+        self.start + page * F::ERASE_SIZE as u32 + index * self.slot_size()
+    }
+
+    /// The page of the journal holding the legacy payload, if any.
+    fn legacy_page(&self) -> Option<u32> {
+        match self.legacy {
+            Some(address)
+                if address >= self.start
+                    && address < self.start + self.pages * F::ERASE_SIZE as u32 =>
+            {
+                Some((address - self.start) / F::ERASE_SIZE as u32)
+            }
+            _ => None,
+        }
+    }
+
+    /// The legacy payload, unless blank.
+    async fn legacy(&mut self) -> Result<Option<Payload>, ()> {
+        match self.legacy {
+            Some(address) => {
+                let mut payload = [0; PAYLOAD_SIZE];
+                self.flash
+                    .read(address, &mut payload[..LEGACY_PAYLOAD_SIZE])
+                    .await
+                    .map_err(|_| ())?;
+                if payload[..LEGACY_PAYLOAD_SIZE].iter().all(|b| *b == 0xFF) {
+                    Ok(None)
+                } else {
+                    Ok(Some(Payload { payload }))
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn read_slot(&mut self, slot: u32, buf: &mut Slot) -> Result<(), ()> {
+        let address = self.address(slot);
+        let len = self.slot_size() as usize;
+        self.flash
+            .read(address, &mut buf.0[..len])
+            .await
+            .map_err(|_| ())
+    }
+
+    fn is_blank(&self, buf: &Slot) -> bool {
+        buf.0[..self.slot_size() as usize]
+            .iter()
+            .all(|b| *b == 0xFF)
+    }
+
+    async fn cursor(&mut self) -> Result<Cursor, ()> {
+        if let Some(cursor) = self.cursor {
+            return Ok(cursor);
+        }
+
+        let mut buf = Slot([0; MAX_SLOT_SIZE]);
+        let mut latest: Option<(u32, u32)> = None;
+        for slot in 0..self.slots() {
+            self.read_slot(slot, &mut buf).await?;
+            if let Some(seq) = verify(&buf.0[..RECORD_SIZE]) {
+                if latest.map(|(_, latest)| seq > latest).unwrap_or(true) {
+                    latest.replace((slot, seq));
+                }
+            }
+        }
+
+        let cursor = match latest {
+            Some((slot, seq)) => Cursor {
+                latest: Some(slot),
+                seq: seq.wrapping_add(1),
+                next: (slot + 1) % self.slots(),
+            },
+            None => Cursor {
+                latest: None,
+                seq: 0,
+                next: match self.legacy_page() {
+                    Some(page) => ((page + 1) % self.pages) * self.slots_per_page(),
+                    None => 0,
+                },
+            },
+        };
+        self.cursor.replace(cursor);
+        Ok(cursor)
+    }
+
+    async fn append(&mut self, payload: &Payload) -> Result<(), ()> {
+        let cursor = self.cursor().await?;
+        let mut buf = Slot([0; MAX_SLOT_SIZE]);
+
+        // skip slots left dirty by an interrupted store, erasing the page
+        // before writing its first slot.
+        let mut slot = cursor.next;
+        loop {
+            if slot % self.slots_per_page() == 0 {
+                let from = self.address(slot);
+                self.flash
+                    .erase(from, from + F::ERASE_SIZE as u32)
+                    .await
+                    .map_err(|_| ())?;
+                break;
+            }
+            self.read_slot(slot, &mut buf).await?;
+            if self.is_blank(&buf) {
+                break;
+            }
+            slot = (slot + 1) % self.slots();
+        }
+
+        // the cursor is scanned again should the write fail.
+        self.cursor.take();
+
+        buf.0.fill(0xFF);
+        buf.0[0..2].copy_from_slice(&RECORD_MAGIC);
+        buf.0[2] = RECORD_VERSION;
+        buf.0[3] = 0;
+        buf.0[4..8].copy_from_slice(&cursor.seq.to_le_bytes());
+        buf.0[8..10].copy_from_slice(&(PAYLOAD_SIZE as u16).to_le_bytes());
+        buf.0[10..12].copy_from_slice(&[0, 0]);
+        buf.0[HEADER_SIZE..RECORD_SIZE].copy_from_slice(&payload.payload);
+        let crc = crc32(&[&buf.0[..12], &buf.0[HEADER_SIZE..RECORD_SIZE]]);
+        buf.0[12..16].copy_from_slice(&crc.to_le_bytes());
+
+        let address = self.address(slot);
+        let len = self.slot_size() as usize;
+        self.flash
+            .write(address, &buf.0[..len])
+            .await
+            .map_err(|_| ())?;
+
+        self.cursor.replace(Cursor {
+            latest: Some(slot),
+            seq: cursor.seq.wrapping_add(1),
+            next: (slot + 1) % self.slots(),
+        });
+        Ok(())
+    }
+
+    async fn latest(&mut self) -> Result<Option<Payload>, ()> {
+        let cursor = self.cursor().await?;
+        if let Some(slot) = cursor.latest {
+            let mut buf = Slot([0; MAX_SLOT_SIZE]);
+            self.read_slot(slot, &mut buf).await?;
+            if verify(&buf.0[..RECORD_SIZE]).is_some() {
+                let mut payload = [0; PAYLOAD_SIZE];
+                payload.copy_from_slice(&buf.0[HEADER_SIZE..RECORD_SIZE]);
+                return Ok(Some(Payload { payload }));
+            }
+            // modified behind our back, rescan next time.
+            self.cursor.take();
+            return Err(());
+        }
+        // until the journal holds a valid record.
+        self.legacy().await
     }
 }
 
@@ -78,17 +387,8 @@ where
     where
         Self: 'm;
 
-    fn store<'m>(&'m mut self, keys: &'m Payload) -> Self::StoreFuture<'m> {
-        async move {
-            self.flash
-                .erase(self.address as u32, self.address as u32 + 4096)
-                .await
-                .map_err(|_| ())?;
-            self.flash
-                .write(self.address as u32, &keys.payload)
-                .await
-                .map_err(|_| ())
-        }
+    fn store<'m>(&'m mut self, payload: &'m Payload) -> Self::StoreFuture<'m> {
+        async move { self.append(payload).await }
     }
 
     type RetrieveFuture<'m> = impl Future<Output = Result<Option<Payload>, ()>>
@@ -96,13 +396,226 @@ where
         Self: 'm;
 
     fn retrieve<'m>(&'m mut self) -> Self::RetrieveFuture<'m> {
-        async move {
-            let mut payload = [0; 512];
-            self.flash
-                .read(self.address as u32, &mut payload)
-                .await
-                .map_err(|_| ())?;
-            Ok(Some(Payload { payload }))
+        async move { self.latest().await }
+    }
+}
+
+/// The sequence number of a complete record of the current version.
+fn verify(record: &[u8]) -> Option<u32> {
+    if record[0..2] != RECORD_MAGIC
+        || record[2] != RECORD_VERSION
+        || u16::from_le_bytes([record[8], record[9]]) as usize != PAYLOAD_SIZE
+    {
+        return None;
+    }
+    let crc = u32::from_le_bytes([record[12], record[13], record[14], record[15]]);
+    if crc32(&[&record[..12], &record[HEADER_SIZE..RECORD_SIZE]]) == crc {
+        Some(u32::from_le_bytes([
+            record[4], record[5], record[6], record[7],
+        ]))
+    } else {
+        None
+    }
+}
+
+/// CRC-32 (IEEE 802.3) over consecutive chunks of data.
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for chunk in chunks {
+        for byte in chunk.iter() {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (!(crc & 1)).wrapping_add(1);
+                crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::ram::RamFlash;
+    use futures::executor::block_on;
+
+    const SIZE: usize = 8192;
+
+    fn payload(value: u8) -> Payload {
+        Payload {
+            payload: [value; PAYLOAD_SIZE],
+        }
+    }
+
+    fn value(storage: &mut FlashStorage<RamFlash<SIZE>>) -> Option<u8> {
+        block_on(storage.retrieve())
+            .unwrap()
+            .map(|payload| payload.payload[0])
+    }
+
+    // as after a reset of the device.
+    fn reopen(storage: FlashStorage<RamFlash<SIZE>>) -> FlashStorage<RamFlash<SIZE>> {
+        let mut flash = storage.release();
+        flash.restore();
+        FlashStorage::new(0, SIZE, flash)
+    }
+
+    #[test]
+    fn blank_flash_is_empty() {
+        let mut storage = FlashStorage::new(0, SIZE, RamFlash::<SIZE>::new());
+        assert_eq!(None, value(&mut storage));
+    }
+
+    #[test]
+    fn rotates_across_pages() {
+        let mut storage = FlashStorage::new(0, SIZE, RamFlash::<SIZE>::new());
+        // several times around both pages.
+        for i in 0..40 {
+            block_on(storage.store(&payload(i))).unwrap();
+            assert_eq!(Some(i), value(&mut storage));
+        }
+        let mut storage = reopen(storage);
+        assert_eq!(Some(39), value(&mut storage));
+        block_on(storage.store(&payload(40))).unwrap();
+        let mut storage = reopen(storage);
+        assert_eq!(Some(40), value(&mut storage));
+    }
+
+    #[test]
+    fn survives_interrupted_write() {
+        let mut storage = FlashStorage::new(0, SIZE, RamFlash::<SIZE>::new());
+        block_on(storage.store(&payload(1))).unwrap();
+
+        let mut flash = storage.release();
+        flash.interrupt_after(100);
+        let mut storage = FlashStorage::new(0, SIZE, flash);
+        assert!(block_on(storage.store(&payload(2))).is_err());
+
+        let mut storage = reopen(storage);
+        assert_eq!(Some(1), value(&mut storage));
+        block_on(storage.store(&payload(3))).unwrap();
+        let mut storage = reopen(storage);
+        assert_eq!(Some(3), value(&mut storage));
+    }
+
+    #[test]
+    fn survives_interrupted_erase() {
+        let mut storage = FlashStorage::new(0, SIZE, RamFlash::<SIZE>::new());
+        let slots = storage.slots_per_page() as u8;
+        for i in 0..slots {
+            block_on(storage.store(&payload(i))).unwrap();
         }
+        // the next store starts the second page, dirty from earlier use.
+        let mut flash = storage.release();
+        block_on(flash.write(4096, &[0; 8])).unwrap();
+        flash.interrupt_after(1000);
+        let mut storage = FlashStorage::new(0, SIZE, flash);
+        assert!(block_on(storage.store(&payload(0xAA))).is_err());
+
+        let mut storage = reopen(storage);
+        assert_eq!(Some(slots - 1), value(&mut storage));
+        block_on(storage.store(&payload(0xAA))).unwrap();
+        let mut storage = reopen(storage);
+        assert_eq!(Some(0xAA), value(&mut storage));
+    }
+
+    #[test]
+    fn ignores_unknown_contents() {
+        let mut flash = RamFlash::<SIZE>::new();
+        block_on(flash.write(0, &[0x12; 512])).unwrap();
+        let mut storage = FlashStorage::new(0, SIZE, flash);
+        assert_eq!(None, value(&mut storage));
+        block_on(storage.store(&payload(7))).unwrap();
+        let mut storage = reopen(storage);
+        assert_eq!(Some(7), value(&mut storage));
+    }
+
+    #[test]
+    fn retrieves_legacy_payload_until_stored() {
+        let mut flash = RamFlash::<SIZE>::new();
+        block_on(flash.write(0, &[0x42; 512])).unwrap();
+        let mut storage = FlashStorage::new(0, SIZE, flash).with_legacy_address(0);
+        assert_eq!(Some(0x42), value(&mut storage));
+        assert_eq!(Some(0x42), value(&mut storage));
+
+        // an interrupted store leaves the legacy payload in place.
+        let mut flash = storage.release();
+        flash.interrupt_after(100);
+        let mut storage = FlashStorage::new(0, SIZE, flash).with_legacy_address(0);
+        assert!(block_on(storage.store(&payload(7))).is_err());
+        let mut storage = reopen(storage).with_legacy_address(0);
+        assert_eq!(Some(0x42), value(&mut storage));
+
+        // the legacy page survives the first store.
+        block_on(storage.store(&payload(7))).unwrap();
+        assert_eq!(Some(7), value(&mut storage));
+        let flash = storage.release();
+        assert_eq!(&[0x42; 512][..], &flash.memory()[..512]);
+        let mut storage = FlashStorage::new(0, SIZE, flash).with_legacy_address(0);
+        assert_eq!(Some(7), value(&mut storage));
+        assert_eq!(Some(7), value(&mut storage));
+    }
+
+    #[test]
+    fn retrieves_legacy_payload_outside_journal() {
+        let mut flash = RamFlash::<{ SIZE + 4096 }>::new();
+        block_on(flash.write(SIZE as u32, &[0x42; 512])).unwrap();
+        let mut storage = FlashStorage::new(0, SIZE, flash).with_legacy_address(SIZE);
+        assert_eq!(
+            Some(0x42),
+            block_on(storage.retrieve()).unwrap().map(|p| p.payload[0])
+        );
+        block_on(storage.store(&payload(7))).unwrap();
+        let mut storage = FlashStorage::new(0, SIZE, storage.release()).with_legacy_address(SIZE);
+        assert_eq!(
+            Some(7),
+            block_on(storage.retrieve()).unwrap().map(|p| p.payload[0])
+        );
+    }
+
+    #[test]
+    fn ignores_blank_legacy_page() {
+        let mut storage =
+            FlashStorage::new(0, SIZE, RamFlash::<SIZE>::new()).with_legacy_address(4096);
+        assert_eq!(None, value(&mut storage));
+    }
+
+    #[test]
+    fn versioned_round_trip() {
+        let mut storage = FlashStorage::new(0, SIZE, RamFlash::<SIZE>::new());
+        assert_eq!(
+            None,
+            block_on(load_versioned::<_, (u16, u32)>(&mut storage, 1)).unwrap()
+        );
+        block_on(store_versioned(
+            &mut storage,
+            1,
+            &(0x1234u16, 0xDEAD_BEEFu32),
+        ))
+        .unwrap();
+        let mut storage = reopen(storage);
+        assert_eq!(
+            Some((0x1234, 0xDEAD_BEEF)),
+            block_on(load_versioned::<_, (u16, u32)>(&mut storage, 1)).unwrap()
+        );
+    }
+
+    #[test]
+    fn versioned_reports_undecodable_payloads() {
+        let mut storage = FlashStorage::new(0, SIZE, RamFlash::<SIZE>::new());
+        block_on(storage.store(&payload(0x12))).unwrap();
+        assert!(block_on(load_versioned::<_, u16>(&mut storage, 1)).is_err());
+
+        block_on(store_versioned(&mut storage, 2, &0x1234u16)).unwrap();
+        assert!(block_on(load_versioned::<_, u16>(&mut storage, 1)).is_err());
+
+        // a bool only ever decodes from 0 or 1.
+        block_on(store_versioned(&mut storage, 1, &0x02u8)).unwrap();
+        assert!(block_on(load_versioned::<_, bool>(&mut storage, 1)).is_err());
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(0xCBF4_3926, crc32(&[b"1234", b"56789"]));
     }
 }
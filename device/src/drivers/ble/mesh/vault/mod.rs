@@ -0,0 +1,516 @@
+use core::cell::Ref;
+use core::convert::TryInto;
+use core::future::{ready, Future, Ready};
+
+use crate::drivers::ble::mesh::crypto;
+use aes::Aes128;
+use cmac::crypto_mac::Output;
+use cmac::Cmac;
+use p256::elliptic_curve::ecdh::diffie_hellman;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+
+use crate::drivers::ble::mesh::address::UnicastAddress;
+use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+use crate::drivers::ble::mesh::config::configuration_manager::ConfigurationManager;
+use crate::drivers::ble::mesh::config::network::{Network, NetworkDetails};
+use crate::drivers::ble::mesh::config::Configuration;
+use crate::drivers::ble::mesh::crypto::nonce::{ApplicationNonce, DeviceNonce};
+use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::{AppKeyIndex, NetKeyIndex};
+use crate::drivers::ble::mesh::provisioning::{self, ProvisioningData};
+use crate::drivers::ble::mesh::storage::Storage;
+use heapless::Vec;
+
+// exercises an async vault keeping its keys out of the configuration.
+#[cfg(test)]
+mod secure_element;
+
+pub trait Vault {
+    fn uuid(&self) -> Uuid;
+
+    type SetPeerPublicKeyFuture<'m>: Future<Output = Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn set_peer_public_key<'m>(&'m mut self, pk: PublicKey) -> Self::SetPeerPublicKeyFuture<'m>;
+
+    type PublicKeyFuture<'m>: Future<Output = Result<PublicKey, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn public_key<'m>(&'m self) -> Self::PublicKeyFuture<'m>;
+
+    /// Whether the key pair is fixed, its public key being distributed
+    /// out-of-band rather than exchanged while provisioning.
+    fn oob_public_key(&self) -> bool {
+        false
+    }
+
+    fn aes_cmac(&self, key: &[u8], input: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError> {
+        crypto::aes_cmac(key, input).map_err(|_| DeviceError::InvalidKeyLength)
+    }
+
+    fn s1(input: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError> {
+        crypto::s1(input).map_err(|_| DeviceError::InvalidKeyLength)
+    }
+
+    fn k1(n: &[u8], salt: &[u8], p: &[u8]) -> Result<Output<Cmac<Aes128>>, DeviceError> {
+        crypto::k1(n, salt, p).map_err(|_| DeviceError::InvalidKeyLength)
+    }
+
+    type KeyFuture<'m>: Future<Output = Result<Output<Cmac<Aes128>>, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    /// Derive `k1` from the shared secret agreed with the provisioner.
+    fn n_k1<'m>(&'m self, salt: &'m [u8], p: &'m [u8]) -> Self::KeyFuture<'m>;
+
+    fn prsk<'m>(&'m self, salt: &'m [u8]) -> Self::KeyFuture<'m> {
+        self.n_k1(salt, b"prsk")
+    }
+
+    fn prsn<'m>(&'m self, salt: &'m [u8]) -> Self::KeyFuture<'m> {
+        self.n_k1(salt, b"prsn")
+    }
+
+    fn prck<'m>(&'m self, salt: &'m [u8]) -> Self::KeyFuture<'m> {
+        self.n_k1(salt, b"prck")
+    }
+
+    fn prdk<'m>(&'m self, salt: &'m [u8]) -> Self::KeyFuture<'m> {
+        self.n_k1(salt, b"prdk")
+    }
+
+    fn k2(n: &[u8], p: &[u8]) -> Result<(u8, [u8; 16], [u8; 16]), DeviceError> {
+        crypto::k2(n, p).map_err(|_| DeviceError::CryptoError("k2"))
+    }
+
+    fn aes_ccm_decrypt(
+        key: &[u8],
+        nonce: &[u8],
+        data: &mut [u8],
+        mic: &[u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<(), DeviceError> {
+        crypto::aes_ccm_decrypt_detached(key, nonce, data, mic, additional_data)
+            .map_err(|_| DeviceError::CryptoError("aes_ccm_decrypt"))
+    }
+
+    type SetProvisioningDataFuture<'m>: Future<Output = Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn set_provisioning_data<'m>(
+        &'m mut self,
+        provisioning_salt: &'m [u8],
+        data: &'m ProvisioningData,
+    ) -> Self::SetProvisioningDataFuture<'m>;
+
+    fn iv_index(&self) -> Option<u32>;
+
+    type DecryptDeviceKeyFuture<'m>: Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn decrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m [u8],
+    ) -> Self::DecryptDeviceKeyFuture<'m>;
+
+    type EncryptDeviceKeyFuture<'m>: Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn encrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m mut [u8],
+    ) -> Self::EncryptDeviceKeyFuture<'m>;
+
+    /// Encrypt with the application key at `app_key_index`, returning the
+    /// AID of the key actually used, as key refresh may substitute a new key.
+    fn encrypt_application_key(
+        &self,
+        net_key_index: &NetKeyIndex,
+        app_key_index: &AppKeyIndex,
+        nonce: ApplicationNonce,
+        bytes: &mut [u8],
+        mic: &mut [u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<ApplicationKeyIdentifier, DeviceError>;
+
+    /// Decrypt with whichever application key with the given AID
+    /// authenticates, returning its index.
+    fn decrypt_application_key(
+        &self,
+        net_key_index: &NetKeyIndex,
+        aid: &ApplicationKeyIdentifier,
+        nonce: ApplicationNonce,
+        bytes: &mut [u8],
+        mic: &[u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<AppKeyIndex, DeviceError>;
+
+    fn primary_unicast_address(&self) -> Option<UnicastAddress>;
+}
+
+/// Provides the vault of a node, over its configuration.
+pub trait VaultFactory<S: Storage> {
+    type Vault<'c>: Vault
+    where
+        Self: 'c,
+        S: 'c;
+
+    fn vault<'c>(&'c self, configuration_manager: &'c ConfigurationManager<S>) -> Self::Vault<'c>;
+
+    /// Whether the vaults take the key pair of the device from the
+    /// configuration, which then generates one.
+    fn stores_key_pair(&self) -> bool {
+        true
+    }
+}
+
+/// Provides a [`StorageVault`], keeping all keys in the configuration.
+#[derive(Default)]
+pub struct StorageVaultFactory;
+
+impl<S: Storage> VaultFactory<S> for StorageVaultFactory {
+    type Vault<'c> = StorageVault<'c, S>
+    where
+        Self: 'c,
+        S: 'c;
+
+    fn vault<'c>(&'c self, configuration_manager: &'c ConfigurationManager<S>) -> Self::Vault<'c> {
+        StorageVault::new(configuration_manager)
+    }
+}
+
+/// Provides [`StorageVault`]s using a fixed key pair of the device, rather
+/// than one generated and kept in the configuration. The public key is
+/// distributed out-of-band, such as printed as a QR code, so the OOB public
+/// key capability is advertised, and the public key of the device is not
+/// sent when selected by the provisioner.
+///
+/// As the provisioner takes the public key from a trusted source, a
+/// man-in-the-middle can no longer substitute its own key during the
+/// exchange, and only the device holding the private key can complete
+/// provisioning. This authenticates the device even with no out-of-band
+/// authentication, while output, input or static OOB authentication still
+/// additionally proves the user is in possession of the device.
+pub struct FixedKeyVaultFactory {
+    secret_key: SecretKey,
+}
+
+impl FixedKeyVaultFactory {
+    /// Use the big-endian P-256 `private_key` as the key pair of the device.
+    pub fn new(private_key: &[u8; 32]) -> Result<Self, DeviceError> {
+        Ok(Self {
+            secret_key: SecretKey::from_be_bytes(private_key)
+                .map_err(|_| DeviceError::KeyInitialization)?,
+        })
+    }
+
+    /// The public key of the device, to distribute out-of-band.
+    pub fn public_key(&self) -> Result<provisioning::PublicKey, DeviceError> {
+        let xy = self.secret_key.public_key().to_encoded_point(false);
+        let mut public_key = provisioning::PublicKey {
+            x: [0; 32],
+            y: [0; 32],
+        };
+        public_key
+            .x
+            .copy_from_slice(xy.x().ok_or(DeviceError::KeyInitialization)?);
+        public_key
+            .y
+            .copy_from_slice(xy.y().ok_or(DeviceError::KeyInitialization)?);
+        Ok(public_key)
+    }
+}
+
+impl<S: Storage> VaultFactory<S> for FixedKeyVaultFactory {
+    type Vault<'c> = StorageVault<'c, S>
+    where
+        Self: 'c,
+        S: 'c;
+
+    fn vault<'c>(&'c self, configuration_manager: &'c ConfigurationManager<S>) -> Self::Vault<'c> {
+        StorageVault {
+            configuration_manager,
+            secret_key: Some(&self.secret_key),
+        }
+    }
+
+    fn stores_key_pair(&self) -> bool {
+        false
+    }
+}
+
+pub struct StorageVault<'c, S: Storage> {
+    configuration_manager: &'c ConfigurationManager<S>,
+    /// The fixed key pair of the device, unless kept in the configuration.
+    secret_key: Option<&'c SecretKey>,
+}
+
+impl<'c, S: Storage + 'c> StorageVault<'c, S> {
+    pub(crate) fn new(configuration_manager: &'c ConfigurationManager<S>) -> Self {
+        Self {
+            configuration_manager,
+            secret_key: None,
+        }
+    }
+
+    fn config(&self) -> Ref<'_, Configuration> {
+        self.configuration_manager.configuration()
+    }
+
+    /// Store the network assigned by the provisioner, along with the device
+    /// key unless kept elsewhere.
+    pub(crate) async fn store_provisioning_data(
+        &self,
+        data: &ProvisioningData,
+        device_key: Option<[u8; 16]>,
+    ) -> Result<(), DeviceError> {
+        let (nid, encryption_key, privacy_key) =
+            crypto::k2(&data.network_key, &[0x00]).map_err(|_| DeviceError::KeyInitialization)?;
+
+        let mut primary_network_details = NetworkDetails::new(
+            data.network_key.into(),
+            data.key_index,
+            nid,
+            encryption_key,
+            privacy_key,
+        );
+        primary_network_details.set_key_refresh_flag(data.key_refresh_flag);
+
+        self.configuration_manager
+            .update_configuration(|config| {
+                if let Some(device_key) = device_key {
+                    config.device_keys_mut().set_device_key(device_key);
+                }
+
+                config.network_mut().replace(Network::new(
+                    primary_network_details,
+                    data.iv_update_flag,
+                    data.iv_index,
+                    data.unicast_address,
+                ));
+
+                Ok(())
+            })
+            .await?;
+
+        info!("Assigned unicast address {:04x}", data.unicast_address);
+        Ok(())
+    }
+}
+
+impl<'c, S: Storage + 'c> Vault for StorageVault<'c, S> {
+    fn uuid(&self) -> Uuid {
+        self.config().uuid().unwrap()
+    }
+
+    type SetPeerPublicKeyFuture<'m> = impl Future<Output = Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn set_peer_public_key<'m>(&'m mut self, pk: PublicKey) -> Self::SetPeerPublicKeyFuture<'m> {
+        async move {
+            let fixed = self.secret_key;
+            self.configuration_manager
+                .update_configuration(|config| {
+                    let secret_key = match fixed {
+                        Some(secret_key) => secret_key.clone(),
+                        None => config
+                            .device_keys()
+                            .private_key()?
+                            .ok_or(DeviceError::KeyInitialization)?,
+                    };
+                    let shared_secret =
+                        diffie_hellman(secret_key.to_nonzero_scalar(), pk.as_affine());
+                    config
+                        .device_keys_mut()
+                        .set_shared_secret(Some(shared_secret))?;
+                    Ok(())
+                })
+                .await
+        }
+    }
+
+    type PublicKeyFuture<'m> = Ready<Result<PublicKey, DeviceError>>
+    where
+        Self: 'm;
+
+    fn public_key<'m>(&'m self) -> Self::PublicKeyFuture<'m> {
+        ready(match self.secret_key {
+            Some(secret_key) => Ok(secret_key.public_key()),
+            None => self
+                .configuration_manager
+                .configuration()
+                .device_keys()
+                .public_key(),
+        })
+    }
+
+    fn oob_public_key(&self) -> bool {
+        self.secret_key.is_some()
+    }
+
+    type KeyFuture<'m> = Ready<Result<Output<Cmac<Aes128>>, DeviceError>>
+    where
+        Self: 'm;
+
+    fn n_k1<'m>(&'m self, salt: &'m [u8], p: &'m [u8]) -> Self::KeyFuture<'m> {
+        let shared_secret = match self.config().device_keys().shared_secret() {
+            Ok(Some(shared_secret)) => shared_secret,
+            Ok(None) => return ready(Err(DeviceError::CryptoError("n_k1"))),
+            Err(e) => return ready(Err(e)),
+        };
+        ready(
+            crypto::k1(shared_secret.as_bytes(), salt, p)
+                .map_err(|_| DeviceError::CryptoError("n_k1")),
+        )
+    }
+
+    type SetProvisioningDataFuture<'m> = impl Future<Output = Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn set_provisioning_data<'m>(
+        &'m mut self,
+        provisioning_salt: &'m [u8],
+        data: &'m ProvisioningData,
+    ) -> Self::SetProvisioningDataFuture<'m> {
+        async move {
+            let device_key = self.prdk(&provisioning_salt).await?;
+            let device_key: [u8; 16] = device_key
+                .into_bytes()
+                .try_into()
+                .map_err(|_| DeviceError::KeyInitialization)?;
+            self.store_provisioning_data(data, Some(device_key)).await
+        }
+    }
+
+    fn iv_index(&self) -> Option<u32> {
+        if let Some(network) = self.configuration_manager.configuration().network() {
+            Some(network.iv_index())
+        } else {
+            None
+        }
+    }
+
+    type DecryptDeviceKeyFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn decrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m [u8],
+    ) -> Self::DecryptDeviceKeyFuture<'m> {
+        ready(match self.config().device_keys().device_key() {
+            Some(device_key) => {
+                crypto::aes_ccm_decrypt_detached(device_key.as_ref(), &*nonce, bytes, mic, None)
+                    .map_err(|_| DeviceError::CryptoError("decrypt device key"))
+            }
+            None => Err(DeviceError::CryptoError("decrypt device key")),
+        })
+    }
+
+    type EncryptDeviceKeyFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn encrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m mut [u8],
+    ) -> Self::EncryptDeviceKeyFuture<'m> {
+        ready(match self.config().device_keys().device_key() {
+            Some(device_key) => {
+                crypto::aes_ccm_encrypt_detached(device_key.as_ref(), &*nonce, bytes, mic, None)
+                    .map_err(|_| DeviceError::CryptoError("encrypt device key"))
+            }
+            None => Err(DeviceError::CryptoError("encrypt device key")),
+        })
+    }
+
+    fn encrypt_application_key(
+        &self,
+        net_key_index: &NetKeyIndex,
+        app_key_index: &AppKeyIndex,
+        nonce: ApplicationNonce,
+        bytes: &mut [u8],
+        mic: &mut [u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<ApplicationKeyIdentifier, DeviceError> {
+        if let Some(network) = self.config().network() {
+            if let Ok(network) = network.find_by_net_key_index(net_key_index) {
+                if let Some((aid, app_key)) = network.transmit_app_key(app_key_index) {
+                    crypto::aes_ccm_encrypt_detached(
+                        app_key.as_ref(),
+                        &*nonce,
+                        bytes,
+                        mic,
+                        additional_data,
+                    )
+                    .map_err(|_| DeviceError::CryptoError("encrypt app key"))?;
+                    return Ok(aid);
+                }
+            }
+        }
+
+        Err(DeviceError::CryptoError("encrypt app key"))
+    }
+
+    fn decrypt_application_key(
+        &self,
+        net_key_index: &NetKeyIndex,
+        aid: &ApplicationKeyIdentifier,
+        nonce: ApplicationNonce,
+        bytes: &mut [u8],
+        mic: &[u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<AppKeyIndex, DeviceError> {
+        if let Some(network) = self.config().network() {
+            if let Ok(network) = network.find_by_net_key_index(net_key_index) {
+                // several keys of the subnet may share the AID, so each is
+                // tried against a copy until one authenticates.
+                let mut temp: Vec<u8, 380> = Vec::new();
+                for (app_key_index, app_key) in network.find_app_keys_by_aid(aid) {
+                    temp.clear();
+                    temp.extend_from_slice(bytes)
+                        .map_err(|_| DeviceError::InsufficientBuffer)?;
+                    if crypto::aes_ccm_decrypt_detached(
+                        app_key.as_ref(),
+                        &*nonce,
+                        &mut temp,
+                        mic,
+                        additional_data,
+                    )
+                    .is_ok()
+                    {
+                        bytes.copy_from_slice(&temp);
+                        return Ok(app_key_index);
+                    }
+                }
+            }
+        }
+
+        Err(DeviceError::CryptoError("decrypt app key"))
+    }
+
+    fn primary_unicast_address(&self) -> Option<UnicastAddress> {
+        if let Some(network) = self.config().network() {
+            Some(*network.unicast_address())
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Framing of the commands exchanged with the emulated secure element.
+//!
+//! A command is sent as a packet:
+//!
+//! ```text
+//! [count, opcode, param1, param2 (u16, little-endian), data..., crc (u16, little-endian)]
+//! ```
+//!
+//! and answered with a response packet, holding either the output of the
+//! command or a single status byte:
+//!
+//! ```text
+//! [count, data..., crc (u16, little-endian)]
+//! ```
+//!
+//! The count covers the whole packet, and the CRC all of it before the CRC.
+
+use crate::drivers::ble::mesh::driver::DeviceError;
+use heapless::Vec;
+
+pub const OPCODE_GEN_KEY: u8 = 0x40;
+pub const OPCODE_ECDH: u8 = 0x43;
+pub const OPCODE_AES: u8 = 0x51;
+pub const OPCODE_KDF: u8 = 0x56;
+
+/// Generate a new private key in the slot, returning its public key.
+pub const GEN_KEY_MODE_CREATE: u8 = 0x04;
+/// Return the public key of the private key in the slot.
+pub const GEN_KEY_MODE_PUBLIC: u8 = 0x00;
+
+/// Write the shared secret to the slot following the private key.
+pub const ECDH_MODE_SLOT: u8 = 0x08;
+
+/// Encrypt a single block with the key in the slot.
+pub const AES_MODE_ENCRYPT: u8 = 0x00;
+
+/// Derive from the key in the source slot.
+pub const KDF_SOURCE_SLOT: u8 = 0x02;
+/// Write the derived key to the target slot.
+pub const KDF_TARGET_SLOT: u8 = 0x08;
+/// Return the derived key.
+pub const KDF_TARGET_OUTPUT: u8 = 0x0C;
+/// The mesh `k1` derivation, with a 16 byte salt followed by `P` as data.
+pub const KDF_ALGORITHM_K1: u8 = 0x60;
+
+pub const STATUS_SUCCESS: u8 = 0x00;
+pub const STATUS_PARSE_ERROR: u8 = 0x03;
+pub const STATUS_EXECUTION_ERROR: u8 = 0x0F;
+pub const STATUS_CRC_ERROR: u8 = 0xFF;
+
+pub(crate) const MAX_DATA: usize = 64;
+pub(crate) const MAX_PACKET: usize = MAX_DATA + 7;
+
+pub(crate) type Packet = Vec<u8, MAX_PACKET>;
+
+pub(crate) struct Command<'d> {
+    pub opcode: u8,
+    pub param1: u8,
+    pub param2: u16,
+    pub data: &'d [u8],
+}
+
+impl<'d> Command<'d> {
+    pub(crate) fn emit(&self, packet: &mut Packet) -> Result<(), DeviceError> {
+        packet.clear();
+        packet
+            .push((self.data.len() + 7) as u8)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        packet
+            .push(self.opcode)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        packet
+            .push(self.param1)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        packet
+            .extend_from_slice(&self.param2.to_le_bytes())
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        packet
+            .extend_from_slice(self.data)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        let crc = crc16(packet);
+        packet
+            .extend_from_slice(&crc)
+            .map_err(|_| DeviceError::InsufficientBuffer)
+    }
+
+    pub(crate) fn parse(packet: &'d [u8]) -> Result<Self, u8> {
+        let body = checked_body(packet)?;
+        if body.len() < 4 {
+            return Err(STATUS_PARSE_ERROR);
+        }
+        Ok(Self {
+            opcode: body[0],
+            param1: body[1],
+            param2: u16::from_le_bytes([body[2], body[3]]),
+            data: &body[4..],
+        })
+    }
+}
+
+pub(crate) fn emit_response(data: &[u8], packet: &mut Packet) {
+    packet.clear();
+    packet.push((data.len() + 3) as u8).ok();
+    packet.extend_from_slice(data).ok();
+    let crc = crc16(packet);
+    packet.extend_from_slice(&crc).ok();
+}
+
+/// The output of a command, once its response is checked. Commands without
+/// output answer with a successful status.
+pub(crate) fn parse_response(packet: &[u8], output_len: usize) -> Result<&[u8], DeviceError> {
+    let body =
+        checked_body(packet).map_err(|_| DeviceError::CryptoError("secure element response"))?;
+    match (body.len(), output_len) {
+        (len, _) if len == output_len && len != 1 => Ok(body),
+        (1, 0) if body[0] == STATUS_SUCCESS => Ok(&body[..0]),
+        (1, _) => {
+            warn!("secure element status {:02x}", body[0]);
+            Err(DeviceError::CryptoError("secure element status"))
+        }
+        _ => Err(DeviceError::CryptoError("secure element response")),
+    }
+}
+
+/// The packet between its count and CRC, once both are checked.
+fn checked_body(packet: &[u8]) -> Result<&[u8], u8> {
+    let count = *packet.first().ok_or(STATUS_PARSE_ERROR)? as usize;
+    if count < 3 || count > packet.len() {
+        return Err(STATUS_PARSE_ERROR);
+    }
+    let (content, crc) = packet[..count].split_at(count - 2);
+    if crc16(content) != crc {
+        return Err(STATUS_CRC_ERROR);
+    }
+    Ok(&content[1..])
+}
+
+/// CRC-16 with polynomial 0x8005, processing bits least significant first.
+pub(crate) fn crc16(data: &[u8]) -> [u8; 2] {
+    let mut crc: u16 = 0;
+    for byte in data {
+        for shift in 0..8 {
+            let data_bit = (byte >> shift) & 1;
+            let crc_bit = (crc >> 15) as u8;
+            crc <<= 1;
+            if data_bit != crc_bit {
+                crc ^= 0x8005;
+            }
+        }
+    }
+    crc.to_le_bytes()
+}
@@ -0,0 +1,219 @@
+//! Software implementation of the command set of the secure element, backing
+//! a [`SecureElementVault`](super::SecureElementVault) in tests.
+//!
+//! No command reads a private key or a shared secret back out of its slot,
+//! though the KDF returns the derived key when not targeting a slot.
+
+use core::future::{ready, Ready};
+
+use p256::elliptic_curve::ecdh::diffie_hellman;
+use p256::elliptic_curve::generic_array::GenericArray;
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::{EncodedPoint, PublicKey, SecretKey};
+use rand_core::{CryptoRng, RngCore};
+
+use crate::drivers::ble::mesh::crypto;
+use crate::drivers::ble::mesh::driver::DeviceError;
+
+use super::command::*;
+use super::Transport;
+
+const SLOTS: usize = 16;
+
+enum Slot {
+    Empty,
+    PrivateKey(SecretKey),
+    SharedSecret([u8; 32]),
+    Key([u8; 16]),
+}
+
+pub struct Emulator<R: RngCore + CryptoRng> {
+    rng: R,
+    slots: [Slot; SLOTS],
+    response: Option<Packet>,
+}
+
+impl<R: RngCore + CryptoRng> Emulator<R> {
+    pub fn new(rng: R) -> Self {
+        const EMPTY: Slot = Slot::Empty;
+        Self {
+            rng,
+            slots: [EMPTY; SLOTS],
+            response: None,
+        }
+    }
+
+    /// Personalize `slot` with a fixed private key.
+    pub fn with_private_key(
+        mut self,
+        slot: u8,
+        private_key: &[u8; 32],
+    ) -> Result<Self, DeviceError> {
+        let private_key =
+            SecretKey::from_be_bytes(private_key).map_err(|_| DeviceError::KeyInitialization)?;
+        *self.slot_mut(slot)? = Slot::PrivateKey(private_key);
+        Ok(self)
+    }
+
+    /// Personalize `slot` with a fixed AES key.
+    pub fn with_key(mut self, slot: u8, key: &[u8; 16]) -> Result<Self, DeviceError> {
+        *self.slot_mut(slot)? = Slot::Key(*key);
+        Ok(self)
+    }
+
+    fn slot_mut(&mut self, slot: u8) -> Result<&mut Slot, DeviceError> {
+        self.slots
+            .get_mut(slot as usize)
+            .ok_or(DeviceError::InvalidState)
+    }
+
+    fn index(slot: u16) -> Result<usize, u8> {
+        if (slot as usize) < SLOTS {
+            Ok(slot as usize)
+        } else {
+            Err(STATUS_EXECUTION_ERROR)
+        }
+    }
+
+    fn execute(&mut self, command: &Command, response: &mut Packet) -> Result<(), u8> {
+        match command.opcode {
+            OPCODE_GEN_KEY => self.gen_key(command, response),
+            OPCODE_ECDH => self.ecdh(command),
+            OPCODE_KDF => self.kdf(command, response),
+            OPCODE_AES => self.aes(command, response),
+            _ => Err(STATUS_PARSE_ERROR),
+        }
+    }
+
+    fn gen_key(&mut self, command: &Command, response: &mut Packet) -> Result<(), u8> {
+        let slot = &mut self.slots[Self::index(command.param2)?];
+        if command.param1 == GEN_KEY_MODE_CREATE {
+            *slot = Slot::PrivateKey(SecretKey::random(&mut self.rng));
+        } else if command.param1 != GEN_KEY_MODE_PUBLIC {
+            return Err(STATUS_PARSE_ERROR);
+        }
+        match slot {
+            Slot::PrivateKey(private_key) => {
+                let point = private_key.public_key().to_encoded_point(false);
+                response
+                    .extend_from_slice(point.x().ok_or(STATUS_EXECUTION_ERROR)?)
+                    .map_err(|_| STATUS_EXECUTION_ERROR)?;
+                response
+                    .extend_from_slice(point.y().ok_or(STATUS_EXECUTION_ERROR)?)
+                    .map_err(|_| STATUS_EXECUTION_ERROR)
+            }
+            _ => Err(STATUS_EXECUTION_ERROR),
+        }
+    }
+
+    fn ecdh(&mut self, command: &Command) -> Result<(), u8> {
+        if command.param1 != ECDH_MODE_SLOT || command.data.len() != 64 {
+            return Err(STATUS_PARSE_ERROR);
+        }
+        let private_key = match &self.slots[Self::index(command.param2)?] {
+            Slot::PrivateKey(private_key) => private_key,
+            _ => return Err(STATUS_EXECUTION_ERROR),
+        };
+        let point = EncodedPoint::from_affine_coordinates(
+            GenericArray::from_slice(&command.data[..32]),
+            GenericArray::from_slice(&command.data[32..]),
+            false,
+        );
+        let peer: Option<PublicKey> = PublicKey::from_encoded_point(&point).into();
+        let peer = peer.ok_or(STATUS_EXECUTION_ERROR)?;
+        let shared_secret = diffie_hellman(private_key.to_nonzero_scalar(), peer.as_affine());
+
+        let mut secret = [0; 32];
+        secret.copy_from_slice(shared_secret.as_bytes());
+        self.slots[Self::index(command.param2 | 1)?] = Slot::SharedSecret(secret);
+        Ok(())
+    }
+
+    fn kdf(&mut self, command: &Command, response: &mut Packet) -> Result<(), u8> {
+        let [source, target] = command.param2.to_le_bytes();
+        if command.param1 & 0xE0 != KDF_ALGORITHM_K1
+            || command.param1 & 0x03 != KDF_SOURCE_SLOT
+            || command.data.len() < 16
+        {
+            return Err(STATUS_PARSE_ERROR);
+        }
+        let n = match &self.slots[Self::index(source as u16)?] {
+            Slot::SharedSecret(secret) => secret,
+            _ => return Err(STATUS_EXECUTION_ERROR),
+        };
+        let (salt, p) = command.data.split_at(16);
+        let key = crypto::k1(n, salt, p).map_err(|_| STATUS_EXECUTION_ERROR)?;
+
+        match command.param1 & 0x1C {
+            KDF_TARGET_OUTPUT => response
+                .extend_from_slice(&key.into_bytes())
+                .map_err(|_| STATUS_EXECUTION_ERROR),
+            KDF_TARGET_SLOT => {
+                let mut derived = [0; 16];
+                derived.copy_from_slice(&key.into_bytes());
+                self.slots[Self::index(target as u16)?] = Slot::Key(derived);
+                Ok(())
+            }
+            _ => Err(STATUS_PARSE_ERROR),
+        }
+    }
+
+    fn aes(&mut self, command: &Command, response: &mut Packet) -> Result<(), u8> {
+        if command.param1 != AES_MODE_ENCRYPT || command.data.len() != 16 {
+            return Err(STATUS_PARSE_ERROR);
+        }
+        let key = match &self.slots[Self::index(command.param2)?] {
+            Slot::Key(key) => key,
+            _ => return Err(STATUS_EXECUTION_ERROR),
+        };
+        let mut block = [0; 16];
+        block.copy_from_slice(command.data);
+        let block = crypto::e(key, block).map_err(|_| STATUS_EXECUTION_ERROR)?;
+        response
+            .extend_from_slice(&block)
+            .map_err(|_| STATUS_EXECUTION_ERROR)
+    }
+}
+
+impl<R: RngCore + CryptoRng> Transport for Emulator<R> {
+    type SendFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn send<'m>(&'m mut self, packet: &'m [u8]) -> Self::SendFuture<'m> {
+        let mut output = Packet::new();
+        let status = match Command::parse(packet) {
+            Ok(command) => match self.execute(&command, &mut output) {
+                Ok(()) if output.is_empty() => Some(STATUS_SUCCESS),
+                Ok(()) => None,
+                Err(status) => Some(status),
+            },
+            Err(status) => Some(status),
+        };
+        if let Some(status) = status {
+            output.clear();
+            output.push(status).ok();
+        }
+
+        let mut response = Packet::new();
+        emit_response(&output, &mut response);
+        self.response.replace(response);
+        ready(Ok(()))
+    }
+
+    type ReceiveFuture<'m> = Ready<Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn receive<'m>(&'m mut self, response: &'m mut [u8]) -> Self::ReceiveFuture<'m> {
+        let packet = match self.response.take() {
+            Some(packet) if packet.len() <= response.len() => packet,
+            Some(_) => return ready(Err(DeviceError::InsufficientBuffer)),
+            None => return ready(Err(DeviceError::InvalidState)),
+        };
+        // the bus reads as idle past the end of the response.
+        response.fill(0xFF);
+        response[..packet.len()].copy_from_slice(&packet);
+        ready(Ok(()))
+    }
+}
@@ -0,0 +1,580 @@
+//! A [`Vault`] keeping the shared secret and the device key of a node in the
+//! slots of an emulated secure element, exercising a vault whose operations
+//! complete asynchronously and whose keys stay out of the configuration.
+//!
+//! The [`Emulator`](emulator::Emulator) implements a command set in the style
+//! of a secure element, without modelling any particular chip: ECDH writes the
+//! shared secret to a slot, a KDF derives the mesh `k1` from it, and AES
+//! encrypts single blocks with the key of a slot. AES-CMAC and AES-CCM are
+//! computed here over those block encryptions.
+//!
+//! Keys derived with [`SecureElement::k1`] are returned to the caller, as is
+//! every key derived through [`Vault::n_k1`], which only spares the device key.
+//! Network and application keys remain in the configuration of the node.
+
+use core::future::Future;
+
+use aes::Aes128;
+use cmac::crypto_mac::Output;
+use cmac::Cmac;
+use futures_intrusive::sync::LocalMutex;
+use p256::elliptic_curve::generic_array::GenericArray;
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::{EncodedPoint, PublicKey};
+
+use crate::drivers::ble::mesh::address::UnicastAddress;
+use crate::drivers::ble::mesh::app::ApplicationKeyIdentifier;
+use crate::drivers::ble::mesh::config::configuration_manager::ConfigurationManager;
+use crate::drivers::ble::mesh::crypto::nonce::{ApplicationNonce, DeviceNonce};
+use crate::drivers::ble::mesh::device::Uuid;
+use crate::drivers::ble::mesh::driver::DeviceError;
+use crate::drivers::ble::mesh::model::foundation::configuration::{AppKeyIndex, NetKeyIndex};
+use crate::drivers::ble::mesh::provisioning::ProvisioningData;
+use crate::drivers::ble::mesh::storage::Storage;
+use crate::drivers::ble::mesh::vault::{StorageVault, Vault, VaultFactory};
+
+use command::*;
+use modes::{Block, BlockCipher};
+
+pub(crate) mod command;
+pub mod emulator;
+pub(crate) mod modes;
+
+/// Slot holding the private key of the node.
+pub const PRIVATE_KEY_SLOT: u8 = 0;
+/// Slot receiving the shared secret agreed with the provisioner.
+pub const SHARED_SECRET_SLOT: u8 = PRIVATE_KEY_SLOT | 1;
+/// Slot receiving the device key, once provisioned.
+pub const DEVICE_KEY_SLOT: u8 = 2;
+
+/// Exchanges packets with the secure element.
+pub trait Transport {
+    type SendFuture<'m>: Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn send<'m>(&'m mut self, packet: &'m [u8]) -> Self::SendFuture<'m>;
+
+    type ReceiveFuture<'m>: Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    /// Fill `response` with the response to the last packet sent.
+    fn receive<'m>(&'m mut self, response: &'m mut [u8]) -> Self::ReceiveFuture<'m>;
+}
+
+/// A secure element holding the keys of a node in its slots.
+///
+/// Unless personalized with a key pair, of which the public key is then
+/// distributed out-of-band, a key pair is to be generated with
+/// [`SecureElement::generate_key_pair`] before provisioning. Either way, the
+/// configuration of the node stores no key pair of its own.
+pub struct SecureElement<T: Transport> {
+    transport: LocalMutex<T>,
+    oob_public_key: bool,
+}
+
+impl<T: Transport> SecureElement<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport: LocalMutex::new(transport, true),
+            oob_public_key: false,
+        }
+    }
+
+    /// The key pair of the secure element is fixed, and its public key,
+    /// as read with [`SecureElement::public_key`], distributed out-of-band.
+    ///
+    /// The counterpart of a [`FixedKeyVaultFactory`](super::FixedKeyVaultFactory)
+    /// for nodes keeping their keys in software.
+    pub fn with_oob_public_key(self) -> Self {
+        Self {
+            oob_public_key: true,
+            ..self
+        }
+    }
+
+    pub async fn generate_key_pair(&self) -> Result<PublicKey, DeviceError> {
+        self.read_public_key(GEN_KEY_MODE_CREATE).await
+    }
+
+    pub async fn public_key(&self) -> Result<PublicKey, DeviceError> {
+        self.read_public_key(GEN_KEY_MODE_PUBLIC).await
+    }
+
+    /// Agree on the shared secret with `peer`, kept within the secure element.
+    pub async fn ecdh(&self, peer: &PublicKey) -> Result<(), DeviceError> {
+        let point = peer.to_encoded_point(false);
+        let mut data = [0; 64];
+        data[..32].copy_from_slice(point.x().ok_or(DeviceError::KeyInitialization)?);
+        data[32..].copy_from_slice(point.y().ok_or(DeviceError::KeyInitialization)?);
+        self.execute(
+            &Command {
+                opcode: OPCODE_ECDH,
+                param1: ECDH_MODE_SLOT,
+                param2: PRIVATE_KEY_SLOT as u16,
+                data: &data,
+            },
+            &mut [],
+        )
+        .await
+    }
+
+    /// Derive `k1(shared secret, salt, p)`.
+    pub async fn k1(&self, salt: &[u8], p: &[u8]) -> Result<Block, DeviceError> {
+        let mut key = [0; 16];
+        self.kdf(KDF_TARGET_OUTPUT, 0, salt, p, &mut key).await?;
+        Ok(key)
+    }
+
+    /// Derive `k1(shared secret, salt, p)` into `slot`.
+    pub async fn k1_into(&self, salt: &[u8], p: &[u8], slot: u8) -> Result<(), DeviceError> {
+        self.kdf(KDF_TARGET_SLOT, slot, salt, p, &mut []).await
+    }
+
+    pub async fn encrypt_block(&self, slot: u8, block: &mut Block) -> Result<(), DeviceError> {
+        let input = *block;
+        self.execute(
+            &Command {
+                opcode: OPCODE_AES,
+                param1: AES_MODE_ENCRYPT,
+                param2: slot as u16,
+                data: &input,
+            },
+            block,
+        )
+        .await
+    }
+
+    pub async fn aes_cmac(&self, slot: u8, input: &[u8]) -> Result<Block, DeviceError> {
+        modes::aes_cmac(&self.cipher(slot), input).await
+    }
+
+    pub async fn aes_ccm_encrypt(
+        &self,
+        slot: u8,
+        nonce: &[u8],
+        data: &mut [u8],
+        mic: &mut [u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<(), DeviceError> {
+        modes::aes_ccm_encrypt(&self.cipher(slot), nonce, data, mic, additional_data).await
+    }
+
+    pub async fn aes_ccm_decrypt(
+        &self,
+        slot: u8,
+        nonce: &[u8],
+        data: &mut [u8],
+        mic: &[u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<(), DeviceError> {
+        modes::aes_ccm_decrypt(&self.cipher(slot), nonce, data, mic, additional_data).await
+    }
+
+    fn cipher(&self, slot: u8) -> SlotCipher<'_, T> {
+        SlotCipher {
+            element: self,
+            slot,
+        }
+    }
+
+    async fn read_public_key(&self, mode: u8) -> Result<PublicKey, DeviceError> {
+        let mut xy = [0; 64];
+        self.execute(
+            &Command {
+                opcode: OPCODE_GEN_KEY,
+                param1: mode,
+                param2: PRIVATE_KEY_SLOT as u16,
+                data: &[],
+            },
+            &mut xy,
+        )
+        .await?;
+        let point = EncodedPoint::from_affine_coordinates(
+            GenericArray::from_slice(&xy[..32]),
+            GenericArray::from_slice(&xy[32..]),
+            false,
+        );
+        Option::from(PublicKey::from_encoded_point(&point)).ok_or(DeviceError::KeyInitialization)
+    }
+
+    async fn kdf(
+        &self,
+        target: u8,
+        target_slot: u8,
+        salt: &[u8],
+        p: &[u8],
+        output: &mut [u8],
+    ) -> Result<(), DeviceError> {
+        let mut data: heapless::Vec<u8, MAX_DATA> = heapless::Vec::new();
+        data.extend_from_slice(salt)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        data.extend_from_slice(p)
+            .map_err(|_| DeviceError::InsufficientBuffer)?;
+        self.execute(
+            &Command {
+                opcode: OPCODE_KDF,
+                param1: KDF_ALGORITHM_K1 | target | KDF_SOURCE_SLOT,
+                param2: u16::from_le_bytes([SHARED_SECRET_SLOT, target_slot]),
+                data: &data,
+            },
+            output,
+        )
+        .await
+    }
+
+    async fn execute(&self, command: &Command<'_>, output: &mut [u8]) -> Result<(), DeviceError> {
+        let mut packet = Packet::new();
+        command.emit(&mut packet)?;
+
+        // room for either the output or a status.
+        let mut response = [0; MAX_PACKET];
+        let response = &mut response[..output.len().max(1) + 3];
+        {
+            // the volatile state of the device spans the command and its response.
+            let mut transport = self.transport.lock().await;
+            transport.send(&packet).await?;
+            transport.receive(response).await?;
+        }
+
+        output.copy_from_slice(parse_response(response, output.len())?);
+        Ok(())
+    }
+}
+
+/// Encrypts blocks with the key in a slot of the secure element.
+struct SlotCipher<'e, T: Transport> {
+    element: &'e SecureElement<T>,
+    slot: u8,
+}
+
+impl<'e, T: Transport> BlockCipher for SlotCipher<'e, T> {
+    type EncryptFuture<'m> = impl Future<Output = Result<Block, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn encrypt<'m>(&'m self, mut block: Block) -> Self::EncryptFuture<'m> {
+        async move {
+            self.element.encrypt_block(self.slot, &mut block).await?;
+            Ok(block)
+        }
+    }
+}
+
+impl<S: Storage, T: Transport> VaultFactory<S> for SecureElement<T> {
+    type Vault<'c> = SecureElementVault<'c, S, T>
+    where
+        Self: 'c,
+        S: 'c;
+
+    fn vault<'c>(&'c self, configuration_manager: &'c ConfigurationManager<S>) -> Self::Vault<'c> {
+        SecureElementVault {
+            inner: StorageVault::new(configuration_manager),
+            element: self,
+        }
+    }
+
+    fn stores_key_pair(&self) -> bool {
+        // the key pair is kept by the secure element.
+        false
+    }
+}
+
+/// Vault of a node keeping its device key in a [`SecureElement`].
+pub struct SecureElementVault<'c, S: Storage, T: Transport> {
+    inner: StorageVault<'c, S>,
+    element: &'c SecureElement<T>,
+}
+
+impl<'c, S: Storage + 'c, T: Transport + 'c> Vault for SecureElementVault<'c, S, T> {
+    fn uuid(&self) -> Uuid {
+        self.inner.uuid()
+    }
+
+    type SetPeerPublicKeyFuture<'m> = impl Future<Output = Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn set_peer_public_key<'m>(&'m mut self, pk: PublicKey) -> Self::SetPeerPublicKeyFuture<'m> {
+        async move { self.element.ecdh(&pk).await }
+    }
+
+    type PublicKeyFuture<'m> = impl Future<Output = Result<PublicKey, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn public_key<'m>(&'m self) -> Self::PublicKeyFuture<'m> {
+        async move { self.element.public_key().await }
+    }
+
+    fn oob_public_key(&self) -> bool {
+        self.element.oob_public_key
+    }
+
+    type KeyFuture<'m> = impl Future<Output = Result<Output<Cmac<Aes128>>, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn n_k1<'m>(&'m self, salt: &'m [u8], p: &'m [u8]) -> Self::KeyFuture<'m> {
+        async move {
+            if p == b"prdk" {
+                // only ever derived into its slot.
+                return Err(DeviceError::CryptoError("n_k1"));
+            }
+            let key = self.element.k1(salt, p).await?;
+            Ok(Output::new(GenericArray::clone_from_slice(&key)))
+        }
+    }
+
+    type SetProvisioningDataFuture<'m> = impl Future<Output = Result<(), DeviceError>>
+    where
+        Self: 'm;
+
+    fn set_provisioning_data<'m>(
+        &'m mut self,
+        provisioning_salt: &'m [u8],
+        data: &'m ProvisioningData,
+    ) -> Self::SetProvisioningDataFuture<'m> {
+        async move {
+            self.element
+                .k1_into(provisioning_salt, b"prdk", DEVICE_KEY_SLOT)
+                .await?;
+            self.inner.store_provisioning_data(data, None).await
+        }
+    }
+
+    fn iv_index(&self) -> Option<u32> {
+        self.inner.iv_index()
+    }
+
+    type DecryptDeviceKeyFuture<'m> = impl Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn decrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m [u8],
+    ) -> Self::DecryptDeviceKeyFuture<'m> {
+        async move {
+            self.element
+                .aes_ccm_decrypt(DEVICE_KEY_SLOT, &*nonce, bytes, mic, None)
+                .await
+                .map_err(|_| DeviceError::CryptoError("decrypt device key"))
+        }
+    }
+
+    type EncryptDeviceKeyFuture<'m> = impl Future<Output = Result<(), DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn encrypt_device_key<'m>(
+        &'m self,
+        nonce: DeviceNonce,
+        bytes: &'m mut [u8],
+        mic: &'m mut [u8],
+    ) -> Self::EncryptDeviceKeyFuture<'m> {
+        async move {
+            self.element
+                .aes_ccm_encrypt(DEVICE_KEY_SLOT, &*nonce, bytes, mic, None)
+                .await
+                .map_err(|_| DeviceError::CryptoError("encrypt device key"))
+        }
+    }
+
+    fn encrypt_application_key(
+        &self,
+        net_key_index: &NetKeyIndex,
+        app_key_index: &AppKeyIndex,
+        nonce: ApplicationNonce,
+        bytes: &mut [u8],
+        mic: &mut [u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<ApplicationKeyIdentifier, DeviceError> {
+        self.inner.encrypt_application_key(
+            net_key_index,
+            app_key_index,
+            nonce,
+            bytes,
+            mic,
+            additional_data,
+        )
+    }
+
+    fn decrypt_application_key(
+        &self,
+        net_key_index: &NetKeyIndex,
+        aid: &ApplicationKeyIdentifier,
+        nonce: ApplicationNonce,
+        bytes: &mut [u8],
+        mic: &[u8],
+        additional_data: Option<&[u8]>,
+    ) -> Result<AppKeyIndex, DeviceError> {
+        self.inner
+            .decrypt_application_key(net_key_index, aid, nonce, bytes, mic, additional_data)
+    }
+
+    fn primary_unicast_address(&self) -> Option<UnicastAddress> {
+        self.inner.primary_unicast_address()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::emulator::Emulator;
+    use super::*;
+    use crate::drivers::ble::mesh::crypto;
+    use futures::executor::block_on;
+    use p256::elliptic_curve::ecdh::diffie_hellman;
+    use p256::SecretKey;
+    use rand_core::{CryptoRng, Error, RngCore};
+
+    struct TestRng(u64);
+
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for b in dest.iter_mut() {
+                *b = self.next_u64() as u8;
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for TestRng {}
+
+    const SALT: [u8; 16] = [
+        0xa2, 0x1c, 0x7d, 0x45, 0xf2, 0x01, 0xcf, 0x95, 0x89, 0xbe, 0x32, 0x82, 0x1f, 0x67, 0x28,
+        0x5b,
+    ];
+
+    /// A secure element sharing a secret with a peer, along with the secret.
+    fn agreed() -> (SecureElement<Emulator<TestRng>>, [u8; 32]) {
+        let mut rng = TestRng(0x2545F4914F6CDD1D);
+        let peer = SecretKey::random(&mut rng);
+        let element = SecureElement::new(Emulator::new(rng));
+        let public_key = block_on(element.generate_key_pair()).unwrap();
+        assert_eq!(public_key, block_on(element.public_key()).unwrap());
+
+        block_on(element.ecdh(&peer.public_key())).unwrap();
+        let shared_secret = diffie_hellman(peer.to_nonzero_scalar(), public_key.as_affine());
+        let mut secret = [0; 32];
+        secret.copy_from_slice(shared_secret.as_bytes());
+        (element, secret)
+    }
+
+    #[test]
+    fn test_k1_over_shared_secret() {
+        let (element, secret) = agreed();
+        let expected = crypto::k1(&secret, &SALT, b"prsk").unwrap().into_bytes();
+        assert_eq!(
+            &expected[..],
+            &block_on(element.k1(&SALT, b"prsk")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_device_key_in_slot() {
+        let (element, secret) = agreed();
+        block_on(element.k1_into(&SALT, b"prdk", DEVICE_KEY_SLOT)).unwrap();
+        let device_key = crypto::k1(&secret, &SALT, b"prdk").unwrap().into_bytes();
+
+        let nonce = [0x02; 13];
+        let mut expected = *b"configuration message";
+        let mut expected_mic = [0; 4];
+        crypto::aes_ccm_encrypt_detached(
+            &device_key,
+            &nonce,
+            &mut expected,
+            &mut expected_mic,
+            None,
+        )
+        .unwrap();
+
+        let mut bytes = *b"configuration message";
+        let mut mic = [0; 4];
+        block_on(element.aes_ccm_encrypt(DEVICE_KEY_SLOT, &nonce, &mut bytes, &mut mic, None))
+            .unwrap();
+        assert_eq!(expected, bytes);
+        assert_eq!(expected_mic, mic);
+
+        mic[0] ^= 0x01;
+        assert!(
+            block_on(element.aes_ccm_decrypt(DEVICE_KEY_SLOT, &nonce, &mut bytes, &mic, None))
+                .is_err()
+        );
+        assert_eq!(expected, bytes);
+        mic[0] ^= 0x01;
+        block_on(element.aes_ccm_decrypt(DEVICE_KEY_SLOT, &nonce, &mut bytes, &mic, None)).unwrap();
+        assert_eq!(b"configuration message", &bytes);
+    }
+
+    #[test]
+    fn test_aes_cmac() {
+        let key = [0x2b; 16];
+        let element = SecureElement::new(
+            Emulator::new(TestRng(1))
+                .with_key(DEVICE_KEY_SLOT, &key)
+                .unwrap(),
+        );
+        for input in [
+            &b""[..],
+            &b"smk2"[..],
+            &b"a message spanning several blocks"[..],
+        ] {
+            let expected = crypto::aes_cmac(&key, input).unwrap().into_bytes();
+            assert_eq!(
+                &expected[..],
+                &block_on(element.aes_cmac(DEVICE_KEY_SLOT, input)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_keys_are_not_used_across_kinds() {
+        let (element, _) = agreed();
+        // neither the private key nor the shared secret encrypt.
+        assert!(block_on(element.encrypt_block(PRIVATE_KEY_SLOT, &mut [0; 16])).is_err());
+        assert!(block_on(element.encrypt_block(SHARED_SECRET_SLOT, &mut [0; 16])).is_err());
+        assert!(block_on(element.encrypt_block(DEVICE_KEY_SLOT, &mut [0; 16])).is_err());
+    }
+
+    #[test]
+    fn test_corrupted_packet() {
+        let mut emulator = Emulator::new(TestRng(1))
+            .with_key(DEVICE_KEY_SLOT, &[0; 16])
+            .unwrap();
+        let mut packet = Packet::new();
+        Command {
+            opcode: OPCODE_AES,
+            param1: AES_MODE_ENCRYPT,
+            param2: DEVICE_KEY_SLOT as u16,
+            data: &[0; 16],
+        }
+        .emit(&mut packet)
+        .unwrap();
+        packet[7] ^= 0x01;
+
+        block_on(emulator.send(&packet)).unwrap();
+        let mut response = [0; 19];
+        block_on(emulator.receive(&mut response)).unwrap();
+        assert_eq!(&[0x04, STATUS_CRC_ERROR], &response[..2]);
+        assert!(parse_response(&response, 16).is_err());
+    }
+}
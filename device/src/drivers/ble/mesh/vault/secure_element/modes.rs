@@ -0,0 +1,194 @@
+//! AES-CMAC and AES-CCM built upon encrypting single blocks, so that the key
+//! is only ever used within the secure element encrypting them.
+
+use crate::drivers::ble::mesh::driver::DeviceError;
+use core::future::Future;
+
+pub(crate) type Block = [u8; 16];
+
+/// Encrypts single blocks with a key it never reveals.
+pub(crate) trait BlockCipher {
+    type EncryptFuture<'m>: Future<Output = Result<Block, DeviceError>> + 'm
+    where
+        Self: 'm;
+
+    fn encrypt<'m>(&'m self, block: Block) -> Self::EncryptFuture<'m>;
+}
+
+/// Length of the nonces used by mesh, leaving 2 bytes for the counter.
+pub(crate) const NONCE_LEN: usize = 13;
+
+pub(crate) async fn aes_cmac<C: BlockCipher>(
+    cipher: &C,
+    input: &[u8],
+) -> Result<Block, DeviceError> {
+    let k1 = cipher.encrypt([0; 16]).await?;
+    let k1 = double(&k1);
+    let k2 = double(&k1);
+
+    let mut state = [0; 16];
+    let mut chunks = input.chunks(16).peekable();
+    if chunks.peek().is_none() {
+        // the empty message is a single, padded block.
+        state[0] ^= 0x80;
+        xor(&mut state, &k2);
+    }
+    while let Some(chunk) = chunks.next() {
+        xor(&mut state[..chunk.len()], chunk);
+        if chunks.peek().is_none() {
+            if chunk.len() == 16 {
+                xor(&mut state, &k1);
+            } else {
+                state[chunk.len()] ^= 0x80;
+                xor(&mut state, &k2);
+            }
+        }
+        state = cipher.encrypt(state).await?;
+    }
+    if input.is_empty() {
+        state = cipher.encrypt(state).await?;
+    }
+    Ok(state)
+}
+
+pub(crate) async fn aes_ccm_encrypt<C: BlockCipher>(
+    cipher: &C,
+    nonce: &[u8],
+    data: &mut [u8],
+    mic: &mut [u8],
+    additional_data: Option<&[u8]>,
+) -> Result<(), DeviceError> {
+    check(nonce, data, mic.len(), additional_data)?;
+    let tag = cbc_mac(cipher, nonce, data, mic.len(), additional_data).await?;
+    ctr(cipher, nonce, data, &tag, mic).await
+}
+
+pub(crate) async fn aes_ccm_decrypt<C: BlockCipher>(
+    cipher: &C,
+    nonce: &[u8],
+    data: &mut [u8],
+    mic: &[u8],
+    additional_data: Option<&[u8]>,
+) -> Result<(), DeviceError> {
+    check(nonce, data, mic.len(), additional_data)?;
+    let mut expected = [0; 8];
+    let expected = &mut expected[..mic.len()];
+    ctr(cipher, nonce, data, &[0; 16], expected).await?;
+    let tag = cbc_mac(cipher, nonce, data, mic.len(), additional_data).await?;
+    let mut difference = 0;
+    for ((e, t), m) in expected.iter().zip(tag.iter()).zip(mic.iter()) {
+        difference |= (e ^ t) ^ m;
+    }
+    if difference != 0 {
+        // leave the data as received.
+        ctr(cipher, nonce, data, &[0; 16], expected).await?;
+        return Err(DeviceError::CryptoError("aes_ccm_decrypt"));
+    }
+    Ok(())
+}
+
+/// Lengths supported by mesh, with 2 byte lengths of the data and the
+/// additional data.
+fn check(
+    nonce: &[u8],
+    data: &[u8],
+    mic_len: usize,
+    additional_data: Option<&[u8]>,
+) -> Result<(), DeviceError> {
+    if nonce.len() != NONCE_LEN
+        || !(mic_len == 4 || mic_len == 8)
+        || data.len() > 0xFFFF
+        || additional_data.unwrap_or(&[]).len() >= 0xFF00
+    {
+        Err(DeviceError::CryptoError("aes_ccm"))
+    } else {
+        Ok(())
+    }
+}
+
+/// The authentication tag of the plaintext `data`, before encryption.
+async fn cbc_mac<C: BlockCipher>(
+    cipher: &C,
+    nonce: &[u8],
+    data: &[u8],
+    mic_len: usize,
+    additional_data: Option<&[u8]>,
+) -> Result<Block, DeviceError> {
+    let additional_data = additional_data.unwrap_or(&[]);
+    let mut state = [0; 16];
+    state[0] = ((mic_len as u8 - 2) / 2) << 3 | 0x01;
+    if !additional_data.is_empty() {
+        state[0] |= 0x40;
+    }
+    state[1..14].copy_from_slice(nonce);
+    state[14..16].copy_from_slice(&(data.len() as u16).to_be_bytes());
+    state = cipher.encrypt(state).await?;
+
+    if !additional_data.is_empty() {
+        // the length prefix shifts the additional data across blocks.
+        let length = (additional_data.len() as u16).to_be_bytes();
+        let mut position = 2;
+        xor(&mut state[..2], &length);
+        for byte in additional_data {
+            if position == 16 {
+                state = cipher.encrypt(state).await?;
+                position = 0;
+            }
+            state[position] ^= byte;
+            position += 1;
+        }
+        state = cipher.encrypt(state).await?;
+    }
+
+    for chunk in data.chunks(16) {
+        xor(&mut state[..chunk.len()], chunk);
+        state = cipher.encrypt(state).await?;
+    }
+    Ok(state)
+}
+
+/// Apply the key stream to `data`, and encrypt `tag` into `mic`.
+async fn ctr<C: BlockCipher>(
+    cipher: &C,
+    nonce: &[u8],
+    data: &mut [u8],
+    tag: &Block,
+    mic: &mut [u8],
+) -> Result<(), DeviceError> {
+    let mut counter = [0; 16];
+    counter[0] = 0x01;
+    counter[1..14].copy_from_slice(nonce);
+
+    let stream = cipher.encrypt(counter).await?;
+    for (i, byte) in mic.iter_mut().enumerate() {
+        *byte = tag[i] ^ stream[i];
+    }
+
+    for (i, chunk) in data.chunks_mut(16).enumerate() {
+        counter[14..16].copy_from_slice(&(i as u16 + 1).to_be_bytes());
+        let stream = cipher.encrypt(counter).await?;
+        xor(chunk, &stream[..chunk.len()]);
+    }
+    Ok(())
+}
+
+/// Multiply by x in GF(2^128), as for deriving the CMAC subkeys.
+fn double(block: &Block) -> Block {
+    let mut doubled = [0; 16];
+    for i in 0..16 {
+        doubled[i] = block[i] << 1;
+        if i < 15 {
+            doubled[i] |= block[i + 1] >> 7;
+        }
+    }
+    if block[0] & 0x80 != 0 {
+        doubled[15] ^= 0x87;
+    }
+    doubled
+}
+
+fn xor(target: &mut [u8], other: &[u8]) {
+    for (t, o) in target.iter_mut().zip(other.iter()) {
+        *t ^= o;
+    }
+}
@@ -4,6 +4,9 @@ use embedded_storage_async::nor_flash::{AsyncNorFlash, AsyncReadNorFlash};
 
 use crate::shared::{Handle, Shared};
 
+#[cfg(any(test, feature = "std"))]
+pub mod ram;
+
 pub type FlashState<F> = Shared<F>;
 pub type SharedFlash<'a, F> = Handle<'a, F>;
 
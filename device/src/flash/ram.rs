@@ -0,0 +1,125 @@
+//! Flash held in RAM, for exercising flash based storage on the host.
+
+use core::future::Future;
+use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind};
+use embedded_storage_async::nor_flash::{AsyncNorFlash, AsyncReadNorFlash};
+
+/// NOR flash of `SIZE` bytes held in RAM, with pages of 4 KiB. As with
+/// real NOR flash, writing only clears bits and erasing sets them again.
+///
+/// A power loss can be simulated with [`RamFlash::interrupt_after`], which
+/// stops programming part way through a write or erase.
+pub struct RamFlash<const SIZE: usize> {
+    memory: [u8; SIZE],
+    remaining: Option<usize>,
+}
+
+impl<const SIZE: usize> RamFlash<SIZE> {
+    /// Erased flash.
+    pub fn new() -> Self {
+        Self {
+            memory: [0xFF; SIZE],
+            remaining: None,
+        }
+    }
+
+    /// Fail all writes and erases once another `bytes` have been programmed
+    /// or erased, leaving the memory partially programmed or erased.
+    pub fn interrupt_after(&mut self, bytes: usize) {
+        self.remaining.replace(bytes);
+    }
+
+    /// Restore normal operation after [`RamFlash::interrupt_after`].
+    pub fn restore(&mut self) {
+        self.remaining.take();
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    fn check(&self, offset: u32, len: usize, align: usize) -> Result<(), NorFlashErrorKind> {
+        if offset as usize + len > SIZE {
+            Err(NorFlashErrorKind::OutOfBounds)
+        } else if offset as usize % align != 0 || len % align != 0 {
+            Err(NorFlashErrorKind::NotAligned)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn program(
+        &mut self,
+        offset: u32,
+        data: impl Iterator<Item = u8>,
+        erase: bool,
+    ) -> Result<(), NorFlashErrorKind> {
+        for (index, byte) in data.enumerate() {
+            if let Some(remaining) = self.remaining.as_mut() {
+                if *remaining == 0 {
+                    return Err(NorFlashErrorKind::Other);
+                }
+                *remaining -= 1;
+            }
+            let cell = &mut self.memory[offset as usize + index];
+            if erase {
+                *cell = 0xFF;
+            } else {
+                *cell &= byte;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<const SIZE: usize> Default for RamFlash<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SIZE: usize> ErrorType for RamFlash<SIZE> {
+    type Error = NorFlashErrorKind;
+}
+
+impl<const SIZE: usize> AsyncReadNorFlash for RamFlash<SIZE> {
+    const READ_SIZE: usize = 1;
+
+    type ReadFuture<'m> = impl Future<Output = Result<(), Self::Error>> + 'm where Self: 'm;
+    fn read<'m>(&'m mut self, offset: u32, data: &'m mut [u8]) -> Self::ReadFuture<'m> {
+        async move {
+            self.check(offset, data.len(), Self::READ_SIZE)?;
+            data.copy_from_slice(&self.memory[offset as usize..offset as usize + data.len()]);
+            Ok(())
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        SIZE
+    }
+}
+
+impl<const SIZE: usize> AsyncNorFlash for RamFlash<SIZE> {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = 4096;
+
+    type WriteFuture<'m> = impl Future<Output = Result<(), Self::Error>> + 'm where Self: 'm;
+    fn write<'m>(&'m mut self, offset: u32, data: &'m [u8]) -> Self::WriteFuture<'m> {
+        async move {
+            self.check(offset, data.len(), Self::WRITE_SIZE)?;
+            self.program(offset, data.iter().copied(), false)
+        }
+    }
+
+    type EraseFuture<'m> = impl Future<Output = Result<(), Self::Error>> + 'm where Self: 'm;
+    fn erase<'m>(&'m mut self, from: u32, to: u32) -> Self::EraseFuture<'m> {
+        async move {
+            if to < from {
+                return Err(NorFlashErrorKind::OutOfBounds);
+            }
+            let len = (to - from) as usize;
+            self.check(from, len, Self::ERASE_SIZE)?;
+            self.program(from, core::iter::repeat(0xFF).take(len), true)
+        }
+    }
+}
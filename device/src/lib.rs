@@ -43,6 +43,10 @@
 //!     }
 //! }
 //! ~~~
+// Allows code generated by the macros to refer to this crate by name,
+// from within this crate as well.
+extern crate self as drogue_device;
+
 pub(crate) mod fmt;
 
 pub mod actors;
@@ -67,6 +71,11 @@ pub use bsp::Board;
 #[doc(hidden)]
 pub use drogue_device_macros::{self as drogue, config, test as drogue_test};
 
+// Allows code generated by the macros to refer to heapless, without the
+// applications depending on it themselves.
+#[doc(hidden)]
+pub use heapless;
+
 #[allow(unused_variables)]
 pub fn print_stack(file: &'static str, line: u32) {
     let _u: u32 = 1;
@@ -4,9 +4,11 @@ MEMORY
   MBR                               : ORIGIN = 0x00000000, LENGTH = 4K
   SOFTDEVICE                        : ORIGIN = 0x00001000, LENGTH = 155648
   FLASH                             : ORIGIN = 0x00027000, LENGTH = 256K
+  STORAGE                           : ORIGIN = 0x000f6000, LENGTH = 8K
   BOOTLOADER                        : ORIGIN = 0x000f8000, LENGTH = 24K
   BOOTLOADER_STATE                  : ORIGIN = 0x000fe000, LENGTH = 4K
-  STORAGE                           : ORIGIN = 0x000ff000, LENGTH = 4K
+  /* storage of earlier releases, read until the storage above is first written */
+  LEGACY_STORAGE                    : ORIGIN = 0x000ff000, LENGTH = 4K
   RAM                               : ORIGIN = 0x2000c698, LENGTH = 211304
 
   /* DFU is stored in external flash */
@@ -20,3 +22,5 @@ __bootloader_dfu_start = ORIGIN(DFU);
 __bootloader_dfu_end = ORIGIN(DFU) + LENGTH(DFU);
 
 __storage = ORIGIN(STORAGE);
+__storage_end = ORIGIN(STORAGE) + LENGTH(STORAGE);
+__legacy_storage = ORIGIN(LEGACY_STORAGE);
@@ -93,6 +93,8 @@ fn config() -> Config {
 
 extern "C" {
     static __storage: u8;
+    static __storage_end: u8;
+    static __legacy_storage: u8;
 }
 
 const COMPANY_IDENTIFIER: CompanyIdentifier = CompanyIdentifier(0x0003);
@@ -119,7 +121,12 @@ async fn main(spawner: Spawner, p: Peripherals) {
     let advertising_bearer = facilities.advertising_bearer();
     //let gatt_bearer = facilities.gatt_bearer();
     let rng = facilities.rng();
-    let storage = FlashStorage::new(unsafe { &__storage as *const u8 as usize }, flash.clone());
+    let storage = FlashStorage::new(
+        unsafe { &__storage as *const u8 as usize },
+        unsafe { &__storage_end as *const u8 as usize },
+        flash.clone(),
+    )
+    .with_legacy_address(unsafe { &__legacy_storage as *const u8 as usize });
 
     let capabilities = Capabilities {
         number_of_elements: 2,
@@ -145,10 +152,18 @@ async fn main(spawner: Spawner, p: Peripherals) {
         FEATURES,
     );
     composition
-        .add_element(ElementDescriptor::new(Location(0x0001)).add_model(SENSOR_SERVER))
+        .add_element(
+            ElementDescriptor::new(Location(0x0001))
+                .add_model(SENSOR_SERVER)
+                .unwrap(),
+        )
         .ok();
     composition
-        .add_element(ElementDescriptor::new(Location(0x0002)).add_model(FIRMWARE_UPDATE_SERVER))
+        .add_element(
+            ElementDescriptor::new(Location(0x0002))
+                .add_model(FIRMWARE_UPDATE_SERVER)
+                .unwrap(),
+        )
         .ok();
 
     let version = FIRMWARE_REVISION.unwrap_or(FIRMWARE_VERSION);
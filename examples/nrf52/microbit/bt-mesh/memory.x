@@ -7,7 +7,7 @@ MEMORY
   DFU                               : ORIGIN = 0x00049000, LENGTH = 143360
   BOOTLOADER                        : ORIGIN = 0x0006c000, LENGTH = 24K
   BOOTLOADER_STATE                  : ORIGIN = 0x00072000, LENGTH = 4K
-  STORAGE                           : ORIGIN = 0x00073000, LENGTH = 4K
+  STORAGE                           : ORIGIN = 0x00073000, LENGTH = 8K
   RAM                               : ORIGIN = 0x20002988, LENGTH = 120440
 }
 
@@ -20,3 +20,6 @@ __bootloader_dfu_end = ORIGIN(DFU) + LENGTH(DFU);
 */
 
 __storage = ORIGIN(STORAGE);
+__storage_end = ORIGIN(STORAGE) + LENGTH(STORAGE);
+/* storage of earlier releases, the first page of the storage above */
+__legacy_storage = ORIGIN(STORAGE);
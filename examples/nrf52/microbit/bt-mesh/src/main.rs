@@ -16,17 +16,19 @@ use drogue_device::drivers::ble::mesh::composition::{
 };
 use drogue_device::drivers::ble::mesh::config::ConfigurationModel;
 use drogue_device::drivers::ble::mesh::driver::elements::AppElementsContext;
+use drogue_device::drivers::ble::mesh::driver::node::oob::DisplayButtonOob;
 use drogue_device::drivers::ble::mesh::driver::DeviceError;
 use drogue_device::drivers::ble::mesh::interface::AdvertisingOnlyNetworkInterfaces;
 use drogue_device::drivers::ble::mesh::model::ModelIdentifier;
 use drogue_device::drivers::ble::mesh::pdu::access::AccessMessage;
 use drogue_device::drivers::ble::mesh::pdu::ParseError;
 use drogue_device::drivers::ble::mesh::provisioning::{
-    Algorithms, Capabilities, InputOOBActions, OOBSize, OutputOOBActions, PublicKeyType,
-    StaticOOBType,
+    Algorithms, Capabilities, InputOOBAction, InputOOBActions, OOBSize, OutputOOBAction,
+    OutputOOBActions, PublicKeyType, StaticOOBType,
 };
 use drogue_device::drivers::ble::mesh::storage::FlashStorage;
 use drogue_device::drivers::ble::mesh::InsufficientBuffer;
+use drogue_device::traits::led::TextDisplay;
 use drogue_device::{
     actors::ble::mesh::{MeshNode, MeshNodeMessage},
     drivers::ble::mesh::model::sensor::{
@@ -49,9 +51,11 @@ cfg_if::cfg_if! {
     }
 }
 
+use core::convert::Infallible;
 use embassy::channel::mpmc::{Channel, DynamicReceiver, DynamicSender};
+use embassy::mutex::Mutex;
 use embassy::time::Ticker;
-use embassy::time::{Duration, Timer};
+use embassy::time::{Duration, Instant, Timer};
 use embassy::util::Forever;
 use embassy::util::{select, Either};
 use embassy::{blocking_mutex::raw::NoopRawMutex, executor::Spawner};
@@ -79,6 +83,7 @@ type ConcreteMeshNode = MeshNode<
     AdvertisingOnlyNetworkInterfaces<SoftdeviceAdvertisingBearer>,
     FlashStorage<SharedFlash<'static, Flash>>,
     SoftdeviceRng,
+    DisplayButtonOob<SharedDisplay, ButtonB>,
 >;
 
 pub struct MyDevice {
@@ -89,6 +94,7 @@ pub struct MyDevice {
 
 static DEVICE: Forever<MyDevice> = Forever::new();
 static MESH: Forever<ConcreteMeshNode> = Forever::new();
+static DISPLAY: Forever<Mutex<NoopRawMutex, LedMatrix>> = Forever::new();
 
 // Application must run at a lower priority than softdevice
 fn config() -> Config {
@@ -100,6 +106,8 @@ fn config() -> Config {
 
 extern "C" {
     static __storage: u8;
+    static __storage_end: u8;
+    static __legacy_storage: u8;
 }
 
 const COMPANY_IDENTIFIER: CompanyIdentifier = CompanyIdentifier(0x0003);
@@ -126,7 +134,19 @@ async fn main(spawner: Spawner, p: Peripherals) {
     let advertising_bearer = facilities.advertising_bearer();
     //let gatt_bearer = facilities.gatt_bearer();
     let rng = facilities.rng();
-    let storage = FlashStorage::new(unsafe { &__storage as *const u8 as usize }, flash.clone());
+    let storage = FlashStorage::new(
+        unsafe { &__storage as *const u8 as usize },
+        unsafe { &__storage_end as *const u8 as usize },
+        flash.clone(),
+    )
+    .with_legacy_address(unsafe { &__legacy_storage as *const u8 as usize });
+
+    // numbers are scrolled across the display, and pushes of button B are
+    // counted for the provisioner to authenticate.
+    let mut output_oob_action = OutputOOBActions::new();
+    output_oob_action.push(OutputOOBAction::OutputNumeric).ok();
+    let mut input_oob_action = InputOOBActions::new();
+    input_oob_action.push(InputOOBAction::Push).ok();
 
     let capabilities = Capabilities {
         #[cfg(feature = "dfu")]
@@ -137,9 +157,9 @@ async fn main(spawner: Spawner, p: Peripherals) {
         public_key_type: PublicKeyType::default(),
         static_oob_type: StaticOOBType::default(),
         output_oob_size: OOBSize::MaximumSize(4),
-        output_oob_action: OutputOOBActions::default(),
-        input_oob_size: OOBSize::MaximumSize(4),
-        input_oob_action: InputOOBActions::default(),
+        output_oob_action,
+        input_oob_size: OOBSize::MaximumSize(1),
+        input_oob_action,
     };
 
     let device = DEVICE.put(MyDevice {
@@ -155,11 +175,19 @@ async fn main(spawner: Spawner, p: Peripherals) {
         FEATURES,
     );
     composition
-        .add_element(ElementDescriptor::new(Location(0x0001)).add_model(SENSOR_SERVER))
+        .add_element(
+            ElementDescriptor::new(Location(0x0001))
+                .add_model(SENSOR_SERVER)
+                .unwrap(),
+        )
         .ok();
     #[cfg(feature = "dfu")]
     composition
-        .add_element(ElementDescriptor::new(Location(0x0002)).add_model(FIRMWARE_UPDATE_SERVER))
+        .add_element(
+            ElementDescriptor::new(Location(0x0002))
+                .add_model(FIRMWARE_UPDATE_SERVER)
+                .unwrap(),
+        )
         .ok();
 
     let version = FIRMWARE_REVISION.unwrap_or(FIRMWARE_VERSION);
@@ -185,11 +213,20 @@ async fn main(spawner: Spawner, p: Peripherals) {
         "Mesh node size: {}",
         core::mem::size_of::<ConcreteMeshNode>()
     );
-    let mesh_node = MESH.put(MeshNode::new(elements, capabilities, network, storage, rng));
+    // the display is shared by the out-of-band authentication during
+    // provisioning, and the state and attention animations.
+    let display = SharedDisplay(DISPLAY.put(Mutex::new(board.display)));
+    let oob = DisplayButtonOob::new(display, ButtonB::new(board.btn_b));
+    let mesh_node = MESH
+        .put(MeshNode::new(elements, capabilities, network, storage, rng).with_oob_handler(oob));
     spawner
         .spawn(mesh_task(mesh_node, device.control.receiver().into()))
         .unwrap();
 
+    spawner
+        .spawn(state_task(display, device.state.receiver().into()))
+        .unwrap();
+
     spawner
         .spawn(reset_task(
             ButtonA::new(board.btn_a),
@@ -234,6 +271,7 @@ pub enum AppState {
     Unprovisioned,
     Provisioned,
     Connected,
+    Attention(u8),
 }
 
 impl Into<Frame<5, 5>> for AppState {
@@ -242,6 +280,7 @@ impl Into<Frame<5, 5>> for AppState {
             AppState::Unprovisioned => animation::UNPROVISIONED,
             AppState::Provisioned => animation::PROVISIONED,
             AppState::Connected => animation::CONNECTED,
+            AppState::Attention(_) => animation::ATTENTION,
         }
     }
 }
@@ -264,19 +303,69 @@ mod animation {
     pub const UNPROVISIONED: Frame<5, 5> = frame_5x5(CROSS_MARK);
     pub const PROVISIONED: Frame<5, 5> = frame_5x5(P);
     pub const CONNECTED: Frame<5, 5> = frame_5x5(CHECK_MARK);
+    pub const ATTENTION: Frame<5, 5> = frame_5x5(&[0b11111; 5]);
+}
+
+/// The LED matrix, shared between tasks.
+#[derive(Clone, Copy)]
+pub struct SharedDisplay(&'static Mutex<NoopRawMutex, LedMatrix>);
+
+impl TextDisplay for SharedDisplay {
+    type Error = Infallible;
+
+    type ScrollFuture<'m> = impl Future<Output = Result<(), Self::Error>> + 'm where Self: 'm;
+    fn scroll<'m>(&'m mut self, text: &'m str) -> Self::ScrollFuture<'m> {
+        async move {
+            self.0.lock().await.scroll(text).await;
+            Ok(())
+        }
+    }
+
+    type ScrollWithSpeedFuture<'m> = impl Future<Output = Result<(), Self::Error>> + 'm where Self: 'm;
+    fn scroll_with_speed<'m>(
+        &'m mut self,
+        text: &'m str,
+        speed: Duration,
+    ) -> Self::ScrollWithSpeedFuture<'m> {
+        async move {
+            self.0.lock().await.scroll_with_speed(text, speed).await;
+            Ok(())
+        }
+    }
+
+    type DisplayFuture<'m> = impl Future<Output = Result<(), Self::Error>> + 'm where Self: 'm;
+    fn display<'m>(&'m mut self, c: char, duration: Duration) -> Self::DisplayFuture<'m> {
+        async move { TextDisplay::display(&mut *self.0.lock().await, c, duration).await }
+    }
 }
 
 #[embassy::task]
-async fn state_task(mut display: LedMatrix, inbox: DynamicReceiver<'static, AppState>) {
+async fn state_task(display: SharedDisplay, inbox: DynamicReceiver<'static, AppState>) {
     let mut ticker = Ticker::every(Duration::from_secs(5));
     let mut current = AppState::Unprovisioned;
     loop {
         match select(ticker.next(), inbox.recv()).await {
             Either::First(_) => {
                 display
+                    .0
+                    .lock()
+                    .await
                     .display(current.into(), Duration::from_secs(1))
                     .await;
             }
+            Either::Second(AppState::Attention(seconds)) => {
+                // Blink until attention is over
+                let until = Instant::now() + Duration::from_secs(seconds as u64);
+                while Instant::now() < until {
+                    display
+                        .0
+                        .lock()
+                        .await
+                        .display(animation::ATTENTION, Duration::from_millis(500))
+                        .await;
+                    Timer::after(Duration::from_millis(500)).await;
+                }
+            }
             Either::Second(s) => {
                 current = s;
             }
@@ -381,6 +470,10 @@ impl ElementsHandler<'static> for CustomElementsHandler {
         }
     }
 
+    fn attention(&mut self, seconds: u8) {
+        let _ = self.display.try_send(AppState::Attention(seconds));
+    }
+
     type DispatchFuture<'m> = impl Future<Output = Result<(), DeviceError>> + 'm where Self: 'm;
     fn dispatch<'m>(
         &'m mut self,
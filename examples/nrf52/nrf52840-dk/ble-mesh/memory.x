@@ -3,8 +3,8 @@ MEMORY
   /* NOTE 1 K = 1 KiBi = 1024 bytes */
   /* These values correspond to the NRF52840 with Softdevices S140 7.0.1 */
 
-  FLASH : ORIGIN = 0x00027000, LENGTH = 864K
-  STORAGE : ORIGIN = 0x000FF000, LENGTH = 4K
+  FLASH : ORIGIN = 0x00027000, LENGTH = 860K
+  STORAGE : ORIGIN = 0x000FE000, LENGTH = 8K
   /*RAM : ORIGIN = 0x20003c28, LENGTH = 240K*/
   /*RAM : ORIGIN = 0x2000b528, LENGTH = 210K*/
   /*RAM : ORIGIN = 0x2000B928, LENGTH = 209K*/
@@ -14,4 +14,7 @@ MEMORY
 }
 
 __storage = ORIGIN(STORAGE);
+__storage_end = ORIGIN(STORAGE) + LENGTH(STORAGE);
+/* storage of earlier releases, the last page of the storage above */
+__legacy_storage = 0x000FF000;
 
@@ -99,6 +99,8 @@ fn config() -> Config {
 
 extern "C" {
     static __storage: u8;
+    static __storage_end: u8;
+    static __legacy_storage: u8;
 }
 
 const COMPANY_IDENTIFIER: CompanyIdentifier = CompanyIdentifier(0x0003);
@@ -119,8 +121,10 @@ async fn main(spawner: Spawner, p: Peripherals) {
     let rng = facilities.rng();
     let storage = FlashStorage::new(
         unsafe { &__storage as *const u8 as usize },
+        unsafe { &__storage_end as *const u8 as usize },
         facilities.flash(),
-    );
+    )
+    .with_legacy_address(unsafe { &__legacy_storage as *const u8 as usize });
 
     let capabilities = Capabilities {
         number_of_elements: 1,
@@ -170,7 +174,9 @@ async fn main(spawner: Spawner, p: Peripherals) {
         .add_element(
             ElementDescriptor::new(Location(0x0001))
                 .add_model(GENERIC_ONOFF_CLIENT) /* the button */
-                .add_model(GENERIC_ONOFF_SERVER), /* the LED */
+                .unwrap()
+                .add_model(GENERIC_ONOFF_SERVER) /* the LED */
+                .unwrap(),
         )
         .ok();
 
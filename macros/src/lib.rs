@@ -3,6 +3,7 @@
 
 extern crate proc_macro;
 mod configure;
+mod message;
 
 use configure::configure;
 use proc_macro::TokenStream;
@@ -123,3 +124,34 @@ pub fn config(input: TokenStream) -> TokenStream {
     let output = configure(&s);
     quote!(#output).into()
 }
+
+/// Derive the `Message` codec of a mesh model message enum.
+///
+/// Each variant is annotated with its opcode, from which a constant of the
+/// given name is generated:
+///
+/// ```ignore
+/// #[derive(Message)]
+/// pub enum VendorMessage<'m> {
+///     #[opcode(VENDOR_GET 0xC1, 0x34, 0x12)]
+///     Get,
+///     #[opcode(VENDOR_SET 0xC2, 0x34, 0x12)]
+///     Set { value: u16, delay: Option<u8> },
+///     #[opcode(VENDOR_WRITE 0xC3, 0x34, 0x12)]
+///     Write { offset: u32, payload: &'m [u8] },
+/// }
+/// ```
+///
+/// Fields are laid out in order as little-endian integers or bools. Trailing
+/// `Option` integers are present only if parameters remain, and a final
+/// `&[u8]` or `heapless::Vec<u8, N>` takes all remaining parameters.
+/// An inherent `parse` for all of the opcodes is generated as well, for
+/// `Model::parse` to call.
+#[proc_macro_derive(Message, attributes(opcode))]
+pub fn message(item: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(item as syn::DeriveInput);
+    match message::derive_message(input) {
+        Ok(result) => result.into(),
+        Err(_) => TokenStream::new(),
+    }
+}
@@ -0,0 +1,392 @@
+use proc_macro::{Diagnostic, Level};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields, GenericArgument, Ident, LitInt, PathArguments, Token, Type};
+
+const INTEGERS: &[&str] = &["u8", "i8", "u16", "i16", "u32", "i32", "u64", "i64"];
+
+/// Layout of a field within the parameters of a message.
+enum Layout {
+    /// Little-endian integer.
+    Integer(Ident),
+    /// Single octet, non-zero if true.
+    Bool,
+    /// Little-endian integer, present if parameters remain.
+    Optional(Ident),
+    /// Remaining parameters, borrowed.
+    Slice,
+    /// Remaining parameters, copied into a `heapless::Vec<u8, N>`.
+    Vec,
+}
+
+impl Layout {
+    fn of(ty: &Type) -> Result<Self, Diagnostic> {
+        let unsupported = || {
+            Diagnostic::spanned(
+                ty.span().unwrap(),
+                Level::Error,
+                "unsupported field type, expected an integer, bool, Option of an integer, &[u8] or heapless::Vec<u8, N>",
+            )
+        };
+        match ty {
+            Type::Path(tp) if tp.qself.is_none() => {
+                let segment = tp.path.segments.last().ok_or_else(unsupported)?;
+                let ident = &segment.ident;
+                match &segment.arguments {
+                    PathArguments::None if INTEGERS.iter().any(|i| ident == i) => {
+                        Ok(Self::Integer(ident.clone()))
+                    }
+                    PathArguments::None if ident == "bool" => Ok(Self::Bool),
+                    PathArguments::AngleBracketed(args) if ident == "Option" => {
+                        match args.args.first() {
+                            Some(GenericArgument::Type(Type::Path(inner))) => {
+                                match inner.path.get_ident() {
+                                    Some(inner) if INTEGERS.iter().any(|i| inner == i) => {
+                                        Ok(Self::Optional(inner.clone()))
+                                    }
+                                    _ => Err(unsupported()),
+                                }
+                            }
+                            _ => Err(unsupported()),
+                        }
+                    }
+                    PathArguments::AngleBracketed(args) if ident == "Vec" => {
+                        match args.args.first() {
+                            Some(GenericArgument::Type(Type::Path(element)))
+                                if element.path.is_ident("u8") =>
+                            {
+                                Ok(Self::Vec)
+                            }
+                            // the remaining parameters are copied octet by octet.
+                            Some(element) => Err(Diagnostic::spanned(
+                                element.span().unwrap(),
+                                Level::Error,
+                                "expected heapless::Vec<u8, N>, holding the remaining octets",
+                            )),
+                            None => Err(unsupported()),
+                        }
+                    }
+                    _ => Err(unsupported()),
+                }
+            }
+            Type::Reference(r) => match &*r.elem {
+                Type::Slice(s) => match &*s.elem {
+                    Type::Path(tp) if tp.path.is_ident("u8") => Ok(Self::Slice),
+                    _ => Err(unsupported()),
+                },
+                _ => Err(unsupported()),
+            },
+            _ => Err(unsupported()),
+        }
+    }
+
+    fn is_rest(&self) -> bool {
+        matches!(self, Self::Slice | Self::Vec)
+    }
+
+    fn emit(&self, field: &Ident) -> TokenStream {
+        let insufficient_buffer = quote!(::drogue_device::drivers::ble::mesh::InsufficientBuffer);
+        match self {
+            Self::Integer(_) => quote! {
+                xmit.extend_from_slice(&#field.to_le_bytes()).map_err(|_| #insufficient_buffer)?;
+            },
+            Self::Bool => quote! {
+                xmit.push(*#field as u8).map_err(|_| #insufficient_buffer)?;
+            },
+            Self::Optional(_) => quote! {
+                if let Some(#field) = #field {
+                    xmit.extend_from_slice(&#field.to_le_bytes()).map_err(|_| #insufficient_buffer)?;
+                }
+            },
+            Self::Slice | Self::Vec => quote! {
+                xmit.extend_from_slice(&#field[..]).map_err(|_| #insufficient_buffer)?;
+            },
+        }
+    }
+
+    fn parse(&self, field: &Ident) -> TokenStream {
+        let parse_error = quote!(::drogue_device::drivers::ble::mesh::pdu::ParseError);
+        let integer = |ty: &Ident| {
+            quote! {{
+                let size = ::core::mem::size_of::<#ty>();
+                if parameters.len() < offset + size {
+                    return Err(#parse_error::InvalidLength);
+                }
+                let val = #ty::from_le_bytes(
+                    ::core::convert::TryInto::try_into(&parameters[offset..offset + size])
+                        .map_err(|_| #parse_error::InvalidLength)?,
+                );
+                offset += size;
+                val
+            }}
+        };
+        match self {
+            Self::Integer(ty) => {
+                let integer = integer(ty);
+                quote!(let #field = #integer;)
+            }
+            Self::Bool => quote! {
+                let #field = {
+                    if parameters.len() < offset + 1 {
+                        return Err(#parse_error::InvalidLength);
+                    }
+                    offset += 1;
+                    parameters[offset - 1] != 0
+                };
+            },
+            Self::Optional(ty) => {
+                let integer = integer(ty);
+                quote! {
+                    let #field = if parameters.len() > offset {
+                        Some(#integer)
+                    } else {
+                        None
+                    };
+                }
+            }
+            Self::Slice => quote! {
+                let #field = &parameters[offset..];
+                offset = parameters.len();
+            },
+            Self::Vec => quote! {
+                let #field = ::drogue_device::heapless::Vec::from_slice(&parameters[offset..])
+                    .map_err(|_| #parse_error::InsufficientBuffer)?;
+                offset = parameters.len();
+            },
+        }
+    }
+}
+
+struct Variant {
+    ident: Ident,
+    opcode: Ident,
+    octets: Vec<LitInt>,
+    fields: Fields,
+    layouts: Vec<(Ident, Layout)>,
+}
+
+fn parse_opcode(input: ParseStream) -> syn::Result<(Ident, Vec<LitInt>)> {
+    let name: Ident = input.parse()?;
+    let octets = Punctuated::<LitInt, Token![,]>::parse_terminated(input)?;
+    Ok((name, octets.into_iter().collect()))
+}
+
+/// The octets of an opcode, unless any is not a valid octet.
+fn opcode_value(octets: &[LitInt]) -> Option<Vec<u8>> {
+    octets
+        .iter()
+        .map(|octet| octet.base10_parse::<u8>().ok())
+        .collect()
+}
+
+pub fn derive_message(input: DeriveInput) -> Result<TokenStream, ()> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            input.span().unwrap().error("messages must be enums").emit();
+            return Err(());
+        }
+    };
+
+    let mut fail = false;
+    let mut variants = Vec::new();
+    for variant in data.variants.iter() {
+        let attr = variant
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("opcode"));
+        let (opcode, octets) = match attr {
+            Some(attr) => match attr.parse_args_with(parse_opcode) {
+                Ok((opcode, octets)) if (1..=3).contains(&octets.len()) => (opcode, octets),
+                _ => {
+                    attr.span()
+                        .unwrap()
+                        .error("expected #[opcode(NAME octet, ...)] of one to three octets")
+                        .emit();
+                    fail = true;
+                    continue;
+                }
+            },
+            None => {
+                variant
+                    .span()
+                    .unwrap()
+                    .error("message variant must have an #[opcode(...)] attribute")
+                    .emit();
+                fail = true;
+                continue;
+            }
+        };
+
+        let mut layouts = Vec::new();
+        for (index, field) in variant.fields.iter().enumerate() {
+            let ident = field
+                .ident
+                .clone()
+                .unwrap_or_else(|| format_ident!("field{}", index));
+            match Layout::of(&field.ty) {
+                Ok(layout) => layouts.push((ident, layout)),
+                Err(diagnostic) => {
+                    diagnostic.emit();
+                    fail = true;
+                }
+            }
+        }
+
+        // optional fields may only be followed by optional fields, and the
+        // remaining parameters by nothing at all.
+        for pair in layouts.windows(2) {
+            let (_, previous) = &pair[0];
+            let (field, next) = &pair[1];
+            if previous.is_rest()
+                || matches!(previous, Layout::Optional(_)) && !matches!(next, Layout::Optional(_))
+            {
+                field
+                    .span()
+                    .unwrap()
+                    .error("field cannot follow an optional field or the remaining parameters")
+                    .emit();
+                fail = true;
+            }
+        }
+
+        variants.push(Variant {
+            ident: variant.ident.clone(),
+            opcode,
+            octets,
+            fields: variant.fields.clone(),
+            layouts,
+        });
+    }
+
+    // parsing dispatches on the opcode alone, which must tell the variants apart.
+    for (index, variant) in variants.iter().enumerate() {
+        let value = opcode_value(&variant.octets);
+        if let Some(other) = variants[..index]
+            .iter()
+            .find(|other| value.is_some() && opcode_value(&other.octets) == value)
+        {
+            Diagnostic::spanned(
+                variant.octets[0].span().unwrap(),
+                Level::Error,
+                format!("duplicate opcode, already used by `{}`", other.ident),
+            )
+            .emit();
+            fail = true;
+        }
+    }
+
+    if fail {
+        return Err(());
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let lifetime = input
+        .generics
+        .lifetimes()
+        .next()
+        .map(|l| l.lifetime.clone());
+    let parameters = match &lifetime {
+        Some(lifetime) => quote!(&#lifetime [u8]),
+        None => quote!(&[u8]),
+    };
+
+    let opcode = quote!(::drogue_device::drivers::ble::mesh::pdu::access::Opcode);
+    let parse_error = quote!(::drogue_device::drivers::ble::mesh::pdu::ParseError);
+
+    let mut constants = Vec::new();
+    let mut opcodes = Vec::new();
+    let mut emitters = Vec::new();
+    let mut parsers = Vec::new();
+    for variant in variants.iter() {
+        let ident = &variant.ident;
+        let constant = &variant.opcode;
+        let octets = &variant.octets;
+        let kind = match octets.len() {
+            1 => quote!(OneOctet),
+            2 => quote!(TwoOctet),
+            _ => quote!(ThreeOctet),
+        };
+        constants.push(quote! {
+            pub const #constant: #opcode = #opcode::#kind(#(#octets),*);
+        });
+
+        let fields: Vec<&Ident> = variant.layouts.iter().map(|(field, _)| field).collect();
+        let (pattern, wildcard) = match &variant.fields {
+            Fields::Named(_) => (
+                quote!(Self::#ident { #(#fields),* }),
+                quote!(Self::#ident { .. }),
+            ),
+            Fields::Unnamed(_) => (
+                quote!(Self::#ident( #(#fields),* )),
+                quote!(Self::#ident(..)),
+            ),
+            Fields::Unit => (quote!(Self::#ident), quote!(Self::#ident)),
+        };
+
+        opcodes.push(quote!(#wildcard => #constant,));
+
+        let emit = variant
+            .layouts
+            .iter()
+            .map(|(field, layout)| layout.emit(field));
+        emitters.push(quote! {
+            #pattern => {
+                #(#emit)*
+                Ok(())
+            }
+        });
+
+        let parse = variant
+            .layouts
+            .iter()
+            .map(|(field, layout)| layout.parse(field));
+        parsers.push(quote! {
+            #constant => {
+                let mut offset = 0;
+                #(#parse)*
+                if offset != parameters.len() {
+                    return Err(#parse_error::InvalidLength);
+                }
+                Ok(Some(#pattern))
+            }
+        });
+    }
+
+    Ok(quote! {
+        #(#constants)*
+
+        impl #impl_generics ::drogue_device::drivers::ble::mesh::model::Message for #name #ty_generics #where_clause {
+            fn opcode(&self) -> #opcode {
+                match self {
+                    #(#opcodes)*
+                }
+            }
+
+            fn emit_parameters<const N: usize>(
+                &self,
+                xmit: &mut ::drogue_device::heapless::Vec<u8, N>,
+            ) -> Result<(), ::drogue_device::drivers::ble::mesh::InsufficientBuffer> {
+                match self {
+                    #(#emitters)*
+                }
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Parse the parameters of a message of any of the opcodes,
+            /// to be called from `Model::parse` for the opcodes of the role.
+            #[allow(unused_mut, unused_assignments)]
+            pub fn parse(opcode: #opcode, parameters: #parameters) -> Result<Option<Self>, #parse_error> {
+                match opcode {
+                    #(#parsers)*
+                    _ => Ok(None),
+                }
+            }
+        }
+    })
+}